@@ -0,0 +1,306 @@
+//! MAC allowlist and the violations found by checking collected MAC tables against it.
+//!
+//! An entry approves a MAC prefix (an OUI, an MA-M/MA-S block or a full MAC) for the
+//! devices in a location and below, for a VLAN, or both. A sighting is only checked where
+//! at least one entry applies, so enforcement can be rolled out one site or VLAN at a time,
+//! and only on access ports and wireless APs; ports with more MACs than the caller's limit
+//! are uplinks. Each violation is recorded once per MAC, device and VLAN.
+
+use crate::locations::{list_locations, unknown_location};
+use crate::nodes::access_ports;
+use crate::{AllowedMac, DbError, Location, MacAddress, MacViolation, Node, PgPool};
+use std::collections::HashMap;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+const MIN_PREFIX_LEN: usize = 6; // An OUI
+const MAX_PREFIX_LEN: usize = 12; // A full MAC
+
+/// Which violations to list, most recently seen first.
+#[derive(Debug, Clone, Default)]
+pub struct MacViolationFilter {
+    pub device_id: Option<Uuid>,
+    pub mac_address: Option<MacAddress>,
+    pub since: Option<OffsetDateTime>, // Seen at or after
+}
+
+impl MacViolationFilter {
+    pub(crate) fn matches(&self, violation: &MacViolation) -> bool {
+        self.device_id.is_none_or(|id| violation.device_id == id)
+            && self.mac_address.is_none_or(|mac| violation.mac_address == mac)
+            && self.since.is_none_or(|since| violation.last_seen >= since)
+    }
+}
+
+/// Lowercase hex digits of an OUI, MA-M/MA-S block or MAC in any usual notation,
+/// e.g. `00:1A:2B`, `001a.2b3c` or `00-1a-2b-3c-4d-5e`.
+pub(crate) fn normalize_prefix(text: &str) -> Result<String, DbError> {
+    let hex: String = text.trim().chars().filter(|c| !matches!(c, ':' | '-' | '.')).collect::<String>().to_lowercase();
+    if !(MIN_PREFIX_LEN..=MAX_PREFIX_LEN).contains(&hex.len()) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(DbError::InvalidQuery(format!(
+            "invalid MAC prefix '{}', expected 6 to 12 hex digits",
+            text
+        )));
+    }
+    Ok(hex)
+}
+
+/// Normalises the prefix and checks the VLAN of a new entry.
+pub(crate) fn check_entry(entry: &AllowedMac) -> Result<AllowedMac, DbError> {
+    if entry.vlan.is_some_and(|vlan| !(1..=4094).contains(&vlan)) {
+        return Err(DbError::InvalidQuery("vlan must be between 1 and 4094".to_string()));
+    }
+    Ok(AllowedMac { mac_prefix: normalize_prefix(&entry.mac_prefix)?, ..entry.clone() })
+}
+
+impl AllowedMac {
+    /// Whether the entry applies to a device in one of `scope` (its location and those
+    /// above it) on `vlan`, whatever the MAC.
+    pub fn covers(&self, scope: &[Uuid], vlan: i32) -> bool {
+        self.location_id.is_none_or(|id| scope.contains(&id)) && self.vlan.is_none_or(|v| v == vlan)
+    }
+
+    pub fn approves(&self, mac: MacAddress) -> bool {
+        mac.hex().starts_with(&self.mac_prefix)
+    }
+}
+
+/// `location_id` and every location above it.
+pub(crate) fn location_scope(locations: &[Location], location_id: Option<Uuid>) -> Vec<Uuid> {
+    let mut scope = Vec::new();
+    let mut next = location_id;
+    while let Some(id) = next {
+        if scope.contains(&id) {
+            break;
+        }
+        scope.push(id);
+        next = locations.iter().find(|l| l.id == id).and_then(|l| l.parent_id);
+    }
+    scope
+}
+
+/// The sightings an applying entry exists for but none approves. Wired sightings are only
+/// checked on access ports; `device_locations` maps each switch or AP to its location.
+pub(crate) fn unapproved<'a>(
+    entries: &[AllowedMac],
+    locations: &[Location],
+    device_locations: &HashMap<Uuid, Option<Uuid>>,
+    nodes: &'a [Node],
+    access_port_max_macs: u32,
+) -> Vec<&'a Node> {
+    let access_ports = access_ports(nodes, access_port_max_macs);
+    nodes
+        .iter()
+        .filter(|node| match node.interface_id {
+            Some(interface_id) => access_ports.contains(&interface_id),
+            None => node.wireless.is_some(),
+        })
+        .filter(|node| {
+            let location_id = device_locations.get(&node.device_id).copied().flatten();
+            let scope = location_scope(locations, location_id);
+            let applying: Vec<&AllowedMac> = entries.iter().filter(|e| e.covers(&scope, node.vlan)).collect();
+            !applying.is_empty() && !applying.iter().any(|e| e.approves(node.mac_address))
+        })
+        .collect()
+}
+
+/// Adds an allowlist entry; its prefix is stored as lowercase hex.
+pub async fn create_allowed_mac(pool: &PgPool, entry: &AllowedMac) -> Result<AllowedMac, DbError> {
+    let entry = check_entry(entry)?;
+    if let Some(location_id) = entry.location_id {
+        crate::get_location(pool, location_id).await.map_err(unknown_location)?;
+    }
+    let row = sqlx::query!(
+        r#"
+        INSERT INTO mac_allowlist (mac_prefix, location_id, vlan, description)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id, created_at
+        "#,
+        entry.mac_prefix,
+        entry.location_id,
+        entry.vlan,
+        entry.description
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(AllowedMac { id: row.id, created_at: row.created_at, ..entry })
+}
+
+pub async fn list_allowed_macs(pool: &PgPool) -> Result<Vec<AllowedMac>, DbError> {
+    let entries = sqlx::query_as!(
+        AllowedMac,
+        r#"SELECT id, mac_prefix, location_id, vlan, description, created_at
+           FROM mac_allowlist ORDER BY mac_prefix, created_at"#
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(entries)
+}
+
+pub async fn delete_allowed_mac(pool: &PgPool, id: Uuid) -> Result<(), DbError> {
+    let result = sqlx::query!("DELETE FROM mac_allowlist WHERE id = $1", id).execute(pool).await?;
+    if result.rows_affected() == 0 {
+        return Err(DbError::NotFound);
+    }
+    Ok(())
+}
+
+/// Checks collected sightings against the allowlist and records the unapproved ones.
+/// Returns their violations, new and already known; a violation whose port changed
+/// forgets an earlier port shutdown.
+pub async fn check_mac_allowlist(
+    pool: &PgPool,
+    nodes: &[Node],
+    access_port_max_macs: u32,
+) -> Result<Vec<MacViolation>, DbError> {
+    let entries = list_allowed_macs(pool).await?;
+    if entries.is_empty() || nodes.is_empty() {
+        return Ok(Vec::new());
+    }
+    let locations = list_locations(pool).await?;
+    let device_ids: Vec<Uuid> = nodes.iter().map(|n| n.device_id).collect();
+    let device_locations: HashMap<Uuid, Option<Uuid>> =
+        sqlx::query!("SELECT id, location_id FROM devices WHERE id = ANY($1)", &device_ids)
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .map(|row| (row.id, row.location_id))
+            .collect();
+
+    let mut violations = Vec::new();
+    for node in unapproved(&entries, &locations, &device_locations, nodes, access_port_max_macs) {
+        let violation = sqlx::query_as!(
+            MacViolation,
+            r#"
+            INSERT INTO mac_violations (mac_address, device_id, interface_id, vlan, first_seen, last_seen)
+            VALUES ($1, $2, $3, $4, $5, $5)
+            ON CONFLICT (mac_address, device_id, vlan) DO UPDATE SET
+                interface_id = EXCLUDED.interface_id,
+                last_seen = EXCLUDED.last_seen,
+                port_shutdown_at = CASE WHEN mac_violations.interface_id IS NOT DISTINCT FROM EXCLUDED.interface_id
+                                        THEN mac_violations.port_shutdown_at END
+            RETURNING id, mac_address as "mac_address: MacAddress", device_id, interface_id, vlan,
+                      first_seen, last_seen, port_shutdown_at
+            "#,
+            node.mac_address as MacAddress,
+            node.device_id,
+            node.interface_id,
+            node.vlan,
+            node.last_seen
+        )
+        .fetch_one(pool)
+        .await?;
+        violations.push(violation);
+    }
+    Ok(violations)
+}
+
+/// Lists recorded violations, most recently seen first.
+pub async fn list_mac_violations(pool: &PgPool, filter: &MacViolationFilter) -> Result<Vec<MacViolation>, DbError> {
+    let violations = sqlx::query_as!(
+        MacViolation,
+        r#"
+        SELECT id, mac_address as "mac_address: MacAddress", device_id, interface_id, vlan,
+               first_seen, last_seen, port_shutdown_at
+        FROM mac_violations
+        WHERE ($1::uuid IS NULL OR device_id = $1)
+          AND ($2::macaddr IS NULL OR mac_address = $2)
+          AND ($3::timestamptz IS NULL OR last_seen >= $3)
+        ORDER BY last_seen DESC, id
+        "#,
+        filter.device_id,
+        filter.mac_address as Option<MacAddress>,
+        filter.since
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(violations)
+}
+
+/// Records that the rogue policy shut the port of a violation.
+pub async fn set_violation_port_shutdown(pool: &PgPool, id: Uuid, at: OffsetDateTime) -> Result<(), DbError> {
+    let result = sqlx::query!("UPDATE mac_violations SET port_shutdown_at = $2 WHERE id = $1", id, at)
+        .execute(pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(DbError::NotFound);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LocationKind;
+
+    fn entry(mac_prefix: &str, location_id: Option<Uuid>, vlan: Option<i32>) -> AllowedMac {
+        AllowedMac {
+            id: Uuid::nil(),
+            mac_prefix: mac_prefix.to_string(),
+            location_id,
+            vlan,
+            description: None,
+            created_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    fn sighting(mac: &str, device_id: Uuid, interface_id: Uuid, vlan: i32) -> Node {
+        let now = OffsetDateTime::now_utc();
+        Node {
+            id: Uuid::new_v4(),
+            mac_address: mac.parse().unwrap(),
+            device_id,
+            interface_id: Some(interface_id),
+            vlan,
+            active: true,
+            first_seen: now,
+            last_seen: now,
+            wireless: None,
+        }
+    }
+
+    #[test]
+    fn normalises_prefixes() {
+        assert_eq!(normalize_prefix("00:1A:2B").unwrap(), "001a2b");
+        assert_eq!(normalize_prefix("001a.2b3c.4d5e").unwrap(), "001a2b3c4d5e");
+        assert!(normalize_prefix("00:1a").is_err());
+        assert!(normalize_prefix("00:1a:zz").is_err());
+    }
+
+    #[test]
+    fn flags_unknown_macs_only_where_an_entry_applies() {
+        let now = OffsetDateTime::now_utc();
+        let location = |kind, parent_id| Location {
+            id: Uuid::new_v4(),
+            parent_id,
+            kind,
+            name: String::new(),
+            description: None,
+            created_at: now,
+            updated_at: now,
+        };
+        let site = location(LocationKind::Site, None);
+        let building = location(LocationKind::Building, Some(site.id));
+        let (enforced, elsewhere) = (Uuid::new_v4(), Uuid::new_v4());
+        let device_locations = HashMap::from([(enforced, Some(building.id)), (elsewhere, None)]);
+        let entries = [entry("001a2b", Some(site.id), None), entry("02aabbccddee", None, Some(30))];
+        let (port_1, port_2, uplink) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let mut nodes = vec![
+            sighting("00:1a:2b:00:00:01", enforced, port_1, 10), // Approved OUI
+            sighting("00:99:99:00:00:01", enforced, port_2, 10), // Rogue
+            sighting("00:99:99:00:00:02", elsewhere, port_1, 10), // No entry applies
+            sighting("02:aa:bb:cc:dd:ee", elsewhere, port_2, 30), // Approved on VLAN 30
+            sighting("02:aa:bb:cc:dd:ef", elsewhere, port_2, 30), // Rogue on VLAN 30
+        ];
+        for i in 0..5 {
+            nodes.push(sighting(&format!("00:99:99:00:01:0{}", i), enforced, uplink, 10));
+        }
+
+        let flagged: Vec<String> = unapproved(&entries, &[site, building], &device_locations, &nodes, 4)
+            .iter()
+            .map(|n| n.mac_address.to_string())
+            .collect();
+
+        assert_eq!(flagged, ["00:99:99:00:00:01", "02:aa:bb:cc:dd:ef"]);
+    }
+}
@@ -0,0 +1,543 @@
+//! Anomalies in MAC and client address sightings.
+//!
+//! Three kinds are flagged: an address claimed by two MACs at the same time, a MAC moving
+//! between switch ports again and again, and a MAC active on more than one access port.
+//! Every backend loads the recent MAC table sightings, client addresses (ARP entries of the
+//! routers and wireless clients of the APs) and port moves and hands them to [`detect`]; [`reconcile`] then folds the findings into the stored anomalies, so one
+//! problem stays one event from its first to its last detection and is resolved once a run
+//! stops finding it.
+
+use crate::nodes::{access_ports, node_from_row, NODE_QUERY};
+use crate::{Anomaly, AnomalyKind, DbError, MacAddress, Node, PgPool, Repository};
+use ipnetwork::IpNetwork;
+use nd_core::AnomalySettings;
+use serde::Serialize;
+use sqlx::postgres::PgRow;
+use sqlx::Row;
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+pub const DEFAULT_ANOMALY_LIMIT: u32 = 100;
+pub const MAX_ANOMALY_LIMIT: u32 = 1000;
+
+/// Which stored anomalies to list, most recently detected first.
+#[derive(Debug, Clone, Default)]
+pub struct AnomalyFilter {
+    pub kind: Option<AnomalyKind>,
+    pub mac_address: Option<MacAddress>, // The flagged MAC or one of the MACs sharing an address
+    pub ip_address: Option<IpAddr>,
+    pub open_only: bool,
+    pub since: Option<OffsetDateTime>, // Detected at or after
+    pub limit: Option<u32>,
+}
+
+impl AnomalyFilter {
+    pub(crate) fn limit(&self) -> u32 {
+        self.limit.unwrap_or(DEFAULT_ANOMALY_LIMIT).clamp(1, MAX_ANOMALY_LIMIT)
+    }
+
+    pub(crate) fn matches(&self, anomaly: &Anomaly) -> bool {
+        self.kind.is_none_or(|kind| anomaly.kind == kind)
+            && self.mac_address.is_none_or(|mac| {
+                anomaly.mac_address == Some(mac) || anomaly.mac_addresses.contains(&mac)
+            })
+            && self.ip_address.is_none_or(|ip| anomaly.ip_address.map(|a| a.ip()) == Some(ip))
+            && (!self.open_only || anomaly.resolved_at.is_none())
+            && self.since.is_none_or(|since| anomaly.last_detected >= since)
+    }
+}
+
+/// What one detection run changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct AnomalyReport {
+    pub opened: u64,
+    pub ongoing: u64,
+    pub resolved: u64,
+}
+
+/// A client address as reported by a router's ARP table or an AP, held by the MAC since
+/// `since` (the ARP entry's `first_seen`, the wireless client's `ip_since`).
+#[derive(Debug, Clone)]
+pub(crate) struct IpClaim {
+    pub mac_address: MacAddress,
+    pub device_id: Uuid,
+    pub ip_address: IpNetwork,
+    pub since: OffsetDateTime,
+    pub last_seen: OffsetDateTime,
+}
+
+/// One move of a MAC sighting from one switch port to another.
+#[derive(Debug, Clone)]
+pub(crate) struct NodeMove {
+    pub mac_address: MacAddress,
+    pub device_id: Uuid,
+    pub from_interface_id: Option<Uuid>, // None once the port is gone
+    pub to_interface_id: Option<Uuid>,
+    pub moved_at: OffsetDateTime,
+}
+
+/// An anomaly found by one run, before it is matched against the stored ones.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Finding {
+    pub kind: AnomalyKind,
+    pub ip_address: Option<IpNetwork>,
+    pub mac_address: Option<MacAddress>,
+    pub mac_addresses: Vec<MacAddress>,
+    pub device_ids: Vec<Uuid>,
+    pub interface_ids: Vec<Uuid>,
+    pub moves: Option<i32>,
+}
+
+type AnomalyKey = (AnomalyKind, Option<IpAddr>, Option<MacAddress>);
+
+impl Finding {
+    fn key(&self) -> AnomalyKey {
+        (self.kind, self.ip_address.map(|ip| ip.ip()), self.mac_address)
+    }
+}
+
+fn anomaly_key(anomaly: &Anomaly) -> AnomalyKey {
+    (anomaly.kind, anomaly.ip_address.map(|ip| ip.ip()), anomaly.mac_address)
+}
+
+pub(crate) fn window_start(settings: &AnomalySettings, now: OffsetDateTime) -> OffsetDateTime {
+    now - time::Duration::minutes(settings.window_minutes.into())
+}
+
+/// Finds the anomalies in the sightings, claims and moves of the detection window.
+/// Older input is ignored, so callers may pass more than the window holds.
+pub(crate) fn detect(
+    settings: &AnomalySettings,
+    now: OffsetDateTime,
+    nodes: &[Node],
+    claims: &[IpClaim],
+    moves: &[NodeMove],
+) -> Vec<Finding> {
+    let start = window_start(settings, now);
+    let mut findings = Vec::new();
+
+    // Two MACs holding one address over overlapping spans, not one handing it to the next
+    let mut by_ip: BTreeMap<IpAddr, Vec<&IpClaim>> = BTreeMap::new();
+    for claim in claims.iter().filter(|c| c.last_seen >= start) {
+        by_ip.entry(claim.ip_address.ip()).or_default().push(claim);
+    }
+    for (ip, claims) in by_ip {
+        let mut macs = BTreeSet::new();
+        let mut devices = BTreeSet::new();
+        for (i, a) in claims.iter().enumerate() {
+            for b in &claims[i + 1..] {
+                if a.mac_address != b.mac_address && a.since <= b.last_seen && b.since <= a.last_seen {
+                    macs.extend([a.mac_address, b.mac_address]);
+                    devices.extend([a.device_id, b.device_id]);
+                }
+            }
+        }
+        if !macs.is_empty() {
+            findings.push(Finding {
+                kind: AnomalyKind::DuplicateIp,
+                ip_address: Some(IpNetwork::from(ip)),
+                mac_address: None,
+                mac_addresses: macs.into_iter().collect(),
+                device_ids: devices.into_iter().collect(),
+                interface_ids: Vec::new(),
+                moves: None,
+            });
+        }
+    }
+
+    let mut by_mac: BTreeMap<MacAddress, Vec<&NodeMove>> = BTreeMap::new();
+    for node_move in moves.iter().filter(|m| m.moved_at >= start) {
+        by_mac.entry(node_move.mac_address).or_default().push(node_move);
+    }
+    for (mac, moves) in by_mac {
+        if moves.len() < settings.flap_moves.max(1) as usize {
+            continue;
+        }
+        let ports: BTreeSet<Uuid> = moves.iter().flat_map(|m| [m.from_interface_id, m.to_interface_id]).flatten().collect();
+        let devices: BTreeSet<Uuid> = moves.iter().map(|m| m.device_id).collect();
+        findings.push(Finding {
+            kind: AnomalyKind::MacFlapping,
+            ip_address: None,
+            mac_address: Some(mac),
+            mac_addresses: Vec::new(),
+            device_ids: devices.into_iter().collect(),
+            interface_ids: ports.into_iter().collect(),
+            moves: Some(moves.len() as i32),
+        });
+    }
+
+    let current: Vec<&Node> = nodes.iter().filter(|n| n.active && n.last_seen >= start).collect();
+    let access_ports = access_ports(current.iter().copied(), settings.access_port_max_macs);
+    let mut mac_ports: BTreeMap<MacAddress, (BTreeSet<Uuid>, BTreeSet<Uuid>)> = BTreeMap::new();
+    for node in &current {
+        if let Some(interface_id) = node.interface_id
+            && access_ports.contains(&interface_id)
+        {
+            let (ports, devices) = mac_ports.entry(node.mac_address).or_default();
+            ports.insert(interface_id);
+            devices.insert(node.device_id);
+        }
+    }
+    for (mac, (ports, devices)) in mac_ports {
+        if ports.len() > 1 {
+            findings.push(Finding {
+                kind: AnomalyKind::MacMultiplePorts,
+                ip_address: None,
+                mac_address: Some(mac),
+                mac_addresses: Vec::new(),
+                device_ids: devices.into_iter().collect(),
+                interface_ids: ports.into_iter().collect(),
+                moves: None,
+            });
+        }
+    }
+    findings
+}
+
+/// The stored anomalies a detection run opens, refreshes and resolves.
+#[derive(Debug, Default)]
+pub(crate) struct Reconciled {
+    pub opened: Vec<Anomaly>,
+    pub ongoing: Vec<Anomaly>,
+    pub resolved: Vec<Anomaly>,
+}
+
+impl Reconciled {
+    pub(crate) fn report(&self) -> AnomalyReport {
+        AnomalyReport {
+            opened: self.opened.len() as u64,
+            ongoing: self.ongoing.len() as u64,
+            resolved: self.resolved.len() as u64,
+        }
+    }
+}
+
+/// Matches findings to the open anomalies by kind, address and MAC.
+pub(crate) fn reconcile(open: Vec<Anomaly>, findings: Vec<Finding>, now: OffsetDateTime) -> Reconciled {
+    let mut open: BTreeMap<AnomalyKey, Anomaly> = open.into_iter().map(|a| (anomaly_key(&a), a)).collect();
+    let mut reconciled = Reconciled::default();
+    for finding in findings {
+        let (bucket, mut anomaly) = match open.remove(&finding.key()) {
+            Some(existing) => (&mut reconciled.ongoing, existing),
+            None => (
+                &mut reconciled.opened,
+                Anomaly {
+                    id: Uuid::new_v4(),
+                    kind: finding.kind,
+                    ip_address: finding.ip_address,
+                    mac_address: finding.mac_address,
+                    mac_addresses: Vec::new(),
+                    device_ids: Vec::new(),
+                    interface_ids: Vec::new(),
+                    moves: None,
+                    first_detected: now,
+                    last_detected: now,
+                    resolved_at: None,
+                },
+            ),
+        };
+        anomaly.mac_addresses = finding.mac_addresses;
+        anomaly.device_ids = finding.device_ids;
+        anomaly.interface_ids = finding.interface_ids;
+        anomaly.moves = finding.moves;
+        anomaly.last_detected = now;
+        bucket.push(anomaly);
+    }
+    reconciled.resolved = open
+        .into_values()
+        .map(|anomaly| Anomaly { resolved_at: Some(now), ..anomaly })
+        .collect();
+    reconciled
+}
+
+const ANOMALY_COLUMNS: &str = "id, kind, ip_address, mac_address, mac_addresses, device_ids, interface_ids, moves, \
+     first_detected, last_detected, resolved_at";
+
+fn anomaly_from_row(row: &PgRow) -> Result<Anomaly, DbError> {
+    Ok(Anomaly {
+        id: row.try_get("id")?,
+        kind: row.try_get("kind")?,
+        ip_address: row.try_get("ip_address")?,
+        mac_address: row.try_get("mac_address")?,
+        mac_addresses: row.try_get("mac_addresses")?,
+        device_ids: row.try_get("device_ids")?,
+        interface_ids: row.try_get("interface_ids")?,
+        moves: row.try_get("moves")?,
+        first_detected: row.try_get("first_detected")?,
+        last_detected: row.try_get("last_detected")?,
+        resolved_at: row.try_get("resolved_at")?,
+    })
+}
+
+/// Runs one detection over the window in `settings` and stores the result.
+pub async fn detect_anomalies(pool: &PgPool, settings: &AnomalySettings) -> Result<AnomalyReport, DbError> {
+    let now = OffsetDateTime::now_utc();
+    let start = window_start(settings, now);
+
+    let nodes = sqlx::query(&format!("{} WHERE n.active AND n.interface_id IS NOT NULL AND n.last_seen >= $1", NODE_QUERY))
+        .bind(start)
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(node_from_row)
+        .collect::<Result<Vec<_>, _>>()?;
+    let claims = sqlx::query!(
+        r#"
+        SELECT n.mac_address as "mac_address!: MacAddress", n.device_id as "device_id!",
+               w.ip_address as "ip_address!", w.ip_since as "since!", w.last_seen as "last_seen!"
+        FROM node_wireless w JOIN nodes n ON n.id = w.node_id
+        WHERE w.ip_address IS NOT NULL AND w.ip_since IS NOT NULL AND w.last_seen >= $1
+        UNION ALL
+        SELECT mac_address, device_id, ip_address, first_seen, last_seen
+        FROM arp_entries WHERE last_seen >= $1
+        "#,
+        start
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| IpClaim {
+        mac_address: row.mac_address,
+        device_id: row.device_id,
+        ip_address: row.ip_address,
+        since: row.since,
+        last_seen: row.last_seen,
+    })
+    .collect::<Vec<_>>();
+    let moves = sqlx::query!(
+        r#"
+        SELECT n.mac_address as "mac_address: MacAddress", n.device_id,
+               m.from_interface_id, m.to_interface_id, m.moved_at
+        FROM node_moves m JOIN nodes n ON n.id = m.node_id
+        WHERE m.moved_at >= $1
+        "#,
+        start
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| NodeMove {
+        mac_address: row.mac_address,
+        device_id: row.device_id,
+        from_interface_id: row.from_interface_id,
+        to_interface_id: row.to_interface_id,
+        moved_at: row.moved_at,
+    })
+    .collect::<Vec<_>>();
+    let findings = detect(settings, now, &nodes, &claims, &moves);
+
+    let mut tx = pool.begin().await?;
+    let open = sqlx::query(&format!("SELECT {} FROM anomalies WHERE resolved_at IS NULL FOR UPDATE", ANOMALY_COLUMNS))
+        .fetch_all(&mut *tx)
+        .await?
+        .iter()
+        .map(anomaly_from_row)
+        .collect::<Result<Vec<_>, _>>()?;
+    let reconciled = reconcile(open, findings, now);
+    for anomaly in &reconciled.opened {
+        sqlx::query!(
+            r#"
+            INSERT INTO anomalies (id, kind, ip_address, mac_address, mac_addresses, device_ids, interface_ids, moves,
+                                   first_detected, last_detected)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            "#,
+            anomaly.id,
+            anomaly.kind as AnomalyKind,
+            anomaly.ip_address,
+            anomaly.mac_address as Option<MacAddress>,
+            &anomaly.mac_addresses as &[MacAddress],
+            &anomaly.device_ids,
+            &anomaly.interface_ids,
+            anomaly.moves,
+            anomaly.first_detected,
+            anomaly.last_detected
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    for anomaly in reconciled.ongoing.iter().chain(&reconciled.resolved) {
+        sqlx::query!(
+            r#"
+            UPDATE anomalies SET mac_addresses = $2, device_ids = $3, interface_ids = $4, moves = $5,
+                                 last_detected = $6, resolved_at = $7
+            WHERE id = $1
+            "#,
+            anomaly.id,
+            &anomaly.mac_addresses as &[MacAddress],
+            &anomaly.device_ids,
+            &anomaly.interface_ids,
+            anomaly.moves,
+            anomaly.last_detected,
+            anomaly.resolved_at
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(reconciled.report())
+}
+
+/// Lists stored anomalies matching the filter, most recently detected first.
+pub async fn list_anomalies(pool: &PgPool, filter: &AnomalyFilter) -> Result<Vec<Anomaly>, DbError> {
+    let rows = sqlx::query(&format!(
+        r#"SELECT {} FROM anomalies
+           WHERE ($1::anomaly_kind IS NULL OR kind = $1)
+             AND ($2::macaddr IS NULL OR mac_address = $2 OR $2 = ANY(mac_addresses))
+             AND ($3::inet IS NULL OR host(ip_address) = host($3))
+             AND (NOT $4 OR resolved_at IS NULL)
+             AND ($5::timestamptz IS NULL OR last_detected >= $5)
+           ORDER BY last_detected DESC, id
+           LIMIT $6"#,
+        ANOMALY_COLUMNS
+    ))
+    .bind(filter.kind)
+    .bind(filter.mac_address)
+    .bind(filter.ip_address.map(IpNetwork::from))
+    .bind(filter.open_only)
+    .bind(filter.since)
+    .bind(i64::from(filter.limit()))
+    .fetch_all(pool)
+    .await?;
+    rows.iter().map(anomaly_from_row).collect()
+}
+
+/// Detects anomalies every `interval_minutes`, for as long as the caller runs it.
+pub async fn run_anomaly_detection(repo: Arc<dyn Repository>, settings: AnomalySettings) {
+    let mut interval = tokio::time::interval(Duration::from_secs(settings.interval_minutes.max(1) * 60));
+    loop {
+        interval.tick().await;
+        match repo.detect_anomalies(&settings).await {
+            Ok(report) => tracing::info!(
+                opened = report.opened,
+                ongoing = report.ongoing,
+                resolved = report.resolved,
+                "Anomaly detection finished"
+            ),
+            Err(e) => tracing::warn!(error = %e, "Anomaly detection failed"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mac(s: &str) -> MacAddress {
+        s.parse().unwrap()
+    }
+
+    fn minutes_ago(now: OffsetDateTime, minutes: i64) -> OffsetDateTime {
+        now - time::Duration::minutes(minutes)
+    }
+
+    fn claim(mac_address: &str, ip: &str, since: OffsetDateTime, last_seen: OffsetDateTime) -> IpClaim {
+        IpClaim { mac_address: mac(mac_address), device_id: Uuid::nil(), ip_address: ip.parse().unwrap(), since, last_seen }
+    }
+
+    fn sighting(mac_address: &str, device_id: Uuid, interface_id: Uuid, last_seen: OffsetDateTime) -> Node {
+        Node {
+            id: Uuid::new_v4(),
+            mac_address: mac(mac_address),
+            device_id,
+            interface_id: Some(interface_id),
+            vlan: 10,
+            active: true,
+            first_seen: last_seen,
+            last_seen,
+            wireless: None,
+        }
+    }
+
+    #[test]
+    fn flags_overlapping_address_claims_but_not_handovers() {
+        let now = OffsetDateTime::now_utc();
+        let settings = AnomalySettings::default();
+        let claims = [
+            // .10 moved from one laptop to the next: no overlap
+            claim("00:00:00:00:00:01", "10.0.0.10/32", minutes_ago(now, 50), minutes_ago(now, 30)),
+            claim("00:00:00:00:00:02", "10.0.0.10/32", minutes_ago(now, 20), minutes_ago(now, 1)),
+            // .20 held by two MACs at once
+            claim("00:00:00:00:00:03", "10.0.0.20/32", minutes_ago(now, 40), minutes_ago(now, 1)),
+            claim("00:00:00:00:00:04", "10.0.0.20/32", minutes_ago(now, 10), minutes_ago(now, 2)),
+            // .30 clashed, but before the window
+            claim("00:00:00:00:00:05", "10.0.0.30/32", minutes_ago(now, 200), minutes_ago(now, 90)),
+            claim("00:00:00:00:00:06", "10.0.0.30/32", minutes_ago(now, 200), minutes_ago(now, 90)),
+        ];
+
+        let findings = detect(&settings, now, &[], &claims, &[]);
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].kind, AnomalyKind::DuplicateIp);
+        assert_eq!(findings[0].ip_address, Some("10.0.0.20".parse().unwrap()));
+        assert_eq!(findings[0].mac_addresses, vec![mac("00:00:00:00:00:03"), mac("00:00:00:00:00:04")]);
+    }
+
+    #[test]
+    fn flags_flapping_and_macs_on_several_access_ports() {
+        let now = OffsetDateTime::now_utc();
+        let settings = AnomalySettings::default();
+        let (switch_a, switch_b) = (Uuid::new_v4(), Uuid::new_v4());
+        let (port_1, port_2, uplink) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let flapping = mac("00:00:00:00:00:0a");
+        let moves: Vec<NodeMove> = (0..3)
+            .map(|i| NodeMove {
+                mac_address: flapping,
+                device_id: switch_a,
+                from_interface_id: Some(if i % 2 == 0 { port_1 } else { port_2 }),
+                to_interface_id: Some(if i % 2 == 0 { port_2 } else { port_1 }),
+                moved_at: minutes_ago(now, 10 * i),
+            })
+            .collect();
+        let mut nodes = vec![
+            sighting("00:00:00:00:00:0b", switch_a, port_1, now),
+            sighting("00:00:00:00:00:0b", switch_b, port_2, now),
+        ];
+        // Everything behind switch B shows up on A's uplink, which is no access port
+        for i in 0..5 {
+            nodes.push(sighting(&format!("00:00:00:00:01:0{}", i), switch_a, uplink, now));
+        }
+        nodes.push(sighting("00:00:00:00:01:00", switch_b, port_2, now));
+
+        let findings = detect(&settings, now, &nodes, &[], &moves);
+
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].kind, AnomalyKind::MacFlapping);
+        assert_eq!(findings[0].mac_address, Some(flapping));
+        assert_eq!(findings[0].moves, Some(3));
+        assert_eq!(findings[1].kind, AnomalyKind::MacMultiplePorts);
+        assert_eq!(findings[1].mac_address, Some(mac("00:00:00:00:00:0b")));
+        assert_eq!(findings[1].device_ids.len(), 2);
+    }
+
+    #[test]
+    fn reconcile_keeps_one_event_per_problem() {
+        let first_run = OffsetDateTime::now_utc();
+        let finding = Finding {
+            kind: AnomalyKind::MacMultiplePorts,
+            ip_address: None,
+            mac_address: Some(mac("00:00:00:00:00:0b")),
+            mac_addresses: Vec::new(),
+            device_ids: Vec::new(),
+            interface_ids: Vec::new(),
+            moves: None,
+        };
+        let opened = reconcile(Vec::new(), vec![finding.clone()], first_run).opened;
+        assert_eq!(opened.len(), 1);
+
+        let second_run = first_run + time::Duration::minutes(15);
+        let reconciled = reconcile(opened.clone(), vec![finding], second_run);
+        assert!(reconciled.opened.is_empty());
+        assert_eq!(reconciled.ongoing[0].id, opened[0].id);
+        assert_eq!(reconciled.ongoing[0].first_detected, first_run);
+        assert_eq!(reconciled.ongoing[0].last_detected, second_run);
+
+        let third_run = second_run + time::Duration::minutes(15);
+        let reconciled = reconcile(reconciled.ongoing, Vec::new(), third_run);
+        assert_eq!(reconciled.report(), AnomalyReport { opened: 0, ongoing: 0, resolved: 1 });
+        assert_eq!(reconciled.resolved[0].resolved_at, Some(third_run));
+    }
+}
@@ -0,0 +1,417 @@
+//! Batch upserts for large discovery sweeps.
+//!
+//! Each call writes a whole batch in one transaction with a fixed number of
+//! statements, passing columns as arrays to `INSERT ... SELECT FROM UNNEST`,
+//! instead of one round trip per row. Rows repeating a conflict key within a
+//! batch are collapsed to the last one, as Postgres cannot update a row twice
+//! in one statement. A failing row (e.g. a duplicate hostname) fails the batch.
+
+use crate::history::{self, AttributeChange};
+use crate::locations;
+use crate::merge::{self, FieldSources, SourceUpdate};
+use crate::{CustomFieldValues, DbError, Device, DeviceStatus, DeviceType, Interface, MacAddress, Node, PgPool};
+use sqlx::types::Json;
+use ipnetwork::IpNetwork;
+use std::collections::HashMap;
+use std::hash::Hash;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// Keeps the last row for each key, in first-seen key order.
+pub(crate) fn dedup_last<T, K: Eq + Hash>(rows: &[T], key: impl Fn(&T) -> K) -> Vec<&T> {
+    let mut positions: HashMap<K, usize> = HashMap::with_capacity(rows.len());
+    let mut deduped: Vec<&T> = Vec::with_capacity(rows.len());
+    for row in rows {
+        match positions.get(&key(row)) {
+            Some(&position) => deduped[position] = row,
+            None => {
+                positions.insert(key(row), deduped.len());
+                deduped.push(row);
+            }
+        }
+    }
+    deduped
+}
+
+/// Batch version of [`upsert_device`](crate::upsert_device), with the same merge,
+/// field source and history rules. Returns the stored devices in input order
+/// (one per distinct ip_address).
+pub async fn upsert_devices(pool: &PgPool, devices: &[Device], source: &str) -> Result<Vec<Device>, DbError> {
+    let devices = dedup_last(devices, |d| d.ip_address);
+    if devices.is_empty() {
+        return Ok(Vec::new());
+    }
+    loop {
+        if let Some(stored) = try_upsert_devices(pool, &devices, source).await? {
+            return Ok(stored);
+        }
+        // Another upsert created some of the devices first; merge into their rows instead
+    }
+}
+
+// One attempt at `upsert_devices`. Returns `None`, changing nothing, when a device was not
+// stored at the start but another transaction inserted it before this one could.
+async fn try_upsert_devices(pool: &PgPool, devices: &[&Device], source: &str) -> Result<Option<Vec<Device>>, DbError> {
+    let mut tx = pool.begin().await?;
+
+    let ips: Vec<IpNetwork> = devices.iter().map(|d| d.ip_address).collect();
+    let existing_rows = sqlx::query!(
+        r#"SELECT
+              id, hostname, ip_address, sys_name, sys_descr, sys_location, vendor, model,
+              os_version, serial_number,
+              status::text as "status: Option<String>",
+              device_type::text as "device_type: Option<String>", location_id,
+              tags, custom_fields as "custom_fields: Json<CustomFieldValues>",
+              last_seen, last_boot, created_at, updated_at
+           FROM devices WHERE ip_address = ANY($1) FOR UPDATE"#,
+        &ips
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let mut existing: HashMap<IpNetwork, Device> = HashMap::with_capacity(existing_rows.len());
+    for row in existing_rows {
+        let status = row.status.flatten().map(DeviceStatus::try_from).transpose().map_err(DbError::MappingError)?;
+        let device_type =
+            row.device_type.flatten().map(DeviceType::try_from).transpose().map_err(DbError::MappingError)?;
+        existing.insert(
+            row.ip_address,
+            Device {
+                id: row.id,
+                hostname: row.hostname,
+                ip_address: row.ip_address,
+                sys_name: row.sys_name,
+                sys_descr: row.sys_descr,
+                sys_location: row.sys_location,
+                vendor: row.vendor,
+                model: row.model,
+                os_version: row.os_version,
+                serial_number: row.serial_number,
+                status,
+                device_type,
+                location_id: row.location_id,
+                tags: row.tags,
+                custom_fields: row.custom_fields.0,
+                last_seen: row.last_seen,
+                last_boot: row.last_boot,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            },
+        );
+    }
+
+    let existing_ids: Vec<Uuid> = existing.values().map(|d| d.id).collect();
+    let mut sources: HashMap<Uuid, FieldSources> = HashMap::new();
+    for row in sqlx::query!(
+        "SELECT device_id, field, source FROM device_field_sources WHERE device_id = ANY($1)",
+        &existing_ids
+    )
+    .fetch_all(&mut *tx)
+    .await?
+    {
+        sources.entry(row.device_id).or_default().insert(row.field, row.source);
+    }
+
+    let no_sources = FieldSources::new();
+    let (merged, source_updates): (Vec<Device>, Vec<Vec<SourceUpdate>>) = devices
+        .iter()
+        .map(|device| match existing.get(&device.ip_address) {
+            Some(current) => {
+                merge::merge_device(current, sources.get(&current.id).unwrap_or(&no_sources), device, source)
+            }
+            None => ((*device).clone(), merge::initial_sources(device)),
+        })
+        .unzip();
+    let rules = locations::load_location_rules(&mut tx).await?;
+    let merged: Vec<Device> = merged
+        .into_iter()
+        .map(|device| {
+            let device_sources = sources.get(&device.id).unwrap_or(&no_sources);
+            Device { location_id: locations::resolve_location(&rules, device_sources, &device), ..device }
+        })
+        .collect();
+
+    let column = |f: fn(&Device) -> Option<String>| merged.iter().map(f).collect::<Vec<_>>();
+    let rows = sqlx::query!(
+        r#"
+        INSERT INTO devices (
+            hostname, ip_address, sys_name, sys_descr, sys_location, vendor, model,
+            os_version, serial_number, status, device_type, location_id, last_seen, last_boot
+        )
+        SELECT hostname, ip_address, sys_name, sys_descr, sys_location, vendor, model,
+               os_version, serial_number, status::device_status, device_type::device_type, location_id, last_seen, last_boot
+        FROM UNNEST(
+            $1::varchar[], $2::inet[], $3::varchar[], $4::text[], $5::varchar[], $6::varchar[], $7::varchar[],
+            $8::varchar[], $9::varchar[], $10::text[], $11::text[], $12::uuid[], $13::timestamptz[], $15::timestamptz[]
+        ) AS t(hostname, ip_address, sys_name, sys_descr, sys_location, vendor, model,
+               os_version, serial_number, status, device_type, location_id, last_seen, last_boot)
+        ON CONFLICT (ip_address) DO UPDATE SET
+            hostname = EXCLUDED.hostname,
+            sys_name = EXCLUDED.sys_name,
+            sys_descr = EXCLUDED.sys_descr,
+            sys_location = EXCLUDED.sys_location,
+            vendor = EXCLUDED.vendor,
+            model = EXCLUDED.model,
+            os_version = EXCLUDED.os_version,
+            serial_number = EXCLUDED.serial_number,
+            status = EXCLUDED.status,
+            device_type = EXCLUDED.device_type,
+            location_id = EXCLUDED.location_id,
+            last_seen = EXCLUDED.last_seen,
+            last_boot = EXCLUDED.last_boot,
+            updated_at = NOW()
+        WHERE devices.id = ANY($14) -- Only rows locked and merged above; racing inserts return nothing
+        RETURNING
+            id, hostname, ip_address, sys_name, sys_descr, sys_location, vendor, model,
+            os_version, serial_number,
+            status::text as "status: Option<String>",
+            device_type::text as "device_type: Option<String>", location_id,
+            tags, custom_fields as "custom_fields: Json<CustomFieldValues>",
+            last_seen, last_boot, created_at, updated_at
+        "#,
+        &column(|d| d.hostname.clone()) as &[Option<String>],
+        &ips,
+        &column(|d| d.sys_name.clone()) as &[Option<String>],
+        &column(|d| d.sys_descr.clone()) as &[Option<String>],
+        &column(|d| d.sys_location.clone()) as &[Option<String>],
+        &column(|d| d.vendor.clone()) as &[Option<String>],
+        &column(|d| d.model.clone()) as &[Option<String>],
+        &column(|d| d.os_version.clone()) as &[Option<String>],
+        &column(|d| d.serial_number.clone()) as &[Option<String>],
+        &column(|d| d.status.as_ref().map(|s| s.as_str().to_string())) as &[Option<String>],
+        &column(|d| d.device_type.map(|t| t.as_str().to_string())) as &[Option<String>],
+        &merged.iter().map(|d| d.location_id).collect::<Vec<_>>() as &[Option<Uuid>],
+        &merged.iter().map(|d| d.last_seen).collect::<Vec<_>>() as &[Option<OffsetDateTime>],
+        &existing_ids,
+        &merged.iter().map(|d| d.last_boot).collect::<Vec<_>>() as &[Option<OffsetDateTime>]
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| crate::duplicate_hostname(e, None))?;
+    if rows.len() < ips.len() {
+        return Ok(None);
+    }
+
+    let mut stored: HashMap<IpNetwork, Device> = HashMap::with_capacity(rows.len());
+    for row in rows {
+        let status = row.status.flatten().map(DeviceStatus::try_from).transpose().map_err(DbError::MappingError)?;
+        let device_type =
+            row.device_type.flatten().map(DeviceType::try_from).transpose().map_err(DbError::MappingError)?;
+        stored.insert(
+            row.ip_address,
+            Device {
+                id: row.id,
+                hostname: row.hostname,
+                ip_address: row.ip_address,
+                sys_name: row.sys_name,
+                sys_descr: row.sys_descr,
+                sys_location: row.sys_location,
+                vendor: row.vendor,
+                model: row.model,
+                os_version: row.os_version,
+                serial_number: row.serial_number,
+                status,
+                device_type,
+                location_id: row.location_id,
+                tags: row.tags,
+                custom_fields: row.custom_fields.0,
+                last_seen: row.last_seen,
+                last_boot: row.last_boot,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            },
+        );
+    }
+    let stored: Vec<Device> = ips
+        .iter()
+        .map(|ip| stored.remove(ip).ok_or_else(|| DbError::MappingError(format!("device {} not returned", ip))))
+        .collect::<Result<_, _>>()?;
+
+    // Field sources: only Set updates come out of a merge
+    let (source_ids, source_fields): (Vec<Uuid>, Vec<String>) = stored
+        .iter()
+        .zip(&source_updates)
+        .flat_map(|(device, updates)| {
+            updates.iter().filter_map(move |update| match update {
+                SourceUpdate::Set(field) => Some((device.id, field.name().to_string())),
+                SourceUpdate::Remove(_) => None,
+            })
+        })
+        .unzip();
+    sqlx::query!(
+        r#"
+        INSERT INTO device_field_sources (device_id, field, source)
+        SELECT device_id, field, $3 FROM UNNEST($1::uuid[], $2::varchar[]) AS t(device_id, field)
+        ON CONFLICT (device_id, field) DO UPDATE SET
+            source = EXCLUDED.source,
+            updated_at = NOW()
+        "#,
+        &source_ids,
+        &source_fields,
+        source
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let changes: Vec<(Uuid, AttributeChange)> = stored
+        .iter()
+        .filter_map(|device| existing.get(&device.ip_address).map(|old| (device, old)))
+        .flat_map(|(device, old)| history::device_changes(old, device).into_iter().map(|c| (device.id, c)))
+        .collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO device_history (device_id, attribute, old_value, new_value, source)
+        SELECT device_id, attribute, old_value, new_value, $5
+        FROM UNNEST($1::uuid[], $2::varchar[], $3::text[], $4::text[]) AS t(device_id, attribute, old_value, new_value)
+        "#,
+        &changes.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+        &changes.iter().map(|(_, c)| c.attribute.to_string()).collect::<Vec<_>>(),
+        &changes.iter().map(|(_, c)| c.old_value.clone()).collect::<Vec<_>>() as &[Option<String>],
+        &changes.iter().map(|(_, c)| c.new_value.clone()).collect::<Vec<_>>() as &[Option<String>],
+        source
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(Some(stored))
+}
+
+/// Batch version of [`upsert_interface`](crate::upsert_interface), with the same
+/// update rules. Returns the number of interfaces written.
+pub async fn upsert_interfaces(pool: &PgPool, interfaces: &[Interface]) -> Result<u64, DbError> {
+    let interfaces = dedup_last(interfaces, |i| (i.device_id, i.if_index));
+    if interfaces.is_empty() {
+        return Ok(0);
+    }
+    let text = |f: fn(&Interface) -> Option<String>| interfaces.iter().map(|i| f(i)).collect::<Vec<_>>();
+
+    let mut tx = pool.begin().await?;
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO interfaces (
+            device_id, if_index, if_name, if_alias, if_descr, if_type,
+            mac_address, ip_address, admin_status, oper_status, speed, mtu, access_vlan, last_changed
+        )
+        SELECT device_id, if_index, if_name, if_alias, if_descr, if_type,
+               mac_address, ip_address, admin_status, oper_status, speed, mtu, access_vlan, last_changed
+        FROM UNNEST(
+            $1::uuid[], $2::int4[], $3::varchar[], $4::varchar[], $5::varchar[], $6::varchar[],
+            $7::macaddr[], $8::inet[], $9::varchar[], $10::varchar[], $11::int8[], $12::int4[], $13::int4[],
+            $14::timestamptz[]
+        ) AS t(device_id, if_index, if_name, if_alias, if_descr, if_type,
+               mac_address, ip_address, admin_status, oper_status, speed, mtu, access_vlan, last_changed)
+        ON CONFLICT (device_id, if_index) DO UPDATE SET
+            if_name = EXCLUDED.if_name,
+            if_alias = EXCLUDED.if_alias,
+            if_descr = EXCLUDED.if_descr,
+            if_type = EXCLUDED.if_type,
+            mac_address = EXCLUDED.mac_address,
+            ip_address = COALESCE(EXCLUDED.ip_address, interfaces.ip_address),
+            admin_status = EXCLUDED.admin_status,
+            oper_status = EXCLUDED.oper_status,
+            speed = EXCLUDED.speed,
+            mtu = EXCLUDED.mtu,
+            access_vlan = COALESCE(EXCLUDED.access_vlan, interfaces.access_vlan),
+            last_changed = COALESCE(EXCLUDED.last_changed, interfaces.last_changed),
+            updated_at = NOW()
+        "#,
+        &interfaces.iter().map(|i| i.device_id).collect::<Vec<_>>(),
+        &interfaces.iter().map(|i| i.if_index).collect::<Vec<_>>(),
+        &text(|i| i.if_name.clone()) as &[Option<String>],
+        &text(|i| i.if_alias.clone()) as &[Option<String>],
+        &text(|i| i.if_descr.clone()) as &[Option<String>],
+        &text(|i| i.if_type.clone()) as &[Option<String>],
+        &interfaces.iter().map(|i| i.mac_address).collect::<Vec<_>>() as &[Option<MacAddress>],
+        &interfaces.iter().map(|i| i.ip_address).collect::<Vec<_>>() as &[Option<IpNetwork>],
+        &text(|i| i.admin_status.clone()) as &[Option<String>],
+        &text(|i| i.oper_status.clone()) as &[Option<String>],
+        &interfaces.iter().map(|i| i.speed).collect::<Vec<_>>() as &[Option<i64>],
+        &interfaces.iter().map(|i| i.mtu).collect::<Vec<_>>() as &[Option<i32>],
+        &interfaces.iter().map(|i| i.access_vlan).collect::<Vec<_>>() as &[Option<i32>],
+        &interfaces.iter().map(|i| i.last_changed).collect::<Vec<_>>() as &[Option<OffsetDateTime>]
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(result.rows_affected())
+}
+
+/// Batch version of [`upsert_node`](crate::upsert_node): records sightings of
+/// many nodes, replacing wireless details where given. Returns the node IDs in
+/// input order (one per distinct (mac_address, device_id, vlan)).
+pub async fn upsert_nodes(pool: &PgPool, nodes: &[Node]) -> Result<Vec<Uuid>, DbError> {
+    let nodes = dedup_last(nodes, |n| (n.mac_address, n.device_id, n.vlan));
+    if nodes.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut tx = pool.begin().await?;
+
+    let rows = sqlx::query!(
+        r#"
+        INSERT INTO nodes (mac_address, device_id, interface_id, vlan, active, last_seen)
+        SELECT mac_address, device_id, interface_id, vlan, TRUE, last_seen
+        FROM UNNEST($1::macaddr[], $2::uuid[], $3::uuid[], $4::int4[], $5::timestamptz[])
+            AS t(mac_address, device_id, interface_id, vlan, last_seen)
+        ON CONFLICT (mac_address, device_id, vlan) DO UPDATE SET
+            interface_id = EXCLUDED.interface_id,
+            active = TRUE,
+            last_seen = EXCLUDED.last_seen,
+            updated_at = NOW()
+        RETURNING id, mac_address as "mac_address: MacAddress", device_id, vlan
+        "#,
+        &nodes.iter().map(|n| n.mac_address).collect::<Vec<_>>() as &[MacAddress],
+        &nodes.iter().map(|n| n.device_id).collect::<Vec<_>>(),
+        &nodes.iter().map(|n| n.interface_id).collect::<Vec<_>>() as &[Option<Uuid>],
+        &nodes.iter().map(|n| n.vlan).collect::<Vec<_>>(),
+        &nodes.iter().map(|n| n.last_seen).collect::<Vec<_>>()
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let ids: HashMap<(MacAddress, Uuid, i32), Uuid> =
+        rows.into_iter().map(|row| ((row.mac_address, row.device_id, row.vlan), row.id)).collect();
+    let node_ids: Vec<Uuid> = nodes
+        .iter()
+        .map(|n| {
+            ids.get(&(n.mac_address, n.device_id, n.vlan))
+                .copied()
+                .ok_or_else(|| DbError::MappingError(format!("node {} not returned", n.mac_address)))
+        })
+        .collect::<Result<_, _>>()?;
+
+    let wireless: Vec<(Uuid, &crate::NodeWireless)> =
+        node_ids.iter().zip(&nodes).filter_map(|(id, n)| n.wireless.as_ref().map(|w| (*id, w))).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO node_wireless (node_id, ssid, rssi, ip_address, last_seen)
+        SELECT * FROM UNNEST($1::uuid[], $2::varchar[], $3::int4[], $4::inet[], $5::timestamptz[])
+        ON CONFLICT (node_id) DO UPDATE SET
+            ssid = EXCLUDED.ssid,
+            rssi = EXCLUDED.rssi,
+            ip_address = EXCLUDED.ip_address,
+            last_seen = EXCLUDED.last_seen
+        "#,
+        &wireless.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+        &wireless.iter().map(|(_, w)| w.ssid.clone()).collect::<Vec<_>>() as &[Option<String>],
+        &wireless.iter().map(|(_, w)| w.rssi).collect::<Vec<_>>() as &[Option<i32>],
+        &wireless.iter().map(|(_, w)| w.ip_address).collect::<Vec<_>>() as &[Option<IpNetwork>],
+        &wireless.iter().map(|(_, w)| w.last_seen).collect::<Vec<_>>()
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(node_ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dedup_keeps_last_row_per_key_in_first_seen_order() {
+        let rows = [("a", 1), ("b", 2), ("a", 3)];
+        let deduped = dedup_last(&rows, |r| r.0);
+        assert_eq!(deduped, vec![&("a", 3), &("b", 2)]);
+    }
+}
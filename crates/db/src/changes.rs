@@ -0,0 +1,109 @@
+//! Inventory change feed over Postgres LISTEN/NOTIFY.
+//!
+//! Triggers on `devices`, `interfaces` and `mac_violations` publish a JSON [`ChangeEvent`] on
+//! [`CHANGES_CHANNEL`] when a transaction commits. Notifications are not stored, so a
+//! listener that loses its connection misses whatever happened meanwhile; the stream
+//! reports that as [`ChangeEvent::Resync`] once it is listening again.
+
+use crate::{DbError, MacAddress, PgPool};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgListener;
+use uuid::Uuid;
+
+/// Channel the change triggers notify on.
+pub const CHANGES_CHANNEL: &str = "nd_changes";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ChangeEvent {
+    DeviceCreated { device_id: Uuid },
+    /// Any attribute changed; refreshing `last_seen` alone does not count.
+    DeviceUpdated { device_id: Uuid },
+    DeviceRemoved { device_id: Uuid },
+    InterfaceOperStatusChanged {
+        interface_id: Uuid,
+        device_id: Uuid,
+        if_index: i32,
+        old_status: Option<String>,
+        new_status: Option<String>,
+    },
+    /// A MAC missing from the allowlist showed up on a switch or AP for the first time.
+    MacViolation {
+        violation_id: Uuid,
+        mac_address: MacAddress,
+        device_id: Uuid,
+        interface_id: Option<Uuid>,
+    },
+    /// Changes may have been missed (the connection dropped); reload whatever is cached.
+    Resync,
+}
+
+/// Change events as they are committed, from [`subscribe_changes`].
+pub struct ChangeStream {
+    pool: PgPool,
+    listener: Option<PgListener>, // None after the connection was lost
+}
+
+async fn listen(pool: &PgPool) -> Result<PgListener, DbError> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener.listen(CHANGES_CHANNEL).await?;
+    Ok(listener)
+}
+
+/// Starts listening for changes on a dedicated connection.
+pub async fn subscribe_changes(pool: &PgPool) -> Result<ChangeStream, DbError> {
+    Ok(ChangeStream { pool: pool.clone(), listener: Some(listen(pool).await?) })
+}
+
+impl ChangeStream {
+    /// Waits for the next change. After the connection drops this reconnects and returns
+    /// `Resync`; when reconnecting fails the error is returned and the next call tries again,
+    /// so callers only need to back off between calls.
+    pub async fn recv(&mut self) -> Result<ChangeEvent, DbError> {
+        loop {
+            let Some(listener) = self.listener.as_mut() else {
+                self.listener = Some(listen(&self.pool).await?);
+                return Ok(ChangeEvent::Resync);
+            };
+            match listener.try_recv().await {
+                Ok(Some(notification)) => match serde_json::from_str(notification.payload()) {
+                    Ok(event) => return Ok(event),
+                    Err(e) => tracing::warn!(error = %e, payload = notification.payload(), "Ignoring unknown change notification"),
+                },
+                Ok(None) => {
+                    tracing::warn!("Change feed connection lost, reconnecting");
+                    self.listener = None;
+                }
+                Err(e) => {
+                    self.listener = None;
+                    return Err(e.into());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_trigger_payloads() {
+        let id = Uuid::new_v4();
+        let payload = format!(
+            r#"{{"event" : "interface_oper_status_changed", "interface_id" : "{id}", "device_id" : "{id}", "if_index" : 3, "old_status" : "up", "new_status" : null}}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<ChangeEvent>(&payload).unwrap(),
+            ChangeEvent::InterfaceOperStatusChanged {
+                interface_id: id,
+                device_id: id,
+                if_index: 3,
+                old_status: Some("up".to_string()),
+                new_status: None,
+            }
+        );
+        let payload = format!(r#"{{"event" : "device_removed", "device_id" : "{id}"}}"#);
+        assert_eq!(serde_json::from_str::<ChangeEvent>(&payload).unwrap(), ChangeEvent::DeviceRemoved { device_id: id });
+    }
+}
@@ -0,0 +1,466 @@
+//! Filtered, sorted and keyset-paginated device listings.
+//!
+//! Pages are continued with an opaque cursor holding the sort key and ID of the
+//! last device returned, so deep pages cost the same as the first and devices
+//! added while paging do not shift later pages.
+
+use crate::{CustomFieldValues, DbError, Device, DeviceStatus, DeviceType, MetadataFilter, PgPool};
+use ipnetwork::IpNetwork;
+use sqlx::postgres::PgRow;
+use sqlx::types::Json;
+use sqlx::{Postgres, QueryBuilder, Row};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+pub const DEFAULT_PAGE_SIZE: u32 = 100;
+pub const MAX_PAGE_SIZE: u32 = 1000;
+
+/// Column a device listing is ordered by; ties are broken by device ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeviceSort {
+    #[default]
+    Hostname,
+    SysName,
+    Vendor,
+    Model,
+    IpAddress,
+    LastSeen,
+}
+
+impl DeviceSort {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeviceSort::Hostname => "hostname",
+            DeviceSort::SysName => "sys_name",
+            DeviceSort::Vendor => "vendor",
+            DeviceSort::Model => "model",
+            DeviceSort::IpAddress => "ip_address",
+            DeviceSort::LastSeen => "last_seen",
+        }
+    }
+
+    // NULLs sort as the lowest value so they can take part in the keyset comparison
+    fn sql_expr(&self) -> &'static str {
+        match self {
+            DeviceSort::Hostname => "COALESCE(hostname, '')",
+            DeviceSort::SysName => "COALESCE(sys_name, '')",
+            DeviceSort::Vendor => "COALESCE(vendor, '')",
+            DeviceSort::Model => "COALESCE(model, '')",
+            DeviceSort::IpAddress => "ip_address",
+            DeviceSort::LastSeen => "COALESCE(last_seen, '-infinity'::timestamptz)",
+        }
+    }
+
+    /// The value of this sort column for `device`.
+    pub(crate) fn key(&self, device: &Device) -> SortKey {
+        let text = |value: &Option<String>| SortKey::Text(value.clone().unwrap_or_default());
+        match self {
+            DeviceSort::Hostname => text(&device.hostname),
+            DeviceSort::SysName => text(&device.sys_name),
+            DeviceSort::Vendor => text(&device.vendor),
+            DeviceSort::Model => text(&device.model),
+            DeviceSort::IpAddress => SortKey::Ip(device.ip_address),
+            DeviceSort::LastSeen => SortKey::Time(device.last_seen),
+        }
+    }
+}
+
+impl FromStr for DeviceSort {
+    type Err = DbError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hostname" => Ok(DeviceSort::Hostname),
+            "sys_name" => Ok(DeviceSort::SysName),
+            "vendor" => Ok(DeviceSort::Vendor),
+            "model" => Ok(DeviceSort::Model),
+            "ip_address" => Ok(DeviceSort::IpAddress),
+            "last_seen" => Ok(DeviceSort::LastSeen),
+            other => Err(DbError::InvalidQuery(format!("unknown sort field '{}'", other))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl FromStr for SortDirection {
+    type Err = DbError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "asc" => Ok(SortDirection::Asc),
+            "desc" => Ok(SortDirection::Desc),
+            other => Err(DbError::InvalidQuery(format!("unknown sort order '{}'", other))),
+        }
+    }
+}
+
+/// Device filters; all set filters must match. Also the membership rules of
+/// dynamic [`DeviceGroup`](crate::DeviceGroup)s, stored as JSON.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceFilter {
+    pub vendor: Option<String>, // case-insensitive
+    pub model: Option<String>,  // case-insensitive
+    pub status: Option<DeviceStatus>,
+    pub device_type: Option<DeviceType>,
+    pub include_retired: bool, // retired/decommissioned are hidden unless set or asked for by status
+    pub subnet: Option<IpNetwork>, // management address within this subnet
+    pub seen_after: Option<OffsetDateTime>,  // last_seen >= this
+    pub seen_before: Option<OffsetDateTime>, // last_seen < this
+    pub search: Option<String>, // substring of sys_name or sys_descr, case-insensitive
+    pub location_id: Option<Uuid>, // in this location or any location below it
+    pub group_id: Option<Uuid>,    // member of this static or dynamic group
+    #[serde(flatten)]
+    pub metadata: MetadataFilter, // tags and custom field values
+}
+
+impl DeviceFilter {
+    fn shows_retired(&self) -> bool {
+        self.include_retired || self.status.as_ref().is_some_and(DeviceStatus::is_retired)
+    }
+
+    /// In-memory equivalent of the SQL conditions, for [`MemoryRepository`](crate::MemoryRepository).
+    /// Locations and groups need the stored hierarchy and are checked by the caller.
+    pub(crate) fn matches(&self, device: &Device) -> bool {
+        let eq_ignore_case =
+            |wanted: &Option<String>, value: &Option<String>| match (wanted, value) {
+                (None, _) => true,
+                (Some(wanted), Some(value)) => wanted.eq_ignore_ascii_case(value),
+                (Some(_), None) => false,
+            };
+        let seen = |bound: Option<OffsetDateTime>, check: fn(&OffsetDateTime, &OffsetDateTime) -> bool| {
+            bound.is_none_or(|bound| device.last_seen.is_some_and(|seen| check(&seen, &bound)))
+        };
+        let search = self.search.as_ref().map(|s| s.to_lowercase());
+
+        eq_ignore_case(&self.vendor, &device.vendor)
+            && eq_ignore_case(&self.model, &device.model)
+            && self.status.as_ref().is_none_or(|status| device.status.as_ref() == Some(status))
+            && (self.shows_retired() || !device.status.as_ref().is_some_and(DeviceStatus::is_retired))
+            && self.device_type.is_none_or(|device_type| device.device_type == Some(device_type))
+            && self.subnet.is_none_or(|subnet| {
+                subnet.contains(device.ip_address.ip()) && device.ip_address.prefix() >= subnet.prefix()
+            })
+            && seen(self.seen_after, OffsetDateTime::ge)
+            && seen(self.seen_before, OffsetDateTime::lt)
+            && search.is_none_or(|search| {
+                [&device.sys_name, &device.sys_descr]
+                    .into_iter()
+                    .flatten()
+                    .any(|text| text.to_lowercase().contains(&search))
+            })
+            && self.metadata.matches(&device.tags, &device.custom_fields)
+    }
+}
+
+/// One page request of a device listing.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceQuery {
+    pub filter: DeviceFilter,
+    pub sort: DeviceSort,
+    pub direction: SortDirection,
+    pub limit: Option<u32>, // defaults to DEFAULT_PAGE_SIZE, capped at MAX_PAGE_SIZE
+    pub cursor: Option<String>, // `next_cursor` of the previous page
+}
+
+impl DeviceQuery {
+    pub(crate) fn page_size(&self) -> u32 {
+        self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+    }
+
+    /// Decodes the cursor, which must come from a listing with the same sort and direction.
+    pub(crate) fn after(&self) -> Result<Option<(SortKey, Uuid)>, DbError> {
+        let Some(cursor) = &self.cursor else {
+            return Ok(None);
+        };
+        let invalid = || DbError::InvalidQuery("invalid cursor".to_string());
+        let mut parts = cursor.splitn(4, '.');
+        let (Some(sort), Some(direction), Some(key), Some(id)) = (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        if sort != self.sort.as_str() || direction != direction_str(self.direction) {
+            return Err(DbError::InvalidQuery("cursor belongs to a different sort order".to_string()));
+        }
+        let key = String::from_utf8(decode_hex(key).ok_or_else(invalid)?).map_err(|_| invalid())?;
+        let key = match self.sort {
+            DeviceSort::IpAddress => SortKey::Ip(key.parse().map_err(|_| invalid())?),
+            DeviceSort::LastSeen if key.is_empty() => SortKey::Time(None),
+            DeviceSort::LastSeen => {
+                let nanos: i128 = key.parse().map_err(|_| invalid())?;
+                SortKey::Time(Some(OffsetDateTime::from_unix_timestamp_nanos(nanos).map_err(|_| invalid())?))
+            }
+            _ => SortKey::Text(key),
+        };
+        Ok(Some((key, id.parse().map_err(|_| invalid())?)))
+    }
+
+    /// Cursor continuing after `last`.
+    pub(crate) fn cursor_after(&self, last: &Device) -> String {
+        let key = match self.sort.key(last) {
+            SortKey::Text(text) => text,
+            SortKey::Ip(ip) => ip.to_string(),
+            SortKey::Time(time) => time.map(|t| t.unix_timestamp_nanos().to_string()).unwrap_or_default(),
+        };
+        format!("{}.{}.{}.{}", self.sort.as_str(), direction_str(self.direction), encode_hex(key.as_bytes()), last.id)
+    }
+}
+
+/// A page of devices and the cursor for the next one, if there is more.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct DevicePage {
+    pub devices: Vec<Device>,
+    pub next_cursor: Option<String>,
+}
+
+/// Sorts and pages devices that already passed the filter, for backends that filter in
+/// memory; orders like [`query_devices`].
+pub(crate) fn page_devices(mut devices: Vec<Device>, query: &DeviceQuery) -> Result<DevicePage, DbError> {
+    let after = query.after()?;
+    devices.sort_by_cached_key(|d| (query.sort.key(d), d.id));
+    if query.direction == SortDirection::Desc {
+        devices.reverse();
+    }
+    if let Some(after) = after {
+        devices.retain(|d| {
+            let position = (query.sort.key(d), d.id);
+            match query.direction {
+                SortDirection::Asc => position > after,
+                SortDirection::Desc => position < after,
+            }
+        });
+    }
+    let page_size = query.page_size() as usize;
+    let next_cursor = (devices.len() > page_size).then(|| query.cursor_after(&devices[page_size - 1]));
+    devices.truncate(page_size);
+    Ok(DevicePage { devices, next_cursor })
+}
+
+/// Typed value of a sort column; orders like the SQL sort expressions.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum SortKey {
+    Text(String),
+    Ip(IpNetwork),
+    Time(Option<OffsetDateTime>),
+}
+
+fn direction_str(direction: SortDirection) -> &'static str {
+    match direction {
+        SortDirection::Asc => "asc",
+        SortDirection::Desc => "desc",
+    }
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok()).collect()
+}
+
+/// Lists one page of devices matching `query`.
+pub async fn query_devices(pool: &PgPool, query: &DeviceQuery) -> Result<DevicePage, DbError> {
+    let after = query.after()?;
+    let page_size = query.page_size();
+
+    let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
+        r#"SELECT
+              id, hostname, ip_address, sys_name, sys_descr, sys_location, vendor, model,
+              os_version, serial_number, status::text as status, device_type::text as device_type, location_id,
+              tags, custom_fields,
+              last_seen, last_boot, created_at, updated_at
+           FROM devices WHERE TRUE"#,
+    );
+    push_conditions(&mut builder, &query.filter);
+    if let Some(group_id) = query.filter.group_id {
+        let rules = sqlx::query_scalar!("SELECT rules FROM device_groups WHERE id = $1", group_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or(DbError::NotFound)?;
+        match rules {
+            Some(rules) => {
+                let rules: DeviceFilter =
+                    serde_json::from_value(rules).map_err(|e| DbError::MappingError(e.to_string()))?;
+                push_conditions(&mut builder, &rules);
+            }
+            None => {
+                builder
+                    .push(" AND id IN (SELECT device_id FROM device_group_members WHERE group_id = ")
+                    .push_bind(group_id)
+                    .push(")");
+            }
+        }
+    }
+
+    let sort_expr = query.sort.sql_expr();
+    let (comparison, direction) = match query.direction {
+        SortDirection::Asc => (">", "ASC"),
+        SortDirection::Desc => ("<", "DESC"),
+    };
+    if let Some((key, id)) = after {
+        builder.push(format_args!(" AND ({}, id) {} (", sort_expr, comparison));
+        match key {
+            SortKey::Text(text) => builder.push_bind(text),
+            SortKey::Ip(ip) => builder.push_bind(ip),
+            SortKey::Time(Some(time)) => builder.push_bind(time),
+            SortKey::Time(None) => builder.push("'-infinity'::timestamptz"),
+        };
+        builder.push(", ").push_bind(id).push(")");
+    }
+    builder.push(format_args!(" ORDER BY {} {}, id {}", sort_expr, direction, direction));
+    // One extra row tells whether another page follows
+    builder.push(" LIMIT ").push_bind(i64::from(page_size) + 1);
+
+    let rows = builder.build().fetch_all(pool).await?;
+    let mut devices = rows.iter().map(device_from_row).collect::<Result<Vec<_>, _>>()?;
+    let next_cursor = if devices.len() > page_size as usize {
+        devices.truncate(page_size as usize);
+        devices.last().map(|last| query.cursor_after(last))
+    } else {
+        None
+    };
+    Ok(DevicePage { devices, next_cursor })
+}
+
+/// Appends the conditions of `filter`, except its group which needs a lookup.
+fn push_conditions(builder: &mut QueryBuilder<'_, Postgres>, filter: &DeviceFilter) {
+    if let Some(vendor) = &filter.vendor {
+        builder.push(" AND lower(vendor) = lower(").push_bind(vendor.clone()).push(")");
+    }
+    if let Some(model) = &filter.model {
+        builder.push(" AND lower(model) = lower(").push_bind(model.clone()).push(")");
+    }
+    if let Some(status) = &filter.status {
+        builder.push(" AND status = ").push_bind(status.clone());
+    }
+    if !filter.shows_retired() {
+        builder.push(" AND status IS DISTINCT FROM 'retired' AND status IS DISTINCT FROM 'decommissioned'");
+    }
+    if let Some(device_type) = filter.device_type {
+        builder.push(" AND device_type = ").push_bind(device_type);
+    }
+    if let Some(subnet) = filter.subnet {
+        builder.push(" AND ip_address <<= ").push_bind(subnet);
+    }
+    if let Some(seen_after) = filter.seen_after {
+        builder.push(" AND last_seen >= ").push_bind(seen_after);
+    }
+    if let Some(seen_before) = filter.seen_before {
+        builder.push(" AND last_seen < ").push_bind(seen_before);
+    }
+    if let Some(search) = &filter.search {
+        let pattern = format!("%{}%", search.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+        builder
+            .push(" AND (sys_name ILIKE ")
+            .push_bind(pattern.clone())
+            .push(" OR sys_descr ILIKE ")
+            .push_bind(pattern)
+            .push(")");
+    }
+    if let Some(location_id) = filter.location_id {
+        builder
+            .push(" AND location_id IN (WITH RECURSIVE subtree AS (SELECT id FROM locations WHERE id = ")
+            .push_bind(location_id)
+            .push(" UNION ALL SELECT l.id FROM locations l JOIN subtree s ON l.parent_id = s.id)")
+            .push(" SELECT id FROM subtree)");
+    }
+    filter.metadata.push_conditions(builder);
+}
+
+pub(crate) fn device_from_row(row: &PgRow) -> Result<Device, DbError> {
+    let status: Option<DeviceStatus> = row
+        .try_get::<Option<String>, _>("status")?
+        .map(DeviceStatus::try_from)
+        .transpose()
+        .map_err(DbError::MappingError)?;
+    let device_type: Option<DeviceType> = row
+        .try_get::<Option<String>, _>("device_type")?
+        .map(DeviceType::try_from)
+        .transpose()
+        .map_err(DbError::MappingError)?;
+
+    Ok(Device {
+        id: row.try_get("id")?,
+        hostname: row.try_get("hostname")?,
+        ip_address: row.try_get("ip_address")?,
+        sys_name: row.try_get("sys_name")?,
+        sys_descr: row.try_get("sys_descr")?,
+        sys_location: row.try_get("sys_location")?,
+        vendor: row.try_get("vendor")?,
+        model: row.try_get("model")?,
+        os_version: row.try_get("os_version")?,
+        serial_number: row.try_get("serial_number")?,
+        status,
+        device_type,
+        location_id: row.try_get("location_id")?,
+        tags: row.try_get("tags")?,
+        custom_fields: row.try_get::<Json<CustomFieldValues>, _>("custom_fields")?.0,
+        last_seen: row.try_get("last_seen")?,
+        last_boot: row.try_get("last_boot")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(hostname: Option<&str>, last_seen: Option<OffsetDateTime>) -> Device {
+        Device {
+            id: Uuid::new_v4(),
+            hostname: hostname.map(str::to_string),
+            last_seen,
+            ..Device::new("10.0.0.1/32".parse().unwrap())
+        }
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        let seen = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        for (sort, device) in [
+            (DeviceSort::Hostname, device(Some("core.sw-1"), None)),
+            (DeviceSort::IpAddress, device(None, None)),
+            (DeviceSort::LastSeen, device(None, Some(seen))),
+            (DeviceSort::LastSeen, device(None, None)),
+        ] {
+            let mut query = DeviceQuery { sort, direction: SortDirection::Desc, ..Default::default() };
+            query.cursor = Some(query.cursor_after(&device));
+            assert_eq!(query.after().unwrap(), Some((sort.key(&device), device.id)));
+        }
+    }
+
+    #[test]
+    fn rejects_cursor_from_other_sort() {
+        let mut query = DeviceQuery::default();
+        query.cursor = Some(query.cursor_after(&device(Some("a"), None)));
+        query.sort = DeviceSort::Vendor;
+        assert!(matches!(query.after(), Err(DbError::InvalidQuery(_))));
+        query.cursor = Some("garbage".to_string());
+        assert!(matches!(query.after(), Err(DbError::InvalidQuery(_))));
+    }
+
+    #[test]
+    fn subnet_filter_requires_containment() {
+        let mut inside = device(None, None);
+        inside.ip_address = "192.0.2.10/32".parse().unwrap();
+        let mut wider = device(None, None);
+        wider.ip_address = "192.0.2.0/23".parse().unwrap();
+        let filter = DeviceFilter { subnet: Some("192.0.2.0/24".parse().unwrap()), ..Default::default() };
+
+        assert!(filter.matches(&inside));
+        assert!(!filter.matches(&wider));
+    }
+}
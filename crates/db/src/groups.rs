@@ -0,0 +1,157 @@
+//! Device groups: static groups list their members, dynamic groups select them
+//! with a [`DeviceFilter`] evaluated at query time (see `DeviceFilter::group_id`).
+
+use crate::{DbError, DeviceFilter, DeviceGroup, PgPool};
+use uuid::Uuid;
+
+/// Dynamic rules are evaluated without further lookups, so they cannot refer to a group.
+pub(crate) fn check_rules(rules: Option<&DeviceFilter>) -> Result<(), DbError> {
+    if rules.is_some_and(|rules| rules.group_id.is_some()) {
+        return Err(DbError::InvalidQuery("group rules cannot refer to another group".to_string()));
+    }
+    Ok(())
+}
+
+fn rules_to_json(rules: Option<&DeviceFilter>) -> Result<Option<serde_json::Value>, DbError> {
+    rules.map(serde_json::to_value).transpose().map_err(|e| DbError::MappingError(e.to_string()))
+}
+
+fn rules_from_json(rules: Option<serde_json::Value>) -> Result<Option<DeviceFilter>, DbError> {
+    rules.map(serde_json::from_value).transpose().map_err(|e| DbError::MappingError(e.to_string()))
+}
+
+fn duplicate_name(error: sqlx::Error) -> DbError {
+    match error.as_database_error() {
+        Some(db_error) if db_error.is_unique_violation() => {
+            DbError::InvalidQuery("a group with this name already exists".to_string())
+        }
+        _ => DbError::from(error),
+    }
+}
+
+pub async fn create_group(pool: &PgPool, group: &DeviceGroup) -> Result<DeviceGroup, DbError> {
+    check_rules(group.rules.as_ref())?;
+    let row = sqlx::query!(
+        r#"
+        INSERT INTO device_groups (name, description, rules)
+        VALUES ($1, $2, $3)
+        RETURNING id, created_at, updated_at
+        "#,
+        group.name,
+        group.description,
+        rules_to_json(group.rules.as_ref())?
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(duplicate_name)?;
+    Ok(DeviceGroup { id: row.id, created_at: row.created_at, updated_at: row.updated_at, ..group.clone() })
+}
+
+/// Updates a group. Turning a static group dynamic drops its listed members.
+pub async fn update_group(pool: &PgPool, group: &DeviceGroup) -> Result<DeviceGroup, DbError> {
+    check_rules(group.rules.as_ref())?;
+    let mut tx = pool.begin().await?;
+    let row = sqlx::query!(
+        r#"
+        UPDATE device_groups SET name = $2, description = $3, rules = $4, updated_at = NOW()
+        WHERE id = $1
+        RETURNING created_at, updated_at
+        "#,
+        group.id,
+        group.name,
+        group.description,
+        rules_to_json(group.rules.as_ref())?
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(duplicate_name)?;
+    if group.rules.is_some() {
+        sqlx::query!("DELETE FROM device_group_members WHERE group_id = $1", group.id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(DeviceGroup { created_at: row.created_at, updated_at: row.updated_at, ..group.clone() })
+}
+
+pub async fn get_group(pool: &PgPool, id: Uuid) -> Result<DeviceGroup, DbError> {
+    let row = sqlx::query!(
+        "SELECT id, name, description, rules, created_at, updated_at FROM device_groups WHERE id = $1",
+        id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(DeviceGroup {
+        id: row.id,
+        name: row.name,
+        description: row.description,
+        rules: rules_from_json(row.rules)?,
+        created_at: row.created_at,
+        updated_at: row.updated_at,
+    })
+}
+
+pub async fn list_groups(pool: &PgPool) -> Result<Vec<DeviceGroup>, DbError> {
+    let rows = sqlx::query!(
+        "SELECT id, name, description, rules, created_at, updated_at FROM device_groups ORDER BY name"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(DeviceGroup {
+                id: row.id,
+                name: row.name,
+                description: row.description,
+                rules: rules_from_json(row.rules)?,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            })
+        })
+        .collect()
+}
+
+pub async fn delete_group(pool: &PgPool, id: Uuid) -> Result<(), DbError> {
+    let result = sqlx::query!("DELETE FROM device_groups WHERE id = $1", id).execute(pool).await?;
+    if result.rows_affected() == 0 {
+        return Err(DbError::NotFound);
+    }
+    Ok(())
+}
+
+/// Adds a device to a static group; adding an existing member does nothing.
+pub async fn add_group_member(pool: &PgPool, group_id: Uuid, device_id: Uuid) -> Result<(), DbError> {
+    if get_group(pool, group_id).await?.rules.is_some() {
+        return Err(DbError::InvalidQuery("members of a dynamic group are set by its rules".to_string()));
+    }
+    let exists = sqlx::query_scalar!(r#"SELECT EXISTS (SELECT 1 FROM devices WHERE id = $1) as "exists!""#, device_id)
+        .fetch_one(pool)
+        .await?;
+    if !exists {
+        return Err(DbError::NotFound);
+    }
+    sqlx::query!(
+        "INSERT INTO device_group_members (group_id, device_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        group_id,
+        device_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn remove_group_member(pool: &PgPool, group_id: Uuid, device_id: Uuid) -> Result<(), DbError> {
+    let result = sqlx::query!(
+        "DELETE FROM device_group_members WHERE group_id = $1 AND device_id = $2",
+        group_id,
+        device_id
+    )
+    .execute(pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(DbError::NotFound);
+    }
+    Ok(())
+}
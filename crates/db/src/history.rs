@@ -0,0 +1,119 @@
+use crate::{DbError, Device, DeviceField, DeviceHistory, DeviceStatus, PgPool};
+use sqlx::PgConnection;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// An attribute that differs between the stored and the incoming device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct AttributeChange {
+    pub attribute: &'static str,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+/// Compares the tracked attributes of two versions of a device. `last_seen`
+/// and the timestamps change on every poll and are not tracked; `last_boot`
+/// only changes on a reboot.
+pub(crate) fn device_changes(old: &Device, new: &Device) -> Vec<AttributeChange> {
+    let status = |s: &Option<DeviceStatus>| s.as_ref().map(|s| s.as_str().to_string());
+    let boot = |t: Option<OffsetDateTime>| t.and_then(|t| t.format(&Rfc3339).ok());
+    DeviceField::ALL
+        .into_iter()
+        .map(|field| (field.name(), field.get(old), field.get(new)))
+        .chain([
+            ("status", status(&old.status), status(&new.status)),
+            ("location_id", old.location_id.map(|id| id.to_string()), new.location_id.map(|id| id.to_string())),
+            ("last_boot", boot(old.last_boot), boot(new.last_boot)),
+        ])
+        .filter(|(_, old_value, new_value)| old_value != new_value)
+        .map(|(attribute, old_value, new_value)| AttributeChange { attribute, old_value, new_value })
+        .collect()
+}
+
+/// Appends changes to the history of a device, inside the caller's transaction.
+pub(crate) async fn record_device_changes(
+    conn: &mut PgConnection,
+    device_id: Uuid,
+    changes: &[AttributeChange],
+    source: &str,
+) -> Result<(), DbError> {
+    for change in changes {
+        sqlx::query!(
+            r#"
+            INSERT INTO device_history (device_id, attribute, old_value, new_value, source)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            device_id,
+            change.attribute,
+            change.old_value,
+            change.new_value,
+            source
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Retrieves the change timeline of a device, newest first.
+pub async fn list_device_history(pool: &PgPool, device_id: Uuid) -> Result<Vec<DeviceHistory>, DbError> {
+    let rows = sqlx::query!(
+        r#"SELECT id, device_id, attribute, old_value, new_value, source, changed_at
+           FROM device_history WHERE device_id = $1 ORDER BY changed_at DESC, attribute"#,
+        device_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| DeviceHistory {
+            id: row.id,
+            device_id: row.device_id,
+            attribute: row.attribute,
+            old_value: row.old_value,
+            new_value: row.new_value,
+            source: row.source,
+            changed_at: row.changed_at,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diffs_tracked_attributes_only() {
+        let now = OffsetDateTime::now_utc();
+        let old = Device {
+            hostname: Some("sw1".to_string()),
+            vendor: Some("Cisco".to_string()),
+            os_version: Some("15.2(4)E".to_string()),
+            status: Some(DeviceStatus::Up),
+            last_seen: Some(now),
+            ..Device::new("192.0.2.1/32".parse().unwrap())
+        };
+        let new = Device {
+            os_version: Some("15.2(7)E".to_string()),
+            serial_number: Some("FOC1234X0AB".to_string()),
+            last_seen: Some(now + time::Duration::minutes(5)),
+            ..old.clone()
+        };
+
+        let changes = device_changes(&old, &new);
+
+        assert_eq!(
+            changes,
+            vec![
+                AttributeChange {
+                    attribute: "os_version",
+                    old_value: Some("15.2(4)E".to_string()),
+                    new_value: Some("15.2(7)E".to_string()),
+                },
+                AttributeChange { attribute: "serial_number", old_value: None, new_value: Some("FOC1234X0AB".to_string()) },
+            ]
+        );
+    }
+}
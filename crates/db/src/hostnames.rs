@@ -0,0 +1,42 @@
+//! PTR names of device and node addresses, as resolved during discovery.
+
+use crate::{DbError, IpHostname, PgPool};
+use ipnetwork::IpNetwork;
+
+/// Stores the name of `ip_address`, or forgets it when the PTR record is gone (`None`).
+pub async fn set_ip_hostname(pool: &PgPool, ip_address: IpNetwork, hostname: Option<&str>) -> Result<(), DbError> {
+    match hostname {
+        Some(hostname) => {
+            sqlx::query!(
+                r#"
+                INSERT INTO ip_hostnames (ip_address, hostname) VALUES ($1, $2)
+                ON CONFLICT (ip_address) DO UPDATE SET hostname = EXCLUDED.hostname, resolved_at = NOW()
+                "#,
+                ip_address,
+                hostname
+            )
+            .execute(pool)
+            .await?;
+        }
+        None => {
+            sqlx::query!("DELETE FROM ip_hostnames WHERE ip_address = $1", ip_address).execute(pool).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Names of the addresses inside `network`, or of all addresses, in address order.
+pub async fn list_ip_hostnames(pool: &PgPool, network: Option<IpNetwork>) -> Result<Vec<IpHostname>, DbError> {
+    let hostnames = sqlx::query_as!(
+        IpHostname,
+        r#"
+        SELECT ip_address, hostname, resolved_at FROM ip_hostnames
+        WHERE $1::inet IS NULL OR ip_address <<= $1
+        ORDER BY ip_address
+        "#,
+        network
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(hostnames)
+}
@@ -0,0 +1,268 @@
+use crate::{CustomFieldValues, DbError, Interface, MacAddress, MetadataFilter, PgPool};
+use sqlx::postgres::PgRow;
+use sqlx::types::Json;
+use sqlx::{Postgres, QueryBuilder, Row};
+use uuid::Uuid;
+
+/// Inserts a new interface or updates an existing one based on (device_id, if_index).
+/// Returns the resulting Interface record.
+pub async fn upsert_interface(pool: &PgPool, interface_data: &Interface) -> Result<Interface, DbError> {
+    let row = sqlx::query!(
+        r#"
+        INSERT INTO interfaces (
+            device_id, if_index, if_name, if_alias, if_descr, if_type,
+            mac_address, ip_address, admin_status, oper_status, speed, mtu, access_vlan, last_changed
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+        ON CONFLICT (device_id, if_index) DO UPDATE SET
+            if_name = EXCLUDED.if_name,
+            if_alias = EXCLUDED.if_alias,
+            if_descr = EXCLUDED.if_descr,
+            if_type = EXCLUDED.if_type,
+            mac_address = EXCLUDED.mac_address,
+            ip_address = COALESCE(EXCLUDED.ip_address, interfaces.ip_address),
+            admin_status = EXCLUDED.admin_status,
+            oper_status = EXCLUDED.oper_status,
+            speed = EXCLUDED.speed,
+            mtu = EXCLUDED.mtu,
+            access_vlan = COALESCE(EXCLUDED.access_vlan, interfaces.access_vlan),
+            last_changed = COALESCE(EXCLUDED.last_changed, interfaces.last_changed),
+            updated_at = NOW()
+        RETURNING
+            id, device_id, if_index, if_name, if_alias, if_descr, if_type,
+            mac_address as "mac_address: MacAddress",
+            ip_address, admin_status, oper_status, speed, mtu, access_vlan, last_changed,
+              tags, custom_fields as "custom_fields: Json<CustomFieldValues>", created_at, updated_at
+        "#,
+        interface_data.device_id,
+        interface_data.if_index,
+        interface_data.if_name,
+        interface_data.if_alias,
+        interface_data.if_descr,
+        interface_data.if_type,
+        interface_data.mac_address as _,
+        interface_data.ip_address,
+        interface_data.admin_status,
+        interface_data.oper_status,
+        interface_data.speed,
+        interface_data.mtu,
+        interface_data.access_vlan,
+        interface_data.last_changed
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(Interface {
+        id: row.id,
+        device_id: row.device_id,
+        if_index: row.if_index,
+        if_name: row.if_name,
+        if_alias: row.if_alias,
+        if_descr: row.if_descr,
+        if_type: row.if_type,
+        mac_address: row.mac_address,
+        ip_address: row.ip_address,
+        admin_status: row.admin_status,
+        oper_status: row.oper_status,
+        speed: row.speed,
+        mtu: row.mtu,
+        access_vlan: row.access_vlan,
+        last_changed: row.last_changed,
+        tags: row.tags,
+        custom_fields: row.custom_fields.0,
+        created_at: row.created_at,
+        updated_at: row.updated_at,
+    })
+}
+
+/// Retrieves all interfaces of a device, ordered by ifIndex.
+pub async fn list_interfaces(pool: &PgPool, device_id: Uuid) -> Result<Vec<Interface>, DbError> {
+    let rows = sqlx::query!(
+        r#"SELECT
+              id, device_id, if_index, if_name, if_alias, if_descr, if_type,
+              mac_address as "mac_address: MacAddress",
+              ip_address, admin_status, oper_status, speed, mtu, access_vlan, last_changed,
+              tags, custom_fields as "custom_fields: Json<CustomFieldValues>", created_at, updated_at
+           FROM interfaces WHERE device_id = $1 ORDER BY if_index"#,
+        device_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Interface {
+            id: row.id,
+            device_id: row.device_id,
+            if_index: row.if_index,
+            if_name: row.if_name,
+            if_alias: row.if_alias,
+            if_descr: row.if_descr,
+            if_type: row.if_type,
+            mac_address: row.mac_address,
+            ip_address: row.ip_address,
+            admin_status: row.admin_status,
+            oper_status: row.oper_status,
+            speed: row.speed,
+            mtu: row.mtu,
+            access_vlan: row.access_vlan,
+            last_changed: row.last_changed,
+            tags: row.tags,
+            custom_fields: row.custom_fields.0,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        })
+        .collect())
+}
+
+/// Retrieves one interface of a device by ifIndex.
+pub async fn get_interface(pool: &PgPool, device_id: Uuid, if_index: i32) -> Result<Interface, DbError> {
+    let row = sqlx::query!(
+        r#"SELECT
+              id, device_id, if_index, if_name, if_alias, if_descr, if_type,
+              mac_address as "mac_address: MacAddress",
+              ip_address, admin_status, oper_status, speed, mtu, access_vlan, last_changed,
+              tags, custom_fields as "custom_fields: Json<CustomFieldValues>", created_at, updated_at
+           FROM interfaces WHERE device_id = $1 AND if_index = $2"#,
+        device_id,
+        if_index
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(Interface {
+        id: row.id,
+        device_id: row.device_id,
+        if_index: row.if_index,
+        if_name: row.if_name,
+        if_alias: row.if_alias,
+        if_descr: row.if_descr,
+        if_type: row.if_type,
+        mac_address: row.mac_address,
+        ip_address: row.ip_address,
+        admin_status: row.admin_status,
+        oper_status: row.oper_status,
+        speed: row.speed,
+        mtu: row.mtu,
+        access_vlan: row.access_vlan,
+        last_changed: row.last_changed,
+        tags: row.tags,
+        custom_fields: row.custom_fields.0,
+        created_at: row.created_at,
+        updated_at: row.updated_at,
+    })
+}
+
+/// Retrieves the interfaces that own a MAC address, on any device.
+pub async fn find_interfaces_by_mac(pool: &PgPool, mac_address: MacAddress) -> Result<Vec<Interface>, DbError> {
+    let rows = sqlx::query!(
+        r#"SELECT
+              id, device_id, if_index, if_name, if_alias, if_descr, if_type,
+              mac_address as "mac_address: MacAddress",
+              ip_address, admin_status, oper_status, speed, mtu, access_vlan, last_changed,
+              tags, custom_fields as "custom_fields: Json<CustomFieldValues>", created_at, updated_at
+           FROM interfaces WHERE mac_address = $1 ORDER BY device_id, if_index"#,
+        mac_address as _
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Interface {
+            id: row.id,
+            device_id: row.device_id,
+            if_index: row.if_index,
+            if_name: row.if_name,
+            if_alias: row.if_alias,
+            if_descr: row.if_descr,
+            if_type: row.if_type,
+            mac_address: row.mac_address,
+            ip_address: row.ip_address,
+            admin_status: row.admin_status,
+            oper_status: row.oper_status,
+            speed: row.speed,
+            mtu: row.mtu,
+            access_vlan: row.access_vlan,
+            last_changed: row.last_changed,
+            tags: row.tags,
+            custom_fields: row.custom_fields.0,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        })
+        .collect())
+}
+
+/// Records settings changed on the device itself (e.g. by port control), leaving
+/// the fields passed as `None` untouched.
+pub async fn update_interface_settings(
+    pool: &PgPool,
+    interface_id: Uuid,
+    admin_status: Option<&str>,
+    if_alias: Option<&str>,
+    access_vlan: Option<i32>,
+) -> Result<(), DbError> {
+    let result = sqlx::query!(
+        r#"UPDATE interfaces SET
+              admin_status = COALESCE($2, admin_status),
+              if_alias = COALESCE($3, if_alias),
+              access_vlan = COALESCE($4, access_vlan),
+              updated_at = NOW()
+           WHERE id = $1"#,
+        interface_id,
+        admin_status,
+        if_alias,
+        access_vlan
+    )
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(DbError::NotFound);
+    }
+    Ok(())
+}
+
+/// Retrieves the interfaces, on any device, carrying the tags and custom field
+/// values of `filter`. An empty filter is rejected rather than listing every interface.
+pub async fn find_interfaces(pool: &PgPool, filter: &MetadataFilter) -> Result<Vec<Interface>, DbError> {
+    if filter.is_empty() {
+        return Err(DbError::InvalidQuery("filter by at least one tag or custom field".to_string()));
+    }
+    let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
+        r#"SELECT
+              id, device_id, if_index, if_name, if_alias, if_descr, if_type, mac_address,
+              ip_address, admin_status, oper_status, speed, mtu, access_vlan, last_changed,
+              tags, custom_fields, created_at, updated_at
+           FROM interfaces WHERE TRUE"#,
+    );
+    filter.push_conditions(&mut builder);
+    builder.push(" ORDER BY device_id, if_index");
+
+    let rows = builder.build().fetch_all(pool).await?;
+    rows.iter().map(interface_from_row).collect()
+}
+
+pub(crate) fn interface_from_row(row: &PgRow) -> Result<Interface, DbError> {
+    Ok(Interface {
+        id: row.try_get("id")?,
+        device_id: row.try_get("device_id")?,
+        if_index: row.try_get("if_index")?,
+        if_name: row.try_get("if_name")?,
+        if_alias: row.try_get("if_alias")?,
+        if_descr: row.try_get("if_descr")?,
+        if_type: row.try_get("if_type")?,
+        mac_address: row.try_get("mac_address")?,
+        ip_address: row.try_get("ip_address")?,
+        admin_status: row.try_get("admin_status")?,
+        oper_status: row.try_get("oper_status")?,
+        speed: row.try_get("speed")?,
+        mtu: row.try_get("mtu")?,
+        access_vlan: row.try_get("access_vlan")?,
+        last_changed: row.try_get("last_changed")?,
+        tags: row.try_get("tags")?,
+        custom_fields: row.try_get::<Json<CustomFieldValues>, _>("custom_fields")?.0,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
@@ -0,0 +1,329 @@
+//! IPAM-lite: subnets derived from interface addresses, and how much of each is in use.
+//!
+//! A subnet is every network configured on an interface, except host routes, loopback and
+//! link-local ranges, so a refresh after discovery keeps the table in line with the routers.
+//! An address counts as used when it was seen within the window: in an ARP table, as a
+//! wireless client, on an interface or as a device's management address. Addresses only seen
+//! before the window are free again and listed as stale.
+
+use crate::{ArpEntry, DbError, MacAddress, PgPool, Subnet};
+use ipnetwork::IpNetwork;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+/// The latest sighting of an address from one source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IpSighting {
+    pub ip_address: IpAddr,
+    pub mac_address: Option<MacAddress>,
+    pub last_seen: OffsetDateTime,
+}
+
+/// What a refresh changed in the subnets table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct SubnetRefresh {
+    pub added: u64,
+    pub removed: u64, // No longer on any interface
+    pub total: u64,
+}
+
+/// Consecutive free addresses, both ends included.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IpRange {
+    pub first: IpAddr,
+    pub last: IpAddr,
+    pub size: u128,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StaleIp {
+    pub ip_address: IpAddr,
+    pub mac_address: Option<MacAddress>,
+    pub last_seen: OffsetDateTime,
+}
+
+/// Usage of one subnet over a window.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SubnetUsage {
+    #[serde(flatten)]
+    pub subnet: Subnet,
+    pub size: u128, // Assignable addresses; IPv4 networks up to /30 lose the network and broadcast address
+    pub used: u128, // Seen within the window
+    pub free: u128,
+    pub utilisation: f64, // Percent of `size` used, two decimals
+    pub free_ranges: Vec<IpRange>,
+    pub stale_ips: Vec<StaleIp>, // Seen before the window but not since; counted as free
+}
+
+/// Start of a usage window of `window_days` ending at `now`.
+pub fn usage_window_start(window_days: u32, now: OffsetDateTime) -> OffsetDateTime {
+    now - Duration::days(i64::from(window_days))
+}
+
+/// The networks of interface addresses that hold more than one host.
+pub(crate) fn interface_subnets<'a>(addresses: impl IntoIterator<Item = &'a IpNetwork>) -> BTreeSet<IpNetwork> {
+    addresses
+        .into_iter()
+        .filter(|address| {
+            let host_prefix = if address.is_ipv4() { 32 } else { 128 };
+            let ip = address.ip();
+            let link_local = match ip {
+                IpAddr::V4(ip) => ip.is_link_local(),
+                IpAddr::V6(ip) => ip.segments()[0] & 0xffc0 == 0xfe80,
+            };
+            (1..host_prefix).contains(&address.prefix()) && !ip.is_loopback() && !ip.is_multicast() && !link_local
+        })
+        .filter_map(|address| IpNetwork::new(address.network(), address.prefix()).ok())
+        .collect()
+}
+
+fn to_number(ip: IpAddr) -> u128 {
+    match ip {
+        IpAddr::V4(ip) => u128::from(u32::from(ip)),
+        IpAddr::V6(ip) => u128::from(ip),
+    }
+}
+
+fn to_address(number: u128, ipv4: bool) -> IpAddr {
+    match ipv4 {
+        true => IpAddr::V4(Ipv4Addr::from(number as u32)),
+        false => IpAddr::V6(Ipv6Addr::from(number)),
+    }
+}
+
+// First and last assignable address of a network, as numbers
+fn assignable_bounds(network: &IpNetwork) -> (u128, u128) {
+    let bits = if network.is_ipv4() { 32 } else { 128 };
+    let host_bits = bits - u32::from(network.prefix());
+    let first = to_number(network.network());
+    let last = first | u128::MAX.checked_shr(128 - host_bits).unwrap_or(0);
+    match network.is_ipv4() && host_bits >= 2 {
+        true => (first + 1, last - 1),
+        false => (first, last),
+    }
+}
+
+/// Works out the usage of `subnet` from the sightings of addresses in it.
+pub fn subnet_usage(subnet: Subnet, sightings: &[IpSighting], window_start: OffsetDateTime) -> SubnetUsage {
+    let ipv4 = subnet.network.is_ipv4();
+    let (first, last) = assignable_bounds(&subnet.network);
+    let mut latest: BTreeMap<u128, &IpSighting> = BTreeMap::new();
+    for sighting in sightings.iter().filter(|s| s.ip_address.is_ipv4() == ipv4) {
+        let number = to_number(sighting.ip_address);
+        if !(first..=last).contains(&number) {
+            continue;
+        }
+        let newer = latest.get(&number).is_none_or(|seen| {
+            (sighting.last_seen, sighting.mac_address.is_some()) > (seen.last_seen, seen.mac_address.is_some())
+        });
+        if newer {
+            latest.insert(number, sighting);
+        }
+    }
+
+    let used: Vec<u128> = latest.iter().filter(|(_, s)| s.last_seen >= window_start).map(|(&n, _)| n).collect();
+    let mut free_ranges = Vec::new();
+    let mut next_free = Some(first);
+    for &number in &used {
+        if let Some(start) = next_free.filter(|&start| start < number) {
+            free_ranges.push((start, number - 1));
+        }
+        next_free = number.checked_add(1);
+    }
+    if let Some(start) = next_free.filter(|&start| start <= last) {
+        free_ranges.push((start, last));
+    }
+
+    let size = (last - first).saturating_add(1);
+    let used_count = used.len() as u128;
+    SubnetUsage {
+        subnet,
+        size,
+        used: used_count,
+        free: size - used_count,
+        utilisation: (used_count as f64 / size as f64 * 10_000.0).round() / 100.0,
+        free_ranges: free_ranges
+            .into_iter()
+            .map(|(start, end)| IpRange {
+                first: to_address(start, ipv4),
+                last: to_address(end, ipv4),
+                size: (end - start).saturating_add(1),
+            })
+            .collect(),
+        stale_ips: latest
+            .values()
+            .filter(|s| s.last_seen < window_start)
+            .map(|s| StaleIp { ip_address: s.ip_address, mac_address: s.mac_address, last_seen: s.last_seen })
+            .collect(),
+    }
+}
+
+/// Stores the ARP table collected from a router. Entries of other devices are untouched;
+/// a binding that moved to another MAC starts over.
+pub async fn upsert_arp_entries(pool: &PgPool, device_id: Uuid, entries: &[ArpEntry]) -> Result<(), DbError> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let now = OffsetDateTime::now_utc();
+    let ip_addresses: Vec<IpNetwork> = entries.iter().map(|e| e.ip_address).collect();
+    let mac_addresses: Vec<MacAddress> = entries.iter().map(|e| e.mac_address).collect();
+    let interface_ids: Vec<Option<Uuid>> = entries.iter().map(|e| e.interface_id).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO arp_entries (device_id, ip_address, mac_address, interface_id, first_seen, last_seen)
+        SELECT DISTINCT ON (ip_address) $1, ip_address, mac_address, interface_id, $5, $5
+        FROM UNNEST($2::inet[], $3::macaddr[], $4::uuid[]) AS e(ip_address, mac_address, interface_id)
+        ON CONFLICT (device_id, ip_address) DO UPDATE SET
+            first_seen = CASE WHEN arp_entries.mac_address = EXCLUDED.mac_address
+                              THEN arp_entries.first_seen ELSE EXCLUDED.first_seen END,
+            mac_address = EXCLUDED.mac_address,
+            interface_id = EXCLUDED.interface_id,
+            last_seen = EXCLUDED.last_seen
+        "#,
+        device_id,
+        &ip_addresses,
+        &mac_addresses as &[MacAddress],
+        &interface_ids as &[Option<Uuid>],
+        now
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Brings the subnets table in line with the interface addresses.
+pub async fn refresh_subnets(pool: &PgPool) -> Result<SubnetRefresh, DbError> {
+    let mut tx = pool.begin().await?;
+    let addresses: Vec<IpNetwork> =
+        sqlx::query_scalar!(r#"SELECT ip_address as "ip_address!" FROM interfaces WHERE ip_address IS NOT NULL"#)
+            .fetch_all(&mut *tx)
+            .await?;
+    let networks: Vec<IpNetwork> = interface_subnets(&addresses).into_iter().collect();
+    let existing: HashSet<IpNetwork> =
+        sqlx::query_scalar!("SELECT network FROM subnets").fetch_all(&mut *tx).await?.into_iter().collect();
+
+    let removed = sqlx::query!("DELETE FROM subnets WHERE network <> ALL($1::inet[])", &networks)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    sqlx::query!(
+        r#"
+        INSERT INTO subnets (network)
+        SELECT UNNEST($1::inet[])::cidr
+        ON CONFLICT (network) DO UPDATE SET last_seen = NOW()
+        "#,
+        &networks
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(SubnetRefresh {
+        added: networks.iter().filter(|n| !existing.contains(n)).count() as u64,
+        removed,
+        total: networks.len() as u64,
+    })
+}
+
+pub async fn list_subnets(pool: &PgPool) -> Result<Vec<Subnet>, DbError> {
+    let subnets = sqlx::query_as!(Subnet, "SELECT id, network, first_seen, last_seen FROM subnets ORDER BY network")
+        .fetch_all(pool)
+        .await?;
+    Ok(subnets)
+}
+
+pub async fn get_subnet(pool: &PgPool, id: Uuid) -> Result<Subnet, DbError> {
+    sqlx::query_as!(Subnet, "SELECT id, network, first_seen, last_seen FROM subnets WHERE id = $1", id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(DbError::NotFound)
+}
+
+/// Every sighting of an address inside `network`, from all sources.
+pub async fn list_ip_sightings(pool: &PgPool, network: IpNetwork) -> Result<Vec<IpSighting>, DbError> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT a.ip_address as "ip_address!", a.mac_address as "mac_address: MacAddress", a.last_seen as "last_seen!"
+        FROM arp_entries a WHERE a.ip_address <<= $1::inet
+        UNION ALL
+        SELECT w.ip_address, n.mac_address, w.last_seen
+        FROM node_wireless w JOIN nodes n ON n.id = w.node_id WHERE w.ip_address <<= $1::inet
+        UNION ALL
+        SELECT i.ip_address, i.mac_address, i.updated_at FROM interfaces i WHERE i.ip_address <<= $1::inet
+        UNION ALL
+        SELECT d.ip_address, NULL, COALESCE(d.last_seen, d.updated_at) FROM devices d WHERE d.ip_address <<= $1::inet
+        "#,
+        network
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| IpSighting { ip_address: row.ip_address.ip(), mac_address: row.mac_address, last_seen: row.last_seen })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subnet(network: &str) -> Subnet {
+        let now = OffsetDateTime::now_utc();
+        Subnet { id: Uuid::nil(), network: network.parse().unwrap(), first_seen: now, last_seen: now }
+    }
+
+    fn sighting(ip: &str, last_seen: OffsetDateTime) -> IpSighting {
+        IpSighting { ip_address: ip.parse().unwrap(), mac_address: None, last_seen }
+    }
+
+    #[test]
+    fn derives_subnets_from_interface_addresses() {
+        let addresses: Vec<IpNetwork> = [
+            "10.1.20.1/24",
+            "10.1.20.2/24",
+            "192.0.2.1/32",
+            "127.0.0.1/8",
+            "fe80::1/64",
+            "2001:db8:1::1/64",
+            "198.51.100.0/31",
+        ]
+        .iter()
+        .map(|a| a.parse().unwrap())
+        .collect();
+
+        let subnets: Vec<String> = interface_subnets(&addresses).iter().map(|n| n.to_string()).collect();
+
+        assert_eq!(subnets, ["10.1.20.0/24", "198.51.100.0/31", "2001:db8:1::/64"]);
+    }
+
+    #[test]
+    fn counts_used_free_and_stale_addresses() {
+        let now = OffsetDateTime::now_utc();
+        let window_start = usage_window_start(30, now);
+        let old = now - Duration::days(60);
+        let sightings = [
+            sighting("10.1.20.1", now),
+            sighting("10.1.20.5", old),
+            sighting("10.1.20.5", now - Duration::days(90)),
+            sighting("10.1.20.6", now),
+            sighting("10.1.20.0", now), // Network address
+            sighting("10.1.21.9", now), // Other subnet
+        ];
+
+        let usage = subnet_usage(subnet("10.1.20.0/29"), &sightings, window_start);
+
+        assert_eq!((usage.size, usage.used, usage.free), (6, 2, 4));
+        assert_eq!(usage.utilisation, 33.33);
+        let ranges: Vec<(String, String)> =
+            usage.free_ranges.iter().map(|r| (r.first.to_string(), r.last.to_string())).collect();
+        assert_eq!(ranges, [("10.1.20.2".to_string(), "10.1.20.5".to_string())]);
+        assert_eq!(usage.stale_ips.len(), 1);
+        assert_eq!(usage.stale_ips[0].last_seen, old);
+
+        let v6 = subnet_usage(subnet("2001:db8:1::/64"), &[sighting("2001:db8:1::1", now)], window_start);
+        assert_eq!(v6.size, 1 << 64);
+        assert_eq!(v6.free_ranges.len(), 2);
+    }
+}
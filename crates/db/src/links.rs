@@ -0,0 +1,693 @@
+//! Links between devices for the topology map.
+//!
+//! A refresh rebuilds every discovered link from the stored LLDP/CDP neighbors and the MAC
+//! tables. A neighbor is matched to a device by management address, sysName/hostname or
+//! chassis MAC, and to a port by ifName, ifDescr or MAC. MAC tables give a link where two
+//! switch ports see each other's MACs and no switch seen behind one is also seen behind the
+//! other; a port seeing a single switch that reports nothing back gives a weaker link. Each
+//! connection is stored once, lower device id first, from its most trusted source.
+//! Manual links are only created and deleted by users: a refresh never changes them, and
+//! drops whatever it finds for a connection they already describe.
+
+use crate::{DbError, Device, DeviceNeighbor, Interface, LinkSource, MacAddress, NeighborProtocol, PgPool, TopologyLink};
+use ipnetwork::IpNetwork;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// Confidence of a neighbor link with both ports known.
+pub const NEIGHBOR_CONFIDENCE: i16 = 100;
+/// Confidence of a neighbor link missing a port, e.g. an LLDP port ID matching no interface.
+pub const NEIGHBOR_PARTIAL_CONFIDENCE: i16 = 80;
+/// Confidence of a link from two ports seeing each other's MACs.
+pub const FDB_MUTUAL_CONFIDENCE: i16 = 70;
+/// Confidence of a link from a port seeing one switch that does not see this one.
+pub const FDB_ONE_SIDED_CONFIDENCE: i16 = 40;
+
+/// Advisory lock key serializing refreshes, so two never sync the same links at once.
+const REFRESH_LOCK: i64 = 0x6e64_6c69_6e6b; // "ndlink"
+
+/// What a refresh changed in the discovered links.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct LinkRefresh {
+    pub added: u64,
+    pub removed: u64, // No longer found
+    pub total: u64,   // Discovered links after the refresh; manual ones are not counted
+}
+
+/// A device as far as matching neighbors goes.
+pub(crate) struct LinkDevice {
+    pub id: Uuid,
+    pub ip_address: IpNetwork,
+    pub sys_name: Option<String>,
+    pub hostname: Option<String>,
+}
+
+/// An interface as far as matching neighbors goes.
+pub(crate) struct LinkPort {
+    pub id: Uuid,
+    pub device_id: Uuid,
+    pub if_name: Option<String>,
+    pub if_descr: Option<String>,
+    pub mac_address: Option<MacAddress>,
+    pub ip_address: Option<IpNetwork>,
+}
+
+impl From<&Device> for LinkDevice {
+    fn from(device: &Device) -> Self {
+        Self {
+            id: device.id,
+            ip_address: device.ip_address,
+            sys_name: device.sys_name.clone(),
+            hostname: device.hostname.clone(),
+        }
+    }
+}
+
+impl From<&Interface> for LinkPort {
+    fn from(interface: &Interface) -> Self {
+        Self {
+            id: interface.id,
+            device_id: interface.device_id,
+            if_name: interface.if_name.clone(),
+            if_descr: interface.if_descr.clone(),
+            mac_address: interface.mac_address,
+            ip_address: interface.ip_address,
+        }
+    }
+}
+
+/// An active node on a switch port, i.e. one MAC table entry.
+pub(crate) struct PortSighting {
+    pub device_id: Uuid,
+    pub interface_id: Uuid,
+    pub mac_address: MacAddress,
+}
+
+// Identity of a stored discovered link, to keep `first_seen` across refreshes
+pub(crate) type LinkKey = (LinkSource, Uuid, Option<Uuid>, Uuid, Option<Uuid>);
+
+pub(crate) fn link_key(link: &TopologyLink) -> LinkKey {
+    (link.source, link.device_id, link.interface_id, link.remote_device_id, link.remote_interface_id)
+}
+
+/// Turns `link` so the lower device id comes first.
+pub(crate) fn oriented(link: TopologyLink) -> TopologyLink {
+    match link.device_id > link.remote_device_id {
+        true => TopologyLink {
+            device_id: link.remote_device_id,
+            interface_id: link.remote_interface_id,
+            remote_device_id: link.device_id,
+            remote_interface_id: link.interface_id,
+            ..link
+        },
+        false => link,
+    }
+}
+
+/// Checks a manual link before it is stored; its endpoints are checked by the backend.
+pub(crate) fn check_manual_link(link: &TopologyLink) -> Result<(), DbError> {
+    if link.device_id == link.remote_device_id {
+        return Err(DbError::InvalidQuery("a link needs two different devices".to_string()));
+    }
+    if !(0..=100).contains(&link.confidence) {
+        return Err(DbError::InvalidQuery(format!("confidence {} is not between 0 and 100", link.confidence)));
+    }
+    Ok(())
+}
+
+// Whether two oriented links can describe the same connection: same devices, and ports
+// equal wherever both are known
+fn same_connection(a: &TopologyLink, b: &TopologyLink) -> bool {
+    let compatible = |x: Option<Uuid>, y: Option<Uuid>| x.is_none() || y.is_none() || x == y;
+    a.device_id == b.device_id
+        && a.remote_device_id == b.remote_device_id
+        && compatible(a.interface_id, b.interface_id)
+        && compatible(a.remote_interface_id, b.remote_interface_id)
+}
+
+fn uses_port(link: &TopologyLink, device_id: Uuid, interface_id: Uuid) -> bool {
+    (link.device_id, link.interface_id) == (device_id, Some(interface_id))
+        || (link.remote_device_id, link.remote_interface_id) == (device_id, Some(interface_id))
+}
+
+fn host_label(name: &str) -> String {
+    name.trim().trim_end_matches('.').split('.').next().unwrap_or_default().to_ascii_lowercase()
+}
+
+fn new_link(
+    (device_id, interface_id): (Uuid, Option<Uuid>),
+    (remote_device_id, remote_interface_id): (Uuid, Option<Uuid>),
+    source: LinkSource,
+    confidence: i16,
+    now: OffsetDateTime,
+) -> TopologyLink {
+    oriented(TopologyLink {
+        id: Uuid::nil(),
+        device_id,
+        interface_id,
+        remote_device_id,
+        remote_interface_id,
+        source,
+        confidence,
+        first_seen: now,
+        last_seen: now,
+    })
+}
+
+/// Works out the discovered links. `manual` are the stored manual links, which take
+/// precedence over anything found.
+pub(crate) fn infer_links(
+    devices: &[LinkDevice],
+    ports: &[LinkPort],
+    neighbors: &[DeviceNeighbor],
+    sightings: &[PortSighting],
+    manual: &[TopologyLink],
+) -> Vec<TopologyLink> {
+    let now = OffsetDateTime::now_utc();
+    let mut owners: HashMap<MacAddress, HashSet<Uuid>> = HashMap::new();
+    for port in ports {
+        if let Some(mac) = port.mac_address {
+            owners.entry(mac).or_default().insert(port.device_id);
+        }
+    }
+    // MACs shared by several devices, e.g. virtual router MACs, say nothing about either
+    let mac_owner = |mac: &MacAddress| owners.get(mac).filter(|o| o.len() == 1).and_then(|o| o.iter().next().copied());
+
+    let mut candidates = Vec::new();
+    for neighbor in neighbors {
+        let Some(remote) = neighbor_device(neighbor, devices, ports, &mac_owner).filter(|&id| id != neighbor.device_id)
+        else {
+            continue;
+        };
+        let remote_port = neighbor.remote_port.as_deref().and_then(|port| find_port(ports, remote, port));
+        let confidence = match neighbor.interface_id.is_some() && remote_port.is_some() {
+            true => NEIGHBOR_CONFIDENCE,
+            false => NEIGHBOR_PARTIAL_CONFIDENCE,
+        };
+        let source = LinkSource::from(neighbor.protocol);
+        candidates.push(new_link((neighbor.device_id, neighbor.interface_id), (remote, remote_port), source, confidence, now));
+    }
+    candidates.extend(fdb_links(sightings, &mac_owner, now));
+    candidates.sort_by_key(|link| (link.source, std::cmp::Reverse(link.confidence)));
+
+    let mut links: Vec<TopologyLink> = Vec::new();
+    for candidate in candidates {
+        if manual.iter().any(|link| same_connection(link, &candidate)) {
+            continue;
+        }
+        if let Some(link) = links.iter_mut().find(|link| same_connection(link, &candidate)) {
+            // Both ends of a neighbor relationship report it; each may know the other's port
+            link.interface_id = link.interface_id.or(candidate.interface_id);
+            link.remote_interface_id = link.remote_interface_id.or(candidate.remote_interface_id);
+            let neighbor_link = matches!(link.source, LinkSource::Lldp | LinkSource::Cdp);
+            if neighbor_link && link.interface_id.is_some() && link.remote_interface_id.is_some() {
+                link.confidence = NEIGHBOR_CONFIDENCE;
+            }
+            continue;
+        }
+        // A port with a neighbor or manual link is not second-guessed by MAC tables
+        let taken = |device_id: Uuid, interface_id: Option<Uuid>| {
+            interface_id.is_some_and(|id| manual.iter().chain(&links).any(|link| uses_port(link, device_id, id)))
+        };
+        if candidate.source == LinkSource::Fdb
+            && (taken(candidate.device_id, candidate.interface_id)
+                || taken(candidate.remote_device_id, candidate.remote_interface_id))
+        {
+            continue;
+        }
+        links.push(candidate);
+    }
+    links
+}
+
+// The device a neighbor announces: by management address, then by name, then by chassis MAC
+fn neighbor_device(
+    neighbor: &DeviceNeighbor,
+    devices: &[LinkDevice],
+    ports: &[LinkPort],
+    mac_owner: &impl Fn(&MacAddress) -> Option<Uuid>,
+) -> Option<Uuid> {
+    if let Some(address) = neighbor.remote_address.map(|a| a.ip()) {
+        let by_device = devices.iter().find(|d| d.ip_address.ip() == address).map(|d| d.id);
+        let by_port = || ports.iter().find(|p| p.ip_address.is_some_and(|ip| ip.ip() == address)).map(|p| p.device_id);
+        if let Some(id) = by_device.or_else(by_port) {
+            return Some(id);
+        }
+    }
+    if let Some(label) = neighbor.remote_sys_name.as_deref().map(host_label).filter(|l| !l.is_empty()) {
+        let named: Vec<Uuid> = devices
+            .iter()
+            .filter(|d| [&d.sys_name, &d.hostname].into_iter().flatten().any(|name| host_label(name) == label))
+            .map(|d| d.id)
+            .collect();
+        if let [id] = named.as_slice() {
+            return Some(*id);
+        }
+    }
+    neighbor.remote_chassis_id.as_deref().and_then(|id| id.parse::<MacAddress>().ok()).and_then(|mac| mac_owner(&mac))
+}
+
+// The port of `device_id` a neighbor names, by ifName/ifDescr or else by a MAC only it has
+fn find_port(ports: &[LinkPort], device_id: Uuid, port: &str) -> Option<Uuid> {
+    let device_ports = || ports.iter().filter(|p| p.device_id == device_id);
+    let by_name = device_ports()
+        .find(|p| [&p.if_name, &p.if_descr].into_iter().flatten().any(|name| name.eq_ignore_ascii_case(port.trim())));
+    if let Some(found) = by_name {
+        return Some(found.id);
+    }
+    let mac = port.parse::<MacAddress>().ok()?;
+    let by_mac: Vec<Uuid> = device_ports().filter(|p| p.mac_address == Some(mac)).map(|p| p.id).collect();
+    match by_mac.as_slice() {
+        [id] => Some(*id),
+        _ => None,
+    }
+}
+
+fn fdb_links(
+    sightings: &[PortSighting],
+    mac_owner: &impl Fn(&MacAddress) -> Option<Uuid>,
+    now: OffsetDateTime,
+) -> Vec<TopologyLink> {
+    // Other devices seen behind each switch port
+    let mut behind: BTreeMap<(Uuid, Uuid), BTreeSet<Uuid>> = BTreeMap::new();
+    for sighting in sightings {
+        if let Some(owner) = mac_owner(&sighting.mac_address).filter(|&owner| owner != sighting.device_id) {
+            behind.entry((sighting.device_id, sighting.interface_id)).or_default().insert(owner);
+        }
+    }
+
+    let mut links = Vec::new();
+    for (&(device_id, interface_id), seen) in &behind {
+        for &remote in seen {
+            let facing: Vec<(&(Uuid, Uuid), &BTreeSet<Uuid>)> =
+                behind.iter().filter(|((id, _), remote_seen)| *id == remote && remote_seen.contains(&device_id)).collect();
+            match facing.as_slice() {
+                [] if seen.len() == 1 => links.push(new_link(
+                    (device_id, Some(interface_id)),
+                    (remote, None),
+                    LinkSource::Fdb,
+                    FDB_ONE_SIDED_CONFIDENCE,
+                    now,
+                )),
+                // Found from both ends; kept from the lower device id's side only
+                [((_, remote_interface_id), remote_seen)] if device_id < remote && seen.is_disjoint(remote_seen) => {
+                    links.push(new_link(
+                        (device_id, Some(interface_id)),
+                        (remote, Some(*remote_interface_id)),
+                        LinkSource::Fdb,
+                        FDB_MUTUAL_CONFIDENCE,
+                        now,
+                    ))
+                }
+                _ => {}
+            }
+        }
+    }
+    links
+}
+
+/// Replaces the stored neighbors of one protocol on a device with a fresh collection.
+pub async fn replace_device_neighbors(
+    pool: &PgPool,
+    device_id: Uuid,
+    protocol: NeighborProtocol,
+    neighbors: &[DeviceNeighbor],
+) -> Result<(), DbError> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!("DELETE FROM device_neighbors WHERE device_id = $1 AND protocol = $2", device_id, protocol.as_str())
+        .execute(&mut *tx)
+        .await?;
+    for neighbor in neighbors {
+        sqlx::query!(
+            r#"
+            INSERT INTO device_neighbors (
+                device_id, interface_id, protocol, remote_chassis_id, remote_port, remote_sys_name, remote_address
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            device_id,
+            neighbor.interface_id,
+            protocol.as_str(),
+            neighbor.remote_chassis_id,
+            neighbor.remote_port,
+            neighbor.remote_sys_name,
+            neighbor.remote_address
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Retrieves the LLDP/CDP neighbors of a device, LLDP first.
+pub async fn list_device_neighbors(pool: &PgPool, device_id: Uuid) -> Result<Vec<DeviceNeighbor>, DbError> {
+    let rows = sqlx::query!(
+        r#"SELECT
+              id, device_id, interface_id, protocol, remote_chassis_id, remote_port,
+              remote_sys_name, remote_address, last_seen
+           FROM device_neighbors WHERE device_id = $1
+           ORDER BY protocol DESC, remote_sys_name, remote_port"#,
+        device_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut neighbors = Vec::with_capacity(rows.len());
+    for row in rows {
+        neighbors.push(DeviceNeighbor {
+            id: row.id,
+            device_id: row.device_id,
+            interface_id: row.interface_id,
+            protocol: NeighborProtocol::try_from(row.protocol).map_err(DbError::MappingError)?,
+            remote_chassis_id: row.remote_chassis_id,
+            remote_port: row.remote_port,
+            remote_sys_name: row.remote_sys_name,
+            remote_address: row.remote_address,
+            last_seen: row.last_seen,
+        });
+    }
+    Ok(neighbors)
+}
+
+/// Rebuilds the discovered links from the stored neighbors and MAC tables.
+pub async fn refresh_topology_links(pool: &PgPool) -> Result<LinkRefresh, DbError> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!("SELECT pg_advisory_xact_lock($1)", REFRESH_LOCK).execute(&mut *tx).await?;
+    let devices = sqlx::query_as!(LinkDevice, "SELECT id, ip_address, sys_name, hostname FROM devices")
+        .fetch_all(&mut *tx)
+        .await?;
+    let ports = sqlx::query_as!(
+        LinkPort,
+        r#"SELECT id, device_id, if_name, if_descr, mac_address as "mac_address: MacAddress", ip_address FROM interfaces"#
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let neighbors = sqlx::query!(
+        r#"SELECT
+              id, device_id, interface_id, protocol, remote_chassis_id, remote_port,
+              remote_sys_name, remote_address, last_seen
+           FROM device_neighbors"#
+    )
+    .fetch_all(&mut *tx)
+    .await?
+    .into_iter()
+    .map(|row| {
+        Ok(DeviceNeighbor {
+            id: row.id,
+            device_id: row.device_id,
+            interface_id: row.interface_id,
+            protocol: NeighborProtocol::try_from(row.protocol).map_err(DbError::MappingError)?,
+            remote_chassis_id: row.remote_chassis_id,
+            remote_port: row.remote_port,
+            remote_sys_name: row.remote_sys_name,
+            remote_address: row.remote_address,
+            last_seen: row.last_seen,
+        })
+    })
+    .collect::<Result<Vec<_>, DbError>>()?;
+    // Only MACs some other device owns matter
+    let sightings = sqlx::query_as!(
+        PortSighting,
+        r#"
+        SELECT n.device_id, n.interface_id as "interface_id!", n.mac_address as "mac_address: MacAddress"
+        FROM nodes n
+        WHERE n.active AND n.interface_id IS NOT NULL
+          AND EXISTS (SELECT 1 FROM interfaces i WHERE i.mac_address = n.mac_address AND i.device_id <> n.device_id)
+        "#
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let stored = fetch_links(&mut tx, None).await?;
+    let manual: Vec<TopologyLink> = stored.iter().filter(|l| l.source == LinkSource::Manual).cloned().collect();
+    let mut existing: HashMap<LinkKey, Uuid> =
+        stored.iter().filter(|l| l.source != LinkSource::Manual).map(|l| (link_key(l), l.id)).collect();
+
+    let links = infer_links(&devices, &ports, &neighbors, &sightings, &manual);
+    let mut added = 0;
+    for link in &links {
+        match existing.remove(&link_key(link)) {
+            Some(id) => {
+                sqlx::query!(
+                    "UPDATE topology_links SET confidence = $2, last_seen = NOW() WHERE id = $1",
+                    id,
+                    link.confidence
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            None => {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO topology_links (
+                        device_id, interface_id, remote_device_id, remote_interface_id, source, confidence
+                    )
+                    VALUES ($1, $2, $3, $4, $5, $6)
+                    "#,
+                    link.device_id,
+                    link.interface_id,
+                    link.remote_device_id,
+                    link.remote_interface_id,
+                    link.source.as_str(),
+                    link.confidence
+                )
+                .execute(&mut *tx)
+                .await?;
+                added += 1;
+            }
+        }
+    }
+    let stale: Vec<Uuid> = existing.into_values().collect();
+    let removed = sqlx::query!("DELETE FROM topology_links WHERE id = ANY($1) AND source <> 'manual'", &stale)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    tx.commit().await?;
+    Ok(LinkRefresh { added, removed, total: links.len() as u64 })
+}
+
+async fn fetch_links(
+    conn: &mut sqlx::PgConnection,
+    device_id: Option<Uuid>,
+) -> Result<Vec<TopologyLink>, DbError> {
+    let rows = sqlx::query!(
+        r#"SELECT
+              id, device_id, interface_id, remote_device_id, remote_interface_id,
+              source, confidence, first_seen, last_seen
+           FROM topology_links
+           WHERE $1::uuid IS NULL OR device_id = $1 OR remote_device_id = $1
+           ORDER BY device_id, remote_device_id, source, interface_id"#,
+        device_id
+    )
+    .fetch_all(conn)
+    .await?;
+
+    let mut links = Vec::with_capacity(rows.len());
+    for row in rows {
+        links.push(TopologyLink {
+            id: row.id,
+            device_id: row.device_id,
+            interface_id: row.interface_id,
+            remote_device_id: row.remote_device_id,
+            remote_interface_id: row.remote_interface_id,
+            source: LinkSource::try_from(row.source).map_err(DbError::MappingError)?,
+            confidence: row.confidence,
+            first_seen: row.first_seen,
+            last_seen: row.last_seen,
+        });
+    }
+    Ok(links)
+}
+
+/// Links of all devices, or of one device at either end.
+pub async fn list_topology_links(pool: &PgPool, device_id: Option<Uuid>) -> Result<Vec<TopologyLink>, DbError> {
+    let mut conn = pool.acquire().await?;
+    fetch_links(&mut conn, device_id).await
+}
+
+/// Stores a link drawn by a user. Each end's interface, if given, must be on its device.
+pub async fn create_topology_link(pool: &PgPool, link: &TopologyLink) -> Result<TopologyLink, DbError> {
+    check_manual_link(link)?;
+    let link = oriented(link.clone());
+    for (device_id, interface_id) in [(link.device_id, link.interface_id), (link.remote_device_id, link.remote_interface_id)] {
+        let found = match interface_id {
+            Some(interface_id) => sqlx::query_scalar!(
+                "SELECT EXISTS (SELECT 1 FROM interfaces WHERE id = $1 AND device_id = $2)",
+                interface_id,
+                device_id
+            )
+            .fetch_one(pool)
+            .await?
+            .unwrap_or(false),
+            None => sqlx::query_scalar!("SELECT EXISTS (SELECT 1 FROM devices WHERE id = $1)", device_id)
+                .fetch_one(pool)
+                .await?
+                .unwrap_or(false),
+        };
+        if !found {
+            return Err(DbError::InvalidQuery(endpoint_error(device_id, interface_id)));
+        }
+    }
+
+    let row = sqlx::query!(
+        r#"
+        INSERT INTO topology_links (device_id, interface_id, remote_device_id, remote_interface_id, source, confidence)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, first_seen, last_seen
+        "#,
+        link.device_id,
+        link.interface_id,
+        link.remote_device_id,
+        link.remote_interface_id,
+        LinkSource::Manual.as_str(),
+        link.confidence
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| match e.as_database_error() {
+        Some(db_error) if db_error.is_unique_violation() => DbError::InvalidQuery(duplicate_link_error()),
+        _ => DbError::from(e),
+    })?;
+    Ok(TopologyLink { id: row.id, source: LinkSource::Manual, first_seen: row.first_seen, last_seen: row.last_seen, ..link })
+}
+
+pub(crate) fn endpoint_error(device_id: Uuid, interface_id: Option<Uuid>) -> String {
+    match interface_id {
+        Some(interface_id) => format!("interface {} is not on device {}", interface_id, device_id),
+        None => format!("device {} does not exist", device_id),
+    }
+}
+
+pub(crate) fn duplicate_link_error() -> String {
+    "a manual link between these endpoints already exists".to_string()
+}
+
+/// The MAC in a bridge ID, after its 4 hex digits of priority.
+pub(crate) fn bridge_mac(bridge_id: &str) -> Option<MacAddress> {
+    bridge_id.get(4..).filter(|mac| mac.len() == 12)?.parse().ok()
+}
+
+pub(crate) fn not_manual_error() -> DbError {
+    DbError::InvalidQuery("only manual links can be deleted; discovered links follow the network".to_string())
+}
+
+/// Deletes a manual link. Discovered links cannot be deleted, as the next refresh would
+/// bring them back.
+pub async fn delete_topology_link(pool: &PgPool, id: Uuid) -> Result<(), DbError> {
+    let source = sqlx::query_scalar!("SELECT source FROM topology_links WHERE id = $1", id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(DbError::NotFound)?;
+    if source != LinkSource::Manual.as_str() {
+        return Err(not_manual_error());
+    }
+    sqlx::query!("DELETE FROM topology_links WHERE id = $1", id).execute(pool).await?;
+    Ok(())
+}
+
+/// Whether the interface connects its device to another network device: it has an LLDP/CDP
+/// neighbor or a link, is a spanning tree root or alternate port, or is a designated port
+/// with another bridge's MAC active behind it, as on the root bridge, which has no root port.
+pub async fn is_uplink_port(pool: &PgPool, interface_id: Uuid) -> Result<bool, DbError> {
+    let row = sqlx::query!(
+        r#"SELECT (
+              EXISTS (SELECT 1 FROM device_neighbors WHERE interface_id = $1)
+              OR EXISTS (SELECT 1 FROM topology_links WHERE interface_id = $1 OR remote_interface_id = $1)
+              OR EXISTS (SELECT 1 FROM stp_ports WHERE interface_id = $1 AND role IN ('root', 'alternate'))
+              OR EXISTS (
+                SELECT 1 FROM stp_ports sp
+                JOIN interfaces i ON i.id = sp.interface_id
+                JOIN nodes n ON n.interface_id = sp.interface_id AND n.active
+                JOIN stp_instances b ON b.instance = sp.instance AND b.device_id <> i.device_id
+                WHERE sp.interface_id = $1 AND sp.role = 'designated'
+                  AND REPLACE(n.mac_address::text, ':', '') = RIGHT(b.bridge_id, 12))
+           ) as "is_uplink!""#,
+        interface_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(row.is_uplink)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(ip: &str, sys_name: &str) -> LinkDevice {
+        LinkDevice { id: Uuid::new_v4(), ip_address: ip.parse().unwrap(), sys_name: Some(sys_name.to_string()), hostname: None }
+    }
+
+    fn port(device: &LinkDevice, name: &str, mac: &str) -> LinkPort {
+        LinkPort {
+            id: Uuid::new_v4(),
+            device_id: device.id,
+            if_name: Some(name.to_string()),
+            if_descr: None,
+            mac_address: Some(mac.parse().unwrap()),
+            ip_address: None,
+        }
+    }
+
+    fn sighting(port: &LinkPort, mac: &str) -> PortSighting {
+        PortSighting { device_id: port.device_id, interface_id: port.id, mac_address: mac.parse().unwrap() }
+    }
+
+    #[test]
+    fn builds_links_from_neighbors_and_mac_tables() {
+        // core -- dist -- access in a chain, plus an edge switch only core's MAC table knows
+        let core = device("10.0.0.1/32", "core");
+        let dist = device("10.0.0.2/32", "dist");
+        let access = device("10.0.0.3/32", "access");
+        let edge = device("10.0.0.4/32", "edge");
+        let core_down = port(&core, "Gi0/1", "02:00:00:00:01:01");
+        let core_edge = port(&core, "Gi0/2", "02:00:00:00:01:02");
+        let dist_up = port(&dist, "Gi0/1", "02:00:00:00:02:01");
+        let dist_down = port(&dist, "Gi0/2", "02:00:00:00:02:02");
+        let access_up = port(&access, "Gi0/1", "02:00:00:00:03:01");
+        let edge_up = port(&edge, "Gi0/1", "02:00:00:00:04:01");
+        let ports = [core_down, core_edge, dist_up, dist_down, access_up, edge_up];
+        let [core_down, core_edge, dist_up, dist_down, access_up, _] = &ports;
+
+        // dist reports core over LLDP by name; core only knows its port ID
+        let now = OffsetDateTime::now_utc();
+        let neighbor = DeviceNeighbor {
+            id: Uuid::nil(),
+            device_id: dist.id,
+            interface_id: Some(dist_up.id),
+            protocol: NeighborProtocol::Lldp,
+            remote_chassis_id: Some("02:00:00:00:01:00".to_string()),
+            remote_port: Some("gi0/1".to_string()),
+            remote_sys_name: Some("core.example.net".to_string()),
+            remote_address: None,
+            last_seen: now,
+        };
+        let sightings = [
+            sighting(core_down, "02:00:00:00:02:01"),
+            sighting(core_down, "02:00:00:00:03:01"),
+            sighting(core_edge, "02:00:00:00:04:01"),
+            sighting(dist_up, "02:00:00:00:01:01"),
+            sighting(dist_down, "02:00:00:00:03:01"),
+            sighting(access_up, "02:00:00:00:01:01"),
+            sighting(access_up, "02:00:00:00:02:02"),
+        ];
+        let devices = [core, dist, access, edge];
+        let [core, dist, access, edge] = &devices;
+        let pair = |a: &LinkDevice, b: &LinkDevice| (a.id.min(b.id), a.id.max(b.id));
+
+        let links = infer_links(&devices, &ports, std::slice::from_ref(&neighbor), &sightings, &[]);
+
+        let found: Vec<_> = links.iter().map(|l| ((l.device_id, l.remote_device_id), l.source, l.confidence)).collect();
+        assert_eq!(found.len(), 3, "{found:?}");
+        assert!(found.contains(&(pair(core, dist), LinkSource::Lldp, NEIGHBOR_CONFIDENCE)));
+        assert!(found.contains(&(pair(dist, access), LinkSource::Fdb, FDB_MUTUAL_CONFIDENCE)));
+        assert!(found.contains(&(pair(core, edge), LinkSource::Fdb, FDB_ONE_SIDED_CONFIDENCE)));
+
+        // A manual link for core -- dist wins over LLDP and the MAC tables
+        let manual = new_link((core.id, None), (dist.id, None), LinkSource::Manual, 100, now);
+        let links = infer_links(&devices, &ports, std::slice::from_ref(&neighbor), &sightings, &[manual]);
+        assert!(links.iter().all(|l| (l.device_id, l.remote_device_id) != pair(core, dist)));
+        assert_eq!(links.len(), 2);
+    }
+}
@@ -0,0 +1,415 @@
+//! Location hierarchy (site > building > room > rack) and the rules placing devices in it.
+//!
+//! Every upsert places a device by the first matching [`LocationRule`], unless it
+//! was placed manually: a manual placement is recorded as the `location_id` field
+//! source and kept until a manual source changes or clears it.
+
+use crate::merge::{self, FieldSources};
+use crate::{history, is_manual_source, DbError, Device, Location, LocationKind, LocationRule, PgPool};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+/// Field source key of a device's location.
+pub const LOCATION_FIELD: &str = "location_id";
+
+/// Source recorded in the history when rules move a device.
+pub(crate) const RULES_SOURCE: &str = "location_rules";
+
+/// Checks that `parent` is the kind of location `kind` must sit in.
+pub(crate) fn check_parent(kind: LocationKind, parent: Option<&Location>) -> Result<(), DbError> {
+    match (kind.parent_kind(), parent.map(|p| p.kind)) {
+        (None, None) => Ok(()),
+        (Some(wanted), Some(actual)) if wanted == actual => Ok(()),
+        (None, Some(_)) => Err(DbError::InvalidQuery("a site cannot have a parent".to_string())),
+        (Some(wanted), _) => Err(DbError::InvalidQuery(format!(
+            "a {} must be inside a {}",
+            kind.as_str(),
+            wanted.as_str()
+        ))),
+    }
+}
+
+impl LocationRule {
+    /// Whether all set conditions of the rule match `device`.
+    pub fn matches(&self, device: &Device) -> bool {
+        let pattern = self.sys_location_pattern.as_ref().map(|p| p.to_lowercase());
+        pattern.is_none_or(|pattern| {
+            device.sys_location.as_ref().is_some_and(|location| location.to_lowercase().contains(&pattern))
+        }) && self.subnet.is_none_or(|subnet| {
+            subnet.contains(device.ip_address.ip()) && device.ip_address.prefix() >= subnet.prefix()
+        })
+    }
+}
+
+pub(crate) fn check_rule(rule: &LocationRule) -> Result<(), DbError> {
+    if rule.sys_location_pattern.as_deref().is_some_and(str::is_empty) {
+        return Err(DbError::InvalidQuery("sys_location_pattern cannot be empty".to_string()));
+    }
+    if rule.sys_location_pattern.is_none() && rule.subnet.is_none() {
+        return Err(DbError::InvalidQuery("a location rule needs a sys_location_pattern or a subnet".to_string()));
+    }
+    Ok(())
+}
+
+/// Location of `device` after applying `rules`, which must be in priority order.
+/// A manual placement is kept; otherwise a device matching no rule has no location.
+pub(crate) fn resolve_location(rules: &[LocationRule], sources: &FieldSources, device: &Device) -> Option<Uuid> {
+    if sources.get(LOCATION_FIELD).is_some_and(|source| is_manual_source(source)) {
+        return device.location_id;
+    }
+    rules.iter().find(|rule| rule.matches(device)).map(|rule| rule.location_id)
+}
+
+/// The location `root` and everything below it, for backends that filter in memory.
+pub(crate) fn subtree(locations: &[Location], root: Uuid) -> Vec<Uuid> {
+    let mut subtree = vec![root];
+    let mut next = 0;
+    while next < subtree.len() {
+        let parent = subtree[next];
+        subtree.extend(locations.iter().filter(|l| l.parent_id == Some(parent)).map(|l| l.id));
+        next += 1;
+    }
+    subtree
+}
+
+/// Loads all location rules in priority order, inside the caller's transaction.
+pub(crate) async fn load_location_rules(conn: &mut PgConnection) -> Result<Vec<LocationRule>, DbError> {
+    let rows = sqlx::query!(
+        r#"SELECT id, location_id, priority, sys_location_pattern, subnet, created_at
+           FROM location_rules ORDER BY priority, created_at, id"#
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| LocationRule {
+            id: row.id,
+            location_id: row.location_id,
+            priority: row.priority,
+            sys_location_pattern: row.sys_location_pattern,
+            subnet: row.subnet,
+            created_at: row.created_at,
+        })
+        .collect())
+}
+
+/// Retrieves the location rules in the order they are tried.
+pub async fn list_location_rules(pool: &PgPool) -> Result<Vec<LocationRule>, DbError> {
+    let mut conn = pool.acquire().await?;
+    load_location_rules(&mut conn).await
+}
+
+/// Adds a rule. Devices are re-placed on their next upsert or by [`apply_location_rules`].
+pub async fn create_location_rule(pool: &PgPool, rule: &LocationRule) -> Result<LocationRule, DbError> {
+    check_rule(rule)?;
+    get_location(pool, rule.location_id).await.map_err(unknown_location)?;
+    let row = sqlx::query!(
+        r#"
+        INSERT INTO location_rules (location_id, priority, sys_location_pattern, subnet)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id, created_at
+        "#,
+        rule.location_id,
+        rule.priority,
+        rule.sys_location_pattern,
+        rule.subnet
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(LocationRule { id: row.id, created_at: row.created_at, ..rule.clone() })
+}
+
+pub async fn delete_location_rule(pool: &PgPool, id: Uuid) -> Result<(), DbError> {
+    let result = sqlx::query!("DELETE FROM location_rules WHERE id = $1", id).execute(pool).await?;
+    if result.rows_affected() == 0 {
+        return Err(DbError::NotFound);
+    }
+    Ok(())
+}
+
+/// Re-places every device not placed manually, e.g. after the rules changed.
+/// Returns the number of devices moved.
+pub async fn apply_location_rules(pool: &PgPool) -> Result<u64, DbError> {
+    let mut tx = pool.begin().await?;
+    let rules = load_location_rules(&mut tx).await?;
+    let devices = sqlx::query!(
+        r#"SELECT d.id, d.ip_address, d.sys_location, d.location_id, s.source as "source?"
+           FROM devices d
+           LEFT JOIN device_field_sources s ON s.device_id = d.id AND s.field = $1
+           FOR UPDATE OF d"#,
+        LOCATION_FIELD
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut moved = 0;
+    for row in devices {
+        let sources: FieldSources = row.source.map(|s| (LOCATION_FIELD.to_string(), s)).into_iter().collect();
+        // Only the fields the rules look at are needed
+        let device = Device {
+            id: row.id,
+            sys_location: row.sys_location,
+            location_id: row.location_id,
+            ..Device::new(row.ip_address)
+        };
+        let location_id = resolve_location(&rules, &sources, &device);
+        if location_id == device.location_id {
+            continue;
+        }
+        sqlx::query!("UPDATE devices SET location_id = $2, updated_at = NOW() WHERE id = $1", device.id, location_id)
+            .execute(&mut *tx)
+            .await?;
+        let changes = history::device_changes(&device, &Device { location_id, ..device.clone() });
+        history::record_device_changes(&mut tx, device.id, &changes, RULES_SOURCE).await?;
+        moved += 1;
+    }
+    tx.commit().await?;
+    Ok(moved)
+}
+
+/// Places a device in a location, or clears the placement (`None`). A manual
+/// placement is kept by rules and discovery; clearing it hands the device back
+/// to the rules. A non-manual `source` cannot change a manual placement.
+pub async fn set_device_location(
+    pool: &PgPool,
+    device_id: Uuid,
+    location_id: Option<Uuid>,
+    source: &str,
+) -> Result<Device, DbError> {
+    if let Some(location_id) = location_id {
+        get_location(pool, location_id).await.map_err(unknown_location)?;
+    }
+    let mut tx = pool.begin().await?;
+    let existing = crate::lock_device(&mut tx, Some(device_id), None).await?.ok_or(DbError::NotFound)?;
+    let mut sources = merge::load_field_sources(&mut tx, device_id).await?;
+    if !is_manual_source(source) && sources.get(LOCATION_FIELD).is_some_and(|s| is_manual_source(s)) {
+        return Ok(existing);
+    }
+
+    let location_id = match location_id {
+        Some(location_id) => {
+            sqlx::query!(
+                r#"
+                INSERT INTO device_field_sources (device_id, field, source)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (device_id, field) DO UPDATE SET
+                    source = EXCLUDED.source,
+                    updated_at = NOW()
+                "#,
+                device_id,
+                LOCATION_FIELD,
+                source
+            )
+            .execute(&mut *tx)
+            .await?;
+            Some(location_id)
+        }
+        None => {
+            sqlx::query!(
+                "DELETE FROM device_field_sources WHERE device_id = $1 AND field = $2",
+                device_id,
+                LOCATION_FIELD
+            )
+            .execute(&mut *tx)
+            .await?;
+            sources.remove(LOCATION_FIELD);
+            let rules = load_location_rules(&mut tx).await?;
+            resolve_location(&rules, &sources, &existing)
+        }
+    };
+
+    let updated_at = sqlx::query_scalar!(
+        "UPDATE devices SET location_id = $2, updated_at = NOW() WHERE id = $1 RETURNING updated_at",
+        device_id,
+        location_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let updated = Device { location_id, updated_at, ..existing.clone() };
+    let changes = history::device_changes(&existing, &updated);
+    history::record_device_changes(&mut tx, device_id, &changes, source).await?;
+    tx.commit().await?;
+    Ok(updated)
+}
+
+pub(crate) fn unknown_location(error: DbError) -> DbError {
+    match error {
+        DbError::NotFound => DbError::InvalidQuery("unknown location".to_string()),
+        other => other,
+    }
+}
+
+fn duplicate_name(error: sqlx::Error) -> DbError {
+    match error.as_database_error() {
+        Some(db_error) if db_error.is_unique_violation() => {
+            DbError::InvalidQuery("a location with this name already exists here".to_string())
+        }
+        _ => DbError::from(error),
+    }
+}
+
+/// Adds a location below `parent_id`, which must be of the next kind up.
+pub async fn create_location(pool: &PgPool, location: &Location) -> Result<Location, DbError> {
+    let parent = match location.parent_id {
+        Some(parent_id) => Some(get_location(pool, parent_id).await.map_err(unknown_location)?),
+        None => None,
+    };
+    check_parent(location.kind, parent.as_ref())?;
+
+    let row = sqlx::query!(
+        r#"
+        INSERT INTO locations (parent_id, kind, name, description)
+        VALUES ($1, $2::location_kind, $3, $4)
+        RETURNING id, created_at, updated_at
+        "#,
+        location.parent_id,
+        location.kind.as_str() as &str,
+        location.name,
+        location.description
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(duplicate_name)?;
+    Ok(Location { id: row.id, created_at: row.created_at, updated_at: row.updated_at, ..location.clone() })
+}
+
+/// Renames, describes or moves a location; its kind cannot change.
+pub async fn update_location(pool: &PgPool, location: &Location) -> Result<Location, DbError> {
+    let current = get_location(pool, location.id).await?;
+    if current.kind != location.kind {
+        return Err(DbError::InvalidQuery("the kind of a location cannot change".to_string()));
+    }
+    let parent = match location.parent_id {
+        Some(parent_id) => Some(get_location(pool, parent_id).await.map_err(unknown_location)?),
+        None => None,
+    };
+    check_parent(location.kind, parent.as_ref())?;
+
+    let updated_at = sqlx::query_scalar!(
+        r#"
+        UPDATE locations SET parent_id = $2, name = $3, description = $4, updated_at = NOW()
+        WHERE id = $1
+        RETURNING updated_at
+        "#,
+        location.id,
+        location.parent_id,
+        location.name,
+        location.description
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(duplicate_name)?;
+    Ok(Location { created_at: current.created_at, updated_at, ..location.clone() })
+}
+
+pub async fn get_location(pool: &PgPool, id: Uuid) -> Result<Location, DbError> {
+    let row = sqlx::query!(
+        r#"SELECT id, parent_id, kind::text as "kind!", name, description, created_at, updated_at
+           FROM locations WHERE id = $1"#,
+        id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(Location {
+        id: row.id,
+        parent_id: row.parent_id,
+        kind: LocationKind::try_from(row.kind).map_err(DbError::MappingError)?,
+        name: row.name,
+        description: row.description,
+        created_at: row.created_at,
+        updated_at: row.updated_at,
+    })
+}
+
+/// Retrieves all locations, parents before their children.
+pub async fn list_locations(pool: &PgPool) -> Result<Vec<Location>, DbError> {
+    let rows = sqlx::query!(
+        r#"SELECT id, parent_id, kind::text as "kind!", name, description, created_at, updated_at
+           FROM locations ORDER BY kind, name"#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(Location {
+                id: row.id,
+                parent_id: row.parent_id,
+                kind: LocationKind::try_from(row.kind).map_err(DbError::MappingError)?,
+                name: row.name,
+                description: row.description,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            })
+        })
+        .collect()
+}
+
+/// Deletes a location with everything below it and its rules; devices in it lose their location.
+pub async fn delete_location(pool: &PgPool, id: Uuid) -> Result<(), DbError> {
+    let result = sqlx::query!("DELETE FROM locations WHERE id = $1", id).execute(pool).await?;
+    if result.rows_affected() == 0 {
+        return Err(DbError::NotFound);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::OffsetDateTime;
+
+    fn rule(priority: i32, pattern: Option<&str>, subnet: Option<&str>) -> LocationRule {
+        LocationRule {
+            id: Uuid::new_v4(),
+            location_id: Uuid::new_v4(),
+            priority,
+            sys_location_pattern: pattern.map(str::to_string),
+            subnet: subnet.map(|s| s.parse().unwrap()),
+            created_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    #[test]
+    fn first_matching_rule_places_device() {
+        let device = Device {
+            sys_location: Some("Berlin DC1, Room 2.14".to_string()),
+            ..Device::new("10.20.3.4/32".parse().unwrap())
+        };
+        let rules = [
+            rule(10, Some("hamburg"), None),
+            rule(20, Some("berlin dc1"), Some("10.30.0.0/16")),
+            rule(30, Some("BERLIN"), Some("10.20.0.0/16")),
+            rule(40, None, Some("10.0.0.0/8")),
+        ];
+
+        assert_eq!(resolve_location(&rules, &FieldSources::new(), &device), Some(rules[2].location_id));
+
+        // A manual placement wins over the rules
+        let placed = Device { location_id: Some(Uuid::nil()), ..device.clone() };
+        let sources = FieldSources::from([(LOCATION_FIELD.to_string(), "manual:alice".to_string())]);
+        assert_eq!(resolve_location(&rules, &sources, &placed), Some(Uuid::nil()));
+    }
+
+    #[test]
+    fn locations_nest_by_kind() {
+        let now = OffsetDateTime::now_utc();
+        let site = Location {
+            id: Uuid::new_v4(),
+            parent_id: None,
+            kind: LocationKind::Site,
+            name: "BER1".to_string(),
+            description: None,
+            created_at: now,
+            updated_at: now,
+        };
+
+        assert!(check_parent(LocationKind::Site, None).is_ok());
+        assert!(check_parent(LocationKind::Building, Some(&site)).is_ok());
+        assert!(check_parent(LocationKind::Rack, Some(&site)).is_err());
+        assert!(check_parent(LocationKind::Room, None).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+//! MAC address type, stored as Postgres MACADDR.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::postgres::{PgArgumentBuffer, PgHasArrayType, PgTypeInfo, PgValueFormat, PgValueRef};
+use sqlx::{Decode, Encode, Postgres, Type};
+use std::fmt;
+use std::str::FromStr;
+
+/// A 48-bit MAC address. Parses colon (`00:1a:2b:3c:4d:5e`), dash
+/// (`00-1A-2B-3C-4D-5E`), Cisco dotted (`001a.2b3c.4d5e`) and bare hex
+/// (`001a2b3c4d5e`) forms; displays and serialises as lowercase colon form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MacAddress([u8; 6]);
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid MAC address '{0}'")]
+pub struct InvalidMacAddress(pub String);
+
+impl MacAddress {
+    pub const fn new(octets: [u8; 6]) -> Self {
+        Self(octets)
+    }
+
+    /// Reads a MAC from exactly six bytes, e.g. an SNMP PhysAddress.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        Some(Self(bytes.try_into().ok()?))
+    }
+
+    pub fn octets(&self) -> [u8; 6] {
+        self.0
+    }
+
+    /// Lowercase hex digits without separators, e.g. `001a2b3c4d5e`.
+    pub fn hex(&self) -> String {
+        self.0.iter().map(|o| format!("{:02x}", o)).collect()
+    }
+
+    /// Group address (I/G bit set), e.g. `01:00:5e:..` for IPv4 multicast.
+    pub fn is_multicast(&self) -> bool {
+        self.0[0] & 0x01 != 0
+    }
+
+    /// Assigned locally (U/L bit set) rather than from an IEEE registry, as by
+    /// hypervisors or by clients randomising their address.
+    pub fn is_locally_administered(&self) -> bool {
+        self.0[0] & 0x02 != 0
+    }
+}
+
+impl FromStr for MacAddress {
+    type Err = InvalidMacAddress;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidMacAddress(s.to_string());
+        let trimmed = s.trim();
+        let groups: Vec<&str> = trimmed.split([':', '-', '.']).collect();
+        // from_str_radix takes a leading sign, so "+1" would otherwise parse as 01
+        if !groups.iter().all(|g| g.bytes().all(|b| b.is_ascii_hexdigit())) {
+            return Err(invalid());
+        }
+        // Separated forms: six 1-2 digit groups (colon/dash) or three 4 digit groups (dotted)
+        let hex: String = match groups.len() {
+            6 if groups.iter().all(|g| (1..=2).contains(&g.len())) => {
+                groups.iter().map(|g| format!("{:0>2}", g)).collect()
+            }
+            3 if groups.iter().all(|g| g.len() == 4) => groups.concat(),
+            1 if trimmed.len() == 12 => trimmed.to_string(),
+            _ => return Err(invalid()),
+        };
+        let mut octets = [0u8; 6];
+        for (i, octet) in octets.iter_mut().enumerate() {
+            let pair = hex.get(i * 2..i * 2 + 2).ok_or_else(invalid)?;
+            *octet = u8::from_str_radix(pair, 16).map_err(|_| invalid())?;
+        }
+        Ok(Self(octets))
+    }
+}
+
+impl fmt::Display for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(f, "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}", a, b, c, d, e, g)
+    }
+}
+
+impl Serialize for MacAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for MacAddress {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl Type<Postgres> for MacAddress {
+    fn type_info() -> PgTypeInfo {
+        PgTypeInfo::with_name("macaddr")
+    }
+}
+
+impl PgHasArrayType for MacAddress {
+    fn array_type_info() -> PgTypeInfo {
+        PgTypeInfo::with_name("_macaddr")
+    }
+}
+
+impl Encode<'_, Postgres> for MacAddress {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> IsNull {
+        buf.extend_from_slice(&self.0);
+        IsNull::No
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for MacAddress {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        match value.format() {
+            PgValueFormat::Binary => {
+                Self::from_bytes(value.as_bytes()?).ok_or_else(|| "macaddr must be 6 bytes".into())
+            }
+            PgValueFormat::Text => Ok(value.as_str()?.parse()?),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_common_formats() {
+        let expected = MacAddress::new([0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e]);
+        for input in ["00:1a:2b:3c:4d:5e", "00-1A-2B-3C-4D-5E", "001a.2b3c.4d5e", "001A2B3C4D5E", "0:1a:2b:3c:4d:5e"] {
+            assert_eq!(input.parse::<MacAddress>(), Ok(expected), "{}", input);
+        }
+        for input in ["", "00:1a:2b:3c:4d", "001a.2b3c.4d5", "00:1a:2b:3c:4d:5g", "001a2b3c4d5e00"] {
+            assert!(input.parse::<MacAddress>().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn rejects_signed_groups() {
+        for input in ["+0:1a:2b:3c:4d:5e", "00:+a:2b:3c:4d:5e", "+01a.2b3c.4d5e", "+01a2b3c4d5e"] {
+            assert!(input.parse::<MacAddress>().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn serialises_as_lowercase_colon_form() {
+        let mac: MacAddress = "001A.2B3C.4D5E".parse().unwrap();
+        assert_eq!(serde_json::to_string(&mac).unwrap(), r#""00:1a:2b:3c:4d:5e""#);
+        assert_eq!(serde_json::from_str::<MacAddress>(r#""00-1a-2b-3c-4d-5e""#).unwrap(), mac);
+    }
+}
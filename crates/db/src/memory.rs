@@ -0,0 +1,1473 @@
+//! In-memory repository for tests.
+//!
+//! Mirrors the conflict keys and update rules of the Postgres queries closely
+//! enough for callers to be tested against it; it is not meant for production use.
+
+use crate::{
+    is_manual_source, DeviceNeighbor, LinkRefresh, LinkRepository, LinkSource, NeighborProtocol, TopologyLink, AllowedMac, AllowlistRepository, Anomaly, ArpEntry, HostnameRepository, IpHostname, IpSighting, IpamRepository, Subnet, SubnetRefresh, AnomalyFilter, AnomalyReport, AnomalyRepository, CustomField, CustomFieldObject, CustomFieldRepository, CustomFieldValues, Device, DbError, DeviceField, DeviceFilter, DeviceGroup, DeviceHistory, DevicePage, DeviceQuery, DeviceRepository, DeviceStatus, GroupRepository, Interface, InterfacePoe, InterfaceRepository, Location,
+    LocationRepository, LocationRule, MacAddress, MacVendor, MacViolation, MacViolationFilter, MetadataFilter, MetadataUpdate, Node, NodeRepository, PortChange, RetentionReport, RetentionRepository, RoutingNeighbor, RoutingProtocol, SearchHit, SearchQuery, SearchRepository, StpInstance, StpPort, StpRoot, TopologyRepository, VendorRepository, WirelessAp,
+};
+use crate::allowlist;
+use crate::anomalies::{self, IpClaim, NodeMove};
+use crate::bulk;
+use crate::device_query;
+use crate::groups;
+use crate::ipam;
+use crate::links::{self, LinkDevice, LinkPort, PortSighting};
+use crate::locations::{self, LOCATION_FIELD};
+use crate::metadata;
+use crate::retention::{self, RETENTION_SOURCE};
+use crate::search;
+use crate::history::{self, AttributeChange};
+use crate::merge::{self, FieldSources, SourceUpdate};
+use async_trait::async_trait;
+use ipnetwork::IpNetwork;
+use nd_core::{AnomalySettings, RetentionSettings};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[derive(Debug, Default)]
+struct MemoryState {
+    devices: Vec<Device>,
+    device_history: Vec<DeviceHistory>,
+    field_sources: HashMap<Uuid, FieldSources>,
+    wireless_aps: Vec<WirelessAp>,
+    interfaces: Vec<Interface>,
+    interface_poe: Vec<InterfacePoe>,
+    port_changes: Vec<PortChange>,
+    nodes: Vec<Node>,
+    node_moves: Vec<NodeMove>,
+    ip_since: HashMap<Uuid, OffsetDateTime>, // node_id -> when its client took the current address
+    anomalies: Vec<Anomaly>,
+    routing_neighbors: Vec<RoutingNeighbor>,
+    stp_instances: Vec<StpInstance>,
+    stp_ports: Vec<StpPort>,
+    locations: Vec<Location>,
+    location_rules: Vec<LocationRule>,
+    groups: Vec<DeviceGroup>,
+    group_members: Vec<(Uuid, Uuid)>, // (group_id, device_id)
+    custom_fields: Vec<CustomField>,
+    allowed_macs: Vec<AllowedMac>,
+    mac_violations: Vec<MacViolation>,
+    mac_vendors: Vec<MacVendor>,
+    arp_entries: Vec<ArpEntry>,
+    subnets: Vec<Subnet>,
+    ip_hostnames: Vec<IpHostname>,
+    device_neighbors: Vec<DeviceNeighbor>,
+    topology_links: Vec<TopologyLink>,
+}
+
+impl MemoryState {
+    // Field sources and history written alongside a device update
+    fn record(
+        &mut self,
+        device_id: Uuid,
+        source_updates: &[SourceUpdate],
+        changes: &[AttributeChange],
+        source: &str,
+        now: OffsetDateTime,
+    ) {
+        merge::update_sources(self.field_sources.entry(device_id).or_default(), source_updates, source);
+        self.device_history.extend(changes.iter().map(|change| DeviceHistory {
+            id: Uuid::new_v4(),
+            device_id,
+            attribute: change.attribute.to_string(),
+            old_value: change.old_value.clone(),
+            new_value: change.new_value.clone(),
+            source: source.to_string(),
+            changed_at: now,
+        }));
+    }
+
+    // Location of a device per the rules, unless placed manually
+    fn place(&self, device: &Device) -> Option<Uuid> {
+        let mut rules = self.location_rules.clone();
+        rules.sort_by_key(|r| (r.priority, r.created_at, r.id));
+        let sources = self.field_sources.get(&device.id).cloned().unwrap_or_default();
+        locations::resolve_location(&rules, &sources, device)
+    }
+
+    fn location(&self, id: Uuid) -> Result<&Location, DbError> {
+        self.locations.iter().find(|l| l.id == id).ok_or(DbError::NotFound)
+    }
+
+    fn location_subtree(&self, root: Uuid) -> Vec<Uuid> {
+        locations::subtree(&self.locations, root)
+    }
+
+    fn check_location(&self, location: &Location) -> Result<(), DbError> {
+        let parent = match location.parent_id {
+            Some(parent_id) => Some(
+                self.location(parent_id).map_err(|_| DbError::InvalidQuery("unknown location".to_string()))?,
+            ),
+            None => None,
+        };
+        locations::check_parent(location.kind, parent)?;
+        if self
+            .locations
+            .iter()
+            .any(|l| l.id != location.id && l.parent_id == location.parent_id && l.name == location.name)
+        {
+            return Err(DbError::InvalidQuery("a location with this name already exists here".to_string()));
+        }
+        Ok(())
+    }
+
+    fn check_group_name(&self, group: &DeviceGroup) -> Result<(), DbError> {
+        if self.groups.iter().any(|g| g.id != group.id && g.name == group.name) {
+            return Err(DbError::InvalidQuery("a group with this name already exists".to_string()));
+        }
+        Ok(())
+    }
+
+    // Filter conditions, including the location subtree; groups are resolved by the caller
+    fn matches(&self, filter: &DeviceFilter, device: &Device) -> bool {
+        filter.matches(device)
+            && filter.location_id.is_none_or(|root| {
+                device.location_id.is_some_and(|location| self.location_subtree(root).contains(&location))
+            })
+    }
+
+    fn interface_id(&self, device_id: Uuid, if_index: i32) -> Option<Uuid> {
+        self.interfaces
+            .iter()
+            .find(|i| i.device_id == device_id && i.if_index == if_index)
+            .map(|i| i.id)
+    }
+
+    fn interface_ids_of(&self, device_id: Uuid) -> Vec<Uuid> {
+        self.interfaces.iter().filter(|i| i.device_id == device_id).map(|i| i.id).collect()
+    }
+
+    fn upsert_poe(&mut self, device_id: Uuid, poe: &InterfacePoe) -> bool {
+        let Some(interface_id) = self.interface_id(device_id, poe.if_index) else {
+            return false;
+        };
+        let now = OffsetDateTime::now_utc();
+        match self.interface_poe.iter_mut().find(|p| p.interface_id == interface_id) {
+            Some(existing) => {
+                *existing =
+                    InterfacePoe { interface_id, last_seen: now, created_at: existing.created_at, updated_at: now, ..poe.clone() };
+            }
+            None => self.interface_poe.push(InterfacePoe {
+                interface_id,
+                last_seen: now,
+                created_at: now,
+                updated_at: now,
+                ..poe.clone()
+            }),
+        }
+        true
+    }
+}
+
+/// A [`Repository`](crate::Repository) holding all data in memory.
+#[derive(Debug, Default)]
+pub struct MemoryRepository {
+    state: Mutex<MemoryState>,
+}
+
+impl MemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn state(&self) -> MutexGuard<'_, MemoryState> {
+        // A panicking test must not poison the repository for the others
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl DeviceRepository for MemoryRepository {
+    async fn upsert_device(&self, device: &Device, source: &str) -> Result<Device, DbError> {
+        let mut state = self.state();
+        let now = OffsetDateTime::now_utc();
+        let position = state.devices.iter().position(|d| d.ip_address == device.ip_address);
+        let (stored, source_updates, changes) = match position {
+            Some(position) => {
+                let existing = state.devices[position].clone();
+                let sources = state.field_sources.get(&existing.id).cloned().unwrap_or_default();
+                let (merged, source_updates) = merge::merge_device(&existing, &sources, device, source);
+                let merged = Device { location_id: state.place(&merged), updated_at: now, ..merged };
+                let changes = history::device_changes(&existing, &merged);
+                state.devices[position] = merged.clone();
+                (merged, source_updates, changes)
+            }
+            None => {
+                let new = Device {
+                    id: Uuid::new_v4(),
+                    tags: Vec::new(),
+                    custom_fields: CustomFieldValues::new(),
+                    created_at: now,
+                    updated_at: now,
+                    ..device.clone()
+                };
+                let new = Device { location_id: state.place(&new), ..new };
+                state.devices.push(new.clone());
+                (new, merge::initial_sources(device), Vec::new())
+            }
+        };
+        state.record(stored.id, &source_updates, &changes, source, now);
+        Ok(stored)
+    }
+
+    async fn upsert_devices(&self, devices: &[Device], source: &str) -> Result<Vec<Device>, DbError> {
+        let mut stored = Vec::new();
+        for device in bulk::dedup_last(devices, |d| d.ip_address) {
+            stored.push(self.upsert_device(device, source).await?);
+        }
+        Ok(stored)
+    }
+
+    async fn set_device_fields(
+        &self,
+        device_id: Uuid,
+        values: &[(DeviceField, Option<String>)],
+        source: &str,
+    ) -> Result<Device, DbError> {
+        self.update_device(device_id, values, None, source).await
+    }
+
+    async fn set_device_status(&self, device_id: Uuid, status: DeviceStatus, source: &str) -> Result<Device, DbError> {
+        self.update_device(device_id, &[], Some(status), source).await
+    }
+
+    async fn update_device(
+        &self,
+        device_id: Uuid,
+        values: &[(DeviceField, Option<String>)],
+        status: Option<DeviceStatus>,
+        source: &str,
+    ) -> Result<Device, DbError> {
+        let mut state = self.state();
+        let now = OffsetDateTime::now_utc();
+        let position = state.devices.iter().position(|d| d.id == device_id).ok_or(DbError::NotFound)?;
+        let existing = state.devices[position].clone();
+        let sources = state.field_sources.get(&device_id).cloned().unwrap_or_default();
+        let (mut updated, source_updates) = merge::apply_field_values(&existing, &sources, values, source)?;
+        if let Some(hostname) = &updated.hostname
+            && state.devices.iter().any(|d| d.id != device_id && d.hostname.as_ref() == Some(hostname))
+        {
+            return Err(DbError::InvalidQuery(format!("hostname '{}' is already used by another device", hostname)));
+        }
+        updated.status = merge::apply_status(&existing, status, source);
+        let updated = Device { location_id: state.place(&updated), updated_at: now, ..updated };
+        let changes = history::device_changes(&existing, &updated);
+        state.devices[position] = updated.clone();
+        state.record(device_id, &source_updates, &changes, source, now);
+        Ok(updated)
+    }
+
+    async fn set_device_metadata(&self, device_id: Uuid, update: &MetadataUpdate) -> Result<Device, DbError> {
+        let mut state = self.state();
+        let position = state.devices.iter().position(|d| d.id == device_id).ok_or(DbError::NotFound)?;
+        let device = &state.devices[position];
+        let (tags, custom_fields) =
+            metadata::apply_update(&state.custom_fields, CustomFieldObject::Device, &device.tags, &device.custom_fields, update)?;
+        let updated = Device { tags, custom_fields, updated_at: OffsetDateTime::now_utc(), ..device.clone() };
+        state.devices[position] = updated.clone();
+        Ok(updated)
+    }
+
+    async fn get_device_field_sources(&self, device_id: Uuid) -> Result<FieldSources, DbError> {
+        Ok(self.state().field_sources.get(&device_id).cloned().unwrap_or_default())
+    }
+
+    async fn list_device_history(&self, device_id: Uuid) -> Result<Vec<DeviceHistory>, DbError> {
+        let mut history: Vec<DeviceHistory> =
+            self.state().device_history.iter().filter(|h| h.device_id == device_id).cloned().collect();
+        history.sort_by(|a, b| b.changed_at.cmp(&a.changed_at).then_with(|| a.attribute.cmp(&b.attribute)));
+        Ok(history)
+    }
+
+    async fn get_device(&self, id: Uuid) -> Result<Device, DbError> {
+        self.state().devices.iter().find(|d| d.id == id).cloned().ok_or(DbError::NotFound)
+    }
+
+    async fn get_device_by_ip(&self, ip_address: IpNetwork) -> Result<Device, DbError> {
+        self.state().devices.iter().find(|d| d.ip_address == ip_address).cloned().ok_or(DbError::NotFound)
+    }
+
+    async fn list_devices(&self) -> Result<Vec<Device>, DbError> {
+        let mut devices = self.state().devices.clone();
+        // Postgres sorts NULL hostnames last
+        devices.sort_by(|a, b| {
+            (a.hostname.is_none(), &a.hostname, a.ip_address).cmp(&(b.hostname.is_none(), &b.hostname, b.ip_address))
+        });
+        Ok(devices)
+    }
+
+    async fn query_devices(&self, query: &DeviceQuery) -> Result<DevicePage, DbError> {
+        query.after()?;
+        let state = self.state();
+        let group = match query.filter.group_id {
+            Some(group_id) => Some(state.groups.iter().find(|g| g.id == group_id).ok_or(DbError::NotFound)?),
+            None => None,
+        };
+        let in_group = |device: &Device| match group {
+            None => true,
+            Some(DeviceGroup { rules: Some(rules), .. }) => state.matches(rules, device),
+            Some(group) => state.group_members.contains(&(group.id, device.id)),
+        };
+        let devices: Vec<Device> =
+            state.devices.iter().filter(|d| state.matches(&query.filter, d) && in_group(d)).cloned().collect();
+        drop(state);
+        device_query::page_devices(devices, query)
+    }
+
+    async fn upsert_wireless_ap(&self, ap: &WirelessAp) -> Result<(), DbError> {
+        let mut state = self.state();
+        let now = OffsetDateTime::now_utc();
+        match state.wireless_aps.iter_mut().find(|a| a.device_id == ap.device_id) {
+            Some(existing) => {
+                *existing = WirelessAp { last_seen: now, created_at: existing.created_at, updated_at: now, ..ap.clone() };
+            }
+            None => state.wireless_aps.push(WirelessAp { last_seen: now, created_at: now, updated_at: now, ..ap.clone() }),
+        }
+        Ok(())
+    }
+
+    async fn list_wireless_aps(&self, controller_id: Uuid) -> Result<Vec<WirelessAp>, DbError> {
+        let mut aps: Vec<_> = self.state().wireless_aps.iter().filter(|a| a.controller_id == controller_id).cloned().collect();
+        aps.sort_by(|a, b| (a.name.is_none(), &a.name, &a.mac_address).cmp(&(b.name.is_none(), &b.name, &b.mac_address)));
+        Ok(aps)
+    }
+}
+
+#[async_trait]
+impl InterfaceRepository for MemoryRepository {
+    async fn upsert_interface(&self, interface: &Interface) -> Result<Interface, DbError> {
+        let mut state = self.state();
+        let now = OffsetDateTime::now_utc();
+        let stored = match state
+            .interfaces
+            .iter_mut()
+            .find(|i| i.device_id == interface.device_id && i.if_index == interface.if_index)
+        {
+            Some(existing) => {
+                *existing = Interface {
+                    id: existing.id,
+                    ip_address: interface.ip_address.or(existing.ip_address),
+                    access_vlan: interface.access_vlan.or(existing.access_vlan),
+                    last_changed: interface.last_changed.or(existing.last_changed),
+                    tags: existing.tags.clone(),
+                    custom_fields: existing.custom_fields.clone(),
+                    created_at: existing.created_at,
+                    updated_at: now,
+                    ..interface.clone()
+                };
+                existing.clone()
+            }
+            None => {
+                // Tags and custom fields are only set through set_interface_metadata
+                let new = Interface {
+                    id: Uuid::new_v4(),
+                    tags: Vec::new(),
+                    custom_fields: CustomFieldValues::new(),
+                    created_at: now,
+                    updated_at: now,
+                    ..interface.clone()
+                };
+                state.interfaces.push(new.clone());
+                new
+            }
+        };
+        Ok(stored)
+    }
+
+    async fn upsert_interfaces(&self, interfaces: &[Interface]) -> Result<u64, DbError> {
+        let interfaces = bulk::dedup_last(interfaces, |i| (i.device_id, i.if_index));
+        for interface in &interfaces {
+            self.upsert_interface(interface).await?;
+        }
+        Ok(interfaces.len() as u64)
+    }
+
+    async fn get_interface(&self, device_id: Uuid, if_index: i32) -> Result<Interface, DbError> {
+        self.state()
+            .interfaces
+            .iter()
+            .find(|i| i.device_id == device_id && i.if_index == if_index)
+            .cloned()
+            .ok_or(DbError::NotFound)
+    }
+
+    async fn list_interfaces(&self, device_id: Uuid) -> Result<Vec<Interface>, DbError> {
+        let mut interfaces: Vec<_> = self.state().interfaces.iter().filter(|i| i.device_id == device_id).cloned().collect();
+        interfaces.sort_by_key(|i| i.if_index);
+        Ok(interfaces)
+    }
+
+    async fn find_interfaces_by_mac(&self, mac_address: MacAddress) -> Result<Vec<Interface>, DbError> {
+        let mut interfaces: Vec<_> =
+            self.state().interfaces.iter().filter(|i| i.mac_address == Some(mac_address)).cloned().collect();
+        interfaces.sort_by_key(|i| (i.device_id, i.if_index));
+        Ok(interfaces)
+    }
+
+    async fn find_interfaces(&self, filter: &MetadataFilter) -> Result<Vec<Interface>, DbError> {
+        if filter.is_empty() {
+            return Err(DbError::InvalidQuery("filter by at least one tag or custom field".to_string()));
+        }
+        let mut interfaces: Vec<_> =
+            self.state().interfaces.iter().filter(|i| filter.matches(&i.tags, &i.custom_fields)).cloned().collect();
+        interfaces.sort_by_key(|i| (i.device_id, i.if_index));
+        Ok(interfaces)
+    }
+
+    async fn set_interface_metadata(&self, interface_id: Uuid, update: &MetadataUpdate) -> Result<Interface, DbError> {
+        let mut state = self.state();
+        let position = state.interfaces.iter().position(|i| i.id == interface_id).ok_or(DbError::NotFound)?;
+        let interface = &state.interfaces[position];
+        let (tags, custom_fields) = metadata::apply_update(
+            &state.custom_fields,
+            CustomFieldObject::Interface,
+            &interface.tags,
+            &interface.custom_fields,
+            update,
+        )?;
+        let updated = Interface { tags, custom_fields, updated_at: OffsetDateTime::now_utc(), ..interface.clone() };
+        state.interfaces[position] = updated.clone();
+        Ok(updated)
+    }
+
+    async fn update_interface_settings(
+        &self,
+        interface_id: Uuid,
+        admin_status: Option<&str>,
+        if_alias: Option<&str>,
+        access_vlan: Option<i32>,
+    ) -> Result<(), DbError> {
+        let mut state = self.state();
+        let interface = state.interfaces.iter_mut().find(|i| i.id == interface_id).ok_or(DbError::NotFound)?;
+        if let Some(status) = admin_status {
+            interface.admin_status = Some(status.to_string());
+        }
+        if let Some(alias) = if_alias {
+            interface.if_alias = Some(alias.to_string());
+        }
+        if let Some(vlan) = access_vlan {
+            interface.access_vlan = Some(vlan);
+        }
+        interface.updated_at = OffsetDateTime::now_utc();
+        Ok(())
+    }
+
+    async fn upsert_interface_poe(&self, device_id: Uuid, poe: &InterfacePoe) -> Result<(), DbError> {
+        match self.state().upsert_poe(device_id, poe) {
+            true => Ok(()),
+            false => Err(DbError::NotFound),
+        }
+    }
+
+    async fn replace_interface_poe(&self, device_id: Uuid, ports: &[InterfacePoe]) -> Result<(), DbError> {
+        let mut state = self.state();
+        let mut kept = Vec::new();
+        for poe in ports {
+            if state.upsert_poe(device_id, poe) {
+                kept.extend(state.interface_id(device_id, poe.if_index));
+            }
+        }
+        let device_interfaces = state.interface_ids_of(device_id);
+        state
+            .interface_poe
+            .retain(|p| !device_interfaces.contains(&p.interface_id) || kept.contains(&p.interface_id));
+        Ok(())
+    }
+
+    async fn get_interface_poe(&self, device_id: Uuid, if_index: i32) -> Result<InterfacePoe, DbError> {
+        let state = self.state();
+        let interface_id = state.interface_id(device_id, if_index).ok_or(DbError::NotFound)?;
+        state.interface_poe.iter().find(|p| p.interface_id == interface_id).cloned().ok_or(DbError::NotFound)
+    }
+
+    async fn list_interface_poe(&self, device_id: Uuid) -> Result<Vec<InterfacePoe>, DbError> {
+        let state = self.state();
+        let device_interfaces = state.interface_ids_of(device_id);
+        let mut ports: Vec<_> =
+            state.interface_poe.iter().filter(|p| device_interfaces.contains(&p.interface_id)).cloned().collect();
+        ports.sort_by_key(|p| p.if_index);
+        Ok(ports)
+    }
+
+    async fn record_port_change(&self, change: &PortChange) -> Result<PortChange, DbError> {
+        let stored = PortChange { id: Uuid::new_v4(), changed_at: OffsetDateTime::now_utc(), ..change.clone() };
+        self.state().port_changes.push(stored.clone());
+        Ok(stored)
+    }
+
+    async fn list_port_changes(&self, device_id: Uuid) -> Result<Vec<PortChange>, DbError> {
+        Ok(self.state().port_changes.iter().rev().filter(|c| c.device_id == device_id).cloned().collect())
+    }
+}
+
+#[async_trait]
+impl NodeRepository for MemoryRepository {
+    async fn upsert_node(&self, node: &Node) -> Result<Uuid, DbError> {
+        let mut state = self.state();
+        let state = &mut *state;
+        let existing = state
+            .nodes
+            .iter_mut()
+            .find(|n| n.mac_address == node.mac_address && n.device_id == node.device_id && n.vlan == node.vlan);
+        let (id, old_ip) = match existing {
+            Some(existing) => {
+                // Same rule as the node_moves trigger: only moves between known ports
+                if let (Some(from), Some(to)) = (existing.interface_id, node.interface_id)
+                    && from != to
+                {
+                    state.node_moves.push(NodeMove {
+                        mac_address: node.mac_address,
+                        device_id: node.device_id,
+                        from_interface_id: Some(from),
+                        to_interface_id: Some(to),
+                        moved_at: node.last_seen,
+                    });
+                }
+                let old_ip = existing.wireless.as_ref().and_then(|w| w.ip_address);
+                existing.interface_id = node.interface_id;
+                existing.active = true;
+                existing.last_seen = node.last_seen;
+                if node.wireless.is_some() {
+                    existing.wireless = node.wireless.clone();
+                }
+                (existing.id, old_ip)
+            }
+            None => {
+                let id = Uuid::new_v4();
+                state.nodes.push(Node { id, active: true, first_seen: OffsetDateTime::now_utc(), ..node.clone() });
+                (id, None)
+            }
+        };
+        if let Some(wireless) = &node.wireless {
+            match wireless.ip_address {
+                None => {
+                    state.ip_since.remove(&id);
+                }
+                Some(ip) if old_ip != Some(ip) || !state.ip_since.contains_key(&id) => {
+                    state.ip_since.insert(id, wireless.last_seen);
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(id)
+    }
+
+    async fn upsert_nodes(&self, nodes: &[Node]) -> Result<Vec<Uuid>, DbError> {
+        let mut ids = Vec::new();
+        for node in bulk::dedup_last(nodes, |n| (n.mac_address, n.device_id, n.vlan)) {
+            ids.push(self.upsert_node(node).await?);
+        }
+        Ok(ids)
+    }
+
+    async fn deactivate_nodes(&self, device_ids: &[Uuid], seen_since: OffsetDateTime) -> Result<u64, DbError> {
+        let mut deactivated = 0;
+        for node in self.state().nodes.iter_mut() {
+            if device_ids.contains(&node.device_id) && node.active && node.last_seen < seen_since {
+                node.active = false;
+                deactivated += 1;
+            }
+        }
+        Ok(deactivated)
+    }
+
+    async fn list_nodes(&self, device_id: Uuid) -> Result<Vec<Node>, DbError> {
+        let mut nodes: Vec<_> = self.state().nodes.iter().filter(|n| n.device_id == device_id && n.active).cloned().collect();
+        nodes.sort_by_key(|n| (n.mac_address, n.vlan));
+        Ok(nodes)
+    }
+
+    async fn find_nodes_by_mac(&self, mac_address: MacAddress) -> Result<Vec<Node>, DbError> {
+        let mut nodes: Vec<_> = self.state().nodes.iter().filter(|n| n.mac_address == mac_address).cloned().collect();
+        nodes.sort_by_key(|n| (std::cmp::Reverse(n.last_seen), n.vlan));
+        Ok(nodes)
+    }
+}
+
+#[async_trait]
+impl TopologyRepository for MemoryRepository {
+    async fn replace_routing_neighbors(
+        &self,
+        device_id: Uuid,
+        protocol: RoutingProtocol,
+        neighbors: &[RoutingNeighbor],
+    ) -> Result<(), DbError> {
+        let mut state = self.state();
+        let now = OffsetDateTime::now_utc();
+        state.routing_neighbors.retain(|n| {
+            n.device_id != device_id
+                || n.protocol != protocol
+                || neighbors.iter().any(|current| current.neighbor_address == n.neighbor_address)
+        });
+        for neighbor in neighbors {
+            let existing = state.routing_neighbors.iter_mut().find(|n| {
+                n.device_id == device_id && n.protocol == protocol && n.neighbor_address == neighbor.neighbor_address
+            });
+            match existing {
+                Some(existing) => {
+                    *existing = RoutingNeighbor {
+                        id: existing.id,
+                        device_id,
+                        protocol,
+                        last_seen: now,
+                        created_at: existing.created_at,
+                        updated_at: now,
+                        ..neighbor.clone()
+                    };
+                }
+                None => state.routing_neighbors.push(RoutingNeighbor {
+                    id: Uuid::new_v4(),
+                    device_id,
+                    protocol,
+                    last_seen: now,
+                    created_at: now,
+                    updated_at: now,
+                    ..neighbor.clone()
+                }),
+            }
+        }
+        Ok(())
+    }
+
+    async fn list_routing_neighbors(&self, device_id: Uuid) -> Result<Vec<RoutingNeighbor>, DbError> {
+        let mut neighbors: Vec<_> =
+            self.state().routing_neighbors.iter().filter(|n| n.device_id == device_id).cloned().collect();
+        neighbors.sort_by(|a, b| (a.protocol.as_str(), a.neighbor_address).cmp(&(b.protocol.as_str(), b.neighbor_address)));
+        Ok(neighbors)
+    }
+
+    async fn replace_stp(&self, device_id: Uuid, instances: &[StpInstance], ports: &[StpPort]) -> Result<(), DbError> {
+        let mut state = self.state();
+        let now = OffsetDateTime::now_utc();
+
+        state.stp_instances.retain(|i| i.device_id != device_id || instances.iter().any(|c| c.instance == i.instance));
+        for instance in instances {
+            let existing = state.stp_instances.iter_mut().find(|i| i.device_id == device_id && i.instance == instance.instance);
+            let (id, created_at) = existing.as_ref().map_or((Uuid::new_v4(), now), |e| (e.id, e.created_at));
+            let stored = StpInstance {
+                id,
+                device_id,
+                root_device_id: None,
+                last_seen: now,
+                created_at,
+                updated_at: now,
+                ..instance.clone()
+            };
+            match existing {
+                Some(existing) => *existing = stored,
+                None => state.stp_instances.push(stored),
+            }
+        }
+
+        let device_interfaces = state.interface_ids_of(device_id);
+        let current: Vec<(Uuid, i32)> = ports
+            .iter()
+            .filter_map(|p| state.interface_id(device_id, p.if_index).map(|id| (id, p.instance)))
+            .collect();
+        state
+            .stp_ports
+            .retain(|p| !device_interfaces.contains(&p.interface_id) || current.contains(&(p.interface_id, p.instance)));
+        for port in ports {
+            let Some(interface_id) = state.interface_id(device_id, port.if_index) else {
+                continue;
+            };
+            let existing = state.stp_ports.iter_mut().find(|p| p.interface_id == interface_id && p.instance == port.instance);
+            let (id, created_at) = existing.as_ref().map_or((Uuid::new_v4(), now), |e| (e.id, e.created_at));
+            let stored = StpPort { id, interface_id, last_seen: now, created_at, updated_at: now, ..port.clone() };
+            match existing {
+                Some(existing) => *existing = stored,
+                None => state.stp_ports.push(stored),
+            }
+        }
+        Ok(())
+    }
+
+    async fn list_stp_instances(&self, device_id: Uuid) -> Result<Vec<StpInstance>, DbError> {
+        let state = self.state();
+        let mut instances: Vec<_> = state
+            .stp_instances
+            .iter()
+            .filter(|i| i.device_id == device_id)
+            .map(|i| StpInstance {
+                root_device_id: state
+                    .stp_instances
+                    .iter()
+                    .find(|r| r.instance == i.instance && r.bridge_id == i.designated_root)
+                    .map(|r| r.device_id),
+                ..i.clone()
+            })
+            .collect();
+        instances.sort_by_key(|i| i.instance);
+        Ok(instances)
+    }
+
+    async fn list_stp_ports(&self, device_id: Uuid) -> Result<Vec<StpPort>, DbError> {
+        let state = self.state();
+        let device_interfaces = state.interface_ids_of(device_id);
+        let mut ports: Vec<_> =
+            state.stp_ports.iter().filter(|p| device_interfaces.contains(&p.interface_id)).cloned().collect();
+        ports.sort_by_key(|p| (p.instance, p.if_index));
+        Ok(ports)
+    }
+
+    async fn list_stp_roots(&self) -> Result<Vec<StpRoot>, DbError> {
+        let state = self.state();
+        let mut roots: Vec<StpRoot> = Vec::new();
+        for instance in &state.stp_instances {
+            let same_tree = |r: &StpRoot| {
+                (r.protocol.as_str(), r.instance, r.designated_root.as_str())
+                    == (instance.protocol.as_str(), instance.instance, instance.designated_root.as_str())
+            };
+            let root = match roots.iter_mut().position(|r| same_tree(r)) {
+                Some(index) => &mut roots[index],
+                None => {
+                    roots.push(StpRoot {
+                        protocol: instance.protocol.clone(),
+                        instance: instance.instance,
+                        vlans: Vec::new(),
+                        designated_root: instance.designated_root.clone(),
+                        root_device_id: state
+                            .stp_instances
+                            .iter()
+                            .find(|r| r.instance == instance.instance && r.bridge_id == instance.designated_root)
+                            .map(|r| r.device_id),
+                        device_ids: Vec::new(),
+                        blocking_ports: 0,
+                    });
+                    roots.last_mut().expect("just pushed")
+                }
+            };
+            root.vlans.extend(&instance.vlans);
+            root.device_ids.push(instance.device_id);
+            let interfaces = state.interface_ids_of(instance.device_id);
+            root.blocking_ports += state
+                .stp_ports
+                .iter()
+                .filter(|p| p.instance == instance.instance && p.state == "blocking" && interfaces.contains(&p.interface_id))
+                .count() as i64;
+        }
+        for root in &mut roots {
+            root.vlans.sort_unstable();
+            root.vlans.dedup();
+            root.device_ids.sort();
+        }
+        roots.sort_by(|a, b| {
+            (a.instance, &a.protocol, &a.designated_root).cmp(&(b.instance, &b.protocol, &b.designated_root))
+        });
+        Ok(roots)
+    }
+}
+
+#[async_trait]
+impl LocationRepository for MemoryRepository {
+    async fn create_location(&self, location: &Location) -> Result<Location, DbError> {
+        let mut state = self.state();
+        state.check_location(location)?;
+        let now = OffsetDateTime::now_utc();
+        let stored = Location { id: Uuid::new_v4(), created_at: now, updated_at: now, ..location.clone() };
+        state.locations.push(stored.clone());
+        Ok(stored)
+    }
+
+    async fn update_location(&self, location: &Location) -> Result<Location, DbError> {
+        let mut state = self.state();
+        let current = state.location(location.id)?.clone();
+        if current.kind != location.kind {
+            return Err(DbError::InvalidQuery("the kind of a location cannot change".to_string()));
+        }
+        state.check_location(location)?;
+        let stored = Location { created_at: current.created_at, updated_at: OffsetDateTime::now_utc(), ..location.clone() };
+        let position = state.locations.iter().position(|l| l.id == location.id).ok_or(DbError::NotFound)?;
+        state.locations[position] = stored.clone();
+        Ok(stored)
+    }
+
+    async fn get_location(&self, id: Uuid) -> Result<Location, DbError> {
+        self.state().location(id).cloned()
+    }
+
+    async fn list_locations(&self) -> Result<Vec<Location>, DbError> {
+        let mut locations = self.state().locations.clone();
+        locations.sort_by(|a, b| (a.kind, &a.name).cmp(&(b.kind, &b.name)));
+        Ok(locations)
+    }
+
+    async fn delete_location(&self, id: Uuid) -> Result<(), DbError> {
+        let mut state = self.state();
+        state.location(id)?;
+        let subtree = state.location_subtree(id);
+        state.locations.retain(|l| !subtree.contains(&l.id));
+        state.location_rules.retain(|r| !subtree.contains(&r.location_id));
+        state.allowed_macs.retain(|e| e.location_id.is_none_or(|location| !subtree.contains(&location)));
+        for device in state.devices.iter_mut() {
+            if device.location_id.is_some_and(|location| subtree.contains(&location)) {
+                device.location_id = None;
+            }
+        }
+        Ok(())
+    }
+
+    async fn create_location_rule(&self, rule: &LocationRule) -> Result<LocationRule, DbError> {
+        locations::check_rule(rule)?;
+        let mut state = self.state();
+        state.location(rule.location_id).map_err(|_| DbError::InvalidQuery("unknown location".to_string()))?;
+        let stored = LocationRule { id: Uuid::new_v4(), created_at: OffsetDateTime::now_utc(), ..rule.clone() };
+        state.location_rules.push(stored.clone());
+        Ok(stored)
+    }
+
+    async fn list_location_rules(&self) -> Result<Vec<LocationRule>, DbError> {
+        let mut rules = self.state().location_rules.clone();
+        rules.sort_by_key(|r| (r.priority, r.created_at, r.id));
+        Ok(rules)
+    }
+
+    async fn delete_location_rule(&self, id: Uuid) -> Result<(), DbError> {
+        let mut state = self.state();
+        let position = state.location_rules.iter().position(|r| r.id == id).ok_or(DbError::NotFound)?;
+        state.location_rules.remove(position);
+        Ok(())
+    }
+
+    async fn apply_location_rules(&self) -> Result<u64, DbError> {
+        let mut state = self.state();
+        let now = OffsetDateTime::now_utc();
+        let mut moved = 0;
+        for position in 0..state.devices.len() {
+            let existing = state.devices[position].clone();
+            let location_id = state.place(&existing);
+            if location_id == existing.location_id {
+                continue;
+            }
+            let updated = Device { location_id, updated_at: now, ..existing.clone() };
+            let changes = history::device_changes(&existing, &updated);
+            state.devices[position] = updated;
+            state.record(existing.id, &[], &changes, "location_rules", now);
+            moved += 1;
+        }
+        Ok(moved)
+    }
+
+    async fn set_device_location(
+        &self,
+        device_id: Uuid,
+        location_id: Option<Uuid>,
+        source: &str,
+    ) -> Result<Device, DbError> {
+        let mut state = self.state();
+        if let Some(location_id) = location_id {
+            state.location(location_id).map_err(|_| DbError::InvalidQuery("unknown location".to_string()))?;
+        }
+        let now = OffsetDateTime::now_utc();
+        let position = state.devices.iter().position(|d| d.id == device_id).ok_or(DbError::NotFound)?;
+        let existing = state.devices[position].clone();
+        let sources = state.field_sources.entry(device_id).or_default();
+        if !is_manual_source(source) && sources.get(LOCATION_FIELD).is_some_and(|s| is_manual_source(s)) {
+            return Ok(existing);
+        }
+        let location_id = match location_id {
+            Some(location_id) => {
+                sources.insert(LOCATION_FIELD.to_string(), source.to_string());
+                Some(location_id)
+            }
+            None => {
+                sources.remove(LOCATION_FIELD);
+                state.place(&existing)
+            }
+        };
+        let updated = Device { location_id, updated_at: now, ..existing.clone() };
+        let changes = history::device_changes(&existing, &updated);
+        state.devices[position] = updated.clone();
+        state.record(device_id, &[], &changes, source, now);
+        Ok(updated)
+    }
+}
+
+#[async_trait]
+impl GroupRepository for MemoryRepository {
+    async fn create_group(&self, group: &DeviceGroup) -> Result<DeviceGroup, DbError> {
+        groups::check_rules(group.rules.as_ref())?;
+        let mut state = self.state();
+        let now = OffsetDateTime::now_utc();
+        let stored = DeviceGroup { id: Uuid::new_v4(), created_at: now, updated_at: now, ..group.clone() };
+        state.check_group_name(&stored)?;
+        state.groups.push(stored.clone());
+        Ok(stored)
+    }
+
+    async fn update_group(&self, group: &DeviceGroup) -> Result<DeviceGroup, DbError> {
+        groups::check_rules(group.rules.as_ref())?;
+        let mut state = self.state();
+        state.check_group_name(group)?;
+        let position = state.groups.iter().position(|g| g.id == group.id).ok_or(DbError::NotFound)?;
+        let stored = DeviceGroup {
+            created_at: state.groups[position].created_at,
+            updated_at: OffsetDateTime::now_utc(),
+            ..group.clone()
+        };
+        state.groups[position] = stored.clone();
+        if group.rules.is_some() {
+            state.group_members.retain(|(group_id, _)| *group_id != group.id);
+        }
+        Ok(stored)
+    }
+
+    async fn get_group(&self, id: Uuid) -> Result<DeviceGroup, DbError> {
+        self.state().groups.iter().find(|g| g.id == id).cloned().ok_or(DbError::NotFound)
+    }
+
+    async fn list_groups(&self) -> Result<Vec<DeviceGroup>, DbError> {
+        let mut groups = self.state().groups.clone();
+        groups.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(groups)
+    }
+
+    async fn delete_group(&self, id: Uuid) -> Result<(), DbError> {
+        let mut state = self.state();
+        let position = state.groups.iter().position(|g| g.id == id).ok_or(DbError::NotFound)?;
+        state.groups.remove(position);
+        state.group_members.retain(|(group_id, _)| *group_id != id);
+        Ok(())
+    }
+
+    async fn add_group_member(&self, group_id: Uuid, device_id: Uuid) -> Result<(), DbError> {
+        let mut state = self.state();
+        let group = state.groups.iter().find(|g| g.id == group_id).ok_or(DbError::NotFound)?;
+        if group.rules.is_some() {
+            return Err(DbError::InvalidQuery("members of a dynamic group are set by its rules".to_string()));
+        }
+        if !state.devices.iter().any(|d| d.id == device_id) {
+            return Err(DbError::NotFound);
+        }
+        if !state.group_members.contains(&(group_id, device_id)) {
+            state.group_members.push((group_id, device_id));
+        }
+        Ok(())
+    }
+
+    async fn remove_group_member(&self, group_id: Uuid, device_id: Uuid) -> Result<(), DbError> {
+        let mut state = self.state();
+        let position =
+            state.group_members.iter().position(|m| *m == (group_id, device_id)).ok_or(DbError::NotFound)?;
+        state.group_members.remove(position);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CustomFieldRepository for MemoryRepository {
+    async fn create_custom_field(&self, field: &CustomField) -> Result<CustomField, DbError> {
+        metadata::check_custom_field(field)?;
+        let mut state = self.state();
+        if state.custom_fields.iter().any(|f| f.object_type == field.object_type && f.name == field.name) {
+            return Err(DbError::InvalidQuery(format!(
+                "{} custom field '{}' already exists",
+                field.object_type.as_str(),
+                field.name
+            )));
+        }
+        let now = OffsetDateTime::now_utc();
+        let stored = CustomField { id: Uuid::new_v4(), created_at: now, updated_at: now, ..field.clone() };
+        state.custom_fields.push(stored.clone());
+        Ok(stored)
+    }
+
+    async fn list_custom_fields(&self) -> Result<Vec<CustomField>, DbError> {
+        let mut fields = self.state().custom_fields.clone();
+        fields.sort_by(|a, b| (a.object_type.as_str(), &a.name).cmp(&(b.object_type.as_str(), &b.name)));
+        Ok(fields)
+    }
+
+    async fn delete_custom_field(&self, id: Uuid) -> Result<(), DbError> {
+        let mut state = self.state();
+        let position = state.custom_fields.iter().position(|f| f.id == id).ok_or(DbError::NotFound)?;
+        let field = state.custom_fields.remove(position);
+        match field.object_type {
+            CustomFieldObject::Device => state.devices.iter_mut().for_each(|d| {
+                d.custom_fields.remove(&field.name);
+            }),
+            CustomFieldObject::Interface => state.interfaces.iter_mut().for_each(|i| {
+                i.custom_fields.remove(&field.name);
+            }),
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SearchRepository for MemoryRepository {
+    async fn search(&self, query: &SearchQuery) -> Result<Vec<SearchHit>, DbError> {
+        let state = self.state();
+        Ok(search::rank(&query.term, &state.devices, &state.interfaces, &state.nodes, &state.arp_entries, query.limit()))
+    }
+}
+
+#[async_trait]
+impl AnomalyRepository for MemoryRepository {
+    async fn detect_anomalies(&self, settings: &AnomalySettings) -> Result<AnomalyReport, DbError> {
+        let now = OffsetDateTime::now_utc();
+        let mut state = self.state();
+        let claims: Vec<IpClaim> = state
+            .nodes
+            .iter()
+            .filter_map(|node| {
+                let wireless = node.wireless.as_ref()?;
+                Some(IpClaim {
+                    mac_address: node.mac_address,
+                    device_id: node.device_id,
+                    ip_address: wireless.ip_address?,
+                    since: *state.ip_since.get(&node.id)?,
+                    last_seen: wireless.last_seen,
+                })
+            })
+            .chain(state.arp_entries.iter().map(|entry| IpClaim {
+                mac_address: entry.mac_address,
+                device_id: entry.device_id,
+                ip_address: entry.ip_address,
+                since: entry.first_seen,
+                last_seen: entry.last_seen,
+            }))
+            .collect();
+        let findings = anomalies::detect(settings, now, &state.nodes, &claims, &state.node_moves);
+        let open = state.anomalies.iter().filter(|a| a.resolved_at.is_none()).cloned().collect();
+        let reconciled = anomalies::reconcile(open, findings, now);
+        for anomaly in reconciled.ongoing.iter().chain(&reconciled.resolved) {
+            if let Some(stored) = state.anomalies.iter_mut().find(|a| a.id == anomaly.id) {
+                *stored = anomaly.clone();
+            }
+        }
+        state.anomalies.extend(reconciled.opened.iter().cloned());
+        Ok(reconciled.report())
+    }
+
+    async fn list_anomalies(&self, filter: &AnomalyFilter) -> Result<Vec<Anomaly>, DbError> {
+        let mut anomalies: Vec<Anomaly> = self.state().anomalies.iter().filter(|a| filter.matches(a)).cloned().collect();
+        anomalies.sort_by(|a, b| b.last_detected.cmp(&a.last_detected).then(a.id.cmp(&b.id)));
+        anomalies.truncate(filter.limit() as usize);
+        Ok(anomalies)
+    }
+}
+
+#[async_trait]
+impl AllowlistRepository for MemoryRepository {
+    async fn create_allowed_mac(&self, entry: &AllowedMac) -> Result<AllowedMac, DbError> {
+        let entry = allowlist::check_entry(entry)?;
+        let mut state = self.state();
+        if let Some(location_id) = entry.location_id {
+            state.location(location_id).map_err(locations::unknown_location)?;
+        }
+        let stored = AllowedMac { id: Uuid::new_v4(), created_at: OffsetDateTime::now_utc(), ..entry };
+        state.allowed_macs.push(stored.clone());
+        Ok(stored)
+    }
+
+    async fn list_allowed_macs(&self) -> Result<Vec<AllowedMac>, DbError> {
+        let mut entries = self.state().allowed_macs.clone();
+        entries.sort_by(|a, b| a.mac_prefix.cmp(&b.mac_prefix).then(a.created_at.cmp(&b.created_at)));
+        Ok(entries)
+    }
+
+    async fn delete_allowed_mac(&self, id: Uuid) -> Result<(), DbError> {
+        let mut state = self.state();
+        let position = state.allowed_macs.iter().position(|e| e.id == id).ok_or(DbError::NotFound)?;
+        state.allowed_macs.remove(position);
+        Ok(())
+    }
+
+    async fn check_mac_allowlist(&self, nodes: &[Node], access_port_max_macs: u32) -> Result<Vec<MacViolation>, DbError> {
+        let mut state = self.state();
+        let device_locations = state.devices.iter().map(|d| (d.id, d.location_id)).collect();
+        let flagged: Vec<Node> =
+            allowlist::unapproved(&state.allowed_macs, &state.locations, &device_locations, nodes, access_port_max_macs)
+                .into_iter()
+                .cloned()
+                .collect();
+        let mut violations = Vec::new();
+        for node in flagged {
+            let existing = state.mac_violations.iter_mut().find(|v| {
+                v.mac_address == node.mac_address && v.device_id == node.device_id && v.vlan == node.vlan
+            });
+            let violation = match existing {
+                Some(violation) => {
+                    if violation.interface_id != node.interface_id {
+                        violation.port_shutdown_at = None;
+                    }
+                    violation.interface_id = node.interface_id;
+                    violation.last_seen = node.last_seen;
+                    violation.clone()
+                }
+                None => {
+                    let violation = MacViolation {
+                        id: Uuid::new_v4(),
+                        mac_address: node.mac_address,
+                        device_id: node.device_id,
+                        interface_id: node.interface_id,
+                        vlan: node.vlan,
+                        first_seen: node.last_seen,
+                        last_seen: node.last_seen,
+                        port_shutdown_at: None,
+                    };
+                    state.mac_violations.push(violation.clone());
+                    violation
+                }
+            };
+            violations.push(violation);
+        }
+        Ok(violations)
+    }
+
+    async fn list_mac_violations(&self, filter: &MacViolationFilter) -> Result<Vec<MacViolation>, DbError> {
+        let mut violations: Vec<MacViolation> =
+            self.state().mac_violations.iter().filter(|v| filter.matches(v)).cloned().collect();
+        violations.sort_by(|a, b| b.last_seen.cmp(&a.last_seen).then(a.id.cmp(&b.id)));
+        Ok(violations)
+    }
+
+    async fn set_violation_port_shutdown(&self, id: Uuid, at: OffsetDateTime) -> Result<(), DbError> {
+        let mut state = self.state();
+        let violation = state.mac_violations.iter_mut().find(|v| v.id == id).ok_or(DbError::NotFound)?;
+        violation.port_shutdown_at = Some(at);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl IpamRepository for MemoryRepository {
+    async fn upsert_arp_entries(&self, device_id: Uuid, entries: &[ArpEntry]) -> Result<(), DbError> {
+        let mut state = self.state();
+        let now = OffsetDateTime::now_utc();
+        for entry in entries {
+            match state.arp_entries.iter_mut().find(|e| e.device_id == device_id && e.ip_address == entry.ip_address) {
+                Some(stored) => {
+                    if stored.mac_address != entry.mac_address {
+                        stored.first_seen = now;
+                    }
+                    stored.mac_address = entry.mac_address;
+                    stored.interface_id = entry.interface_id;
+                    stored.last_seen = now;
+                }
+                None => state.arp_entries.push(ArpEntry {
+                    id: Uuid::new_v4(),
+                    device_id,
+                    first_seen: now,
+                    last_seen: now,
+                    ..entry.clone()
+                }),
+            }
+        }
+        Ok(())
+    }
+
+    async fn refresh_subnets(&self) -> Result<SubnetRefresh, DbError> {
+        let mut state = self.state();
+        let now = OffsetDateTime::now_utc();
+        let networks = ipam::interface_subnets(state.interfaces.iter().filter_map(|i| i.ip_address.as_ref()));
+        let before = state.subnets.len();
+        state.subnets.retain(|s| networks.contains(&s.network));
+        let removed = (before - state.subnets.len()) as u64;
+        let mut added = 0;
+        for network in &networks {
+            match state.subnets.iter_mut().find(|s| s.network == *network) {
+                Some(subnet) => subnet.last_seen = now,
+                None => {
+                    state.subnets.push(Subnet { id: Uuid::new_v4(), network: *network, first_seen: now, last_seen: now });
+                    added += 1;
+                }
+            }
+        }
+        Ok(SubnetRefresh { added, removed, total: networks.len() as u64 })
+    }
+
+    async fn list_subnets(&self) -> Result<Vec<Subnet>, DbError> {
+        let mut subnets = self.state().subnets.clone();
+        subnets.sort_by_key(|s| s.network);
+        Ok(subnets)
+    }
+
+    async fn get_subnet(&self, id: Uuid) -> Result<Subnet, DbError> {
+        self.state().subnets.iter().find(|s| s.id == id).cloned().ok_or(DbError::NotFound)
+    }
+
+    async fn list_ip_sightings(&self, network: IpNetwork) -> Result<Vec<IpSighting>, DbError> {
+        let state = self.state();
+        let sighting = |ip: &IpNetwork, mac_address, last_seen| IpSighting { ip_address: ip.ip(), mac_address, last_seen };
+        let arp = state.arp_entries.iter().map(|e| sighting(&e.ip_address, Some(e.mac_address), e.last_seen));
+        let wireless = state.nodes.iter().filter_map(|n| {
+            let wireless = n.wireless.as_ref()?;
+            Some(sighting(wireless.ip_address.as_ref()?, Some(n.mac_address), wireless.last_seen))
+        });
+        let interfaces =
+            state.interfaces.iter().filter_map(|i| Some(sighting(i.ip_address.as_ref()?, i.mac_address, i.updated_at)));
+        let devices = state.devices.iter().map(|d| sighting(&d.ip_address, None, d.last_seen.unwrap_or(d.updated_at)));
+        Ok(arp.chain(wireless).chain(interfaces).chain(devices).filter(|s| network.contains(s.ip_address)).collect())
+    }
+}
+
+#[async_trait]
+impl HostnameRepository for MemoryRepository {
+    async fn set_ip_hostname(&self, ip_address: IpNetwork, hostname: Option<&str>) -> Result<(), DbError> {
+        let mut state = self.state();
+        state.ip_hostnames.retain(|h| h.ip_address != ip_address);
+        if let Some(hostname) = hostname {
+            let resolved_at = OffsetDateTime::now_utc();
+            state.ip_hostnames.push(IpHostname { ip_address, hostname: hostname.to_string(), resolved_at });
+        }
+        Ok(())
+    }
+
+    async fn list_ip_hostnames(&self, network: Option<IpNetwork>) -> Result<Vec<IpHostname>, DbError> {
+        let mut hostnames: Vec<IpHostname> = self
+            .state()
+            .ip_hostnames
+            .iter()
+            .filter(|h| network.is_none_or(|n| n.contains(h.ip_address.ip())))
+            .cloned()
+            .collect();
+        hostnames.sort_by_key(|h| h.ip_address);
+        Ok(hostnames)
+    }
+}
+
+#[async_trait]
+impl LinkRepository for MemoryRepository {
+    async fn replace_device_neighbors(
+        &self,
+        device_id: Uuid,
+        protocol: NeighborProtocol,
+        neighbors: &[DeviceNeighbor],
+    ) -> Result<(), DbError> {
+        let mut state = self.state();
+        let now = OffsetDateTime::now_utc();
+        state.device_neighbors.retain(|n| n.device_id != device_id || n.protocol != protocol);
+        state.device_neighbors.extend(neighbors.iter().map(|neighbor| DeviceNeighbor {
+            id: Uuid::new_v4(),
+            device_id,
+            protocol,
+            last_seen: now,
+            ..neighbor.clone()
+        }));
+        Ok(())
+    }
+
+    async fn list_device_neighbors(&self, device_id: Uuid) -> Result<Vec<DeviceNeighbor>, DbError> {
+        let mut neighbors: Vec<_> =
+            self.state().device_neighbors.iter().filter(|n| n.device_id == device_id).cloned().collect();
+        neighbors.sort_by(|a, b| {
+            (a.protocol.as_str(), &a.remote_sys_name, &a.remote_port)
+                .cmp(&(b.protocol.as_str(), &b.remote_sys_name, &b.remote_port))
+                .reverse()
+        });
+        Ok(neighbors)
+    }
+
+    async fn refresh_topology_links(&self) -> Result<LinkRefresh, DbError> {
+        let mut state = self.state();
+        let now = OffsetDateTime::now_utc();
+        let devices: Vec<LinkDevice> = state.devices.iter().map(LinkDevice::from).collect();
+        let ports: Vec<LinkPort> = state.interfaces.iter().map(LinkPort::from).collect();
+        let sightings: Vec<PortSighting> = state
+            .nodes
+            .iter()
+            .filter(|n| n.active)
+            .filter_map(|n| {
+                let interface_id = n.interface_id?;
+                Some(PortSighting { device_id: n.device_id, interface_id, mac_address: n.mac_address })
+            })
+            .collect();
+        let manual: Vec<TopologyLink> =
+            state.topology_links.iter().filter(|l| l.source == LinkSource::Manual).cloned().collect();
+        let found = links::infer_links(&devices, &ports, &state.device_neighbors, &sightings, &manual);
+
+        let before = state.topology_links.len();
+        let found_keys: Vec<_> = found.iter().map(links::link_key).collect();
+        state.topology_links.retain(|l| l.source == LinkSource::Manual || found_keys.contains(&links::link_key(l)));
+        let removed = (before - state.topology_links.len()) as u64;
+        let mut added = 0;
+        for link in &found {
+            match state.topology_links.iter_mut().find(|l| links::link_key(l) == links::link_key(link)) {
+                Some(stored) => {
+                    stored.confidence = link.confidence;
+                    stored.last_seen = now;
+                }
+                None => {
+                    state.topology_links.push(TopologyLink { id: Uuid::new_v4(), ..link.clone() });
+                    added += 1;
+                }
+            }
+        }
+        Ok(LinkRefresh { added, removed, total: found.len() as u64 })
+    }
+
+    async fn list_topology_links(&self, device_id: Option<Uuid>) -> Result<Vec<TopologyLink>, DbError> {
+        let mut links: Vec<TopologyLink> = self
+            .state()
+            .topology_links
+            .iter()
+            .filter(|l| device_id.is_none_or(|id| l.device_id == id || l.remote_device_id == id))
+            .cloned()
+            .collect();
+        links.sort_by_key(|l| (l.device_id, l.remote_device_id, l.source.as_str(), l.interface_id));
+        Ok(links)
+    }
+
+    async fn create_topology_link(&self, link: &TopologyLink) -> Result<TopologyLink, DbError> {
+        links::check_manual_link(link)?;
+        let mut state = self.state();
+        let link = links::oriented(link.clone());
+        for (device_id, interface_id) in [(link.device_id, link.interface_id), (link.remote_device_id, link.remote_interface_id)] {
+            let found = match interface_id {
+                Some(interface_id) => state.interfaces.iter().any(|i| i.id == interface_id && i.device_id == device_id),
+                None => state.devices.iter().any(|d| d.id == device_id),
+            };
+            if !found {
+                return Err(DbError::InvalidQuery(links::endpoint_error(device_id, interface_id)));
+            }
+        }
+        let now = OffsetDateTime::now_utc();
+        let stored = TopologyLink { id: Uuid::new_v4(), source: LinkSource::Manual, first_seen: now, last_seen: now, ..link };
+        if state.topology_links.iter().any(|l| links::link_key(l) == links::link_key(&stored)) {
+            return Err(DbError::InvalidQuery(links::duplicate_link_error()));
+        }
+        state.topology_links.push(stored.clone());
+        Ok(stored)
+    }
+
+    async fn delete_topology_link(&self, id: Uuid) -> Result<(), DbError> {
+        let mut state = self.state();
+        let link = state.topology_links.iter().find(|l| l.id == id).ok_or(DbError::NotFound)?;
+        if link.source != LinkSource::Manual {
+            return Err(links::not_manual_error());
+        }
+        state.topology_links.retain(|l| l.id != id);
+        Ok(())
+    }
+
+    async fn is_uplink_port(&self, interface_id: Uuid) -> Result<bool, DbError> {
+        let state = self.state();
+        let has_role = |port: &StpPort, roles: &[&str]| {
+            port.interface_id == interface_id && port.role.as_deref().is_some_and(|r| roles.contains(&r))
+        };
+        let linked = |link: &TopologyLink| {
+            link.interface_id == Some(interface_id) || link.remote_interface_id == Some(interface_id)
+        };
+        if state.device_neighbors.iter().any(|n| n.interface_id == Some(interface_id))
+            || state.topology_links.iter().any(linked)
+            || state.stp_ports.iter().any(|p| has_role(p, &["root", "alternate"]))
+        {
+            return Ok(true);
+        }
+        let Some(device_id) = state.interfaces.iter().find(|i| i.id == interface_id).map(|i| i.device_id) else {
+            return Ok(false);
+        };
+        // Designated ports with another bridge behind them, e.g. on the root bridge
+        let behind: Vec<MacAddress> = state
+            .nodes
+            .iter()
+            .filter(|n| n.active && n.interface_id == Some(interface_id))
+            .map(|n| n.mac_address)
+            .collect();
+        Ok(state.stp_ports.iter().filter(|p| has_role(p, &["designated"])).any(|port| {
+            state
+                .stp_instances
+                .iter()
+                .filter(|b| b.instance == port.instance && b.device_id != device_id)
+                .filter_map(|b| links::bridge_mac(&b.bridge_id))
+                .any(|mac| behind.contains(&mac))
+        }))
+    }
+}
+
+// Removes the stale rows unless `dry_run`; returns how many there are
+fn prune<T>(rows: &mut Vec<T>, dry_run: bool, stale: impl Fn(&T) -> bool) -> u64 {
+    let count = rows.iter().filter(|row| stale(row)).count() as u64;
+    if !dry_run {
+        rows.retain(|row| !stale(row));
+    }
+    count
+}
+
+#[async_trait]
+impl RetentionRepository for MemoryRepository {
+    async fn apply_retention(&self, settings: &RetentionSettings) -> Result<RetentionReport, DbError> {
+        let mut state = self.state();
+        let state = &mut *state;
+        let dry_run = settings.dry_run;
+        let mut report = RetentionReport { dry_run, ..RetentionReport::default() };
+        if let Some(days) = settings.node_move_days {
+            let cutoff = retention::cutoff(days);
+            report.node_moves = prune(&mut state.node_moves, dry_run, |m| m.moved_at < cutoff);
+        }
+        if let Some(days) = settings.node_days {
+            let cutoff = retention::cutoff(days);
+            report.nodes = prune(&mut state.nodes, dry_run, |n| n.last_seen < cutoff);
+        }
+        if let Some(days) = settings.device_history_days {
+            let cutoff = retention::cutoff(days);
+            report.device_history = prune(&mut state.device_history, dry_run, |h| h.changed_at < cutoff);
+        }
+        if let Some(days) = settings.port_change_days {
+            let cutoff = retention::cutoff(days);
+            report.port_changes = prune(&mut state.port_changes, dry_run, |c| c.changed_at < cutoff);
+        }
+        if let Some(days) = settings.arp_days {
+            let cutoff = retention::cutoff(days);
+            report.arp_entries = prune(&mut state.arp_entries, dry_run, |e| e.last_seen < cutoff);
+        }
+        if let Some(days) = settings.anomaly_days {
+            let cutoff = retention::cutoff(days);
+            report.anomalies =
+                prune(&mut state.anomalies, dry_run, |a| a.resolved_at.is_some() && a.last_detected < cutoff);
+        }
+        if let Some(days) = settings.mac_violation_days {
+            let cutoff = retention::cutoff(days);
+            report.mac_violations = prune(&mut state.mac_violations, dry_run, |v| v.last_seen < cutoff);
+        }
+        if let Some(days) = settings.archive_device_days {
+            let cutoff = retention::cutoff(days);
+            let now = OffsetDateTime::now_utc();
+            let stale: Vec<usize> = (0..state.devices.len())
+                .filter(|&i| {
+                    let device = &state.devices[i];
+                    device.last_seen.is_some_and(|seen| seen < cutoff)
+                        && !device.status.as_ref().is_some_and(DeviceStatus::is_held)
+                })
+                .collect();
+            report.archived_devices = stale.len() as u64;
+            if !dry_run {
+                for i in stale {
+                    let existing = state.devices[i].clone();
+                    let retired = Device { status: Some(DeviceStatus::Retired), ..existing.clone() };
+                    let changes = history::device_changes(&existing, &retired);
+                    state.devices[i] = retired;
+                    state.record(existing.id, &[], &changes, RETENTION_SOURCE, now);
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[async_trait]
+impl VendorRepository for MemoryRepository {
+    async fn replace_mac_vendors(&self, vendors: &[MacVendor]) -> Result<u64, DbError> {
+        let mut state = self.state();
+        let lengths: Vec<usize> = vendors.iter().map(|v| v.prefix.len()).collect();
+        state.mac_vendors.retain(|v| !lengths.contains(&v.prefix.len()));
+        for vendor in vendors {
+            match state.mac_vendors.iter_mut().find(|v| v.prefix == vendor.prefix) {
+                Some(stored) => *stored = vendor.clone(),
+                None => state.mac_vendors.push(vendor.clone()),
+            }
+        }
+        state.mac_vendors.sort_by(|a, b| a.prefix.cmp(&b.prefix));
+        Ok(state.mac_vendors.iter().filter(|v| lengths.contains(&v.prefix.len())).count() as u64)
+    }
+
+    async fn list_mac_vendors(&self) -> Result<Vec<MacVendor>, DbError> {
+        Ok(self.state().mac_vendors.clone())
+    }
+}
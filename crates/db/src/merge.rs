@@ -0,0 +1,406 @@
+//! Merge rules for device upserts.
+//!
+//! Discovery often fails to read single attributes, so a missing (NULL) value
+//! never overwrites a known one. Each attribute remembers the source that last
+//! set it; values set by a manual source are kept until a manual source changes
+//! or clears them, so overrides such as a hostname survive rediscovery.
+//! Likewise, lifecycle statuses such as `retired` are only changed by a manual source.
+
+use crate::{DbError, Device, DeviceStatus, DeviceType, PgPool};
+use sqlx::PgConnection;
+use std::collections::HashMap;
+use std::str::FromStr;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+/// Source of values entered by an operator. Also matches `manual:<user>`.
+pub const MANUAL_SOURCE: &str = "manual";
+
+pub fn is_manual_source(source: &str) -> bool {
+    source == MANUAL_SOURCE || source.strip_prefix(MANUAL_SOURCE).is_some_and(|rest| rest.starts_with(':'))
+}
+
+/// Device attributes with per-field merge and source tracking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceField {
+    Hostname,
+    SysName,
+    SysDescr,
+    SysLocation,
+    Vendor,
+    Model,
+    OsVersion,
+    SerialNumber,
+    DeviceType,
+}
+
+impl DeviceField {
+    pub const ALL: [DeviceField; 9] = [
+        DeviceField::Hostname,
+        DeviceField::SysName,
+        DeviceField::SysDescr,
+        DeviceField::SysLocation,
+        DeviceField::Vendor,
+        DeviceField::Model,
+        DeviceField::OsVersion,
+        DeviceField::SerialNumber,
+        DeviceField::DeviceType,
+    ];
+
+    /// Column name, also used in device_history and device_field_sources.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeviceField::Hostname => "hostname",
+            DeviceField::SysName => "sys_name",
+            DeviceField::SysDescr => "sys_descr",
+            DeviceField::SysLocation => "sys_location",
+            DeviceField::Vendor => "vendor",
+            DeviceField::Model => "model",
+            DeviceField::OsVersion => "os_version",
+            DeviceField::SerialNumber => "serial_number",
+            DeviceField::DeviceType => "device_type",
+        }
+    }
+
+    /// The field's value as text, as stored in device_history.
+    pub fn get(&self, device: &Device) -> Option<String> {
+        match self.text(device) {
+            Some(text) => text.clone(),
+            None => device.device_type.map(|t| t.as_str().to_string()),
+        }
+    }
+
+    /// Sets the field from text, e.g. a manual override; `None` clears it.
+    pub fn set(&self, device: &mut Device, value: Option<String>) -> Result<(), DbError> {
+        match self.text_mut(device) {
+            Some(text) => *text = value,
+            None => {
+                device.device_type = value.map(DeviceType::try_from).transpose().map_err(DbError::InvalidQuery)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn is_set(&self, device: &Device) -> bool {
+        match self.text(device) {
+            Some(text) => text.is_some(),
+            None => device.device_type.is_some(),
+        }
+    }
+
+    fn copy(&self, from: &Device, to: &mut Device) {
+        match (self.text(from), self.text_mut(to)) {
+            (Some(from), Some(to)) => *to = from.clone(),
+            _ => to.device_type = from.device_type,
+        }
+    }
+
+    // None for the typed fields
+    fn text<'a>(&self, device: &'a Device) -> Option<&'a Option<String>> {
+        match self {
+            DeviceField::Hostname => Some(&device.hostname),
+            DeviceField::SysName => Some(&device.sys_name),
+            DeviceField::SysDescr => Some(&device.sys_descr),
+            DeviceField::SysLocation => Some(&device.sys_location),
+            DeviceField::Vendor => Some(&device.vendor),
+            DeviceField::Model => Some(&device.model),
+            DeviceField::OsVersion => Some(&device.os_version),
+            DeviceField::SerialNumber => Some(&device.serial_number),
+            DeviceField::DeviceType => None,
+        }
+    }
+
+    fn text_mut<'a>(&self, device: &'a mut Device) -> Option<&'a mut Option<String>> {
+        match self {
+            DeviceField::Hostname => Some(&mut device.hostname),
+            DeviceField::SysName => Some(&mut device.sys_name),
+            DeviceField::SysDescr => Some(&mut device.sys_descr),
+            DeviceField::SysLocation => Some(&mut device.sys_location),
+            DeviceField::Vendor => Some(&mut device.vendor),
+            DeviceField::Model => Some(&mut device.model),
+            DeviceField::OsVersion => Some(&mut device.os_version),
+            DeviceField::SerialNumber => Some(&mut device.serial_number),
+            DeviceField::DeviceType => None,
+        }
+    }
+}
+
+impl FromStr for DeviceField {
+    type Err = DbError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DeviceField::ALL
+            .into_iter()
+            .find(|field| field.name() == s)
+            .ok_or_else(|| DbError::InvalidQuery(format!("unknown device field '{}'", s)))
+    }
+}
+
+/// Source that last set each field of a device, keyed by field name.
+pub type FieldSources = HashMap<String, String>;
+
+/// How a field's stored source changes after a merge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SourceUpdate {
+    Set(DeviceField),
+    Remove(DeviceField),
+}
+
+/// Merges an incoming device from `source` into the stored one. Returns the
+/// device to store and the field sources to update.
+pub(crate) fn merge_device(
+    existing: &Device,
+    sources: &FieldSources,
+    incoming: &Device,
+    source: &str,
+) -> (Device, Vec<SourceUpdate>) {
+    // Lifecycle states set by an operator are not overridden by what discovery sees
+    let status = match &existing.status {
+        Some(held) if held.is_held() && !is_manual_source(source) => Some(held.clone()),
+        _ => incoming.status.clone().or_else(|| existing.status.clone()),
+    };
+    let mut merged = Device {
+        id: existing.id,
+        status,
+        last_seen: incoming.last_seen.or(existing.last_seen),
+        last_boot: later_boot(existing.last_boot, incoming.last_boot),
+        created_at: existing.created_at,
+        ..existing.clone()
+    };
+    let mut updates = Vec::new();
+    for field in DeviceField::ALL {
+        if !field.is_set(incoming) || protected(sources, field, source) {
+            continue;
+        }
+        field.copy(incoming, &mut merged);
+        updates.push(SourceUpdate::Set(field));
+    }
+    (merged, updates)
+}
+
+/// The boot time to keep: `incoming` only if the device rebooted since `stored`. Boot
+/// times worked out from sysUpTime shift by the poll's latency and clock drift, and
+/// jump by ~497 days when the 32-bit counter wraps; neither is a reboot.
+pub(crate) fn later_boot(stored: Option<OffsetDateTime>, incoming: Option<OffsetDateTime>) -> Option<OffsetDateTime> {
+    match (stored, incoming) {
+        (Some(stored), Some(incoming)) if !is_reboot(incoming - stored) => Some(stored),
+        (stored, incoming) => incoming.or(stored),
+    }
+}
+
+// Whether the boot time moving `later` (negative if earlier) means the device rebooted
+fn is_reboot(later: Duration) -> bool {
+    let wraps = (later / SYS_UPTIME_WRAP).round();
+    later > BOOT_TIME_SLACK && (wraps < 1.0 || (later - SYS_UPTIME_WRAP * wraps).abs() > BOOT_TIME_SLACK)
+}
+
+/// How far a boot time may move without counting as a reboot.
+const BOOT_TIME_SLACK: Duration = Duration::minutes(5);
+/// sysUpTime is in hundredths of a second and wraps at 2^32 of them.
+const SYS_UPTIME_WRAP: Duration = Duration::milliseconds(42_949_672_960);
+
+/// Sources to record for a newly inserted device: every field it has a value for.
+pub(crate) fn initial_sources(device: &Device) -> Vec<SourceUpdate> {
+    DeviceField::ALL.into_iter().filter(|f| f.is_set(device)).map(SourceUpdate::Set).collect()
+}
+
+/// Explicitly sets or clears (`None`) fields of a stored device. Clearing also
+/// forgets the field's source, so a cleared manual override is filled by the
+/// next discovery again. Fields overridden manually are skipped unless `source` is manual.
+/// Fails on a value the field cannot hold, such as an unknown device type.
+pub(crate) fn apply_field_values(
+    existing: &Device,
+    sources: &FieldSources,
+    values: &[(DeviceField, Option<String>)],
+    source: &str,
+) -> Result<(Device, Vec<SourceUpdate>), DbError> {
+    let mut updated = existing.clone();
+    let mut updates = Vec::new();
+    for (field, value) in values {
+        if protected(sources, *field, source) {
+            continue;
+        }
+        field.set(&mut updated, value.clone())?;
+        updates.push(match value {
+            Some(_) => SourceUpdate::Set(*field),
+            None => SourceUpdate::Remove(*field),
+        });
+    }
+    Ok((updated, updates))
+}
+
+/// The status of `existing` after setting `status`, which only a manual source may
+/// change once the device is held, e.g. retired.
+pub(crate) fn apply_status(existing: &Device, status: Option<DeviceStatus>, source: &str) -> Option<DeviceStatus> {
+    match status {
+        Some(_) if existing.status.as_ref().is_some_and(DeviceStatus::is_held) && !is_manual_source(source) => {
+            existing.status.clone()
+        }
+        Some(status) => Some(status),
+        None => existing.status.clone(),
+    }
+}
+
+/// Applies source updates to an in-memory source map.
+pub(crate) fn update_sources(sources: &mut FieldSources, updates: &[SourceUpdate], source: &str) {
+    for update in updates {
+        match update {
+            SourceUpdate::Set(field) => sources.insert(field.name().to_string(), source.to_string()),
+            SourceUpdate::Remove(field) => sources.remove(field.name()),
+        };
+    }
+}
+
+fn protected(sources: &FieldSources, field: DeviceField, source: &str) -> bool {
+    !is_manual_source(source) && sources.get(field.name()).is_some_and(|current| is_manual_source(current))
+}
+
+/// Loads the field sources of a device inside the caller's transaction.
+pub(crate) async fn load_field_sources(conn: &mut PgConnection, device_id: Uuid) -> Result<FieldSources, DbError> {
+    let rows = sqlx::query!("SELECT field, source FROM device_field_sources WHERE device_id = $1", device_id)
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(rows.into_iter().map(|row| (row.field, row.source)).collect())
+}
+
+/// Stores source updates inside the caller's transaction.
+pub(crate) async fn save_field_sources(
+    conn: &mut PgConnection,
+    device_id: Uuid,
+    updates: &[SourceUpdate],
+    source: &str,
+) -> Result<(), DbError> {
+    for update in updates {
+        match update {
+            SourceUpdate::Set(field) => {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO device_field_sources (device_id, field, source)
+                    VALUES ($1, $2, $3)
+                    ON CONFLICT (device_id, field) DO UPDATE SET
+                        source = EXCLUDED.source,
+                        updated_at = NOW()
+                    "#,
+                    device_id,
+                    field.name(),
+                    source
+                )
+                .execute(&mut *conn)
+                .await?;
+            }
+            SourceUpdate::Remove(field) => {
+                sqlx::query!(
+                    "DELETE FROM device_field_sources WHERE device_id = $1 AND field = $2",
+                    device_id,
+                    field.name()
+                )
+                .execute(&mut *conn)
+                .await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Retrieves which source last set each field of a device.
+pub async fn get_device_field_sources(pool: &PgPool, device_id: Uuid) -> Result<FieldSources, DbError> {
+    let mut conn = pool.acquire().await?;
+    load_field_sources(&mut conn, device_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device() -> Device {
+        let now = OffsetDateTime::now_utc();
+        Device {
+            id: Uuid::new_v4(),
+            hostname: Some("core-1".to_string()),
+            sys_name: Some("core-1.example.net".to_string()),
+            sys_descr: Some("Cisco IOS Software".to_string()),
+            vendor: Some("Cisco".to_string()),
+            os_version: Some("15.2".to_string()),
+            device_type: Some(DeviceType::Router),
+            last_seen: Some(now),
+            ..Device::new("192.0.2.1/32".parse().unwrap())
+        }
+    }
+
+    #[test]
+    fn nulls_do_not_clobber_known_values() {
+        let existing = device();
+        let incoming = Device {
+            id: Uuid::nil(),
+            sys_descr: None,
+            vendor: None,
+            model: Some("WS-C3850".to_string()),
+            ..existing.clone()
+        };
+
+        let (merged, updates) = merge_device(&existing, &FieldSources::new(), &incoming, "discovery");
+
+        assert_eq!(merged.id, existing.id);
+        assert_eq!(merged.sys_descr, existing.sys_descr);
+        assert_eq!(merged.vendor.as_deref(), Some("Cisco"));
+        assert_eq!(merged.model.as_deref(), Some("WS-C3850"));
+        assert!(!updates.contains(&SourceUpdate::Set(DeviceField::Vendor)));
+        assert!(updates.contains(&SourceUpdate::Set(DeviceField::Model)));
+    }
+
+    #[test]
+    fn manual_values_survive_discovery() {
+        let existing = device();
+        let mut sources = FieldSources::new();
+        let (overridden, updates) = apply_field_values(
+            &existing,
+            &sources,
+            &[(DeviceField::Hostname, Some("core-1-override".to_string()))],
+            "manual:alice",
+        )
+        .unwrap();
+        update_sources(&mut sources, &updates, "manual:alice");
+
+        let (merged, _) = merge_device(&overridden, &sources, &device(), "discovery");
+        assert_eq!(merged.hostname.as_deref(), Some("core-1-override"));
+
+        // Clearing the override hands the field back to discovery
+        let (cleared, updates) = apply_field_values(&merged, &sources, &[(DeviceField::Hostname, None)], "manual").unwrap();
+        update_sources(&mut sources, &updates, "manual");
+        let (merged, _) = merge_device(&cleared, &sources, &device(), "discovery");
+        assert_eq!(merged.hostname.as_deref(), Some("core-1"));
+    }
+
+    #[test]
+    fn discovery_keeps_lifecycle_status() {
+        let retired = Device { status: Some(DeviceStatus::Retired), ..device() };
+        let seen_up = Device { status: Some(DeviceStatus::Up), device_type: None, ..device() };
+
+        let (merged, _) = merge_device(&retired, &FieldSources::new(), &seen_up, "discovery");
+        assert_eq!(merged.status, Some(DeviceStatus::Retired));
+        assert_eq!(merged.device_type, Some(DeviceType::Router));
+
+        let (merged, _) = merge_device(&retired, &FieldSources::new(), &seen_up, "manual:alice");
+        assert_eq!(merged.status, Some(DeviceStatus::Up));
+    }
+
+    #[test]
+    fn boot_time_only_moves_on_a_reboot() {
+        let booted = device().last_seen.unwrap() - Duration::days(30);
+        let seen_booted = |at| Device { last_boot: Some(at), ..device() };
+        let existing = seen_booted(booted);
+        let merge = |incoming: &Device| merge_device(&existing, &FieldSources::new(), incoming, "discovery").0.last_boot;
+
+        // Polling latency moves the derived boot time back and forth
+        assert_eq!(merge(&seen_booted(booted - Duration::seconds(2))), Some(booted));
+        assert_eq!(merge(&seen_booted(booted + Duration::seconds(2))), Some(booted));
+        assert_eq!(merge(&device()), Some(booted));
+        // sysUpTime wrapped after 497 days rather than the device rebooting
+        assert_eq!(merge(&seen_booted(booted + SYS_UPTIME_WRAP + Duration::seconds(1))), Some(booted));
+
+        let rebooted = booted + Duration::days(29);
+        assert_eq!(merge(&seen_booted(rebooted)), Some(rebooted));
+        let changes = crate::history::device_changes(&existing, &seen_booted(rebooted));
+        assert_eq!(changes.iter().map(|c| c.attribute).collect::<Vec<_>>(), vec!["last_boot"]);
+    }
+}
@@ -0,0 +1,395 @@
+//! User-defined custom fields and free-form tags on devices and interfaces.
+//!
+//! Both are only written through [`set_device_metadata`] and
+//! [`set_interface_metadata`]; upserts from discovery leave them alone, so they
+//! survive rediscovery. Custom field values are validated against the field's
+//! [`CustomField`] schema and stored as a JSONB object keyed by field name.
+
+use crate::{
+    CustomField, CustomFieldObject, CustomFieldType, DbError, Device, Interface, MacAddress, PgPool,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::types::Json;
+use sqlx::{PgConnection, Postgres, QueryBuilder};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use time::format_description::well_known::Iso8601;
+use time::Date;
+use uuid::Uuid;
+
+/// Custom field values of a device or interface, by field name.
+pub type CustomFieldValues = BTreeMap<String, Value>;
+
+const MAX_NAME_LEN: usize = 64;
+
+/// Changes to the tags and custom fields of a device or interface.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetadataUpdate {
+    pub tags: Option<Vec<String>>, // replaces all tags when set
+    pub custom_fields: CustomFieldValues, // null removes a value; fields not listed are kept
+}
+
+/// Tags and custom field values that must all match.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetadataFilter {
+    pub tags: Vec<String>,
+    pub custom_fields: BTreeMap<String, String>, // compared with the stored value as text
+}
+
+impl MetadataFilter {
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.custom_fields.is_empty()
+    }
+
+    pub(crate) fn push_conditions(&self, builder: &mut QueryBuilder<'_, Postgres>) {
+        if !self.tags.is_empty() {
+            let tags: Vec<String> = self.tags.iter().map(|t| normalize_tag(t)).collect();
+            builder.push(" AND tags @> ").push_bind(tags);
+        }
+        for (name, value) in &self.custom_fields {
+            builder.push(" AND custom_fields ->> ").push_bind(name.clone()).push(" = ").push_bind(value.clone());
+        }
+    }
+
+    /// In-memory equivalent of the SQL conditions.
+    pub(crate) fn matches(&self, tags: &[String], values: &CustomFieldValues) -> bool {
+        self.tags.iter().all(|tag| tags.contains(&normalize_tag(tag)))
+            && self
+                .custom_fields
+                .iter()
+                .all(|(name, wanted)| values.get(name).is_some_and(|value| value_text(value) == *wanted))
+    }
+}
+
+// Text of a value as Postgres' ->> returns it
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn normalize_tag(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
+
+/// Trims, lowercases, sorts and deduplicates tags. Commas are rejected as
+/// the listing API separates tags with them.
+pub(crate) fn normalize_tags(tags: &[String]) -> Result<Vec<String>, DbError> {
+    let mut normalized = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = normalize_tag(tag);
+        if tag.is_empty() || tag.len() > MAX_NAME_LEN || tag.contains(',') {
+            return Err(DbError::InvalidQuery(format!("invalid tag '{}'", tag)));
+        }
+        normalized.push(tag);
+    }
+    normalized.sort();
+    normalized.dedup();
+    Ok(normalized)
+}
+
+impl CustomField {
+    /// Checks `value` against the field type and returns it in canonical form,
+    /// e.g. an IPv6 address compressed.
+    pub fn validate(&self, value: &Value) -> Result<Value, DbError> {
+        let invalid = || {
+            DbError::InvalidQuery(format!("invalid value {} for {} field '{}'", value, self.field_type.as_str(), self.name))
+        };
+        match (self.field_type, value) {
+            (CustomFieldType::String, Value::String(_)) => Ok(value.clone()),
+            (CustomFieldType::Int, Value::Number(number)) if number.is_i64() => Ok(value.clone()),
+            (CustomFieldType::Enum, Value::String(choice)) if self.choices.contains(choice) => Ok(value.clone()),
+            (CustomFieldType::Date, Value::String(date)) => {
+                let date = Date::parse(date, &Iso8601::DATE).map_err(|_| invalid())?;
+                Ok(Value::String(date.to_string()))
+            }
+            (CustomFieldType::Ip, Value::String(ip)) => {
+                let ip: IpAddr = ip.parse().map_err(|_| invalid())?;
+                Ok(Value::String(ip.to_string()))
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+pub(crate) fn check_custom_field(field: &CustomField) -> Result<(), DbError> {
+    let valid_name = !field.name.is_empty()
+        && field.name.len() <= MAX_NAME_LEN
+        && field.name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid_name {
+        return Err(DbError::InvalidQuery(format!(
+            "invalid custom field name '{}': use lowercase letters, digits and '_'",
+            field.name
+        )));
+    }
+    match (field.field_type, field.choices.is_empty()) {
+        (CustomFieldType::Enum, true) => Err(DbError::InvalidQuery("enum fields need choices".to_string())),
+        (CustomFieldType::Enum, false) | (_, true) => Ok(()),
+        (_, false) => Err(DbError::InvalidQuery("only enum fields have choices".to_string())),
+    }
+}
+
+/// Applies `update` to the current tags and values of an object of type `object`,
+/// validating each value against its field in `schemas`.
+pub(crate) fn apply_update(
+    schemas: &[CustomField],
+    object: CustomFieldObject,
+    tags: &[String],
+    values: &CustomFieldValues,
+    update: &MetadataUpdate,
+) -> Result<(Vec<String>, CustomFieldValues), DbError> {
+    let tags = match &update.tags {
+        Some(new_tags) => normalize_tags(new_tags)?,
+        None => tags.to_vec(),
+    };
+    let mut values = values.clone();
+    for (name, value) in &update.custom_fields {
+        if value.is_null() {
+            values.remove(name);
+            continue;
+        }
+        let field = schemas
+            .iter()
+            .find(|f| f.object_type == object && f.name == *name)
+            .ok_or_else(|| DbError::InvalidQuery(format!("unknown {} custom field '{}'", object.as_str(), name)))?;
+        values.insert(name.clone(), field.validate(value)?);
+    }
+    Ok((tags, values))
+}
+
+async fn load_custom_fields(conn: &mut PgConnection) -> Result<Vec<CustomField>, DbError> {
+    let rows = sqlx::query!(
+        r#"SELECT id, object_type::text as "object_type!", name, field_type::text as "field_type!",
+                  choices, description, created_at, updated_at
+           FROM custom_fields ORDER BY object_type, name"#
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(CustomField {
+                id: row.id,
+                object_type: CustomFieldObject::try_from(row.object_type).map_err(DbError::MappingError)?,
+                name: row.name,
+                field_type: CustomFieldType::try_from(row.field_type).map_err(DbError::MappingError)?,
+                choices: row.choices,
+                description: row.description,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            })
+        })
+        .collect()
+}
+
+/// Retrieves all custom field schemas, device fields first.
+pub async fn list_custom_fields(pool: &PgPool) -> Result<Vec<CustomField>, DbError> {
+    let mut conn = pool.acquire().await?;
+    load_custom_fields(&mut conn).await
+}
+
+/// Defines a custom field; names are unique per object type.
+pub async fn create_custom_field(pool: &PgPool, field: &CustomField) -> Result<CustomField, DbError> {
+    check_custom_field(field)?;
+    let row = sqlx::query!(
+        r#"
+        INSERT INTO custom_fields (object_type, name, field_type, choices, description)
+        VALUES ($1::custom_field_object, $2, $3::custom_field_type, $4, $5)
+        RETURNING id, created_at, updated_at
+        "#,
+        field.object_type.as_str() as &str,
+        field.name,
+        field.field_type.as_str() as &str,
+        &field.choices,
+        field.description
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| match e.as_database_error() {
+        Some(db_error) if db_error.is_unique_violation() => {
+            DbError::InvalidQuery(format!("{} custom field '{}' already exists", field.object_type.as_str(), field.name))
+        }
+        _ => DbError::from(e),
+    })?;
+    Ok(CustomField { id: row.id, created_at: row.created_at, updated_at: row.updated_at, ..field.clone() })
+}
+
+/// Deletes a custom field together with its values.
+pub async fn delete_custom_field(pool: &PgPool, id: Uuid) -> Result<(), DbError> {
+    let mut tx = pool.begin().await?;
+    let field = sqlx::query!(
+        r#"DELETE FROM custom_fields WHERE id = $1 RETURNING object_type::text as "object_type!", name"#,
+        id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    match CustomFieldObject::try_from(field.object_type).map_err(DbError::MappingError)? {
+        CustomFieldObject::Device => {
+            sqlx::query!(
+                "UPDATE devices SET custom_fields = custom_fields - $1::text WHERE custom_fields ? $1",
+                field.name
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        CustomFieldObject::Interface => {
+            sqlx::query!(
+                "UPDATE interfaces SET custom_fields = custom_fields - $1::text WHERE custom_fields ? $1",
+                field.name
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Sets the tags and custom field values of a device.
+pub async fn set_device_metadata(pool: &PgPool, device_id: Uuid, update: &MetadataUpdate) -> Result<Device, DbError> {
+    let mut tx = pool.begin().await?;
+    let existing = crate::lock_device(&mut tx, Some(device_id), None).await?.ok_or(DbError::NotFound)?;
+    let schemas = load_custom_fields(&mut tx).await?;
+    let (tags, custom_fields) =
+        apply_update(&schemas, CustomFieldObject::Device, &existing.tags, &existing.custom_fields, update)?;
+
+    let updated_at = sqlx::query_scalar!(
+        "UPDATE devices SET tags = $2, custom_fields = $3, updated_at = NOW() WHERE id = $1 RETURNING updated_at",
+        device_id,
+        &tags,
+        Json(&custom_fields) as _
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(Device { tags, custom_fields, updated_at, ..existing })
+}
+
+/// Sets the tags and custom field values of an interface.
+pub async fn set_interface_metadata(
+    pool: &PgPool,
+    interface_id: Uuid,
+    update: &MetadataUpdate,
+) -> Result<Interface, DbError> {
+    let mut tx = pool.begin().await?;
+    let row = sqlx::query!(
+        r#"SELECT tags, custom_fields as "custom_fields: Json<CustomFieldValues>"
+           FROM interfaces WHERE id = $1 FOR UPDATE"#,
+        interface_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    let schemas = load_custom_fields(&mut tx).await?;
+    let (tags, custom_fields) =
+        apply_update(&schemas, CustomFieldObject::Interface, &row.tags, &row.custom_fields.0, update)?;
+
+    let row = sqlx::query!(
+        r#"
+        UPDATE interfaces SET tags = $2, custom_fields = $3, updated_at = NOW()
+        WHERE id = $1
+        RETURNING
+            id, device_id, if_index, if_name, if_alias, if_descr, if_type,
+            mac_address as "mac_address: MacAddress",
+            ip_address, admin_status, oper_status, speed, mtu, access_vlan, last_changed,
+            tags, custom_fields as "custom_fields: Json<CustomFieldValues>", created_at, updated_at
+        "#,
+        interface_id,
+        &tags,
+        Json(&custom_fields) as _
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(Interface {
+        id: row.id,
+        device_id: row.device_id,
+        if_index: row.if_index,
+        if_name: row.if_name,
+        if_alias: row.if_alias,
+        if_descr: row.if_descr,
+        if_type: row.if_type,
+        mac_address: row.mac_address,
+        ip_address: row.ip_address,
+        admin_status: row.admin_status,
+        oper_status: row.oper_status,
+        speed: row.speed,
+        mtu: row.mtu,
+        access_vlan: row.access_vlan,
+        last_changed: row.last_changed,
+        tags: row.tags,
+        custom_fields: row.custom_fields.0,
+        created_at: row.created_at,
+        updated_at: row.updated_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use time::OffsetDateTime;
+
+    fn field(name: &str, field_type: CustomFieldType, choices: &[&str]) -> CustomField {
+        let now = OffsetDateTime::now_utc();
+        CustomField {
+            id: Uuid::new_v4(),
+            object_type: CustomFieldObject::Device,
+            name: name.to_string(),
+            field_type,
+            choices: choices.iter().map(|c| c.to_string()).collect(),
+            description: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn validates_values_by_type() {
+        let schemas = [
+            field("cost_center", CustomFieldType::Int, &[]),
+            field("tier", CustomFieldType::Enum, &["gold", "silver"]),
+            field("warranty_until", CustomFieldType::Date, &[]),
+            field("oob_ip", CustomFieldType::Ip, &[]),
+        ];
+        let current = CustomFieldValues::from([("tier".to_string(), json!("silver"))]);
+        let update = MetadataUpdate {
+            tags: Some(vec![" Core ".to_string(), "prod".to_string(), "core".to_string()]),
+            custom_fields: BTreeMap::from([
+                ("cost_center".to_string(), json!(4711)),
+                ("warranty_until".to_string(), json!("2027-03-31")),
+                ("oob_ip".to_string(), json!("2001:DB8:0:0::1")),
+                ("tier".to_string(), Value::Null),
+            ]),
+        };
+
+        let (tags, values) = apply_update(&schemas, CustomFieldObject::Device, &[], &current, &update).unwrap();
+
+        assert_eq!(tags, ["core", "prod"]);
+        assert_eq!(values.get("oob_ip"), Some(&json!("2001:db8::1")));
+        assert!(!values.contains_key("tier"));
+        assert!(MetadataFilter {
+            tags: vec!["CORE".to_string()],
+            custom_fields: BTreeMap::from([("cost_center".to_string(), "4711".to_string())]),
+        }
+        .matches(&tags, &values));
+
+        for (name, value) in [
+            ("cost_center", json!("4711")),
+            ("tier", json!("bronze")),
+            ("warranty_until", json!("31.03.2027")),
+            ("oob_ip", json!("10.0.0.300")),
+            ("owner", json!("neteng")),
+        ] {
+            let update = MetadataUpdate { tags: None, custom_fields: BTreeMap::from([(name.to_string(), value)]) };
+            assert!(apply_update(&schemas, CustomFieldObject::Device, &[], &current, &update).is_err(), "{}", name);
+        }
+        // Device fields do not apply to interfaces
+        let update = MetadataUpdate { tags: None, custom_fields: BTreeMap::from([("cost_center".to_string(), json!(1))]) };
+        assert!(apply_update(&schemas, CustomFieldObject::Interface, &[], &current, &update).is_err());
+    }
+}
@@ -0,0 +1,197 @@
+//! Schema migrations from `/migrations` (SQLite: `/migrations/sqlite`), embedded in
+//! the binary at build time.
+
+use crate::{DbError, DbPool};
+use sqlx::migrate::{Migrate, Migrator};
+use sqlx::{Database, Pool};
+
+static MIGRATOR: Migrator = sqlx::migrate!("../../migrations");
+
+#[cfg(feature = "sqlite")]
+static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("../../migrations/sqlite");
+
+/// State of one migration as seen by [`migration_status`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+    pub known: bool, // false when applied by a newer binary
+}
+
+/// Versions recorded in the database's migration table, oldest first.
+async fn applied_versions<DB>(pool: &Pool<DB>) -> Result<Vec<i64>, DbError>
+where
+    DB: Database,
+    DB::Connection: Migrate,
+{
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let mut versions: Vec<i64> = conn.list_applied_migrations().await?.into_iter().map(|m| m.version).collect();
+    versions.sort_unstable();
+    Ok(versions)
+}
+
+async fn check_version<DB>(migrator: &Migrator, pool: &Pool<DB>) -> Result<(), DbError>
+where
+    DB: Database,
+    DB::Connection: Migrate,
+{
+    let latest_known = migrator.iter().map(|m| m.version).max().unwrap_or(0);
+    match applied_versions(pool).await?.into_iter().find(|v| !migrator.version_exists(*v)) {
+        Some(unknown) => Err(DbError::SchemaTooNew { applied: unknown, latest_known }),
+        None => Ok(()),
+    }
+}
+
+async fn run<DB>(migrator: &Migrator, pool: &Pool<DB>) -> Result<(), DbError>
+where
+    DB: Database,
+    DB::Connection: Migrate,
+{
+    check_version(migrator, pool).await?;
+    migrator.run(pool).await?;
+    Ok(())
+}
+
+async fn undo<DB>(migrator: &Migrator, pool: &Pool<DB>) -> Result<Option<i64>, DbError>
+where
+    DB: Database,
+    DB::Connection: Migrate,
+{
+    check_version(migrator, pool).await?;
+    let applied = applied_versions(pool).await?;
+    let Some((&latest, earlier)) = applied.split_last() else {
+        return Ok(None);
+    };
+    migrator.undo(pool, earlier.last().copied().unwrap_or(0)).await?;
+    Ok(Some(latest))
+}
+
+async fn status<DB>(migrator: &Migrator, pool: &Pool<DB>) -> Result<Vec<MigrationStatus>, DbError>
+where
+    DB: Database,
+    DB::Connection: Migrate,
+{
+    let applied = applied_versions(pool).await?;
+    let mut status: Vec<MigrationStatus> = migrator
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .map(|m| MigrationStatus {
+            version: m.version,
+            description: m.description.to_string(),
+            applied: applied.contains(&m.version),
+            known: true,
+        })
+        .collect();
+    status.extend(applied.iter().filter(|v| !migrator.version_exists(**v)).map(|&version| MigrationStatus {
+        version,
+        description: String::new(),
+        applied: true,
+        known: false,
+    }));
+    Ok(status)
+}
+
+/// Fails with `SchemaTooNew` when the database has migrations this binary does not know,
+/// i.e. it was migrated by a newer release whose schema this one may not handle.
+pub async fn check_schema_version(pool: &DbPool) -> Result<(), DbError> {
+    match pool {
+        DbPool::Postgres(pool) => check_version(&MIGRATOR, pool).await,
+        #[cfg(feature = "sqlite")]
+        DbPool::Sqlite(pool) => check_version(&SQLITE_MIGRATOR, pool).await,
+    }
+}
+
+/// Applies all pending migrations.
+pub async fn migrate(pool: &DbPool) -> Result<(), DbError> {
+    match pool {
+        DbPool::Postgres(pool) => run(&MIGRATOR, pool).await,
+        #[cfg(feature = "sqlite")]
+        DbPool::Sqlite(pool) => run(&SQLITE_MIGRATOR, pool).await,
+    }
+}
+
+/// Reverts the most recently applied migration. Returns its version, or `None`
+/// when nothing was applied.
+pub async fn migrate_down(pool: &DbPool) -> Result<Option<i64>, DbError> {
+    match pool {
+        DbPool::Postgres(pool) => undo(&MIGRATOR, pool).await,
+        #[cfg(feature = "sqlite")]
+        DbPool::Sqlite(pool) => undo(&SQLITE_MIGRATOR, pool).await,
+    }
+}
+
+/// Lists embedded migrations with whether each is applied, followed by any
+/// applied migrations this binary does not know.
+pub async fn migration_status(pool: &DbPool) -> Result<Vec<MigrationStatus>, DbError> {
+    match pool {
+        DbPool::Postgres(pool) => status(&MIGRATOR, pool).await,
+        #[cfg(feature = "sqlite")]
+        DbPool::Sqlite(pool) => status(&SQLITE_MIGRATOR, pool).await,
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+
+    async fn pool() -> DbPool {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1) // Every connection to :memory: is its own database
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        DbPool::Sqlite(pool)
+    }
+
+    fn latest() -> i64 {
+        SQLITE_MIGRATOR.iter().map(|m| m.version).max().unwrap()
+    }
+
+    #[tokio::test]
+    async fn applies_reverts_and_reports_migrations() {
+        let pool = pool().await;
+        let status = migration_status(&pool).await.unwrap();
+        assert!(!status.is_empty());
+        assert!(status.iter().all(|m| m.known && !m.applied));
+
+        migrate(&pool).await.unwrap();
+        assert!(migration_status(&pool).await.unwrap().iter().all(|m| m.applied));
+        migrate(&pool).await.unwrap(); // Nothing left to apply
+
+        assert_eq!(migrate_down(&pool).await.unwrap(), Some(latest()));
+        let status = migration_status(&pool).await.unwrap();
+        let pending: Vec<i64> = status.iter().filter(|m| !m.applied).map(|m| m.version).collect();
+        assert_eq!(pending, [latest()]);
+
+        migrate(&pool).await.unwrap();
+        assert!(migration_status(&pool).await.unwrap().iter().all(|m| m.applied));
+        check_schema_version(&pool).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn refuses_a_schema_from_a_newer_release() {
+        let pool = pool().await;
+        migrate(&pool).await.unwrap();
+        let DbPool::Sqlite(sqlite) = &pool else { unreachable!() };
+        let newer = latest() + 1;
+        sqlx::query(
+            "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time) \
+             VALUES (?, 'from a newer release', 1, x'00', 0)",
+        )
+        .bind(newer)
+        .execute(sqlite)
+        .await
+        .unwrap();
+
+        for result in [check_schema_version(&pool).await, migrate(&pool).await, migrate_down(&pool).await.map(|_| ())] {
+            assert!(
+                matches!(result, Err(DbError::SchemaTooNew { applied, latest_known }) if applied == newer && latest_known == latest())
+            );
+        }
+        let status = migration_status(&pool).await.unwrap();
+        let unknown = status.last().unwrap();
+        assert_eq!((unknown.version, unknown.applied, unknown.known), (newer, true, false));
+    }
+}
@@ -3,14 +3,46 @@ use uuid::Uuid;
 use ipnetwork::IpNetwork;
 use sqlx::{FromRow, Type};
 use serde::{Serialize, Deserialize};
+use crate::{CustomFieldValues, DeviceFilter, MacAddress};
 
-// Mirror the device_status enum from the migration
+// Mirror the device_status enum from the migration.
+// Up/Down/Unknown are reachability as seen by discovery; the rest are lifecycle
+// states set by operators.
 #[derive(Debug, Clone, PartialEq, Eq, Type, Serialize, Deserialize)]
 #[sqlx(type_name = "device_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
 pub enum DeviceStatus {
     Up,
     Down,
     Unknown,
+    Active, // In service; discovery replaces it with Up/Down on the next poll
+    Maintenance,
+    Retired,
+    Decommissioned,
+}
+
+impl DeviceStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeviceStatus::Up => "up",
+            DeviceStatus::Down => "down",
+            DeviceStatus::Unknown => "unknown",
+            DeviceStatus::Active => "active",
+            DeviceStatus::Maintenance => "maintenance",
+            DeviceStatus::Retired => "retired",
+            DeviceStatus::Decommissioned => "decommissioned",
+        }
+    }
+
+    /// Lifecycle states discovery must keep; only a manual source changes them.
+    pub fn is_held(&self) -> bool {
+        matches!(self, DeviceStatus::Maintenance | DeviceStatus::Retired | DeviceStatus::Decommissioned)
+    }
+
+    /// Out of service for good: not polled, and hidden from listings by default.
+    pub fn is_retired(&self) -> bool {
+        matches!(self, DeviceStatus::Retired | DeviceStatus::Decommissioned)
+    }
 }
 
 // Allow converting from String (case-insensitive) for manual mapping
@@ -22,11 +54,63 @@ impl TryFrom<String> for DeviceStatus {
             "up" => Ok(DeviceStatus::Up),
             "down" => Ok(DeviceStatus::Down),
             "unknown" => Ok(DeviceStatus::Unknown),
+            "active" => Ok(DeviceStatus::Active),
+            "maintenance" => Ok(DeviceStatus::Maintenance),
+            "retired" => Ok(DeviceStatus::Retired),
+            "decommissioned" => Ok(DeviceStatus::Decommissioned),
             _ => Err(format!("Invalid device status string: {}", value)),
         }
     }
 }
 
+// Mirror the device_type enum from the migration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Type, Serialize, Deserialize)]
+#[sqlx(type_name = "device_type", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceType {
+    Router,
+    Switch,
+    Firewall,
+    AccessPoint,
+    Host,
+    Printer,
+    Storage,
+    Unknown,
+}
+
+impl DeviceType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeviceType::Router => "router",
+            DeviceType::Switch => "switch",
+            DeviceType::Firewall => "firewall",
+            DeviceType::AccessPoint => "access_point",
+            DeviceType::Host => "host",
+            DeviceType::Printer => "printer",
+            DeviceType::Storage => "storage",
+            DeviceType::Unknown => "unknown",
+        }
+    }
+}
+
+impl TryFrom<String> for DeviceType {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "router" => Ok(DeviceType::Router),
+            "switch" => Ok(DeviceType::Switch),
+            "firewall" => Ok(DeviceType::Firewall),
+            "access_point" => Ok(DeviceType::AccessPoint),
+            "host" => Ok(DeviceType::Host),
+            "printer" => Ok(DeviceType::Printer),
+            "storage" => Ok(DeviceType::Storage),
+            "unknown" => Ok(DeviceType::Unknown),
+            _ => Err(format!("Invalid device type string: {}", value)),
+        }
+    }
+}
+
 // Struct corresponding to the 'devices' table
 #[derive(Debug, Clone, PartialEq, FromRow, Serialize, Deserialize)]
 pub struct Device {
@@ -35,16 +119,52 @@ pub struct Device {
     pub ip_address: IpNetwork, // sqlx maps INET to ipnetwork::IpNetwork
     pub sys_name: Option<String>,
     pub sys_descr: Option<String>,
+    pub sys_location: Option<String>,
     pub vendor: Option<String>,
     pub model: Option<String>,
     pub os_version: Option<String>,
     pub serial_number: Option<String>,
     pub status: Option<DeviceStatus>, // Mapped from device_status enum
+    pub device_type: Option<DeviceType>, // Mapped from device_type enum
+    pub location_id: Option<Uuid>, // Most specific location (site, building, room or rack)
+    pub tags: Vec<String>, // Set by users only, like custom_fields
+    #[sqlx(json)]
+    pub custom_fields: CustomFieldValues,
     pub last_seen: Option<OffsetDateTime>, // TIMESTAMPTZ maps to OffsetDateTime
-    pub created_at: OffsetDateTime, 
+    pub last_boot: Option<OffsetDateTime>, // From sysUpTime; only moves forward, on a reboot
+    pub created_at: OffsetDateTime,
     pub updated_at: OffsetDateTime,
 }
 
+impl Device {
+    /// A device known only by its management address; set the rest with struct update syntax.
+    /// The id is nil until the device is stored.
+    pub fn new(ip_address: IpNetwork) -> Self {
+        let now = OffsetDateTime::now_utc();
+        Device {
+            id: Uuid::nil(),
+            hostname: None,
+            ip_address,
+            sys_name: None,
+            sys_descr: None,
+            sys_location: None,
+            vendor: None,
+            model: None,
+            os_version: None,
+            serial_number: None,
+            status: None,
+            device_type: None,
+            location_id: None,
+            tags: Vec::new(),
+            custom_fields: CustomFieldValues::new(),
+            last_seen: None,
+            last_boot: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
 // Struct corresponding to the 'interfaces' table
 #[derive(Debug, Clone, PartialEq, FromRow, Serialize, Deserialize)]
 pub struct Interface {
@@ -55,14 +175,561 @@ pub struct Interface {
     pub if_alias: Option<String>,
     pub if_descr: Option<String>,
     pub if_type: Option<String>,
-    // pub mac_address: Option<[u8; 6]>, // sqlx currently needs feature for MACADDR, handle differently or use String for now
-    pub mac_address: Option<String>, // Using String for MAC for now
+    pub mac_address: Option<MacAddress>, // MACADDR
     pub ip_address: Option<IpNetwork>,
     pub admin_status: Option<String>,
     pub oper_status: Option<String>,
     pub speed: Option<i64>, // BIGINT maps to i64
     pub mtu: Option<i32>,
+    pub access_vlan: Option<i32>,
     pub last_changed: Option<OffsetDateTime>,
+    pub tags: Vec<String>, // Set by users only, like custom_fields
+    #[sqlx(json)]
+    pub custom_fields: CustomFieldValues,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+} 
+// Routing protocol a neighbor was learned from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RoutingProtocol {
+    Bgp,
+    Ospf,
+}
+
+impl RoutingProtocol {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RoutingProtocol::Bgp => "bgp",
+            RoutingProtocol::Ospf => "ospf",
+        }
+    }
+}
+
+impl TryFrom<String> for RoutingProtocol {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "bgp" => Ok(RoutingProtocol::Bgp),
+            "ospf" => Ok(RoutingProtocol::Ospf),
+            _ => Err(format!("Invalid routing protocol string: {}", value)),
+        }
+    }
+}
+
+// Struct corresponding to the 'routing_neighbors' table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoutingNeighbor {
+    pub id: Uuid,
+    pub device_id: Uuid,
+    pub protocol: RoutingProtocol,
+    pub neighbor_address: IpNetwork,
+    pub remote_router_id: Option<IpNetwork>,
+    pub remote_as: Option<i64>, // BGP only
+    pub state: String,
+    pub uptime_seconds: Option<i64>, // BGP only
+    pub last_seen: OffsetDateTime,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+// Struct corresponding to the 'stp_instances' table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StpInstance {
+    pub id: Uuid,
+    pub device_id: Uuid,
+    pub instance: i32, // 0 = default tree (the CIST on MSTP bridges), otherwise VLAN ID or MST instance
+    pub protocol: String, // stp (instance 0), pvst (per-VLAN trees) or mst (MST instances)
+    pub vlans: Vec<i32>, // VLANs carried by the tree; empty for instance 0, which carries the rest
+    pub bridge_id: String,
+    pub designated_root: String,
+    pub root_cost: Option<i32>,
+    pub root_port_if_index: Option<i32>,
+    pub root_device_id: Option<Uuid>, // Read-only: device whose bridge ID is the designated root
+    pub last_seen: OffsetDateTime,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+/// The root bridge of one spanning tree instance, as reported across devices.
+/// Devices that disagree on the root (e.g., in separate MST regions) get a row each.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StpRoot {
+    pub protocol: String,
+    pub instance: i32,
+    pub vlans: Vec<i32>,
+    pub designated_root: String,
+    pub root_device_id: Option<Uuid>, // Device whose bridge ID is the designated root, if known
+    pub device_ids: Vec<Uuid>, // Devices reporting this root
+    pub blocking_ports: i64, // On those devices
+}
+
+// Struct corresponding to the 'stp_ports' table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StpPort {
+    pub id: Uuid,
+    pub interface_id: Uuid,
+    pub if_index: i32, // Read from/matched against the interfaces table
+    pub instance: i32,
+    pub state: String,
+    pub role: Option<String>,
+    pub path_cost: Option<i32>,
+    pub designated_bridge: Option<String>,
+    pub last_seen: OffsetDateTime,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+// Struct corresponding to the 'nodes' table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Node {
+    pub id: Uuid,
+    pub mac_address: MacAddress,
+    pub device_id: Uuid, // Switch for wired nodes, AP for wireless clients
+    pub interface_id: Option<Uuid>,
+    pub vlan: i32, // 0 when unknown
+    pub active: bool,
+    pub first_seen: OffsetDateTime,
+    pub last_seen: OffsetDateTime,
+    pub wireless: Option<NodeWireless>, // From 'node_wireless', if associated over Wi-Fi
+}
+
+// Struct corresponding to the 'node_wireless' table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeWireless {
+    pub ssid: Option<String>,
+    pub rssi: Option<i32>, // dBm
+    pub ip_address: Option<IpNetwork>,
+    pub last_seen: OffsetDateTime,
+}
+
+// Struct corresponding to the 'wireless_aps' table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WirelessAp {
+    pub device_id: Uuid,
+    pub controller_id: Uuid,
+    pub mac_address: MacAddress,
+    pub name: Option<String>,
+    pub location: Option<String>,
+    pub oper_status: Option<String>,
+    pub last_seen: OffsetDateTime,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+// Struct corresponding to the 'interface_poe' table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InterfacePoe {
+    pub interface_id: Uuid,
+    pub if_index: i32, // Read from/matched against the interfaces table
+    pub group_index: i32,
+    pub port_index: i32,
+    pub admin_enabled: bool,
+    pub detection_status: Option<String>,
+    pub power_class: Option<String>,
+    pub power_mw: Option<i32>,
+    pub last_seen: OffsetDateTime,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+// Struct corresponding to the 'device_history' table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceHistory {
+    pub id: Uuid,
+    pub device_id: Uuid,
+    pub attribute: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub source: String,
+    pub changed_at: OffsetDateTime,
+}
+
+// Struct corresponding to the 'port_changes' audit table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PortChange {
+    pub id: Uuid,
+    pub device_id: Uuid,
+    pub interface_id: Option<Uuid>,
+    pub if_index: i32,
+    pub action: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub changed_by: String,
+    pub succeeded: bool,
+    pub error: Option<String>,
+    pub changed_at: OffsetDateTime,
+}
+
+// Mirror the location_kind enum; each kind sits directly below the previous one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Type, Serialize, Deserialize)]
+#[sqlx(type_name = "location_kind", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum LocationKind {
+    Site,
+    Building,
+    Room,
+    Rack,
+}
+
+impl LocationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LocationKind::Site => "site",
+            LocationKind::Building => "building",
+            LocationKind::Room => "room",
+            LocationKind::Rack => "rack",
+        }
+    }
+
+    /// Kind of the required parent; sites are top-level.
+    pub fn parent_kind(&self) -> Option<LocationKind> {
+        match self {
+            LocationKind::Site => None,
+            LocationKind::Building => Some(LocationKind::Site),
+            LocationKind::Room => Some(LocationKind::Building),
+            LocationKind::Rack => Some(LocationKind::Room),
+        }
+    }
+}
+
+impl TryFrom<String> for LocationKind {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "site" => Ok(LocationKind::Site),
+            "building" => Ok(LocationKind::Building),
+            "room" => Ok(LocationKind::Room),
+            "rack" => Ok(LocationKind::Rack),
+            _ => Err(format!("Invalid location kind string: {}", value)),
+        }
+    }
+}
+
+// Struct corresponding to the 'locations' table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    pub id: Uuid,
+    pub parent_id: Option<Uuid>, // None for sites
+    pub kind: LocationKind,
+    pub name: String,
+    pub description: Option<String>,
     pub created_at: OffsetDateTime,
     pub updated_at: OffsetDateTime,
-} 
\ No newline at end of file
+}
+
+// Struct corresponding to the 'location_rules' table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LocationRule {
+    pub id: Uuid,
+    pub location_id: Uuid,
+    pub priority: i32, // Lowest matching priority wins
+    pub sys_location_pattern: Option<String>, // Case-insensitive substring of sys_location
+    pub subnet: Option<IpNetwork>,
+    pub created_at: OffsetDateTime,
+}
+
+// Struct corresponding to the 'device_groups' table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceGroup {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub rules: Option<DeviceFilter>, // Dynamic group membership; None for static groups
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+// Mirror the custom_field_object enum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Type, Serialize, Deserialize)]
+#[sqlx(type_name = "custom_field_object", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum CustomFieldObject {
+    Device,
+    Interface,
+}
+
+impl CustomFieldObject {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CustomFieldObject::Device => "device",
+            CustomFieldObject::Interface => "interface",
+        }
+    }
+}
+
+impl TryFrom<String> for CustomFieldObject {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "device" => Ok(CustomFieldObject::Device),
+            "interface" => Ok(CustomFieldObject::Interface),
+            _ => Err(format!("Invalid custom field object string: {}", value)),
+        }
+    }
+}
+
+// Mirror the custom_field_type enum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Type, Serialize, Deserialize)]
+#[sqlx(type_name = "custom_field_type", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum CustomFieldType {
+    String,
+    Int,
+    Enum, // one of the field's choices
+    Date, // YYYY-MM-DD
+    Ip,
+}
+
+impl CustomFieldType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CustomFieldType::String => "string",
+            CustomFieldType::Int => "int",
+            CustomFieldType::Enum => "enum",
+            CustomFieldType::Date => "date",
+            CustomFieldType::Ip => "ip",
+        }
+    }
+}
+
+impl TryFrom<String> for CustomFieldType {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "string" => Ok(CustomFieldType::String),
+            "int" => Ok(CustomFieldType::Int),
+            "enum" => Ok(CustomFieldType::Enum),
+            "date" => Ok(CustomFieldType::Date),
+            "ip" => Ok(CustomFieldType::Ip),
+            _ => Err(format!("Invalid custom field type string: {}", value)),
+        }
+    }
+}
+
+// Struct corresponding to the 'custom_fields' table: the schema of one user-defined field
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustomField {
+    pub id: Uuid,
+    pub object_type: CustomFieldObject,
+    pub name: String,
+    pub field_type: CustomFieldType,
+    pub choices: Vec<String>, // Allowed values of enum fields, empty otherwise
+    pub description: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+// Mirror the anomaly_kind enum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Type, Serialize, Deserialize)]
+#[sqlx(type_name = "anomaly_kind", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyKind {
+    DuplicateIp,      // One address claimed by several MACs at the same time
+    MacFlapping,      // A MAC moving between switch ports again and again
+    MacMultiplePorts, // A MAC active on more than one access port at once
+}
+
+impl AnomalyKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnomalyKind::DuplicateIp => "duplicate_ip",
+            AnomalyKind::MacFlapping => "mac_flapping",
+            AnomalyKind::MacMultiplePorts => "mac_multiple_ports",
+        }
+    }
+}
+
+impl TryFrom<String> for AnomalyKind {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "duplicate_ip" => Ok(AnomalyKind::DuplicateIp),
+            "mac_flapping" => Ok(AnomalyKind::MacFlapping),
+            "mac_multiple_ports" => Ok(AnomalyKind::MacMultiplePorts),
+            _ => Err(format!("Invalid anomaly kind string: {}", value)),
+        }
+    }
+}
+
+// Struct corresponding to the 'anomalies' table: one finding from its first to its last detection
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Anomaly {
+    pub id: Uuid,
+    pub kind: AnomalyKind,
+    pub ip_address: Option<IpNetwork>, // duplicate_ip
+    pub mac_address: Option<MacAddress>, // mac_flapping, mac_multiple_ports
+    pub mac_addresses: Vec<MacAddress>, // duplicate_ip: the MACs claiming the address
+    pub device_ids: Vec<Uuid>, // Switches and APs that saw it
+    pub interface_ids: Vec<Uuid>, // Ports involved
+    pub moves: Option<i32>, // mac_flapping: port moves within the detection window
+    pub first_detected: OffsetDateTime,
+    pub last_detected: OffsetDateTime,
+    pub resolved_at: Option<OffsetDateTime>, // Set once a detection run no longer finds it
+}
+
+// Struct corresponding to the 'mac_allowlist' table: MACs approved for a site or VLAN
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AllowedMac {
+    pub id: Uuid,
+    pub mac_prefix: String, // Lowercase hex: an OUI (6 digits), MA-M/MA-S block or full MAC (12 digits)
+    pub location_id: Option<Uuid>, // Devices in this location or below; everywhere when None
+    pub vlan: Option<i32>, // Every VLAN when None
+    pub description: Option<String>,
+    pub created_at: OffsetDateTime,
+}
+
+// Struct corresponding to the 'mac_violations' table: an unknown MAC on a switch or AP
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MacViolation {
+    pub id: Uuid,
+    pub mac_address: MacAddress,
+    pub device_id: Uuid, // Switch for wired nodes, AP for wireless clients
+    pub interface_id: Option<Uuid>, // Port it was last seen on; None for wireless clients
+    pub vlan: i32,
+    pub first_seen: OffsetDateTime,
+    pub last_seen: OffsetDateTime,
+    pub port_shutdown_at: Option<OffsetDateTime>, // When the rogue policy shut the port
+}
+
+// Struct corresponding to the 'mac_vendors' table: one IEEE assignment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MacVendor {
+    pub prefix: String, // Lowercase hex: 6 digits for MA-L (OUI), 7 for MA-M, 9 for MA-S
+    pub organization: String,
+}
+
+// Struct corresponding to the 'arp_entries' table: an IP to MAC binding on a router
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArpEntry {
+    pub id: Uuid,
+    pub device_id: Uuid,
+    pub interface_id: Option<Uuid>, // Layer 3 interface the entry was learned on
+    pub ip_address: IpNetwork, // Host address (/32 or /128)
+    pub mac_address: MacAddress,
+    pub first_seen: OffsetDateTime,
+    pub last_seen: OffsetDateTime,
+}
+
+// Struct corresponding to the 'subnets' table: a network configured on some interface
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Subnet {
+    pub id: Uuid,
+    pub network: IpNetwork, // Network address and prefix, e.g. 10.1.20.0/24
+    pub first_seen: OffsetDateTime,
+    pub last_seen: OffsetDateTime,
+}
+
+// Struct corresponding to the 'ip_hostnames' table: the PTR name of an address
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IpHostname {
+    pub ip_address: IpNetwork, // Host address (/32 or /128)
+    pub hostname: String,      // Without the trailing dot
+    pub resolved_at: OffsetDateTime,
+}
+
+// Discovery protocol a device neighbor was learned from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NeighborProtocol {
+    Lldp,
+    Cdp,
+}
+
+impl NeighborProtocol {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NeighborProtocol::Lldp => "lldp",
+            NeighborProtocol::Cdp => "cdp",
+        }
+    }
+}
+
+impl TryFrom<String> for NeighborProtocol {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "lldp" => Ok(NeighborProtocol::Lldp),
+            "cdp" => Ok(NeighborProtocol::Cdp),
+            _ => Err(format!("Invalid neighbor protocol string: {}", value)),
+        }
+    }
+}
+
+// Struct corresponding to the 'device_neighbors' table: a device announcing itself on a port
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceNeighbor {
+    pub id: Uuid,
+    pub device_id: Uuid,
+    pub interface_id: Option<Uuid>, // Local port
+    pub protocol: NeighborProtocol,
+    pub remote_chassis_id: Option<String>, // LLDP chassis ID or CDP device ID
+    pub remote_port: Option<String>,
+    pub remote_sys_name: Option<String>,
+    pub remote_address: Option<IpNetwork>, // Management address
+    pub last_seen: OffsetDateTime,
+}
+
+// How a topology link is known; declared from most to least trusted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkSource {
+    Manual,
+    Lldp,
+    Cdp,
+    Fdb, // Inferred from switches seeing each other's MACs
+}
+
+impl LinkSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkSource::Manual => "manual",
+            LinkSource::Lldp => "lldp",
+            LinkSource::Cdp => "cdp",
+            LinkSource::Fdb => "fdb",
+        }
+    }
+}
+
+impl From<NeighborProtocol> for LinkSource {
+    fn from(protocol: NeighborProtocol) -> Self {
+        match protocol {
+            NeighborProtocol::Lldp => LinkSource::Lldp,
+            NeighborProtocol::Cdp => LinkSource::Cdp,
+        }
+    }
+}
+
+impl TryFrom<String> for LinkSource {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "manual" => Ok(LinkSource::Manual),
+            "lldp" => Ok(LinkSource::Lldp),
+            "cdp" => Ok(LinkSource::Cdp),
+            "fdb" => Ok(LinkSource::Fdb),
+            _ => Err(format!("Invalid link source string: {}", value)),
+        }
+    }
+}
+
+// Struct corresponding to the 'topology_links' table: a connection between two devices
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopologyLink {
+    pub id: Uuid,
+    pub device_id: Uuid,
+    pub interface_id: Option<Uuid>, // None when the port is not known
+    pub remote_device_id: Uuid,
+    pub remote_interface_id: Option<Uuid>,
+    pub source: LinkSource,
+    pub confidence: i16, // 0-100
+    pub first_seen: OffsetDateTime,
+    pub last_seen: OffsetDateTime,
+}
@@ -0,0 +1,184 @@
+use crate::{DbError, MacAddress, Node, NodeWireless, PgPool};
+use sqlx::postgres::PgRow;
+use sqlx::Row;
+use std::collections::{BTreeMap, BTreeSet};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// Records a sighting of a node, inserting it or refreshing `last_seen`.
+/// The wireless details are replaced when given. Returns the node's ID.
+pub async fn upsert_node(pool: &PgPool, node: &Node) -> Result<Uuid, DbError> {
+    let mut tx = pool.begin().await?;
+
+    let node_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO nodes (mac_address, device_id, interface_id, vlan, active, last_seen)
+        VALUES ($1, $2, $3, $4, TRUE, $5)
+        ON CONFLICT (mac_address, device_id, vlan) DO UPDATE SET
+            interface_id = EXCLUDED.interface_id,
+            active = TRUE,
+            last_seen = EXCLUDED.last_seen,
+            updated_at = NOW()
+        RETURNING id
+        "#,
+        node.mac_address as _,
+        node.device_id,
+        node.interface_id,
+        node.vlan,
+        node.last_seen
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if let Some(wireless) = &node.wireless {
+        sqlx::query!(
+            r#"
+            INSERT INTO node_wireless (node_id, ssid, rssi, ip_address, last_seen)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (node_id) DO UPDATE SET
+                ssid = EXCLUDED.ssid,
+                rssi = EXCLUDED.rssi,
+                ip_address = EXCLUDED.ip_address,
+                last_seen = EXCLUDED.last_seen
+            "#,
+            node_id,
+            wireless.ssid,
+            wireless.rssi,
+            wireless.ip_address,
+            wireless.last_seen
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(node_id)
+}
+
+pub(crate) const NODE_QUERY: &str = "SELECT n.id, n.mac_address, n.device_id, n.interface_id, n.vlan, n.active, n.first_seen, \
+     n.last_seen, w.node_id as wireless_node_id, w.ssid, w.rssi, w.ip_address as wireless_ip_address, \
+     w.last_seen as wireless_last_seen FROM nodes n LEFT JOIN node_wireless w ON w.node_id = n.id";
+
+pub(crate) fn node_from_row(row: &PgRow) -> Result<Node, DbError> {
+    let wireless = match row.try_get::<Option<Uuid>, _>("wireless_node_id")? {
+        Some(_) => Some(NodeWireless {
+            ssid: row.try_get("ssid")?,
+            rssi: row.try_get("rssi")?,
+            ip_address: row.try_get("wireless_ip_address")?,
+            last_seen: row.try_get("wireless_last_seen")?,
+        }),
+        None => None,
+    };
+    Ok(Node {
+        id: row.try_get("id")?,
+        mac_address: row.try_get("mac_address")?,
+        device_id: row.try_get("device_id")?,
+        interface_id: row.try_get("interface_id")?,
+        vlan: row.try_get("vlan")?,
+        active: row.try_get("active")?,
+        first_seen: row.try_get("first_seen")?,
+        last_seen: row.try_get("last_seen")?,
+        wireless,
+    })
+}
+
+/// Ports among the sightings that carry at most `max_macs` distinct MACs. Busier ports are
+/// taken for uplinks, which carry every MAC behind them.
+pub(crate) fn access_ports<'a>(nodes: impl IntoIterator<Item = &'a Node>, max_macs: u32) -> BTreeSet<Uuid> {
+    let mut port_macs: BTreeMap<Uuid, BTreeSet<MacAddress>> = BTreeMap::new();
+    for node in nodes {
+        if let Some(interface_id) = node.interface_id {
+            port_macs.entry(interface_id).or_default().insert(node.mac_address);
+        }
+    }
+    port_macs.into_iter().filter(|(_, macs)| macs.len() <= max_macs as usize).map(|(port, _)| port).collect()
+}
+
+/// Marks nodes on the given devices that were not seen since `seen_since` as inactive.
+/// Called after a collection run so nodes that moved or left stop showing as present.
+/// Returns the number of nodes deactivated.
+pub async fn deactivate_nodes(pool: &PgPool, device_ids: &[Uuid], seen_since: OffsetDateTime) -> Result<u64, DbError> {
+    let result = sqlx::query!(
+        r#"UPDATE nodes SET active = FALSE
+           WHERE device_id = ANY($1) AND active AND last_seen < $2"#,
+        device_ids,
+        seen_since
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Retrieves the active nodes on a device (switch or AP), with wireless details if any.
+pub async fn list_nodes(pool: &PgPool, device_id: Uuid) -> Result<Vec<Node>, DbError> {
+    let rows = sqlx::query!(
+        r#"SELECT
+              n.id, n.mac_address as "mac_address: MacAddress", n.device_id, n.interface_id, n.vlan,
+              n.active, n.first_seen, n.last_seen,
+              w.node_id as "wireless_node_id?", w.ssid as "ssid?", w.rssi as "rssi?",
+              w.ip_address as "wireless_ip_address?", w.last_seen as "wireless_last_seen?"
+           FROM nodes n LEFT JOIN node_wireless w ON w.node_id = n.id
+           WHERE n.device_id = $1 AND n.active
+           ORDER BY n.mac_address, n.vlan"#,
+        device_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Node {
+            id: row.id,
+            mac_address: row.mac_address,
+            device_id: row.device_id,
+            interface_id: row.interface_id,
+            vlan: row.vlan,
+            active: row.active,
+            first_seen: row.first_seen,
+            last_seen: row.last_seen,
+            wireless: row.wireless_node_id.zip(row.wireless_last_seen).map(|(_, last_seen)| NodeWireless {
+                ssid: row.ssid,
+                rssi: row.rssi,
+                ip_address: row.wireless_ip_address,
+                last_seen,
+            }),
+        })
+        .collect())
+}
+
+/// Retrieves every sighting of a MAC address, active or not, most recent first.
+pub async fn find_nodes_by_mac(pool: &PgPool, mac_address: MacAddress) -> Result<Vec<Node>, DbError> {
+    let rows = sqlx::query!(
+        r#"SELECT
+              n.id, n.mac_address as "mac_address: MacAddress", n.device_id, n.interface_id, n.vlan,
+              n.active, n.first_seen, n.last_seen,
+              w.node_id as "wireless_node_id?", w.ssid as "ssid?", w.rssi as "rssi?",
+              w.ip_address as "wireless_ip_address?", w.last_seen as "wireless_last_seen?"
+           FROM nodes n LEFT JOIN node_wireless w ON w.node_id = n.id
+           WHERE n.mac_address = $1
+           ORDER BY n.last_seen DESC, n.vlan"#,
+        mac_address as _
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Node {
+            id: row.id,
+            mac_address: row.mac_address,
+            device_id: row.device_id,
+            interface_id: row.interface_id,
+            vlan: row.vlan,
+            active: row.active,
+            first_seen: row.first_seen,
+            last_seen: row.last_seen,
+            wireless: row.wireless_node_id.zip(row.wireless_last_seen).map(|(_, last_seen)| NodeWireless {
+                ssid: row.ssid,
+                rssi: row.rssi,
+                ip_address: row.wireless_ip_address,
+                last_seen,
+            }),
+        })
+        .collect())
+}
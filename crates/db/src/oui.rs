@@ -0,0 +1,235 @@
+//! MAC vendor lookup against the IEEE registries.
+//!
+//! MA-L (OUI), MA-M and MA-S assignments are stored by hex prefix in `mac_vendors`,
+//! filled offline by `nd-rust oui import` from the CSV files the IEEE publishes. Until the
+//! first import, the registries bundled in `data/` are used (without organization addresses).
+
+use crate::{DbError, MacAddress, MacVendor, PgPool, Repository};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// MA-L, MA-M, MA-S and the retired IAB registry, as published by the IEEE
+const BUNDLED_CSVS: [&str; 4] = [
+    include_str!("../data/oui.csv"),
+    include_str!("../data/mam.csv"),
+    include_str!("../data/oui36.csv"),
+    include_str!("../data/iab.csv"),
+];
+
+/// Prefix lengths in hex digits, most specific first: MA-S (and the older IAB), MA-M, MA-L.
+const PREFIX_LENGTHS: [usize; 3] = [9, 7, 6];
+
+/// What a MAC says about its origin, as shown next to MACs in API responses.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MacInfo {
+    pub vendor: Option<String>,
+    pub locally_administered: bool, // Not from an IEEE block, so there is no vendor
+    pub randomized: bool,           // Locally administered unicast, as used by clients for privacy
+}
+
+/// IEEE assignments by prefix, for looking up many MACs without a query each.
+#[derive(Debug, Clone, Default)]
+pub struct OuiDatabase {
+    organizations: HashMap<String, String>, // prefix -> organization
+}
+
+impl OuiDatabase {
+    pub fn new(vendors: impl IntoIterator<Item = MacVendor>) -> Self {
+        Self { organizations: vendors.into_iter().map(|v| (v.prefix, v.organization)).collect() }
+    }
+
+    /// The registries shipped with the application.
+    pub fn bundled() -> Self {
+        Self::new(BUNDLED_CSVS.iter().flat_map(|csv| parse_ieee_csv(csv).expect("bundled IEEE registries are valid")))
+    }
+
+    /// The imported assignments, or the bundled list if none were imported yet.
+    pub async fn load(repo: &dyn Repository) -> Result<Self, DbError> {
+        let vendors = repo.list_mac_vendors().await?;
+        if vendors.is_empty() {
+            return Ok(Self::bundled());
+        }
+        Ok(Self::new(vendors))
+    }
+
+    pub fn len(&self) -> usize {
+        self.organizations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.organizations.is_empty()
+    }
+
+    /// The organization holding the most specific block containing `mac`.
+    pub fn vendor(&self, mac: MacAddress) -> Option<&str> {
+        if mac.is_locally_administered() {
+            return None;
+        }
+        let hex = mac.hex();
+        PREFIX_LENGTHS.iter().find_map(|&len| self.organizations.get(&hex[..len]).map(String::as_str))
+    }
+
+    pub fn describe(&self, mac: MacAddress) -> MacInfo {
+        MacInfo {
+            vendor: self.vendor(mac).map(str::to_string),
+            locally_administered: mac.is_locally_administered(),
+            randomized: mac.is_locally_administered() && !mac.is_multicast(),
+        }
+    }
+}
+
+/// Reads an IEEE registry CSV (`oui.csv`, `mam.csv`, `oui36.csv` or `iab.csv`), with the
+/// header `Registry,Assignment,Organization Name,Organization Address`. CID rows, which
+/// are not MAC blocks, are skipped.
+pub fn parse_ieee_csv(text: &str) -> Result<Vec<MacVendor>, DbError> {
+    let invalid = |line: usize, message: String| DbError::InvalidQuery(format!("line {}: {}", line, message));
+    let mut records = csv_records(text).into_iter();
+    let header = records.next().unwrap_or_default();
+    let columns: Vec<String> = header.1.iter().map(|c| c.trim().to_lowercase()).collect();
+    if columns.len() < 3 || columns[..3] != ["registry", "assignment", "organization name"] {
+        return Err(invalid(1, "expected the IEEE header 'Registry,Assignment,Organization Name,...'".to_string()));
+    }
+
+    let mut vendors = Vec::new();
+    for (line, fields) in records {
+        if fields.iter().all(|f| f.trim().is_empty()) {
+            continue;
+        }
+        let [registry, assignment, organization, ..] = fields.as_slice() else {
+            return Err(invalid(line, format!("expected at least 3 fields, found {}", fields.len())));
+        };
+        let len = match registry.trim() {
+            "MA-L" => 6,
+            "MA-M" => 7,
+            "MA-S" | "IAB" => 9,
+            "CID" => continue,
+            other => return Err(invalid(line, format!("unknown registry '{}'", other))),
+        };
+        let prefix = assignment.trim().to_lowercase();
+        if prefix.len() != len || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid(line, format!("invalid {} assignment '{}'", registry.trim(), assignment)));
+        }
+        vendors.push(MacVendor { prefix, organization: organization.trim().to_string() });
+    }
+    Ok(vendors)
+}
+
+// Splits CSV text into records with the line each starts on; quoted fields may
+// contain commas, doubled quotes and line breaks.
+fn csv_records(text: &str) -> Vec<(usize, Vec<String>)> {
+    let mut records = Vec::new();
+    let (mut fields, mut field) = (Vec::new(), String::new());
+    let (mut line, mut start) = (1, 1);
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                fields.push(std::mem::take(&mut field));
+                records.push((start, std::mem::take(&mut fields)));
+                line += 1;
+                start = line;
+            }
+            c => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    if !field.is_empty() || !fields.is_empty() {
+        fields.push(field);
+        records.push((start, fields));
+    }
+    records
+}
+
+/// Replaces the stored assignments of the registries present in `vendors`, so importing
+/// the MA-L file alone keeps earlier MA-M and MA-S imports. Returns the number stored.
+pub async fn replace_mac_vendors(pool: &PgPool, vendors: &[MacVendor]) -> Result<u64, DbError> {
+    let mut lengths: Vec<i32> = vendors.iter().map(|v| v.prefix.len() as i32).collect();
+    lengths.sort_unstable();
+    lengths.dedup();
+    let prefixes: Vec<String> = vendors.iter().map(|v| v.prefix.clone()).collect();
+    let organizations: Vec<String> = vendors.iter().map(|v| v.organization.clone()).collect();
+
+    let mut tx = pool.begin().await?;
+    sqlx::query!("DELETE FROM mac_vendors WHERE length(prefix) = ANY($1)", &lengths)
+        .execute(&mut *tx)
+        .await?;
+    // Files may list a block twice; the last one wins
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO mac_vendors (prefix, organization)
+        SELECT DISTINCT ON (prefix) prefix, organization
+        FROM UNNEST($1::text[], $2::text[]) WITH ORDINALITY AS v(prefix, organization, position)
+        ORDER BY prefix, position DESC
+        "#,
+        &prefixes,
+        &organizations
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(result.rows_affected())
+}
+
+pub async fn list_mac_vendors(pool: &PgPool) -> Result<Vec<MacVendor>, DbError> {
+    let vendors = sqlx::query_as!(MacVendor, "SELECT prefix, organization FROM mac_vendors ORDER BY prefix")
+        .fetch_all(pool)
+        .await?;
+    Ok(vendors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_ieee_csv() {
+        let csv = "Registry,Assignment,Organization Name,Organization Address\r\n\
+                   MA-L,00000C,\"Cisco Systems, Inc\",\"170 West Tasman Drive\nSan Jose CA 95134\n\"\r\n\
+                   MA-M,0055DA5,\"Example \"\"Labs\"\"\",Toronto\r\n\
+                   CID,0A0027,Some Company,\r\n";
+        let vendors = parse_ieee_csv(csv).unwrap();
+        assert_eq!(vendors.len(), 2);
+        assert_eq!(vendors[0], MacVendor { prefix: "00000c".to_string(), organization: "Cisco Systems, Inc".to_string() });
+        assert_eq!(vendors[1].organization, "Example \"Labs\"");
+
+        assert!(parse_ieee_csv("Assignment,Organization Name\n00000C,Cisco\n").is_err());
+        let error = parse_ieee_csv("Registry,Assignment,Organization Name\nMA-M,00000C,Cisco\n").unwrap_err();
+        assert!(error.to_string().contains("line 2"));
+    }
+
+    #[test]
+    fn prefers_the_most_specific_block() {
+        let vendor = |prefix: &str, organization: &str| MacVendor {
+            prefix: prefix.to_string(),
+            organization: organization.to_string(),
+        };
+        let oui = OuiDatabase::new([vendor("70b3d5", "IEEE Registration Authority"), vendor("70b3d5123", "Acme")]);
+        let mac = |text: &str| text.parse::<MacAddress>().unwrap();
+
+        assert_eq!(oui.vendor(mac("70:b3:d5:12:34:56")), Some("Acme"));
+        assert_eq!(oui.vendor(mac("70:b3:d5:99:00:01")), Some("IEEE Registration Authority"));
+
+        let bundled = OuiDatabase::bundled();
+        assert_eq!(bundled.vendor(mac("00:50:56:aa:bb:cc")), Some("VMware, Inc."));
+        // MA-M C85CE27 and MA-S 70B3D5060 sit inside other organizations' blocks
+        assert_eq!(bundled.vendor(mac("c8:5c:e2:70:00:01")), Some("SYNERGY SYSTEMS AND SOLUTIONS"));
+        assert_eq!(bundled.vendor(mac("70:b3:d5:06:00:01")), Some("RCH SPA"));
+        assert_eq!(bundled.vendor(mac("70:b3:d5:00:00:01")), Some("IEEE Registration Authority")); // Unassigned MA-S
+
+        let random = oui.describe(mac("da:a1:19:00:00:01"));
+        assert_eq!((random.vendor, random.locally_administered, random.randomized), (None, true, true));
+        assert!(!oui.describe(mac("33:33:00:00:00:01")).randomized); // IPv6 multicast
+    }
+}
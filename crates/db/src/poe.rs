@@ -0,0 +1,129 @@
+use crate::{DbError, InterfacePoe, PgPool};
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+async fn upsert_poe_row<'e>(executor: impl PgExecutor<'e>, device_id: Uuid, poe: &InterfacePoe) -> Result<u64, DbError> {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO interface_poe (
+            interface_id, group_index, port_index, admin_enabled,
+            detection_status, power_class, power_mw, last_seen
+        )
+        SELECT id, $3, $4, $5, $6, $7, $8, NOW()
+        FROM interfaces WHERE device_id = $1 AND if_index = $2
+        ON CONFLICT (interface_id) DO UPDATE SET
+            group_index = EXCLUDED.group_index,
+            port_index = EXCLUDED.port_index,
+            admin_enabled = EXCLUDED.admin_enabled,
+            detection_status = EXCLUDED.detection_status,
+            power_class = EXCLUDED.power_class,
+            power_mw = EXCLUDED.power_mw,
+            last_seen = EXCLUDED.last_seen,
+            updated_at = NOW()
+        "#,
+        device_id,
+        poe.if_index,
+        poe.group_index,
+        poe.port_index,
+        poe.admin_enabled,
+        poe.detection_status,
+        poe.power_class,
+        poe.power_mw
+    )
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Stores the PoE state of a single interface, matched by ifIndex.
+/// Fails with `NotFound` when the device has no such interface.
+pub async fn upsert_interface_poe(pool: &PgPool, device_id: Uuid, poe: &InterfacePoe) -> Result<(), DbError> {
+    match upsert_poe_row(pool, device_id, poe).await? {
+        0 => Err(DbError::NotFound),
+        _ => Ok(()),
+    }
+}
+
+/// Replaces the PoE state of a device's interfaces with a fresh collection.
+/// Entries without a stored interface are skipped; interfaces no longer
+/// reported as PSE ports lose their PoE row.
+pub async fn replace_interface_poe(pool: &PgPool, device_id: Uuid, ports: &[InterfacePoe]) -> Result<(), DbError> {
+    let mut tx = pool.begin().await?;
+
+    for poe in ports {
+        upsert_poe_row(&mut *tx, device_id, poe).await?;
+    }
+
+    let current_if_indexes: Vec<i32> = ports.iter().map(|p| p.if_index).collect();
+    sqlx::query!(
+        r#"DELETE FROM interface_poe p USING interfaces i
+           WHERE p.interface_id = i.id AND i.device_id = $1 AND NOT (i.if_index = ANY($2))"#,
+        device_id,
+        &current_if_indexes
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Retrieves the PoE state of all PSE ports on a device, ordered by ifIndex.
+pub async fn list_interface_poe(pool: &PgPool, device_id: Uuid) -> Result<Vec<InterfacePoe>, DbError> {
+    let rows = sqlx::query!(
+        r#"SELECT
+              p.interface_id, i.if_index, p.group_index, p.port_index, p.admin_enabled,
+              p.detection_status, p.power_class, p.power_mw, p.last_seen, p.created_at, p.updated_at
+           FROM interface_poe p JOIN interfaces i ON i.id = p.interface_id
+           WHERE i.device_id = $1 ORDER BY i.if_index"#,
+        device_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| InterfacePoe {
+            interface_id: row.interface_id,
+            if_index: row.if_index,
+            group_index: row.group_index,
+            port_index: row.port_index,
+            admin_enabled: row.admin_enabled,
+            detection_status: row.detection_status,
+            power_class: row.power_class,
+            power_mw: row.power_mw,
+            last_seen: row.last_seen,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        })
+        .collect())
+}
+
+/// Retrieves the PoE state of one interface by ifIndex.
+pub async fn get_interface_poe(pool: &PgPool, device_id: Uuid, if_index: i32) -> Result<InterfacePoe, DbError> {
+    let row = sqlx::query!(
+        r#"SELECT
+              p.interface_id, i.if_index, p.group_index, p.port_index, p.admin_enabled,
+              p.detection_status, p.power_class, p.power_mw, p.last_seen, p.created_at, p.updated_at
+           FROM interface_poe p JOIN interfaces i ON i.id = p.interface_id
+           WHERE i.device_id = $1 AND i.if_index = $2"#,
+        device_id,
+        if_index
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(InterfacePoe {
+        interface_id: row.interface_id,
+        if_index: row.if_index,
+        group_index: row.group_index,
+        port_index: row.port_index,
+        admin_enabled: row.admin_enabled,
+        detection_status: row.detection_status,
+        power_class: row.power_class,
+        power_mw: row.power_mw,
+        last_seen: row.last_seen,
+        created_at: row.created_at,
+        updated_at: row.updated_at,
+    })
+}
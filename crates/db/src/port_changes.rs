@@ -0,0 +1,58 @@
+use crate::{DbError, PgPool, PortChange};
+use uuid::Uuid;
+
+/// Appends a port control change (successful or not) to the audit log.
+pub async fn record_port_change(pool: &PgPool, change: &PortChange) -> Result<PortChange, DbError> {
+    let row = sqlx::query!(
+        r#"
+        INSERT INTO port_changes (
+            device_id, interface_id, if_index, action, old_value, new_value, changed_by, succeeded, error
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        RETURNING id, changed_at
+        "#,
+        change.device_id,
+        change.interface_id,
+        change.if_index,
+        change.action,
+        change.old_value,
+        change.new_value,
+        change.changed_by,
+        change.succeeded,
+        change.error
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(PortChange { id: row.id, changed_at: row.changed_at, ..change.clone() })
+}
+
+/// Retrieves the port control changes made on a device, newest first.
+pub async fn list_port_changes(pool: &PgPool, device_id: Uuid) -> Result<Vec<PortChange>, DbError> {
+    let rows = sqlx::query!(
+        r#"SELECT
+              id, device_id, interface_id, if_index, action, old_value, new_value,
+              changed_by, succeeded, error, changed_at
+           FROM port_changes WHERE device_id = $1 ORDER BY changed_at DESC"#,
+        device_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| PortChange {
+            id: row.id,
+            device_id: row.device_id,
+            interface_id: row.interface_id,
+            if_index: row.if_index,
+            action: row.action,
+            old_value: row.old_value,
+            new_value: row.new_value,
+            changed_by: row.changed_by,
+            succeeded: row.succeeded,
+            error: row.error,
+            changed_at: row.changed_at,
+        })
+        .collect())
+}
@@ -0,0 +1,680 @@
+//! Storage traits used by `discovery` and `web`.
+//!
+//! [`PgRepository`] implements them with the query functions of this crate;
+//! [`MemoryRepository`](crate::MemoryRepository) keeps everything in memory so
+//! collectors, services and handlers can be tested without a database.
+
+use crate::{
+    AllowedMac, DeviceNeighbor, LinkRefresh, NeighborProtocol, TopologyLink, Anomaly, ArpEntry, IpHostname, IpSighting, Subnet, SubnetRefresh, AnomalyFilter, AnomalyReport, CustomField, Device, DbError, DeviceField, DeviceGroup, DeviceHistory, DevicePage, DeviceQuery, DeviceStatus, FieldSources, Interface, InterfacePoe, Location, LocationRule, MacAddress, MacVendor, MacViolation, MacViolationFilter, MetadataFilter, MetadataUpdate, Node, PgPool, PortChange, RetentionReport, RoutingNeighbor, RoutingProtocol, SearchHit, SearchQuery, StpInstance,
+    StpPort, StpRoot, WirelessAp,
+};
+use async_trait::async_trait;
+use ipnetwork::IpNetwork;
+use nd_core::{AnomalySettings, RetentionSettings};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// Devices, their change history and the controller/AP relationships between them.
+#[async_trait]
+pub trait DeviceRepository: Send + Sync {
+    async fn upsert_device(&self, device: &Device, source: &str) -> Result<Device, DbError>;
+    async fn upsert_devices(&self, devices: &[Device], source: &str) -> Result<Vec<Device>, DbError>;
+    async fn set_device_fields(
+        &self,
+        device_id: Uuid,
+        values: &[(DeviceField, Option<String>)],
+        source: &str,
+    ) -> Result<Device, DbError>;
+    async fn set_device_status(&self, device_id: Uuid, status: DeviceStatus, source: &str) -> Result<Device, DbError>;
+    async fn update_device(
+        &self,
+        device_id: Uuid,
+        values: &[(DeviceField, Option<String>)],
+        status: Option<DeviceStatus>,
+        source: &str,
+    ) -> Result<Device, DbError>;
+    async fn set_device_metadata(&self, device_id: Uuid, update: &MetadataUpdate) -> Result<Device, DbError>;
+    async fn get_device_field_sources(&self, device_id: Uuid) -> Result<FieldSources, DbError>;
+    async fn list_device_history(&self, device_id: Uuid) -> Result<Vec<DeviceHistory>, DbError>;
+    async fn get_device(&self, id: Uuid) -> Result<Device, DbError>;
+    async fn get_device_by_ip(&self, ip_address: IpNetwork) -> Result<Device, DbError>;
+    async fn list_devices(&self) -> Result<Vec<Device>, DbError>;
+    async fn query_devices(&self, query: &DeviceQuery) -> Result<DevicePage, DbError>;
+    async fn upsert_wireless_ap(&self, ap: &WirelessAp) -> Result<(), DbError>;
+    async fn list_wireless_aps(&self, controller_id: Uuid) -> Result<Vec<WirelessAp>, DbError>;
+}
+
+/// Interfaces, their PoE state and the port control audit log.
+#[async_trait]
+pub trait InterfaceRepository: Send + Sync {
+    async fn upsert_interface(&self, interface: &Interface) -> Result<Interface, DbError>;
+    async fn upsert_interfaces(&self, interfaces: &[Interface]) -> Result<u64, DbError>;
+    async fn get_interface(&self, device_id: Uuid, if_index: i32) -> Result<Interface, DbError>;
+    async fn list_interfaces(&self, device_id: Uuid) -> Result<Vec<Interface>, DbError>;
+    async fn find_interfaces_by_mac(&self, mac_address: MacAddress) -> Result<Vec<Interface>, DbError>;
+    async fn find_interfaces(&self, filter: &MetadataFilter) -> Result<Vec<Interface>, DbError>;
+    async fn set_interface_metadata(&self, interface_id: Uuid, update: &MetadataUpdate) -> Result<Interface, DbError>;
+    async fn update_interface_settings(
+        &self,
+        interface_id: Uuid,
+        admin_status: Option<&str>,
+        if_alias: Option<&str>,
+        access_vlan: Option<i32>,
+    ) -> Result<(), DbError>;
+    async fn upsert_interface_poe(&self, device_id: Uuid, poe: &InterfacePoe) -> Result<(), DbError>;
+    async fn replace_interface_poe(&self, device_id: Uuid, ports: &[InterfacePoe]) -> Result<(), DbError>;
+    async fn get_interface_poe(&self, device_id: Uuid, if_index: i32) -> Result<InterfacePoe, DbError>;
+    async fn list_interface_poe(&self, device_id: Uuid) -> Result<Vec<InterfacePoe>, DbError>;
+    async fn record_port_change(&self, change: &PortChange) -> Result<PortChange, DbError>;
+    async fn list_port_changes(&self, device_id: Uuid) -> Result<Vec<PortChange>, DbError>;
+}
+
+/// End hosts seen on switch ports and APs.
+#[async_trait]
+pub trait NodeRepository: Send + Sync {
+    async fn upsert_node(&self, node: &Node) -> Result<Uuid, DbError>;
+    async fn upsert_nodes(&self, nodes: &[Node]) -> Result<Vec<Uuid>, DbError>;
+    async fn deactivate_nodes(&self, device_ids: &[Uuid], seen_since: OffsetDateTime) -> Result<u64, DbError>;
+    async fn list_nodes(&self, device_id: Uuid) -> Result<Vec<Node>, DbError>;
+    async fn find_nodes_by_mac(&self, mac_address: MacAddress) -> Result<Vec<Node>, DbError>;
+}
+
+/// Routing adjacencies and spanning tree state.
+#[async_trait]
+pub trait TopologyRepository: Send + Sync {
+    async fn replace_routing_neighbors(
+        &self,
+        device_id: Uuid,
+        protocol: RoutingProtocol,
+        neighbors: &[RoutingNeighbor],
+    ) -> Result<(), DbError>;
+    async fn list_routing_neighbors(&self, device_id: Uuid) -> Result<Vec<RoutingNeighbor>, DbError>;
+    async fn replace_stp(&self, device_id: Uuid, instances: &[StpInstance], ports: &[StpPort]) -> Result<(), DbError>;
+    async fn list_stp_instances(&self, device_id: Uuid) -> Result<Vec<StpInstance>, DbError>;
+    async fn list_stp_ports(&self, device_id: Uuid) -> Result<Vec<StpPort>, DbError>;
+    async fn list_stp_roots(&self) -> Result<Vec<StpRoot>, DbError>;
+}
+
+/// The location hierarchy, the rules placing devices in it, and device placement.
+#[async_trait]
+pub trait LocationRepository: Send + Sync {
+    async fn create_location(&self, location: &Location) -> Result<Location, DbError>;
+    async fn update_location(&self, location: &Location) -> Result<Location, DbError>;
+    async fn get_location(&self, id: Uuid) -> Result<Location, DbError>;
+    async fn list_locations(&self) -> Result<Vec<Location>, DbError>;
+    async fn delete_location(&self, id: Uuid) -> Result<(), DbError>;
+    async fn create_location_rule(&self, rule: &LocationRule) -> Result<LocationRule, DbError>;
+    async fn list_location_rules(&self) -> Result<Vec<LocationRule>, DbError>;
+    async fn delete_location_rule(&self, id: Uuid) -> Result<(), DbError>;
+    async fn apply_location_rules(&self) -> Result<u64, DbError>;
+    async fn set_device_location(
+        &self,
+        device_id: Uuid,
+        location_id: Option<Uuid>,
+        source: &str,
+    ) -> Result<Device, DbError>;
+}
+
+/// Static and dynamic device groups.
+#[async_trait]
+pub trait GroupRepository: Send + Sync {
+    async fn create_group(&self, group: &DeviceGroup) -> Result<DeviceGroup, DbError>;
+    async fn update_group(&self, group: &DeviceGroup) -> Result<DeviceGroup, DbError>;
+    async fn get_group(&self, id: Uuid) -> Result<DeviceGroup, DbError>;
+    async fn list_groups(&self) -> Result<Vec<DeviceGroup>, DbError>;
+    async fn delete_group(&self, id: Uuid) -> Result<(), DbError>;
+    async fn add_group_member(&self, group_id: Uuid, device_id: Uuid) -> Result<(), DbError>;
+    async fn remove_group_member(&self, group_id: Uuid, device_id: Uuid) -> Result<(), DbError>;
+}
+
+/// Schemas of the user-defined custom fields on devices and interfaces.
+#[async_trait]
+pub trait CustomFieldRepository: Send + Sync {
+    async fn create_custom_field(&self, field: &CustomField) -> Result<CustomField, DbError>;
+    async fn list_custom_fields(&self) -> Result<Vec<CustomField>, DbError>;
+    async fn delete_custom_field(&self, id: Uuid) -> Result<(), DbError>;
+}
+
+/// Search across devices, interfaces and nodes by one query string.
+#[async_trait]
+pub trait SearchRepository: Send + Sync {
+    async fn search(&self, query: &SearchQuery) -> Result<Vec<SearchHit>, DbError>;
+}
+
+/// Duplicate addresses and misbehaving MACs found in the node sightings.
+#[async_trait]
+pub trait AnomalyRepository: Send + Sync {
+    async fn detect_anomalies(&self, settings: &AnomalySettings) -> Result<AnomalyReport, DbError>;
+    async fn list_anomalies(&self, filter: &AnomalyFilter) -> Result<Vec<Anomaly>, DbError>;
+}
+
+/// Approved MACs and the unknown ones seen where they are enforced.
+#[async_trait]
+pub trait AllowlistRepository: Send + Sync {
+    async fn create_allowed_mac(&self, entry: &AllowedMac) -> Result<AllowedMac, DbError>;
+    async fn list_allowed_macs(&self) -> Result<Vec<AllowedMac>, DbError>;
+    async fn delete_allowed_mac(&self, id: Uuid) -> Result<(), DbError>;
+    async fn check_mac_allowlist(&self, nodes: &[Node], access_port_max_macs: u32) -> Result<Vec<MacViolation>, DbError>;
+    async fn list_mac_violations(&self, filter: &MacViolationFilter) -> Result<Vec<MacViolation>, DbError>;
+    async fn set_violation_port_shutdown(&self, id: Uuid, at: OffsetDateTime) -> Result<(), DbError>;
+}
+
+/// ARP tables and the subnets derived from interface addresses.
+#[async_trait]
+pub trait IpamRepository: Send + Sync {
+    async fn upsert_arp_entries(&self, device_id: Uuid, entries: &[ArpEntry]) -> Result<(), DbError>;
+    async fn refresh_subnets(&self) -> Result<SubnetRefresh, DbError>;
+    async fn list_subnets(&self) -> Result<Vec<Subnet>, DbError>;
+    async fn get_subnet(&self, id: Uuid) -> Result<Subnet, DbError>;
+    async fn list_ip_sightings(&self, network: IpNetwork) -> Result<Vec<IpSighting>, DbError>;
+}
+
+/// Reverse DNS names of addresses.
+#[async_trait]
+pub trait HostnameRepository: Send + Sync {
+    async fn set_ip_hostname(&self, ip_address: IpNetwork, hostname: Option<&str>) -> Result<(), DbError>;
+    async fn list_ip_hostnames(&self, network: Option<IpNetwork>) -> Result<Vec<IpHostname>, DbError>;
+}
+
+/// LLDP/CDP neighbors and the links between devices built from them.
+#[async_trait]
+pub trait LinkRepository: Send + Sync {
+    async fn replace_device_neighbors(
+        &self,
+        device_id: Uuid,
+        protocol: NeighborProtocol,
+        neighbors: &[DeviceNeighbor],
+    ) -> Result<(), DbError>;
+    async fn list_device_neighbors(&self, device_id: Uuid) -> Result<Vec<DeviceNeighbor>, DbError>;
+    async fn refresh_topology_links(&self) -> Result<LinkRefresh, DbError>;
+    async fn list_topology_links(&self, device_id: Option<Uuid>) -> Result<Vec<TopologyLink>, DbError>;
+    async fn create_topology_link(&self, link: &TopologyLink) -> Result<TopologyLink, DbError>;
+    async fn delete_topology_link(&self, id: Uuid) -> Result<(), DbError>;
+    async fn is_uplink_port(&self, interface_id: Uuid) -> Result<bool, DbError>;
+}
+
+/// IEEE MAC block assignments imported for vendor lookups.
+#[async_trait]
+pub trait VendorRepository: Send + Sync {
+    async fn replace_mac_vendors(&self, vendors: &[MacVendor]) -> Result<u64, DbError>;
+    async fn list_mac_vendors(&self) -> Result<Vec<MacVendor>, DbError>;
+}
+
+/// Age-based pruning of historical data; see [`crate::apply_retention`].
+#[async_trait]
+pub trait RetentionRepository: Send + Sync {
+    async fn apply_retention(&self, settings: &RetentionSettings) -> Result<RetentionReport, DbError>;
+}
+
+/// Everything the application stores, as one object for `Arc<dyn Repository>`.
+pub trait Repository:
+    DeviceRepository
+    + InterfaceRepository
+    + NodeRepository
+    + TopologyRepository
+    + LocationRepository
+    + GroupRepository
+    + CustomFieldRepository
+    + SearchRepository
+    + AnomalyRepository
+    + AllowlistRepository
+    + VendorRepository
+    + IpamRepository
+    + HostnameRepository
+    + LinkRepository
+    + RetentionRepository
+{
+}
+
+impl<T> Repository for T where
+    T: DeviceRepository
+        + InterfaceRepository
+        + NodeRepository
+        + TopologyRepository
+        + LocationRepository
+        + GroupRepository
+        + CustomFieldRepository
+        + SearchRepository
+        + AnomalyRepository
+        + AllowlistRepository
+        + VendorRepository
+        + IpamRepository
+        + HostnameRepository
+        + LinkRepository
+        + RetentionRepository
+{
+}
+
+/// The Postgres implementation, backed by a connection pool.
+#[derive(Debug, Clone)]
+pub struct PgRepository {
+    pool: PgPool,
+}
+
+impl PgRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub fn pool(&self) -> &PgPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl DeviceRepository for PgRepository {
+    async fn upsert_device(&self, device: &Device, source: &str) -> Result<Device, DbError> {
+        crate::upsert_device(&self.pool, device, source).await
+    }
+
+    async fn upsert_devices(&self, devices: &[Device], source: &str) -> Result<Vec<Device>, DbError> {
+        crate::upsert_devices(&self.pool, devices, source).await
+    }
+
+    async fn set_device_fields(
+        &self,
+        device_id: Uuid,
+        values: &[(DeviceField, Option<String>)],
+        source: &str,
+    ) -> Result<Device, DbError> {
+        crate::set_device_fields(&self.pool, device_id, values, source).await
+    }
+
+    async fn set_device_status(&self, device_id: Uuid, status: DeviceStatus, source: &str) -> Result<Device, DbError> {
+        crate::set_device_status(&self.pool, device_id, status, source).await
+    }
+
+    async fn update_device(
+        &self,
+        device_id: Uuid,
+        values: &[(DeviceField, Option<String>)],
+        status: Option<DeviceStatus>,
+        source: &str,
+    ) -> Result<Device, DbError> {
+        crate::update_device(&self.pool, device_id, values, status, source).await
+    }
+
+    async fn set_device_metadata(&self, device_id: Uuid, update: &MetadataUpdate) -> Result<Device, DbError> {
+        crate::set_device_metadata(&self.pool, device_id, update).await
+    }
+
+    async fn get_device_field_sources(&self, device_id: Uuid) -> Result<FieldSources, DbError> {
+        crate::get_device_field_sources(&self.pool, device_id).await
+    }
+
+    async fn list_device_history(&self, device_id: Uuid) -> Result<Vec<DeviceHistory>, DbError> {
+        crate::list_device_history(&self.pool, device_id).await
+    }
+
+    async fn get_device(&self, id: Uuid) -> Result<Device, DbError> {
+        crate::get_device(&self.pool, id).await
+    }
+
+    async fn get_device_by_ip(&self, ip_address: IpNetwork) -> Result<Device, DbError> {
+        crate::get_device_by_ip(&self.pool, ip_address).await
+    }
+
+    async fn list_devices(&self) -> Result<Vec<Device>, DbError> {
+        crate::list_devices(&self.pool).await
+    }
+
+    async fn query_devices(&self, query: &DeviceQuery) -> Result<DevicePage, DbError> {
+        crate::query_devices(&self.pool, query).await
+    }
+
+    async fn upsert_wireless_ap(&self, ap: &WirelessAp) -> Result<(), DbError> {
+        crate::upsert_wireless_ap(&self.pool, ap).await
+    }
+
+    async fn list_wireless_aps(&self, controller_id: Uuid) -> Result<Vec<WirelessAp>, DbError> {
+        crate::list_wireless_aps(&self.pool, controller_id).await
+    }
+}
+
+#[async_trait]
+impl InterfaceRepository for PgRepository {
+    async fn upsert_interface(&self, interface: &Interface) -> Result<Interface, DbError> {
+        crate::upsert_interface(&self.pool, interface).await
+    }
+
+    async fn upsert_interfaces(&self, interfaces: &[Interface]) -> Result<u64, DbError> {
+        crate::upsert_interfaces(&self.pool, interfaces).await
+    }
+
+    async fn get_interface(&self, device_id: Uuid, if_index: i32) -> Result<Interface, DbError> {
+        crate::get_interface(&self.pool, device_id, if_index).await
+    }
+
+    async fn list_interfaces(&self, device_id: Uuid) -> Result<Vec<Interface>, DbError> {
+        crate::list_interfaces(&self.pool, device_id).await
+    }
+
+    async fn find_interfaces_by_mac(&self, mac_address: MacAddress) -> Result<Vec<Interface>, DbError> {
+        crate::find_interfaces_by_mac(&self.pool, mac_address).await
+    }
+
+    async fn find_interfaces(&self, filter: &MetadataFilter) -> Result<Vec<Interface>, DbError> {
+        crate::find_interfaces(&self.pool, filter).await
+    }
+
+    async fn set_interface_metadata(&self, interface_id: Uuid, update: &MetadataUpdate) -> Result<Interface, DbError> {
+        crate::set_interface_metadata(&self.pool, interface_id, update).await
+    }
+
+    async fn update_interface_settings(
+        &self,
+        interface_id: Uuid,
+        admin_status: Option<&str>,
+        if_alias: Option<&str>,
+        access_vlan: Option<i32>,
+    ) -> Result<(), DbError> {
+        crate::update_interface_settings(&self.pool, interface_id, admin_status, if_alias, access_vlan).await
+    }
+
+    async fn upsert_interface_poe(&self, device_id: Uuid, poe: &InterfacePoe) -> Result<(), DbError> {
+        crate::upsert_interface_poe(&self.pool, device_id, poe).await
+    }
+
+    async fn replace_interface_poe(&self, device_id: Uuid, ports: &[InterfacePoe]) -> Result<(), DbError> {
+        crate::replace_interface_poe(&self.pool, device_id, ports).await
+    }
+
+    async fn get_interface_poe(&self, device_id: Uuid, if_index: i32) -> Result<InterfacePoe, DbError> {
+        crate::get_interface_poe(&self.pool, device_id, if_index).await
+    }
+
+    async fn list_interface_poe(&self, device_id: Uuid) -> Result<Vec<InterfacePoe>, DbError> {
+        crate::list_interface_poe(&self.pool, device_id).await
+    }
+
+    async fn record_port_change(&self, change: &PortChange) -> Result<PortChange, DbError> {
+        crate::record_port_change(&self.pool, change).await
+    }
+
+    async fn list_port_changes(&self, device_id: Uuid) -> Result<Vec<PortChange>, DbError> {
+        crate::list_port_changes(&self.pool, device_id).await
+    }
+}
+
+#[async_trait]
+impl NodeRepository for PgRepository {
+    async fn upsert_node(&self, node: &Node) -> Result<Uuid, DbError> {
+        crate::upsert_node(&self.pool, node).await
+    }
+
+    async fn upsert_nodes(&self, nodes: &[Node]) -> Result<Vec<Uuid>, DbError> {
+        crate::upsert_nodes(&self.pool, nodes).await
+    }
+
+    async fn deactivate_nodes(&self, device_ids: &[Uuid], seen_since: OffsetDateTime) -> Result<u64, DbError> {
+        crate::deactivate_nodes(&self.pool, device_ids, seen_since).await
+    }
+
+    async fn list_nodes(&self, device_id: Uuid) -> Result<Vec<Node>, DbError> {
+        crate::list_nodes(&self.pool, device_id).await
+    }
+
+    async fn find_nodes_by_mac(&self, mac_address: MacAddress) -> Result<Vec<Node>, DbError> {
+        crate::find_nodes_by_mac(&self.pool, mac_address).await
+    }
+}
+
+#[async_trait]
+impl TopologyRepository for PgRepository {
+    async fn replace_routing_neighbors(
+        &self,
+        device_id: Uuid,
+        protocol: RoutingProtocol,
+        neighbors: &[RoutingNeighbor],
+    ) -> Result<(), DbError> {
+        crate::replace_routing_neighbors(&self.pool, device_id, protocol, neighbors).await
+    }
+
+    async fn list_routing_neighbors(&self, device_id: Uuid) -> Result<Vec<RoutingNeighbor>, DbError> {
+        crate::list_routing_neighbors(&self.pool, device_id).await
+    }
+
+    async fn replace_stp(&self, device_id: Uuid, instances: &[StpInstance], ports: &[StpPort]) -> Result<(), DbError> {
+        crate::replace_stp(&self.pool, device_id, instances, ports).await
+    }
+
+    async fn list_stp_instances(&self, device_id: Uuid) -> Result<Vec<StpInstance>, DbError> {
+        crate::list_stp_instances(&self.pool, device_id).await
+    }
+
+    async fn list_stp_ports(&self, device_id: Uuid) -> Result<Vec<StpPort>, DbError> {
+        crate::list_stp_ports(&self.pool, device_id).await
+    }
+
+    async fn list_stp_roots(&self) -> Result<Vec<StpRoot>, DbError> {
+        crate::list_stp_roots(&self.pool).await
+    }
+
+}
+
+#[async_trait]
+impl LocationRepository for PgRepository {
+    async fn create_location(&self, location: &Location) -> Result<Location, DbError> {
+        crate::create_location(&self.pool, location).await
+    }
+
+    async fn update_location(&self, location: &Location) -> Result<Location, DbError> {
+        crate::update_location(&self.pool, location).await
+    }
+
+    async fn get_location(&self, id: Uuid) -> Result<Location, DbError> {
+        crate::get_location(&self.pool, id).await
+    }
+
+    async fn list_locations(&self) -> Result<Vec<Location>, DbError> {
+        crate::list_locations(&self.pool).await
+    }
+
+    async fn delete_location(&self, id: Uuid) -> Result<(), DbError> {
+        crate::delete_location(&self.pool, id).await
+    }
+
+    async fn create_location_rule(&self, rule: &LocationRule) -> Result<LocationRule, DbError> {
+        crate::create_location_rule(&self.pool, rule).await
+    }
+
+    async fn list_location_rules(&self) -> Result<Vec<LocationRule>, DbError> {
+        crate::list_location_rules(&self.pool).await
+    }
+
+    async fn delete_location_rule(&self, id: Uuid) -> Result<(), DbError> {
+        crate::delete_location_rule(&self.pool, id).await
+    }
+
+    async fn apply_location_rules(&self) -> Result<u64, DbError> {
+        crate::apply_location_rules(&self.pool).await
+    }
+
+    async fn set_device_location(
+        &self,
+        device_id: Uuid,
+        location_id: Option<Uuid>,
+        source: &str,
+    ) -> Result<Device, DbError> {
+        crate::set_device_location(&self.pool, device_id, location_id, source).await
+    }
+}
+
+#[async_trait]
+impl GroupRepository for PgRepository {
+    async fn create_group(&self, group: &DeviceGroup) -> Result<DeviceGroup, DbError> {
+        crate::create_group(&self.pool, group).await
+    }
+
+    async fn update_group(&self, group: &DeviceGroup) -> Result<DeviceGroup, DbError> {
+        crate::update_group(&self.pool, group).await
+    }
+
+    async fn get_group(&self, id: Uuid) -> Result<DeviceGroup, DbError> {
+        crate::get_group(&self.pool, id).await
+    }
+
+    async fn list_groups(&self) -> Result<Vec<DeviceGroup>, DbError> {
+        crate::list_groups(&self.pool).await
+    }
+
+    async fn delete_group(&self, id: Uuid) -> Result<(), DbError> {
+        crate::delete_group(&self.pool, id).await
+    }
+
+    async fn add_group_member(&self, group_id: Uuid, device_id: Uuid) -> Result<(), DbError> {
+        crate::add_group_member(&self.pool, group_id, device_id).await
+    }
+
+    async fn remove_group_member(&self, group_id: Uuid, device_id: Uuid) -> Result<(), DbError> {
+        crate::remove_group_member(&self.pool, group_id, device_id).await
+    }
+}
+
+#[async_trait]
+impl CustomFieldRepository for PgRepository {
+    async fn create_custom_field(&self, field: &CustomField) -> Result<CustomField, DbError> {
+        crate::create_custom_field(&self.pool, field).await
+    }
+
+    async fn list_custom_fields(&self) -> Result<Vec<CustomField>, DbError> {
+        crate::list_custom_fields(&self.pool).await
+    }
+
+    async fn delete_custom_field(&self, id: Uuid) -> Result<(), DbError> {
+        crate::delete_custom_field(&self.pool, id).await
+    }
+}
+
+#[async_trait]
+impl SearchRepository for PgRepository {
+    async fn search(&self, query: &SearchQuery) -> Result<Vec<SearchHit>, DbError> {
+        crate::search(&self.pool, query).await
+    }
+}
+
+#[async_trait]
+impl AnomalyRepository for PgRepository {
+    async fn detect_anomalies(&self, settings: &AnomalySettings) -> Result<AnomalyReport, DbError> {
+        crate::detect_anomalies(&self.pool, settings).await
+    }
+
+    async fn list_anomalies(&self, filter: &AnomalyFilter) -> Result<Vec<Anomaly>, DbError> {
+        crate::list_anomalies(&self.pool, filter).await
+    }
+}
+
+#[async_trait]
+impl AllowlistRepository for PgRepository {
+    async fn create_allowed_mac(&self, entry: &AllowedMac) -> Result<AllowedMac, DbError> {
+        crate::create_allowed_mac(&self.pool, entry).await
+    }
+
+    async fn list_allowed_macs(&self) -> Result<Vec<AllowedMac>, DbError> {
+        crate::list_allowed_macs(&self.pool).await
+    }
+
+    async fn delete_allowed_mac(&self, id: Uuid) -> Result<(), DbError> {
+        crate::delete_allowed_mac(&self.pool, id).await
+    }
+
+    async fn check_mac_allowlist(&self, nodes: &[Node], access_port_max_macs: u32) -> Result<Vec<MacViolation>, DbError> {
+        crate::check_mac_allowlist(&self.pool, nodes, access_port_max_macs).await
+    }
+
+    async fn list_mac_violations(&self, filter: &MacViolationFilter) -> Result<Vec<MacViolation>, DbError> {
+        crate::list_mac_violations(&self.pool, filter).await
+    }
+
+    async fn set_violation_port_shutdown(&self, id: Uuid, at: OffsetDateTime) -> Result<(), DbError> {
+        crate::set_violation_port_shutdown(&self.pool, id, at).await
+    }
+}
+
+#[async_trait]
+impl VendorRepository for PgRepository {
+    async fn replace_mac_vendors(&self, vendors: &[MacVendor]) -> Result<u64, DbError> {
+        crate::replace_mac_vendors(&self.pool, vendors).await
+    }
+
+    async fn list_mac_vendors(&self) -> Result<Vec<MacVendor>, DbError> {
+        crate::list_mac_vendors(&self.pool).await
+    }
+}
+
+#[async_trait]
+impl IpamRepository for PgRepository {
+    async fn upsert_arp_entries(&self, device_id: Uuid, entries: &[ArpEntry]) -> Result<(), DbError> {
+        crate::upsert_arp_entries(&self.pool, device_id, entries).await
+    }
+
+    async fn refresh_subnets(&self) -> Result<SubnetRefresh, DbError> {
+        crate::refresh_subnets(&self.pool).await
+    }
+
+    async fn list_subnets(&self) -> Result<Vec<Subnet>, DbError> {
+        crate::list_subnets(&self.pool).await
+    }
+
+    async fn get_subnet(&self, id: Uuid) -> Result<Subnet, DbError> {
+        crate::get_subnet(&self.pool, id).await
+    }
+
+    async fn list_ip_sightings(&self, network: IpNetwork) -> Result<Vec<IpSighting>, DbError> {
+        crate::list_ip_sightings(&self.pool, network).await
+    }
+}
+
+#[async_trait]
+impl HostnameRepository for PgRepository {
+    async fn set_ip_hostname(&self, ip_address: IpNetwork, hostname: Option<&str>) -> Result<(), DbError> {
+        crate::set_ip_hostname(&self.pool, ip_address, hostname).await
+    }
+
+    async fn list_ip_hostnames(&self, network: Option<IpNetwork>) -> Result<Vec<IpHostname>, DbError> {
+        crate::list_ip_hostnames(&self.pool, network).await
+    }
+}
+
+#[async_trait]
+impl LinkRepository for PgRepository {
+    async fn replace_device_neighbors(
+        &self,
+        device_id: Uuid,
+        protocol: NeighborProtocol,
+        neighbors: &[DeviceNeighbor],
+    ) -> Result<(), DbError> {
+        crate::replace_device_neighbors(&self.pool, device_id, protocol, neighbors).await
+    }
+
+    async fn list_device_neighbors(&self, device_id: Uuid) -> Result<Vec<DeviceNeighbor>, DbError> {
+        crate::list_device_neighbors(&self.pool, device_id).await
+    }
+
+    async fn refresh_topology_links(&self) -> Result<LinkRefresh, DbError> {
+        crate::refresh_topology_links(&self.pool).await
+    }
+
+    async fn list_topology_links(&self, device_id: Option<Uuid>) -> Result<Vec<TopologyLink>, DbError> {
+        crate::list_topology_links(&self.pool, device_id).await
+    }
+
+    async fn create_topology_link(&self, link: &TopologyLink) -> Result<TopologyLink, DbError> {
+        crate::create_topology_link(&self.pool, link).await
+    }
+
+    async fn delete_topology_link(&self, id: Uuid) -> Result<(), DbError> {
+        crate::delete_topology_link(&self.pool, id).await
+    }
+
+    async fn is_uplink_port(&self, interface_id: Uuid) -> Result<bool, DbError> {
+        crate::is_uplink_port(&self.pool, interface_id).await
+    }
+}
+
+#[async_trait]
+impl RetentionRepository for PgRepository {
+    async fn apply_retention(&self, settings: &RetentionSettings) -> Result<RetentionReport, DbError> {
+        crate::apply_retention(&self.pool, settings).await
+    }
+}
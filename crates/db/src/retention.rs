@@ -0,0 +1,397 @@
+//! Age-based pruning of historical data, configured by `RetentionSettings`.
+//!
+//! Every backend implements [`RetentionRepository`](crate::RetentionRepository); on Postgres
+//! rows go in batches of `batch_size`, one short statement each, and rows locked by a
+//! running collection are skipped until the next run. Stale devices are not deleted but
+//! retired, which keeps their history and takes them out of discovery and listings.
+
+use crate::{DbError, PgPool, Repository};
+use nd_core::RetentionSettings;
+use serde::Serialize;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+/// Source recorded in the device history when retention retires a device.
+pub const RETENTION_SOURCE: &str = "retention";
+
+/// What one run removed or, in a dry run, would remove.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RetentionReport {
+    pub dry_run: bool,
+    pub nodes: u64,
+    pub device_history: u64,
+    pub port_changes: u64,
+    pub arp_entries: u64,
+    pub node_moves: u64,
+    pub anomalies: u64, // Resolved ones
+    pub mac_violations: u64,
+    pub archived_devices: u64,
+}
+
+pub(crate) fn cutoff(days: u32) -> OffsetDateTime {
+    OffsetDateTime::now_utc() - time::Duration::days(days.into())
+}
+
+/// Runs `batch` until it affects fewer than `batch_size` rows; returns the total.
+pub(crate) async fn in_batches<F, Fut>(batch_size: i64, mut batch: F) -> Result<u64, DbError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<u64, DbError>>,
+{
+    let mut total = 0;
+    loop {
+        let affected = batch().await?;
+        total += affected;
+        if affected < batch_size as u64 {
+            return Ok(total);
+        }
+    }
+}
+
+async fn prune_nodes(pool: &PgPool, days: u32, batch_size: i64, dry_run: bool) -> Result<u64, DbError> {
+    let cutoff = cutoff(days);
+    if dry_run {
+        let count = sqlx::query_scalar!(r#"SELECT COUNT(*) as "count!" FROM nodes WHERE last_seen < $1"#, cutoff)
+            .fetch_one(pool)
+            .await?;
+        return Ok(count as u64);
+    }
+    in_batches(batch_size, || async move {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM nodes WHERE id IN (
+                SELECT id FROM nodes WHERE last_seen < $1 LIMIT $2 FOR UPDATE SKIP LOCKED
+            )
+            "#,
+            cutoff,
+            batch_size
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected())
+    })
+    .await
+}
+
+async fn prune_device_history(pool: &PgPool, days: u32, batch_size: i64, dry_run: bool) -> Result<u64, DbError> {
+    let cutoff = cutoff(days);
+    if dry_run {
+        let count =
+            sqlx::query_scalar!(r#"SELECT COUNT(*) as "count!" FROM device_history WHERE changed_at < $1"#, cutoff)
+                .fetch_one(pool)
+                .await?;
+        return Ok(count as u64);
+    }
+    in_batches(batch_size, || async move {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM device_history WHERE id IN (
+                SELECT id FROM device_history WHERE changed_at < $1 LIMIT $2 FOR UPDATE SKIP LOCKED
+            )
+            "#,
+            cutoff,
+            batch_size
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected())
+    })
+    .await
+}
+
+async fn prune_port_changes(pool: &PgPool, days: u32, batch_size: i64, dry_run: bool) -> Result<u64, DbError> {
+    let cutoff = cutoff(days);
+    if dry_run {
+        let count =
+            sqlx::query_scalar!(r#"SELECT COUNT(*) as "count!" FROM port_changes WHERE changed_at < $1"#, cutoff)
+                .fetch_one(pool)
+                .await?;
+        return Ok(count as u64);
+    }
+    in_batches(batch_size, || async move {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM port_changes WHERE id IN (
+                SELECT id FROM port_changes WHERE changed_at < $1 LIMIT $2 FOR UPDATE SKIP LOCKED
+            )
+            "#,
+            cutoff,
+            batch_size
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected())
+    })
+    .await
+}
+
+async fn prune_arp_entries(pool: &PgPool, days: u32, batch_size: i64, dry_run: bool) -> Result<u64, DbError> {
+    let cutoff = cutoff(days);
+    if dry_run {
+        let count =
+            sqlx::query_scalar!(r#"SELECT COUNT(*) as "count!" FROM arp_entries WHERE last_seen < $1"#, cutoff)
+                .fetch_one(pool)
+                .await?;
+        return Ok(count as u64);
+    }
+    in_batches(batch_size, || async move {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM arp_entries WHERE id IN (
+                SELECT id FROM arp_entries WHERE last_seen < $1 LIMIT $2 FOR UPDATE SKIP LOCKED
+            )
+            "#,
+            cutoff,
+            batch_size
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected())
+    })
+    .await
+}
+
+async fn prune_node_moves(pool: &PgPool, days: u32, batch_size: i64, dry_run: bool) -> Result<u64, DbError> {
+    let cutoff = cutoff(days);
+    if dry_run {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count!" FROM node_moves WHERE moved_at < $1"#,
+            cutoff
+        )
+        .fetch_one(pool)
+        .await?;
+        return Ok(count as u64);
+    }
+    in_batches(batch_size, || async move {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM node_moves WHERE id IN (
+                SELECT id FROM node_moves WHERE moved_at < $1 LIMIT $2 FOR UPDATE SKIP LOCKED
+            )
+            "#,
+            cutoff,
+            batch_size
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected())
+    })
+    .await
+}
+
+// Open anomalies stay however old they are
+async fn prune_anomalies(pool: &PgPool, days: u32, batch_size: i64, dry_run: bool) -> Result<u64, DbError> {
+    let cutoff = cutoff(days);
+    if dry_run {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count!" FROM anomalies WHERE resolved_at IS NOT NULL AND last_detected < $1"#,
+            cutoff
+        )
+        .fetch_one(pool)
+        .await?;
+        return Ok(count as u64);
+    }
+    in_batches(batch_size, || async move {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM anomalies WHERE id IN (
+                SELECT id FROM anomalies WHERE resolved_at IS NOT NULL AND last_detected < $1 LIMIT $2 FOR UPDATE SKIP LOCKED
+            )
+            "#,
+            cutoff,
+            batch_size
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected())
+    })
+    .await
+}
+
+async fn prune_mac_violations(pool: &PgPool, days: u32, batch_size: i64, dry_run: bool) -> Result<u64, DbError> {
+    let cutoff = cutoff(days);
+    if dry_run {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count!" FROM mac_violations WHERE last_seen < $1"#,
+            cutoff
+        )
+        .fetch_one(pool)
+        .await?;
+        return Ok(count as u64);
+    }
+    in_batches(batch_size, || async move {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM mac_violations WHERE id IN (
+                SELECT id FROM mac_violations WHERE last_seen < $1 LIMIT $2 FOR UPDATE SKIP LOCKED
+            )
+            "#,
+            cutoff,
+            batch_size
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected())
+    })
+    .await
+}
+
+/// Retires devices not seen since the cutoff, leaving those an operator already gave a
+/// lifecycle status alone, and records each in the device history.
+async fn archive_devices(pool: &PgPool, days: u32, batch_size: i64, dry_run: bool) -> Result<u64, DbError> {
+    let cutoff = cutoff(days);
+    if dry_run {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) as "count!" FROM devices
+            WHERE last_seen < $1 AND (status IS NULL OR status NOT IN ('maintenance', 'retired', 'decommissioned'))
+            "#,
+            cutoff
+        )
+        .fetch_one(pool)
+        .await?;
+        return Ok(count as u64);
+    }
+    in_batches(batch_size, || async move {
+        // The history insert reports one row per retired device
+        let result = sqlx::query!(
+            r#"
+            WITH stale AS (
+                SELECT id, status FROM devices
+                WHERE last_seen < $1 AND (status IS NULL OR status NOT IN ('maintenance', 'retired', 'decommissioned'))
+                LIMIT $2 FOR UPDATE SKIP LOCKED
+            ), retired AS (
+                UPDATE devices SET status = 'retired' FROM stale WHERE devices.id = stale.id
+            )
+            INSERT INTO device_history (device_id, attribute, old_value, new_value, source)
+            SELECT id, 'status', status::text, 'retired', $3 FROM stale
+            "#,
+            cutoff,
+            batch_size,
+            RETENTION_SOURCE
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected())
+    })
+    .await
+}
+
+/// Applies the retention settings once.
+pub async fn apply_retention(pool: &PgPool, settings: &RetentionSettings) -> Result<RetentionReport, DbError> {
+    let batch_size = i64::from(settings.batch_size.max(1));
+    let dry_run = settings.dry_run;
+    let mut report = RetentionReport { dry_run, ..RetentionReport::default() };
+    // Moves first, so deleting their sightings does not take them along uncounted
+    if let Some(days) = settings.node_move_days {
+        report.node_moves = prune_node_moves(pool, days, batch_size, dry_run).await?;
+    }
+    if let Some(days) = settings.node_days {
+        report.nodes = prune_nodes(pool, days, batch_size, dry_run).await?;
+    }
+    if let Some(days) = settings.device_history_days {
+        report.device_history = prune_device_history(pool, days, batch_size, dry_run).await?;
+    }
+    if let Some(days) = settings.port_change_days {
+        report.port_changes = prune_port_changes(pool, days, batch_size, dry_run).await?;
+    }
+    if let Some(days) = settings.arp_days {
+        report.arp_entries = prune_arp_entries(pool, days, batch_size, dry_run).await?;
+    }
+    if let Some(days) = settings.anomaly_days {
+        report.anomalies = prune_anomalies(pool, days, batch_size, dry_run).await?;
+    }
+    if let Some(days) = settings.mac_violation_days {
+        report.mac_violations = prune_mac_violations(pool, days, batch_size, dry_run).await?;
+    }
+    if let Some(days) = settings.archive_device_days {
+        report.archived_devices = archive_devices(pool, days, batch_size, dry_run).await?;
+    }
+    Ok(report)
+}
+
+/// Applies the retention settings every `interval_minutes`, for as long as the caller runs it.
+pub async fn run_retention(repo: Arc<dyn Repository>, settings: RetentionSettings) {
+    let mut interval = tokio::time::interval(Duration::from_secs(settings.interval_minutes.max(1) * 60));
+    loop {
+        interval.tick().await;
+        match repo.apply_retention(&settings).await {
+            Ok(report) => tracing::info!(
+                dry_run = report.dry_run,
+                nodes = report.nodes,
+                device_history = report.device_history,
+                port_changes = report.port_changes,
+                arp_entries = report.arp_entries,
+                node_moves = report.node_moves,
+                anomalies = report.anomalies,
+                mac_violations = report.mac_violations,
+                archived_devices = report.archived_devices,
+                "Retention run finished"
+            ),
+            Err(e) => tracing::warn!(error = %e, "Retention run failed"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Device, DeviceRepository, DeviceStatus, MemoryRepository, Node, NodeRepository, RetentionRepository};
+    use uuid::Uuid;
+
+    fn device(ip: &str, last_seen: OffsetDateTime, status: Option<DeviceStatus>) -> Device {
+        Device {
+            status,
+            last_seen: Some(last_seen),
+            ..Device::new(ip.parse().unwrap())
+        }
+    }
+
+    #[tokio::test]
+    async fn counts_in_dry_runs_then_prunes_and_retires() {
+        let repo = MemoryRepository::new();
+        let now = OffsetDateTime::now_utc();
+        let old = now - time::Duration::days(400);
+        let stale = repo.upsert_device(&device("192.0.2.1/32", old, None), "discovery").await.unwrap();
+        let held = repo.upsert_device(&device("192.0.2.2/32", old, Some(DeviceStatus::Maintenance)), "discovery").await.unwrap();
+        let current = repo.upsert_device(&device("192.0.2.3/32", now, None), "discovery").await.unwrap();
+        let node = |mac: &str, interface_id: Uuid, last_seen: OffsetDateTime| Node {
+            id: Uuid::nil(),
+            mac_address: mac.parse().unwrap(),
+            device_id: current.id,
+            interface_id: Some(interface_id),
+            vlan: 10,
+            active: true,
+            first_seen: last_seen,
+            last_seen,
+            wireless: None,
+        };
+        // One old sighting that moved once long ago, one current one
+        repo.upsert_node(&node("02:00:00:00:00:01", Uuid::new_v4(), old)).await.unwrap();
+        repo.upsert_node(&node("02:00:00:00:00:01", Uuid::new_v4(), old)).await.unwrap();
+        repo.upsert_node(&node("02:00:00:00:00:02", Uuid::new_v4(), now)).await.unwrap();
+
+        let settings = RetentionSettings {
+            node_days: Some(90),
+            node_move_days: Some(30),
+            archive_device_days: Some(180),
+            dry_run: true,
+            ..RetentionSettings::default()
+        };
+        let expected = RetentionReport { dry_run: true, nodes: 1, node_moves: 1, archived_devices: 1, ..RetentionReport::default() };
+        assert_eq!(repo.apply_retention(&settings).await.unwrap(), expected);
+        assert_eq!(repo.list_nodes(current.id).await.unwrap().len(), 2);
+        assert_eq!(repo.get_device(stale.id).await.unwrap().status, None);
+
+        let settings = RetentionSettings { dry_run: false, ..settings };
+        assert_eq!(repo.apply_retention(&settings).await.unwrap(), RetentionReport { dry_run: false, ..expected });
+        assert_eq!(repo.list_nodes(current.id).await.unwrap().len(), 1);
+        assert_eq!(repo.get_device(stale.id).await.unwrap().status, Some(DeviceStatus::Retired));
+        assert_eq!(repo.get_device(held.id).await.unwrap().status, Some(DeviceStatus::Maintenance));
+        let history = repo.list_device_history(stale.id).await.unwrap();
+        assert_eq!((history[0].new_value.as_deref(), history[0].source.as_str()), (Some("retired"), RETENTION_SOURCE));
+        assert_eq!(repo.apply_retention(&settings).await.unwrap(), RetentionReport::default());
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{DbError, PgPool, RoutingNeighbor, RoutingProtocol};
+use uuid::Uuid;
+
+/// Replaces the stored neighbors of one protocol on a device with a fresh collection.
+/// Existing neighbors are updated in place so `created_at` keeps the first sighting;
+/// neighbors that are no longer reported by the device are removed.
+pub async fn replace_routing_neighbors(
+    pool: &PgPool,
+    device_id: Uuid,
+    protocol: RoutingProtocol,
+    neighbors: &[RoutingNeighbor],
+) -> Result<(), DbError> {
+    let mut tx = pool.begin().await?;
+
+    for neighbor in neighbors {
+        sqlx::query!(
+            r#"
+            INSERT INTO routing_neighbors (
+                device_id, protocol, neighbor_address, remote_router_id,
+                remote_as, state, uptime_seconds, last_seen
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, NOW())
+            ON CONFLICT (device_id, protocol, neighbor_address) DO UPDATE SET
+                remote_router_id = EXCLUDED.remote_router_id,
+                remote_as = EXCLUDED.remote_as,
+                state = EXCLUDED.state,
+                uptime_seconds = EXCLUDED.uptime_seconds,
+                last_seen = EXCLUDED.last_seen,
+                updated_at = NOW()
+            "#,
+            device_id,
+            protocol.as_str(),
+            neighbor.neighbor_address,
+            neighbor.remote_router_id,
+            neighbor.remote_as,
+            neighbor.state,
+            neighbor.uptime_seconds
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let current_addresses: Vec<_> = neighbors.iter().map(|n| n.neighbor_address).collect();
+    sqlx::query!(
+        r#"DELETE FROM routing_neighbors
+           WHERE device_id = $1 AND protocol = $2 AND NOT (neighbor_address = ANY($3))"#,
+        device_id,
+        protocol.as_str(),
+        &current_addresses
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Retrieves the routing neighbors of a device, BGP first, then by address.
+pub async fn list_routing_neighbors(pool: &PgPool, device_id: Uuid) -> Result<Vec<RoutingNeighbor>, DbError> {
+    let rows = sqlx::query!(
+        r#"SELECT
+              id, device_id, protocol, neighbor_address, remote_router_id,
+              remote_as, state, uptime_seconds, last_seen, created_at, updated_at
+           FROM routing_neighbors WHERE device_id = $1
+           ORDER BY protocol, neighbor_address"#,
+        device_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut neighbors = Vec::with_capacity(rows.len());
+    for row in rows {
+        let protocol = RoutingProtocol::try_from(row.protocol).map_err(DbError::MappingError)?;
+        neighbors.push(RoutingNeighbor {
+            id: row.id,
+            device_id: row.device_id,
+            protocol,
+            neighbor_address: row.neighbor_address,
+            remote_router_id: row.remote_router_id,
+            remote_as: row.remote_as,
+            state: row.state,
+            uptime_seconds: row.uptime_seconds,
+            last_seen: row.last_seen,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        });
+    }
+    Ok(neighbors)
+}
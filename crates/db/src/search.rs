@@ -0,0 +1,645 @@
+//! Unified search over devices, interfaces, node (MAC) sightings and ARP entries.
+//!
+//! One query string is classified as an address, a subnet, a MAC in any notation, or text.
+//! Text matches hostnames, sysNames and serials, interface names and descriptions, and MAC
+//! fragments. Postgres narrows candidates with trigram, text search and GiST indexes; every
+//! backend then ranks them with [`rank`], so results are ordered the same everywhere.
+
+use crate::device_query::device_from_row;
+use crate::interfaces::interface_from_row;
+use crate::nodes::{node_from_row, NODE_QUERY};
+use crate::{ArpEntry, DbError, Device, Interface, MacAddress, Node, PgPool};
+use ipnetwork::IpNetwork;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::net::IpAddr;
+use uuid::Uuid;
+
+pub const DEFAULT_SEARCH_LIMIT: u32 = 50;
+pub const MAX_SEARCH_LIMIT: u32 = 200;
+
+const MIN_QUERY_LEN: usize = 2;
+const MIN_MAC_FRAGMENT_LEN: usize = 4;
+const SIMILARITY_THRESHOLD: f32 = 0.3; // pg_trgm's default for `%`
+
+/// What a search query was recognised as.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchTerm {
+    Address(IpAddr),
+    Subnet(IpNetwork),
+    Mac(MacAddress),
+    Text(String),
+}
+
+impl SearchTerm {
+    /// Classifies a query: an IP address, a CIDR, a MAC (colon, dash, dotted or bare hex),
+    /// or otherwise text.
+    pub fn parse(query: &str) -> Result<Self, DbError> {
+        let query = query.trim();
+        if query.chars().count() < MIN_QUERY_LEN {
+            return Err(DbError::InvalidQuery(format!("search for at least {} characters", MIN_QUERY_LEN)));
+        }
+        if let Ok(address) = query.parse() {
+            return Ok(SearchTerm::Address(address));
+        }
+        if query.contains('/') && let Ok(subnet) = query.parse() {
+            return Ok(SearchTerm::Subnet(subnet));
+        }
+        if let Ok(mac) = query.parse() {
+            return Ok(SearchTerm::Mac(mac));
+        }
+        Ok(SearchTerm::Text(query.to_string()))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SearchTerm::Address(_) => "ip",
+            SearchTerm::Subnet(_) => "subnet",
+            SearchTerm::Mac(_) => "mac",
+            SearchTerm::Text(_) => "text",
+        }
+    }
+}
+
+/// One search request.
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    pub term: SearchTerm,
+    pub limit: Option<u32>, // defaults to DEFAULT_SEARCH_LIMIT, capped at MAX_SEARCH_LIMIT
+}
+
+impl SearchQuery {
+    pub(crate) fn limit(&self) -> u32 {
+        self.limit.unwrap_or(DEFAULT_SEARCH_LIMIT).clamp(1, MAX_SEARCH_LIMIT)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchHitKind {
+    Device,
+    Interface,
+    Node,
+    Arp,
+}
+
+/// A matching object, with the field that matched best.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchHit {
+    pub kind: SearchHitKind,
+    pub device_id: Uuid,
+    pub device_label: String, // hostname, sysName or management address
+    pub interface_id: Option<Uuid>,
+    pub node_id: Option<Uuid>,
+    pub arp_entry_id: Option<Uuid>,
+    pub field: &'static str,
+    pub value: String,
+    pub score: f32, // 1.0 for exact matches, lower for prefixes, fragments and typos
+}
+
+fn device_label(device: &Device) -> String {
+    device.hostname.clone().or_else(|| device.sys_name.clone()).unwrap_or_else(|| device.ip_address.ip().to_string())
+}
+
+/// Lowercase hex digits of a query that could be part of a MAC, e.g. `4d:5e` or `2b3c.4d`.
+fn mac_fragment(text: &str) -> Option<String> {
+    let hex: String = text.chars().filter(|c| !matches!(c, ':' | '-' | '.')).collect();
+    (hex.len() >= MIN_MAC_FRAGMENT_LEN && hex.chars().all(|c| c.is_ascii_hexdigit())).then(|| hex.to_lowercase())
+}
+
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty())
+}
+
+/// Trigrams of each word padded like pg_trgm does, so scores follow `similarity()`.
+fn trigrams(text: &str) -> HashSet<[char; 3]> {
+    let lower = text.to_lowercase();
+    words(&lower)
+        .flat_map(|word| {
+            let padded: Vec<char> = "  ".chars().chain(word.chars()).chain(" ".chars()).collect();
+            padded.windows(3).map(|w| [w[0], w[1], w[2]]).collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn similarity(a: &str, b: &str) -> f32 {
+    let (a, b) = (trigrams(a), trigrams(b));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f32 / union as f32
+}
+
+/// Exact, prefix and fragment matches, ignoring case.
+fn fragment_score(value: &str, term: &str) -> Option<f32> {
+    let (value, term) = (value.to_lowercase(), term.to_lowercase());
+    if value == term {
+        Some(1.0)
+    } else if value.starts_with(&term) {
+        Some(0.9)
+    } else if value.contains(&term) {
+        Some(0.7)
+    } else {
+        None
+    }
+}
+
+/// Fragment matches, then all words present in any order, then trigram similarity.
+fn text_score(value: &str, term: &str) -> Option<f32> {
+    fragment_score(value, term)
+        .or_else(|| {
+            let value = value.to_lowercase();
+            let present: HashSet<&str> = words(&value).collect();
+            let term = term.to_lowercase();
+            words(&term).all(|w| present.contains(w)).then_some(0.65)
+        })
+        .or_else(|| {
+            let similarity = similarity(value, term);
+            (similarity >= SIMILARITY_THRESHOLD).then_some(0.6 * similarity)
+        })
+}
+
+/// Best scoring of the candidate fields, the first on ties.
+fn best(fields: impl IntoIterator<Item = (&'static str, Option<String>, Option<f32>)>) -> Option<(&'static str, String, f32)> {
+    fields.into_iter().fold(None, |best, (field, value, score)| match (value, score) {
+        (Some(value), Some(score)) if best.as_ref().is_none_or(|(_, _, s)| score > *s) => Some((field, value, score)),
+        _ => best,
+    })
+}
+
+fn device_match(term: &SearchTerm, device: &Device) -> Option<(&'static str, String, f32)> {
+    let address = device.ip_address.ip();
+    match term {
+        SearchTerm::Address(query) => (address == *query).then(|| ("ip_address", address.to_string(), 1.0)),
+        SearchTerm::Subnet(subnet) => subnet.contains(address).then(|| ("ip_address", address.to_string(), 0.8)),
+        SearchTerm::Mac(_) => None,
+        SearchTerm::Text(text) => best(
+            [("hostname", &device.hostname), ("sys_name", &device.sys_name), ("serial_number", &device.serial_number)]
+                .map(|(field, value)| (field, value.clone(), value.as_deref().and_then(|v| text_score(v, text)))),
+        ),
+    }
+}
+
+fn interface_match(term: &SearchTerm, interface: &Interface) -> Option<(&'static str, String, f32)> {
+    match term {
+        SearchTerm::Address(query) => {
+            let network = interface.ip_address?;
+            if network.ip() == *query {
+                Some(("ip_address", network.to_string(), 1.0))
+            } else {
+                // The interface serves the subnet the address is in
+                network.contains(*query).then(|| ("ip_address", network.to_string(), 0.5))
+            }
+        }
+        SearchTerm::Subnet(subnet) => {
+            let network = interface.ip_address?;
+            subnet.contains(network.ip()).then(|| ("ip_address", network.to_string(), 0.8))
+        }
+        SearchTerm::Mac(mac) => (interface.mac_address? == *mac).then(|| ("mac_address", mac.to_string(), 1.0)),
+        SearchTerm::Text(text) => {
+            let mac = interface.mac_address.and_then(|mac| {
+                let score = fragment_score(&mac.hex(), &mac_fragment(text)?)?;
+                Some(("mac_address", Some(mac.to_string()), Some(score)))
+            });
+            best(
+                [("if_name", &interface.if_name), ("if_alias", &interface.if_alias), ("if_descr", &interface.if_descr)]
+                    .map(|(field, value)| (field, value.clone(), value.as_deref().and_then(|v| text_score(v, text))))
+                    .into_iter()
+                    .chain(mac),
+            )
+        }
+    }
+}
+
+fn node_match(term: &SearchTerm, node: &Node) -> Option<(&'static str, String, f32)> {
+    let address = node.wireless.as_ref().and_then(|w| w.ip_address).map(|ip| ip.ip());
+    let (field, value, score) = match term {
+        SearchTerm::Address(query) => (address? == *query).then(|| ("ip_address", query.to_string(), 1.0)),
+        SearchTerm::Subnet(subnet) => {
+            let address = address?;
+            subnet.contains(address).then(|| ("ip_address", address.to_string(), 0.8))
+        }
+        SearchTerm::Mac(mac) => (node.mac_address == *mac).then(|| ("mac_address", mac.to_string(), 1.0)),
+        SearchTerm::Text(text) => {
+            let score = fragment_score(&node.mac_address.hex(), &mac_fragment(text)?)?;
+            Some(("mac_address", node.mac_address.to_string(), score))
+        }
+    }?;
+    // Sightings that are no longer current rank below current ones
+    Some((field, value, if node.active { score } else { score * 0.9 }))
+}
+
+fn arp_match(term: &SearchTerm, entry: &ArpEntry) -> Option<(&'static str, String, f32)> {
+    let address = entry.ip_address.ip();
+    match term {
+        SearchTerm::Address(query) => (address == *query).then(|| ("ip_address", address.to_string(), 1.0)),
+        SearchTerm::Subnet(subnet) => subnet.contains(address).then(|| ("ip_address", address.to_string(), 0.8)),
+        SearchTerm::Mac(mac) => (entry.mac_address == *mac).then(|| ("mac_address", mac.to_string(), 1.0)),
+        SearchTerm::Text(text) => {
+            let score = fragment_score(&entry.mac_address.hex(), &mac_fragment(text)?)?;
+            Some(("mac_address", entry.mac_address.to_string(), score))
+        }
+    }
+}
+
+/// Scores the candidates against the term and returns the best `limit` hits. Interfaces,
+/// nodes and ARP entries are labelled by their device when it is among `devices`.
+pub(crate) fn rank(
+    term: &SearchTerm,
+    devices: &[Device],
+    interfaces: &[Interface],
+    nodes: &[Node],
+    arp_entries: &[ArpEntry],
+    limit: u32,
+) -> Vec<SearchHit> {
+    let label = |device_id: Uuid| {
+        devices.iter().find(|d| d.id == device_id).map(device_label).unwrap_or_else(|| device_id.to_string())
+    };
+    let device_hits = devices.iter().filter_map(|device| {
+        let (field, value, score) = device_match(term, device)?;
+        Some(SearchHit {
+            kind: SearchHitKind::Device,
+            device_id: device.id,
+            device_label: device_label(device),
+            interface_id: None,
+            node_id: None,
+            arp_entry_id: None,
+            field,
+            value,
+            score,
+        })
+    });
+    let interface_hits = interfaces.iter().filter_map(|interface| {
+        let (field, value, score) = interface_match(term, interface)?;
+        Some(SearchHit {
+            kind: SearchHitKind::Interface,
+            device_id: interface.device_id,
+            device_label: label(interface.device_id),
+            interface_id: Some(interface.id),
+            node_id: None,
+            arp_entry_id: None,
+            field,
+            value,
+            score,
+        })
+    });
+    let node_hits = nodes.iter().filter_map(|node| {
+        let (field, value, score) = node_match(term, node)?;
+        Some(SearchHit {
+            kind: SearchHitKind::Node,
+            device_id: node.device_id,
+            device_label: label(node.device_id),
+            interface_id: node.interface_id,
+            node_id: Some(node.id),
+            arp_entry_id: None,
+            field,
+            value,
+            score,
+        })
+    });
+    let arp_hits = arp_entries.iter().filter_map(|entry| {
+        let (field, value, score) = arp_match(term, entry)?;
+        Some(SearchHit {
+            kind: SearchHitKind::Arp,
+            device_id: entry.device_id,
+            device_label: label(entry.device_id),
+            interface_id: entry.interface_id,
+            node_id: None,
+            arp_entry_id: Some(entry.id),
+            field,
+            value,
+            score,
+        })
+    });
+    let mut hits: Vec<SearchHit> = device_hits.chain(interface_hits).chain(node_hits).chain(arp_hits).collect();
+    hits.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(a.kind.cmp(&b.kind))
+            .then_with(|| a.device_label.cmp(&b.device_label))
+            .then_with(|| a.value.cmp(&b.value))
+    });
+    hits.truncate(limit as usize);
+    hits
+}
+
+const DEVICE_COLUMNS: &str = "id, hostname, ip_address, sys_name, sys_descr, sys_location, vendor, model, \
+     os_version, serial_number, status::text as status, device_type::text as device_type, location_id, \
+     tags, custom_fields, last_seen, last_boot, created_at, updated_at";
+
+const INTERFACE_COLUMNS: &str = "id, device_id, if_index, if_name, if_alias, if_descr, if_type, mac_address, \
+     ip_address, admin_status, oper_status, speed, mtu, access_vlan, last_changed, tags, custom_fields, \
+     created_at, updated_at";
+
+/// Searches devices, interfaces, nodes and ARP entries; see [`SearchTerm::parse`] for the query forms.
+pub async fn search(pool: &PgPool, query: &SearchQuery) -> Result<Vec<SearchHit>, DbError> {
+    let limit = i64::from(query.limit());
+    let (device_rows, interface_rows, node_rows, arp_entries) = match &query.term {
+        SearchTerm::Address(address) => {
+            let address = IpNetwork::from(*address);
+            (
+                sqlx::query(&format!("SELECT {} FROM devices WHERE ip_address >>= $1 LIMIT $2", DEVICE_COLUMNS))
+                    .bind(address)
+                    .bind(limit)
+                    .fetch_all(pool)
+                    .await?,
+                sqlx::query(&format!(
+                    "SELECT {} FROM interfaces WHERE ip_address >>= $1 ORDER BY masklen(ip_address) DESC LIMIT $2",
+                    INTERFACE_COLUMNS
+                ))
+                .bind(address)
+                .bind(limit)
+                .fetch_all(pool)
+                .await?,
+                sqlx::query(&format!("{} WHERE w.ip_address >>= $1 ORDER BY n.last_seen DESC LIMIT $2", NODE_QUERY))
+                    .bind(address)
+                    .bind(limit)
+                    .fetch_all(pool)
+                    .await?,
+                sqlx::query_as!(
+                    ArpEntry,
+                    r#"SELECT id, device_id, interface_id, ip_address, mac_address as "mac_address: MacAddress",
+                              first_seen, last_seen
+                       FROM arp_entries WHERE ip_address >>= $1 ORDER BY last_seen DESC LIMIT $2"#,
+                    address,
+                    limit
+                )
+                .fetch_all(pool)
+                .await?,
+            )
+        }
+        SearchTerm::Subnet(subnet) => (
+            // Overlap uses the GiST indexes; rank() keeps what is really inside
+            sqlx::query(&format!("SELECT {} FROM devices WHERE ip_address && $1 ORDER BY ip_address LIMIT $2", DEVICE_COLUMNS))
+                .bind(subnet)
+                .bind(limit)
+                .fetch_all(pool)
+                .await?,
+            sqlx::query(&format!(
+                "SELECT {} FROM interfaces WHERE ip_address && $1 ORDER BY ip_address LIMIT $2",
+                INTERFACE_COLUMNS
+            ))
+            .bind(subnet)
+            .bind(limit)
+            .fetch_all(pool)
+            .await?,
+            sqlx::query(&format!("{} WHERE w.ip_address && $1 ORDER BY w.ip_address LIMIT $2", NODE_QUERY))
+                .bind(subnet)
+                .bind(limit)
+                .fetch_all(pool)
+                .await?,
+            sqlx::query_as!(
+                ArpEntry,
+                r#"SELECT id, device_id, interface_id, ip_address, mac_address as "mac_address: MacAddress",
+                          first_seen, last_seen
+                   FROM arp_entries WHERE ip_address && $1 ORDER BY ip_address LIMIT $2"#,
+                subnet,
+                limit
+            )
+            .fetch_all(pool)
+            .await?,
+        ),
+        SearchTerm::Mac(mac) => (
+            Vec::new(),
+            sqlx::query(&format!("SELECT {} FROM interfaces WHERE mac_address = $1 LIMIT $2", INTERFACE_COLUMNS))
+                .bind(mac)
+                .bind(limit)
+                .fetch_all(pool)
+                .await?,
+            sqlx::query(&format!("{} WHERE n.mac_address = $1 ORDER BY n.active DESC, n.last_seen DESC LIMIT $2", NODE_QUERY))
+                .bind(mac)
+                .bind(limit)
+                .fetch_all(pool)
+                .await?,
+            sqlx::query_as!(
+                ArpEntry,
+                r#"SELECT id, device_id, interface_id, ip_address, mac_address as "mac_address: MacAddress",
+                          first_seen, last_seen
+                   FROM arp_entries WHERE mac_address = $1 ORDER BY last_seen DESC LIMIT $2"#,
+                mac as &MacAddress,
+                limit
+            )
+            .fetch_all(pool)
+            .await?,
+        ),
+        SearchTerm::Text(text) => {
+            let pattern = format!("%{}%", text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+            // An empty pattern matches nothing when the text cannot be part of a MAC
+            let mac_pattern = mac_fragment(text).map(|hex| format!("%{}%", hex)).unwrap_or_default();
+            (
+                sqlx::query(&format!(
+                    r#"SELECT {} FROM devices
+                       WHERE hostname ILIKE $1 OR sys_name ILIKE $1 OR serial_number ILIKE $1
+                          OR hostname % $2 OR sys_name % $2 OR serial_number % $2
+                       ORDER BY GREATEST(similarity(hostname, $2), similarity(sys_name, $2), similarity(serial_number, $2)) DESC
+                       LIMIT $3"#,
+                    DEVICE_COLUMNS
+                ))
+                .bind(&pattern)
+                .bind(text)
+                .bind(limit)
+                .fetch_all(pool)
+                .await?,
+                sqlx::query(&format!(
+                    r#"SELECT {} FROM interfaces
+                       WHERE if_name ILIKE $1 OR if_alias ILIKE $1 OR if_alias % $2
+                          OR to_tsvector('simple', COALESCE(if_alias, '') || ' ' || COALESCE(if_descr, ''))
+                             @@ plainto_tsquery('simple', $2)
+                          OR replace(mac_address::text, ':', '') LIKE $3
+                       ORDER BY GREATEST(similarity(if_name, $2), similarity(if_alias, $2)) DESC
+                       LIMIT $4"#,
+                    INTERFACE_COLUMNS
+                ))
+                .bind(&pattern)
+                .bind(text)
+                .bind(&mac_pattern)
+                .bind(limit)
+                .fetch_all(pool)
+                .await?,
+                sqlx::query(&format!(
+                    "{} WHERE replace(n.mac_address::text, ':', '') LIKE $1 ORDER BY n.active DESC, n.last_seen DESC LIMIT $2",
+                    NODE_QUERY
+                ))
+                .bind(&mac_pattern)
+                .bind(limit)
+                .fetch_all(pool)
+                .await?,
+                sqlx::query_as!(
+                    ArpEntry,
+                    r#"SELECT id, device_id, interface_id, ip_address, mac_address as "mac_address: MacAddress",
+                              first_seen, last_seen
+                       FROM arp_entries WHERE replace(mac_address::text, ':', '') LIKE $1
+                       ORDER BY last_seen DESC LIMIT $2"#,
+                    mac_pattern,
+                    limit
+                )
+                .fetch_all(pool)
+                .await?,
+            )
+        }
+    };
+    let mut devices = device_rows.iter().map(device_from_row).collect::<Result<Vec<_>, _>>()?;
+    let interfaces = interface_rows.iter().map(interface_from_row).collect::<Result<Vec<_>, _>>()?;
+    let nodes = node_rows.iter().map(node_from_row).collect::<Result<Vec<_>, _>>()?;
+
+    // Devices of interface, node and ARP hits, for their labels
+    let known: HashSet<Uuid> = devices.iter().map(|d| d.id).collect();
+    let owners: Vec<Uuid> = interfaces
+        .iter()
+        .map(|i| i.device_id)
+        .chain(nodes.iter().map(|n| n.device_id))
+        .chain(arp_entries.iter().map(|e| e.device_id))
+        .filter(|id| !known.contains(id))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    if !owners.is_empty() {
+        let rows = sqlx::query(&format!("SELECT {} FROM devices WHERE id = ANY($1)", DEVICE_COLUMNS))
+            .bind(&owners)
+            .fetch_all(pool)
+            .await?;
+        for row in &rows {
+            devices.push(device_from_row(row)?);
+        }
+    }
+    Ok(rank(&query.term, &devices, &interfaces, &nodes, &arp_entries, query.limit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CustomFieldValues, NodeWireless};
+    use time::OffsetDateTime;
+
+    fn device(hostname: &str, ip: &str, serial: &str) -> Device {
+        let now = OffsetDateTime::now_utc();
+        Device {
+            id: Uuid::new_v4(),
+            hostname: Some(hostname.to_string()),
+            serial_number: Some(serial.to_string()),
+            last_seen: Some(now),
+            ..Device::new(ip.parse().unwrap())
+        }
+    }
+
+    fn interface(device_id: Uuid, alias: &str, ip: &str, mac: &str) -> Interface {
+        let now = OffsetDateTime::now_utc();
+        Interface {
+            id: Uuid::new_v4(),
+            device_id,
+            if_index: 1,
+            if_name: Some("Gi1/0/1".to_string()),
+            if_alias: Some(alias.to_string()),
+            if_descr: None,
+            if_type: None,
+            mac_address: Some(mac.parse().unwrap()),
+            ip_address: Some(ip.parse().unwrap()),
+            admin_status: None,
+            oper_status: None,
+            speed: None,
+            mtu: None,
+            access_vlan: None,
+            last_changed: None,
+            tags: Vec::new(),
+            custom_fields: CustomFieldValues::new(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn classifies_queries() {
+        assert_eq!(SearchTerm::parse(" 10.0.0.1 ").unwrap(), SearchTerm::Address("10.0.0.1".parse().unwrap()));
+        assert_eq!(SearchTerm::parse("10.0.0.0/8").unwrap(), SearchTerm::Subnet("10.0.0.0/8".parse().unwrap()));
+        let mac = SearchTerm::Mac("00:1a:2b:3c:4d:5e".parse().unwrap());
+        assert_eq!(SearchTerm::parse("001A.2B3C.4D5E").unwrap(), mac);
+        assert_eq!(SearchTerm::parse("00-1a-2b-3c-4d-5e").unwrap(), mac);
+        assert_eq!(SearchTerm::parse("core-sw").unwrap(), SearchTerm::Text("core-sw".to_string()));
+        assert!(SearchTerm::parse(" x ").is_err());
+    }
+
+    #[test]
+    fn ranks_exact_before_prefix_fragment_and_typo() {
+        let exact = device("core", "10.0.0.1/32", "FOC1111");
+        let prefix = device("core-sw1", "10.0.0.2/32", "FOC2222");
+        let fragment = device("dc1-core", "10.0.0.3/32", "FOC3333");
+        let typo = device("coer", "10.0.0.4/32", "FOC4444");
+        let devices = [typo, fragment, prefix, exact];
+        let hits = rank(&SearchTerm::Text("Core".to_string()), &devices, &[], &[], &[], 10);
+        let labels: Vec<&str> = hits.iter().map(|h| h.device_label.as_str()).collect();
+        assert_eq!(labels, ["core", "core-sw1", "dc1-core"]);
+
+        let hits = rank(&SearchTerm::Text("core-sw2".to_string()), &devices, &[], &[], &[], 10);
+        assert_eq!(hits[0].device_label, "core-sw1"); // Fuzzy
+        assert!(hits[0].score < 0.6);
+
+        let hits = rank(&SearchTerm::Text("foc3333".to_string()), &devices, &[], &[], &[], 10);
+        assert_eq!((hits[0].field, hits[0].score), ("serial_number", 1.0));
+    }
+
+    #[test]
+    fn finds_addresses_macs_and_descriptions_on_interfaces_and_nodes() {
+        let router = device("edge-1", "192.0.2.1/32", "FOC1");
+        let uplink = interface(router.id, "Uplink to ISP core", "198.51.100.1/30", "00:1a:2b:3c:4d:5e");
+        let now = OffsetDateTime::now_utc();
+        let node = Node {
+            id: Uuid::new_v4(),
+            mac_address: "02:00:00:aa:bb:cc".parse().unwrap(),
+            device_id: router.id,
+            interface_id: Some(uplink.id),
+            vlan: 10,
+            active: true,
+            first_seen: now,
+            last_seen: now,
+            wireless: Some(NodeWireless { ssid: None, rssi: None, ip_address: Some("198.51.100.2/32".parse().unwrap()), last_seen: now }),
+        };
+        let devices = [router];
+        let interfaces = [uplink];
+        let nodes = [node];
+        let search = |q: &str| rank(&SearchTerm::parse(q).unwrap(), &devices, &interfaces, &nodes, &[], 10);
+
+        let hits = search("198.51.100.2");
+        assert_eq!(hits.iter().map(|h| (h.kind, h.score)).collect::<Vec<_>>(), [
+            (SearchHitKind::Node, 1.0),
+            (SearchHitKind::Interface, 0.5)
+        ]);
+        assert_eq!(hits[0].device_label, "edge-1");
+        assert_eq!(search("198.51.100.0/24").len(), 2);
+        assert_eq!(search("001a.2b3c.4d5e")[0].kind, SearchHitKind::Interface);
+        assert_eq!(search("aa:bb:cc")[0].node_id, Some(nodes[0].id));
+        let hits = search("core uplink");
+        assert_eq!((hits[0].field, hits[0].value.as_str()), ("if_alias", "Uplink to ISP core"));
+    }
+
+    #[test]
+    fn finds_addresses_and_macs_in_arp_tables() {
+        let router = device("core-1", "192.0.2.1/32", "FOC1");
+        let vlan = interface(router.id, "Users", "10.1.20.1/24", "00:1a:2b:3c:4d:01");
+        let now = OffsetDateTime::now_utc();
+        let entry = ArpEntry {
+            id: Uuid::new_v4(),
+            device_id: router.id,
+            interface_id: Some(vlan.id),
+            ip_address: "10.1.20.9/32".parse().unwrap(),
+            mac_address: "02:00:00:aa:bb:cc".parse().unwrap(),
+            first_seen: now,
+            last_seen: now,
+        };
+        let devices = [router];
+        let interfaces = [vlan];
+        let arp_entries = [entry];
+        let search = |q: &str| rank(&SearchTerm::parse(q).unwrap(), &devices, &interfaces, &[], &arp_entries, 10);
+
+        let hits = search("10.1.20.9");
+        assert_eq!(hits.iter().map(|h| (h.kind, h.score)).collect::<Vec<_>>(), [
+            (SearchHitKind::Arp, 1.0),
+            (SearchHitKind::Interface, 0.5)
+        ]);
+        assert_eq!((hits[0].device_label.as_str(), hits[0].interface_id), ("core-1", Some(interfaces[0].id)));
+        assert_eq!(hits[0].arp_entry_id, Some(arp_entries[0].id));
+        assert_eq!(search("10.1.20.0/24").len(), 2);
+        assert_eq!(search("0200.00aa.bbcc")[0].kind, SearchHitKind::Arp);
+        assert_eq!(search("aa:bb:cc")[0].value, "02:00:00:aa:bb:cc");
+    }
+}
@@ -0,0 +1,3001 @@
+//! SQLite backend for single-host and lab deployments (`sqlite` feature).
+//!
+//! Uses its own schema (`migrations/sqlite`) with addresses stored as canonical text, so
+//! subnet, location and metadata filters and search are evaluated in memory over loaded rows,
+//! which is fine at the size of a site that runs without Postgres. Merging, history and
+//! placement reuse the same rules as the Postgres queries. There is no change feed.
+
+use crate::{
+    is_manual_source, DeviceNeighbor, LinkRefresh, LinkRepository, LinkSource, NeighborProtocol, TopologyLink, AllowedMac, AllowlistRepository, Anomaly, AnomalyFilter, ArpEntry, HostnameRepository, IpHostname, IpSighting, IpamRepository, Subnet, SubnetRefresh, AnomalyKind, AnomalyReport, AnomalyRepository, CustomField, CustomFieldObject, CustomFieldRepository, CustomFieldType, CustomFieldValues, DbError, Device,
+    DeviceField, DeviceFilter, DeviceGroup, DeviceHistory, DevicePage, DeviceQuery, DeviceRepository, DeviceStatus,
+    DeviceType, GroupRepository, Interface, InterfacePoe, InterfaceRepository, Location, LocationKind, LocationRepository,
+    LocationRule, MacAddress, MacVendor, MacViolation, MacViolationFilter, MetadataFilter, MetadataUpdate, Node, NodeRepository, NodeWireless, PortChange,
+    RetentionReport, RetentionRepository, RoutingNeighbor, RoutingProtocol, SearchHit, SearchQuery, SearchRepository,
+    StpInstance, StpPort, StpRoot, TopologyRepository,
+    VendorRepository, WirelessAp,
+};
+use crate::allowlist;
+use crate::anomalies::{self, IpClaim, NodeMove};
+use crate::bulk;
+use crate::device_query;
+use crate::groups;
+use crate::history::{self, AttributeChange};
+use crate::ipam;
+use crate::links::{self, LinkDevice, LinkPort, PortSighting};
+use crate::locations::{self, LOCATION_FIELD, RULES_SOURCE};
+use crate::merge::{self, FieldSources};
+use crate::metadata;
+use crate::retention::{self, RETENTION_SOURCE};
+use crate::search;
+use async_trait::async_trait;
+use ipnetwork::IpNetwork;
+use nd_core::{AnomalySettings, RetentionSettings};
+use sqlx::sqlite::{SqlitePool, SqliteRow};
+use sqlx::types::Json;
+use sqlx::{Row, SqliteConnection};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+const DEVICE_COLUMNS: &str = "id, hostname, ip_address, sys_name, sys_descr, sys_location, vendor, model, os_version, \
+     serial_number, status, device_type, location_id, tags, custom_fields, last_seen, last_boot, created_at, updated_at";
+
+const INTERFACE_COLUMNS: &str = "id, device_id, if_index, if_name, if_alias, if_descr, if_type, mac_address, ip_address, \
+     admin_status, oper_status, speed, mtu, access_vlan, last_changed, tags, custom_fields, created_at, updated_at";
+
+const NODE_QUERY: &str = "SELECT n.id, n.mac_address, n.device_id, n.interface_id, n.vlan, n.active, n.first_seen, \
+     n.last_seen, w.node_id as wireless_node_id, w.ssid, w.rssi, w.ip_address as wireless_ip_address, \
+     w.last_seen as wireless_last_seen FROM nodes n LEFT JOIN node_wireless w ON w.node_id = n.id";
+
+fn mapping(error: impl Display) -> DbError {
+    DbError::MappingError(error.to_string())
+}
+
+fn unique_violation(error: sqlx::Error, message: impl FnOnce() -> String) -> DbError {
+    match error.as_database_error() {
+        Some(db_error) if db_error.is_unique_violation() => DbError::InvalidQuery(message()),
+        _ => DbError::from(error),
+    }
+}
+
+fn ip(row: &SqliteRow, column: &str) -> Result<Option<IpNetwork>, DbError> {
+    row.try_get::<Option<String>, _>(column)?.map(|text| text.parse().map_err(mapping)).transpose()
+}
+
+fn mac(row: &SqliteRow, column: &str) -> Result<Option<MacAddress>, DbError> {
+    row.try_get::<Option<String>, _>(column)?.map(|text| text.parse().map_err(mapping)).transpose()
+}
+
+fn device_from_row(row: &SqliteRow) -> Result<Device, DbError> {
+    Ok(Device {
+        id: row.try_get("id")?,
+        hostname: row.try_get("hostname")?,
+        ip_address: ip(row, "ip_address")?.ok_or_else(|| mapping("device without ip_address"))?,
+        sys_name: row.try_get("sys_name")?,
+        sys_descr: row.try_get("sys_descr")?,
+        sys_location: row.try_get("sys_location")?,
+        vendor: row.try_get("vendor")?,
+        model: row.try_get("model")?,
+        os_version: row.try_get("os_version")?,
+        serial_number: row.try_get("serial_number")?,
+        status: row.try_get::<Option<String>, _>("status")?.map(DeviceStatus::try_from).transpose().map_err(mapping)?,
+        device_type: row.try_get::<Option<String>, _>("device_type")?.map(DeviceType::try_from).transpose().map_err(mapping)?,
+        location_id: row.try_get("location_id")?,
+        tags: row.try_get::<Json<Vec<String>>, _>("tags")?.0,
+        custom_fields: row.try_get::<Json<CustomFieldValues>, _>("custom_fields")?.0,
+        last_seen: row.try_get("last_seen")?,
+        last_boot: row.try_get("last_boot")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
+
+fn interface_from_row(row: &SqliteRow) -> Result<Interface, DbError> {
+    Ok(Interface {
+        id: row.try_get("id")?,
+        device_id: row.try_get("device_id")?,
+        if_index: row.try_get("if_index")?,
+        if_name: row.try_get("if_name")?,
+        if_alias: row.try_get("if_alias")?,
+        if_descr: row.try_get("if_descr")?,
+        if_type: row.try_get("if_type")?,
+        mac_address: mac(row, "mac_address")?,
+        ip_address: ip(row, "ip_address")?,
+        admin_status: row.try_get("admin_status")?,
+        oper_status: row.try_get("oper_status")?,
+        speed: row.try_get("speed")?,
+        mtu: row.try_get("mtu")?,
+        access_vlan: row.try_get("access_vlan")?,
+        last_changed: row.try_get("last_changed")?,
+        tags: row.try_get::<Json<Vec<String>>, _>("tags")?.0,
+        custom_fields: row.try_get::<Json<CustomFieldValues>, _>("custom_fields")?.0,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
+
+fn node_from_row(row: &SqliteRow) -> Result<Node, DbError> {
+    let wireless = match row.try_get::<Option<Uuid>, _>("wireless_node_id")? {
+        Some(_) => Some(NodeWireless {
+            ssid: row.try_get("ssid")?,
+            rssi: row.try_get("rssi")?,
+            ip_address: ip(row, "wireless_ip_address")?,
+            last_seen: row.try_get("wireless_last_seen")?,
+        }),
+        None => None,
+    };
+    Ok(Node {
+        id: row.try_get("id")?,
+        mac_address: mac(row, "mac_address")?.ok_or_else(|| mapping("node without mac_address"))?,
+        device_id: row.try_get("device_id")?,
+        interface_id: row.try_get("interface_id")?,
+        vlan: row.try_get("vlan")?,
+        active: row.try_get("active")?,
+        first_seen: row.try_get("first_seen")?,
+        last_seen: row.try_get("last_seen")?,
+        wireless,
+    })
+}
+
+fn subnet_from_row(row: &SqliteRow) -> Result<Subnet, DbError> {
+    Ok(Subnet {
+        id: row.try_get("id")?,
+        network: ip(row, "network")?.ok_or_else(|| mapping("subnet without network"))?,
+        first_seen: row.try_get("first_seen")?,
+        last_seen: row.try_get("last_seen")?,
+    })
+}
+
+fn arp_entry_from_row(row: &SqliteRow) -> Result<ArpEntry, DbError> {
+    Ok(ArpEntry {
+        id: row.try_get("id")?,
+        device_id: row.try_get("device_id")?,
+        interface_id: row.try_get("interface_id")?,
+        ip_address: ip(row, "ip_address")?.ok_or_else(|| mapping("ARP entry without ip_address"))?,
+        mac_address: mac(row, "mac_address")?.ok_or_else(|| mapping("ARP entry without mac_address"))?,
+        first_seen: row.try_get("first_seen")?,
+        last_seen: row.try_get("last_seen")?,
+    })
+}
+
+fn location_from_row(row: &SqliteRow) -> Result<Location, DbError> {
+    Ok(Location {
+        id: row.try_get("id")?,
+        parent_id: row.try_get("parent_id")?,
+        kind: LocationKind::try_from(row.try_get::<String, _>("kind")?).map_err(mapping)?,
+        name: row.try_get("name")?,
+        description: row.try_get("description")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
+
+fn rule_from_row(row: &SqliteRow) -> Result<LocationRule, DbError> {
+    Ok(LocationRule {
+        id: row.try_get("id")?,
+        location_id: row.try_get("location_id")?,
+        priority: row.try_get("priority")?,
+        sys_location_pattern: row.try_get("sys_location_pattern")?,
+        subnet: ip(row, "subnet")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+fn group_from_row(row: &SqliteRow) -> Result<DeviceGroup, DbError> {
+    Ok(DeviceGroup {
+        id: row.try_get("id")?,
+        name: row.try_get("name")?,
+        description: row.try_get("description")?,
+        rules: row.try_get::<Option<Json<DeviceFilter>>, _>("rules")?.map(|rules| rules.0),
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
+
+fn custom_field_from_row(row: &SqliteRow) -> Result<CustomField, DbError> {
+    Ok(CustomField {
+        id: row.try_get("id")?,
+        object_type: CustomFieldObject::try_from(row.try_get::<String, _>("object_type")?).map_err(mapping)?,
+        name: row.try_get("name")?,
+        field_type: CustomFieldType::try_from(row.try_get::<String, _>("field_type")?).map_err(mapping)?,
+        choices: row.try_get::<Json<Vec<String>>, _>("choices")?.0,
+        description: row.try_get("description")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
+
+async fn device_by_id(conn: &mut SqliteConnection, id: Uuid) -> Result<Device, DbError> {
+    let row = sqlx::query(&format!("SELECT {} FROM devices WHERE id = ?", DEVICE_COLUMNS))
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(DbError::NotFound)?;
+    device_from_row(&row)
+}
+
+async fn all_devices(conn: &mut SqliteConnection) -> Result<Vec<Device>, DbError> {
+    let rows = sqlx::query(&format!("SELECT {} FROM devices", DEVICE_COLUMNS)).fetch_all(&mut *conn).await?;
+    rows.iter().map(device_from_row).collect()
+}
+
+async fn insert_device(conn: &mut SqliteConnection, device: &Device) -> Result<(), DbError> {
+    sqlx::query(&format!(
+        "INSERT INTO devices ({}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        DEVICE_COLUMNS
+    ))
+    .bind(device.id)
+    .bind(&device.hostname)
+    .bind(device.ip_address.to_string())
+    .bind(&device.sys_name)
+    .bind(&device.sys_descr)
+    .bind(&device.sys_location)
+    .bind(&device.vendor)
+    .bind(&device.model)
+    .bind(&device.os_version)
+    .bind(&device.serial_number)
+    .bind(device.status.as_ref().map(DeviceStatus::as_str))
+    .bind(device.device_type.map(|t| t.as_str()))
+    .bind(device.location_id)
+    .bind(Json(&device.tags))
+    .bind(Json(&device.custom_fields))
+    .bind(device.last_seen)
+    .bind(device.last_boot)
+    .bind(device.created_at)
+    .bind(device.updated_at)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Writes every column of a stored device.
+async fn save_device(conn: &mut SqliteConnection, device: &Device) -> Result<(), DbError> {
+    sqlx::query(
+        r#"
+        UPDATE devices SET
+            hostname = ?, ip_address = ?, sys_name = ?, sys_descr = ?, sys_location = ?, vendor = ?, model = ?,
+            os_version = ?, serial_number = ?, status = ?, device_type = ?, location_id = ?, tags = ?,
+            custom_fields = ?, last_seen = ?, last_boot = ?, updated_at = ?
+        WHERE id = ?
+        "#,
+    )
+    .bind(&device.hostname)
+    .bind(device.ip_address.to_string())
+    .bind(&device.sys_name)
+    .bind(&device.sys_descr)
+    .bind(&device.sys_location)
+    .bind(&device.vendor)
+    .bind(&device.model)
+    .bind(&device.os_version)
+    .bind(&device.serial_number)
+    .bind(device.status.as_ref().map(DeviceStatus::as_str))
+    .bind(device.device_type.map(|t| t.as_str()))
+    .bind(device.location_id)
+    .bind(Json(&device.tags))
+    .bind(Json(&device.custom_fields))
+    .bind(device.last_seen)
+    .bind(device.last_boot)
+    .bind(device.updated_at)
+    .bind(device.id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+async fn load_sources(conn: &mut SqliteConnection, device_id: Uuid) -> Result<FieldSources, DbError> {
+    let rows = sqlx::query("SELECT field, source FROM device_field_sources WHERE device_id = ?")
+        .bind(device_id)
+        .fetch_all(&mut *conn)
+        .await?;
+    rows.iter().map(|row| Ok((row.try_get("field")?, row.try_get("source")?))).collect()
+}
+
+async fn save_sources(conn: &mut SqliteConnection, device_id: Uuid, sources: &FieldSources) -> Result<(), DbError> {
+    sqlx::query("DELETE FROM device_field_sources WHERE device_id = ?").bind(device_id).execute(&mut *conn).await?;
+    for (field, source) in sources {
+        sqlx::query("INSERT INTO device_field_sources (device_id, field, source) VALUES (?, ?, ?)")
+            .bind(device_id)
+            .bind(field)
+            .bind(source)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+async fn record_changes(
+    conn: &mut SqliteConnection,
+    device_id: Uuid,
+    changes: &[AttributeChange],
+    source: &str,
+    now: OffsetDateTime,
+) -> Result<(), DbError> {
+    for change in changes {
+        sqlx::query(
+            "INSERT INTO device_history (id, device_id, attribute, old_value, new_value, source, changed_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(Uuid::new_v4())
+        .bind(device_id)
+        .bind(change.attribute)
+        .bind(&change.old_value)
+        .bind(&change.new_value)
+        .bind(source)
+        .bind(now)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+async fn load_rules(conn: &mut SqliteConnection) -> Result<Vec<LocationRule>, DbError> {
+    let rows = sqlx::query(
+        "SELECT id, location_id, priority, sys_location_pattern, subnet, created_at \
+         FROM location_rules ORDER BY priority, julianday(created_at), id",
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    rows.iter().map(rule_from_row).collect()
+}
+
+async fn load_locations(conn: &mut SqliteConnection) -> Result<Vec<Location>, DbError> {
+    let rows = sqlx::query("SELECT id, parent_id, kind, name, description, created_at, updated_at FROM locations")
+        .fetch_all(&mut *conn)
+        .await?;
+    rows.iter().map(location_from_row).collect()
+}
+
+async fn location_by_id(conn: &mut SqliteConnection, id: Uuid) -> Result<Location, DbError> {
+    let row = sqlx::query("SELECT id, parent_id, kind, name, description, created_at, updated_at FROM locations WHERE id = ?")
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(DbError::NotFound)?;
+    location_from_row(&row)
+}
+
+fn unknown_location(error: DbError) -> DbError {
+    match error {
+        DbError::NotFound => DbError::InvalidQuery("unknown location".to_string()),
+        other => other,
+    }
+}
+
+async fn load_custom_fields(conn: &mut SqliteConnection) -> Result<Vec<CustomField>, DbError> {
+    let rows = sqlx::query(
+        "SELECT id, object_type, name, field_type, choices, description, created_at, updated_at \
+         FROM custom_fields ORDER BY object_type, name",
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    rows.iter().map(custom_field_from_row).collect()
+}
+
+async fn interface_id(conn: &mut SqliteConnection, device_id: Uuid, if_index: i32) -> Result<Option<Uuid>, DbError> {
+    Ok(sqlx::query_scalar("SELECT id FROM interfaces WHERE device_id = ? AND if_index = ?")
+        .bind(device_id)
+        .bind(if_index)
+        .fetch_optional(&mut *conn)
+        .await?)
+}
+
+/// Device filter including the location subtree; groups are resolved by the caller.
+fn device_matcher<'a>(filter: &'a DeviceFilter, all_locations: &[Location]) -> impl Fn(&Device) -> bool + 'a {
+    let subtree = filter.location_id.map(|root| locations::subtree(all_locations, root));
+    move |device| {
+        filter.matches(device)
+            && subtree.as_ref().is_none_or(|subtree| device.location_id.is_some_and(|l| subtree.contains(&l)))
+    }
+}
+
+/// A [`Repository`](crate::Repository) over a SQLite database.
+#[derive(Debug, Clone)]
+pub struct SqliteRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    async fn upsert_device_in(
+        conn: &mut SqliteConnection,
+        rules: &[LocationRule],
+        device: &Device,
+        source: &str,
+    ) -> Result<Device, DbError> {
+        let now = OffsetDateTime::now_utc();
+        let existing = sqlx::query(&format!("SELECT {} FROM devices WHERE ip_address = ?", DEVICE_COLUMNS))
+            .bind(device.ip_address.to_string())
+            .fetch_optional(&mut *conn)
+            .await?
+            .map(|row| device_from_row(&row))
+            .transpose()?;
+        match existing {
+            Some(existing) => {
+                let mut sources = load_sources(conn, existing.id).await?;
+                let (merged, source_updates) = merge::merge_device(&existing, &sources, device, source);
+                let merged =
+                    Device { location_id: locations::resolve_location(rules, &sources, &merged), updated_at: now, ..merged };
+                save_device(conn, &merged).await?;
+                merge::update_sources(&mut sources, &source_updates, source);
+                save_sources(conn, existing.id, &sources).await?;
+                record_changes(conn, existing.id, &history::device_changes(&existing, &merged), source, now).await?;
+                Ok(merged)
+            }
+            None => {
+                // Tags and custom fields are only set through set_device_metadata
+                let new = Device {
+                    id: Uuid::new_v4(),
+                    tags: Vec::new(),
+                    custom_fields: CustomFieldValues::new(),
+                    created_at: now,
+                    updated_at: now,
+                    ..device.clone()
+                };
+                let new = Device { location_id: locations::resolve_location(rules, &FieldSources::new(), &new), ..new };
+                insert_device(conn, &new).await?;
+                let mut sources = FieldSources::new();
+                merge::update_sources(&mut sources, &merge::initial_sources(device), source);
+                save_sources(conn, new.id, &sources).await?;
+                Ok(new)
+            }
+        }
+    }
+
+    async fn upsert_interface_in(conn: &mut SqliteConnection, interface: &Interface) -> Result<Interface, DbError> {
+        let now = OffsetDateTime::now_utc();
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO interfaces (
+                id, device_id, if_index, if_name, if_alias, if_descr, if_type, mac_address, ip_address,
+                admin_status, oper_status, speed, mtu, access_vlan, last_changed, created_at, updated_at
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (device_id, if_index) DO UPDATE SET
+                if_name = excluded.if_name,
+                if_alias = excluded.if_alias,
+                if_descr = excluded.if_descr,
+                if_type = excluded.if_type,
+                mac_address = excluded.mac_address,
+                ip_address = COALESCE(excluded.ip_address, interfaces.ip_address),
+                admin_status = excluded.admin_status,
+                oper_status = excluded.oper_status,
+                speed = excluded.speed,
+                mtu = excluded.mtu,
+                access_vlan = COALESCE(excluded.access_vlan, interfaces.access_vlan),
+                last_changed = COALESCE(excluded.last_changed, interfaces.last_changed),
+                updated_at = excluded.updated_at
+            RETURNING {}
+            "#,
+            INTERFACE_COLUMNS
+        ))
+        .bind(Uuid::new_v4())
+        .bind(interface.device_id)
+        .bind(interface.if_index)
+        .bind(&interface.if_name)
+        .bind(&interface.if_alias)
+        .bind(&interface.if_descr)
+        .bind(&interface.if_type)
+        .bind(interface.mac_address.map(|m| m.to_string()))
+        .bind(interface.ip_address.map(|ip| ip.to_string()))
+        .bind(&interface.admin_status)
+        .bind(&interface.oper_status)
+        .bind(interface.speed)
+        .bind(interface.mtu)
+        .bind(interface.access_vlan)
+        .bind(interface.last_changed)
+        .bind(now)
+        .bind(now)
+        .fetch_one(&mut *conn)
+        .await?;
+        interface_from_row(&row)
+    }
+
+    /// Returns the interface the PoE port belongs to, or `None` if it is unknown.
+    async fn upsert_poe_in(conn: &mut SqliteConnection, device_id: Uuid, poe: &InterfacePoe) -> Result<Option<Uuid>, DbError> {
+        let Some(interface_id) = interface_id(conn, device_id, poe.if_index).await? else {
+            return Ok(None);
+        };
+        let now = OffsetDateTime::now_utc();
+        sqlx::query(
+            r#"
+            INSERT INTO interface_poe (
+                interface_id, group_index, port_index, admin_enabled, detection_status, power_class, power_mw,
+                last_seen, created_at, updated_at
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (interface_id) DO UPDATE SET
+                group_index = excluded.group_index,
+                port_index = excluded.port_index,
+                admin_enabled = excluded.admin_enabled,
+                detection_status = excluded.detection_status,
+                power_class = excluded.power_class,
+                power_mw = excluded.power_mw,
+                last_seen = excluded.last_seen,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(interface_id)
+        .bind(poe.group_index)
+        .bind(poe.port_index)
+        .bind(poe.admin_enabled)
+        .bind(&poe.detection_status)
+        .bind(&poe.power_class)
+        .bind(poe.power_mw)
+        .bind(now)
+        .bind(now)
+        .bind(now)
+        .execute(&mut *conn)
+        .await?;
+        Ok(Some(interface_id))
+    }
+
+    async fn upsert_node_in(conn: &mut SqliteConnection, node: &Node) -> Result<Uuid, DbError> {
+        let id: Uuid = sqlx::query_scalar(
+            r#"
+            INSERT INTO nodes (id, mac_address, device_id, interface_id, vlan, active, first_seen, last_seen)
+            VALUES (?, ?, ?, ?, ?, 1, ?, ?)
+            ON CONFLICT (mac_address, device_id, vlan) DO UPDATE SET
+                interface_id = excluded.interface_id,
+                active = 1,
+                last_seen = excluded.last_seen
+            RETURNING id
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(node.mac_address.to_string())
+        .bind(node.device_id)
+        .bind(node.interface_id)
+        .bind(node.vlan)
+        .bind(OffsetDateTime::now_utc())
+        .bind(node.last_seen)
+        .fetch_one(&mut *conn)
+        .await?;
+        if let Some(wireless) = &node.wireless {
+            sqlx::query(
+                r#"
+                INSERT INTO node_wireless (node_id, ssid, rssi, ip_address, last_seen)
+                VALUES (?, ?, ?, ?, ?)
+                ON CONFLICT (node_id) DO UPDATE SET
+                    ssid = excluded.ssid,
+                    rssi = excluded.rssi,
+                    ip_address = excluded.ip_address,
+                    last_seen = excluded.last_seen
+                "#,
+            )
+            .bind(id)
+            .bind(&wireless.ssid)
+            .bind(wireless.rssi)
+            .bind(wireless.ip_address.map(|ip| ip.to_string()))
+            .bind(wireless.last_seen)
+            .execute(&mut *conn)
+            .await?;
+        }
+        Ok(id)
+    }
+}
+
+#[async_trait]
+impl DeviceRepository for SqliteRepository {
+    async fn upsert_device(&self, device: &Device, source: &str) -> Result<Device, DbError> {
+        let mut tx = self.pool.begin().await?;
+        let rules = load_rules(&mut tx).await?;
+        let stored = Self::upsert_device_in(&mut tx, &rules, device, source).await?;
+        tx.commit().await?;
+        Ok(stored)
+    }
+
+    async fn upsert_devices(&self, devices: &[Device], source: &str) -> Result<Vec<Device>, DbError> {
+        let mut tx = self.pool.begin().await?;
+        let rules = load_rules(&mut tx).await?;
+        let mut stored = Vec::new();
+        for device in bulk::dedup_last(devices, |d| d.ip_address) {
+            stored.push(Self::upsert_device_in(&mut tx, &rules, device, source).await?);
+        }
+        tx.commit().await?;
+        Ok(stored)
+    }
+
+    async fn set_device_fields(
+        &self,
+        device_id: Uuid,
+        values: &[(DeviceField, Option<String>)],
+        source: &str,
+    ) -> Result<Device, DbError> {
+        self.update_device(device_id, values, None, source).await
+    }
+
+    async fn set_device_status(&self, device_id: Uuid, status: DeviceStatus, source: &str) -> Result<Device, DbError> {
+        self.update_device(device_id, &[], Some(status), source).await
+    }
+
+    async fn update_device(
+        &self,
+        device_id: Uuid,
+        values: &[(DeviceField, Option<String>)],
+        status: Option<DeviceStatus>,
+        source: &str,
+    ) -> Result<Device, DbError> {
+        let mut tx = self.pool.begin().await?;
+        let now = OffsetDateTime::now_utc();
+        let existing = device_by_id(&mut tx, device_id).await?;
+        let mut sources = load_sources(&mut tx, device_id).await?;
+        let (mut updated, source_updates) = merge::apply_field_values(&existing, &sources, values, source)?;
+        updated.status = merge::apply_status(&existing, status, source);
+        let rules = load_rules(&mut tx).await?;
+        let updated =
+            Device { location_id: locations::resolve_location(&rules, &sources, &updated), updated_at: now, ..updated };
+        save_device(&mut tx, &updated).await.map_err(|e| match e {
+            DbError::QueryFailed(e) => unique_violation(e, || {
+                format!("hostname '{}' is already used by another device", updated.hostname.as_deref().unwrap_or_default())
+            }),
+            e => e,
+        })?;
+        merge::update_sources(&mut sources, &source_updates, source);
+        save_sources(&mut tx, device_id, &sources).await?;
+        record_changes(&mut tx, device_id, &history::device_changes(&existing, &updated), source, now).await?;
+        tx.commit().await?;
+        Ok(updated)
+    }
+
+    async fn set_device_metadata(&self, device_id: Uuid, update: &MetadataUpdate) -> Result<Device, DbError> {
+        let mut tx = self.pool.begin().await?;
+        let device = device_by_id(&mut tx, device_id).await?;
+        let schemas = load_custom_fields(&mut tx).await?;
+        let (tags, custom_fields) =
+            metadata::apply_update(&schemas, CustomFieldObject::Device, &device.tags, &device.custom_fields, update)?;
+        let updated = Device { tags, custom_fields, updated_at: OffsetDateTime::now_utc(), ..device };
+        save_device(&mut tx, &updated).await?;
+        tx.commit().await?;
+        Ok(updated)
+    }
+
+    async fn get_device_field_sources(&self, device_id: Uuid) -> Result<FieldSources, DbError> {
+        let mut conn = self.pool.acquire().await?;
+        load_sources(&mut conn, device_id).await
+    }
+
+    async fn list_device_history(&self, device_id: Uuid) -> Result<Vec<DeviceHistory>, DbError> {
+        let rows = sqlx::query(
+            "SELECT id, device_id, attribute, old_value, new_value, source, changed_at FROM device_history \
+             WHERE device_id = ? ORDER BY julianday(changed_at) DESC, attribute",
+        )
+        .bind(device_id)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(DeviceHistory {
+                    id: row.try_get("id")?,
+                    device_id: row.try_get("device_id")?,
+                    attribute: row.try_get("attribute")?,
+                    old_value: row.try_get("old_value")?,
+                    new_value: row.try_get("new_value")?,
+                    source: row.try_get("source")?,
+                    changed_at: row.try_get("changed_at")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn get_device(&self, id: Uuid) -> Result<Device, DbError> {
+        let mut conn = self.pool.acquire().await?;
+        device_by_id(&mut conn, id).await
+    }
+
+    async fn get_device_by_ip(&self, ip_address: IpNetwork) -> Result<Device, DbError> {
+        let row = sqlx::query(&format!("SELECT {} FROM devices WHERE ip_address = ?", DEVICE_COLUMNS))
+            .bind(ip_address.to_string())
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or(DbError::NotFound)?;
+        device_from_row(&row)
+    }
+
+    async fn list_devices(&self) -> Result<Vec<Device>, DbError> {
+        let mut conn = self.pool.acquire().await?;
+        let mut devices = all_devices(&mut conn).await?;
+        // Same order as Postgres: NULL hostnames last, then by address
+        devices.sort_by(|a, b| {
+            (a.hostname.is_none(), &a.hostname, a.ip_address).cmp(&(b.hostname.is_none(), &b.hostname, b.ip_address))
+        });
+        Ok(devices)
+    }
+
+    async fn query_devices(&self, query: &DeviceQuery) -> Result<DevicePage, DbError> {
+        query.after()?;
+        let mut conn = self.pool.acquire().await?;
+        let all_locations = load_locations(&mut conn).await?;
+        let group = match query.filter.group_id {
+            Some(group_id) => Some(group_by_id(&mut conn, group_id).await?),
+            None => None,
+        };
+        let members: Option<HashSet<Uuid>> = match &group {
+            Some(DeviceGroup { id, rules: None, .. }) => Some(
+                sqlx::query_scalar("SELECT device_id FROM device_group_members WHERE group_id = ?")
+                    .bind(id)
+                    .fetch_all(&mut *conn)
+                    .await?
+                    .into_iter()
+                    .collect(),
+            ),
+            _ => None,
+        };
+        let matches = device_matcher(&query.filter, &all_locations);
+        let group_rules = group.as_ref().and_then(|g| g.rules.as_ref()).map(|rules| device_matcher(rules, &all_locations));
+        let devices = all_devices(&mut conn)
+            .await?
+            .into_iter()
+            .filter(|d| matches(d))
+            .filter(|d| group_rules.as_ref().is_none_or(|in_group| in_group(d)))
+            .filter(|d| members.as_ref().is_none_or(|members| members.contains(&d.id)))
+            .collect();
+        device_query::page_devices(devices, query)
+    }
+
+    async fn upsert_wireless_ap(&self, ap: &WirelessAp) -> Result<(), DbError> {
+        let now = OffsetDateTime::now_utc();
+        sqlx::query(
+            r#"
+            INSERT INTO wireless_aps (
+                device_id, controller_id, mac_address, name, location, oper_status, last_seen, created_at, updated_at
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (device_id) DO UPDATE SET
+                controller_id = excluded.controller_id,
+                mac_address = excluded.mac_address,
+                name = excluded.name,
+                location = excluded.location,
+                oper_status = excluded.oper_status,
+                last_seen = excluded.last_seen,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(ap.device_id)
+        .bind(ap.controller_id)
+        .bind(ap.mac_address.to_string())
+        .bind(&ap.name)
+        .bind(&ap.location)
+        .bind(&ap.oper_status)
+        .bind(now)
+        .bind(now)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn list_wireless_aps(&self, controller_id: Uuid) -> Result<Vec<WirelessAp>, DbError> {
+        let rows = sqlx::query(
+            "SELECT device_id, controller_id, mac_address, name, location, oper_status, last_seen, created_at, updated_at \
+             FROM wireless_aps WHERE controller_id = ? ORDER BY name IS NULL, name, mac_address",
+        )
+        .bind(controller_id)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(WirelessAp {
+                    device_id: row.try_get("device_id")?,
+                    controller_id: row.try_get("controller_id")?,
+                    mac_address: mac(row, "mac_address")?.ok_or_else(|| mapping("access point without mac_address"))?,
+                    name: row.try_get("name")?,
+                    location: row.try_get("location")?,
+                    oper_status: row.try_get("oper_status")?,
+                    last_seen: row.try_get("last_seen")?,
+                    created_at: row.try_get("created_at")?,
+                    updated_at: row.try_get("updated_at")?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl InterfaceRepository for SqliteRepository {
+    async fn upsert_interface(&self, interface: &Interface) -> Result<Interface, DbError> {
+        let mut conn = self.pool.acquire().await?;
+        Self::upsert_interface_in(&mut conn, interface).await
+    }
+
+    async fn upsert_interfaces(&self, interfaces: &[Interface]) -> Result<u64, DbError> {
+        let interfaces = bulk::dedup_last(interfaces, |i| (i.device_id, i.if_index));
+        let mut tx = self.pool.begin().await?;
+        for interface in &interfaces {
+            Self::upsert_interface_in(&mut tx, interface).await?;
+        }
+        tx.commit().await?;
+        Ok(interfaces.len() as u64)
+    }
+
+    async fn get_interface(&self, device_id: Uuid, if_index: i32) -> Result<Interface, DbError> {
+        let row = sqlx::query(&format!("SELECT {} FROM interfaces WHERE device_id = ? AND if_index = ?", INTERFACE_COLUMNS))
+            .bind(device_id)
+            .bind(if_index)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or(DbError::NotFound)?;
+        interface_from_row(&row)
+    }
+
+    async fn list_interfaces(&self, device_id: Uuid) -> Result<Vec<Interface>, DbError> {
+        let rows = sqlx::query(&format!("SELECT {} FROM interfaces WHERE device_id = ? ORDER BY if_index", INTERFACE_COLUMNS))
+            .bind(device_id)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(interface_from_row).collect()
+    }
+
+    async fn find_interfaces_by_mac(&self, mac_address: MacAddress) -> Result<Vec<Interface>, DbError> {
+        let rows = sqlx::query(&format!("SELECT {} FROM interfaces WHERE mac_address = ?", INTERFACE_COLUMNS))
+            .bind(mac_address.to_string())
+            .fetch_all(&self.pool)
+            .await?;
+        let mut interfaces = rows.iter().map(interface_from_row).collect::<Result<Vec<_>, _>>()?;
+        interfaces.sort_by_key(|i| (i.device_id, i.if_index));
+        Ok(interfaces)
+    }
+
+    async fn find_interfaces(&self, filter: &MetadataFilter) -> Result<Vec<Interface>, DbError> {
+        if filter.is_empty() {
+            return Err(DbError::InvalidQuery("filter by at least one tag or custom field".to_string()));
+        }
+        let rows = sqlx::query(&format!("SELECT {} FROM interfaces", INTERFACE_COLUMNS)).fetch_all(&self.pool).await?;
+        let mut interfaces = Vec::new();
+        for row in &rows {
+            let interface = interface_from_row(row)?;
+            if filter.matches(&interface.tags, &interface.custom_fields) {
+                interfaces.push(interface);
+            }
+        }
+        interfaces.sort_by_key(|i| (i.device_id, i.if_index));
+        Ok(interfaces)
+    }
+
+    async fn set_interface_metadata(&self, interface_id: Uuid, update: &MetadataUpdate) -> Result<Interface, DbError> {
+        let mut tx = self.pool.begin().await?;
+        let row = sqlx::query(&format!("SELECT {} FROM interfaces WHERE id = ?", INTERFACE_COLUMNS))
+            .bind(interface_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(DbError::NotFound)?;
+        let interface = interface_from_row(&row)?;
+        let schemas = load_custom_fields(&mut tx).await?;
+        let (tags, custom_fields) = metadata::apply_update(
+            &schemas,
+            CustomFieldObject::Interface,
+            &interface.tags,
+            &interface.custom_fields,
+            update,
+        )?;
+        let updated = Interface { tags, custom_fields, updated_at: OffsetDateTime::now_utc(), ..interface };
+        sqlx::query("UPDATE interfaces SET tags = ?, custom_fields = ?, updated_at = ? WHERE id = ?")
+            .bind(Json(&updated.tags))
+            .bind(Json(&updated.custom_fields))
+            .bind(updated.updated_at)
+            .bind(interface_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(updated)
+    }
+
+    async fn update_interface_settings(
+        &self,
+        interface_id: Uuid,
+        admin_status: Option<&str>,
+        if_alias: Option<&str>,
+        access_vlan: Option<i32>,
+    ) -> Result<(), DbError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE interfaces SET
+                admin_status = COALESCE(?, admin_status),
+                if_alias = COALESCE(?, if_alias),
+                access_vlan = COALESCE(?, access_vlan),
+                updated_at = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(admin_status)
+        .bind(if_alias)
+        .bind(access_vlan)
+        .bind(OffsetDateTime::now_utc())
+        .bind(interface_id)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn upsert_interface_poe(&self, device_id: Uuid, poe: &InterfacePoe) -> Result<(), DbError> {
+        let mut conn = self.pool.acquire().await?;
+        Self::upsert_poe_in(&mut conn, device_id, poe).await?.ok_or(DbError::NotFound)?;
+        Ok(())
+    }
+
+    async fn replace_interface_poe(&self, device_id: Uuid, ports: &[InterfacePoe]) -> Result<(), DbError> {
+        let mut tx = self.pool.begin().await?;
+        let mut kept = HashSet::new();
+        for poe in ports {
+            kept.extend(Self::upsert_poe_in(&mut tx, device_id, poe).await?);
+        }
+        let current: Vec<Uuid> = sqlx::query_scalar(
+            "SELECT p.interface_id FROM interface_poe p JOIN interfaces i ON i.id = p.interface_id WHERE i.device_id = ?",
+        )
+        .bind(device_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        for interface_id in current.into_iter().filter(|id| !kept.contains(id)) {
+            sqlx::query("DELETE FROM interface_poe WHERE interface_id = ?").bind(interface_id).execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_interface_poe(&self, device_id: Uuid, if_index: i32) -> Result<InterfacePoe, DbError> {
+        self.list_interface_poe(device_id).await?.into_iter().find(|p| p.if_index == if_index).ok_or(DbError::NotFound)
+    }
+
+    async fn list_interface_poe(&self, device_id: Uuid) -> Result<Vec<InterfacePoe>, DbError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT p.interface_id, i.if_index, p.group_index, p.port_index, p.admin_enabled, p.detection_status,
+                   p.power_class, p.power_mw, p.last_seen, p.created_at, p.updated_at
+            FROM interface_poe p JOIN interfaces i ON i.id = p.interface_id
+            WHERE i.device_id = ? ORDER BY i.if_index
+            "#,
+        )
+        .bind(device_id)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(InterfacePoe {
+                    interface_id: row.try_get("interface_id")?,
+                    if_index: row.try_get("if_index")?,
+                    group_index: row.try_get("group_index")?,
+                    port_index: row.try_get("port_index")?,
+                    admin_enabled: row.try_get("admin_enabled")?,
+                    detection_status: row.try_get("detection_status")?,
+                    power_class: row.try_get("power_class")?,
+                    power_mw: row.try_get("power_mw")?,
+                    last_seen: row.try_get("last_seen")?,
+                    created_at: row.try_get("created_at")?,
+                    updated_at: row.try_get("updated_at")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn record_port_change(&self, change: &PortChange) -> Result<PortChange, DbError> {
+        let stored = PortChange { id: Uuid::new_v4(), changed_at: OffsetDateTime::now_utc(), ..change.clone() };
+        sqlx::query(
+            r#"
+            INSERT INTO port_changes (
+                id, device_id, interface_id, if_index, action, old_value, new_value, changed_by, succeeded, error, changed_at
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(stored.id)
+        .bind(stored.device_id)
+        .bind(stored.interface_id)
+        .bind(stored.if_index)
+        .bind(&stored.action)
+        .bind(&stored.old_value)
+        .bind(&stored.new_value)
+        .bind(&stored.changed_by)
+        .bind(stored.succeeded)
+        .bind(&stored.error)
+        .bind(stored.changed_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(stored)
+    }
+
+    async fn list_port_changes(&self, device_id: Uuid) -> Result<Vec<PortChange>, DbError> {
+        let rows = sqlx::query(
+            "SELECT id, device_id, interface_id, if_index, action, old_value, new_value, changed_by, succeeded, error, \
+             changed_at FROM port_changes WHERE device_id = ? ORDER BY julianday(changed_at) DESC, rowid DESC",
+        )
+        .bind(device_id)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(PortChange {
+                    id: row.try_get("id")?,
+                    device_id: row.try_get("device_id")?,
+                    interface_id: row.try_get("interface_id")?,
+                    if_index: row.try_get("if_index")?,
+                    action: row.try_get("action")?,
+                    old_value: row.try_get("old_value")?,
+                    new_value: row.try_get("new_value")?,
+                    changed_by: row.try_get("changed_by")?,
+                    succeeded: row.try_get("succeeded")?,
+                    error: row.try_get("error")?,
+                    changed_at: row.try_get("changed_at")?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl NodeRepository for SqliteRepository {
+    async fn upsert_node(&self, node: &Node) -> Result<Uuid, DbError> {
+        let mut tx = self.pool.begin().await?;
+        let id = Self::upsert_node_in(&mut tx, node).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn upsert_nodes(&self, nodes: &[Node]) -> Result<Vec<Uuid>, DbError> {
+        let mut tx = self.pool.begin().await?;
+        let mut ids = Vec::new();
+        for node in bulk::dedup_last(nodes, |n| (n.mac_address, n.device_id, n.vlan)) {
+            ids.push(Self::upsert_node_in(&mut tx, node).await?);
+        }
+        tx.commit().await?;
+        Ok(ids)
+    }
+
+    async fn deactivate_nodes(&self, device_ids: &[Uuid], seen_since: OffsetDateTime) -> Result<u64, DbError> {
+        let mut tx = self.pool.begin().await?;
+        let mut deactivated = 0;
+        for device_id in device_ids {
+            let result = sqlx::query(
+                "UPDATE nodes SET active = 0 WHERE device_id = ? AND active = 1 AND julianday(last_seen) < julianday(?)",
+            )
+            .bind(device_id)
+            .bind(seen_since)
+            .execute(&mut *tx)
+            .await?;
+            deactivated += result.rows_affected();
+        }
+        tx.commit().await?;
+        Ok(deactivated)
+    }
+
+    async fn list_nodes(&self, device_id: Uuid) -> Result<Vec<Node>, DbError> {
+        let rows = sqlx::query(&format!("{} WHERE n.device_id = ? AND n.active = 1", NODE_QUERY))
+            .bind(device_id)
+            .fetch_all(&self.pool)
+            .await?;
+        let mut nodes = rows.iter().map(node_from_row).collect::<Result<Vec<_>, _>>()?;
+        nodes.sort_by_key(|n| (n.mac_address, n.vlan));
+        Ok(nodes)
+    }
+
+    async fn find_nodes_by_mac(&self, mac_address: MacAddress) -> Result<Vec<Node>, DbError> {
+        let rows = sqlx::query(&format!("{} WHERE n.mac_address = ?", NODE_QUERY))
+            .bind(mac_address.to_string())
+            .fetch_all(&self.pool)
+            .await?;
+        let mut nodes = rows.iter().map(node_from_row).collect::<Result<Vec<_>, _>>()?;
+        nodes.sort_by_key(|n| (std::cmp::Reverse(n.last_seen), n.vlan));
+        Ok(nodes)
+    }
+}
+
+#[async_trait]
+impl TopologyRepository for SqliteRepository {
+    async fn replace_routing_neighbors(
+        &self,
+        device_id: Uuid,
+        protocol: RoutingProtocol,
+        neighbors: &[RoutingNeighbor],
+    ) -> Result<(), DbError> {
+        let mut tx = self.pool.begin().await?;
+        let now = OffsetDateTime::now_utc();
+        let current: HashSet<String> = neighbors.iter().map(|n| n.neighbor_address.to_string()).collect();
+        let stored: Vec<(Uuid, String)> =
+            sqlx::query_as("SELECT id, neighbor_address FROM routing_neighbors WHERE device_id = ? AND protocol = ?")
+                .bind(device_id)
+                .bind(protocol.as_str())
+                .fetch_all(&mut *tx)
+                .await?;
+        for (id, _) in stored.iter().filter(|(_, address)| !current.contains(address)) {
+            sqlx::query("DELETE FROM routing_neighbors WHERE id = ?").bind(id).execute(&mut *tx).await?;
+        }
+        for neighbor in neighbors {
+            sqlx::query(
+                r#"
+                INSERT INTO routing_neighbors (
+                    id, device_id, protocol, neighbor_address, remote_router_id, remote_as, state, uptime_seconds,
+                    last_seen, created_at, updated_at
+                )
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT (device_id, protocol, neighbor_address) DO UPDATE SET
+                    remote_router_id = excluded.remote_router_id,
+                    remote_as = excluded.remote_as,
+                    state = excluded.state,
+                    uptime_seconds = excluded.uptime_seconds,
+                    last_seen = excluded.last_seen,
+                    updated_at = excluded.updated_at
+                "#,
+            )
+            .bind(Uuid::new_v4())
+            .bind(device_id)
+            .bind(protocol.as_str())
+            .bind(neighbor.neighbor_address.to_string())
+            .bind(neighbor.remote_router_id.map(|ip| ip.to_string()))
+            .bind(neighbor.remote_as)
+            .bind(&neighbor.state)
+            .bind(neighbor.uptime_seconds)
+            .bind(now)
+            .bind(now)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn list_routing_neighbors(&self, device_id: Uuid) -> Result<Vec<RoutingNeighbor>, DbError> {
+        let rows = sqlx::query(
+            "SELECT id, device_id, protocol, neighbor_address, remote_router_id, remote_as, state, uptime_seconds, \
+             last_seen, created_at, updated_at FROM routing_neighbors WHERE device_id = ?",
+        )
+        .bind(device_id)
+        .fetch_all(&self.pool)
+        .await?;
+        let mut neighbors = rows
+            .iter()
+            .map(|row| {
+                Ok(RoutingNeighbor {
+                    id: row.try_get("id")?,
+                    device_id: row.try_get("device_id")?,
+                    protocol: RoutingProtocol::try_from(row.try_get::<String, _>("protocol")?).map_err(mapping)?,
+                    neighbor_address: ip(row, "neighbor_address")?.ok_or_else(|| mapping("neighbor without address"))?,
+                    remote_router_id: ip(row, "remote_router_id")?,
+                    remote_as: row.try_get("remote_as")?,
+                    state: row.try_get("state")?,
+                    uptime_seconds: row.try_get("uptime_seconds")?,
+                    last_seen: row.try_get("last_seen")?,
+                    created_at: row.try_get("created_at")?,
+                    updated_at: row.try_get("updated_at")?,
+                })
+            })
+            .collect::<Result<Vec<_>, DbError>>()?;
+        neighbors.sort_by(|a, b| (a.protocol.as_str(), a.neighbor_address).cmp(&(b.protocol.as_str(), b.neighbor_address)));
+        Ok(neighbors)
+    }
+
+    async fn replace_stp(&self, device_id: Uuid, instances: &[StpInstance], ports: &[StpPort]) -> Result<(), DbError> {
+        let mut tx = self.pool.begin().await?;
+        let now = OffsetDateTime::now_utc();
+
+        let stored: Vec<(Uuid, i32)> = sqlx::query_as("SELECT id, instance FROM stp_instances WHERE device_id = ?")
+            .bind(device_id)
+            .fetch_all(&mut *tx)
+            .await?;
+        for (id, _) in stored.iter().filter(|(_, instance)| !instances.iter().any(|i| i.instance == *instance)) {
+            sqlx::query("DELETE FROM stp_instances WHERE id = ?").bind(id).execute(&mut *tx).await?;
+        }
+        for instance in instances {
+            sqlx::query(
+                r#"
+                INSERT INTO stp_instances (
+                    id, device_id, instance, protocol, vlans, bridge_id, designated_root, root_cost, root_port_if_index,
+                    last_seen, created_at, updated_at
+                )
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT (device_id, instance) DO UPDATE SET
+                    protocol = excluded.protocol,
+                    vlans = excluded.vlans,
+                    bridge_id = excluded.bridge_id,
+                    designated_root = excluded.designated_root,
+                    root_cost = excluded.root_cost,
+                    root_port_if_index = excluded.root_port_if_index,
+                    last_seen = excluded.last_seen,
+                    updated_at = excluded.updated_at
+                "#,
+            )
+            .bind(Uuid::new_v4())
+            .bind(device_id)
+            .bind(instance.instance)
+            .bind(&instance.protocol)
+            .bind(Json(&instance.vlans))
+            .bind(&instance.bridge_id)
+            .bind(&instance.designated_root)
+            .bind(instance.root_cost)
+            .bind(instance.root_port_if_index)
+            .bind(now)
+            .bind(now)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let mut current = HashSet::new();
+        for port in ports {
+            let Some(interface_id) = interface_id(&mut tx, device_id, port.if_index).await? else {
+                continue;
+            };
+            current.insert((interface_id, port.instance));
+            sqlx::query(
+                r#"
+                INSERT INTO stp_ports (
+                    id, interface_id, instance, state, role, path_cost, designated_bridge, last_seen, created_at, updated_at
+                )
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT (interface_id, instance) DO UPDATE SET
+                    state = excluded.state,
+                    role = excluded.role,
+                    path_cost = excluded.path_cost,
+                    designated_bridge = excluded.designated_bridge,
+                    last_seen = excluded.last_seen,
+                    updated_at = excluded.updated_at
+                "#,
+            )
+            .bind(Uuid::new_v4())
+            .bind(interface_id)
+            .bind(port.instance)
+            .bind(&port.state)
+            .bind(&port.role)
+            .bind(port.path_cost)
+            .bind(&port.designated_bridge)
+            .bind(now)
+            .bind(now)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+        let stored: Vec<(Uuid, Uuid, i32)> = sqlx::query_as(
+            "SELECT sp.id, sp.interface_id, sp.instance FROM stp_ports sp \
+             JOIN interfaces i ON i.id = sp.interface_id WHERE i.device_id = ?",
+        )
+        .bind(device_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        for (id, _, _) in stored.iter().filter(|(_, interface_id, instance)| !current.contains(&(*interface_id, *instance))) {
+            sqlx::query("DELETE FROM stp_ports WHERE id = ?").bind(id).execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn list_stp_instances(&self, device_id: Uuid) -> Result<Vec<StpInstance>, DbError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT si.id, si.device_id, si.instance, si.protocol, si.vlans, si.bridge_id, si.designated_root,
+                   si.root_cost, si.root_port_if_index,
+                   (SELECT r.device_id FROM stp_instances r
+                    WHERE r.instance = si.instance AND r.bridge_id = si.designated_root LIMIT 1) as root_device_id,
+                   si.last_seen, si.created_at, si.updated_at
+            FROM stp_instances si WHERE si.device_id = ? ORDER BY si.instance
+            "#,
+        )
+        .bind(device_id)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(StpInstance {
+                    id: row.try_get("id")?,
+                    device_id: row.try_get("device_id")?,
+                    instance: row.try_get("instance")?,
+                    protocol: row.try_get("protocol")?,
+                    vlans: row.try_get::<Json<Vec<i32>>, _>("vlans")?.0,
+                    bridge_id: row.try_get("bridge_id")?,
+                    designated_root: row.try_get("designated_root")?,
+                    root_cost: row.try_get("root_cost")?,
+                    root_port_if_index: row.try_get("root_port_if_index")?,
+                    root_device_id: row.try_get("root_device_id")?,
+                    last_seen: row.try_get("last_seen")?,
+                    created_at: row.try_get("created_at")?,
+                    updated_at: row.try_get("updated_at")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn list_stp_ports(&self, device_id: Uuid) -> Result<Vec<StpPort>, DbError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT sp.id, sp.interface_id, i.if_index, sp.instance, sp.state, sp.role, sp.path_cost, sp.designated_bridge,
+                   sp.last_seen, sp.created_at, sp.updated_at
+            FROM stp_ports sp JOIN interfaces i ON i.id = sp.interface_id
+            WHERE i.device_id = ? ORDER BY sp.instance, i.if_index
+            "#,
+        )
+        .bind(device_id)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(StpPort {
+                    id: row.try_get("id")?,
+                    interface_id: row.try_get("interface_id")?,
+                    if_index: row.try_get("if_index")?,
+                    instance: row.try_get("instance")?,
+                    state: row.try_get("state")?,
+                    role: row.try_get("role")?,
+                    path_cost: row.try_get("path_cost")?,
+                    designated_bridge: row.try_get("designated_bridge")?,
+                    last_seen: row.try_get("last_seen")?,
+                    created_at: row.try_get("created_at")?,
+                    updated_at: row.try_get("updated_at")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn list_stp_roots(&self) -> Result<Vec<StpRoot>, DbError> {
+        // One row per device and instance, grouped by root below
+        let rows = sqlx::query(
+            r#"
+            SELECT si.protocol, si.instance, si.designated_root, si.device_id, si.vlans,
+                   (SELECT r.device_id FROM stp_instances r
+                    WHERE r.instance = si.instance AND r.bridge_id = si.designated_root LIMIT 1) as root_device_id,
+                   (SELECT COUNT(*) FROM stp_ports sp JOIN interfaces i ON i.id = sp.interface_id
+                    WHERE i.device_id = si.device_id AND sp.instance = si.instance AND sp.state = 'blocking') as blocking_ports
+            FROM stp_instances si ORDER BY si.instance, si.protocol, si.designated_root, si.device_id
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let mut roots: Vec<StpRoot> = Vec::new();
+        for row in &rows {
+            let (protocol, instance, designated_root): (String, i32, String) =
+                (row.try_get("protocol")?, row.try_get("instance")?, row.try_get("designated_root")?);
+            let same_tree = roots
+                .last()
+                .is_some_and(|r| (r.protocol.as_str(), r.instance, r.designated_root.as_str()) == (&protocol, instance, &designated_root));
+            if !same_tree {
+                roots.push(StpRoot {
+                    protocol,
+                    instance,
+                    vlans: Vec::new(),
+                    designated_root,
+                    root_device_id: row.try_get("root_device_id")?,
+                    device_ids: Vec::new(),
+                    blocking_ports: 0,
+                });
+            }
+            let root = roots.last_mut().expect("pushed above");
+            root.vlans.extend(row.try_get::<Json<Vec<i32>>, _>("vlans")?.0);
+            root.device_ids.push(row.try_get("device_id")?);
+            root.blocking_ports += row.try_get::<i64, _>("blocking_ports")?;
+        }
+        for root in &mut roots {
+            root.vlans.sort_unstable();
+            root.vlans.dedup();
+        }
+        Ok(roots)
+    }
+}
+
+async fn check_location(conn: &mut SqliteConnection, location: &Location) -> Result<(), DbError> {
+    let parent = match location.parent_id {
+        Some(parent_id) => Some(location_by_id(conn, parent_id).await.map_err(unknown_location)?),
+        None => None,
+    };
+    locations::check_parent(location.kind, parent.as_ref())
+}
+
+fn duplicate_location(error: sqlx::Error) -> DbError {
+    unique_violation(error, || "a location with this name already exists here".to_string())
+}
+
+fn duplicate_group(error: sqlx::Error) -> DbError {
+    unique_violation(error, || "a group with this name already exists".to_string())
+}
+
+async fn group_by_id(conn: &mut SqliteConnection, id: Uuid) -> Result<DeviceGroup, DbError> {
+    let row = sqlx::query("SELECT id, name, description, rules, created_at, updated_at FROM device_groups WHERE id = ?")
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(DbError::NotFound)?;
+    group_from_row(&row)
+}
+
+#[async_trait]
+impl LocationRepository for SqliteRepository {
+    async fn create_location(&self, location: &Location) -> Result<Location, DbError> {
+        let mut conn = self.pool.acquire().await?;
+        check_location(&mut conn, location).await?;
+        let now = OffsetDateTime::now_utc();
+        let stored = Location { id: Uuid::new_v4(), created_at: now, updated_at: now, ..location.clone() };
+        sqlx::query(
+            "INSERT INTO locations (id, parent_id, kind, name, description, created_at, updated_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(stored.id)
+        .bind(stored.parent_id)
+        .bind(stored.kind.as_str())
+        .bind(&stored.name)
+        .bind(&stored.description)
+        .bind(stored.created_at)
+        .bind(stored.updated_at)
+        .execute(&mut *conn)
+        .await
+        .map_err(duplicate_location)?;
+        Ok(stored)
+    }
+
+    async fn update_location(&self, location: &Location) -> Result<Location, DbError> {
+        let mut conn = self.pool.acquire().await?;
+        let current = location_by_id(&mut conn, location.id).await?;
+        if current.kind != location.kind {
+            return Err(DbError::InvalidQuery("the kind of a location cannot change".to_string()));
+        }
+        check_location(&mut conn, location).await?;
+        let stored = Location { created_at: current.created_at, updated_at: OffsetDateTime::now_utc(), ..location.clone() };
+        sqlx::query("UPDATE locations SET parent_id = ?, name = ?, description = ?, updated_at = ? WHERE id = ?")
+            .bind(stored.parent_id)
+            .bind(&stored.name)
+            .bind(&stored.description)
+            .bind(stored.updated_at)
+            .bind(stored.id)
+            .execute(&mut *conn)
+            .await
+            .map_err(duplicate_location)?;
+        Ok(stored)
+    }
+
+    async fn get_location(&self, id: Uuid) -> Result<Location, DbError> {
+        let mut conn = self.pool.acquire().await?;
+        location_by_id(&mut conn, id).await
+    }
+
+    async fn list_locations(&self) -> Result<Vec<Location>, DbError> {
+        let mut conn = self.pool.acquire().await?;
+        let mut locations = load_locations(&mut conn).await?;
+        locations.sort_by(|a, b| (a.kind, &a.name).cmp(&(b.kind, &b.name)));
+        Ok(locations)
+    }
+
+    async fn delete_location(&self, id: Uuid) -> Result<(), DbError> {
+        // Locations below and their rules go with it; devices there lose their placement
+        let result = sqlx::query("DELETE FROM locations WHERE id = ?").bind(id).execute(&self.pool).await?;
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn create_location_rule(&self, rule: &LocationRule) -> Result<LocationRule, DbError> {
+        locations::check_rule(rule)?;
+        let mut conn = self.pool.acquire().await?;
+        location_by_id(&mut conn, rule.location_id).await.map_err(unknown_location)?;
+        let stored = LocationRule { id: Uuid::new_v4(), created_at: OffsetDateTime::now_utc(), ..rule.clone() };
+        sqlx::query(
+            "INSERT INTO location_rules (id, location_id, priority, sys_location_pattern, subnet, created_at) \
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(stored.id)
+        .bind(stored.location_id)
+        .bind(stored.priority)
+        .bind(&stored.sys_location_pattern)
+        .bind(stored.subnet.map(|subnet| subnet.to_string()))
+        .bind(stored.created_at)
+        .execute(&mut *conn)
+        .await?;
+        Ok(stored)
+    }
+
+    async fn list_location_rules(&self) -> Result<Vec<LocationRule>, DbError> {
+        let mut conn = self.pool.acquire().await?;
+        load_rules(&mut conn).await
+    }
+
+    async fn delete_location_rule(&self, id: Uuid) -> Result<(), DbError> {
+        let result = sqlx::query("DELETE FROM location_rules WHERE id = ?").bind(id).execute(&self.pool).await?;
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn apply_location_rules(&self) -> Result<u64, DbError> {
+        let mut tx = self.pool.begin().await?;
+        let now = OffsetDateTime::now_utc();
+        let rules = load_rules(&mut tx).await?;
+        let mut moved = 0;
+        for existing in all_devices(&mut tx).await? {
+            let sources = load_sources(&mut tx, existing.id).await?;
+            let location_id = locations::resolve_location(&rules, &sources, &existing);
+            if location_id == existing.location_id {
+                continue;
+            }
+            let updated = Device { location_id, updated_at: now, ..existing.clone() };
+            save_device(&mut tx, &updated).await?;
+            record_changes(&mut tx, existing.id, &history::device_changes(&existing, &updated), RULES_SOURCE, now).await?;
+            moved += 1;
+        }
+        tx.commit().await?;
+        Ok(moved)
+    }
+
+    async fn set_device_location(
+        &self,
+        device_id: Uuid,
+        location_id: Option<Uuid>,
+        source: &str,
+    ) -> Result<Device, DbError> {
+        let mut tx = self.pool.begin().await?;
+        if let Some(location_id) = location_id {
+            location_by_id(&mut tx, location_id).await.map_err(unknown_location)?;
+        }
+        let now = OffsetDateTime::now_utc();
+        let existing = device_by_id(&mut tx, device_id).await?;
+        let mut sources = load_sources(&mut tx, device_id).await?;
+        if !is_manual_source(source) && sources.get(LOCATION_FIELD).is_some_and(|s| is_manual_source(s)) {
+            return Ok(existing);
+        }
+        let location_id = match location_id {
+            Some(location_id) => {
+                sources.insert(LOCATION_FIELD.to_string(), source.to_string());
+                Some(location_id)
+            }
+            None => {
+                sources.remove(LOCATION_FIELD);
+                let rules = load_rules(&mut tx).await?;
+                locations::resolve_location(&rules, &sources, &existing)
+            }
+        };
+        let updated = Device { location_id, updated_at: now, ..existing.clone() };
+        save_device(&mut tx, &updated).await?;
+        save_sources(&mut tx, device_id, &sources).await?;
+        record_changes(&mut tx, device_id, &history::device_changes(&existing, &updated), source, now).await?;
+        tx.commit().await?;
+        Ok(updated)
+    }
+}
+
+#[async_trait]
+impl GroupRepository for SqliteRepository {
+    async fn create_group(&self, group: &DeviceGroup) -> Result<DeviceGroup, DbError> {
+        groups::check_rules(group.rules.as_ref())?;
+        let now = OffsetDateTime::now_utc();
+        let stored = DeviceGroup { id: Uuid::new_v4(), created_at: now, updated_at: now, ..group.clone() };
+        sqlx::query(
+            "INSERT INTO device_groups (id, name, description, rules, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(stored.id)
+        .bind(&stored.name)
+        .bind(&stored.description)
+        .bind(stored.rules.as_ref().map(Json))
+        .bind(stored.created_at)
+        .bind(stored.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(duplicate_group)?;
+        Ok(stored)
+    }
+
+    async fn update_group(&self, group: &DeviceGroup) -> Result<DeviceGroup, DbError> {
+        groups::check_rules(group.rules.as_ref())?;
+        let mut tx = self.pool.begin().await?;
+        let current = group_by_id(&mut tx, group.id).await?;
+        let stored = DeviceGroup { created_at: current.created_at, updated_at: OffsetDateTime::now_utc(), ..group.clone() };
+        sqlx::query("UPDATE device_groups SET name = ?, description = ?, rules = ?, updated_at = ? WHERE id = ?")
+            .bind(&stored.name)
+            .bind(&stored.description)
+            .bind(stored.rules.as_ref().map(Json))
+            .bind(stored.updated_at)
+            .bind(stored.id)
+            .execute(&mut *tx)
+            .await
+            .map_err(duplicate_group)?;
+        if group.rules.is_some() {
+            sqlx::query("DELETE FROM device_group_members WHERE group_id = ?").bind(group.id).execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(stored)
+    }
+
+    async fn get_group(&self, id: Uuid) -> Result<DeviceGroup, DbError> {
+        let mut conn = self.pool.acquire().await?;
+        group_by_id(&mut conn, id).await
+    }
+
+    async fn list_groups(&self) -> Result<Vec<DeviceGroup>, DbError> {
+        let rows = sqlx::query("SELECT id, name, description, rules, created_at, updated_at FROM device_groups ORDER BY name")
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(group_from_row).collect()
+    }
+
+    async fn delete_group(&self, id: Uuid) -> Result<(), DbError> {
+        let result = sqlx::query("DELETE FROM device_groups WHERE id = ?").bind(id).execute(&self.pool).await?;
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn add_group_member(&self, group_id: Uuid, device_id: Uuid) -> Result<(), DbError> {
+        let mut conn = self.pool.acquire().await?;
+        if group_by_id(&mut conn, group_id).await?.rules.is_some() {
+            return Err(DbError::InvalidQuery("members of a dynamic group are set by its rules".to_string()));
+        }
+        device_by_id(&mut conn, device_id).await?;
+        sqlx::query("INSERT INTO device_group_members (group_id, device_id) VALUES (?, ?) ON CONFLICT DO NOTHING")
+            .bind(group_id)
+            .bind(device_id)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_group_member(&self, group_id: Uuid, device_id: Uuid) -> Result<(), DbError> {
+        let result = sqlx::query("DELETE FROM device_group_members WHERE group_id = ? AND device_id = ?")
+            .bind(group_id)
+            .bind(device_id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CustomFieldRepository for SqliteRepository {
+    async fn create_custom_field(&self, field: &CustomField) -> Result<CustomField, DbError> {
+        metadata::check_custom_field(field)?;
+        let now = OffsetDateTime::now_utc();
+        let stored = CustomField { id: Uuid::new_v4(), created_at: now, updated_at: now, ..field.clone() };
+        sqlx::query(
+            "INSERT INTO custom_fields (id, object_type, name, field_type, choices, description, created_at, updated_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(stored.id)
+        .bind(stored.object_type.as_str())
+        .bind(&stored.name)
+        .bind(stored.field_type.as_str())
+        .bind(Json(&stored.choices))
+        .bind(&stored.description)
+        .bind(stored.created_at)
+        .bind(stored.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            unique_violation(e, || format!("{} custom field '{}' already exists", field.object_type.as_str(), field.name))
+        })?;
+        Ok(stored)
+    }
+
+    async fn list_custom_fields(&self) -> Result<Vec<CustomField>, DbError> {
+        let mut conn = self.pool.acquire().await?;
+        load_custom_fields(&mut conn).await
+    }
+
+    async fn delete_custom_field(&self, id: Uuid) -> Result<(), DbError> {
+        let mut tx = self.pool.begin().await?;
+        let row = sqlx::query(
+            "SELECT id, object_type, name, field_type, choices, description, created_at, updated_at \
+             FROM custom_fields WHERE id = ?",
+        )
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(DbError::NotFound)?;
+        let field = custom_field_from_row(&row)?;
+        sqlx::query("DELETE FROM custom_fields WHERE id = ?").bind(id).execute(&mut *tx).await?;
+        // Names are [a-z0-9_], so they are safe in a JSON path
+        let table = match field.object_type {
+            CustomFieldObject::Device => "devices",
+            CustomFieldObject::Interface => "interfaces",
+        };
+        sqlx::query(&format!("UPDATE {} SET custom_fields = json_remove(custom_fields, ?)", table))
+            .bind(format!("$.{}", field.name))
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SearchRepository for SqliteRepository {
+    async fn search(&self, query: &SearchQuery) -> Result<Vec<SearchHit>, DbError> {
+        let mut conn = self.pool.acquire().await?;
+        let devices = all_devices(&mut conn).await?;
+        let rows = sqlx::query(&format!("SELECT {} FROM interfaces", INTERFACE_COLUMNS)).fetch_all(&mut *conn).await?;
+        let interfaces = rows.iter().map(interface_from_row).collect::<Result<Vec<_>, _>>()?;
+        let rows = sqlx::query(NODE_QUERY).fetch_all(&mut *conn).await?;
+        let nodes = rows.iter().map(node_from_row).collect::<Result<Vec<_>, _>>()?;
+        let rows = sqlx::query("SELECT id, device_id, interface_id, ip_address, mac_address, first_seen, last_seen FROM arp_entries")
+            .fetch_all(&mut *conn)
+            .await?;
+        let arp_entries = rows.iter().map(arp_entry_from_row).collect::<Result<Vec<_>, _>>()?;
+        Ok(search::rank(&query.term, &devices, &interfaces, &nodes, &arp_entries, query.limit()))
+    }
+}
+
+const ANOMALY_COLUMNS: &str = "id, kind, ip_address, mac_address, mac_addresses, device_ids, interface_ids, moves, \
+     first_detected, last_detected, resolved_at";
+
+fn anomaly_from_row(row: &SqliteRow) -> Result<Anomaly, DbError> {
+    Ok(Anomaly {
+        id: row.try_get("id")?,
+        kind: AnomalyKind::try_from(row.try_get::<String, _>("kind")?).map_err(mapping)?,
+        ip_address: ip(row, "ip_address")?,
+        mac_address: mac(row, "mac_address")?,
+        mac_addresses: row.try_get::<Json<Vec<MacAddress>>, _>("mac_addresses")?.0,
+        device_ids: row.try_get::<Json<Vec<Uuid>>, _>("device_ids")?.0,
+        interface_ids: row.try_get::<Json<Vec<Uuid>>, _>("interface_ids")?.0,
+        moves: row.try_get("moves")?,
+        first_detected: row.try_get("first_detected")?,
+        last_detected: row.try_get("last_detected")?,
+        resolved_at: row.try_get("resolved_at")?,
+    })
+}
+
+#[async_trait]
+impl AnomalyRepository for SqliteRepository {
+    async fn detect_anomalies(&self, settings: &AnomalySettings) -> Result<AnomalyReport, DbError> {
+        let now = OffsetDateTime::now_utc();
+        let start = anomalies::window_start(settings, now);
+        let mut tx = self.pool.begin().await?;
+
+        let rows = sqlx::query(&format!(
+            "{} WHERE n.active = 1 AND n.interface_id IS NOT NULL AND julianday(n.last_seen) >= julianday(?)",
+            NODE_QUERY
+        ))
+        .bind(start)
+        .fetch_all(&mut *tx)
+        .await?;
+        let nodes = rows.iter().map(node_from_row).collect::<Result<Vec<_>, _>>()?;
+        let rows = sqlx::query(
+            "SELECT n.mac_address, n.device_id, w.ip_address, w.ip_since, w.last_seen \
+             FROM node_wireless w JOIN nodes n ON n.id = w.node_id \
+             WHERE w.ip_address IS NOT NULL AND w.ip_since IS NOT NULL AND julianday(w.last_seen) >= julianday(?) \
+             UNION ALL \
+             SELECT mac_address, device_id, ip_address, first_seen, last_seen \
+             FROM arp_entries WHERE julianday(last_seen) >= julianday(?)",
+        )
+        .bind(start)
+        .bind(start)
+        .fetch_all(&mut *tx)
+        .await?;
+        let claims = rows
+            .iter()
+            .map(|row| {
+                Ok(IpClaim {
+                    mac_address: mac(row, "mac_address")?.ok_or_else(|| mapping("node without mac_address"))?,
+                    device_id: row.try_get("device_id")?,
+                    ip_address: ip(row, "ip_address")?.ok_or_else(|| mapping("claim without ip_address"))?,
+                    since: row.try_get("ip_since")?,
+                    last_seen: row.try_get("last_seen")?,
+                })
+            })
+            .collect::<Result<Vec<_>, DbError>>()?;
+        let rows = sqlx::query(
+            "SELECT n.mac_address, n.device_id, m.from_interface_id, m.to_interface_id, m.moved_at \
+             FROM node_moves m JOIN nodes n ON n.id = m.node_id WHERE julianday(m.moved_at) >= julianday(?)",
+        )
+        .bind(start)
+        .fetch_all(&mut *tx)
+        .await?;
+        let moves = rows
+            .iter()
+            .map(|row| {
+                Ok(NodeMove {
+                    mac_address: mac(row, "mac_address")?.ok_or_else(|| mapping("node without mac_address"))?,
+                    device_id: row.try_get("device_id")?,
+                    from_interface_id: row.try_get("from_interface_id")?,
+                    to_interface_id: row.try_get("to_interface_id")?,
+                    moved_at: row.try_get("moved_at")?,
+                })
+            })
+            .collect::<Result<Vec<_>, DbError>>()?;
+        let findings = anomalies::detect(settings, now, &nodes, &claims, &moves);
+
+        let rows = sqlx::query(&format!("SELECT {} FROM anomalies WHERE resolved_at IS NULL", ANOMALY_COLUMNS))
+            .fetch_all(&mut *tx)
+            .await?;
+        let open = rows.iter().map(anomaly_from_row).collect::<Result<Vec<_>, _>>()?;
+        let reconciled = anomalies::reconcile(open, findings, now);
+        for anomaly in &reconciled.opened {
+            sqlx::query(&format!(
+                "INSERT INTO anomalies ({}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                ANOMALY_COLUMNS
+            ))
+            .bind(anomaly.id)
+            .bind(anomaly.kind.as_str())
+            .bind(anomaly.ip_address.map(|ip| ip.to_string()))
+            .bind(anomaly.mac_address.map(|mac| mac.to_string()))
+            .bind(Json(&anomaly.mac_addresses))
+            .bind(Json(&anomaly.device_ids))
+            .bind(Json(&anomaly.interface_ids))
+            .bind(anomaly.moves)
+            .bind(anomaly.first_detected)
+            .bind(anomaly.last_detected)
+            .bind(anomaly.resolved_at)
+            .execute(&mut *tx)
+            .await?;
+        }
+        for anomaly in reconciled.ongoing.iter().chain(&reconciled.resolved) {
+            sqlx::query(
+                "UPDATE anomalies SET mac_addresses = ?, device_ids = ?, interface_ids = ?, moves = ?, \
+                 last_detected = ?, resolved_at = ? WHERE id = ?",
+            )
+            .bind(Json(&anomaly.mac_addresses))
+            .bind(Json(&anomaly.device_ids))
+            .bind(Json(&anomaly.interface_ids))
+            .bind(anomaly.moves)
+            .bind(anomaly.last_detected)
+            .bind(anomaly.resolved_at)
+            .bind(anomaly.id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(reconciled.report())
+    }
+
+    async fn list_anomalies(&self, filter: &AnomalyFilter) -> Result<Vec<Anomaly>, DbError> {
+        // Addresses are text here, so MAC and address matching happens on the loaded rows
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM anomalies \
+             WHERE (? IS NULL OR kind = ?) AND (? = 0 OR resolved_at IS NULL) \
+               AND (? IS NULL OR julianday(last_detected) >= julianday(?)) \
+             ORDER BY julianday(last_detected) DESC, id",
+            ANOMALY_COLUMNS
+        ))
+        .bind(filter.kind.map(|kind| kind.as_str()))
+        .bind(filter.kind.map(|kind| kind.as_str()))
+        .bind(filter.open_only)
+        .bind(filter.since)
+        .bind(filter.since)
+        .fetch_all(&self.pool)
+        .await?;
+        let mut anomalies = Vec::new();
+        for row in &rows {
+            let anomaly = anomaly_from_row(row)?;
+            if filter.matches(&anomaly) {
+                anomalies.push(anomaly);
+            }
+        }
+        anomalies.truncate(filter.limit() as usize);
+        Ok(anomalies)
+    }
+}
+
+const ALLOWED_MAC_COLUMNS: &str = "id, mac_prefix, location_id, vlan, description, created_at";
+
+const MAC_VIOLATION_COLUMNS: &str =
+    "id, mac_address, device_id, interface_id, vlan, first_seen, last_seen, port_shutdown_at";
+
+fn allowed_mac_from_row(row: &SqliteRow) -> Result<AllowedMac, DbError> {
+    Ok(AllowedMac {
+        id: row.try_get("id")?,
+        mac_prefix: row.try_get("mac_prefix")?,
+        location_id: row.try_get("location_id")?,
+        vlan: row.try_get("vlan")?,
+        description: row.try_get("description")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+fn mac_violation_from_row(row: &SqliteRow) -> Result<MacViolation, DbError> {
+    Ok(MacViolation {
+        id: row.try_get("id")?,
+        mac_address: mac(row, "mac_address")?.ok_or_else(|| mapping("violation without mac_address"))?,
+        device_id: row.try_get("device_id")?,
+        interface_id: row.try_get("interface_id")?,
+        vlan: row.try_get("vlan")?,
+        first_seen: row.try_get("first_seen")?,
+        last_seen: row.try_get("last_seen")?,
+        port_shutdown_at: row.try_get("port_shutdown_at")?,
+    })
+}
+
+async fn load_allowed_macs(conn: &mut SqliteConnection) -> Result<Vec<AllowedMac>, DbError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM mac_allowlist ORDER BY mac_prefix, julianday(created_at)",
+        ALLOWED_MAC_COLUMNS
+    ))
+    .fetch_all(&mut *conn)
+    .await?;
+    rows.iter().map(allowed_mac_from_row).collect()
+}
+
+#[async_trait]
+impl AllowlistRepository for SqliteRepository {
+    async fn create_allowed_mac(&self, entry: &AllowedMac) -> Result<AllowedMac, DbError> {
+        let entry = allowlist::check_entry(entry)?;
+        let mut conn = self.pool.acquire().await?;
+        if let Some(location_id) = entry.location_id {
+            location_by_id(&mut conn, location_id).await.map_err(unknown_location)?;
+        }
+        let stored = AllowedMac { id: Uuid::new_v4(), created_at: OffsetDateTime::now_utc(), ..entry };
+        sqlx::query(&format!("INSERT INTO mac_allowlist ({}) VALUES (?, ?, ?, ?, ?, ?)", ALLOWED_MAC_COLUMNS))
+            .bind(stored.id)
+            .bind(&stored.mac_prefix)
+            .bind(stored.location_id)
+            .bind(stored.vlan)
+            .bind(&stored.description)
+            .bind(stored.created_at)
+            .execute(&mut *conn)
+            .await?;
+        Ok(stored)
+    }
+
+    async fn list_allowed_macs(&self) -> Result<Vec<AllowedMac>, DbError> {
+        let mut conn = self.pool.acquire().await?;
+        load_allowed_macs(&mut conn).await
+    }
+
+    async fn delete_allowed_mac(&self, id: Uuid) -> Result<(), DbError> {
+        let result = sqlx::query("DELETE FROM mac_allowlist WHERE id = ?").bind(id).execute(&self.pool).await?;
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn check_mac_allowlist(&self, nodes: &[Node], access_port_max_macs: u32) -> Result<Vec<MacViolation>, DbError> {
+        let mut tx = self.pool.begin().await?;
+        let entries = load_allowed_macs(&mut tx).await?;
+        if entries.is_empty() || nodes.is_empty() {
+            return Ok(Vec::new());
+        }
+        let locations = load_locations(&mut tx).await?;
+        let device_locations: HashMap<Uuid, Option<Uuid>> =
+            all_devices(&mut tx).await?.into_iter().map(|d| (d.id, d.location_id)).collect();
+
+        let mut violations = Vec::new();
+        for node in allowlist::unapproved(&entries, &locations, &device_locations, nodes, access_port_max_macs) {
+            let row = sqlx::query(&format!(
+                "INSERT INTO mac_violations (id, mac_address, device_id, interface_id, vlan, first_seen, last_seen) \
+                 VALUES (?, ?, ?, ?, ?, ?, ?) \
+                 ON CONFLICT (mac_address, device_id, vlan) DO UPDATE SET \
+                     interface_id = excluded.interface_id, \
+                     last_seen = excluded.last_seen, \
+                     port_shutdown_at = CASE WHEN mac_violations.interface_id IS excluded.interface_id \
+                                             THEN mac_violations.port_shutdown_at END \
+                 RETURNING {}",
+                MAC_VIOLATION_COLUMNS
+            ))
+            .bind(Uuid::new_v4())
+            .bind(node.mac_address.to_string())
+            .bind(node.device_id)
+            .bind(node.interface_id)
+            .bind(node.vlan)
+            .bind(node.last_seen)
+            .bind(node.last_seen)
+            .fetch_one(&mut *tx)
+            .await?;
+            violations.push(mac_violation_from_row(&row)?);
+        }
+        tx.commit().await?;
+        Ok(violations)
+    }
+
+    async fn list_mac_violations(&self, filter: &MacViolationFilter) -> Result<Vec<MacViolation>, DbError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM mac_violations \
+             WHERE (? IS NULL OR device_id = ?) AND (? IS NULL OR mac_address = ?) \
+               AND (? IS NULL OR julianday(last_seen) >= julianday(?)) \
+             ORDER BY julianday(last_seen) DESC, id",
+            MAC_VIOLATION_COLUMNS
+        ))
+        .bind(filter.device_id)
+        .bind(filter.device_id)
+        .bind(filter.mac_address.map(|mac| mac.to_string()))
+        .bind(filter.mac_address.map(|mac| mac.to_string()))
+        .bind(filter.since)
+        .bind(filter.since)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(mac_violation_from_row).collect()
+    }
+
+    async fn set_violation_port_shutdown(&self, id: Uuid, at: OffsetDateTime) -> Result<(), DbError> {
+        let result = sqlx::query("UPDATE mac_violations SET port_shutdown_at = ? WHERE id = ?")
+            .bind(at)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl RetentionRepository for SqliteRepository {
+    async fn apply_retention(&self, settings: &RetentionSettings) -> Result<RetentionReport, DbError> {
+        let batch_size = i64::from(settings.batch_size.max(1));
+        let dry_run = settings.dry_run;
+        let mut report = RetentionReport { dry_run, ..RetentionReport::default() };
+        let pool = &self.pool;
+        // Table and the condition that makes a row stale; moves go before the sightings they cascade from
+        let rules = [
+            (settings.node_move_days, "node_moves", "julianday(moved_at) < julianday(?)", &mut report.node_moves),
+            (settings.node_days, "nodes", "julianday(last_seen) < julianday(?)", &mut report.nodes),
+            (settings.device_history_days, "device_history", "julianday(changed_at) < julianday(?)", &mut report.device_history),
+            (settings.port_change_days, "port_changes", "julianday(changed_at) < julianday(?)", &mut report.port_changes),
+            (settings.arp_days, "arp_entries", "julianday(last_seen) < julianday(?)", &mut report.arp_entries),
+            (
+                settings.anomaly_days,
+                "anomalies",
+                "resolved_at IS NOT NULL AND julianday(last_detected) < julianday(?)",
+                &mut report.anomalies,
+            ),
+            (settings.mac_violation_days, "mac_violations", "julianday(last_seen) < julianday(?)", &mut report.mac_violations),
+        ];
+        for (days, table, condition, count) in rules {
+            let Some(days) = days else {
+                continue;
+            };
+            let cutoff = retention::cutoff(days);
+            *count = if dry_run {
+                let stale: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {} WHERE {}", table, condition))
+                    .bind(cutoff)
+                    .fetch_one(pool)
+                    .await?;
+                stale as u64
+            } else {
+                retention::in_batches(batch_size, || async move {
+                    let result = sqlx::query(&format!(
+                        "DELETE FROM {table} WHERE id IN (SELECT id FROM {table} WHERE {condition} LIMIT ?)",
+                        table = table,
+                        condition = condition
+                    ))
+                    .bind(cutoff)
+                    .bind(batch_size)
+                    .execute(pool)
+                    .await?;
+                    Ok(result.rows_affected())
+                })
+                .await?
+            };
+        }
+
+        if let Some(days) = settings.archive_device_days {
+            let cutoff = retention::cutoff(days);
+            let mut conn = pool.acquire().await?;
+            let stale: Vec<Device> = all_devices(&mut conn)
+                .await?
+                .into_iter()
+                .filter(|d| {
+                    d.last_seen.is_some_and(|seen| seen < cutoff) && !d.status.as_ref().is_some_and(DeviceStatus::is_held)
+                })
+                .collect();
+            drop(conn);
+            report.archived_devices = stale.len() as u64;
+            if !dry_run {
+                let now = OffsetDateTime::now_utc();
+                for batch in stale.chunks(batch_size as usize) {
+                    let mut tx = pool.begin().await?;
+                    for existing in batch {
+                        let retired = Device { status: Some(DeviceStatus::Retired), ..existing.clone() };
+                        save_device(&mut tx, &retired).await?;
+                        record_changes(&mut tx, existing.id, &history::device_changes(existing, &retired), RETENTION_SOURCE, now)
+                            .await?;
+                    }
+                    tx.commit().await?;
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[async_trait]
+impl VendorRepository for SqliteRepository {
+    async fn replace_mac_vendors(&self, vendors: &[MacVendor]) -> Result<u64, DbError> {
+        let mut lengths: Vec<usize> = vendors.iter().map(|v| v.prefix.len()).collect();
+        lengths.sort_unstable();
+        lengths.dedup();
+        let now = OffsetDateTime::now_utc();
+        let mut tx = self.pool.begin().await?;
+        for len in lengths {
+            sqlx::query("DELETE FROM mac_vendors WHERE length(prefix) = ?").bind(len as i64).execute(&mut *tx).await?;
+        }
+        // Files may list a block twice; the last one wins
+        for vendor in vendors {
+            sqlx::query(
+                "INSERT INTO mac_vendors (prefix, organization, imported_at) VALUES (?, ?, ?) \
+                 ON CONFLICT (prefix) DO UPDATE SET organization = excluded.organization",
+            )
+            .bind(&vendor.prefix)
+            .bind(&vendor.organization)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(vendors.iter().map(|v| &v.prefix).collect::<HashSet<_>>().len() as u64)
+    }
+
+    async fn list_mac_vendors(&self) -> Result<Vec<MacVendor>, DbError> {
+        let rows = sqlx::query("SELECT prefix, organization FROM mac_vendors ORDER BY prefix").fetch_all(&self.pool).await?;
+        rows.iter()
+            .map(|row| Ok(MacVendor { prefix: row.try_get("prefix")?, organization: row.try_get("organization")? }))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl IpamRepository for SqliteRepository {
+    async fn upsert_arp_entries(&self, device_id: Uuid, entries: &[ArpEntry]) -> Result<(), DbError> {
+        let now = OffsetDateTime::now_utc();
+        let mut tx = self.pool.begin().await?;
+        for entry in entries {
+            sqlx::query(
+                "INSERT INTO arp_entries (id, device_id, interface_id, ip_address, mac_address, first_seen, last_seen) \
+                 VALUES (?, ?, ?, ?, ?, ?, ?) \
+                 ON CONFLICT (device_id, ip_address) DO UPDATE SET \
+                 first_seen = CASE WHEN arp_entries.mac_address = excluded.mac_address \
+                                   THEN arp_entries.first_seen ELSE excluded.first_seen END, \
+                 mac_address = excluded.mac_address, interface_id = excluded.interface_id, last_seen = excluded.last_seen",
+            )
+            .bind(Uuid::new_v4())
+            .bind(device_id)
+            .bind(entry.interface_id)
+            .bind(entry.ip_address.to_string())
+            .bind(entry.mac_address.to_string())
+            .bind(now)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn refresh_subnets(&self) -> Result<SubnetRefresh, DbError> {
+        let now = OffsetDateTime::now_utc();
+        let mut tx = self.pool.begin().await?;
+        let rows = sqlx::query("SELECT ip_address FROM interfaces WHERE ip_address IS NOT NULL").fetch_all(&mut *tx).await?;
+        let addresses = rows.iter().map(|row| ip(row, "ip_address")).collect::<Result<Vec<_>, _>>()?;
+        let networks = ipam::interface_subnets(addresses.iter().flatten());
+        let existing = sqlx::query("SELECT network FROM subnets").fetch_all(&mut *tx).await?;
+        let existing = existing.iter().map(|row| ip(row, "network")).collect::<Result<Vec<_>, _>>()?;
+
+        let mut removed = 0;
+        for network in existing.iter().flatten().filter(|n| !networks.contains(n)) {
+            sqlx::query("DELETE FROM subnets WHERE network = ?").bind(network.to_string()).execute(&mut *tx).await?;
+            removed += 1;
+        }
+        for network in &networks {
+            sqlx::query(
+                "INSERT INTO subnets (id, network, first_seen, last_seen) VALUES (?, ?, ?, ?) \
+                 ON CONFLICT (network) DO UPDATE SET last_seen = excluded.last_seen",
+            )
+            .bind(Uuid::new_v4())
+            .bind(network.to_string())
+            .bind(now)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(SubnetRefresh {
+            added: networks.iter().filter(|n| !existing.contains(&Some(**n))).count() as u64,
+            removed,
+            total: networks.len() as u64,
+        })
+    }
+
+    async fn list_subnets(&self) -> Result<Vec<Subnet>, DbError> {
+        let rows = sqlx::query("SELECT id, network, first_seen, last_seen FROM subnets").fetch_all(&self.pool).await?;
+        let mut subnets = rows.iter().map(subnet_from_row).collect::<Result<Vec<_>, _>>()?;
+        subnets.sort_by_key(|s| s.network);
+        Ok(subnets)
+    }
+
+    async fn get_subnet(&self, id: Uuid) -> Result<Subnet, DbError> {
+        let row = sqlx::query("SELECT id, network, first_seen, last_seen FROM subnets WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or(DbError::NotFound)?;
+        subnet_from_row(&row)
+    }
+
+    async fn list_ip_sightings(&self, network: IpNetwork) -> Result<Vec<IpSighting>, DbError> {
+        let mut conn = self.pool.acquire().await?;
+        let sighting = |ip: &IpNetwork, mac_address, last_seen| IpSighting { ip_address: ip.ip(), mac_address, last_seen };
+        let mut sightings = Vec::new();
+        for row in sqlx::query("SELECT ip_address, mac_address, last_seen FROM arp_entries").fetch_all(&mut *conn).await? {
+            if let Some(ip_address) = ip(&row, "ip_address")? {
+                sightings.push(sighting(&ip_address, mac(&row, "mac_address")?, row.try_get("last_seen")?));
+            }
+        }
+        for row in sqlx::query(NODE_QUERY).fetch_all(&mut *conn).await? {
+            let node = node_from_row(&row)?;
+            if let Some(wireless) = &node.wireless
+                && let Some(ip_address) = &wireless.ip_address
+            {
+                sightings.push(sighting(ip_address, Some(node.mac_address), wireless.last_seen));
+            }
+        }
+        let rows = sqlx::query(&format!("SELECT {} FROM interfaces", INTERFACE_COLUMNS)).fetch_all(&mut *conn).await?;
+        for interface in rows.iter().map(interface_from_row) {
+            let interface = interface?;
+            if let Some(ip_address) = &interface.ip_address {
+                sightings.push(sighting(ip_address, interface.mac_address, interface.updated_at));
+            }
+        }
+        for device in all_devices(&mut conn).await? {
+            sightings.push(sighting(&device.ip_address, None, device.last_seen.unwrap_or(device.updated_at)));
+        }
+        sightings.retain(|s| network.contains(s.ip_address));
+        Ok(sightings)
+    }
+}
+
+#[async_trait]
+impl HostnameRepository for SqliteRepository {
+    async fn set_ip_hostname(&self, ip_address: IpNetwork, hostname: Option<&str>) -> Result<(), DbError> {
+        match hostname {
+            Some(hostname) => {
+                sqlx::query(
+                    "INSERT INTO ip_hostnames (ip_address, hostname, resolved_at) VALUES (?, ?, ?) \
+                     ON CONFLICT (ip_address) DO UPDATE SET hostname = excluded.hostname, resolved_at = excluded.resolved_at",
+                )
+                .bind(ip_address.to_string())
+                .bind(hostname)
+                .bind(OffsetDateTime::now_utc())
+                .execute(&self.pool)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM ip_hostnames WHERE ip_address = ?")
+                    .bind(ip_address.to_string())
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn list_ip_hostnames(&self, network: Option<IpNetwork>) -> Result<Vec<IpHostname>, DbError> {
+        let rows = sqlx::query("SELECT ip_address, hostname, resolved_at FROM ip_hostnames").fetch_all(&self.pool).await?;
+        let mut hostnames = Vec::new();
+        for row in &rows {
+            let ip_address = ip(row, "ip_address")?.ok_or_else(|| mapping("hostname without ip_address"))?;
+            if network.is_none_or(|n| n.contains(ip_address.ip())) {
+                hostnames.push(IpHostname {
+                    ip_address,
+                    hostname: row.try_get("hostname")?,
+                    resolved_at: row.try_get("resolved_at")?,
+                });
+            }
+        }
+        hostnames.sort_by_key(|h| h.ip_address);
+        Ok(hostnames)
+    }
+}
+
+const NEIGHBOR_COLUMNS: &str = "id, device_id, interface_id, protocol, remote_chassis_id, remote_port, remote_sys_name, \
+     remote_address, last_seen";
+
+const LINK_COLUMNS: &str =
+    "id, device_id, interface_id, remote_device_id, remote_interface_id, source, confidence, first_seen, last_seen";
+
+fn neighbor_from_row(row: &SqliteRow) -> Result<DeviceNeighbor, DbError> {
+    Ok(DeviceNeighbor {
+        id: row.try_get("id")?,
+        device_id: row.try_get("device_id")?,
+        interface_id: row.try_get("interface_id")?,
+        protocol: NeighborProtocol::try_from(row.try_get::<String, _>("protocol")?).map_err(mapping)?,
+        remote_chassis_id: row.try_get("remote_chassis_id")?,
+        remote_port: row.try_get("remote_port")?,
+        remote_sys_name: row.try_get("remote_sys_name")?,
+        remote_address: ip(row, "remote_address")?,
+        last_seen: row.try_get("last_seen")?,
+    })
+}
+
+fn link_from_row(row: &SqliteRow) -> Result<TopologyLink, DbError> {
+    Ok(TopologyLink {
+        id: row.try_get("id")?,
+        device_id: row.try_get("device_id")?,
+        interface_id: row.try_get("interface_id")?,
+        remote_device_id: row.try_get("remote_device_id")?,
+        remote_interface_id: row.try_get("remote_interface_id")?,
+        source: LinkSource::try_from(row.try_get::<String, _>("source")?).map_err(mapping)?,
+        confidence: row.try_get("confidence")?,
+        first_seen: row.try_get("first_seen")?,
+        last_seen: row.try_get("last_seen")?,
+    })
+}
+
+async fn load_links(conn: &mut SqliteConnection) -> Result<Vec<TopologyLink>, DbError> {
+    let rows = sqlx::query(&format!("SELECT {} FROM topology_links", LINK_COLUMNS)).fetch_all(&mut *conn).await?;
+    rows.iter().map(link_from_row).collect()
+}
+
+#[async_trait]
+impl LinkRepository for SqliteRepository {
+    async fn replace_device_neighbors(
+        &self,
+        device_id: Uuid,
+        protocol: NeighborProtocol,
+        neighbors: &[DeviceNeighbor],
+    ) -> Result<(), DbError> {
+        let now = OffsetDateTime::now_utc();
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM device_neighbors WHERE device_id = ? AND protocol = ?")
+            .bind(device_id)
+            .bind(protocol.as_str())
+            .execute(&mut *tx)
+            .await?;
+        for neighbor in neighbors {
+            sqlx::query(&format!("INSERT INTO device_neighbors ({}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)", NEIGHBOR_COLUMNS))
+                .bind(Uuid::new_v4())
+                .bind(device_id)
+                .bind(neighbor.interface_id)
+                .bind(protocol.as_str())
+                .bind(&neighbor.remote_chassis_id)
+                .bind(&neighbor.remote_port)
+                .bind(&neighbor.remote_sys_name)
+                .bind(neighbor.remote_address.map(|ip| ip.to_string()))
+                .bind(now)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn list_device_neighbors(&self, device_id: Uuid) -> Result<Vec<DeviceNeighbor>, DbError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM device_neighbors WHERE device_id = ? \
+             ORDER BY protocol DESC, remote_sys_name, remote_port",
+            NEIGHBOR_COLUMNS
+        ))
+        .bind(device_id)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(neighbor_from_row).collect()
+    }
+
+    async fn refresh_topology_links(&self) -> Result<LinkRefresh, DbError> {
+        let now = OffsetDateTime::now_utc();
+        let mut tx = self.pool.begin().await?;
+        let devices: Vec<LinkDevice> = all_devices(&mut tx).await?.iter().map(LinkDevice::from).collect();
+        let rows = sqlx::query(&format!("SELECT {} FROM interfaces", INTERFACE_COLUMNS)).fetch_all(&mut *tx).await?;
+        let ports = rows.iter().map(|row| interface_from_row(row).map(|i| LinkPort::from(&i))).collect::<Result<Vec<_>, _>>()?;
+        let rows = sqlx::query(&format!("SELECT {} FROM device_neighbors", NEIGHBOR_COLUMNS)).fetch_all(&mut *tx).await?;
+        let neighbors = rows.iter().map(neighbor_from_row).collect::<Result<Vec<_>, _>>()?;
+        let rows = sqlx::query(
+            "SELECT device_id, interface_id, mac_address FROM nodes WHERE active AND interface_id IS NOT NULL",
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        let mut sightings = Vec::with_capacity(rows.len());
+        for row in &rows {
+            sightings.push(PortSighting {
+                device_id: row.try_get("device_id")?,
+                interface_id: row.try_get("interface_id")?,
+                mac_address: mac(row, "mac_address")?.ok_or_else(|| mapping("node without mac_address"))?,
+            });
+        }
+        let stored = load_links(&mut tx).await?;
+        let manual: Vec<TopologyLink> = stored.iter().filter(|l| l.source == LinkSource::Manual).cloned().collect();
+        let mut existing: HashMap<links::LinkKey, Uuid> =
+            stored.iter().filter(|l| l.source != LinkSource::Manual).map(|l| (links::link_key(l), l.id)).collect();
+
+        let found = links::infer_links(&devices, &ports, &neighbors, &sightings, &manual);
+        let mut added = 0;
+        for link in &found {
+            match existing.remove(&links::link_key(link)) {
+                Some(id) => {
+                    sqlx::query("UPDATE topology_links SET confidence = ?, last_seen = ? WHERE id = ?")
+                        .bind(link.confidence)
+                        .bind(now)
+                        .bind(id)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                None => {
+                    insert_link(&mut tx, &TopologyLink { id: Uuid::new_v4(), ..link.clone() }).await?;
+                    added += 1;
+                }
+            }
+        }
+        for id in existing.values() {
+            sqlx::query("DELETE FROM topology_links WHERE id = ?").bind(id).execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(LinkRefresh { added, removed: existing.len() as u64, total: found.len() as u64 })
+    }
+
+    async fn list_topology_links(&self, device_id: Option<Uuid>) -> Result<Vec<TopologyLink>, DbError> {
+        let mut conn = self.pool.acquire().await?;
+        let mut links: Vec<TopologyLink> = load_links(&mut conn)
+            .await?
+            .into_iter()
+            .filter(|l| device_id.is_none_or(|id| l.device_id == id || l.remote_device_id == id))
+            .collect();
+        links.sort_by_key(|l| (l.device_id, l.remote_device_id, l.source.as_str(), l.interface_id));
+        Ok(links)
+    }
+
+    async fn create_topology_link(&self, link: &TopologyLink) -> Result<TopologyLink, DbError> {
+        links::check_manual_link(link)?;
+        let link = links::oriented(link.clone());
+        let mut conn = self.pool.acquire().await?;
+        for (device_id, interface_id) in [(link.device_id, link.interface_id), (link.remote_device_id, link.remote_interface_id)] {
+            let found = match interface_id {
+                Some(interface_id) => sqlx::query("SELECT 1 FROM interfaces WHERE id = ? AND device_id = ?")
+                    .bind(interface_id)
+                    .bind(device_id)
+                    .fetch_optional(&mut *conn)
+                    .await?
+                    .is_some(),
+                None => sqlx::query("SELECT 1 FROM devices WHERE id = ?").bind(device_id).fetch_optional(&mut *conn).await?.is_some(),
+            };
+            if !found {
+                return Err(DbError::InvalidQuery(links::endpoint_error(device_id, interface_id)));
+            }
+        }
+        let now = OffsetDateTime::now_utc();
+        let stored = TopologyLink { id: Uuid::new_v4(), source: LinkSource::Manual, first_seen: now, last_seen: now, ..link };
+        insert_link(&mut conn, &stored).await.map_err(|e| unique_violation(e, links::duplicate_link_error))?;
+        Ok(stored)
+    }
+
+    async fn delete_topology_link(&self, id: Uuid) -> Result<(), DbError> {
+        let source: String = sqlx::query_scalar("SELECT source FROM topology_links WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or(DbError::NotFound)?;
+        if source != LinkSource::Manual.as_str() {
+            return Err(links::not_manual_error());
+        }
+        sqlx::query("DELETE FROM topology_links WHERE id = ?").bind(id).execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn is_uplink_port(&self, interface_id: Uuid) -> Result<bool, DbError> {
+        Ok(sqlx::query_scalar(
+            r#"SELECT
+                  EXISTS (SELECT 1 FROM device_neighbors WHERE interface_id = ?1)
+                  OR EXISTS (SELECT 1 FROM topology_links WHERE interface_id = ?1 OR remote_interface_id = ?1)
+                  OR EXISTS (SELECT 1 FROM stp_ports WHERE interface_id = ?1 AND role IN ('root', 'alternate'))
+                  OR EXISTS (
+                    SELECT 1 FROM stp_ports sp
+                    JOIN interfaces i ON i.id = sp.interface_id
+                    JOIN nodes n ON n.interface_id = sp.interface_id AND n.active
+                    JOIN stp_instances b ON b.instance = sp.instance AND b.device_id <> i.device_id
+                    WHERE sp.interface_id = ?1 AND sp.role = 'designated'
+                      AND REPLACE(n.mac_address, ':', '') = SUBSTR(b.bridge_id, 5))"#,
+        )
+        .bind(interface_id)
+        .fetch_one(&self.pool)
+        .await?)
+    }
+}
+
+async fn insert_link(conn: &mut SqliteConnection, link: &TopologyLink) -> Result<(), sqlx::Error> {
+    sqlx::query(&format!("INSERT INTO topology_links ({}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)", LINK_COLUMNS))
+        .bind(link.id)
+        .bind(link.device_id)
+        .bind(link.interface_id)
+        .bind(link.remote_device_id)
+        .bind(link.remote_interface_id)
+        .bind(link.source.as_str())
+        .bind(link.confidence)
+        .bind(link.first_seen)
+        .bind(link.last_seen)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DbPool;
+
+    async fn repository() -> SqliteRepository {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1) // Every connection to :memory: is its own database
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::migrate(&DbPool::Sqlite(pool.clone())).await.unwrap();
+        SqliteRepository::new(pool)
+    }
+
+    fn device(ip: &str) -> Device {
+        let now = OffsetDateTime::now_utc();
+        Device {
+            sys_location: Some("HQ, floor 2".to_string()),
+            vendor: Some("Cisco".to_string()),
+            os_version: Some("17.6.1".to_string()),
+            status: Some(DeviceStatus::Up),
+            last_seen: Some(now),
+            ..Device::new(ip.parse().unwrap())
+        }
+    }
+
+    fn interface(device_id: Uuid, if_index: i32, mac: &str) -> Interface {
+        let now = OffsetDateTime::now_utc();
+        Interface {
+            id: Uuid::nil(),
+            device_id,
+            if_index,
+            if_name: Some(format!("Gi1/0/{}", if_index)),
+            if_alias: None,
+            if_descr: None,
+            if_type: None,
+            mac_address: Some(mac.parse().unwrap()),
+            ip_address: None,
+            admin_status: Some("up".to_string()),
+            oper_status: Some("up".to_string()),
+            speed: None,
+            mtu: None,
+            access_vlan: Some(10),
+            last_changed: None,
+            tags: Vec::new(),
+            custom_fields: CustomFieldValues::new(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[tokio::test]
+    async fn merges_places_and_filters_devices() {
+        let repo = repository().await;
+        let now = OffsetDateTime::now_utc();
+        let site = repo
+            .create_location(&Location {
+                id: Uuid::nil(),
+                parent_id: None,
+                kind: LocationKind::Site,
+                name: "HQ".to_string(),
+                description: None,
+                created_at: now,
+                updated_at: now,
+            })
+            .await
+            .unwrap();
+        repo.create_location_rule(&LocationRule {
+            id: Uuid::nil(),
+            location_id: site.id,
+            priority: 10,
+            sys_location_pattern: Some("hq".to_string()),
+            subnet: None,
+            created_at: now,
+        })
+        .await
+        .unwrap();
+
+        let stored = repo.upsert_device(&device("192.0.2.1/32"), "discovery").await.unwrap();
+        assert_eq!(stored.location_id, Some(site.id));
+        let lab = Device { sys_location: Some("Lab".to_string()), ..device("198.51.100.1/32") };
+        assert_eq!(repo.upsert_device(&lab, "discovery").await.unwrap().location_id, None);
+
+        // A manual override survives rediscovery and shows up in the history
+        repo.set_device_fields(stored.id, &[(DeviceField::OsVersion, Some("custom".to_string()))], "manual:alice")
+            .await
+            .unwrap();
+        let again = repo.upsert_device(&device("192.0.2.1/32"), "discovery").await.unwrap();
+        assert_eq!((again.id, again.os_version.as_deref()), (stored.id, Some("custom")));
+        let history = repo.list_device_history(stored.id).await.unwrap();
+        assert_eq!(history[0].new_value.as_deref(), Some("custom"));
+
+        let query = DeviceQuery {
+            filter: DeviceFilter { subnet: Some("192.0.2.0/24".parse().unwrap()), ..Default::default() },
+            ..Default::default()
+        };
+        assert_eq!(repo.query_devices(&query).await.unwrap().devices, vec![again.clone()]);
+        let query = DeviceQuery { filter: DeviceFilter { location_id: Some(site.id), ..Default::default() }, ..Default::default() };
+        assert_eq!(repo.query_devices(&query).await.unwrap().devices.len(), 1);
+
+        repo.delete_location(site.id).await.unwrap();
+        assert_eq!(repo.get_device(stored.id).await.unwrap().location_id, None);
+    }
+
+    #[tokio::test]
+    async fn stores_interfaces_nodes_and_metadata() {
+        let repo = repository().await;
+        let stored = repo.upsert_device(&device("192.0.2.1/32"), "discovery").await.unwrap();
+        let first = repo.upsert_interface(&interface(stored.id, 1, "00:1a:2b:3c:4d:01")).await.unwrap();
+        let again = repo.upsert_interface(&Interface { access_vlan: None, ..interface(stored.id, 1, "00-1A-2B-3C-4D-01") }).await.unwrap();
+        assert_eq!((again.id, again.access_vlan), (first.id, Some(10)));
+        let mac: MacAddress = "00:1a:2b:3c:4d:01".parse().unwrap();
+        assert_eq!(repo.find_interfaces_by_mac(mac).await.unwrap().len(), 1);
+
+        let now = OffsetDateTime::now_utc();
+        let node = Node {
+            id: Uuid::nil(),
+            mac_address: "02:00:00:00:00:01".parse().unwrap(),
+            device_id: stored.id,
+            interface_id: Some(first.id),
+            vlan: 10,
+            active: true,
+            first_seen: now,
+            last_seen: now,
+            wireless: None,
+        };
+        repo.upsert_nodes(&[node.clone(), node.clone()]).await.unwrap();
+        assert_eq!(repo.deactivate_nodes(&[stored.id], now + time::Duration::seconds(1)).await.unwrap(), 1);
+        assert!(repo.list_nodes(stored.id).await.unwrap().is_empty());
+        assert_eq!(repo.find_nodes_by_mac(node.mac_address).await.unwrap().len(), 1);
+
+        let field = repo
+            .create_custom_field(&CustomField {
+                id: Uuid::nil(),
+                object_type: CustomFieldObject::Interface,
+                name: "circuit_id".to_string(),
+                field_type: CustomFieldType::String,
+                choices: Vec::new(),
+                description: None,
+                created_at: now,
+                updated_at: now,
+            })
+            .await
+            .unwrap();
+        let update = MetadataUpdate {
+            tags: Some(vec!["Uplink".to_string()]),
+            custom_fields: [("circuit_id".to_string(), serde_json::json!("CKT-1"))].into(),
+        };
+        repo.set_interface_metadata(first.id, &update).await.unwrap();
+        repo.upsert_interfaces(&[interface(stored.id, 1, "00:1a:2b:3c:4d:01")]).await.unwrap();
+        let filter = MetadataFilter { tags: vec!["uplink".to_string()], ..Default::default() };
+        assert_eq!(repo.find_interfaces(&filter).await.unwrap()[0].custom_fields["circuit_id"], "CKT-1");
+
+        repo.delete_custom_field(field.id).await.unwrap();
+        assert!(repo.get_interface(stored.id, 1).await.unwrap().custom_fields.is_empty());
+    }
+
+    #[tokio::test]
+    async fn lists_spanning_tree_roots_across_devices() {
+        let repo = repository().await;
+        let core = repo.upsert_device(&device("192.0.2.1/32"), "discovery").await.unwrap();
+        let access = repo.upsert_device(&device("192.0.2.2/32"), "discovery").await.unwrap();
+        let uplink = repo.upsert_interface(&interface(access.id, 2, "00:1a:2b:3c:4d:02")).await.unwrap();
+        let now = OffsetDateTime::now_utc();
+        let instance = |device_id, vlans: Vec<i32>, bridge_id: &str| StpInstance {
+            id: Uuid::nil(),
+            device_id,
+            instance: 1,
+            protocol: "mst".to_string(),
+            vlans,
+            bridge_id: bridge_id.to_string(),
+            designated_root: "1001aabbccddeeff".to_string(),
+            root_cost: None,
+            root_port_if_index: None,
+            root_device_id: None,
+            last_seen: now,
+            created_at: now,
+            updated_at: now,
+        };
+        let blocking = StpPort {
+            id: Uuid::nil(),
+            interface_id: uplink.id,
+            if_index: 2,
+            instance: 1,
+            state: "blocking".to_string(),
+            role: Some("alternate".to_string()),
+            path_cost: None,
+            designated_bridge: None,
+            last_seen: now,
+            created_at: now,
+            updated_at: now,
+        };
+        repo.replace_stp(core.id, &[instance(core.id, vec![10, 11], "1001aabbccddeeff")], &[]).await.unwrap();
+        repo.replace_stp(access.id, &[instance(access.id, vec![10, 12], "8001001122334455")], &[blocking]).await.unwrap();
+
+        assert_eq!(repo.list_stp_instances(access.id).await.unwrap()[0].vlans, vec![10, 12]);
+        let roots = repo.list_stp_roots().await.unwrap();
+        assert_eq!(roots.len(), 1);
+        assert_eq!((roots[0].protocol.as_str(), roots[0].root_device_id), ("mst", Some(core.id)));
+        assert_eq!((roots[0].vlans.as_slice(), roots[0].device_ids.len(), roots[0].blocking_ports), (&[10, 11, 12][..], 2, 1));
+    }
+
+    #[tokio::test]
+    async fn detects_flapping_and_duplicate_addresses() {
+        let repo = repository().await;
+        let switch = repo.upsert_device(&device("192.0.2.1/32"), "discovery").await.unwrap();
+        let port_1 = repo.upsert_interface(&interface(switch.id, 1, "00:1a:2b:3c:4d:01")).await.unwrap();
+        let port_2 = repo.upsert_interface(&interface(switch.id, 2, "00:1a:2b:3c:4d:02")).await.unwrap();
+        let now = OffsetDateTime::now_utc();
+        let node = |mac: &str, interface_id: Uuid, ip: Option<&str>| Node {
+            id: Uuid::nil(),
+            mac_address: mac.parse().unwrap(),
+            device_id: switch.id,
+            interface_id: Some(interface_id),
+            vlan: 10,
+            active: true,
+            first_seen: now,
+            last_seen: now,
+            wireless: ip.map(|ip| NodeWireless { ssid: None, rssi: None, ip_address: Some(ip.parse().unwrap()), last_seen: now }),
+        };
+        // Three moves between the two ports
+        for port in [port_1.id, port_2.id, port_1.id, port_2.id] {
+            repo.upsert_node(&node("02:00:00:00:00:01", port, None)).await.unwrap();
+        }
+        repo.upsert_node(&node("02:00:00:00:00:02", port_1.id, Some("10.0.0.5/32"))).await.unwrap();
+        repo.upsert_node(&node("02:00:00:00:00:03", port_1.id, Some("10.0.0.5/32"))).await.unwrap();
+        // Two routers resolving one wired address to different MACs
+        let router = repo.upsert_device(&device("192.0.2.2/32"), "discovery").await.unwrap();
+        let arp = |device_id: Uuid, mac: &str| ArpEntry {
+            id: Uuid::nil(),
+            device_id,
+            interface_id: None,
+            ip_address: "10.0.0.7/32".parse().unwrap(),
+            mac_address: mac.parse().unwrap(),
+            first_seen: now,
+            last_seen: now,
+        };
+        // Spans only overlap once each has been seen again
+        for _ in 0..2 {
+            repo.upsert_arp_entries(switch.id, &[arp(switch.id, "02:00:00:00:00:04")]).await.unwrap();
+            repo.upsert_arp_entries(router.id, &[arp(router.id, "02:00:00:00:00:05")]).await.unwrap();
+        }
+
+        let settings = AnomalySettings::default();
+        assert_eq!(repo.detect_anomalies(&settings).await.unwrap(), AnomalyReport { opened: 3, ongoing: 0, resolved: 0 });
+        assert_eq!(repo.detect_anomalies(&settings).await.unwrap(), AnomalyReport { opened: 0, ongoing: 3, resolved: 0 });
+
+        let filter = AnomalyFilter { mac_address: Some("02:00:00:00:00:03".parse().unwrap()), ..Default::default() };
+        let found = repo.list_anomalies(&filter).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].kind, AnomalyKind::DuplicateIp);
+        let filter = AnomalyFilter { ip_address: Some("10.0.0.7".parse().unwrap()), ..Default::default() };
+        assert_eq!(repo.list_anomalies(&filter).await.unwrap()[0].device_ids.len(), 2);
+        let filter = AnomalyFilter { kind: Some(AnomalyKind::MacFlapping), open_only: true, ..Default::default() };
+        assert_eq!(repo.list_anomalies(&filter).await.unwrap()[0].moves, Some(3));
+    }
+
+    #[tokio::test]
+    async fn records_macs_missing_from_the_allowlist() {
+        let repo = repository().await;
+        let switch = repo.upsert_device(&device("192.0.2.1/32"), "discovery").await.unwrap();
+        let port_1 = repo.upsert_interface(&interface(switch.id, 1, "00:1a:2b:3c:4d:01")).await.unwrap();
+        let port_2 = repo.upsert_interface(&interface(switch.id, 2, "00:1a:2b:3c:4d:02")).await.unwrap();
+        let entry = AllowedMac {
+            id: Uuid::nil(),
+            mac_prefix: "00:1A:2B".to_string(),
+            location_id: None,
+            vlan: Some(10),
+            description: None,
+            created_at: OffsetDateTime::now_utc(),
+        };
+        assert_eq!(repo.create_allowed_mac(&entry).await.unwrap().mac_prefix, "001a2b");
+        let now = OffsetDateTime::now_utc();
+        let node = |mac: &str, interface_id: Uuid| Node {
+            id: Uuid::nil(),
+            mac_address: mac.parse().unwrap(),
+            device_id: switch.id,
+            interface_id: Some(interface_id),
+            vlan: 10,
+            active: true,
+            first_seen: now,
+            last_seen: now,
+            wireless: None,
+        };
+
+        let nodes = [node("00:1a:2b:00:00:01", port_1.id), node("02:00:00:00:00:01", port_1.id)];
+        let violations = repo.check_mac_allowlist(&nodes, 4).await.unwrap();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].mac_address.to_string(), "02:00:00:00:00:01");
+        repo.set_violation_port_shutdown(violations[0].id, now).await.unwrap();
+
+        // Seen again on another port: same violation, shutdown forgotten
+        let moved = repo.check_mac_allowlist(&[node("02:00:00:00:00:01", port_2.id)], 4).await.unwrap();
+        assert_eq!(moved[0].id, violations[0].id);
+        assert_eq!(moved[0].interface_id, Some(port_2.id));
+        assert_eq!(moved[0].port_shutdown_at, None);
+        let filter = MacViolationFilter { device_id: Some(switch.id), ..Default::default() };
+        assert_eq!(repo.list_mac_violations(&filter).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn derives_subnets_and_sightings() {
+        let repo = repository().await;
+        let router = repo.upsert_device(&device("10.1.20.1/32"), "discovery").await.unwrap();
+        let vlan = Interface {
+            ip_address: Some("10.1.20.1/24".parse().unwrap()),
+            ..interface(router.id, 20, "00:1a:2b:3c:4d:01")
+        };
+        let vlan = repo.upsert_interface(&vlan).await.unwrap();
+        let now = OffsetDateTime::now_utc();
+        let arp = ArpEntry {
+            id: Uuid::nil(),
+            device_id: router.id,
+            interface_id: Some(vlan.id),
+            ip_address: "10.1.20.9/32".parse().unwrap(),
+            mac_address: "00:1a:2b:00:00:09".parse().unwrap(),
+            first_seen: now,
+            last_seen: now,
+        };
+        repo.upsert_arp_entries(router.id, std::slice::from_ref(&arp)).await.unwrap();
+        repo.upsert_arp_entries(router.id, &[arp]).await.unwrap();
+
+        let refresh = repo.refresh_subnets().await.unwrap();
+        assert_eq!(refresh, SubnetRefresh { added: 1, removed: 0, total: 1 });
+        assert_eq!(repo.refresh_subnets().await.unwrap().added, 0);
+        let subnets = repo.list_subnets().await.unwrap();
+        assert_eq!(subnets[0].network.to_string(), "10.1.20.0/24");
+        assert_eq!(repo.get_subnet(subnets[0].id).await.unwrap(), subnets[0]);
+
+        // The ARP entry, the interface address and the device's management address
+        let sightings = repo.list_ip_sightings(subnets[0].network).await.unwrap();
+        assert_eq!(sightings.len(), 3);
+        assert!(repo.list_ip_sightings("10.1.21.0/24".parse().unwrap()).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn stores_reverse_dns_names() {
+        let repo = repository().await;
+        repo.set_ip_hostname("10.1.20.5/32".parse().unwrap(), Some("pc-5.example.net")).await.unwrap();
+        repo.set_ip_hostname("10.1.20.6/32".parse().unwrap(), Some("pc-6.example.net")).await.unwrap();
+        repo.set_ip_hostname("10.1.20.6/32".parse().unwrap(), None).await.unwrap();
+        let names = repo.list_ip_hostnames(Some("10.1.20.0/24".parse().unwrap())).await.unwrap();
+        assert_eq!(names.len(), 1);
+        assert_eq!(names[0].hostname, "pc-5.example.net");
+
+        let first = repo.upsert_device(&device("192.0.2.1/32"), "discovery").await.unwrap();
+        let second = repo.upsert_device(&device("192.0.2.2/32"), "discovery").await.unwrap();
+        let name = |hostname: &str| [(DeviceField::Hostname, Some(hostname.to_string()))];
+        repo.set_device_fields(first.id, &name("sw-1.example.net"), "dns").await.unwrap();
+        let error = repo.set_device_fields(second.id, &name("sw-1.example.net"), "dns").await.unwrap_err();
+        assert!(matches!(error, DbError::InvalidQuery(_)));
+    }
+
+    #[tokio::test]
+    async fn builds_links_and_keeps_manual_ones() {
+        let repo = repository().await;
+        let core = repo.upsert_device(&Device { sys_name: Some("core".to_string()), ..device("10.0.0.1/32") }, "discovery").await.unwrap();
+        let access = repo.upsert_device(&device("10.0.0.2/32"), "discovery").await.unwrap();
+        let other = repo.upsert_device(&device("10.0.0.3/32"), "discovery").await.unwrap();
+        let core_port = repo.upsert_interface(&interface(core.id, 1, "02:00:00:00:01:01")).await.unwrap();
+        let access_port = repo.upsert_interface(&interface(access.id, 1, "02:00:00:00:02:01")).await.unwrap();
+        let now = OffsetDateTime::now_utc();
+        let neighbor = DeviceNeighbor {
+            id: Uuid::nil(),
+            device_id: access.id,
+            interface_id: Some(access_port.id),
+            protocol: NeighborProtocol::Lldp,
+            remote_chassis_id: None,
+            remote_port: core_port.if_name.clone(),
+            remote_sys_name: Some("core.example.net".to_string()),
+            remote_address: None,
+            last_seen: now,
+        };
+        repo.replace_device_neighbors(access.id, NeighborProtocol::Lldp, &[neighbor]).await.unwrap();
+        assert_eq!(repo.list_device_neighbors(access.id).await.unwrap().len(), 1);
+
+        assert_eq!(repo.refresh_topology_links().await.unwrap(), LinkRefresh { added: 1, removed: 0, total: 1 });
+        assert_eq!(repo.refresh_topology_links().await.unwrap().added, 0);
+        let links = repo.list_topology_links(Some(core.id)).await.unwrap();
+        assert_eq!(links.len(), 1);
+        assert_eq!((links[0].source, links[0].confidence), (LinkSource::Lldp, 100));
+        let error = repo.delete_topology_link(links[0].id).await.unwrap_err();
+        assert!(matches!(error, DbError::InvalidQuery(_)));
+
+        let manual = TopologyLink {
+            id: Uuid::nil(),
+            device_id: other.id,
+            interface_id: Some(core_port.id), // Not on `other`
+            remote_device_id: access.id,
+            remote_interface_id: None,
+            source: LinkSource::Manual,
+            confidence: 100,
+            first_seen: now,
+            last_seen: now,
+        };
+        let error = repo.create_topology_link(&manual).await.unwrap_err();
+        assert!(matches!(error, DbError::InvalidQuery(_)));
+        let manual = repo.create_topology_link(&TopologyLink { interface_id: None, ..manual }).await.unwrap();
+        let error = repo.create_topology_link(&manual).await.unwrap_err(); // One link per connection and source
+        assert!(matches!(error, DbError::InvalidQuery(_)));
+
+        // Neighbors gone: the LLDP link goes, the manual one stays
+        repo.replace_device_neighbors(access.id, NeighborProtocol::Lldp, &[]).await.unwrap();
+        assert_eq!(repo.refresh_topology_links().await.unwrap().removed, 1);
+        assert_eq!(repo.list_topology_links(None).await.unwrap(), std::slice::from_ref(&manual));
+        repo.delete_topology_link(manual.id).await.unwrap();
+        assert!(repo.list_topology_links(None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn prunes_in_batches_and_retires_stale_devices() {
+        let repo = repository().await;
+        let now = OffsetDateTime::now_utc();
+        let old = now - time::Duration::days(400);
+        let stale = repo.upsert_device(&Device { last_seen: Some(old), ..device("192.0.2.1/32") }, "discovery").await.unwrap();
+        let switch = repo.upsert_device(&device("192.0.2.2/32"), "discovery").await.unwrap();
+        let port_1 = repo.upsert_interface(&interface(switch.id, 1, "00:1a:2b:3c:4d:01")).await.unwrap();
+        let port_2 = repo.upsert_interface(&interface(switch.id, 2, "00:1a:2b:3c:4d:02")).await.unwrap();
+        let node = |mac: &str, interface_id: Uuid, last_seen: OffsetDateTime| Node {
+            id: Uuid::nil(),
+            mac_address: mac.parse().unwrap(),
+            device_id: switch.id,
+            interface_id: Some(interface_id),
+            vlan: 10,
+            active: true,
+            first_seen: last_seen,
+            last_seen,
+            wireless: None,
+        };
+        // Three old sightings, one of which moved long ago, and a current one
+        for mac in ["02:00:00:00:00:01", "02:00:00:00:00:02", "02:00:00:00:00:03"] {
+            repo.upsert_node(&node(mac, port_1.id, old)).await.unwrap();
+        }
+        repo.upsert_node(&node("02:00:00:00:00:01", port_2.id, old)).await.unwrap();
+        repo.upsert_node(&node("02:00:00:00:00:04", port_1.id, now)).await.unwrap();
+        // An old resolved anomaly goes, an old open one stays
+        for resolved_at in [Some(old), None] {
+            sqlx::query(
+                "INSERT INTO anomalies (id, kind, mac_address, first_detected, last_detected, resolved_at) \
+                 VALUES (?, 'mac_flapping', '02:00:00:00:00:01', ?, ?, ?)",
+            )
+            .bind(Uuid::new_v4())
+            .bind(old)
+            .bind(old)
+            .bind(resolved_at)
+            .execute(&repo.pool)
+            .await
+            .unwrap();
+        }
+        sqlx::query(
+            "INSERT INTO mac_violations (id, mac_address, device_id, vlan, first_seen, last_seen) \
+             VALUES (?, '02:00:00:00:00:02', ?, 10, ?, ?)",
+        )
+        .bind(Uuid::new_v4())
+        .bind(switch.id)
+        .bind(old)
+        .bind(old)
+        .execute(&repo.pool)
+        .await
+        .unwrap();
+
+        let settings = RetentionSettings {
+            node_days: Some(90),
+            node_move_days: Some(30),
+            anomaly_days: Some(180),
+            mac_violation_days: Some(180),
+            device_history_days: Some(365),
+            archive_device_days: Some(180),
+            batch_size: 1,
+            dry_run: true,
+            ..RetentionSettings::default()
+        };
+        let expected = RetentionReport {
+            dry_run: true,
+            nodes: 3,
+            node_moves: 1,
+            anomalies: 1,
+            mac_violations: 1,
+            archived_devices: 1,
+            ..RetentionReport::default()
+        };
+        assert_eq!(repo.apply_retention(&settings).await.unwrap(), expected);
+        let count = |table: &'static str| {
+            let pool = repo.pool.clone();
+            async move {
+                sqlx::query_scalar::<_, i64>(&format!("SELECT COUNT(*) FROM {}", table)).fetch_one(&pool).await.unwrap()
+            }
+        };
+        assert_eq!(count("nodes").await, 4);
+
+        // One row per statement: every kind takes several batches
+        let settings = RetentionSettings { dry_run: false, ..settings };
+        assert_eq!(repo.apply_retention(&settings).await.unwrap(), RetentionReport { dry_run: false, ..expected });
+        assert_eq!(count("nodes").await, 1);
+        assert_eq!(count("node_moves").await, 0);
+        assert_eq!(count("anomalies").await, 1);
+        assert_eq!(count("mac_violations").await, 0);
+        assert_eq!(repo.get_device(stale.id).await.unwrap().status, Some(DeviceStatus::Retired));
+        assert_eq!(repo.list_device_history(stale.id).await.unwrap()[0].source, RETENTION_SOURCE);
+        assert_eq!(repo.apply_retention(&settings).await.unwrap(), RetentionReport::default());
+    }
+}
@@ -0,0 +1,205 @@
+use crate::{DbError, PgPool, StpInstance, StpPort, StpRoot};
+use uuid::Uuid;
+
+/// Replaces the spanning tree state of a device with a fresh collection.
+/// Ports are matched to interfaces by ifIndex; ports without a stored
+/// interface are skipped. Instances and ports no longer reported are removed.
+pub async fn replace_stp(
+    pool: &PgPool,
+    device_id: Uuid,
+    instances: &[StpInstance],
+    ports: &[StpPort],
+) -> Result<(), DbError> {
+    let mut tx = pool.begin().await?;
+
+    for instance in instances {
+        sqlx::query!(
+            r#"
+            INSERT INTO stp_instances (
+                device_id, instance, protocol, vlans, bridge_id, designated_root, root_cost, root_port_if_index, last_seen
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, NOW())
+            ON CONFLICT (device_id, instance) DO UPDATE SET
+                protocol = EXCLUDED.protocol,
+                vlans = EXCLUDED.vlans,
+                bridge_id = EXCLUDED.bridge_id,
+                designated_root = EXCLUDED.designated_root,
+                root_cost = EXCLUDED.root_cost,
+                root_port_if_index = EXCLUDED.root_port_if_index,
+                last_seen = EXCLUDED.last_seen,
+                updated_at = NOW()
+            "#,
+            device_id,
+            instance.instance,
+            instance.protocol,
+            &instance.vlans,
+            instance.bridge_id,
+            instance.designated_root,
+            instance.root_cost,
+            instance.root_port_if_index
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for port in ports {
+        sqlx::query!(
+            r#"
+            INSERT INTO stp_ports (
+                interface_id, instance, state, role, path_cost, designated_bridge, last_seen
+            )
+            SELECT id, $3, $4, $5, $6, $7, NOW()
+            FROM interfaces WHERE device_id = $1 AND if_index = $2
+            ON CONFLICT (interface_id, instance) DO UPDATE SET
+                state = EXCLUDED.state,
+                role = EXCLUDED.role,
+                path_cost = EXCLUDED.path_cost,
+                designated_bridge = EXCLUDED.designated_bridge,
+                last_seen = EXCLUDED.last_seen,
+                updated_at = NOW()
+            "#,
+            device_id,
+            port.if_index,
+            port.instance,
+            port.state,
+            port.role,
+            port.path_cost,
+            port.designated_bridge
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let current_instances: Vec<i32> = instances.iter().map(|i| i.instance).collect();
+    sqlx::query!(
+        "DELETE FROM stp_instances WHERE device_id = $1 AND NOT (instance = ANY($2))",
+        device_id,
+        &current_instances
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let (port_if_indexes, port_instances): (Vec<i32>, Vec<i32>) =
+        ports.iter().map(|p| (p.if_index, p.instance)).unzip();
+    sqlx::query!(
+        r#"DELETE FROM stp_ports sp USING interfaces i
+           WHERE sp.interface_id = i.id AND i.device_id = $1
+             AND (i.if_index, sp.instance) NOT IN (SELECT * FROM UNNEST($2::int4[], $3::int4[]))"#,
+        device_id,
+        &port_if_indexes,
+        &port_instances
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Retrieves the spanning tree instances of a device, resolving the root bridge
+/// to a known device where another device reports that bridge ID for the same instance.
+pub async fn list_stp_instances(pool: &PgPool, device_id: Uuid) -> Result<Vec<StpInstance>, DbError> {
+    let rows = sqlx::query!(
+        r#"SELECT
+              si.id, si.device_id, si.instance, si.protocol, si.vlans, si.bridge_id, si.designated_root,
+              si.root_cost, si.root_port_if_index,
+              (SELECT r.device_id FROM stp_instances r
+                WHERE r.instance = si.instance AND r.bridge_id = si.designated_root
+                LIMIT 1) as root_device_id,
+              si.last_seen, si.created_at, si.updated_at
+           FROM stp_instances si WHERE si.device_id = $1 ORDER BY si.instance"#,
+        device_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| StpInstance {
+            id: row.id,
+            device_id: row.device_id,
+            instance: row.instance,
+            protocol: row.protocol,
+            vlans: row.vlans,
+            bridge_id: row.bridge_id,
+            designated_root: row.designated_root,
+            root_cost: row.root_cost,
+            root_port_if_index: row.root_port_if_index,
+            root_device_id: row.root_device_id,
+            last_seen: row.last_seen,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        })
+        .collect())
+}
+
+/// Retrieves the root bridge of every spanning tree instance across devices, by instance,
+/// with the devices reporting it and how many of their ports block.
+pub async fn list_stp_roots(pool: &PgPool) -> Result<Vec<StpRoot>, DbError> {
+    let rows = sqlx::query!(
+        r#"SELECT
+              si.protocol, si.instance, si.designated_root,
+              (SELECT r.device_id FROM stp_instances r
+                WHERE r.instance = si.instance AND r.bridge_id = si.designated_root
+                LIMIT 1) as root_device_id,
+              ARRAY_AGG(si.device_id ORDER BY si.device_id) as "device_ids!",
+              COALESCE((SELECT ARRAY_AGG(DISTINCT v ORDER BY v)
+                FROM stp_instances x, UNNEST(x.vlans) v
+                WHERE x.protocol = si.protocol AND x.instance = si.instance
+                  AND x.designated_root = si.designated_root), '{}') as "vlans!",
+              (SELECT COUNT(*) FROM stp_ports sp
+                JOIN interfaces i ON i.id = sp.interface_id
+                JOIN stp_instances x ON x.device_id = i.device_id AND x.instance = sp.instance
+                WHERE x.protocol = si.protocol AND x.instance = si.instance
+                  AND x.designated_root = si.designated_root AND sp.state = 'blocking') as "blocking_ports!"
+           FROM stp_instances si
+           GROUP BY si.protocol, si.instance, si.designated_root
+           ORDER BY si.instance, si.protocol, si.designated_root"#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| StpRoot {
+            protocol: row.protocol,
+            instance: row.instance,
+            vlans: row.vlans,
+            designated_root: row.designated_root,
+            root_device_id: row.root_device_id,
+            device_ids: row.device_ids,
+            blocking_ports: row.blocking_ports,
+        })
+        .collect())
+}
+
+/// Retrieves the per-interface spanning tree state of a device, by instance then ifIndex.
+pub async fn list_stp_ports(pool: &PgPool, device_id: Uuid) -> Result<Vec<StpPort>, DbError> {
+    let rows = sqlx::query!(
+        r#"SELECT
+              sp.id, sp.interface_id, i.if_index, sp.instance, sp.state, sp.role,
+              sp.path_cost, sp.designated_bridge, sp.last_seen, sp.created_at, sp.updated_at
+           FROM stp_ports sp JOIN interfaces i ON i.id = sp.interface_id
+           WHERE i.device_id = $1 ORDER BY sp.instance, i.if_index"#,
+        device_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| StpPort {
+            id: row.id,
+            interface_id: row.interface_id,
+            if_index: row.if_index,
+            instance: row.instance,
+            state: row.state,
+            role: row.role,
+            path_cost: row.path_cost,
+            designated_bridge: row.designated_bridge,
+            last_seen: row.last_seen,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        })
+        .collect())
+}
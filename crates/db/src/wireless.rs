@@ -0,0 +1,58 @@
+use crate::{DbError, MacAddress, PgPool, WirelessAp};
+use uuid::Uuid;
+
+/// Links an AP device to its controller, inserting or refreshing the AP record.
+/// An AP that moved to another controller is re-linked.
+pub async fn upsert_wireless_ap(pool: &PgPool, ap: &WirelessAp) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO wireless_aps (device_id, controller_id, mac_address, name, location, oper_status, last_seen)
+        VALUES ($1, $2, $3, $4, $5, $6, NOW())
+        ON CONFLICT (device_id) DO UPDATE SET
+            controller_id = EXCLUDED.controller_id,
+            mac_address = EXCLUDED.mac_address,
+            name = EXCLUDED.name,
+            location = EXCLUDED.location,
+            oper_status = EXCLUDED.oper_status,
+            last_seen = EXCLUDED.last_seen,
+            updated_at = NOW()
+        "#,
+        ap.device_id,
+        ap.controller_id,
+        ap.mac_address as _,
+        ap.name,
+        ap.location,
+        ap.oper_status
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Retrieves the APs managed by a controller, ordered by name.
+pub async fn list_wireless_aps(pool: &PgPool, controller_id: Uuid) -> Result<Vec<WirelessAp>, DbError> {
+    let rows = sqlx::query!(
+        r#"SELECT
+              device_id, controller_id, mac_address as "mac_address: MacAddress", name, location,
+              oper_status, last_seen, created_at, updated_at
+           FROM wireless_aps WHERE controller_id = $1 ORDER BY name, mac_address"#,
+        controller_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| WirelessAp {
+            device_id: row.device_id,
+            controller_id: row.controller_id,
+            mac_address: row.mac_address,
+            name: row.name,
+            location: row.location,
+            oper_status: row.oper_status,
+            last_seen: row.last_seen,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        })
+        .collect())
+}
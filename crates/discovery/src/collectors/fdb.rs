@@ -0,0 +1,218 @@
+//! MAC address table collector (Q-BRIDGE-MIB dot1qTpFdbTable, BRIDGE-MIB dot1dTpFdbTable).
+//!
+//! VLAN-aware agents report all VLANs in dot1qTpFdbTable, indexed by filtering
+//! database; dot1qVlanFdbId maps those back to VLANs. Other agents only have
+//! dot1dTpFdbTable, which Cisco switches answer once per VLAN through community
+//! string indexing, like the spanning tree collector. Bridge ports are mapped to
+//! interfaces through dot1dBasePortIfIndex.
+
+use super::stp::{parse_vtp_vlans, VTP_VLAN_STATE};
+use super::{column, column_by_index, mac_from_index};
+use db::{Interface, Node};
+use nd_core::{SnmpClient, SnmpError, SnmpValueOwned, VarBind};
+use std::collections::{BTreeMap, HashMap};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// BRIDGE-MIB::dot1dBasePortEntry
+const DOT1D_BASE_PORT_ENTRY: [u32; 10] = [1, 3, 6, 1, 2, 1, 17, 1, 4, 1];
+/// BRIDGE-MIB::dot1dTpFdbEntry
+const DOT1D_TP_FDB_ENTRY: [u32; 10] = [1, 3, 6, 1, 2, 1, 17, 4, 3, 1];
+/// Q-BRIDGE-MIB::dot1qTpFdbEntry
+const DOT1Q_TP_FDB_ENTRY: [u32; 12] = [1, 3, 6, 1, 2, 1, 17, 7, 1, 2, 2, 1];
+/// Q-BRIDGE-MIB::dot1qVlanCurrentEntry
+const DOT1Q_VLAN_CURRENT_ENTRY: [u32; 12] = [1, 3, 6, 1, 2, 1, 17, 7, 1, 4, 2, 1];
+
+const BASE_PORT_IF_INDEX: u32 = 2;
+/// dot1dTpFdbPort and dot1qTpFdbPort
+const FDB_PORT: u32 = 2;
+/// dot1dTpFdbStatus and dot1qTpFdbStatus
+const FDB_STATUS: u32 = 3;
+const VLAN_FDB_ID: u32 = 3;
+
+/// Entries learned from traffic; the others are static, the bridge's own or invalid.
+const FDB_STATUS_LEARNED: i64 = 3;
+
+/// Walks the MAC address table on the target and returns one node per MAC and VLAN,
+/// on the interface it was learned on. `interfaces` are the device's stored interfaces.
+pub async fn collect_fdb(client: &impl SnmpClient, device_id: Uuid, interfaces: &[Interface]) -> Result<Vec<Node>, SnmpError> {
+    let base_rows = client.walk(&column(&DOT1D_BASE_PORT_ENTRY, BASE_PORT_IF_INDEX)).await?;
+    let q_rows = client.walk(&DOT1Q_TP_FDB_ENTRY).await?;
+    if !q_rows.is_empty() {
+        let vlan_rows = client.walk(&column(&DOT1Q_VLAN_CURRENT_ENTRY, VLAN_FDB_ID)).await?;
+        return Ok(parse_dot1q_fdb(&base_rows, &q_rows, &vlan_rows, device_id, interfaces));
+    }
+
+    let vlans = parse_vtp_vlans(&client.walk(&VTP_VLAN_STATE).await?);
+    if vlans.is_empty() {
+        let fdb_rows = client.walk(&DOT1D_TP_FDB_ENTRY).await?;
+        return Ok(parse_dot1d_fdb(&base_rows, &fdb_rows, 0, device_id, interfaces));
+    }
+
+    let mut nodes = Vec::new();
+    for vlan in vlans {
+        let vlan_client = client.with_community_index(&vlan.to_string());
+        let walk = async {
+            let base_rows = vlan_client.walk(&column(&DOT1D_BASE_PORT_ENTRY, BASE_PORT_IF_INDEX)).await?;
+            let fdb_rows = vlan_client.walk(&DOT1D_TP_FDB_ENTRY).await?;
+            Ok::<_, SnmpError>((base_rows, fdb_rows))
+        };
+        match walk.await {
+            Ok((base_rows, fdb_rows)) => {
+                nodes.extend(parse_dot1d_fdb(&base_rows, &fdb_rows, vlan as i32, device_id, interfaces));
+            }
+            Err(e) => {
+                tracing::warn!(%device_id, vlan, error = %e, "Per-VLAN MAC table walk failed");
+            }
+        }
+    }
+    Ok(nodes)
+}
+
+/// Maps walked dot1dTpFdbTable rows, indexed by MAC, to nodes in `vlan`.
+pub fn parse_dot1d_fdb(
+    base_rows: &[VarBind],
+    fdb_rows: &[VarBind],
+    vlan: i32,
+    device_id: Uuid,
+    interfaces: &[Interface],
+) -> Vec<Node> {
+    let ports = column_by_index(fdb_rows, &column(&DOT1D_TP_FDB_ENTRY, FDB_PORT));
+    let status = column_by_index(fdb_rows, &column(&DOT1D_TP_FDB_ENTRY, FDB_STATUS));
+    let entries = ports.iter().map(|(index, port)| (vlan, index.as_slice(), port, status.get(index)));
+    nodes_from_entries(base_rows, entries, device_id, interfaces)
+}
+
+/// Maps walked dot1qTpFdbTable rows, indexed by filtering database and MAC, to nodes.
+/// Filtering databases no VLAN maps to are taken to be the VLAN itself.
+pub fn parse_dot1q_fdb(
+    base_rows: &[VarBind],
+    fdb_rows: &[VarBind],
+    vlan_rows: &[VarBind],
+    device_id: Uuid,
+    interfaces: &[Interface],
+) -> Vec<Node> {
+    // Index: dot1qVlanTimeMark, dot1qVlanIndex. With shared learning the lowest VLAN stands for the rest
+    let mut fdb_vlans: HashMap<i64, i32> = HashMap::new();
+    for (index, value) in column_by_index(vlan_rows, &column(&DOT1Q_VLAN_CURRENT_ENTRY, VLAN_FDB_ID)) {
+        let (Some(fdb_id), [_, vlan]) = (value.as_i64(), index.as_slice()) else {
+            continue;
+        };
+        let Ok(vlan) = i32::try_from(*vlan) else {
+            continue;
+        };
+        fdb_vlans.entry(fdb_id).and_modify(|v| *v = (*v).min(vlan)).or_insert(vlan);
+    }
+
+    let ports = column_by_index(fdb_rows, &column(&DOT1Q_TP_FDB_ENTRY, FDB_PORT));
+    let status = column_by_index(fdb_rows, &column(&DOT1Q_TP_FDB_ENTRY, FDB_STATUS));
+    let entries = ports.iter().filter_map(|(index, port)| {
+        let (&fdb_id, mac) = index.split_first()?;
+        let vlan = fdb_vlans.get(&i64::from(fdb_id)).copied().or_else(|| i32::try_from(fdb_id).ok())?;
+        Some((vlan, mac, port, status.get(index)))
+    });
+    nodes_from_entries(base_rows, entries, device_id, interfaces)
+}
+
+// Keeps learned entries on known bridge ports; an agent without the status column
+// only lists learned ones
+fn nodes_from_entries<'a>(
+    base_rows: &[VarBind],
+    entries: impl Iterator<Item = (i32, &'a [u32], &'a SnmpValueOwned, Option<&'a SnmpValueOwned>)>,
+    device_id: Uuid,
+    interfaces: &[Interface],
+) -> Vec<Node> {
+    let port_if_index: BTreeMap<Vec<u32>, i64> = column_by_index(base_rows, &column(&DOT1D_BASE_PORT_ENTRY, BASE_PORT_IF_INDEX))
+        .into_iter()
+        .filter_map(|(port, value)| Some((port, value.as_i64()?)))
+        .collect();
+    let interface_ids: HashMap<i64, Uuid> = interfaces.iter().map(|i| (i64::from(i.if_index), i.id)).collect();
+
+    let now = OffsetDateTime::now_utc();
+    entries
+        .filter(|(_, _, _, status)| status.is_none_or(|s| s.as_i64() == Some(FDB_STATUS_LEARNED)))
+        .filter_map(|(vlan, mac, port, _)| {
+            let if_index = port_if_index.get(&vec![u32::try_from(port.as_i64()?).ok()?])?;
+            Some(Node {
+                id: Uuid::nil(),
+                mac_address: mac_from_index(mac)?,
+                device_id,
+                interface_id: interface_ids.get(if_index).copied(),
+                vlan,
+                active: true,
+                first_seen: now,
+                last_seen: now,
+                wireless: None,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(entry: &[u32], column_number: u32, index: &[u32], value: SnmpValueOwned) -> VarBind {
+        let mut oid = column(entry, column_number);
+        oid.extend_from_slice(index);
+        (oid, value)
+    }
+
+    fn interface(if_index: i32) -> Interface {
+        let now = OffsetDateTime::now_utc();
+        Interface {
+            id: Uuid::new_v4(),
+            device_id: Uuid::nil(),
+            if_index,
+            if_name: Some(format!("Gi1/0/{}", if_index)),
+            if_alias: None,
+            if_descr: None,
+            if_type: None,
+            mac_address: None,
+            ip_address: None,
+            admin_status: None,
+            oper_status: None,
+            speed: None,
+            mtu: None,
+            access_vlan: None,
+            last_changed: None,
+            tags: Vec::new(),
+            custom_fields: Default::default(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn maps_learned_macs_to_interfaces_and_vlans() {
+        let interfaces = [interface(10101), interface(10102)];
+        let base_rows = vec![
+            row(&DOT1D_BASE_PORT_ENTRY, BASE_PORT_IF_INDEX, &[1], SnmpValueOwned::Integer(10101)),
+            row(&DOT1D_BASE_PORT_ENTRY, BASE_PORT_IF_INDEX, &[2], SnmpValueOwned::Integer(10102)),
+        ];
+        // Filtering database 5 serves VLAN 20; database 30 has no mapping and is VLAN 30
+        let vlan_rows = vec![row(&DOT1Q_VLAN_CURRENT_ENTRY, VLAN_FDB_ID, &[0, 20], SnmpValueOwned::Integer(5))];
+        let laptop = [5, 0x00, 0x1a, 0x2b, 0x00, 0x00, 0x01];
+        let phone = [30, 0x00, 0x1a, 0x2b, 0x00, 0x00, 0x02];
+        let own = [5, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+        let fdb_rows = vec![
+            row(&DOT1Q_TP_FDB_ENTRY, FDB_PORT, &laptop, SnmpValueOwned::Integer(1)),
+            row(&DOT1Q_TP_FDB_ENTRY, FDB_PORT, &phone, SnmpValueOwned::Integer(2)),
+            row(&DOT1Q_TP_FDB_ENTRY, FDB_PORT, &own, SnmpValueOwned::Integer(0)),
+            row(&DOT1Q_TP_FDB_ENTRY, FDB_STATUS, &laptop, SnmpValueOwned::Integer(3)),
+            row(&DOT1Q_TP_FDB_ENTRY, FDB_STATUS, &phone, SnmpValueOwned::Integer(3)),
+            row(&DOT1Q_TP_FDB_ENTRY, FDB_STATUS, &own, SnmpValueOwned::Integer(4)),
+        ];
+
+        let nodes = parse_dot1q_fdb(&base_rows, &fdb_rows, &vlan_rows, Uuid::nil(), &interfaces);
+
+        let found: Vec<_> = nodes.iter().map(|n| (n.mac_address.to_string(), n.vlan, n.interface_id)).collect();
+        assert_eq!(
+            found,
+            vec![
+                ("00:1a:2b:00:00:01".to_string(), 20, Some(interfaces[0].id)),
+                ("00:1a:2b:00:00:02".to_string(), 30, Some(interfaces[1].id)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! IF-MIB interface collector (ifTable and ifXTable).
+
+use super::{column, column_by_index};
+use db::{CustomFieldValues, Interface, MacAddress};
+use nd_core::{SnmpClient, SnmpError, SnmpValueOwned, VarBind};
+use std::collections::BTreeMap;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// IF-MIB::ifEntry
+const IF_ENTRY: [u32; 9] = [1, 3, 6, 1, 2, 1, 2, 2, 1];
+/// IF-MIB::ifXEntry
+const IF_X_ENTRY: [u32; 10] = [1, 3, 6, 1, 2, 1, 31, 1, 1, 1];
+
+const IF_DESCR: u32 = 2;
+const IF_TYPE: u32 = 3;
+const IF_MTU: u32 = 4;
+const IF_SPEED: u32 = 5;
+const IF_PHYS_ADDRESS: u32 = 6;
+const IF_ADMIN_STATUS: u32 = 7;
+const IF_OPER_STATUS: u32 = 8;
+const IF_NAME: u32 = 1;
+const IF_HIGH_SPEED: u32 = 15;
+const IF_ALIAS: u32 = 18;
+
+/// ifSpeed saturates at this value; ifHighSpeed (Mbit/s) must be used above it.
+const IF_SPEED_SATURATED: i64 = u32::MAX as i64;
+const BITS_PER_MBIT: i64 = 1_000_000;
+
+/// Walks ifTable and ifXTable on the target and returns one `Interface` per ifIndex.
+pub async fn collect_interfaces(client: &impl SnmpClient, device_id: Uuid) -> Result<Vec<Interface>, SnmpError> {
+    let if_rows = client.walk(&IF_ENTRY).await?;
+    // ifXTable is optional on very old agents
+    let if_x_rows = match client.walk(&IF_X_ENTRY).await {
+        Ok(rows) => rows,
+        Err(e) => {
+            tracing::warn!(%device_id, error = %e, "ifXTable walk failed; continuing without it");
+            Vec::new()
+        }
+    };
+    Ok(parse_interfaces(&if_rows, &if_x_rows, device_id))
+}
+
+/// Maps walked ifTable/ifXTable rows to interfaces.
+pub fn parse_interfaces(if_rows: &[VarBind], if_x_rows: &[VarBind], device_id: Uuid) -> Vec<Interface> {
+    let descr = column_by_index(if_rows, &column(&IF_ENTRY, IF_DESCR));
+    let if_type = column_by_index(if_rows, &column(&IF_ENTRY, IF_TYPE));
+    let mtu = column_by_index(if_rows, &column(&IF_ENTRY, IF_MTU));
+    let speed = column_by_index(if_rows, &column(&IF_ENTRY, IF_SPEED));
+    let phys_address = column_by_index(if_rows, &column(&IF_ENTRY, IF_PHYS_ADDRESS));
+    let admin_status = column_by_index(if_rows, &column(&IF_ENTRY, IF_ADMIN_STATUS));
+    let oper_status = column_by_index(if_rows, &column(&IF_ENTRY, IF_OPER_STATUS));
+    let name = column_by_index(if_x_rows, &column(&IF_X_ENTRY, IF_NAME));
+    let high_speed = column_by_index(if_x_rows, &column(&IF_X_ENTRY, IF_HIGH_SPEED));
+    let alias = column_by_index(if_x_rows, &column(&IF_X_ENTRY, IF_ALIAS));
+
+    // Every interface has an ifDescr row; use it (then ifName) as the list of indexes
+    let mut indexes: Vec<&Vec<u32>> = descr.keys().chain(name.keys()).collect();
+    indexes.sort();
+    indexes.dedup();
+
+    let now = OffsetDateTime::now_utc();
+    indexes
+        .into_iter()
+        .filter_map(|index| {
+            let if_index = match index.as_slice() {
+                [i] => i32::try_from(*i).ok()?,
+                _ => return None,
+            };
+            let text = |col: &BTreeMap<Vec<u32>, SnmpValueOwned>| {
+                col.get(index).and_then(SnmpValueOwned::as_string).filter(|s| !s.is_empty())
+            };
+            let number = |col: &BTreeMap<Vec<u32>, SnmpValueOwned>| col.get(index).and_then(SnmpValueOwned::as_i64);
+
+            let speed_bps = match (number(&speed), number(&high_speed)) {
+                (Some(bps), _) if bps < IF_SPEED_SATURATED => Some(bps),
+                (_, Some(mbps)) => Some(mbps * BITS_PER_MBIT),
+                (bps, None) => bps,
+            };
+
+            Some(Interface {
+                id: Uuid::nil(),
+                device_id,
+                if_index,
+                if_name: text(&name),
+                if_alias: text(&alias),
+                if_descr: text(&descr),
+                if_type: number(&if_type).map(if_type_name),
+                mac_address: phys_address.get(index).and_then(SnmpValueOwned::as_bytes).and_then(MacAddress::from_bytes),
+                ip_address: None,
+                admin_status: number(&admin_status).map(if_status_name),
+                oper_status: number(&oper_status).map(if_status_name),
+                speed: speed_bps,
+                mtu: number(&mtu).and_then(|m| i32::try_from(m).ok()),
+                access_vlan: None,
+                last_changed: None,
+                tags: Vec::new(),
+                custom_fields: CustomFieldValues::new(),
+                created_at: now,
+                updated_at: now,
+            })
+        })
+        .collect()
+}
+
+/// IANAifType names for the common types; others are stored as their number.
+fn if_type_name(value: i64) -> String {
+    match value {
+        1 => "other".to_string(),
+        6 => "ethernetCsmacd".to_string(),
+        24 => "softwareLoopback".to_string(),
+        53 => "propVirtual".to_string(),
+        71 => "ieee80211".to_string(),
+        131 => "tunnel".to_string(),
+        135 => "l2vlan".to_string(),
+        136 => "l3ipvlan".to_string(),
+        161 => "ieee8023adLag".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// ifAdminStatus / ifOperStatus textual conventions.
+fn if_status_name(value: i64) -> String {
+    match value {
+        1 => "up".to_string(),
+        2 => "down".to_string(),
+        3 => "testing".to_string(),
+        4 => "unknown".to_string(),
+        5 => "dormant".to_string(),
+        6 => "notPresent".to_string(),
+        7 => "lowerLayerDown".to_string(),
+        other => other.to_string(),
+    }
+}
@@ -0,0 +1,156 @@
+//! IP-MIB collector: interface addresses (ipAddrTable) and the ARP table (ipNetToMediaTable).
+
+use super::{column, column_by_index, ipv4_from_index};
+use db::{ArpEntry, Interface, MacAddress};
+use ipnetwork::{ipv4_mask_to_prefix, IpNetwork, Ipv4Network};
+use nd_core::{SnmpClient, SnmpError, SnmpValueOwned, VarBind};
+use std::collections::{BTreeMap, HashMap};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// IP-MIB::ipAddrEntry
+const IP_ADDR_ENTRY: [u32; 9] = [1, 3, 6, 1, 2, 1, 4, 20, 1];
+/// IP-MIB::ipNetToMediaEntry
+const IP_NET_TO_MEDIA_ENTRY: [u32; 9] = [1, 3, 6, 1, 2, 1, 4, 22, 1];
+
+const IP_AD_ENT_IF_INDEX: u32 = 2;
+const IP_AD_ENT_NET_MASK: u32 = 3;
+
+const IP_NET_TO_MEDIA_PHYS_ADDRESS: u32 = 2;
+const IP_NET_TO_MEDIA_TYPE: u32 = 4;
+
+/// ipNetToMediaType value of entries the agent has invalidated.
+const MEDIA_TYPE_INVALID: i64 = 2;
+
+/// Walks ipAddrTable and returns the address of each interface with its prefix length.
+/// An interface with several addresses keeps the lowest one.
+pub async fn collect_ip_addresses(client: &impl SnmpClient) -> Result<BTreeMap<i32, IpNetwork>, SnmpError> {
+    let rows = client.walk(&IP_ADDR_ENTRY).await?;
+    Ok(parse_ip_addresses(&rows))
+}
+
+/// Walks ipNetToMediaTable and returns the ARP entries, linked to `interfaces` by ifIndex.
+pub async fn collect_arp(
+    client: &impl SnmpClient,
+    device_id: Uuid,
+    interfaces: &[Interface],
+) -> Result<Vec<ArpEntry>, SnmpError> {
+    let rows = client.walk(&IP_NET_TO_MEDIA_ENTRY).await?;
+    Ok(parse_arp_entries(&rows, device_id, interfaces))
+}
+
+/// Maps walked ipAddrTable rows, indexed by the IPv4 address, to addresses by ifIndex.
+pub fn parse_ip_addresses(rows: &[VarBind]) -> BTreeMap<i32, IpNetwork> {
+    let if_index = column_by_index(rows, &column(&IP_ADDR_ENTRY, IP_AD_ENT_IF_INDEX));
+    let net_mask = column_by_index(rows, &column(&IP_ADDR_ENTRY, IP_AD_ENT_NET_MASK));
+
+    let mut addresses = BTreeMap::new();
+    // Rows come back ordered by address, so the first one per interface is the lowest
+    for (index, value) in &if_index {
+        let (Some(address), Some(if_index)) = (ipv4_from_index(index), value.as_i64()) else {
+            continue;
+        };
+        let prefix = net_mask
+            .get(index)
+            .and_then(SnmpValueOwned::as_ipv4)
+            .and_then(|mask| ipv4_mask_to_prefix(mask).ok())
+            .unwrap_or(32);
+        let (Ok(if_index), Ok(network)) = (i32::try_from(if_index), Ipv4Network::new(address, prefix)) else {
+            continue;
+        };
+        addresses.entry(if_index).or_insert(IpNetwork::V4(network));
+    }
+    addresses
+}
+
+/// Maps walked ipNetToMediaTable rows, indexed by ifIndex and the IPv4 address, to ARP
+/// entries. Invalidated and incomplete entries are skipped.
+pub fn parse_arp_entries(rows: &[VarBind], device_id: Uuid, interfaces: &[Interface]) -> Vec<ArpEntry> {
+    let phys_address = column_by_index(rows, &column(&IP_NET_TO_MEDIA_ENTRY, IP_NET_TO_MEDIA_PHYS_ADDRESS));
+    let media_type = column_by_index(rows, &column(&IP_NET_TO_MEDIA_ENTRY, IP_NET_TO_MEDIA_TYPE));
+    let interface_ids: HashMap<i32, Uuid> = interfaces.iter().map(|i| (i.if_index, i.id)).collect();
+
+    let now = OffsetDateTime::now_utc();
+    phys_address
+        .iter()
+        .filter(|(index, _)| media_type.get(*index).and_then(SnmpValueOwned::as_i64) != Some(MEDIA_TYPE_INVALID))
+        .filter_map(|(index, value)| {
+            let (if_index, address) = index.split_first()?;
+            let address = ipv4_from_index(address)?;
+            let mac_address = value.as_bytes().and_then(MacAddress::from_bytes).filter(|m| m.octets() != [0; 6])?;
+            Some(ArpEntry {
+                id: Uuid::nil(),
+                device_id,
+                interface_id: i32::try_from(*if_index).ok().and_then(|i| interface_ids.get(&i).copied()),
+                ip_address: IpNetwork::V4(Ipv4Network::from(address)),
+                mac_address,
+                first_seen: now,
+                last_seen: now,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(entry: &[u32], column_number: u32, index: &[u32], value: SnmpValueOwned) -> VarBind {
+        let mut oid = column(entry, column_number);
+        oid.extend_from_slice(index);
+        (oid, value)
+    }
+
+    #[test]
+    fn parses_addresses_and_arp_entries() {
+        let rows = vec![
+            row(&IP_ADDR_ENTRY, IP_AD_ENT_IF_INDEX, &[10, 1, 20, 1], SnmpValueOwned::Integer(3)),
+            row(&IP_ADDR_ENTRY, IP_AD_ENT_IF_INDEX, &[10, 1, 20, 2], SnmpValueOwned::Integer(3)),
+            row(&IP_ADDR_ENTRY, IP_AD_ENT_IF_INDEX, &[192, 0, 2, 1], SnmpValueOwned::Integer(7)),
+            row(&IP_ADDR_ENTRY, IP_AD_ENT_NET_MASK, &[10, 1, 20, 1], SnmpValueOwned::IpAddress([255, 255, 255, 0])),
+            row(&IP_ADDR_ENTRY, IP_AD_ENT_NET_MASK, &[10, 1, 20, 2], SnmpValueOwned::IpAddress([255, 255, 255, 0])),
+        ];
+        let addresses = parse_ip_addresses(&rows);
+        assert_eq!(addresses[&3], "10.1.20.1/24".parse::<IpNetwork>().unwrap());
+        assert_eq!(addresses[&7], "192.0.2.1/32".parse::<IpNetwork>().unwrap());
+
+        let mac = |last: u8| SnmpValueOwned::OctetString(vec![0x00, 0x1a, 0x2b, 0x3c, 0x4d, last]);
+        let rows = vec![
+            row(&IP_NET_TO_MEDIA_ENTRY, IP_NET_TO_MEDIA_PHYS_ADDRESS, &[3, 10, 1, 20, 5], mac(0x05)),
+            row(&IP_NET_TO_MEDIA_ENTRY, IP_NET_TO_MEDIA_PHYS_ADDRESS, &[3, 10, 1, 20, 6], mac(0x06)),
+            row(&IP_NET_TO_MEDIA_ENTRY, IP_NET_TO_MEDIA_PHYS_ADDRESS, &[9, 10, 1, 30, 7], mac(0x07)),
+            row(&IP_NET_TO_MEDIA_ENTRY, IP_NET_TO_MEDIA_PHYS_ADDRESS, &[3, 10, 1, 20, 8], SnmpValueOwned::OctetString(vec![0; 6])),
+            row(&IP_NET_TO_MEDIA_ENTRY, IP_NET_TO_MEDIA_TYPE, &[3, 10, 1, 20, 6], SnmpValueOwned::Integer(MEDIA_TYPE_INVALID)),
+        ];
+        let now = OffsetDateTime::now_utc();
+        let vlan = Interface {
+            id: Uuid::new_v4(),
+            device_id: Uuid::nil(),
+            if_index: 3,
+            if_name: Some("Vlan20".to_string()),
+            if_alias: None,
+            if_descr: None,
+            if_type: None,
+            mac_address: None,
+            ip_address: None,
+            admin_status: None,
+            oper_status: None,
+            speed: None,
+            mtu: None,
+            access_vlan: None,
+            last_changed: None,
+            tags: Vec::new(),
+            custom_fields: Default::default(),
+            created_at: now,
+            updated_at: now,
+        };
+
+        let entries = parse_arp_entries(&rows, Uuid::nil(), std::slice::from_ref(&vlan));
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].ip_address, "10.1.20.5/32".parse::<IpNetwork>().unwrap());
+        assert_eq!(entries[0].mac_address, "00:1a:2b:3c:4d:05".parse().unwrap());
+        assert_eq!(entries[0].interface_id, Some(vlan.id));
+        assert_eq!(entries[1].interface_id, None); // ifIndex 9 was not collected
+    }
+}
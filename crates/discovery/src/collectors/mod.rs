@@ -0,0 +1,57 @@
+//! SNMP collectors that gather per-device tables after a device has been identified.
+//!
+//! Each collector walks the MIB tables it needs and maps the rows into `db` models.
+//! Row parsing is kept separate from the walk so it can be tested against recorded data.
+
+pub mod fdb;
+pub mod interfaces;
+pub mod ip;
+pub mod neighbors;
+pub mod poe;
+pub mod routing;
+pub mod stp;
+pub mod system;
+pub mod wireless;
+
+use db::MacAddress;
+use nd_core::{oid_suffix, SnmpValueOwned, VarBind};
+use std::collections::BTreeMap;
+use std::net::Ipv4Addr;
+
+/// Groups walked rows of one table column by their index suffix.
+/// `column_oid` is the full OID of the column (table entry OID plus column number).
+pub(crate) fn column_by_index(rows: &[VarBind], column_oid: &[u32]) -> BTreeMap<Vec<u32>, SnmpValueOwned> {
+    rows.iter()
+        .filter_map(|(oid, value)| {
+            oid_suffix(oid, column_oid)
+                .filter(|index| !index.is_empty())
+                .map(|index| (index.to_vec(), value.clone()))
+        })
+        .collect()
+}
+
+/// Builds the OID of a table column from the table entry OID and column number.
+pub(crate) fn column(entry_oid: &[u32], column_number: u32) -> Vec<u32> {
+    let mut oid = entry_oid.to_vec();
+    oid.push(column_number);
+    oid
+}
+
+/// Reads a MAC address encoded as six sub-identifiers of a table index.
+pub(crate) fn mac_from_index(index: &[u32]) -> Option<MacAddress> {
+    let bytes: Option<Vec<u8>> = index.iter().map(|part| u8::try_from(*part).ok()).collect();
+    MacAddress::from_bytes(&bytes?)
+}
+
+/// Reads an IPv4 address from the first four sub-identifiers of a table index.
+pub(crate) fn ipv4_from_index(index: &[u32]) -> Option<Ipv4Addr> {
+    match index {
+        [a, b, c, d, ..] => Some(Ipv4Addr::new(
+            u8::try_from(*a).ok()?,
+            u8::try_from(*b).ok()?,
+            u8::try_from(*c).ok()?,
+            u8::try_from(*d).ok()?,
+        )),
+        _ => None,
+    }
+}
@@ -0,0 +1,283 @@
+//! Link layer neighbor collector (LLDP-MIB lldpRemTable, CISCO-CDP-MIB cdpCacheTable).
+
+use super::{column, column_by_index, ipv4_from_index};
+use db::{DeviceNeighbor, Interface, MacAddress, NeighborProtocol};
+use ipnetwork::IpNetwork;
+use nd_core::{SnmpClient, SnmpError, SnmpValueOwned, VarBind};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// LLDP-MIB::lldpRemEntry
+const LLDP_REM_ENTRY: [u32; 10] = [1, 0, 8802, 1, 1, 2, 1, 4, 1, 1];
+/// LLDP-MIB::lldpRemManAddrEntry
+const LLDP_REM_MAN_ADDR_ENTRY: [u32; 10] = [1, 0, 8802, 1, 1, 2, 1, 4, 2, 1];
+/// LLDP-MIB::lldpLocPortEntry
+const LLDP_LOC_PORT_ENTRY: [u32; 10] = [1, 0, 8802, 1, 1, 2, 1, 3, 7, 1];
+/// CISCO-CDP-MIB::cdpCacheEntry
+const CDP_CACHE_ENTRY: [u32; 13] = [1, 3, 6, 1, 4, 1, 9, 9, 23, 1, 2, 1, 1];
+
+const LLDP_REM_CHASSIS_ID_SUBTYPE: u32 = 4;
+const LLDP_REM_CHASSIS_ID: u32 = 5;
+const LLDP_REM_PORT_ID_SUBTYPE: u32 = 6;
+const LLDP_REM_PORT_ID: u32 = 7;
+const LLDP_REM_PORT_DESC: u32 = 8;
+const LLDP_REM_SYS_NAME: u32 = 9;
+const LLDP_REM_MAN_ADDR_IF_SUBTYPE: u32 = 3;
+const LLDP_LOC_PORT_ID: u32 = 3;
+const LLDP_LOC_PORT_DESC: u32 = 4;
+
+/// LldpChassisIdSubtype values carrying a MAC or a network address.
+const CHASSIS_ID_MAC_ADDRESS: i64 = 4;
+const CHASSIS_ID_NETWORK_ADDRESS: i64 = 5;
+/// LldpPortIdSubtype value carrying a MAC.
+const PORT_ID_MAC_ADDRESS: i64 = 3;
+/// IANA address family number of IPv4, as used in LLDP addresses.
+const ADDRESS_FAMILY_IPV4: u32 = 1;
+
+const CDP_CACHE_ADDRESS_TYPE: u32 = 3;
+const CDP_CACHE_ADDRESS: u32 = 4;
+const CDP_CACHE_DEVICE_ID: u32 = 6;
+const CDP_CACHE_DEVICE_PORT: u32 = 7;
+
+/// cdpCacheAddressType value of an IPv4 address.
+const CDP_ADDRESS_TYPE_IP: i64 = 1;
+
+/// Neighbors of both protocols as collected from one device.
+#[derive(Debug, Default)]
+pub struct Neighbors {
+    pub lldp: Vec<DeviceNeighbor>,
+    pub cdp: Vec<DeviceNeighbor>,
+}
+
+/// Walks the LLDP remote tables and the CDP cache, linking neighbors to `interfaces`.
+/// Devices that do not run a protocol simply return an empty table for it.
+pub async fn collect_neighbors(
+    client: &impl SnmpClient,
+    device_id: Uuid,
+    interfaces: &[Interface],
+) -> Result<Neighbors, SnmpError> {
+    let remote_rows = client.walk(&LLDP_REM_ENTRY).await?;
+    let address_rows = client.walk(&LLDP_REM_MAN_ADDR_ENTRY).await?;
+    let local_rows = client.walk(&LLDP_LOC_PORT_ENTRY).await?;
+    let cdp_rows = client.walk(&CDP_CACHE_ENTRY).await?;
+    Ok(Neighbors {
+        lldp: parse_lldp_neighbors(&remote_rows, &address_rows, &local_rows, device_id, interfaces),
+        cdp: parse_cdp_neighbors(&cdp_rows, device_id, interfaces),
+    })
+}
+
+/// Maps walked lldpRemTable rows, indexed by time mark, local port number and remote index,
+/// to neighbors. The local port is found through lldpLocPortTable by name, or else taken
+/// as the ifIndex equal to the port number, which is what most agents use.
+pub fn parse_lldp_neighbors(
+    remote_rows: &[VarBind],
+    address_rows: &[VarBind],
+    local_rows: &[VarBind],
+    device_id: Uuid,
+    interfaces: &[Interface],
+) -> Vec<DeviceNeighbor> {
+    let remote = |number| column_by_index(remote_rows, &column(&LLDP_REM_ENTRY, number));
+    let chassis_id_subtype = remote(LLDP_REM_CHASSIS_ID_SUBTYPE);
+    let chassis_id = remote(LLDP_REM_CHASSIS_ID);
+    let port_id_subtype = remote(LLDP_REM_PORT_ID_SUBTYPE);
+    let port_id = remote(LLDP_REM_PORT_ID);
+    let port_desc = remote(LLDP_REM_PORT_DESC);
+    let sys_name = remote(LLDP_REM_SYS_NAME);
+    let local = |number| column_by_index(local_rows, &column(&LLDP_LOC_PORT_ENTRY, number));
+    let local_port_id = local(LLDP_LOC_PORT_ID);
+    let local_port_desc = local(LLDP_LOC_PORT_DESC);
+
+    // First IPv4 management address of each remote; rows are indexed by the remote's index,
+    // the address family and the length-prefixed address
+    let mut addresses: BTreeMap<&[u32], Ipv4Addr> = BTreeMap::new();
+    let address_column = column_by_index(address_rows, &column(&LLDP_REM_MAN_ADDR_ENTRY, LLDP_REM_MAN_ADDR_IF_SUBTYPE));
+    for index in address_column.keys() {
+        if let [remote_index @ .., ADDRESS_FAMILY_IPV4, 4, a, b, c, d] = index.as_slice() {
+            if let Some(address) = ipv4_from_index(&[*a, *b, *c, *d]) {
+                addresses.entry(remote_index).or_insert(address);
+            }
+        }
+    }
+
+    let now = OffsetDateTime::now_utc();
+    let mut neighbors = Vec::new();
+    for (index, chassis) in &chassis_id {
+        let [_, local_port, _] = index.as_slice() else {
+            continue;
+        };
+        let chassis_subtype = chassis_id_subtype.get(index).and_then(SnmpValueOwned::as_i64);
+        let remote_chassis_id = match chassis_subtype {
+            Some(CHASSIS_ID_MAC_ADDRESS) => chassis.as_bytes().and_then(MacAddress::from_bytes).map(|m| m.to_string()),
+            _ => chassis.as_string(),
+        };
+        let remote_port = match port_id_subtype.get(index).and_then(SnmpValueOwned::as_i64) {
+            Some(PORT_ID_MAC_ADDRESS) => {
+                port_id.get(index).and_then(SnmpValueOwned::as_bytes).and_then(MacAddress::from_bytes).map(|m| m.to_string())
+            }
+            _ => port_id.get(index).and_then(SnmpValueOwned::as_string),
+        }
+        .filter(|port| !port.is_empty())
+        .or_else(|| port_desc.get(index).and_then(SnmpValueOwned::as_string));
+        let chassis_address = match (chassis_subtype, chassis.as_bytes()) {
+            // A network address starts with its IANA family
+            (Some(CHASSIS_ID_NETWORK_ADDRESS), Some([1, a, b, c, d])) => Some(Ipv4Addr::new(*a, *b, *c, *d)),
+            _ => None,
+        };
+        let remote_address = addresses.get(index.as_slice()).copied().or(chassis_address);
+
+        let local_port_key = vec![*local_port];
+        let local_names = [local_port_id.get(&local_port_key), local_port_desc.get(&local_port_key)];
+        let by_name = interfaces.iter().find(|interface| {
+            local_names.iter().flatten().filter_map(|value| value.as_string()).any(|name| {
+                [&interface.if_name, &interface.if_descr].into_iter().flatten().any(|n| n.eq_ignore_ascii_case(&name))
+            })
+        });
+        let interface_id = by_name
+            .or_else(|| interfaces.iter().find(|i| i64::from(i.if_index) == i64::from(*local_port)))
+            .map(|i| i.id);
+
+        neighbors.push(DeviceNeighbor {
+            id: Uuid::nil(),
+            device_id,
+            interface_id,
+            protocol: NeighborProtocol::Lldp,
+            remote_chassis_id,
+            remote_port,
+            remote_sys_name: sys_name.get(index).and_then(SnmpValueOwned::as_string).filter(|name| !name.is_empty()),
+            remote_address: remote_address.map(|ip| IpNetwork::from(IpAddr::V4(ip))),
+            last_seen: now,
+        });
+    }
+    neighbors
+}
+
+/// Maps walked cdpCacheTable rows, indexed by ifIndex and device index, to neighbors.
+/// Device IDs often end in the serial number in parentheses, which is dropped from the name.
+pub fn parse_cdp_neighbors(rows: &[VarBind], device_id: Uuid, interfaces: &[Interface]) -> Vec<DeviceNeighbor> {
+    let address_type = column_by_index(rows, &column(&CDP_CACHE_ENTRY, CDP_CACHE_ADDRESS_TYPE));
+    let address = column_by_index(rows, &column(&CDP_CACHE_ENTRY, CDP_CACHE_ADDRESS));
+    let remote_device_id = column_by_index(rows, &column(&CDP_CACHE_ENTRY, CDP_CACHE_DEVICE_ID));
+    let device_port = column_by_index(rows, &column(&CDP_CACHE_ENTRY, CDP_CACHE_DEVICE_PORT));
+
+    let now = OffsetDateTime::now_utc();
+    remote_device_id
+        .iter()
+        .filter_map(|(index, value)| {
+            let [if_index, _] = index.as_slice() else {
+                return None;
+            };
+            let remote_chassis_id = value.as_string().filter(|id| !id.is_empty())?;
+            let remote_sys_name = remote_chassis_id.split('(').next().unwrap_or_default().trim().to_string();
+            let remote_address = match address_type.get(index).and_then(SnmpValueOwned::as_i64) {
+                Some(CDP_ADDRESS_TYPE_IP) => address.get(index).and_then(SnmpValueOwned::as_ipv4),
+                _ => None,
+            };
+            Some(DeviceNeighbor {
+                id: Uuid::nil(),
+                device_id,
+                interface_id: interfaces.iter().find(|i| i64::from(i.if_index) == i64::from(*if_index)).map(|i| i.id),
+                protocol: NeighborProtocol::Cdp,
+                remote_port: device_port.get(index).and_then(SnmpValueOwned::as_string).filter(|port| !port.is_empty()),
+                remote_sys_name: Some(remote_sys_name).filter(|name| !name.is_empty()),
+                remote_chassis_id: Some(remote_chassis_id),
+                remote_address: remote_address.map(|ip| IpNetwork::from(IpAddr::V4(ip))),
+                last_seen: now,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(entry: &[u32], column_number: u32, index: &[u32], value: SnmpValueOwned) -> VarBind {
+        let mut oid = column(entry, column_number);
+        oid.extend_from_slice(index);
+        (oid, value)
+    }
+
+    fn text(value: &str) -> SnmpValueOwned {
+        SnmpValueOwned::OctetString(value.as_bytes().to_vec())
+    }
+
+    fn interface(if_index: i32, if_name: &str) -> Interface {
+        let now = OffsetDateTime::now_utc();
+        Interface {
+            id: Uuid::new_v4(),
+            device_id: Uuid::nil(),
+            if_index,
+            if_name: Some(if_name.to_string()),
+            if_alias: None,
+            if_descr: None,
+            if_type: None,
+            mac_address: None,
+            ip_address: None,
+            admin_status: None,
+            oper_status: None,
+            speed: None,
+            mtu: None,
+            access_vlan: None,
+            last_changed: None,
+            tags: Vec::new(),
+            custom_fields: Default::default(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn parses_lldp_and_cdp_neighbors() {
+        let interfaces = [interface(10101, "Gi1/0/1"), interface(10102, "Gi1/0/2")];
+        let remote = &LLDP_REM_ENTRY;
+        let local = &LLDP_LOC_PORT_ENTRY;
+        let mac = SnmpValueOwned::OctetString(vec![0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e]);
+        // Port 1 is named in lldpLocPortTable; port 10102 is only known by ifIndex
+        let remote_rows = vec![
+            row(remote, LLDP_REM_CHASSIS_ID_SUBTYPE, &[0, 1, 3], SnmpValueOwned::Integer(CHASSIS_ID_MAC_ADDRESS)),
+            row(remote, LLDP_REM_CHASSIS_ID_SUBTYPE, &[0, 10102, 4], SnmpValueOwned::Integer(7)),
+            row(remote, LLDP_REM_CHASSIS_ID, &[0, 1, 3], mac),
+            row(remote, LLDP_REM_CHASSIS_ID, &[0, 10102, 4], text("ap-17")),
+            row(remote, LLDP_REM_PORT_ID_SUBTYPE, &[0, 1, 3], SnmpValueOwned::Integer(5)),
+            row(remote, LLDP_REM_PORT_ID, &[0, 1, 3], text("Gi0/48")),
+            row(remote, LLDP_REM_PORT_DESC, &[0, 10102, 4], text("eth0")),
+            row(remote, LLDP_REM_SYS_NAME, &[0, 1, 3], text("core-1.example.net")),
+        ];
+        let address_rows = vec![row(
+            &LLDP_REM_MAN_ADDR_ENTRY,
+            LLDP_REM_MAN_ADDR_IF_SUBTYPE,
+            &[0, 1, 3, 1, 4, 10, 0, 0, 1],
+            SnmpValueOwned::Integer(2),
+        )];
+        let local_rows = vec![row(local, LLDP_LOC_PORT_ID, &[1], text("gi1/0/1"))];
+
+        let lldp = parse_lldp_neighbors(&remote_rows, &address_rows, &local_rows, Uuid::nil(), &interfaces);
+
+        assert_eq!(lldp.len(), 2);
+        assert_eq!(lldp[0].interface_id, Some(interfaces[0].id));
+        assert_eq!(lldp[0].remote_chassis_id.as_deref(), Some("00:1a:2b:3c:4d:5e"));
+        assert_eq!(lldp[0].remote_port.as_deref(), Some("Gi0/48"));
+        assert_eq!(lldp[0].remote_sys_name.as_deref(), Some("core-1.example.net"));
+        assert_eq!(lldp[0].remote_address, Some("10.0.0.1/32".parse().unwrap()));
+        assert_eq!(lldp[1].interface_id, Some(interfaces[1].id));
+        assert_eq!(lldp[1].remote_port.as_deref(), Some("eth0"));
+        assert_eq!(lldp[1].remote_sys_name, None);
+
+        let cdp_rows = vec![
+            row(&CDP_CACHE_ENTRY, CDP_CACHE_ADDRESS_TYPE, &[10102, 1], SnmpValueOwned::Integer(CDP_ADDRESS_TYPE_IP)),
+            row(&CDP_CACHE_ENTRY, CDP_CACHE_ADDRESS, &[10102, 1], SnmpValueOwned::OctetString(vec![10, 0, 0, 2])),
+            row(&CDP_CACHE_ENTRY, CDP_CACHE_DEVICE_ID, &[10102, 1], text("dist-2.example.net(FOC1234X0AB)")),
+            row(&CDP_CACHE_ENTRY, CDP_CACHE_DEVICE_PORT, &[10102, 1], text("GigabitEthernet1/0/24")),
+        ];
+
+        let cdp = parse_cdp_neighbors(&cdp_rows, Uuid::nil(), &interfaces);
+
+        assert_eq!(cdp.len(), 1);
+        assert_eq!(cdp[0].interface_id, Some(interfaces[1].id));
+        assert_eq!(cdp[0].remote_sys_name.as_deref(), Some("dist-2.example.net"));
+        assert_eq!(cdp[0].remote_port.as_deref(), Some("GigabitEthernet1/0/24"));
+        assert_eq!(cdp[0].remote_address, Some("10.0.0.2/32".parse().unwrap()));
+    }
+}
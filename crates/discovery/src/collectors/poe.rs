@@ -0,0 +1,189 @@
+//! Power over Ethernet collector (POWER-ETHERNET-MIB pethPsePortTable).
+//!
+//! PSE ports are indexed by (group, port) rather than ifIndex. Groups are
+//! usually stack members, so ports are matched to interfaces by name
+//! (`Gi1/0/7`, `1/7`), falling back to ifIndex == port on single-unit agents.
+
+use super::{column, column_by_index};
+use db::{Interface, InterfacePoe};
+use nd_core::{SnmpClient, SnmpError, SnmpValueOwned, VarBind};
+use std::collections::BTreeMap;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// POWER-ETHERNET-MIB::pethPsePortEntry
+pub(crate) const PETH_PSE_PORT_ENTRY: [u32; 10] = [1, 3, 6, 1, 2, 1, 105, 1, 1, 1];
+pub(crate) const PSE_PORT_ADMIN_ENABLE: u32 = 3;
+const PSE_PORT_DETECTION_STATUS: u32 = 6;
+const PSE_PORT_POWER_CLASSIFICATIONS: u32 = 10;
+/// CISCO-POWER-ETHERNET-EXT-MIB::cpeExtPsePortPwrConsumption (milliwatts)
+const CPE_EXT_PSE_PORT_PWR_CONSUMPTION: [u32; 14] = [1, 3, 6, 1, 4, 1, 9, 9, 402, 1, 2, 1, 1, 9];
+
+/// TruthValue encoding of pethPsePortAdminEnable
+pub(crate) const TRUTH_TRUE: i64 = 1;
+pub(crate) const TRUTH_FALSE: i64 = 2;
+
+/// Walks the PSE port table and maps each port to one of `interfaces`.
+/// Agents without POWER-ETHERNET-MIB return an empty list.
+pub async fn collect_poe(client: &impl SnmpClient, interfaces: &[Interface]) -> Result<Vec<InterfacePoe>, SnmpError> {
+    let port_rows = client.walk(&PETH_PSE_PORT_ENTRY).await?;
+    if port_rows.is_empty() {
+        return Ok(Vec::new());
+    }
+    // Consumption is vendor specific; only Cisco reports it per port
+    let consumption_rows = client.walk(&CPE_EXT_PSE_PORT_PWR_CONSUMPTION).await.unwrap_or_default();
+    Ok(parse_poe(&port_rows, &consumption_rows, interfaces))
+}
+
+/// Maps walked pethPsePortTable rows (and optional Cisco consumption rows) to
+/// per-interface PoE state. Ports that match no interface are dropped.
+pub fn parse_poe(port_rows: &[VarBind], consumption_rows: &[VarBind], interfaces: &[Interface]) -> Vec<InterfacePoe> {
+    let admin = column_by_index(port_rows, &column(&PETH_PSE_PORT_ENTRY, PSE_PORT_ADMIN_ENABLE));
+    let detection = column_by_index(port_rows, &column(&PETH_PSE_PORT_ENTRY, PSE_PORT_DETECTION_STATUS));
+    let class = column_by_index(port_rows, &column(&PETH_PSE_PORT_ENTRY, PSE_PORT_POWER_CLASSIFICATIONS));
+    let consumption = column_by_index(consumption_rows, &CPE_EXT_PSE_PORT_PWR_CONSUMPTION);
+
+    let now = OffsetDateTime::now_utc();
+    admin
+        .iter()
+        .filter_map(|(index, admin_value)| {
+            let (group, port) = match index.as_slice() {
+                [group, port] => (*group, *port),
+                _ => return None,
+            };
+            let Some(if_index) = match_interface(interfaces, group, port) else {
+                tracing::debug!(group, port, "PSE port matches no interface");
+                return None;
+            };
+            let number = |col: &BTreeMap<Vec<u32>, SnmpValueOwned>| {
+                col.get(index).and_then(SnmpValueOwned::as_i64)
+            };
+
+            Some(InterfacePoe {
+                interface_id: Uuid::nil(),
+                if_index,
+                group_index: i32::try_from(group).ok()?,
+                port_index: i32::try_from(port).ok()?,
+                admin_enabled: admin_value.as_i64() == Some(TRUTH_TRUE),
+                detection_status: number(&detection).map(detection_status_name),
+                power_class: number(&class).and_then(power_class_name),
+                power_mw: number(&consumption).and_then(|mw| i32::try_from(mw).ok()),
+                last_seen: now,
+                created_at: now,
+                updated_at: now,
+            })
+        })
+        .collect()
+}
+
+/// Finds the ifIndex of the interface named like `<prefix>group/0/port` or
+/// `<prefix>group/port`; single-group agents may instead number PSE ports by ifIndex.
+fn match_interface(interfaces: &[Interface], group: u32, port: u32) -> Option<i32> {
+    let suffixes = [format!("{}/0/{}", group, port), format!("{}/{}", group, port)];
+    let by_name = interfaces.iter().find(|interface| {
+        [&interface.if_name, &interface.if_descr].into_iter().flatten().any(|name| {
+            suffixes.iter().any(|suffix| {
+                name.strip_suffix(suffix.as_str())
+                    .is_some_and(|prefix| !prefix.ends_with(|c: char| c.is_ascii_digit() || c == '/'))
+            })
+        })
+    });
+    if let Some(interface) = by_name {
+        return Some(interface.if_index);
+    }
+    let port = i32::try_from(port).ok()?;
+    interfaces.iter().find(|interface| interface.if_index == port).map(|interface| interface.if_index)
+}
+
+/// pethPsePortDetectionStatus enumeration.
+fn detection_status_name(value: i64) -> String {
+    match value {
+        1 => "disabled".to_string(),
+        2 => "searching".to_string(),
+        3 => "deliveringPower".to_string(),
+        4 => "fault".to_string(),
+        5 => "test".to_string(),
+        6 => "otherFault".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// pethPsePortPowerClassifications: class0(1) .. class4(5).
+fn power_class_name(value: i64) -> Option<String> {
+    (1..=5).contains(&value).then(|| format!("class{}", value - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use db::CustomFieldValues;
+
+    fn interface(if_index: i32, name: &str) -> Interface {
+        let now = OffsetDateTime::now_utc();
+        Interface {
+            id: Uuid::nil(),
+            device_id: Uuid::nil(),
+            if_index,
+            if_name: Some(name.to_string()),
+            if_alias: None,
+            if_descr: None,
+            if_type: None,
+            mac_address: None,
+            ip_address: None,
+            admin_status: None,
+            oper_status: None,
+            speed: None,
+            mtu: None,
+            access_vlan: None,
+            last_changed: None,
+            tags: Vec::new(),
+            custom_fields: CustomFieldValues::new(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn row(column_oid: &[u32], index: [u32; 2], value: i64) -> VarBind {
+        ([column_oid, &index[..]].concat(), SnmpValueOwned::Integer(value))
+    }
+
+    #[test]
+    fn maps_stacked_ports_by_name() {
+        let interfaces = vec![interface(10107, "Gi1/0/7"), interface(10207, "Gi2/0/7"), interface(10217, "Gi2/0/17")];
+        let admin_col = column(&PETH_PSE_PORT_ENTRY, PSE_PORT_ADMIN_ENABLE);
+        let detection_col = column(&PETH_PSE_PORT_ENTRY, PSE_PORT_DETECTION_STATUS);
+        let class_col = column(&PETH_PSE_PORT_ENTRY, PSE_PORT_POWER_CLASSIFICATIONS);
+        let port_rows = vec![
+            row(&admin_col, [1, 7], TRUTH_TRUE),
+            row(&admin_col, [2, 7], TRUTH_FALSE),
+            row(&detection_col, [1, 7], 3),
+            row(&detection_col, [2, 7], 1),
+            row(&class_col, [1, 7], 4),
+        ];
+        let consumption_rows = vec![row(&CPE_EXT_PSE_PORT_PWR_CONSUMPTION, [1, 7], 6500)];
+
+        let poe = parse_poe(&port_rows, &consumption_rows, &interfaces);
+
+        assert_eq!(poe.len(), 2);
+        assert_eq!(poe[0].if_index, 10107);
+        assert!(poe[0].admin_enabled);
+        assert_eq!(poe[0].detection_status.as_deref(), Some("deliveringPower"));
+        assert_eq!(poe[0].power_class.as_deref(), Some("class3"));
+        assert_eq!(poe[0].power_mw, Some(6500));
+        // 2/0/17 must not be taken for 2/0/7
+        assert_eq!(poe[1].if_index, 10207);
+        assert!(!poe[1].admin_enabled);
+        assert_eq!(poe[1].power_mw, None);
+    }
+
+    #[test]
+    fn falls_back_to_if_index() {
+        let interfaces = vec![interface(3, "port3")];
+        let admin_col = column(&PETH_PSE_PORT_ENTRY, PSE_PORT_ADMIN_ENABLE);
+        let port_rows = vec![row(&admin_col, [1, 3], TRUTH_TRUE), row(&admin_col, [1, 4], TRUTH_TRUE)];
+
+        let poe = parse_poe(&port_rows, &[], &interfaces);
+
+        assert_eq!(poe.iter().map(|p| p.if_index).collect::<Vec<_>>(), vec![3]);
+    }
+}
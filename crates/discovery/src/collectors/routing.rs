@@ -0,0 +1,209 @@
+//! Routing protocol neighbor collector (BGP4-MIB bgpPeerTable, OSPF-MIB ospfNbrTable).
+
+use super::{column, column_by_index, ipv4_from_index};
+use db::{RoutingNeighbor, RoutingProtocol};
+use ipnetwork::IpNetwork;
+use nd_core::{SnmpClient, SnmpError, SnmpValueOwned, VarBind};
+use std::net::{IpAddr, Ipv4Addr};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// BGP4-MIB::bgpPeerEntry
+const BGP_PEER_ENTRY: [u32; 9] = [1, 3, 6, 1, 2, 1, 15, 3, 1];
+/// OSPF-MIB::ospfNbrEntry
+const OSPF_NBR_ENTRY: [u32; 9] = [1, 3, 6, 1, 2, 1, 14, 10, 1];
+
+const BGP_PEER_IDENTIFIER: u32 = 1;
+const BGP_PEER_STATE: u32 = 2;
+const BGP_PEER_REMOTE_ADDR: u32 = 7;
+const BGP_PEER_REMOTE_AS: u32 = 9;
+const BGP_PEER_FSM_ESTABLISHED_TIME: u32 = 16;
+
+const OSPF_NBR_IP_ADDR: u32 = 1;
+const OSPF_NBR_RTR_ID: u32 = 3;
+const OSPF_NBR_STATE: u32 = 6;
+
+/// Routing neighbors of both protocols as collected from one device.
+/// Each table is walked on its own, so one failing leaves the other's result intact.
+#[derive(Debug)]
+pub struct RoutingNeighbors {
+    pub bgp: Result<Vec<RoutingNeighbor>, SnmpError>,
+    pub ospf: Result<Vec<RoutingNeighbor>, SnmpError>,
+}
+
+/// Walks the BGP peer and OSPF neighbor tables on the target.
+/// Devices that do not run a protocol simply return an empty table for it.
+pub async fn collect_routing_neighbors(client: &impl SnmpClient, device_id: Uuid) -> RoutingNeighbors {
+    let bgp = client.walk(&BGP_PEER_ENTRY).await.map(|rows| parse_bgp_peers(&rows, device_id));
+    let ospf = client.walk(&OSPF_NBR_ENTRY).await.map(|rows| parse_ospf_neighbors(&rows, device_id));
+    RoutingNeighbors { bgp, ospf }
+}
+
+/// Maps walked bgpPeerTable rows to neighbors. Rows are indexed by the peer's IPv4 address.
+pub fn parse_bgp_peers(rows: &[VarBind], device_id: Uuid) -> Vec<RoutingNeighbor> {
+    let identifier = column_by_index(rows, &column(&BGP_PEER_ENTRY, BGP_PEER_IDENTIFIER));
+    let state = column_by_index(rows, &column(&BGP_PEER_ENTRY, BGP_PEER_STATE));
+    let remote_addr = column_by_index(rows, &column(&BGP_PEER_ENTRY, BGP_PEER_REMOTE_ADDR));
+    let remote_as = column_by_index(rows, &column(&BGP_PEER_ENTRY, BGP_PEER_REMOTE_AS));
+    let established_time = column_by_index(rows, &column(&BGP_PEER_ENTRY, BGP_PEER_FSM_ESTABLISHED_TIME));
+
+    let now = OffsetDateTime::now_utc();
+    state
+        .iter()
+        .filter_map(|(index, state_value)| {
+            let neighbor_address = remote_addr
+                .get(index)
+                .and_then(SnmpValueOwned::as_ipv4)
+                .or_else(|| ipv4_from_index(index))?;
+            let state_name = bgp_state_name(state_value.as_i64()?);
+            // The established timer keeps counting from the last session; it is only an uptime while up
+            let uptime_seconds = if state_name == "established" {
+                established_time.get(index).and_then(SnmpValueOwned::as_i64)
+            } else {
+                None
+            };
+            Some(RoutingNeighbor {
+                id: Uuid::nil(),
+                device_id,
+                protocol: RoutingProtocol::Bgp,
+                neighbor_address: host_network(neighbor_address),
+                remote_router_id: identifier
+                    .get(index)
+                    .and_then(SnmpValueOwned::as_ipv4)
+                    .filter(|id| !id.is_unspecified())
+                    .map(host_network),
+                remote_as: remote_as.get(index).and_then(SnmpValueOwned::as_i64),
+                state: state_name,
+                uptime_seconds,
+                last_seen: now,
+                created_at: now,
+                updated_at: now,
+            })
+        })
+        .collect()
+}
+
+/// Maps walked ospfNbrTable rows to neighbors. Rows are indexed by
+/// ospfNbrIpAddr followed by ospfNbrAddressLessIndex.
+pub fn parse_ospf_neighbors(rows: &[VarBind], device_id: Uuid) -> Vec<RoutingNeighbor> {
+    let ip_addr = column_by_index(rows, &column(&OSPF_NBR_ENTRY, OSPF_NBR_IP_ADDR));
+    let router_id = column_by_index(rows, &column(&OSPF_NBR_ENTRY, OSPF_NBR_RTR_ID));
+    let state = column_by_index(rows, &column(&OSPF_NBR_ENTRY, OSPF_NBR_STATE));
+
+    let now = OffsetDateTime::now_utc();
+    state
+        .iter()
+        .filter_map(|(index, state_value)| {
+            let neighbor_address = ip_addr
+                .get(index)
+                .and_then(SnmpValueOwned::as_ipv4)
+                .or_else(|| ipv4_from_index(index))?;
+            Some(RoutingNeighbor {
+                id: Uuid::nil(),
+                device_id,
+                protocol: RoutingProtocol::Ospf,
+                neighbor_address: host_network(neighbor_address),
+                remote_router_id: router_id.get(index).and_then(SnmpValueOwned::as_ipv4).map(host_network),
+                remote_as: None,
+                state: ospf_state_name(state_value.as_i64()?),
+                uptime_seconds: None,
+                last_seen: now,
+                created_at: now,
+                updated_at: now,
+            })
+        })
+        .collect()
+}
+
+fn host_network(address: Ipv4Addr) -> IpNetwork {
+    IpNetwork::from(IpAddr::V4(address))
+}
+
+/// BGP4-MIB bgpPeerState values.
+fn bgp_state_name(value: i64) -> String {
+    match value {
+        1 => "idle".to_string(),
+        2 => "connect".to_string(),
+        3 => "active".to_string(),
+        4 => "openSent".to_string(),
+        5 => "openConfirm".to_string(),
+        6 => "established".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// OSPF-MIB ospfNbrState values.
+fn ospf_state_name(value: i64) -> String {
+    match value {
+        1 => "down".to_string(),
+        2 => "attempt".to_string(),
+        3 => "init".to_string(),
+        4 => "twoWay".to_string(),
+        5 => "exchangeStart".to_string(),
+        6 => "exchange".to_string(),
+        7 => "loading".to_string(),
+        8 => "full".to_string(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(entry: &[u32], column_number: u32, index: &[u32], value: SnmpValueOwned) -> VarBind {
+        let mut oid = column(entry, column_number);
+        oid.extend_from_slice(index);
+        (oid, value)
+    }
+
+    #[test]
+    fn parses_bgp_peers() {
+        let peer_up = [192, 0, 2, 1];
+        let peer_down = [198, 51, 100, 7];
+        let rows = vec![
+            row(&BGP_PEER_ENTRY, BGP_PEER_IDENTIFIER, &peer_up, SnmpValueOwned::IpAddress([10, 0, 0, 1])),
+            row(&BGP_PEER_ENTRY, BGP_PEER_IDENTIFIER, &peer_down, SnmpValueOwned::IpAddress([0, 0, 0, 0])),
+            row(&BGP_PEER_ENTRY, BGP_PEER_STATE, &peer_up, SnmpValueOwned::Integer(6)),
+            row(&BGP_PEER_ENTRY, BGP_PEER_STATE, &peer_down, SnmpValueOwned::Integer(3)),
+            row(&BGP_PEER_ENTRY, BGP_PEER_REMOTE_AS, &peer_up, SnmpValueOwned::Gauge32(4_200_000_001)),
+            row(&BGP_PEER_ENTRY, BGP_PEER_REMOTE_AS, &peer_down, SnmpValueOwned::Integer(65010)),
+            row(&BGP_PEER_ENTRY, BGP_PEER_FSM_ESTABLISHED_TIME, &peer_up, SnmpValueOwned::Gauge32(86_400)),
+            row(&BGP_PEER_ENTRY, BGP_PEER_FSM_ESTABLISHED_TIME, &peer_down, SnmpValueOwned::Gauge32(120)),
+        ];
+
+        let peers = parse_bgp_peers(&rows, Uuid::nil());
+
+        assert_eq!(peers.len(), 2);
+        let up = &peers[0];
+        assert_eq!(up.neighbor_address, host_network(Ipv4Addr::new(192, 0, 2, 1)));
+        assert_eq!(up.remote_router_id, Some(host_network(Ipv4Addr::new(10, 0, 0, 1))));
+        assert_eq!(up.remote_as, Some(4_200_000_001));
+        assert_eq!(up.state, "established");
+        assert_eq!(up.uptime_seconds, Some(86_400));
+
+        let down = &peers[1];
+        assert_eq!(down.state, "active");
+        assert_eq!(down.remote_router_id, None);
+        assert_eq!(down.uptime_seconds, None);
+    }
+
+    #[test]
+    fn parses_ospf_neighbors() {
+        let index = [10, 1, 1, 2, 0];
+        let rows = vec![
+            row(&OSPF_NBR_ENTRY, OSPF_NBR_IP_ADDR, &index, SnmpValueOwned::IpAddress([10, 1, 1, 2])),
+            row(&OSPF_NBR_ENTRY, OSPF_NBR_RTR_ID, &index, SnmpValueOwned::IpAddress([10, 255, 0, 2])),
+            row(&OSPF_NBR_ENTRY, OSPF_NBR_STATE, &index, SnmpValueOwned::Integer(8)),
+        ];
+
+        let neighbors = parse_ospf_neighbors(&rows, Uuid::nil());
+
+        assert_eq!(neighbors.len(), 1);
+        assert_eq!(neighbors[0].protocol, RoutingProtocol::Ospf);
+        assert_eq!(neighbors[0].neighbor_address, host_network(Ipv4Addr::new(10, 1, 1, 2)));
+        assert_eq!(neighbors[0].remote_router_id, Some(host_network(Ipv4Addr::new(10, 255, 0, 2))));
+        assert_eq!(neighbors[0].state, "full");
+        assert_eq!(neighbors[0].remote_as, None);
+    }
+}
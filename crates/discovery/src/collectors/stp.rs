@@ -0,0 +1,529 @@
+//! Spanning tree collector (BRIDGE-MIB dot1dBase and dot1dStp, IEEE8021-MSTP-MIB).
+//!
+//! Cisco switches run one tree per VLAN and expose each through community
+//! string indexing (`community@vlan`); the VLAN list comes from CISCO-VTP-MIB.
+//! Other agents report a single tree, stored as instance 0, and MSTP bridges
+//! add their MST instances, with the VLANs mapped to each, from IEEE8021-MSTP-MIB.
+//!
+//! Port roles are read from IEEE8021-MSTP-MIB where the agent has it. BRIDGE-MIB
+//! has no role column, so other agents only get the roles the MIB states
+//! outright: `root` for dot1dStpRootPort and `disabled` for disabled ports.
+
+use super::{column, column_by_index};
+use db::{StpInstance, StpPort};
+use nd_core::{oid_suffix, SnmpClient, SnmpError, SnmpValueOwned, VarBind};
+use std::collections::BTreeMap;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// BRIDGE-MIB::dot1dBase
+const DOT1D_BASE: [u32; 8] = [1, 3, 6, 1, 2, 1, 17, 1];
+/// BRIDGE-MIB::dot1dStp
+const DOT1D_STP: [u32; 8] = [1, 3, 6, 1, 2, 1, 17, 2];
+/// CISCO-VTP-MIB::vtpVlanState
+pub(crate) const VTP_VLAN_STATE: [u32; 14] = [1, 3, 6, 1, 4, 1, 9, 9, 46, 1, 3, 1, 1, 2];
+
+/// dot1dBaseBridgeAddress.0 relative to dot1dBase
+const BASE_BRIDGE_ADDRESS: [u32; 2] = [1, 0];
+/// dot1dBasePortEntry relative to dot1dBase
+const BASE_PORT_ENTRY: [u32; 2] = [4, 1];
+const BASE_PORT_IF_INDEX: u32 = 2;
+
+/// Scalars relative to dot1dStp
+const STP_PRIORITY: [u32; 2] = [2, 0];
+const STP_DESIGNATED_ROOT: [u32; 2] = [5, 0];
+const STP_ROOT_COST: [u32; 2] = [6, 0];
+const STP_ROOT_PORT: [u32; 2] = [7, 0];
+/// dot1dStpPortEntry relative to dot1dStp
+const STP_PORT_ENTRY: [u32; 2] = [15, 1];
+const STP_PORT_STATE: u32 = 3;
+const STP_PORT_PATH_COST: u32 = 5;
+const STP_PORT_DESIGNATED_BRIDGE: u32 = 8;
+
+/// IEEE8021-MSTP-MIB::ieee8021MstpEntry (one row per MST instance)
+const MSTP_ENTRY: [u32; 11] = [1, 3, 111, 2, 802, 1, 1, 6, 1, 2, 1];
+const MSTP_BRIDGE_ID: u32 = 3;
+const MSTP_DESIGNATED_ROOT: u32 = 7;
+const MSTP_ROOT_PATH_COST: u32 = 8;
+const MSTP_ROOT_PORT: u32 = 9;
+/// ieee8021MstpVids0..3: VLANs 0-1023, 1024-2047, 2048-3071 and 3072-4095
+const MSTP_VIDS: [u32; 4] = [11, 12, 13, 14];
+/// IEEE8021-MSTP-MIB::ieee8021MstpCistPortEntry (ports of the CIST, instance 0)
+const MSTP_CIST_PORT_ENTRY: [u32; 11] = [1, 3, 111, 2, 802, 1, 1, 6, 1, 3, 1];
+const MSTP_CIST_PORT_ROLE: u32 = 14;
+/// IEEE8021-MSTP-MIB::ieee8021MstpPortEntry (ports of each MST instance)
+const MSTP_PORT_ENTRY: [u32; 11] = [1, 3, 111, 2, 802, 1, 1, 6, 1, 4, 1];
+const MSTP_PORT_STATE: u32 = 5;
+const MSTP_PORT_PATH_COST: u32 = 7;
+const MSTP_PORT_DESIGNATED_BRIDGE: u32 = 10;
+const MSTP_PORT_ROLE: u32 = 12;
+
+const VTP_VLAN_OPERATIONAL: i64 = 1;
+/// FDDI/Token Ring default VLANs that never carry a spanning tree of interest
+const RESERVED_VLANS: std::ops::RangeInclusive<u32> = 1002..=1005;
+const DEFAULT_INSTANCE: i32 = 0;
+
+/// Stored as `StpInstance::protocol`
+const PROTOCOL_STP: &str = "stp";
+const PROTOCOL_PVST: &str = "pvst";
+const PROTOCOL_MST: &str = "mst";
+
+/// Spanning tree state collected from one device.
+#[derive(Debug, Default)]
+pub struct StpCollection {
+    pub instances: Vec<StpInstance>,
+    pub ports: Vec<StpPort>,
+}
+
+/// Walks the bridge and spanning tree tables on the target, once per VLAN on
+/// Cisco switches. Devices without spanning tree return an empty collection.
+pub async fn collect_stp(client: &impl SnmpClient, device_id: Uuid) -> Result<StpCollection, SnmpError> {
+    let vlan_rows = client.walk(&VTP_VLAN_STATE).await?;
+    let vlans = parse_vtp_vlans(&vlan_rows);
+
+    let mut collection = StpCollection::default();
+    if vlans.is_empty() {
+        let base_rows = client.walk(&DOT1D_BASE).await?;
+        let stp_rows = client.walk(&DOT1D_STP).await?;
+        let cist_port_rows = client.walk(&MSTP_CIST_PORT_ENTRY).await?;
+        collection.extend(parse_stp(&base_rows, &stp_rows, &cist_port_rows, DEFAULT_INSTANCE, device_id));
+        let mstp_rows = client.walk(&MSTP_ENTRY).await?;
+        if !mstp_rows.is_empty() {
+            let mstp_port_rows = client.walk(&MSTP_PORT_ENTRY).await?;
+            collection.extend(parse_mstp(&base_rows, &mstp_rows, &mstp_port_rows, device_id));
+        }
+        return Ok(collection);
+    }
+
+    for vlan in vlans {
+        let vlan_client = client.with_community_index(&vlan.to_string());
+        let walk = async {
+            let base_rows = vlan_client.walk(&DOT1D_BASE).await?;
+            let stp_rows = vlan_client.walk(&DOT1D_STP).await?;
+            Ok::<_, SnmpError>((base_rows, stp_rows))
+        };
+        match walk.await {
+            Ok((base_rows, stp_rows)) => {
+                collection.extend(parse_stp(&base_rows, &stp_rows, &[], vlan as i32, device_id));
+            }
+            Err(e) => {
+                tracing::warn!(%device_id, vlan, error = %e, "Per-VLAN spanning tree walk failed");
+            }
+        }
+    }
+    Ok(collection)
+}
+
+impl StpCollection {
+    fn extend(&mut self, other: StpCollection) {
+        self.instances.extend(other.instances);
+        self.ports.extend(other.ports);
+    }
+}
+
+/// Returns the operational VLANs from a vtpVlanState walk (index: domain, VLAN).
+pub fn parse_vtp_vlans(rows: &[VarBind]) -> Vec<u32> {
+    let mut vlans: Vec<u32> = rows
+        .iter()
+        .filter_map(|(oid, value)| match oid_suffix(oid, &VTP_VLAN_STATE)? {
+            [_domain, vlan] if value.as_i64() == Some(VTP_VLAN_OPERATIONAL) => Some(*vlan),
+            _ => None,
+        })
+        .filter(|vlan| !RESERVED_VLANS.contains(vlan))
+        .collect();
+    vlans.sort_unstable();
+    vlans.dedup();
+    vlans
+}
+
+/// Maps walked dot1dBase and dot1dStp subtrees to one spanning tree instance, with port
+/// roles from a walk of ieee8021MstpCistPortEntry when the agent has one.
+/// Returns an empty collection when the agent does not report a designated root.
+pub fn parse_stp(
+    base_rows: &[VarBind],
+    stp_rows: &[VarBind],
+    cist_port_rows: &[VarBind],
+    instance: i32,
+    device_id: Uuid,
+) -> StpCollection {
+    let scalar = |rows: &[VarBind], base: &[u32], relative: &[u32]| {
+        rows.iter()
+            .find(|(oid, _)| oid_suffix(oid, base) == Some(relative))
+            .map(|(_, value)| value.clone())
+    };
+
+    let designated_root = match scalar(stp_rows, &DOT1D_STP, &STP_DESIGNATED_ROOT)
+        .as_ref()
+        .and_then(SnmpValueOwned::as_bytes)
+        .and_then(format_bridge_id)
+    {
+        Some(root) => root,
+        None => return StpCollection::default(),
+    };
+    let bridge_id = scalar(base_rows, &DOT1D_BASE, &BASE_BRIDGE_ADDRESS)
+        .as_ref()
+        .and_then(SnmpValueOwned::as_bytes)
+        .and_then(|mac| {
+            let priority = scalar(stp_rows, &DOT1D_STP, &STP_PRIORITY)?.as_i64()?;
+            let mut id = u16::try_from(priority).ok()?.to_be_bytes().to_vec();
+            id.extend_from_slice(mac);
+            format_bridge_id(&id)
+        })
+        .unwrap_or_default();
+
+    let port_if_index = port_if_indexes(base_rows);
+    let cist_roles = port_roles(cist_port_rows, &MSTP_CIST_PORT_ENTRY, MSTP_CIST_PORT_ROLE);
+    let root_port = scalar(stp_rows, &DOT1D_STP, &STP_ROOT_PORT)
+        .and_then(|value| value.as_i64())
+        .filter(|port| *port != 0)
+        .and_then(|port| u32::try_from(port).ok());
+
+    let now = OffsetDateTime::now_utc();
+    let stp_port_entry = [&DOT1D_STP[..], &STP_PORT_ENTRY[..]].concat();
+    let state = column_by_index(stp_rows, &column(&stp_port_entry, STP_PORT_STATE));
+    let path_cost = column_by_index(stp_rows, &column(&stp_port_entry, STP_PORT_PATH_COST));
+    let designated_bridge = column_by_index(stp_rows, &column(&stp_port_entry, STP_PORT_DESIGNATED_BRIDGE));
+
+    let ports = state
+        .iter()
+        .filter_map(|(port, state_value)| {
+            let if_index = *port_if_index.get(port)?;
+            let state_name = port_state_name(state_value.as_i64()?);
+            let port_designated_bridge = designated_bridge
+                .get(port)
+                .and_then(SnmpValueOwned::as_bytes)
+                .and_then(format_bridge_id);
+            let is_root_port = root_port.is_some_and(|root| port.as_slice() == [root]);
+            let role = port_role(&state_name, is_root_port, port.first().and_then(|port| cist_roles.get(port)));
+            Some(StpPort {
+                id: Uuid::nil(),
+                interface_id: Uuid::nil(),
+                if_index,
+                instance,
+                state: state_name,
+                role,
+                path_cost: path_cost.get(port).and_then(SnmpValueOwned::as_i64).and_then(|c| i32::try_from(c).ok()),
+                designated_bridge: port_designated_bridge,
+                last_seen: now,
+                created_at: now,
+                updated_at: now,
+            })
+        })
+        .collect();
+
+    let (protocol, vlans) = match instance {
+        DEFAULT_INSTANCE => (PROTOCOL_STP, Vec::new()),
+        vlan => (PROTOCOL_PVST, vec![vlan]),
+    };
+    StpCollection {
+        instances: vec![StpInstance {
+            id: Uuid::nil(),
+            device_id,
+            instance,
+            protocol: protocol.to_string(),
+            vlans,
+            bridge_id,
+            designated_root,
+            root_cost: scalar(stp_rows, &DOT1D_STP, &STP_ROOT_COST)
+                .and_then(|value| value.as_i64())
+                .and_then(|c| i32::try_from(c).ok()),
+            root_port_if_index: root_port.and_then(|port| port_if_index.get(&vec![port]).copied()),
+            root_device_id: None,
+            last_seen: now,
+            created_at: now,
+            updated_at: now,
+        }],
+        ports,
+    }
+}
+
+/// Maps walked ieee8021MstpEntry and ieee8021MstpPortEntry columns to the MST instances of
+/// a bridge (the CIST, instance 0, comes from BRIDGE-MIB). Rows are indexed by component,
+/// instance and port; only the first component is read. Bridge port numbers are mapped
+/// to ifIndex through dot1dBasePortIfIndex.
+pub fn parse_mstp(base_rows: &[VarBind], mstp_rows: &[VarBind], mstp_port_rows: &[VarBind], device_id: Uuid) -> StpCollection {
+    // component, instance -> column -> value
+    let mut entries: BTreeMap<(u32, u32), BTreeMap<u32, &SnmpValueOwned>> = BTreeMap::new();
+    for (oid, value) in mstp_rows {
+        if let Some([column, component, instance]) = oid_suffix(oid, &MSTP_ENTRY) {
+            entries.entry((*component, *instance)).or_default().insert(*column, value);
+        }
+    }
+    let Some(component) = entries.keys().map(|(component, _)| *component).min() else {
+        return StpCollection::default();
+    };
+    // instance, port -> column -> value
+    let mut port_entries: BTreeMap<(u32, u32), BTreeMap<u32, &SnmpValueOwned>> = BTreeMap::new();
+    for (oid, value) in mstp_port_rows {
+        if let Some([column, port_component, instance, port]) = oid_suffix(oid, &MSTP_PORT_ENTRY) {
+            if *port_component == component {
+                port_entries.entry((*instance, *port)).or_default().insert(*column, value);
+            }
+        }
+    }
+
+    let port_if_index = port_if_indexes(base_rows);
+    let now = OffsetDateTime::now_utc();
+    let mut collection = StpCollection::default();
+    for ((_, mst_id), columns) in entries.iter().filter(|((c, mst_id), _)| *c == component && *mst_id != 0) {
+        let bridge_id = |column| columns.get(&column).and_then(|value| value.as_bytes()).and_then(format_bridge_id);
+        let integer = |column| columns.get(&column).and_then(|value| value.as_i64()).and_then(|v| i32::try_from(v).ok());
+        let Some(designated_root) = bridge_id(MSTP_DESIGNATED_ROOT) else {
+            continue;
+        };
+        let Ok(instance) = i32::try_from(*mst_id) else {
+            continue;
+        };
+        let root_port = integer(MSTP_ROOT_PORT).filter(|port| *port != 0);
+        let mut vlans = Vec::new();
+        for (block, column) in MSTP_VIDS.iter().enumerate() {
+            let bitmap = columns.get(column).and_then(|value| value.as_bytes()).unwrap_or_default();
+            vlans.extend(vlans_in_bitmap(bitmap, block as i32 * 1024));
+        }
+        collection.instances.push(StpInstance {
+            id: Uuid::nil(),
+            device_id,
+            instance,
+            protocol: PROTOCOL_MST.to_string(),
+            vlans,
+            bridge_id: bridge_id(MSTP_BRIDGE_ID).unwrap_or_default(),
+            designated_root,
+            root_cost: integer(MSTP_ROOT_PATH_COST),
+            root_port_if_index: root_port.and_then(|port| port_if_index.get(&vec![port as u32]).copied()),
+            root_device_id: None,
+            last_seen: now,
+            created_at: now,
+            updated_at: now,
+        });
+
+        for ((_, port), port_columns) in port_entries.range((*mst_id, 0)..=(*mst_id, u32::MAX)) {
+            let Some(&if_index) = port_if_index.get(&vec![*port]) else {
+                continue;
+            };
+            let Some(state) = port_columns.get(&MSTP_PORT_STATE).and_then(|value| value.as_i64()).map(mstp_port_state_name)
+            else {
+                continue;
+            };
+            let role = port_columns.get(&MSTP_PORT_ROLE).and_then(|value| value.as_i64());
+            collection.ports.push(StpPort {
+                id: Uuid::nil(),
+                interface_id: Uuid::nil(),
+                if_index,
+                instance,
+                role: port_role(&state, false, role.as_ref()),
+                state,
+                path_cost: port_columns
+                    .get(&MSTP_PORT_PATH_COST)
+                    .and_then(|value| value.as_i64())
+                    .and_then(|c| i32::try_from(c).ok()),
+                designated_bridge: port_columns
+                    .get(&MSTP_PORT_DESIGNATED_BRIDGE)
+                    .and_then(|value| value.as_bytes())
+                    .and_then(format_bridge_id),
+                last_seen: now,
+                created_at: now,
+                updated_at: now,
+            });
+        }
+    }
+    collection
+}
+
+/// Bridge port numbers are local to the bridge; maps them to ifIndex (dot1dBasePortIfIndex).
+fn port_if_indexes(base_rows: &[VarBind]) -> BTreeMap<Vec<u32>, i32> {
+    column_by_index(base_rows, &column(&[&DOT1D_BASE[..], &BASE_PORT_ENTRY[..]].concat(), BASE_PORT_IF_INDEX))
+        .into_iter()
+        .filter_map(|(port, value)| Some((port, i32::try_from(value.as_i64()?).ok()?)))
+        .collect()
+}
+
+/// Bridge port -> role value from an IEEE8021-MSTP-MIB port table indexed by component and
+/// port, keeping the first component.
+fn port_roles(rows: &[VarBind], entry: &[u32], role_column: u32) -> BTreeMap<u32, i64> {
+    let mut roles: BTreeMap<(u32, u32), i64> = BTreeMap::new();
+    for (index, value) in column_by_index(rows, &column(entry, role_column)) {
+        if let ([component, port], Some(role)) = (index.as_slice(), value.as_i64()) {
+            roles.insert((*component, *port), role);
+        }
+    }
+    let component = roles.keys().map(|(component, _)| *component).min();
+    roles.into_iter().filter(|((c, _), _)| Some(*c) == component).map(|((_, port), role)| (port, role)).collect()
+}
+
+/// VLAN IDs set in a PortList-style bitmap whose first bit is VLAN `first`.
+fn vlans_in_bitmap(bitmap: &[u8], first: i32) -> Vec<i32> {
+    let mut vlans = Vec::new();
+    for (byte_index, byte) in bitmap.iter().enumerate() {
+        for bit in 0..8 {
+            if byte & (0x80 >> bit) != 0 {
+                vlans.push(first + byte_index as i32 * 8 + bit);
+            }
+        }
+    }
+    vlans
+}
+
+/// Formats an 8-byte BridgeId (2 bytes priority, 6 bytes MAC) as 16 hex digits.
+fn format_bridge_id(bytes: &[u8]) -> Option<String> {
+    if bytes.len() != 8 {
+        return None;
+    }
+    Some(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// BRIDGE-MIB dot1dStpPortState values.
+fn port_state_name(value: i64) -> String {
+    match value {
+        1 => "disabled".to_string(),
+        2 => "blocking".to_string(),
+        3 => "listening".to_string(),
+        4 => "learning".to_string(),
+        5 => "forwarding".to_string(),
+        6 => "broken".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// IEEE8021-MSTP-MIB ieee8021MstpPortState values, which differ from BRIDGE-MIB's.
+fn mstp_port_state_name(value: i64) -> String {
+    match value {
+        1 => "disabled".to_string(),
+        2 => "listening".to_string(),
+        3 => "learning".to_string(),
+        4 => "forwarding".to_string(),
+        5 => "blocking".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// The role reported in IEEE8021-MSTP-MIB (ieee8021MstpCistPortRole or ieee8021MstpPortRole),
+/// else what BRIDGE-MIB states: the root port, and disabled ports.
+fn port_role(state: &str, is_root_port: bool, reported: Option<&i64>) -> Option<String> {
+    let role = match (state, reported) {
+        ("disabled" | "broken", _) => "disabled",
+        (_, Some(1)) => "root",
+        (_, Some(2)) => "alternate",
+        (_, Some(3)) => "designated",
+        (_, Some(4)) => "backup",
+        _ if is_root_port => "root",
+        _ => return None,
+    };
+    Some(role.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oid(parts: &[&[u32]]) -> Vec<u32> {
+        parts.concat()
+    }
+
+    #[test]
+    fn parses_blocking_port_and_root() {
+        let bridge_mac = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+        let root_id = [0x10, 0x00, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff];
+        let base_rows = vec![
+            (oid(&[&DOT1D_BASE, &BASE_BRIDGE_ADDRESS]), SnmpValueOwned::OctetString(bridge_mac.to_vec())),
+            (oid(&[&DOT1D_BASE, &BASE_PORT_ENTRY, &[BASE_PORT_IF_INDEX, 1]]), SnmpValueOwned::Integer(10101)),
+            (oid(&[&DOT1D_BASE, &BASE_PORT_ENTRY, &[BASE_PORT_IF_INDEX, 2]]), SnmpValueOwned::Integer(10102)),
+            (oid(&[&DOT1D_BASE, &BASE_PORT_ENTRY, &[BASE_PORT_IF_INDEX, 3]]), SnmpValueOwned::Integer(10103)),
+        ];
+        let own_id = [0x80, 0x00, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+        let port_entry = [&DOT1D_STP[..], &STP_PORT_ENTRY[..]].concat();
+        let stp_rows = vec![
+            (oid(&[&DOT1D_STP, &STP_PRIORITY]), SnmpValueOwned::Integer(32768)),
+            (oid(&[&DOT1D_STP, &STP_DESIGNATED_ROOT]), SnmpValueOwned::OctetString(root_id.to_vec())),
+            (oid(&[&DOT1D_STP, &STP_ROOT_COST]), SnmpValueOwned::Integer(4)),
+            (oid(&[&DOT1D_STP, &STP_ROOT_PORT]), SnmpValueOwned::Integer(1)),
+            (oid(&[&port_entry, &[STP_PORT_STATE, 1]]), SnmpValueOwned::Integer(5)),
+            (oid(&[&port_entry, &[STP_PORT_STATE, 2]]), SnmpValueOwned::Integer(2)),
+            (oid(&[&port_entry, &[STP_PORT_STATE, 3]]), SnmpValueOwned::Integer(5)),
+            (oid(&[&port_entry, &[STP_PORT_DESIGNATED_BRIDGE, 3]]), SnmpValueOwned::OctetString(own_id.to_vec())),
+        ];
+        // Component 1, bridge ports 2 and 3
+        let cist_port_rows = vec![
+            (oid(&[&MSTP_CIST_PORT_ENTRY, &[MSTP_CIST_PORT_ROLE, 1, 2]]), SnmpValueOwned::Integer(2)),
+            (oid(&[&MSTP_CIST_PORT_ENTRY, &[MSTP_CIST_PORT_ROLE, 1, 3]]), SnmpValueOwned::Integer(3)),
+        ];
+
+        let collection = parse_stp(&base_rows, &stp_rows, &cist_port_rows, 20, Uuid::nil());
+
+        let instance = &collection.instances[0];
+        assert_eq!(instance.instance, 20);
+        assert_eq!((instance.protocol.as_str(), instance.vlans.as_slice()), ("pvst", &[20][..]));
+        assert_eq!(instance.bridge_id, "8000001122334455");
+        assert_eq!(instance.designated_root, "1000aabbccddeeff");
+        assert_eq!(instance.root_port_if_index, Some(10101));
+        assert_eq!(instance.root_cost, Some(4));
+
+        let roles: Vec<_> = collection.ports.iter().map(|p| (p.if_index, p.state.as_str(), p.role.as_deref())).collect();
+        assert_eq!(
+            roles,
+            vec![
+                (10101, "forwarding", Some("root")),
+                (10102, "blocking", Some("alternate")),
+                (10103, "forwarding", Some("designated")),
+            ]
+        );
+
+        // Without IEEE8021-MSTP-MIB only the root port has a role
+        let collection = parse_stp(&base_rows, &stp_rows, &[], 0, Uuid::nil());
+        let roles: Vec<_> = collection.ports.iter().map(|p| p.role.as_deref()).collect();
+        assert_eq!(roles, vec![Some("root"), None, None]);
+        assert_eq!(collection.instances[0].protocol, "stp");
+    }
+
+    #[test]
+    fn parses_mst_instances_and_their_vlans() {
+        let base_rows = vec![
+            (oid(&[&DOT1D_BASE, &BASE_PORT_ENTRY, &[BASE_PORT_IF_INDEX, 1]]), SnmpValueOwned::Integer(10101)),
+            (oid(&[&DOT1D_BASE, &BASE_PORT_ENTRY, &[BASE_PORT_IF_INDEX, 2]]), SnmpValueOwned::Integer(10102)),
+        ];
+        let root_id = [0x10, 0x01, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff];
+        let own_id = [0x80, 0x01, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+        // VLANs 10 and 11 in Vids0, 1030 in Vids1
+        let mut vids0 = vec![0u8; 128];
+        vids0[1] = 0b0011_0000;
+        let mut vids1 = vec![0u8; 128];
+        vids1[0] = 0b0000_0010;
+        let mstp_rows = vec![
+            (oid(&[&MSTP_ENTRY, &[MSTP_BRIDGE_ID, 1, 1]]), SnmpValueOwned::OctetString(own_id.to_vec())),
+            (oid(&[&MSTP_ENTRY, &[MSTP_DESIGNATED_ROOT, 1, 1]]), SnmpValueOwned::OctetString(root_id.to_vec())),
+            (oid(&[&MSTP_ENTRY, &[MSTP_ROOT_PATH_COST, 1, 1]]), SnmpValueOwned::Integer(20000)),
+            (oid(&[&MSTP_ENTRY, &[MSTP_ROOT_PORT, 1, 1]]), SnmpValueOwned::Integer(1)),
+            (oid(&[&MSTP_ENTRY, &[MSTP_VIDS[0], 1, 1]]), SnmpValueOwned::OctetString(vids0)),
+            (oid(&[&MSTP_ENTRY, &[MSTP_VIDS[1], 1, 1]]), SnmpValueOwned::OctetString(vids1)),
+        ];
+        let mstp_port_rows = vec![
+            (oid(&[&MSTP_PORT_ENTRY, &[MSTP_PORT_STATE, 1, 1, 1]]), SnmpValueOwned::Integer(4)),
+            (oid(&[&MSTP_PORT_ENTRY, &[MSTP_PORT_ROLE, 1, 1, 1]]), SnmpValueOwned::Integer(1)),
+            (oid(&[&MSTP_PORT_ENTRY, &[MSTP_PORT_STATE, 1, 1, 2]]), SnmpValueOwned::Integer(5)),
+            (oid(&[&MSTP_PORT_ENTRY, &[MSTP_PORT_ROLE, 1, 1, 2]]), SnmpValueOwned::Integer(4)),
+            (oid(&[&MSTP_PORT_ENTRY, &[MSTP_PORT_PATH_COST, 1, 1, 2]]), SnmpValueOwned::Integer(20000)),
+        ];
+
+        let collection = parse_mstp(&base_rows, &mstp_rows, &mstp_port_rows, Uuid::nil());
+
+        let instance = &collection.instances[0];
+        assert_eq!((instance.protocol.as_str(), instance.instance), ("mst", 1));
+        assert_eq!(instance.vlans, vec![10, 11, 1030]);
+        assert_eq!(instance.bridge_id, "8001001122334455");
+        assert_eq!(instance.designated_root, "1001aabbccddeeff");
+        assert_eq!((instance.root_port_if_index, instance.root_cost), (Some(10101), Some(20000)));
+
+        let roles: Vec<_> = collection.ports.iter().map(|p| (p.if_index, p.state.as_str(), p.role.as_deref())).collect();
+        assert_eq!(roles, vec![(10101, "forwarding", Some("root")), (10102, "blocking", Some("backup"))]);
+    }
+
+    #[test]
+    fn skips_reserved_and_suspended_vlans() {
+        let rows = vec![
+            (oid(&[&VTP_VLAN_STATE, &[1, 1]]), SnmpValueOwned::Integer(1)),
+            (oid(&[&VTP_VLAN_STATE, &[1, 20]]), SnmpValueOwned::Integer(1)),
+            (oid(&[&VTP_VLAN_STATE, &[1, 30]]), SnmpValueOwned::Integer(2)),
+            (oid(&[&VTP_VLAN_STATE, &[1, 1002]]), SnmpValueOwned::Integer(1)),
+        ];
+        assert_eq!(parse_vtp_vlans(&rows), vec![1, 20]);
+    }
+}
@@ -0,0 +1,35 @@
+//! System group collector (SNMPv2-MIB sysUpTime).
+
+use nd_core::{SnmpClient, SnmpError, SnmpValueOwned};
+use time::{Duration, OffsetDateTime};
+
+/// SNMPv2-MIB::sysUpTime.0, hundredths of a second since the agent started
+const SYS_UP_TIME: [u32; 9] = [1, 3, 6, 1, 2, 1, 1, 3, 0];
+
+/// Reads sysUpTime and works out when the device booted, as of `now`.
+pub async fn collect_boot_time(client: &impl SnmpClient, now: OffsetDateTime) -> Result<OffsetDateTime, SnmpError> {
+    let uptime = client.get(&SYS_UP_TIME).await?;
+    boot_time(&uptime, now).ok_or_else(|| SnmpError::Snmp(format!("sysUpTime is not TimeTicks: {:?}", uptime)))
+}
+
+/// The boot time `uptime` (TimeTicks) before `now`.
+pub fn boot_time(uptime: &SnmpValueOwned, now: OffsetDateTime) -> Option<OffsetDateTime> {
+    match uptime {
+        SnmpValueOwned::TimeTicks(ticks) => Some(now - Duration::milliseconds(i64::from(*ticks) * 10)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subtracts_uptime_from_now() {
+        let now = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        // 1 day, 2 hours and 3.5 seconds
+        let boot = boot_time(&SnmpValueOwned::TimeTicks(9_360_350), now).unwrap();
+        assert_eq!(now - boot, Duration::days(1) + Duration::hours(2) + Duration::milliseconds(3_500));
+        assert_eq!(boot_time(&SnmpValueOwned::Integer(5), now), None);
+    }
+}
@@ -0,0 +1,185 @@
+//! Wireless controller collector (AIRESPACE-WIRELESS-MIB, used by Cisco WLCs).
+//!
+//! Collects the APs joined to a controller and the clients associated to them.
+//! Other controller MIBs can be added as further parsers producing the same
+//! [`WirelessCollection`].
+
+use super::{column, column_by_index, mac_from_index};
+use db::MacAddress;
+use nd_core::{SnmpClient, SnmpError, SnmpValueOwned, VarBind};
+use std::collections::BTreeMap;
+use std::net::Ipv4Addr;
+
+/// AIRESPACE-WIRELESS-MIB::bsnAPEntry (indexed by AP MAC)
+const BSN_AP_ENTRY: [u32; 11] = [1, 3, 6, 1, 4, 1, 14179, 2, 2, 1, 1];
+/// AIRESPACE-WIRELESS-MIB::bsnMobileStationEntry (indexed by client MAC)
+const BSN_MOBILE_STATION_ENTRY: [u32; 11] = [1, 3, 6, 1, 4, 1, 14179, 2, 1, 4, 1];
+/// AIRESPACE-WIRELESS-MIB::bsnMobileStationStatsEntry (indexed by client MAC)
+const BSN_MOBILE_STATION_STATS_ENTRY: [u32; 11] = [1, 3, 6, 1, 4, 1, 14179, 2, 1, 6, 1];
+
+const BSN_AP_NAME: u32 = 3;
+const BSN_AP_LOCATION: u32 = 4;
+const BSN_AP_OPERATION_STATUS: u32 = 6;
+const BSN_AP_MODEL: u32 = 16;
+const BSN_AP_SERIAL_NUMBER: u32 = 17;
+const BSN_AP_IP_ADDRESS: u32 = 19;
+
+const BSN_MOBILE_STATION_IP_ADDRESS: u32 = 2;
+const BSN_MOBILE_STATION_AP_MAC_ADDR: u32 = 4;
+const BSN_MOBILE_STATION_SSID: u32 = 7;
+const BSN_MOBILE_STATION_RSSI: u32 = 1;
+
+/// An access point as reported by its controller.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WirelessApEntry {
+    pub mac_address: MacAddress,
+    pub name: Option<String>,
+    pub location: Option<String>,
+    pub ip_address: Option<Ipv4Addr>, // None until the AP has an address
+    pub model: Option<String>,
+    pub serial_number: Option<String>,
+    pub oper_status: Option<String>,
+}
+
+/// A client associated to an AP.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WirelessClientEntry {
+    pub mac_address: MacAddress,
+    pub ap_mac_address: MacAddress,
+    pub ssid: Option<String>,
+    pub ip_address: Option<Ipv4Addr>,
+    pub rssi: Option<i32>,
+}
+
+#[derive(Debug, Default)]
+pub struct WirelessCollection {
+    pub aps: Vec<WirelessApEntry>,
+    pub clients: Vec<WirelessClientEntry>,
+}
+
+/// Walks the AP and client tables. Devices that are not controllers return an empty collection.
+pub async fn collect_wireless(client: &impl SnmpClient) -> Result<WirelessCollection, SnmpError> {
+    let ap_rows = client.walk(&BSN_AP_ENTRY).await?;
+    if ap_rows.is_empty() {
+        return Ok(WirelessCollection::default());
+    }
+    let station_rows = client.walk(&BSN_MOBILE_STATION_ENTRY).await?;
+    let stats_rows = client.walk(&BSN_MOBILE_STATION_STATS_ENTRY).await?;
+    Ok(WirelessCollection {
+        aps: parse_aps(&ap_rows),
+        clients: parse_clients(&station_rows, &stats_rows),
+    })
+}
+
+/// Maps walked bsnAPTable rows to APs.
+pub fn parse_aps(rows: &[VarBind]) -> Vec<WirelessApEntry> {
+    let name = column_by_index(rows, &column(&BSN_AP_ENTRY, BSN_AP_NAME));
+    let location = column_by_index(rows, &column(&BSN_AP_ENTRY, BSN_AP_LOCATION));
+    let status = column_by_index(rows, &column(&BSN_AP_ENTRY, BSN_AP_OPERATION_STATUS));
+    let model = column_by_index(rows, &column(&BSN_AP_ENTRY, BSN_AP_MODEL));
+    let serial = column_by_index(rows, &column(&BSN_AP_ENTRY, BSN_AP_SERIAL_NUMBER));
+    let ip = column_by_index(rows, &column(&BSN_AP_ENTRY, BSN_AP_IP_ADDRESS));
+
+    name.iter()
+        .filter_map(|(index, name_value)| {
+            let text = |col: &BTreeMap<Vec<u32>, SnmpValueOwned>| {
+                col.get(index).and_then(SnmpValueOwned::as_string).filter(|s| !s.is_empty())
+            };
+            Some(WirelessApEntry {
+                mac_address: mac_from_index(index)?,
+                name: name_value.as_string().filter(|s| !s.is_empty()),
+                location: text(&location),
+                ip_address: ip.get(index).and_then(SnmpValueOwned::as_ipv4).filter(|ip| !ip.is_unspecified()),
+                model: text(&model),
+                serial_number: text(&serial),
+                oper_status: status.get(index).and_then(SnmpValueOwned::as_i64).map(ap_status_name),
+            })
+        })
+        .collect()
+}
+
+/// Maps walked bsnMobileStationTable and bsnMobileStationStatsTable rows to clients.
+pub fn parse_clients(station_rows: &[VarBind], stats_rows: &[VarBind]) -> Vec<WirelessClientEntry> {
+    let ip = column_by_index(station_rows, &column(&BSN_MOBILE_STATION_ENTRY, BSN_MOBILE_STATION_IP_ADDRESS));
+    let ap_mac = column_by_index(station_rows, &column(&BSN_MOBILE_STATION_ENTRY, BSN_MOBILE_STATION_AP_MAC_ADDR));
+    let ssid = column_by_index(station_rows, &column(&BSN_MOBILE_STATION_ENTRY, BSN_MOBILE_STATION_SSID));
+    let rssi = column_by_index(stats_rows, &column(&BSN_MOBILE_STATION_STATS_ENTRY, BSN_MOBILE_STATION_RSSI));
+
+    ap_mac
+        .iter()
+        .filter_map(|(index, ap_mac_value)| {
+            Some(WirelessClientEntry {
+                mac_address: mac_from_index(index)?,
+                ap_mac_address: ap_mac_value.as_bytes().and_then(MacAddress::from_bytes)?,
+                ssid: ssid.get(index).and_then(SnmpValueOwned::as_string).filter(|s| !s.is_empty()),
+                ip_address: ip.get(index).and_then(SnmpValueOwned::as_ipv4).filter(|ip| !ip.is_unspecified()),
+                rssi: rssi.get(index).and_then(SnmpValueOwned::as_i64).and_then(|r| i32::try_from(r).ok()),
+            })
+        })
+        .collect()
+}
+
+/// bsnAPOperationStatus values.
+fn ap_status_name(value: i64) -> String {
+    match value {
+        1 => "associated".to_string(),
+        2 => "disassociating".to_string(),
+        3 => "downloading".to_string(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nd_core::SnmpSimulator;
+
+    const WLC_RECORDING: &str = include_str!("../../testdata/airespace-wlc.snmprec");
+
+    #[tokio::test]
+    async fn collects_aps_and_clients_from_recorded_controller() {
+        let simulator = SnmpSimulator::from_snmprec("public", WLC_RECORDING).unwrap();
+
+        let collection = collect_wireless(&simulator).await.unwrap();
+
+        assert_eq!(
+            collection.aps[0],
+            WirelessApEntry {
+                mac_address: "00:3a:7d:11:22:30".parse().unwrap(),
+                name: Some("AP-Lobby".to_string()),
+                location: Some("Building A, ground floor".to_string()),
+                ip_address: Some(Ipv4Addr::new(10, 40, 0, 11)),
+                model: Some("AIR-AP2802I-E-K9".to_string()),
+                serial_number: Some("FGL2231A0BC".to_string()),
+                oper_status: Some("associated".to_string()),
+            }
+        );
+        let warehouse = &collection.aps[1];
+        assert_eq!(warehouse.ip_address, None);
+        assert_eq!(warehouse.location, None);
+        assert_eq!(warehouse.oper_status.as_deref(), Some("downloading"));
+
+        assert_eq!(collection.clients.len(), 3);
+        assert_eq!(
+            collection.clients[0],
+            WirelessClientEntry {
+                mac_address: "00:18:de:01:02:03".parse().unwrap(),
+                ap_mac_address: "00:3a:7d:11:22:30".parse().unwrap(),
+                ssid: Some("corp".to_string()),
+                ip_address: Some(Ipv4Addr::new(10, 50, 0, 101)),
+                rssi: Some(-58),
+            }
+        );
+        let unaddressed = &collection.clients[2];
+        assert_eq!(unaddressed.ap_mac_address.to_string(), "00:3a:7d:11:22:40");
+        assert_eq!(unaddressed.ip_address, None);
+        assert_eq!(unaddressed.rssi, None);
+    }
+
+    #[tokio::test]
+    async fn non_controller_returns_empty_collection() {
+        let simulator = SnmpSimulator::from_snmprec("public", "1.3.6.1.2.1.1.5.0|4|access-sw-01").unwrap();
+        let collection = collect_wireless(&simulator).await.unwrap();
+        assert!(collection.aps.is_empty() && collection.clients.is_empty());
+    }
+}
@@ -0,0 +1,50 @@
+//! Write operations against devices through SNMP SET.
+//!
+//! Every change is read back from the agent after the SET, since agents may
+//! accept a write and then ignore it (e.g. a PSE port held down by policy).
+//! [`PortControl`] wraps these operations with the deny-list, audit log and
+//! database updates.
+
+pub mod poe;
+pub mod port;
+mod service;
+
+pub use service::{PortAction, PortControl};
+
+use db::DbError;
+use nd_core::{SnmpClient, SnmpError, SnmpValueOwned};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ControlError {
+    #[error("SNMP communication failed: {0}")]
+    Snmp(#[from] SnmpError),
+    #[error("Change not applied: expected {expected:?}, agent reports {actual:?}")]
+    NotApplied { expected: SnmpValueOwned, actual: SnmpValueOwned },
+    #[error("Database error: {0}")]
+    Db(#[from] DbError),
+    #[error("Port is protected: {0}")]
+    Protected(String),
+    #[error("Invalid value: {0}")]
+    InvalidValue(String),
+    #[error("No SNMP write community configured")]
+    NoWriteCommunity,
+}
+
+/// Writes `value` to `oid` and reads it back, failing if the agent reports
+/// another value. Returns the value the OID had before the write.
+pub(crate) async fn set_verified(
+    client: &impl SnmpClient,
+    oid: &[u32],
+    value: SnmpValueOwned,
+) -> Result<SnmpValueOwned, ControlError> {
+    let previous = client.get(oid).await?;
+    client.set(oid, value.clone()).await?;
+    let actual = client.get(oid).await?;
+    // Agents may answer with another numeric type (e.g. Gauge32 for an Unsigned32 write)
+    let numeric_match = value.as_i64().is_some() && actual.as_i64() == value.as_i64();
+    if actual != value && !numeric_match {
+        return Err(ControlError::NotApplied { expected: value, actual });
+    }
+    Ok(previous)
+}
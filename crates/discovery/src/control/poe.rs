@@ -0,0 +1,56 @@
+//! PoE enable/disable through pethPsePortAdminEnable.
+
+use super::{set_verified, ControlError};
+use crate::collectors::poe::{PETH_PSE_PORT_ENTRY, PSE_PORT_ADMIN_ENABLE, TRUTH_FALSE, TRUTH_TRUE};
+use nd_core::{SnmpClient, SnmpValueOwned};
+use std::time::Duration;
+
+/// Time a powered device needs without power to fully reset.
+pub const POWER_CYCLE_OFF_TIME: Duration = Duration::from_secs(5);
+
+fn admin_enable_oid(group: u32, port: u32) -> Vec<u32> {
+    let mut oid = PETH_PSE_PORT_ENTRY.to_vec();
+    oid.extend([PSE_PORT_ADMIN_ENABLE, group, port]);
+    oid
+}
+
+/// Enables or disables power on a PSE port and verifies the new admin state.
+/// Returns whether power was enabled before the change.
+pub async fn set_poe_admin(client: &impl SnmpClient, group: u32, port: u32, enabled: bool) -> Result<bool, ControlError> {
+    let value = if enabled { TRUTH_TRUE } else { TRUTH_FALSE };
+    let previous = set_verified(client, &admin_enable_oid(group, port), SnmpValueOwned::Integer(value)).await?;
+    Ok(previous.as_i64() == Some(TRUTH_TRUE))
+}
+
+/// Removes power from a PSE port for `off_time`, then restores it.
+pub async fn power_cycle(client: &impl SnmpClient, group: u32, port: u32, off_time: Duration) -> Result<(), ControlError> {
+    set_poe_admin(client, group, port, false).await?;
+    tokio::time::sleep(off_time).await;
+    set_poe_admin(client, group, port, true).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nd_core::SnmpSimulator;
+
+    const RECORDING: &str = "1.3.6.1.2.1.105.1.1.1.3.1.7|2|1\n1.3.6.1.2.1.105.1.1.1.3.1.8|2|2\n";
+
+    #[tokio::test]
+    async fn disables_and_power_cycles() {
+        let simulator = SnmpSimulator::from_snmprec("private", RECORDING).unwrap();
+
+        set_poe_admin(&simulator, 1, 7, false).await.unwrap();
+        assert_eq!(simulator.get(&admin_enable_oid(1, 7)).await.unwrap(), SnmpValueOwned::Integer(TRUTH_FALSE));
+
+        power_cycle(&simulator, 1, 8, Duration::ZERO).await.unwrap();
+        assert_eq!(simulator.get(&admin_enable_oid(1, 8)).await.unwrap(), SnmpValueOwned::Integer(TRUTH_TRUE));
+    }
+
+    #[tokio::test]
+    async fn unknown_port_is_an_error() {
+        let simulator = SnmpSimulator::from_snmprec("private", RECORDING).unwrap();
+        assert!(matches!(set_poe_admin(&simulator, 2, 7, true).await, Err(ControlError::Snmp(_))));
+    }
+}
@@ -0,0 +1,110 @@
+//! Interface settings through IF-MIB and VLAN MIBs: ifAdminStatus, ifAlias
+//! and the access (untagged) VLAN.
+
+use super::{set_verified, ControlError};
+use nd_core::{oid_suffix, SnmpClient, SnmpValueOwned};
+
+/// IF-MIB::ifAdminStatus
+const IF_ADMIN_STATUS: [u32; 10] = [1, 3, 6, 1, 2, 1, 2, 2, 1, 7];
+/// IF-MIB::ifAlias
+const IF_ALIAS: [u32; 11] = [1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 18];
+/// CISCO-VLAN-MEMBERSHIP-MIB::vmVlan, indexed by ifIndex
+const VM_VLAN: [u32; 14] = [1, 3, 6, 1, 4, 1, 9, 9, 68, 1, 2, 2, 1, 2];
+/// Q-BRIDGE-MIB::dot1qPvid, indexed by bridge port
+const DOT1Q_PVID: [u32; 13] = [1, 3, 6, 1, 2, 1, 17, 7, 1, 4, 5, 1, 1];
+/// BRIDGE-MIB::dot1dBasePortIfIndex
+const DOT1D_BASE_PORT_IF_INDEX: [u32; 11] = [1, 3, 6, 1, 2, 1, 17, 1, 4, 1, 2];
+
+const ADMIN_UP: i64 = 1;
+const ADMIN_DOWN: i64 = 2;
+/// ifAlias is DisplayString (SIZE(0..64))
+pub const MAX_ALIAS_LEN: usize = 64;
+pub const VLAN_RANGE: std::ops::RangeInclusive<i32> = 1..=4094;
+
+fn indexed(column_oid: &[u32], index: u32) -> Vec<u32> {
+    let mut oid = column_oid.to_vec();
+    oid.push(index);
+    oid
+}
+
+/// Sets ifAdminStatus up or down. Returns whether the port was up before.
+pub async fn set_admin_status(client: &impl SnmpClient, if_index: u32, up: bool) -> Result<bool, ControlError> {
+    let value = if up { ADMIN_UP } else { ADMIN_DOWN };
+    let previous = set_verified(client, &indexed(&IF_ADMIN_STATUS, if_index), SnmpValueOwned::Integer(value)).await?;
+    Ok(previous.as_i64() == Some(ADMIN_UP))
+}
+
+/// Sets the port description (ifAlias). Returns the previous description.
+pub async fn set_alias(client: &impl SnmpClient, if_index: u32, alias: &str) -> Result<Option<String>, ControlError> {
+    if alias.len() > MAX_ALIAS_LEN {
+        return Err(ControlError::InvalidValue(format!("description longer than {} bytes", MAX_ALIAS_LEN)));
+    }
+    let value = SnmpValueOwned::OctetString(alias.as_bytes().to_vec());
+    let previous = set_verified(client, &indexed(&IF_ALIAS, if_index), value).await?;
+    Ok(previous.as_string())
+}
+
+/// Moves an access port to `vlan`. Cisco agents are changed through vmVlan;
+/// others through the Q-BRIDGE port VLAN ID of the port's bridge port.
+/// Returns the previous VLAN.
+pub async fn set_access_vlan(client: &impl SnmpClient, if_index: u32, vlan: i32) -> Result<Option<i64>, ControlError> {
+    if !VLAN_RANGE.contains(&vlan) {
+        return Err(ControlError::InvalidValue(format!("VLAN {} outside {:?}", vlan, VLAN_RANGE)));
+    }
+
+    let vm_vlan_oid = indexed(&VM_VLAN, if_index);
+    if client.get(&vm_vlan_oid).await.is_ok() {
+        let previous = set_verified(client, &vm_vlan_oid, SnmpValueOwned::Integer(i64::from(vlan))).await?;
+        return Ok(previous.as_i64());
+    }
+
+    let bridge_port = bridge_port_of(client, if_index).await?;
+    let pvid_oid = indexed(&DOT1Q_PVID, bridge_port);
+    let previous = set_verified(client, &pvid_oid, SnmpValueOwned::Gauge32(vlan as u32)).await?;
+    Ok(previous.as_i64())
+}
+
+/// Looks up the bridge port number of an interface.
+async fn bridge_port_of(client: &impl SnmpClient, if_index: u32) -> Result<u32, ControlError> {
+    let rows = client.walk(&DOT1D_BASE_PORT_IF_INDEX).await?;
+    rows.iter()
+        .find_map(|(oid, value)| match oid_suffix(oid, &DOT1D_BASE_PORT_IF_INDEX)? {
+            [port] if value.as_i64() == Some(i64::from(if_index)) => Some(*port),
+            _ => None,
+        })
+        .ok_or_else(|| ControlError::InvalidValue(format!("ifIndex {} is not a bridge port", if_index)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nd_core::SnmpSimulator;
+
+    #[tokio::test]
+    async fn sets_cisco_and_qbridge_vlans() {
+        let cisco = SnmpSimulator::from_snmprec("private", "1.3.6.1.4.1.9.9.68.1.2.2.1.2.10105|2|10\n").unwrap();
+        assert_eq!(set_access_vlan(&cisco, 10105, 20).await.unwrap(), Some(10));
+        assert_eq!(cisco.get(&indexed(&VM_VLAN, 10105)).await.unwrap(), SnmpValueOwned::Integer(20));
+
+        let qbridge = SnmpSimulator::from_snmprec(
+            "private",
+            "1.3.6.1.2.1.17.1.4.1.2.5|2|1005\n1.3.6.1.2.1.17.7.1.4.5.1.1.5|66|1\n",
+        )
+        .unwrap();
+        assert_eq!(set_access_vlan(&qbridge, 1005, 30).await.unwrap(), Some(1));
+        assert!(matches!(set_access_vlan(&qbridge, 1006, 30).await, Err(ControlError::InvalidValue(_))));
+        assert!(matches!(set_access_vlan(&qbridge, 1005, 4095).await, Err(ControlError::InvalidValue(_))));
+    }
+
+    #[tokio::test]
+    async fn sets_admin_status_and_alias() {
+        let simulator = SnmpSimulator::from_snmprec(
+            "private",
+            "1.3.6.1.2.1.2.2.1.7.3|2|1\n1.3.6.1.2.1.31.1.1.1.18.3|4|printer\n",
+        )
+        .unwrap();
+        assert!(set_admin_status(&simulator, 3, false).await.unwrap());
+        assert_eq!(set_alias(&simulator, 3, "camera 12").await.unwrap().as_deref(), Some("printer"));
+        assert_eq!(simulator.get(&indexed(&IF_ALIAS, 3)).await.unwrap().as_string().as_deref(), Some("camera 12"));
+    }
+}
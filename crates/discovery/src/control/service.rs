@@ -0,0 +1,344 @@
+use super::{poe, port, ControlError};
+use db::{Device, Interface, PortChange, Repository};
+use nd_core::{PortControlSettings, ProtectedPort, SnmpClient, SnmpSession, SnmpSettings};
+use std::net::IpAddr;
+use std::sync::Arc;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// A change requested on one switch port.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PortAction {
+    AdminStatus { up: bool },
+    Description(String),
+    AccessVlan(i32),
+    Poe { enabled: bool },
+    PowerCycle,
+}
+
+impl PortAction {
+    /// Name recorded in the audit log.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PortAction::AdminStatus { .. } => "admin_status",
+            PortAction::Description(_) => "description",
+            PortAction::AccessVlan(_) => "access_vlan",
+            PortAction::Poe { .. } => "poe",
+            PortAction::PowerCycle => "power_cycle",
+        }
+    }
+
+    fn new_value(&self) -> Option<String> {
+        match self {
+            PortAction::AdminStatus { up } => Some(admin_status_name(*up).to_string()),
+            PortAction::Description(alias) => Some(alias.clone()),
+            PortAction::AccessVlan(vlan) => Some(vlan.to_string()),
+            PortAction::Poe { enabled } => Some(enabled.to_string()),
+            PortAction::PowerCycle => None,
+        }
+    }
+}
+
+fn admin_status_name(up: bool) -> &'static str {
+    if up {
+        "up"
+    } else {
+        "down"
+    }
+}
+
+/// Applies port changes through SNMP SET: refuses protected ports, verifies
+/// each write, records it in the audit log and updates the stored interface.
+pub struct PortControl {
+    repo: Arc<dyn Repository>,
+    write_community: Option<String>,
+    settings: PortControlSettings,
+}
+
+impl PortControl {
+    pub fn new(repo: Arc<dyn Repository>, snmp: Option<&SnmpSettings>, settings: PortControlSettings) -> Self {
+        let write_community = snmp.and_then(|s| s.write_community.clone());
+        Self { repo, write_community, settings }
+    }
+
+    /// Applies `action` to the interface `if_index` of a device on behalf of `changed_by`.
+    /// Every attempt past the deny-list is logged, including failed ones.
+    pub async fn apply(
+        &self,
+        device_id: Uuid,
+        if_index: i32,
+        action: PortAction,
+        changed_by: &str,
+    ) -> Result<PortChange, ControlError> {
+        let community = self.write_community.as_deref().ok_or(ControlError::NoWriteCommunity)?;
+        let device = self.repo.get_device(device_id).await?;
+        let client = SnmpSession::new(device.ip_address.ip().to_string(), community.as_bytes());
+        self.apply_with(&client, &device, if_index, action, changed_by).await
+    }
+
+    /// Applies `action` through `client`, e.g. a recorded agent in tests.
+    async fn apply_with(
+        &self,
+        client: &impl SnmpClient,
+        device: &Device,
+        if_index: i32,
+        action: PortAction,
+        changed_by: &str,
+    ) -> Result<PortChange, ControlError> {
+        let interface = self.repo.get_interface(device.id, if_index).await?;
+        self.check_protected(device.ip_address.ip(), &interface).await?;
+
+        let result = self.execute(client, &interface, &action).await;
+
+        let mut change = PortChange {
+            id: Uuid::nil(),
+            device_id: device.id,
+            interface_id: Some(interface.id),
+            if_index,
+            action: action.name().to_string(),
+            old_value: None,
+            new_value: action.new_value(),
+            changed_by: changed_by.to_string(),
+            succeeded: result.is_ok(),
+            error: None,
+            changed_at: OffsetDateTime::now_utc(),
+        };
+        match &result {
+            Ok(old_value) => change.old_value = old_value.clone(),
+            Err(e) => change.error = Some(e.to_string()),
+        }
+        let change = self.repo.record_port_change(&change).await?;
+        tracing::info!(
+            device = %device.ip_address,
+            if_index,
+            action = %change.action,
+            changed_by,
+            succeeded = change.succeeded,
+            "Port change"
+        );
+
+        result.map(|_| change)
+    }
+
+    /// Performs the SNMP operation and updates the stored state. Returns the previous value.
+    async fn execute(
+        &self,
+        client: &impl SnmpClient,
+        interface: &Interface,
+        action: &PortAction,
+    ) -> Result<Option<String>, ControlError> {
+        let if_index = u32::try_from(interface.if_index)
+            .map_err(|_| ControlError::InvalidValue(format!("ifIndex {}", interface.if_index)))?;
+        match action {
+            PortAction::AdminStatus { up } => {
+                let was_up = port::set_admin_status(client, if_index, *up).await?;
+                let status = admin_status_name(*up);
+                self.repo.update_interface_settings(interface.id, Some(status), None, None).await?;
+                Ok(Some(admin_status_name(was_up).to_string()))
+            }
+            PortAction::Description(alias) => {
+                let previous = port::set_alias(client, if_index, alias).await?;
+                self.repo.update_interface_settings(interface.id, None, Some(alias), None).await?;
+                Ok(previous)
+            }
+            PortAction::AccessVlan(vlan) => {
+                let previous = port::set_access_vlan(client, if_index, *vlan).await?;
+                self.repo.update_interface_settings(interface.id, None, None, Some(*vlan)).await?;
+                Ok(previous.map(|v| v.to_string()))
+            }
+            PortAction::Poe { enabled } => {
+                let mut pse = self.repo.get_interface_poe(interface.device_id, interface.if_index).await?;
+                let was_enabled =
+                    poe::set_poe_admin(client, pse.group_index as u32, pse.port_index as u32, *enabled).await?;
+                pse.admin_enabled = *enabled;
+                self.repo.upsert_interface_poe(interface.device_id, &pse).await?;
+                Ok(Some(was_enabled.to_string()))
+            }
+            PortAction::PowerCycle => {
+                let pse = self.repo.get_interface_poe(interface.device_id, interface.if_index).await?;
+                poe::power_cycle(client, pse.group_index as u32, pse.port_index as u32, poe::POWER_CYCLE_OFF_TIME)
+                    .await?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Refuses changes on ports matching the deny-list and, unless disabled, on uplinks.
+    async fn check_protected(&self, device_ip: IpAddr, interface: &Interface) -> Result<(), ControlError> {
+        if let Some(rule) = matching_rule(&self.settings.protected_ports, device_ip, interface) {
+            return Err(ControlError::Protected(format!("matches protected port '{}'", rule.interface)));
+        }
+        if self.settings.protect_uplinks && self.repo.is_uplink_port(interface.id).await? {
+            return Err(ControlError::Protected("uplink to another network device".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Finds the first deny-list rule covering the interface, by ifName or ifDescr.
+fn matching_rule<'a>(rules: &'a [ProtectedPort], device_ip: IpAddr, interface: &Interface) -> Option<&'a ProtectedPort> {
+    let names: Vec<&str> = [&interface.if_name, &interface.if_descr].into_iter().flatten().map(String::as_str).collect();
+    rules.iter().find(|rule| {
+        rule.device.is_none_or(|ip| ip == device_ip)
+            && names.iter().any(|name| match rule.interface.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => *name == rule.interface,
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use db::{
+        CustomFieldValues, DeviceRepository, InterfaceRepository, MemoryRepository, Node, NodeRepository, StpInstance, StpPort,
+        TopologyRepository,
+    };
+    use nd_core::SnmpSimulator;
+
+    fn interface(name: &str) -> Interface {
+        let now = OffsetDateTime::now_utc();
+        Interface {
+            id: Uuid::nil(),
+            device_id: Uuid::nil(),
+            if_index: 1,
+            if_name: Some(name.to_string()),
+            if_alias: None,
+            if_descr: None,
+            if_type: None,
+            mac_address: None,
+            ip_address: None,
+            admin_status: None,
+            oper_status: None,
+            speed: None,
+            mtu: None,
+            access_vlan: None,
+            last_changed: None,
+            tags: Vec::new(),
+            custom_fields: CustomFieldValues::new(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn deny_list_matches_by_device_and_name() {
+        let switch: IpAddr = "192.0.2.1".parse().unwrap();
+        let other: IpAddr = "192.0.2.2".parse().unwrap();
+        let rules = vec![
+            ProtectedPort { device: None, interface: "Po*".to_string() },
+            ProtectedPort { device: Some(switch), interface: "Gi1/0/48".to_string() },
+        ];
+
+        assert!(matching_rule(&rules, other, &interface("Po1")).is_some());
+        assert!(matching_rule(&rules, switch, &interface("Gi1/0/48")).is_some());
+        assert!(matching_rule(&rules, other, &interface("Gi1/0/48")).is_none());
+        assert!(matching_rule(&rules, switch, &interface("Gi1/0/4")).is_none());
+    }
+
+    #[tokio::test]
+    async fn applies_and_audits_changes() {
+        let repo = Arc::new(MemoryRepository::new());
+        let device = Device { hostname: Some("sw1".to_string()), ..Device::new("192.0.2.1/32".parse().unwrap()) };
+        let device = repo.upsert_device(&device, "test").await.unwrap();
+        for (if_index, name) in [(1, "Gi1/0/1"), (2, "Po1")] {
+            let mut port = interface(name);
+            port.device_id = device.id;
+            port.if_index = if_index;
+            repo.upsert_interface(&port).await.unwrap();
+        }
+        let simulator = SnmpSimulator::from_snmprec("private", "1.3.6.1.2.1.31.1.1.1.18.1|4|old\n").unwrap();
+        let settings = PortControlSettings {
+            protect_uplinks: true,
+            protected_ports: vec![ProtectedPort { device: None, interface: "Po*".to_string() }],
+        };
+        let control = PortControl::new(repo.clone(), None, settings);
+
+        let change = control
+            .apply_with(&simulator, &device, 1, PortAction::Description("desk 4".to_string()), "alice")
+            .await
+            .unwrap();
+        assert_eq!((change.old_value.as_deref(), change.new_value.as_deref()), (Some("old"), Some("desk 4")));
+        assert_eq!(repo.get_interface(device.id, 1).await.unwrap().if_alias.as_deref(), Some("desk 4"));
+
+        let denied = control.apply_with(&simulator, &device, 2, PortAction::AdminStatus { up: false }, "alice").await;
+        assert!(matches!(denied, Err(ControlError::Protected(_))));
+
+        // Failed writes are logged too
+        assert!(control.apply_with(&simulator, &device, 1, PortAction::AccessVlan(20), "bob").await.is_err());
+        let log = repo.list_port_changes(device.id).await.unwrap();
+        let entries: Vec<_> = log.iter().map(|c| (c.changed_by.as_str(), c.succeeded)).collect();
+        assert_eq!(entries, vec![("bob", false), ("alice", true)]);
+    }
+
+    #[tokio::test]
+    async fn protects_designated_ports_towards_other_bridges_on_the_root_bridge() {
+        let repo = Arc::new(MemoryRepository::new());
+        let now = OffsetDateTime::now_utc();
+        let switch = |ip: &str| Device::new(ip.parse().unwrap());
+        let root = repo.upsert_device(&switch("192.0.2.1/32"), "test").await.unwrap();
+        let access = repo.upsert_device(&switch("192.0.2.2/32"), "test").await.unwrap();
+        let mut ports = Vec::new();
+        for if_index in [1, 2] {
+            let mut port = interface(&format!("Gi1/0/{}", if_index));
+            port.device_id = root.id;
+            port.if_index = if_index;
+            ports.push(repo.upsert_interface(&port).await.unwrap());
+        }
+        // The root bridge has no root port; every forwarding port is designated
+        let instance = |device_id, bridge_id: &str| StpInstance {
+            id: Uuid::nil(),
+            device_id,
+            instance: 0,
+            protocol: "stp".to_string(),
+            vlans: Vec::new(),
+            bridge_id: bridge_id.to_string(),
+            designated_root: "1000001122334401".to_string(),
+            root_cost: None,
+            root_port_if_index: None,
+            root_device_id: None,
+            last_seen: now,
+            created_at: now,
+            updated_at: now,
+        };
+        let designated: Vec<StpPort> = ports
+            .iter()
+            .map(|port| StpPort {
+                id: Uuid::nil(),
+                interface_id: port.id,
+                if_index: port.if_index,
+                instance: 0,
+                state: "forwarding".to_string(),
+                role: Some("designated".to_string()),
+                path_cost: None,
+                designated_bridge: Some("1000001122334401".to_string()),
+                last_seen: now,
+                created_at: now,
+                updated_at: now,
+            })
+            .collect();
+        repo.replace_stp(root.id, &[instance(root.id, "1000001122334401")], &designated).await.unwrap();
+        repo.replace_stp(access.id, &[instance(access.id, "8000001122334402")], &[]).await.unwrap();
+        // The access switch's bridge MAC behind port 1, a workstation behind port 2
+        for (port, mac) in [(&ports[0], "00:11:22:33:44:02"), (&ports[1], "02:00:00:00:00:01")] {
+            let node = Node {
+                id: Uuid::nil(),
+                mac_address: mac.parse().unwrap(),
+                device_id: root.id,
+                interface_id: Some(port.id),
+                vlan: 1,
+                active: true,
+                first_seen: now,
+                last_seen: now,
+                wireless: None,
+            };
+            repo.upsert_node(&node).await.unwrap();
+        }
+        let settings = PortControlSettings { protect_uplinks: true, protected_ports: Vec::new() };
+        let control = PortControl::new(repo.clone(), None, settings);
+
+        let ip = root.ip_address.ip();
+        assert!(matches!(control.check_protected(ip, &ports[0]).await, Err(ControlError::Protected(_))));
+        assert!(control.check_protected(ip, &ports[1]).await.is_ok());
+    }
+}
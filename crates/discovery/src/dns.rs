@@ -0,0 +1,196 @@
+//! Reverse DNS (PTR) lookups through hickory-resolver.
+//!
+//! Queries go to `settings.resolver`, or the nameservers in /etc/resolv.conf, over UDP and
+//! again over TCP when an answer comes back truncated. The resolver caches answers for their
+//! TTL, clamped to the configured bounds. Addresses without a name, and failed lookups, are
+//! remembered for `negative_ttl_secs` even when the answer carries no SOA to cache it by, so
+//! sweeps over the same addresses do not wait on an unreachable resolver for every one of them.
+
+use crate::DiscoveryError;
+use hickory_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use hickory_resolver::name_server::TokioConnectionProvider;
+use hickory_resolver::TokioResolver;
+use nd_core::DnsSettings;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const DNS_PORT: u16 = 53;
+
+pub struct PtrResolver {
+    resolver: TokioResolver,
+    settings: DnsSettings,
+    negative: Mutex<HashMap<IpAddr, (Instant, Negative)>>, // address -> when to ask again
+}
+
+#[derive(Clone, Copy)]
+enum Negative {
+    NoName,
+    Failed,
+}
+
+impl PtrResolver {
+    /// Queries `settings.resolver`, or the nameservers in /etc/resolv.conf.
+    pub fn new(settings: DnsSettings) -> Result<Self, DiscoveryError> {
+        let mut builder = match &settings.resolver {
+            Some(resolver) => {
+                let server = parse_resolver(resolver)
+                    .ok_or_else(|| DiscoveryError::DnsError(format!("invalid resolver address '{}'", resolver)))?;
+                let servers = NameServerConfigGroup::from_ips_clear(&[server.ip()], server.port(), true);
+                TokioResolver::builder_with_config(
+                    ResolverConfig::from_parts(None, Vec::new(), servers),
+                    TokioConnectionProvider::default(),
+                )
+            }
+            None => TokioResolver::builder_tokio()
+                .map_err(|e| DiscoveryError::DnsError(format!("no resolver configured and none in the system: {}", e)))?,
+        };
+        apply_settings(builder.options_mut(), &settings);
+        Ok(Self { resolver: builder.build(), settings, negative: Mutex::new(HashMap::new()) })
+    }
+
+    /// The first nameserver asked, for logging.
+    pub fn server(&self) -> Option<SocketAddr> {
+        self.resolver.config().name_servers().first().map(|server| server.socket_addr)
+    }
+
+    /// The PTR name of `ip`, `None` if it has none.
+    pub async fn lookup(&self, ip: IpAddr) -> Result<Option<String>, DiscoveryError> {
+        match self.remembered(ip) {
+            Some(Negative::NoName) => return Ok(None),
+            Some(Negative::Failed) => return Err(DiscoveryError::DnsError(format!("lookup of {} failed recently", ip))),
+            None => {}
+        }
+        let name = match self.resolver.reverse_lookup(ip).await {
+            Ok(answer) => answer.iter().map(|ptr| ptr.to_utf8().trim_end_matches('.').to_string()).find(|name| !name.is_empty()),
+            Err(e) if e.is_no_records_found() => None, // NXDOMAIN or no PTR record
+            Err(e) => {
+                self.remember(ip, Negative::Failed);
+                return Err(DiscoveryError::DnsError(format!("reverse lookup of {} failed: {}", ip, e)));
+            }
+        };
+        if name.is_none() {
+            self.remember(ip, Negative::NoName);
+        }
+        Ok(name)
+    }
+
+    fn remembered(&self, ip: IpAddr) -> Option<Negative> {
+        let negative = self.negative.lock().unwrap_or_else(|e| e.into_inner());
+        negative.get(&ip).filter(|(retry_at, _)| *retry_at > Instant::now()).map(|&(_, kind)| kind)
+    }
+
+    fn remember(&self, ip: IpAddr, kind: Negative) {
+        let mut negative = self.negative.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        if negative.len() >= self.settings.cache_entries {
+            negative.retain(|_, (retry_at, _)| *retry_at > now);
+            if negative.len() >= self.settings.cache_entries {
+                return;
+            }
+        }
+        negative.insert(ip, (now + Duration::from_secs(u64::from(self.settings.negative_ttl_secs)), kind));
+    }
+}
+
+fn apply_settings(options: &mut ResolverOpts, settings: &DnsSettings) {
+    let ttl = |secs: u32| Some(Duration::from_secs(u64::from(secs)));
+    options.timeout = Duration::from_millis(settings.timeout_ms);
+    options.cache_size = settings.cache_entries;
+    options.positive_min_ttl = ttl(settings.min_ttl_secs);
+    options.positive_max_ttl = ttl(settings.max_ttl_secs.max(settings.min_ttl_secs));
+    options.negative_min_ttl = ttl(settings.negative_ttl_secs);
+    options.negative_max_ttl = ttl(settings.negative_ttl_secs);
+}
+
+// `192.0.2.53`, `192.0.2.53:5353`, `2001:db8::53` or `[2001:db8::53]:5353`
+fn parse_resolver(resolver: &str) -> Option<SocketAddr> {
+    let resolver = resolver.trim();
+    resolver.parse().ok().or_else(|| resolver.parse::<IpAddr>().ok().map(|ip| SocketAddr::new(ip, DNS_PORT)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_resolver::proto::op::{Message, MessageType, ResponseCode};
+    use hickory_resolver::proto::rr::rdata::PTR;
+    use hickory_resolver::proto::rr::{RData, Record};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, UdpSocket};
+
+    const NAME: &str = "sw-1.example.net.";
+
+    // Names 192.0.2.10 and 192.0.2.12, the latter only over TCP; the rest have no name
+    fn answer(query: &[u8], over_tcp: bool) -> Vec<u8> {
+        let query = Message::from_vec(query).unwrap();
+        let name = query.queries()[0].name().clone();
+        let mut response = Message::new();
+        response.set_id(query.id()).set_message_type(MessageType::Response).set_recursion_desired(true);
+        response.add_queries(query.queries().to_vec());
+        match name.to_utf8().as_str() {
+            "10.2.0.192.in-addr.arpa." => {}
+            "12.2.0.192.in-addr.arpa." if !over_tcp => {
+                response.set_truncated(true);
+                return response.to_vec().unwrap();
+            }
+            "12.2.0.192.in-addr.arpa." => {}
+            _ => {
+                response.set_response_code(ResponseCode::NXDomain);
+                return response.to_vec().unwrap();
+            }
+        }
+        response.add_answer(Record::from_rdata(name, 3600, RData::PTR(PTR(NAME.parse().unwrap()))));
+        response.to_vec().unwrap()
+    }
+
+    #[tokio::test]
+    async fn resolves_over_udp_and_tcp_and_caches_answers() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = server.local_addr().unwrap();
+        let tcp = TcpListener::bind(address).await.unwrap();
+        let queries = Arc::new(AtomicUsize::new(0));
+        let counted = queries.clone();
+        tokio::spawn(async move {
+            let mut buffer = [0u8; 512];
+            while let Ok((len, peer)) = server.recv_from(&mut buffer).await {
+                counted.fetch_add(1, Ordering::SeqCst);
+                server.send_to(&answer(&buffer[..len], false), peer).await.unwrap();
+            }
+        });
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = tcp.accept().await {
+                tokio::spawn(async move {
+                    // Each message is preceded by its length
+                    while let Ok(len) = stream.read_u16().await {
+                        let mut query = vec![0u8; usize::from(len)];
+                        stream.read_exact(&mut query).await.unwrap();
+                        let response = answer(&query, true);
+                        stream.write_u16(response.len() as u16).await.unwrap();
+                        stream.write_all(&response).await.unwrap();
+                    }
+                });
+            }
+        });
+        let settings = DnsSettings { resolver: Some(address.to_string()), ..Default::default() };
+        let resolver = PtrResolver::new(settings).unwrap();
+        assert_eq!(resolver.server(), Some(address));
+
+        for _ in 0..2 {
+            assert_eq!(resolver.lookup("192.0.2.10".parse().unwrap()).await.unwrap().as_deref(), Some("sw-1.example.net"));
+            assert_eq!(resolver.lookup("192.0.2.11".parse().unwrap()).await.unwrap(), None);
+        }
+        assert_eq!(queries.load(Ordering::SeqCst), 2); // The second round came from the cache
+        // Truncated over UDP, answered over TCP
+        assert_eq!(resolver.lookup("192.0.2.12".parse().unwrap()).await.unwrap().as_deref(), Some("sw-1.example.net"));
+    }
+
+    #[test]
+    fn parses_resolver_addresses() {
+        assert_eq!(parse_resolver("192.0.2.53"), Some("192.0.2.53:53".parse().unwrap()));
+        assert_eq!(parse_resolver("[2001:db8::53]:5353"), Some("[2001:db8::53]:5353".parse().unwrap()));
+        assert_eq!(parse_resolver("resolver.example.net"), None);
+    }
+}
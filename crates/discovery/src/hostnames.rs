@@ -0,0 +1,96 @@
+//! Reverse DNS names for devices and the addresses seen by them.
+//!
+//! Every name found is stored by address. A device also takes the name of its management
+//! address as `hostname` (source `dns`), unless the hostname was set manually or another
+//! device has it already; sysName stays as collected and a disagreeing name is logged.
+//! Failed lookups leave stored names alone.
+
+use crate::dns::PtrResolver;
+use crate::DiscoveryError;
+use db::{DbError, Device, DeviceField, Repository};
+use futures_util::stream::{self, StreamExt};
+use ipnetwork::IpNetwork;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// Recorded as the source of hostnames set from reverse DNS.
+pub const DNS_SOURCE: &str = "dns";
+
+/// Lookups in flight at once when resolving a batch of addresses.
+const CONCURRENT_LOOKUPS: usize = 16;
+
+pub struct HostnameResolution {
+    repo: Arc<dyn Repository>,
+    resolver: PtrResolver,
+}
+
+impl HostnameResolution {
+    pub fn new(repo: Arc<dyn Repository>, resolver: PtrResolver) -> Self {
+        Self { repo, resolver }
+    }
+
+    /// Looks up the device's management address and sets its hostname from the answer.
+    pub async fn resolve_device(&self, device: &Device) -> Result<Option<String>, DiscoveryError> {
+        let Some(hostname) = self.lookup(device.ip_address.ip()).await? else {
+            return Ok(None);
+        };
+        if let Some(sys_name) = device.sys_name.as_ref().filter(|sys_name| !names_match(&hostname, sys_name)) {
+            tracing::warn!(device = %device.ip_address.ip(), %hostname, %sys_name, "Reverse DNS name differs from sysName");
+        }
+        if device.hostname.as_ref() != Some(&hostname) {
+            let values = [(DeviceField::Hostname, Some(hostname.clone()))];
+            match self.repo.set_device_fields(device.id, &values, DNS_SOURCE).await {
+                Ok(_) => {}
+                Err(DbError::InvalidQuery(message)) => {
+                    tracing::warn!(device = %device.ip_address.ip(), %message, "Could not set hostname from reverse DNS")
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(Some(hostname))
+    }
+
+    /// Looks up `addresses`, e.g. of a router's ARP table, and returns how many have a name.
+    pub async fn resolve_addresses(&self, addresses: &[IpAddr]) -> Result<usize, DiscoveryError> {
+        let mut lookups = stream::iter(addresses.iter().copied()).map(|address| self.lookup(address)).buffer_unordered(CONCURRENT_LOOKUPS);
+        let mut named = 0;
+        while let Some(hostname) = lookups.next().await {
+            if hostname?.is_some() {
+                named += 1;
+            }
+        }
+        Ok(named)
+    }
+
+    // Stores the answer for `ip`; DNS failures are logged and count as no name
+    async fn lookup(&self, ip: IpAddr) -> Result<Option<String>, DiscoveryError> {
+        let hostname = match self.resolver.lookup(ip).await {
+            Ok(hostname) => hostname,
+            Err(e) => {
+                tracing::debug!(%ip, resolver = ?self.resolver.server(), error = %e, "Reverse DNS lookup failed");
+                return Ok(None);
+            }
+        };
+        self.repo.set_ip_hostname(IpNetwork::from(ip), hostname.as_deref()).await?;
+        Ok(hostname)
+    }
+}
+
+/// Whether a DNS name and a sysName name the same host. Either may be fully qualified,
+/// so only the first labels are compared, ignoring case.
+pub fn names_match(hostname: &str, sys_name: &str) -> bool {
+    let host = |name: &str| name.trim().trim_end_matches('.').split('.').next().unwrap_or_default().to_ascii_lowercase();
+    host(hostname) == host(sys_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_host_labels() {
+        assert!(names_match("sw-1.example.net", "SW-1"));
+        assert!(names_match("sw-1.example.net.", "sw-1.corp.example.net"));
+        assert!(!names_match("sw-1.example.net", "sw-2"));
+    }
+}
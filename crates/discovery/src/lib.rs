@@ -1,8 +1,23 @@
-use nd_core::Settings; // Use settings from nd_core
-use db::{Device, DeviceStatus, DbError, PgPool}; // Use types from db crate
+use db::{ArpEntry, Device, DbError, DeviceStatus, DeviceType, Interface, LinkRefresh, NeighborProtocol, Node, NodeWireless, Repository, RoutingProtocol, SubnetRefresh, WirelessAp}; // Use types from db crate
+use collectors::wireless::WirelessCollection;
+use nd_core::{SnmpClient, SnmpSession};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
 use ipnetwork::IpNetwork;
 use thiserror::Error;
 use std::net::IpAddr;
+use uuid::Uuid;
+
+pub mod collectors;
+pub mod control;
+pub mod dns;
+pub mod hostnames;
+pub mod rogue;
+
+use hostnames::HostnameResolution;
+use rogue::RogueCheck;
 
 // --- Structs and Enums previously in nd_core/src/discovery.rs ---
 
@@ -32,6 +47,8 @@ pub enum DiscoveryError {
     DbError(#[from] DbError), // Use DbError from db crate
     #[error("Unsupported target type")]
     UnsupportedTarget,
+    #[error("DNS lookup failed: {0}")]
+    DnsError(String),
     #[error("Invalid IP range: {0} > {1}")]
     InvalidRange(IpAddr, IpAddr),
     #[error("Other error: {0}")]
@@ -40,28 +57,49 @@ pub enum DiscoveryError {
 
 #[derive(Debug)]
 pub enum DiscoveryResult {
-    DeviceFound(Device),
+    DeviceFound(Box<Device>),
     DeviceSkipped { ip: IpAddr, reason: String },
     DeviceFailed { ip: IpAddr, error: DiscoveryError },
 }
 
+/// Outcome of one pass over all devices.
+#[derive(Debug, Default)]
+pub struct SweepSummary {
+    pub devices: usize,
+    pub failed: usize, // Devices whose collection stopped with an error
+    pub subnets: SubnetRefresh,
+    pub links: LinkRefresh,
+}
+
 pub struct DiscoveryManager {
-    db_pool: PgPool,
+    repo: Arc<dyn Repository>,
+    rogue_check: Option<RogueCheck>,
+    hostnames: Option<HostnameResolution>,
     // config: Settings, // Might need config too
 }
 
 impl DiscoveryManager {
-    pub fn new(db_pool: PgPool/*, config: Settings*/) -> Self {
-        Self { db_pool/*, config*/ }
+    pub fn new(repo: Arc<dyn Repository>/*, config: Settings*/) -> Self {
+        Self { repo, rogue_check: None, hostnames: None/*, config*/ }
+    }
+
+    /// Checks the MACs collected from now on against the allowlist.
+    pub fn with_rogue_check(self, rogue_check: RogueCheck) -> Self {
+        Self { rogue_check: Some(rogue_check), ..self }
+    }
+
+    /// Looks up the PTR names of each collected device and the addresses it has seen.
+    pub fn with_hostname_resolution(self, hostnames: HostnameResolution) -> Self {
+        Self { hostnames: Some(hostnames), ..self }
     }
 
     pub async fn run_discovery(&self, _job: DiscoveryJob) -> Result<(), DiscoveryError> {
         tracing::info!("Placeholder: Running discovery job...");
         // ... (Placeholder logic remains the same) ...
         
-        // Example using db_pool eventually:
+        // Example using the repository eventually:
         // let device_data = Device { ... }; // Populate from SNMP results
-        // let _saved_device = db::upsert_device(&self.db_pool, &device_data).await?; 
+        // let _saved_device = self.repo.upsert_device(&device_data, "discovery").await?;
 
         let simulated_ip: IpAddr = "192.168.1.1".parse().unwrap();
         let simulated_result = DiscoveryResult::DeviceSkipped {
@@ -72,6 +110,474 @@ impl DiscoveryManager {
 
         Ok(())
     }
+
+    /// Runs the per-device collectors against an already identified device
+    /// and stores their results.
+    pub async fn collect_device(&self, device: &Device, creds: &SnmpCredentials) -> Result<(), DiscoveryError> {
+        let client = SnmpSession::new(device.ip_address.ip().to_string(), creds.community.as_bytes());
+        self.collect_device_with(device, &client).await
+    }
+
+    /// Collects every known device in turn, then rebuilds what spans devices: the
+    /// subnets and the topology links. A device that fails is logged and the sweep moves on.
+    pub async fn sweep(&self, creds: &SnmpCredentials) -> Result<SweepSummary, DiscoveryError> {
+        let devices = self.repo.list_devices().await?;
+        let mut summary = SweepSummary { devices: devices.len(), ..Default::default() };
+        for device in &devices {
+            if let Err(e) = self.collect_device(device, creds).await {
+                tracing::warn!(device = %device.ip_address.ip(), error = %e, "Device collection failed");
+                summary.failed += 1;
+            }
+        }
+        summary.subnets = self.repo.refresh_subnets().await?;
+        summary.links = self.repo.refresh_topology_links().await?;
+        Ok(summary)
+    }
+
+    /// Runs the collectors through `client`, e.g. a recorded agent in tests.
+    /// Retired and decommissioned devices are skipped.
+    pub async fn collect_device_with(&self, device: &Device, client: &impl SnmpClient) -> Result<(), DiscoveryError> {
+        let target = device.ip_address.ip().to_string();
+        if device.status.as_ref().is_some_and(DeviceStatus::is_retired) {
+            tracing::info!(device = %target, status = ?device.status, "Skipping collection for retired device");
+            return Ok(());
+        }
+
+        // Without the interface table the agent is unusable; every other collector is optional
+        let mut interfaces = collectors::interfaces::collect_interfaces(client, device.id)
+            .await
+            .map_err(|e| DiscoveryError::SnmpError(e.to_string()))?;
+        match collectors::ip::collect_ip_addresses(client).await {
+            Ok(addresses) => {
+                for interface in &mut interfaces {
+                    interface.ip_address = addresses.get(&interface.if_index).copied();
+                }
+            }
+            Err(e) => tracing::warn!(device = %target, error = %e, "ipAddrTable walk failed; storing interfaces without addresses"),
+        }
+        self.repo.upsert_interfaces(&interfaces).await?;
+        tracing::info!(device = %target, count = interfaces.len(), "Collected interfaces");
+        skip_failed(&target, "Boot time", self.collect_boot_time(device, client).await);
+
+        // ARP entries link to interfaces by id, which the upsert above assigned
+        let stored_interfaces = self.repo.list_interfaces(device.id).await?;
+        let arp = skip_failed(&target, "ARP", self.collect_arp(device, client, &stored_interfaces).await);
+        skip_failed(&target, "MAC table", self.collect_fdb(device, client, &stored_interfaces).await);
+        skip_failed(&target, "PoE", self.collect_poe(device, client, &interfaces).await);
+        skip_failed(&target, "Routing", self.collect_routing(device, client).await);
+        skip_failed(&target, "Spanning tree", self.collect_stp(device, client).await);
+        let wireless = skip_failed(&target, "Wireless", self.collect_wireless(device, client).await);
+        skip_failed(&target, "Neighbor", self.collect_neighbors(device, client, &stored_interfaces).await);
+
+        if let Some(hostnames) = &self.hostnames {
+            let mut addresses: Vec<IpAddr> = arp.iter().map(|e| e.ip_address.ip()).collect();
+            addresses.extend(wireless.clients.iter().filter_map(|c| c.ip_address).map(IpAddr::V4));
+            skip_failed(&target, "Reverse DNS", self.resolve_hostnames(hostnames, device, addresses).await);
+        }
+
+        Ok(())
+    }
+
+    // Only the boot time is sent, so the upsert leaves every other field as stored;
+    // the merge keeps the stored boot time unless the device rebooted since
+    async fn collect_boot_time(&self, device: &Device, client: &impl SnmpClient) -> Result<(), DiscoveryError> {
+        let now = OffsetDateTime::now_utc();
+        let last_boot = collectors::system::collect_boot_time(client, now)
+            .await
+            .map_err(|e| DiscoveryError::SnmpError(e.to_string()))?;
+        let update = Device { last_seen: Some(now), last_boot: Some(last_boot), ..Device::new(device.ip_address) };
+        let stored = self.repo.upsert_device(&update, "discovery").await?;
+        tracing::info!(device = %device.ip_address.ip(), last_boot = ?stored.last_boot, "Collected boot time");
+        Ok(())
+    }
+
+    async fn collect_arp(
+        &self,
+        device: &Device,
+        client: &impl SnmpClient,
+        interfaces: &[Interface],
+    ) -> Result<Vec<ArpEntry>, DiscoveryError> {
+        let arp = collectors::ip::collect_arp(client, device.id, interfaces)
+            .await
+            .map_err(|e| DiscoveryError::SnmpError(e.to_string()))?;
+        self.repo.upsert_arp_entries(device.id, &arp).await?;
+        tracing::info!(device = %device.ip_address.ip(), arp_entries = arp.len(), "Collected ARP table");
+        Ok(arp)
+    }
+
+    // Wired nodes: MACs learned on each port, checked against the allowlist like wireless clients
+    async fn collect_fdb(&self, device: &Device, client: &impl SnmpClient, interfaces: &[Interface]) -> Result<(), DiscoveryError> {
+        let run_started = OffsetDateTime::now_utc();
+        let nodes = collectors::fdb::collect_fdb(client, device.id, interfaces)
+            .await
+            .map_err(|e| DiscoveryError::SnmpError(e.to_string()))?;
+        self.repo.upsert_nodes(&nodes).await?;
+        let deactivated = self.repo.deactivate_nodes(&[device.id], run_started).await?;
+        if let Some(rogue_check) = &self.rogue_check {
+            rogue_check.check(&nodes).await?;
+        }
+        tracing::info!(device = %device.ip_address.ip(), nodes = nodes.len(), deactivated, "Collected MAC address table");
+        Ok(())
+    }
+
+    async fn collect_poe(&self, device: &Device, client: &impl SnmpClient, interfaces: &[Interface]) -> Result<(), DiscoveryError> {
+        let poe = collectors::poe::collect_poe(client, interfaces)
+            .await
+            .map_err(|e| DiscoveryError::SnmpError(e.to_string()))?;
+        self.repo.replace_interface_poe(device.id, &poe).await?;
+        tracing::info!(device = %device.ip_address.ip(), ports = poe.len(), "Collected PoE port state");
+        Ok(())
+    }
+
+    // A protocol whose walk failed keeps its stored neighbors rather than losing them
+    async fn collect_routing(&self, device: &Device, client: &impl SnmpClient) -> Result<(), DiscoveryError> {
+        let neighbors = collectors::routing::collect_routing_neighbors(client, device.id).await;
+        for (protocol, collected) in [(RoutingProtocol::Bgp, &neighbors.bgp), (RoutingProtocol::Ospf, &neighbors.ospf)] {
+            match collected {
+                Ok(collected) => self.repo.replace_routing_neighbors(device.id, protocol, collected).await?,
+                Err(e) => tracing::warn!(
+                    device = %device.ip_address.ip(),
+                    protocol = protocol.as_str(),
+                    error = %e,
+                    "Routing neighbor walk failed; keeping the stored neighbors"
+                ),
+            }
+        }
+        tracing::info!(
+            device = %device.ip_address.ip(),
+            bgp_peers = ?neighbors.bgp.as_ref().ok().map(Vec::len),
+            ospf_neighbors = ?neighbors.ospf.as_ref().ok().map(Vec::len),
+            "Collected routing neighbors"
+        );
+        Ok(())
+    }
+
+    async fn collect_stp(&self, device: &Device, client: &impl SnmpClient) -> Result<(), DiscoveryError> {
+        let stp = collectors::stp::collect_stp(client, device.id)
+            .await
+            .map_err(|e| DiscoveryError::SnmpError(e.to_string()))?;
+        self.repo.replace_stp(device.id, &stp.instances, &stp.ports).await?;
+        tracing::info!(device = %device.ip_address.ip(), instances = stp.instances.len(), "Collected spanning tree state");
+        Ok(())
+    }
+
+    async fn collect_wireless(&self, device: &Device, client: &impl SnmpClient) -> Result<WirelessCollection, DiscoveryError> {
+        let wireless = collectors::wireless::collect_wireless(client)
+            .await
+            .map_err(|e| DiscoveryError::SnmpError(e.to_string()))?;
+        if !wireless.aps.is_empty() {
+            self.store_wireless(device, &wireless).await?;
+            tracing::info!(
+                device = %device.ip_address.ip(),
+                aps = wireless.aps.len(),
+                clients = wireless.clients.len(),
+                "Collected wireless APs and clients"
+            );
+        }
+        Ok(wireless)
+    }
+
+    async fn collect_neighbors(
+        &self,
+        device: &Device,
+        client: &impl SnmpClient,
+        interfaces: &[Interface],
+    ) -> Result<(), DiscoveryError> {
+        let neighbors = collectors::neighbors::collect_neighbors(client, device.id, interfaces)
+            .await
+            .map_err(|e| DiscoveryError::SnmpError(e.to_string()))?;
+        self.repo.replace_device_neighbors(device.id, NeighborProtocol::Lldp, &neighbors.lldp).await?;
+        self.repo.replace_device_neighbors(device.id, NeighborProtocol::Cdp, &neighbors.cdp).await?;
+        tracing::info!(
+            device = %device.ip_address.ip(),
+            lldp_neighbors = neighbors.lldp.len(),
+            cdp_neighbors = neighbors.cdp.len(),
+            "Collected link layer neighbors"
+        );
+        Ok(())
+    }
+
+    async fn resolve_hostnames(
+        &self,
+        hostnames: &HostnameResolution,
+        device: &Device,
+        mut addresses: Vec<IpAddr>,
+    ) -> Result<(), DiscoveryError> {
+        let hostname = hostnames.resolve_device(device).await?;
+        addresses.sort_unstable();
+        addresses.dedup();
+        let named = hostnames.resolve_addresses(&addresses).await?;
+        tracing::info!(
+            device = %device.ip_address.ip(),
+            ?hostname,
+            addresses = addresses.len(),
+            named,
+            "Resolved reverse DNS names"
+        );
+        Ok(())
+    }
+
+    /// Stores a controller's APs as devices linked to it, and its clients as
+    /// nodes on their AP. Clients no longer associated to these APs are deactivated.
+    async fn store_wireless(&self, controller: &Device, wireless: &WirelessCollection) -> Result<(), DiscoveryError> {
+        let run_started = OffsetDateTime::now_utc();
+        let mut ap_device_ids = HashMap::new();
+
+        for ap in &wireless.aps {
+            // APs without an address cannot be stored as devices yet
+            let Some(ap_ip) = ap.ip_address else {
+                tracing::debug!(ap = %ap.mac_address, "Skipping AP without an IP address");
+                continue;
+            };
+            let status = if ap.oper_status.as_deref() == Some("associated") {
+                DeviceStatus::Up
+            } else {
+                DeviceStatus::Down
+            };
+            let ap_device = Device {
+                sys_name: ap.name.clone(),
+                sys_location: ap.location.clone(),
+                model: ap.model.clone(),
+                serial_number: ap.serial_number.clone(),
+                status: Some(status),
+                device_type: Some(DeviceType::AccessPoint),
+                last_seen: Some(run_started),
+                ..Device::new(IpNetwork::from(IpAddr::V4(ap_ip)))
+            };
+            let ap_device = self.repo.upsert_device(&ap_device, "wireless").await?;
+            self.repo
+                .upsert_wireless_ap(&WirelessAp {
+                    device_id: ap_device.id,
+                    controller_id: controller.id,
+                    mac_address: ap.mac_address,
+                    name: ap.name.clone(),
+                    location: ap.location.clone(),
+                    oper_status: ap.oper_status.clone(),
+                    last_seen: run_started,
+                    created_at: run_started,
+                    updated_at: run_started,
+                })
+                .await?;
+            ap_device_ids.insert(ap.mac_address, ap_device.id);
+        }
+
+        let now = OffsetDateTime::now_utc();
+        let clients: Vec<Node> = wireless
+            .clients
+            .iter()
+            .filter_map(|client| {
+                let &ap_device_id = ap_device_ids.get(&client.ap_mac_address)?;
+                Some(Node {
+                    id: Uuid::nil(),
+                    mac_address: client.mac_address,
+                    device_id: ap_device_id,
+                    interface_id: None,
+                    vlan: 0,
+                    active: true,
+                    first_seen: now,
+                    last_seen: now,
+                    wireless: Some(NodeWireless {
+                        ssid: client.ssid.clone(),
+                        rssi: client.rssi,
+                        ip_address: client.ip_address.map(|ip| IpNetwork::from(IpAddr::V4(ip))),
+                        last_seen: now,
+                    }),
+                })
+            })
+            .collect();
+        self.repo.upsert_nodes(&clients).await?;
+        if let Some(rogue_check) = &self.rogue_check {
+            rogue_check.check(&clients).await?;
+        }
+
+        let ap_ids: Vec<Uuid> = ap_device_ids.into_values().collect();
+        self.repo.deactivate_nodes(&ap_ids, run_started).await?;
+        Ok(())
+    }
+}
+
+/// Sweeps all devices every `interval`, for as long as the caller runs it.
+pub async fn run_sweeps(manager: DiscoveryManager, creds: SnmpCredentials, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        match manager.sweep(&creds).await {
+            Ok(summary) => tracing::info!(
+                devices = summary.devices,
+                failed = summary.failed,
+                subnets = summary.subnets.total,
+                new_subnets = summary.subnets.added,
+                links = summary.links.total,
+                new_links = summary.links.added,
+                "Discovery sweep finished"
+            ),
+            Err(e) => tracing::warn!(error = %e, "Discovery sweep failed"),
+        }
+    }
+}
+
+// A failed optional collector is logged and leaves the rest of the device to run
+fn skip_failed<T: Default>(target: &str, collector: &str, result: Result<T, DiscoveryError>) -> T {
+    result.unwrap_or_else(|e| {
+        tracing::warn!(device = %target, error = %e, "{} collection failed; continuing without it", collector);
+        T::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use db::{
+        AllowedMac, AllowlistRepository, DeviceRepository, InterfaceRepository, MacViolationFilter, MemoryRepository, NodeRepository,
+        RoutingNeighbor, TopologyRepository,
+    };
+    use nd_core::{RogueSettings, SnmpSimulator};
+
+    fn device(hostname: &str, ip: &str) -> Device {
+        Device {
+            hostname: Some(hostname.to_string()),
+            status: Some(DeviceStatus::Up),
+            last_seen: Some(OffsetDateTime::now_utc()),
+            ..Device::new(ip.parse().unwrap())
+        }
+    }
+
+    #[tokio::test]
+    async fn stores_controller_aps_and_clients() {
+        let repo = Arc::new(MemoryRepository::new());
+        let controller = repo.upsert_device(&device("wlc-01", "10.40.0.2/32"), "test").await.unwrap();
+        let simulator =
+            SnmpSimulator::from_snmprec("public", include_str!("../testdata/airespace-wlc.snmprec")).unwrap();
+
+        let manager = DiscoveryManager::new(repo.clone());
+        manager.collect_device_with(&controller, &simulator).await.unwrap();
+
+        // The warehouse AP has no IP address yet and is not stored
+        let aps = repo.list_wireless_aps(controller.id).await.unwrap();
+        assert_eq!(aps.len(), 1);
+        assert_eq!(aps[0].name.as_deref(), Some("AP-Lobby"));
+        let ap_device = repo.get_device(aps[0].device_id).await.unwrap();
+        assert_eq!(ap_device.ip_address, "10.40.0.11/32".parse().unwrap());
+
+        let clients = repo.list_nodes(ap_device.id).await.unwrap();
+        assert_eq!(clients.len(), 2);
+        assert!(clients.iter().all(|c| c.wireless.is_some()));
+    }
+
+    #[tokio::test]
+    async fn stores_mac_table_and_checks_the_allowlist() {
+        let repo = Arc::new(MemoryRepository::new());
+        let switch = repo.upsert_device(&device("access-01", "10.40.0.4/32"), "test").await.unwrap();
+        let entry = AllowedMac {
+            id: Uuid::nil(),
+            mac_prefix: "00:1a:2b".to_string(),
+            location_id: None,
+            vlan: None,
+            description: None,
+            created_at: OffsetDateTime::now_utc(),
+        };
+        repo.create_allowed_mac(&entry).await.unwrap();
+        let simulator =
+            SnmpSimulator::from_snmprec("public", include_str!("../testdata/access-switch.snmprec")).unwrap();
+
+        let rogue_check = RogueCheck::new(repo.clone(), RogueSettings::default(), 4);
+        let manager = DiscoveryManager::new(repo.clone()).with_rogue_check(rogue_check);
+        manager.collect_device_with(&switch, &simulator).await.unwrap();
+
+        let interfaces = repo.list_interfaces(switch.id).await.unwrap();
+        let port = |name: &str| interfaces.iter().find(|i| i.if_name.as_deref() == Some(name)).map(|i| i.id);
+        let nodes = repo.list_nodes(switch.id).await.unwrap();
+        let mut nodes: Vec<_> = nodes.iter().map(|n| (n.mac_address.to_string(), n.vlan, n.interface_id)).collect();
+        nodes.sort();
+        assert_eq!(
+            nodes,
+            vec![
+                ("00:1a:2b:00:00:01".to_string(), 10, port("Gi1/0/1")),
+                ("02:00:00:00:00:07".to_string(), 10, port("Gi1/0/2")),
+            ]
+        );
+        let violations = repo.list_mac_violations(&MacViolationFilter::default()).await.unwrap();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].interface_id, port("Gi1/0/2"));
+    }
+
+    // Times out on the walks of some subtrees, like an agent without those MIBs
+    struct PartialAgent {
+        inner: SnmpSimulator,
+        failing: Vec<Vec<u32>>,
+    }
+
+    impl SnmpClient for PartialAgent {
+        async fn walk(&self, base_oid: &[u32]) -> Result<Vec<nd_core::VarBind>, nd_core::SnmpError> {
+            if self.failing.iter().any(|prefix| base_oid.starts_with(prefix)) {
+                return Err(nd_core::SnmpError::Snmp("Request timed out".to_string()));
+            }
+            self.inner.walk(base_oid).await
+        }
+
+        async fn get(&self, oid: &[u32]) -> Result<nd_core::SnmpValueOwned, nd_core::SnmpError> {
+            self.inner.get(oid).await
+        }
+
+        async fn set(&self, oid: &[u32], value: nd_core::SnmpValueOwned) -> Result<(), nd_core::SnmpError> {
+            self.inner.set(oid, value).await
+        }
+
+        fn with_community_index(&self, index: &str) -> Self {
+            Self { inner: self.inner.with_community_index(index), failing: self.failing.clone() }
+        }
+    }
+
+    #[tokio::test]
+    async fn keeps_collecting_after_a_failed_collector() {
+        let repo = Arc::new(MemoryRepository::new());
+        let controller = repo.upsert_device(&device("wlc-02", "10.40.0.3/32"), "test").await.unwrap();
+        let agent = PartialAgent {
+            inner: SnmpSimulator::from_snmprec("public", include_str!("../testdata/airespace-wlc.snmprec")).unwrap(),
+            failing: vec![vec![1, 3, 6, 1, 2, 1, 4], vec![1, 3, 6, 1, 2, 1, 17]], // IP-MIB and BRIDGE-MIB
+        };
+
+        let manager = DiscoveryManager::new(repo.clone());
+        manager.collect_device_with(&controller, &agent).await.unwrap();
+        assert_eq!(repo.list_wireless_aps(controller.id).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn keeps_the_neighbors_of_a_protocol_whose_walk_failed() {
+        let repo = Arc::new(MemoryRepository::new());
+        let router = repo.upsert_device(&device("rtr-01", "10.0.0.1/32"), "test").await.unwrap();
+        let now = OffsetDateTime::now_utc();
+        let neighbor = |protocol, address: &str| RoutingNeighbor {
+            id: Uuid::nil(),
+            device_id: router.id,
+            protocol,
+            neighbor_address: address.parse().unwrap(),
+            remote_router_id: None,
+            remote_as: None,
+            state: "full".to_string(),
+            uptime_seconds: None,
+            last_seen: now,
+            created_at: now,
+            updated_at: now,
+        };
+        repo.replace_routing_neighbors(router.id, RoutingProtocol::Bgp, &[neighbor(RoutingProtocol::Bgp, "192.0.2.1/32")])
+            .await
+            .unwrap();
+        repo.replace_routing_neighbors(router.id, RoutingProtocol::Ospf, &[neighbor(RoutingProtocol::Ospf, "192.0.2.2/32")])
+            .await
+            .unwrap();
+        let agent = PartialAgent {
+            inner: SnmpSimulator::from_snmprec("public", include_str!("../testdata/access-switch.snmprec")).unwrap(),
+            failing: vec![vec![1, 3, 6, 1, 2, 1, 15]], // BGP4-MIB
+        };
+
+        let manager = DiscoveryManager::new(repo.clone());
+        manager.collect_device_with(&router, &agent).await.unwrap();
+        // The OSPF table walked empty and replaces the stored neighbor; BGP is kept
+        let neighbors = repo.list_routing_neighbors(router.id).await.unwrap();
+        let protocols: Vec<_> = neighbors.iter().map(|n| n.protocol).collect();
+        assert_eq!(protocols, vec![RoutingProtocol::Bgp]);
+    }
 }
 
 // Remove default lib content if present
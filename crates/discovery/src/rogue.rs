@@ -0,0 +1,127 @@
+//! Checks collected MAC tables against the allowlist.
+//!
+//! Each unapproved MAC is recorded as a violation and logged. With `shutdown_ports`
+//! set and a [`PortControl`] attached, the access port it was seen on is shut down
+//! once; the change goes through the deny-list and audit log like any other.
+
+use crate::control::{PortAction, PortControl};
+use crate::DiscoveryError;
+use db::{MacViolation, Node, Repository};
+use nd_core::RogueSettings;
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+/// Recorded as `changed_by` for ports shut by the rogue policy.
+pub const ROGUE_POLICY: &str = "rogue_policy";
+
+pub struct RogueCheck {
+    repo: Arc<dyn Repository>,
+    settings: RogueSettings,
+    access_port_max_macs: u32,
+    port_control: Option<Arc<PortControl>>,
+}
+
+impl RogueCheck {
+    /// Ports with more than `access_port_max_macs` MACs are uplinks and are not checked;
+    /// pass `anomalies.access_port_max_macs` so both agree on what an access port is.
+    pub fn new(repo: Arc<dyn Repository>, settings: RogueSettings, access_port_max_macs: u32) -> Self {
+        Self { repo, settings, access_port_max_macs, port_control: None }
+    }
+
+    /// Lets the check shut ports down; only used when `shutdown_ports` is set.
+    pub fn with_port_control(self, port_control: Arc<PortControl>) -> Self {
+        Self { port_control: Some(port_control), ..self }
+    }
+
+    /// Records the unapproved MACs among `nodes` and returns their violations.
+    pub async fn check(&self, nodes: &[Node]) -> Result<Vec<MacViolation>, DiscoveryError> {
+        let mut violations = self.repo.check_mac_allowlist(nodes, self.access_port_max_macs).await?;
+        for violation in &violations {
+            tracing::warn!(
+                mac = %violation.mac_address,
+                device_id = %violation.device_id,
+                interface_id = ?violation.interface_id,
+                vlan = violation.vlan,
+                first_seen = %violation.first_seen,
+                "MAC not on the allowlist"
+            );
+        }
+        if let Some(port_control) = self.port_control.as_ref().filter(|_| self.settings.shutdown_ports) {
+            for violation in violations.iter_mut().filter(|v| v.port_shutdown_at.is_none()) {
+                self.shut_down(port_control, violation).await?;
+            }
+        }
+        Ok(violations)
+    }
+
+    // Failed or refused shutdowns are logged by port control and retried on the next check
+    async fn shut_down(&self, port_control: &PortControl, violation: &mut MacViolation) -> Result<(), DiscoveryError> {
+        let Some(interface_id) = violation.interface_id else {
+            return Ok(()); // Wireless clients have no port of their own
+        };
+        let interfaces = self.repo.list_interfaces(violation.device_id).await?;
+        let Some(interface) = interfaces.iter().find(|i| i.id == interface_id) else {
+            return Ok(());
+        };
+        let action = PortAction::AdminStatus { up: false };
+        match port_control.apply(violation.device_id, interface.if_index, action, ROGUE_POLICY).await {
+            Ok(_) => {
+                let now = OffsetDateTime::now_utc();
+                self.repo.set_violation_port_shutdown(violation.id, now).await?;
+                violation.port_shutdown_at = Some(now);
+            }
+            Err(e) => tracing::error!(
+                mac = %violation.mac_address,
+                device_id = %violation.device_id,
+                if_index = interface.if_index,
+                error = %e,
+                "Could not shut down port of unapproved MAC"
+            ),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use db::{AllowedMac, AllowlistRepository, MacViolationFilter, MemoryRepository};
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn records_unapproved_macs() {
+        let repo = Arc::new(MemoryRepository::new());
+        let now = OffsetDateTime::now_utc();
+        let entry = AllowedMac {
+            id: Uuid::nil(),
+            mac_prefix: "00:1a:2b".to_string(),
+            location_id: None,
+            vlan: None,
+            description: Some("Corporate laptops".to_string()),
+            created_at: now,
+        };
+        repo.create_allowed_mac(&entry).await.unwrap();
+        let (switch, port) = (Uuid::new_v4(), Uuid::new_v4());
+        let node = |mac: &str| Node {
+            id: Uuid::nil(),
+            mac_address: mac.parse().unwrap(),
+            device_id: switch,
+            interface_id: Some(port),
+            vlan: 10,
+            active: true,
+            first_seen: now,
+            last_seen: now,
+            wireless: None,
+        };
+
+        // Shutdown is requested, but without port control the port is left alone
+        let settings = RogueSettings { shutdown_ports: true };
+        let check = RogueCheck::new(repo.clone(), settings, 4);
+        let violations = check.check(&[node("00:1a:2b:00:00:01"), node("02:00:00:00:00:01")]).await.unwrap();
+
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].interface_id, Some(port));
+        assert_eq!(violations[0].port_shutdown_at, None);
+        assert_eq!(repo.list_mac_violations(&MacViolationFilter::default()).await.unwrap().len(), 1);
+    }
+}
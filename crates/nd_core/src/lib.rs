@@ -3,6 +3,243 @@ use serde::Deserialize;
 #[derive(Debug, Deserialize)]
 pub struct DatabaseSettings {
     pub url: Option<String>,
+    pub run_migrations: Option<bool>, // Apply pending migrations at startup (default: false)
+}
+
+#[derive(Deserialize)]
+pub struct SnmpSettings {
+    pub community: Option<String>,
+    pub write_community: Option<String>, // Required for SNMP SET operations (e.g., PoE control)
+}
+
+// Keep community strings out of logs
+impl std::fmt::Debug for SnmpSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mask = |c: &Option<String>| c.as_ref().map(|_| "********");
+        f.debug_struct("SnmpSettings")
+            .field("community", &mask(&self.community))
+            .field("write_community", &mask(&self.write_community))
+            .finish()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ApiToken {
+    pub user: String, // Recorded as the author of changes made with this token
+    pub token: String,
+}
+
+// Keep bearer tokens out of logs
+impl std::fmt::Debug for ApiToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiToken").field("user", &self.user).field("token", &"********").finish()
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ApiSettings {
+    #[serde(default)]
+    pub tokens: Vec<ApiToken>, // Bearer tokens accepted by write endpoints
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProtectedPort {
+    pub device: Option<std::net::IpAddr>, // Applies to every device when omitted
+    pub interface: String, // ifName/ifDescr; a trailing '*' matches any suffix
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PortControlSettings {
+    #[serde(default = "default_protect_uplinks")]
+    pub protect_uplinks: bool, // Refuse changes on ports leading to other network devices
+    #[serde(default)]
+    pub protected_ports: Vec<ProtectedPort>,
+}
+
+fn default_protect_uplinks() -> bool {
+    true
+}
+
+impl Default for PortControlSettings {
+    fn default() -> Self {
+        Self { protect_uplinks: default_protect_uplinks(), protected_ports: Vec::new() }
+    }
+}
+
+/// Periodic collection from every known device.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DiscoverySettings {
+    #[serde(default = "default_discovery_interval")]
+    pub interval_minutes: u64, // Between the starts of two sweeps over all devices
+}
+
+fn default_discovery_interval() -> u64 {
+    60
+}
+
+impl Default for DiscoverySettings {
+    fn default() -> Self {
+        Self { interval_minutes: default_discovery_interval() }
+    }
+}
+
+/// How long historical data is kept; a missing age keeps that data forever.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RetentionSettings {
+    pub node_days: Option<u32>, // MAC sightings not seen for this long are deleted
+    pub device_history_days: Option<u32>,
+    pub port_change_days: Option<u32>,
+    pub arp_days: Option<u32>, // ARP entries not refreshed for this long are deleted
+    pub node_move_days: Option<u32>, // Port moves of MAC sightings
+    pub anomaly_days: Option<u32>, // Resolved anomalies last detected this long ago; open ones stay
+    pub mac_violation_days: Option<u32>, // Allowlist violations not seen for this long
+    pub archive_device_days: Option<u32>, // Devices not seen for this long are retired
+    #[serde(default = "default_retention_interval")]
+    pub interval_minutes: u64, // Between pruning runs of the server
+    #[serde(default = "default_retention_batch")]
+    pub batch_size: u32, // Rows per statement, to keep locks short
+    #[serde(default)]
+    pub dry_run: bool, // Only report what would be removed
+}
+
+fn default_retention_interval() -> u64 {
+    60
+}
+
+fn default_retention_batch() -> u32 {
+    1000
+}
+
+impl Default for RetentionSettings {
+    fn default() -> Self {
+        Self {
+            node_days: None,
+            device_history_days: None,
+            port_change_days: None,
+            arp_days: None,
+            node_move_days: None,
+            anomaly_days: None,
+            mac_violation_days: None,
+            archive_device_days: None,
+            interval_minutes: default_retention_interval(),
+            batch_size: default_retention_batch(),
+            dry_run: false,
+        }
+    }
+}
+
+/// Thresholds for flagging duplicate IPs and misbehaving MACs.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AnomalySettings {
+    #[serde(default = "default_anomaly_interval")]
+    pub interval_minutes: u64, // Between detection runs of the server
+    #[serde(default = "default_anomaly_window")]
+    pub window_minutes: u32, // Sightings and port moves older than this are ignored
+    #[serde(default = "default_flap_moves")]
+    pub flap_moves: u32, // Port moves within the window that make a MAC flap
+    #[serde(default = "default_access_port_max_macs")]
+    pub access_port_max_macs: u32, // Ports with more active MACs are uplinks, not access ports
+}
+
+fn default_anomaly_interval() -> u64 {
+    15
+}
+
+fn default_anomaly_window() -> u32 {
+    60
+}
+
+fn default_flap_moves() -> u32 {
+    3
+}
+
+fn default_access_port_max_macs() -> u32 {
+    4
+}
+
+impl Default for AnomalySettings {
+    fn default() -> Self {
+        Self {
+            interval_minutes: default_anomaly_interval(),
+            window_minutes: default_anomaly_window(),
+            flap_moves: default_flap_moves(),
+            access_port_max_macs: default_access_port_max_macs(),
+        }
+    }
+}
+
+/// What discovery does when a MAC missing from the allowlist shows up on an access port.
+/// Access ports are told from uplinks by `anomalies.access_port_max_macs`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RogueSettings {
+    #[serde(default)]
+    pub shutdown_ports: bool, // Shut the port through port control; off unless set explicitly
+}
+
+/// How subnet usage is judged from address sightings.
+#[derive(Debug, Clone, Deserialize)]
+pub struct IpamSettings {
+    #[serde(default = "default_ipam_window")]
+    pub window_days: u32, // Addresses not seen for this long count as free (and stale)
+}
+
+fn default_ipam_window() -> u32 {
+    30
+}
+
+impl Default for IpamSettings {
+    fn default() -> Self {
+        Self { window_days: default_ipam_window() }
+    }
+}
+
+/// Reverse DNS lookups of device and node addresses during discovery.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DnsSettings {
+    pub resolver: Option<String>, // host:port; the nameservers in /etc/resolv.conf when unset
+    #[serde(default = "default_dns_timeout")]
+    pub timeout_ms: u64,
+    #[serde(default = "default_dns_min_ttl")]
+    pub min_ttl_secs: u32, // Answers are cached at least this long, whatever their TTL
+    #[serde(default = "default_dns_max_ttl")]
+    pub max_ttl_secs: u32,
+    #[serde(default = "default_dns_negative_ttl")]
+    pub negative_ttl_secs: u32, // Addresses without a PTR record and failed lookups
+    #[serde(default = "default_dns_cache_entries")]
+    pub cache_entries: usize,
+}
+
+fn default_dns_timeout() -> u64 {
+    2000
+}
+
+fn default_dns_min_ttl() -> u32 {
+    60
+}
+
+fn default_dns_max_ttl() -> u32 {
+    86_400
+}
+
+fn default_dns_negative_ttl() -> u32 {
+    300
+}
+
+fn default_dns_cache_entries() -> usize {
+    100_000
+}
+
+impl Default for DnsSettings {
+    fn default() -> Self {
+        Self {
+            resolver: None,
+            timeout_ms: default_dns_timeout(),
+            min_ttl_secs: default_dns_min_ttl(),
+            max_ttl_secs: default_dns_max_ttl(),
+            negative_ttl_secs: default_dns_negative_ttl(),
+            cache_entries: default_dns_cache_entries(),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -10,6 +247,15 @@ pub struct Settings {
     pub debug: Option<bool>,
     pub database: Option<DatabaseSettings>,
     pub log_level: Option<String>,
+    pub snmp: Option<SnmpSettings>,
+    pub api: Option<ApiSettings>,
+    pub port_control: Option<PortControlSettings>,
+    pub discovery: Option<DiscoverySettings>,
+    pub retention: Option<RetentionSettings>,
+    pub anomalies: Option<AnomalySettings>,
+    pub rogue: Option<RogueSettings>,
+    pub ipam: Option<IpamSettings>,
+    pub dns: Option<DnsSettings>,
 }
 
 impl Settings {
@@ -33,4 +279,10 @@ impl Settings {
 // pub use discovery::{DiscoveryJob, DiscoveryResult, DiscoveryManager, DiscoveryTarget, DiscoveryError, SnmpCredentials};
 
 mod snmp;
-pub use snmp::{snmp_get_v2c, SnmpError, SnmpValueOwned};
+pub use snmp::{
+    format_oid, oid_suffix, parse_oid, snmp_get_v2c, snmp_set_v2c, snmp_walk_v2c, SnmpClient, SnmpError, SnmpSession, SnmpValueOwned,
+    VarBind,
+};
+
+mod simulator;
+pub use simulator::{parse_snmprec, SnmpSimulator};
@@ -0,0 +1,236 @@
+// crates/nd_core/src/simulator.rs
+//! In-process SNMP agent that replays recorded walks.
+//!
+//! Recordings use the snmpsim `.snmprec` format, one variable binding per line:
+//! `OID|TAG|VALUE`, where TAG is the BER type number (2 Integer, 4 OctetString,
+//! 5 Null, 6 OID, 64 IpAddress, 65 Counter32, 66 Gauge32, 67 TimeTicks,
+//! 68 Opaque, 70 Counter64). A tag suffixed with `x` carries a hex-encoded value.
+//! As in snmpsim, each community selects its own recording, so community
+//! indexed instances are recorded under e.g. `public@20`. SETs update the
+//! recording in place, so a later GET reads back the written value.
+
+use crate::snmp::{format_oid, oid_suffix, parse_oid, SnmpClient, SnmpError, SnmpValueOwned, VarBind};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+
+/// noCreation: the agent refuses to create a variable that does not exist.
+const ERROR_STATUS_NO_CREATION: u32 = 11;
+
+#[derive(Debug, Clone)]
+pub struct SnmpSimulator {
+    recordings: Arc<RwLock<HashMap<String, Vec<VarBind>>>>,
+    community: String,
+}
+
+impl SnmpSimulator {
+    /// Creates a simulator answering for `community` with the given recording.
+    pub fn from_snmprec(community: &str, recording: &str) -> Result<Self, SnmpError> {
+        let simulator = Self { recordings: Arc::new(RwLock::new(HashMap::new())), community: community.to_string() };
+        simulator.with_recording(community, recording)
+    }
+
+    /// Adds a recording for another community (e.g. a `community@vlan` instance).
+    pub fn with_recording(self, community: &str, recording: &str) -> Result<Self, SnmpError> {
+        let varbinds = parse_snmprec(recording)?;
+        self.recordings
+            .write()
+            .map_err(|_| SnmpError::Snmp("Simulator state poisoned".to_string()))?
+            .insert(community.to_string(), varbinds);
+        Ok(self)
+    }
+
+    /// Runs `f` against the recording of this simulator's community.
+    fn with_varbinds<T>(&self, f: impl FnOnce(&mut Vec<VarBind>) -> Result<T, SnmpError>) -> Result<T, SnmpError> {
+        let mut recordings = self
+            .recordings
+            .write()
+            .map_err(|_| SnmpError::Snmp("Simulator state poisoned".to_string()))?;
+        // An unknown community times out on a real agent
+        let varbinds = recordings
+            .get_mut(&self.community)
+            .ok_or_else(|| SnmpError::Snmp(format!("No response for community {}", self.community)))?;
+        f(varbinds)
+    }
+
+    fn walk_sync(&self, base_oid: &[u32]) -> Result<Vec<VarBind>, SnmpError> {
+        self.with_varbinds(|varbinds| {
+            Ok(varbinds
+                .iter()
+                .filter(|(oid, _)| oid_suffix(oid, base_oid).is_some_and(|suffix| !suffix.is_empty()))
+                .cloned()
+                .collect())
+        })
+    }
+
+    fn get_sync(&self, oid: &[u32]) -> Result<SnmpValueOwned, SnmpError> {
+        self.with_varbinds(|varbinds| {
+            varbinds
+                .iter()
+                .find(|(recorded_oid, _)| recorded_oid == oid)
+                .map(|(_, value)| value.clone())
+                .ok_or(SnmpError::NoVarBindValue)
+        })
+    }
+
+    fn set_sync(&self, oid: &[u32], value: SnmpValueOwned) -> Result<(), SnmpError> {
+        self.with_varbinds(|varbinds| {
+            let (_, recorded_value) = varbinds
+                .iter_mut()
+                .find(|(recorded_oid, _)| recorded_oid == oid)
+                .ok_or(SnmpError::SetFailed { status: ERROR_STATUS_NO_CREATION, index: 1 })?;
+            *recorded_value = value;
+            Ok(())
+        })
+    }
+}
+
+impl SnmpClient for SnmpSimulator {
+    fn walk(&self, base_oid: &[u32]) -> impl Future<Output = Result<Vec<VarBind>, SnmpError>> + Send {
+        let result = self.walk_sync(base_oid);
+        async move { result }
+    }
+
+    fn get(&self, oid: &[u32]) -> impl Future<Output = Result<SnmpValueOwned, SnmpError>> + Send {
+        let result = self.get_sync(oid);
+        async move { result }
+    }
+
+    fn set(&self, oid: &[u32], value: SnmpValueOwned) -> impl Future<Output = Result<(), SnmpError>> + Send {
+        let result = self.set_sync(oid, value);
+        async move { result }
+    }
+
+    fn with_community_index(&self, index: &str) -> Self {
+        Self { recordings: Arc::clone(&self.recordings), community: format!("{}@{}", self.community, index) }
+    }
+}
+
+/// Parses a `.snmprec` recording into variable bindings sorted by OID.
+pub fn parse_snmprec(recording: &str) -> Result<Vec<VarBind>, SnmpError> {
+    let mut varbinds = Vec::new();
+    for (line_number, line) in recording.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |reason: &str| SnmpError::InvalidRecording { line: line_number + 1, reason: reason.to_string() };
+
+        let mut fields = line.splitn(3, '|');
+        let (Some(oid_text), Some(tag), Some(value)) = (fields.next(), fields.next(), fields.next()) else {
+            return Err(invalid("expected OID|TAG|VALUE"));
+        };
+        let oid = parse_oid(oid_text).ok_or_else(|| invalid("malformed OID"))?;
+        let (tag, is_hex) = match tag.strip_suffix('x') {
+            Some(tag) => (tag, true),
+            None => (tag, false),
+        };
+        let bytes = if is_hex {
+            decode_hex(value).ok_or_else(|| invalid("malformed hex value"))?
+        } else {
+            value.as_bytes().to_vec()
+        };
+        let text = || String::from_utf8_lossy(&bytes).to_string();
+
+        let parsed = match tag {
+            "2" => text().parse().ok().map(SnmpValueOwned::Integer),
+            "4" => Some(SnmpValueOwned::OctetString(bytes.clone())),
+            "5" => Some(SnmpValueOwned::Null),
+            "6" => parse_oid(&text()).map(SnmpValueOwned::ObjectIdentifier),
+            "64" if is_hex => <[u8; 4]>::try_from(bytes.as_slice()).ok().map(SnmpValueOwned::IpAddress),
+            "64" => text().parse::<std::net::Ipv4Addr>().ok().map(|ip| SnmpValueOwned::IpAddress(ip.octets())),
+            "65" => text().parse().ok().map(SnmpValueOwned::Counter32),
+            "66" => text().parse().ok().map(SnmpValueOwned::Gauge32),
+            "67" => text().parse().ok().map(SnmpValueOwned::TimeTicks),
+            "68" => Some(SnmpValueOwned::Opaque(bytes.clone())),
+            "70" => text().parse().ok().map(SnmpValueOwned::Counter64),
+            _ => return Err(invalid(&format!("unsupported tag {}", tag))),
+        };
+        let value = parsed.ok_or_else(|| invalid(&format!("value does not match tag {}", tag)))?;
+        varbinds.push((oid, value));
+    }
+    varbinds.sort_by(|(a, _), (b, _)| a.cmp(b));
+    varbinds.dedup_by(|(a, _), (b, _)| {
+        if a == b {
+            tracing::warn!(oid = %format_oid(a), "Duplicate OID in SNMP recording; keeping the first");
+        }
+        a == b
+    });
+    Ok(varbinds)
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim();
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECORDING: &str = "\
+# sysDescr and a short ifDescr column
+1.3.6.1.2.1.1.1.0|4|Test agent
+1.3.6.1.2.1.2.2.1.2.2|4|Gi0/2
+1.3.6.1.2.1.2.2.1.2.1|4|Gi0/1
+1.3.6.1.2.1.2.2.1.6.1|4x|001122aabbcc
+1.3.6.1.2.1.4.20.1.1.10.0.0.1|64|10.0.0.1
+";
+
+    #[tokio::test]
+    async fn walks_subtree_in_oid_order() {
+        let simulator = SnmpSimulator::from_snmprec("public", RECORDING).unwrap();
+        let rows = simulator.walk(&[1, 3, 6, 1, 2, 1, 2, 2, 1, 2]).await.unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                (vec![1, 3, 6, 1, 2, 1, 2, 2, 1, 2, 1], SnmpValueOwned::OctetString(b"Gi0/1".to_vec())),
+                (vec![1, 3, 6, 1, 2, 1, 2, 2, 1, 2, 2], SnmpValueOwned::OctetString(b"Gi0/2".to_vec())),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn decodes_hex_and_ip_values() {
+        let simulator = SnmpSimulator::from_snmprec("public", RECORDING).unwrap();
+        let mac = simulator.walk(&[1, 3, 6, 1, 2, 1, 2, 2, 1, 6]).await.unwrap();
+        assert_eq!(mac[0].1, SnmpValueOwned::OctetString(vec![0x00, 0x11, 0x22, 0xaa, 0xbb, 0xcc]));
+        let ip = simulator.walk(&[1, 3, 6, 1, 2, 1, 4, 20]).await.unwrap();
+        assert_eq!(ip[0].1, SnmpValueOwned::IpAddress([10, 0, 0, 1]));
+    }
+
+    #[tokio::test]
+    async fn community_index_selects_its_own_recording() {
+        let simulator = SnmpSimulator::from_snmprec("public", RECORDING)
+            .unwrap()
+            .with_recording("public@20", "1.3.6.1.2.1.17.1.1.0|4x|0011223344aa")
+            .unwrap();
+        let vlan20 = simulator.with_community_index("20");
+        assert_eq!(vlan20.walk(&[1, 3, 6, 1, 2, 1, 17]).await.unwrap().len(), 1);
+        assert!(simulator.with_community_index("30").walk(&[1, 3, 6, 1]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn set_is_read_back_and_cannot_create() {
+        let simulator = SnmpSimulator::from_snmprec("public", RECORDING).unwrap();
+        let sys_descr = [1, 3, 6, 1, 2, 1, 1, 1, 0];
+        simulator.set(&sys_descr, SnmpValueOwned::OctetString(b"Renamed".to_vec())).await.unwrap();
+        assert_eq!(simulator.get(&sys_descr).await.unwrap(), SnmpValueOwned::OctetString(b"Renamed".to_vec()));
+
+        let missing = [1, 3, 6, 1, 2, 1, 1, 99, 0];
+        let err = simulator.set(&missing, SnmpValueOwned::Integer(1)).await.unwrap_err();
+        assert!(matches!(err, SnmpError::SetFailed { status: ERROR_STATUS_NO_CREATION, .. }));
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        let err = parse_snmprec("1.3.6.1.2.1.1.1.0|2|not-a-number").unwrap_err();
+        assert!(matches!(err, SnmpError::InvalidRecording { line: 1, .. }));
+    }
+}
@@ -1,9 +1,17 @@
 // crates/nd_core/src/snmp.rs
 use snmp::{SyncSession, Value};
-use std::net::ToSocketAddrs;
+use std::future::Future;
+use std::net::{Ipv4Addr, SocketAddr, ToSocketAddrs};
 use std::time::Duration;
 use tokio::task;
 
+/// Standard SNMP agent port.
+const SNMP_PORT: u16 = 161;
+/// Per-request timeout used for every session.
+const SNMP_TIMEOUT: Duration = Duration::from_secs(5);
+/// Number of rows requested per GETBULK round-trip during a walk.
+const WALK_MAX_REPETITIONS: u32 = 25;
+
 // Define our own owned version of snmp::Value
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SnmpValueOwned {
@@ -20,6 +28,69 @@ pub enum SnmpValueOwned {
     // No error variants here
 }
 
+impl SnmpValueOwned {
+    /// Returns the value as a signed integer for any of the numeric types.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            SnmpValueOwned::Integer(i) => Some(*i),
+            SnmpValueOwned::Counter32(c) | SnmpValueOwned::Gauge32(c) | SnmpValueOwned::TimeTicks(c) => Some(i64::from(*c)),
+            SnmpValueOwned::Counter64(c) => i64::try_from(*c).ok(),
+            _ => None,
+        }
+    }
+
+    /// Returns the raw bytes of string-like values.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            SnmpValueOwned::OctetString(b) | SnmpValueOwned::Opaque(b) => Some(b),
+            SnmpValueOwned::IpAddress(ip) => Some(ip),
+            _ => None,
+        }
+    }
+
+    /// Returns an OctetString as text, trimming trailing NULs and whitespace.
+    pub fn as_string(&self) -> Option<String> {
+        match self {
+            SnmpValueOwned::OctetString(b) => {
+                let text = String::from_utf8_lossy(b);
+                Some(text.trim_end_matches(['\0', ' ', '\r', '\n']).to_string())
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the value as an IPv4 address (IpAddress or a 4-byte OctetString).
+    pub fn as_ipv4(&self) -> Option<Ipv4Addr> {
+        match self {
+            SnmpValueOwned::IpAddress(ip) => Some(Ipv4Addr::from(*ip)),
+            SnmpValueOwned::OctetString(b) if b.len() == 4 => Some(Ipv4Addr::new(b[0], b[1], b[2], b[3])),
+            _ => None,
+        }
+    }
+}
+
+/// A single variable binding returned by a walk: the full OID and its value.
+pub type VarBind = (Vec<u32>, SnmpValueOwned);
+
+/// Returns the part of `oid` that follows `base`, or `None` if `oid` is not under `base`.
+pub fn oid_suffix<'a>(oid: &'a [u32], base: &[u32]) -> Option<&'a [u32]> {
+    oid.strip_prefix(base)
+}
+
+/// Parses a dotted OID string such as "1.3.6.1.2.1.1.1.0" (a leading dot is allowed).
+pub fn parse_oid(text: &str) -> Option<Vec<u32>> {
+    text.trim()
+        .trim_start_matches('.')
+        .split('.')
+        .map(|part| part.parse::<u32>().ok())
+        .collect()
+}
+
+/// Formats an OID as a dotted string.
+pub fn format_oid(oid: &[u32]) -> String {
+    oid.iter().map(|part| part.to_string()).collect::<Vec<_>>().join(".")
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum SnmpError {
     #[error("SNMP communication error: {0}")]
@@ -32,6 +103,113 @@ pub enum SnmpError {
     OidMismatch { expected: Vec<u32>, got: Vec<u32> },
     #[error("Response contained no variable bindings or null value")]
     NoVarBindValue,
+    #[error("Invalid SNMP recording at line {line}: {reason}")]
+    InvalidRecording { line: usize, reason: String },
+    #[error("SET rejected by agent (error status {status}, index {index})")]
+    SetFailed { status: u32, index: u32 },
+    #[error("Value type cannot be sent in a SET: {0:?}")]
+    UnsupportedSetValue(SnmpValueOwned),
+}
+
+/// Read access to an SNMP agent, implemented by live sessions and the simulator.
+/// Collectors are written against this trait so they can be tested with recorded walks.
+pub trait SnmpClient: Send + Sync {
+    /// Walks the subtree under `base_oid`, see [`snmp_walk_v2c`].
+    fn walk(&self, base_oid: &[u32]) -> impl Future<Output = Result<Vec<VarBind>, SnmpError>> + Send;
+
+    /// Reads a single OID, see [`snmp_get_v2c`].
+    fn get(&self, oid: &[u32]) -> impl Future<Output = Result<SnmpValueOwned, SnmpError>> + Send;
+
+    /// Writes a single OID, see [`snmp_set_v2c`].
+    fn set(&self, oid: &[u32], value: SnmpValueOwned) -> impl Future<Output = Result<(), SnmpError>> + Send;
+
+    /// Returns a client for the same agent using community string indexing
+    /// (`community@index`), as Cisco uses for per-VLAN BRIDGE-MIB instances.
+    fn with_community_index(&self, index: &str) -> Self
+    where
+        Self: Sized;
+}
+
+/// A live SNMPv2c agent.
+#[derive(Debug, Clone)]
+pub struct SnmpSession {
+    target_addr: String,
+    community: Vec<u8>,
+}
+
+impl SnmpSession {
+    pub fn new(target_addr: impl Into<String>, community: &[u8]) -> Self {
+        Self { target_addr: target_addr.into(), community: community.to_vec() }
+    }
+
+    pub fn target_addr(&self) -> &str {
+        &self.target_addr
+    }
+}
+
+impl SnmpClient for SnmpSession {
+    fn walk(&self, base_oid: &[u32]) -> impl Future<Output = Result<Vec<VarBind>, SnmpError>> + Send {
+        snmp_walk_v2c(&self.target_addr, &self.community, base_oid)
+    }
+
+    fn get(&self, oid: &[u32]) -> impl Future<Output = Result<SnmpValueOwned, SnmpError>> + Send {
+        snmp_get_v2c(&self.target_addr, &self.community, oid)
+    }
+
+    fn set(&self, oid: &[u32], value: SnmpValueOwned) -> impl Future<Output = Result<(), SnmpError>> + Send {
+        snmp_set_v2c(&self.target_addr, &self.community, oid, value)
+    }
+
+    fn with_community_index(&self, index: &str) -> Self {
+        let mut community = self.community.clone();
+        community.push(b'@');
+        community.extend_from_slice(index.as_bytes());
+        Self { target_addr: self.target_addr.clone(), community }
+    }
+}
+
+/// Resolves the target host to a socket address on the SNMP port.
+fn resolve_target(target_addr: &str) -> Result<SocketAddr, SnmpError> {
+    (target_addr, SNMP_PORT)
+        .to_socket_addrs().map_err(|e| SnmpError::Io(e.to_string()))? // Map IO error
+        .next()
+        .ok_or_else(|| SnmpError::Io("Could not resolve target address".to_string()))
+}
+
+fn open_session(target_addr: &str, community: &[u8]) -> Result<SyncSession, SnmpError> {
+    let socket_addr = resolve_target(target_addr)?;
+    SyncSession::new(socket_addr, community, Some(SNMP_TIMEOUT), 0)
+        .map_err(|e| SnmpError::Snmp(format!("{:?}", e)))
+}
+
+fn read_oid(oid: &snmp::ObjectIdentifier) -> Result<Vec<u32>, SnmpError> {
+    // Use a buffer to read the OID parts
+    let mut oid_buf = [0u32; 128]; // ObjectIdentifier::read_name needs a buffer
+    let oid_parts = oid.read_name(&mut oid_buf)
+        .map_err(|e| SnmpError::Snmp(format!("Failed to read OID: {:?}", e)))?;
+    Ok(oid_parts.to_vec())
+}
+
+/// Converts a borrowed `snmp::Value` into our owned representation.
+fn value_to_owned(value: Value) -> Result<SnmpValueOwned, SnmpError> {
+    match value {
+        Value::Null => Ok(SnmpValueOwned::Null),
+        Value::Integer(i) => Ok(SnmpValueOwned::Integer(i)),
+        Value::OctetString(s) => Ok(SnmpValueOwned::OctetString(s.to_vec())),
+        Value::ObjectIdentifier(oid) => Ok(SnmpValueOwned::ObjectIdentifier(read_oid(&oid)?)),
+        Value::IpAddress(ip) => Ok(SnmpValueOwned::IpAddress(ip)),
+        Value::Counter32(c) => Ok(SnmpValueOwned::Counter32(c)),
+        // Handle Gauge32/Unsigned32
+        Value::Unsigned32(g) => Ok(SnmpValueOwned::Gauge32(g)),
+        Value::Timeticks(t) => Ok(SnmpValueOwned::TimeTicks(t)),
+        Value::Opaque(o) => Ok(SnmpValueOwned::Opaque(o.to_vec())),
+        Value::Counter64(c) => Ok(SnmpValueOwned::Counter64(c)),
+        _ => {
+            // For any unhandled variants
+            tracing::warn!("Unhandled SNMP value type");
+            Err(SnmpError::NoVarBindValue)
+        }
+    }
 }
 
 /// Performs an SNMPv2c GET request for a single OID using spawn_blocking.
@@ -47,32 +225,17 @@ pub async fn snmp_get_v2c(
 
     // Use map_err to convert potential errors within spawn_blocking
     task::spawn_blocking(move || -> Result<SnmpValueOwned, SnmpError> { // Add Result return type hint
-        let socket_addr = (target_owned.as_str(), 161u16)
-            .to_socket_addrs().map_err(|e| SnmpError::Io(e.to_string()))? // Map IO error
-            .next()
-            .ok_or_else(|| SnmpError::Io("Could not resolve target address".to_string()))?;
-
-        let mut sess = SyncSession::new(
-            socket_addr,
-            &community_owned,
-            Some(Duration::from_secs(5)),
-            0,
-        ).map_err(|e| SnmpError::Snmp(format!("{:?}", e)))?;
+        let mut sess = open_session(&target_owned, &community_owned)?;
 
         let response = sess.get(&oid_owned).map_err(|e| SnmpError::Snmp(format!("{:?}", e)))?;
 
-        let mut varbinds_iter = response.varbinds.into_iter();
+        let mut varbinds_iter = response.varbinds;
         if let Some((response_oid, value)) = varbinds_iter.next() {
             if varbinds_iter.next().is_some() {
                 tracing::warn!(target = %target_owned, oid = ?oid_owned, "Received multiple varbinds for single GET");
             }
 
-            // Use a buffer to read the OID parts
-            let mut oid_buf = [0u32; 128]; // ObjectIdentifier::read_name needs a buffer
-            let response_oid_parts = response_oid.read_name(&mut oid_buf)
-                .map_err(|e| SnmpError::Snmp(format!("Failed to read OID: {:?}", e)))?;
-            
-            let response_oid_vec = response_oid_parts.to_vec();
+            let response_oid_vec = read_oid(&response_oid)?;
             if response_oid_vec != oid_owned.as_slice() {
                 return Err(SnmpError::OidMismatch {
                     expected: oid_owned,
@@ -80,31 +243,92 @@ pub async fn snmp_get_v2c(
                 });
             }
 
-            match value {
-                Value::Null => Err(SnmpError::NoVarBindValue),
-                Value::Integer(i) => Ok(SnmpValueOwned::Integer(i)),
-                Value::OctetString(s) => Ok(SnmpValueOwned::OctetString(s.to_vec())),
-                Value::ObjectIdentifier(oid) => {
-                    let mut oid_buf = [0u32; 128];
-                    let oid_parts = oid.read_name(&mut oid_buf)
-                        .map_err(|e| SnmpError::Snmp(format!("Failed to read OID: {:?}", e)))?;
-                    Ok(SnmpValueOwned::ObjectIdentifier(oid_parts.to_vec()))
-                },
-                Value::IpAddress(ip) => Ok(SnmpValueOwned::IpAddress(ip)),
-                Value::Counter32(c) => Ok(SnmpValueOwned::Counter32(c)),
-                // Handle Gauge32/Unsigned32
-                Value::Unsigned32(g) => Ok(SnmpValueOwned::Gauge32(g)),
-                Value::Timeticks(t) => Ok(SnmpValueOwned::TimeTicks(t)),
-                Value::Opaque(o) => Ok(SnmpValueOwned::Opaque(o.to_vec())),
-                Value::Counter64(c) => Ok(SnmpValueOwned::Counter64(c)),
-                _ => {
-                    // For any unhandled variants
-                    tracing::warn!("Unhandled SNMP value type");
-                    Err(SnmpError::NoVarBindValue)
-                }
+            match value_to_owned(value)? {
+                SnmpValueOwned::Null => Err(SnmpError::NoVarBindValue),
+                owned => Ok(owned),
             }
         } else {
             Err(SnmpError::NoVarBindValue)
         }
     }).await.map_err(|e| SnmpError::Join(e.to_string()))? // Map JoinError and flatten Result<Result<_,_>,_>
-} 
\ No newline at end of file
+}
+
+/// Walks the subtree under `base_oid` with SNMPv2c GETBULK using spawn_blocking.
+/// Returns every variable binding below the base OID in lexicographic order.
+/// An empty result means the agent does not implement the subtree.
+pub async fn snmp_walk_v2c(
+    target_addr: &str,
+    community: &[u8],
+    base_oid: &[u32],
+) -> Result<Vec<VarBind>, SnmpError> {
+    let target_owned = target_addr.to_string();
+    let community_owned = community.to_vec();
+    let base_owned = base_oid.to_vec();
+
+    task::spawn_blocking(move || -> Result<Vec<VarBind>, SnmpError> {
+        let mut sess = open_session(&target_owned, &community_owned)?;
+        let mut results = Vec::new();
+        let mut cursor = base_owned.clone();
+
+        loop {
+            let response = sess
+                .getbulk(&[cursor.as_slice()], 0, WALK_MAX_REPETITIONS)
+                .map_err(|e| SnmpError::Snmp(format!("{:?}", e)))?;
+
+            let mut received = 0;
+            for (response_oid, value) in response.varbinds {
+                let oid = read_oid(&response_oid)?;
+                // Leaving the subtree (or an agent that does not advance) ends the walk
+                if oid_suffix(&oid, &base_owned).is_none() || oid <= cursor {
+                    return Ok(results);
+                }
+                received += 1;
+                cursor = oid.clone();
+                results.push((oid, value_to_owned(value)?));
+            }
+
+            // endOfMibView/noSuchObject values stop the varbind iterator early
+            if received < WALK_MAX_REPETITIONS {
+                return Ok(results);
+            }
+        }
+    }).await.map_err(|e| SnmpError::Join(e.to_string()))?
+}
+
+/// Performs an SNMPv2c SET of a single OID using spawn_blocking.
+/// Fails with `SetFailed` when the agent rejects the write (e.g. read-only community).
+pub async fn snmp_set_v2c(
+    target_addr: &str,
+    community: &[u8],
+    oid_parts: &[u32],
+    value: SnmpValueOwned,
+) -> Result<(), SnmpError> {
+    let target_owned = target_addr.to_string();
+    let community_owned = community.to_vec();
+    let oid_owned = oid_parts.to_vec();
+
+    task::spawn_blocking(move || -> Result<(), SnmpError> {
+        let mut sess = open_session(&target_owned, &community_owned)?;
+        let set_value = match &value {
+            SnmpValueOwned::Null => Value::Null,
+            SnmpValueOwned::Integer(i) => Value::Integer(*i),
+            SnmpValueOwned::OctetString(s) => Value::OctetString(s),
+            SnmpValueOwned::IpAddress(ip) => Value::IpAddress(*ip),
+            SnmpValueOwned::Counter32(c) => Value::Counter32(*c),
+            SnmpValueOwned::Gauge32(g) => Value::Unsigned32(*g),
+            SnmpValueOwned::TimeTicks(t) => Value::Timeticks(*t),
+            SnmpValueOwned::Opaque(o) => Value::Opaque(o),
+            SnmpValueOwned::Counter64(c) => Value::Counter64(*c),
+            // The crate can only encode OIDs it has parsed itself
+            SnmpValueOwned::ObjectIdentifier(_) => return Err(SnmpError::UnsupportedSetValue(value.clone())),
+        };
+
+        let response = sess
+            .set(&[(oid_owned.as_slice(), set_value)])
+            .map_err(|e| SnmpError::Snmp(format!("{:?}", e)))?;
+        if response.error_status != 0 {
+            return Err(SnmpError::SetFailed { status: response.error_status, index: response.error_index });
+        }
+        Ok(())
+    }).await.map_err(|e| SnmpError::Join(e.to_string()))?
+}
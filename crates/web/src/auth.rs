@@ -0,0 +1,36 @@
+//! Bearer-token authentication for write endpoints.
+//!
+//! Tokens come from the `api.tokens` settings; each maps to the user name
+//! recorded as the author of the changes made with it.
+
+use crate::{ApiError, AppState};
+use axum::{async_trait, extract::FromRequestParts, http::header::AUTHORIZATION, http::request::Parts};
+
+/// The authenticated caller of a request.
+pub(crate) struct AuthUser(pub String);
+
+#[async_trait]
+impl FromRequestParts<AppState> for AuthUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let presented = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(ApiError::Unauthorized)?;
+
+        state
+            .api_tokens
+            .iter()
+            .find(|(_, token)| constant_time_eq(token.as_bytes(), presented.as_bytes()))
+            .map(|(user, _)| AuthUser(user.clone()))
+            .ok_or(ApiError::Unauthorized)
+    }
+}
+
+/// Compares without returning early on the first differing byte.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
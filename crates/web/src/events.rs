@@ -0,0 +1,68 @@
+//! Server-sent events for inventory changes.
+//!
+//! One task follows the database change feed and fans it out over a broadcast channel;
+//! every `GET /api/events` client reads its own receiver.
+
+use crate::AppState;
+use axum::extract::State;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use db::{ChangeEvent, PgPool};
+use futures_util::stream::{self, Stream};
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// Events a slow client may fall behind by before it is told to resync.
+pub(crate) const CHANGE_BUFFER: usize = 1024;
+
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Forwards database changes to `changes` for as long as the server runs, retrying with
+/// backoff while the database is unreachable.
+pub(crate) async fn forward_changes(pool: PgPool, changes: broadcast::Sender<ChangeEvent>) {
+    let mut backoff = Duration::from_secs(1);
+    let mut stream = loop {
+        match db::subscribe_changes(&pool).await {
+            Ok(stream) => break stream,
+            Err(e) => {
+                tracing::warn!(error = %e, retry_in = ?backoff, "Cannot subscribe to changes");
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    };
+    if backoff > Duration::from_secs(1) {
+        let _ = changes.send(ChangeEvent::Resync); // Clients connected meanwhile missed changes
+    }
+    backoff = Duration::from_secs(1);
+    loop {
+        match stream.recv().await {
+            Ok(event) => {
+                backoff = Duration::from_secs(1);
+                let _ = changes.send(event); // Fails only when nobody is listening
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, retry_in = ?backoff, "Change feed failed");
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+}
+
+pub(crate) async fn events_handler(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    tracing::info!("Client subscribed to /api/events");
+    let events = stream::unfold(state.changes.subscribe(), |mut receiver| async move {
+        let change = match receiver.recv().await {
+            Ok(change) => change,
+            Err(RecvError::Lagged(missed)) => {
+                tracing::warn!(missed, "Events client fell behind");
+                ChangeEvent::Resync
+            }
+            Err(RecvError::Closed) => return None,
+        };
+        Some((Event::default().json_data(&change), receiver))
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
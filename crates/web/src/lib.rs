@@ -1,26 +1,60 @@
 use axum::{
-    routing::get,
+    routing::{delete, get, patch, post, put},
     response::{IntoResponse, Response, Json},
-    extract::State,
+    extract::{Path, Query, State},
     http::StatusCode,
     Router,
 };
-use db::{PgPool, DbError, Device};
-use nd_core::Settings;
+use db::{
+    DbPool, Repository, AllowedMac, Anomaly, AnomalyFilter, AnomalyKind, AnomalyReport, ChangeEvent, CustomField, CustomFieldObject, CustomFieldType, DbError, Device, DeviceField, DeviceFilter, DeviceGroup, DeviceHistory, DevicePage,
+    DeviceQuery, DeviceStatus, DeviceType, FieldSources, Interface, InterfacePoe, InvalidMacAddress, Location, LocationKind,
+    DeviceNeighbor, IpHostname, LinkRefresh, LinkSource, LocationRule, MacAddress, MacInfo, MacViolation, MacViolationFilter, MetadataFilter, MetadataUpdate, Node, OuiDatabase, PortChange, RoutingNeighbor, SearchHit, SearchQuery, SearchTerm, StpPort, StpRoot, Subnet, SubnetRefresh, SubnetUsage, TopologyLink, WirelessAp,
+    subnet_usage, usage_window_start,
+    MANUAL_SOURCE,
+};
+use discovery::control::{ControlError, PortAction, PortControl};
+use serde::{Deserialize, Serialize};
+use nd_core::{AnomalySettings, IpamSettings, Settings};
+use std::collections::{BTreeMap, HashSet};
 use std::net::SocketAddr;
+use std::sync::Arc;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tokio::sync::{broadcast, OnceCell};
 use tower_http::trace::{TraceLayer, DefaultMakeSpan};
 use tower_http::cors::{CorsLayer, Any};
+use uuid::Uuid;
+
+mod auth;
+use auth::AuthUser;
+
+mod events;
+use events::{events_handler, forward_changes, CHANGE_BUFFER};
 
 // Define an AppState that holds the database pool
 #[derive(Clone)]
 struct AppState {
-    db_pool: PgPool,
+    repo: Arc<dyn Repository>,
+    port_control: Arc<PortControl>,
+    api_tokens: Arc<Vec<(String, String)>>, // (user, bearer token)
+    changes: broadcast::Sender<ChangeEvent>,
+    anomalies: Arc<AnomalySettings>, // Thresholds for detection runs started over the API
+    vendors: Arc<OnceCell<OuiDatabase>>, // Loaded on first use; `nd-rust oui import` takes effect on restart
+    ipam: Arc<IpamSettings>,
+}
+
+impl AppState {
+    async fn vendors(&self) -> Result<&OuiDatabase, ApiError> {
+        Ok(self.vendors.get_or_try_init(|| OuiDatabase::load(self.repo.as_ref())).await?)
+    }
 }
 
 // Define a custom error type for API responses
 #[derive(Debug)]
 enum ApiError {
     DbError(DbError),
+    ControlError(ControlError),
+    Unauthorized,
+    #[allow(dead_code)] // No handler reports non-database failures yet
     InternalError(String),
 }
 
@@ -28,8 +62,9 @@ enum ApiError {
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         let (status, error_message) = match self {
-            ApiError::DbError(db_error) => match db_error {
+            ApiError::DbError(db_error) | ApiError::ControlError(ControlError::Db(db_error)) => match db_error {
                 DbError::NotFound => (StatusCode::NOT_FOUND, "Resource not found".to_string()),
+                DbError::InvalidQuery(msg) => (StatusCode::BAD_REQUEST, msg),
                 DbError::QueryFailed(e) => {
                     tracing::error!(error = %e, "Database query failed");
                     (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string())
@@ -37,6 +72,21 @@ impl IntoResponse for ApiError {
                 // Add other DbError variants as needed
                 _ => (StatusCode::INTERNAL_SERVER_ERROR, "An unexpected database error occurred".to_string()),
             },
+            ApiError::ControlError(e @ (ControlError::Protected(_) | ControlError::InvalidValue(_))) => {
+                let status = match e {
+                    ControlError::Protected(_) => StatusCode::FORBIDDEN,
+                    _ => StatusCode::UNPROCESSABLE_ENTITY,
+                };
+                (status, e.to_string())
+            }
+            ApiError::ControlError(ControlError::NoWriteCommunity) => {
+                (StatusCode::SERVICE_UNAVAILABLE, "Port control is not configured".to_string())
+            }
+            ApiError::ControlError(e) => {
+                tracing::warn!(error = %e, "Port change failed on device");
+                (StatusCode::BAD_GATEWAY, e.to_string())
+            }
+            ApiError::Unauthorized => (StatusCode::UNAUTHORIZED, "Missing or invalid bearer token".to_string()),
             ApiError::InternalError(msg) => {
                 tracing::error!(error = %msg, "Internal server error");
                 (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string())
@@ -53,31 +103,1131 @@ impl From<DbError> for ApiError {
     }
 }
 
+impl From<ControlError> for ApiError {
+    fn from(err: ControlError) -> Self {
+        ApiError::ControlError(err)
+    }
+}
+
 // Basic root handler for health check
 async fn root_handler() -> &'static str {
     "nd-rust API Server is running"
 }
 
-// Handler to list devices
+// Query parameters of /api/devices; all optional
+#[derive(Debug, Default, Deserialize)]
+struct ListDevicesParams {
+    vendor: Option<String>,
+    model: Option<String>,
+    status: Option<String>,
+    device_type: Option<String>,
+    include_retired: Option<bool>, // retired/decommissioned devices are hidden by default
+    subnet: Option<String>,      // CIDR, e.g. 10.1.0.0/16
+    seen_after: Option<String>,  // RFC 3339
+    seen_before: Option<String>, // RFC 3339
+    q: Option<String>,           // free text on sys_name / sys_descr
+    location: Option<Uuid>,      // in this location or below it
+    group: Option<Uuid>,
+    tag: Option<String>,         // comma-separated, all must be set
+    field: Option<String>,       // comma-separated name:value custom field values
+    sort: Option<String>,
+    order: Option<String>, // asc | desc
+    limit: Option<u32>,
+    cursor: Option<String>,
+}
+
+impl ListDevicesParams {
+    fn into_query(self) -> Result<DeviceQuery, DbError> {
+        let invalid = |name: &str, value: &str| DbError::InvalidQuery(format!("invalid {} '{}'", name, value));
+        let timestamp = |name: &str, value: Option<String>| {
+            value
+                .map(|v| OffsetDateTime::parse(&v, &Rfc3339).map_err(|_| invalid(name, &v)))
+                .transpose()
+        };
+        Ok(DeviceQuery {
+            filter: DeviceFilter {
+                vendor: self.vendor,
+                model: self.model,
+                status: self
+                    .status
+                    .map(|v| DeviceStatus::try_from(v.clone()).map_err(|_| invalid("status", &v)))
+                    .transpose()?,
+                device_type: self
+                    .device_type
+                    .map(|v| DeviceType::try_from(v.clone()).map_err(|_| invalid("device_type", &v)))
+                    .transpose()?,
+                include_retired: self.include_retired.unwrap_or(false),
+                subnet: self.subnet.map(|v| v.parse().map_err(|_| invalid("subnet", &v))).transpose()?,
+                seen_after: timestamp("seen_after", self.seen_after)?,
+                seen_before: timestamp("seen_before", self.seen_before)?,
+                search: self.q.filter(|q| !q.is_empty()),
+                location_id: self.location,
+                group_id: self.group,
+                metadata: metadata_filter(self.tag, self.field)?,
+            },
+            sort: self.sort.as_deref().map(str::parse).transpose()?.unwrap_or_default(),
+            direction: self.order.as_deref().map(str::parse).transpose()?.unwrap_or_default(),
+            limit: self.limit,
+            cursor: self.cursor,
+        })
+    }
+}
+
+// Parses the `tag` and `field` query parameters shared by device and interface listings
+fn metadata_filter(tag: Option<String>, field: Option<String>) -> Result<MetadataFilter, DbError> {
+    let tags = tag.iter().flat_map(|t| t.split(',')).filter(|t| !t.is_empty()).map(str::to_string).collect();
+    let custom_fields = field
+        .iter()
+        .flat_map(|f| f.split(','))
+        .filter(|f| !f.is_empty())
+        .map(|f| {
+            f.split_once(':')
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .ok_or_else(|| DbError::InvalidQuery(format!("invalid field filter '{}', expected name:value", f)))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(MetadataFilter { tags, custom_fields })
+}
+
+// Handler to list devices, one page at a time
 async fn list_devices_handler(
-    State(state): State<AppState>
-) -> Result<Json<Vec<Device>>, ApiError> {
-    tracing::info!("Handling request for /api/devices");
-    let devices = db::list_devices(&state.db_pool).await?;
-    Ok(Json(devices))
+    State(state): State<AppState>,
+    Query(params): Query<ListDevicesParams>,
+) -> Result<Json<DevicePage>, ApiError> {
+    tracing::info!(?params, "Handling request for /api/devices");
+    let page = state.repo.query_devices(&params.into_query()?).await?;
+    Ok(Json(page))
 }
 
-// Function to create and run the Axum server
-pub async fn run_server(pool: PgPool, settings: &Settings) -> Result<(), Box<dyn std::error::Error>> {
-    let app_state = AppState { db_pool: pool };
+// Handler to override device attributes by hand, e.g. `{"hostname": "core-1", "vendor": null}`.
+// Overridden values are kept by discovery; null clears the override and the value.
+// `status` sets the lifecycle, e.g. `{"status": "retired"}`, and cannot be cleared.
+async fn update_device_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(device_id): Path<Uuid>,
+    Json(mut request): Json<BTreeMap<String, Option<String>>>,
+) -> Result<Json<Device>, ApiError> {
+    tracing::info!(%device_id, user = %user.0, "Handling device override request");
+    let status = request
+        .remove("status")
+        .map(|v| {
+            let v = v.unwrap_or_default();
+            DeviceStatus::try_from(v.clone()).map_err(|_| DbError::InvalidQuery(format!("invalid status '{}'", v)))
+        })
+        .transpose()?;
+    let values = request
+        .into_iter()
+        .map(|(field, value)| Ok((field.parse::<DeviceField>()?, value)))
+        .collect::<Result<Vec<_>, DbError>>()?;
+    let source = format!("{}:{}", MANUAL_SOURCE, user.0);
+    let device = state.repo.update_device(device_id, &values, status, &source).await?;
+    Ok(Json(device))
+}
+
+// Handler to show which source last set each attribute of a device
+async fn get_field_sources_handler(
+    State(state): State<AppState>,
+    Path(device_id): Path<Uuid>,
+) -> Result<Json<FieldSources>, ApiError> {
+    tracing::info!(%device_id, "Handling request for /api/devices/:id/field-sources");
+    let sources = state.repo.get_device_field_sources(device_id).await?;
+    Ok(Json(sources))
+}
+
+// Handler to show the change timeline of a device (upgrades, serial swaps, status changes)
+async fn list_device_history_handler(
+    State(state): State<AppState>,
+    Path(device_id): Path<Uuid>,
+) -> Result<Json<Vec<DeviceHistory>>, ApiError> {
+    tracing::info!(%device_id, "Handling request for /api/devices/:id/history");
+    let history = state.repo.list_device_history(device_id).await?;
+    Ok(Json(history))
+}
+
+// Handler to list a device's BGP peers and OSPF neighbors
+async fn list_routing_neighbors_handler(
+    State(state): State<AppState>,
+    Path(device_id): Path<Uuid>,
+) -> Result<Json<Vec<RoutingNeighbor>>, ApiError> {
+    tracing::info!(%device_id, "Handling request for /api/devices/:id/routing-neighbors");
+    let neighbors = state.repo.list_routing_neighbors(device_id).await?;
+    Ok(Json(neighbors))
+}
+
+// Handler to list the devices a device sees over LLDP and CDP
+async fn list_device_neighbors_handler(
+    State(state): State<AppState>,
+    Path(device_id): Path<Uuid>,
+) -> Result<Json<Vec<DeviceNeighbor>>, ApiError> {
+    tracing::info!(%device_id, "Handling request for /api/devices/:id/neighbors");
+    let neighbors = state.repo.list_device_neighbors(device_id).await?;
+    Ok(Json(neighbors))
+}
+
+// Spanning tree view of one instance (VLAN) on a device
+#[derive(Debug, Serialize)]
+struct StpInstanceView {
+    instance: i32,
+    protocol: String,
+    vlans: Vec<i32>,
+    bridge_id: String,
+    designated_root: String,
+    is_root: bool,
+    root_device_id: Option<Uuid>,
+    root_port_if_index: Option<i32>,
+    root_cost: Option<i32>,
+    blocking_ports: Vec<i32>, // ifIndexes
+    ports: Vec<StpPort>,
+}
+
+// Handler to show the spanning tree root and blocking ports per instance
+async fn get_stp_handler(
+    State(state): State<AppState>,
+    Path(device_id): Path<Uuid>,
+) -> Result<Json<Vec<StpInstanceView>>, ApiError> {
+    tracing::info!(%device_id, "Handling request for /api/devices/:id/stp");
+    let instances = state.repo.list_stp_instances(device_id).await?;
+    let ports = state.repo.list_stp_ports(device_id).await?;
+
+    let views = instances
+        .into_iter()
+        .map(|instance| {
+            let instance_ports: Vec<StpPort> =
+                ports.iter().filter(|p| p.instance == instance.instance).cloned().collect();
+            StpInstanceView {
+                instance: instance.instance,
+                is_root: instance.bridge_id == instance.designated_root,
+                blocking_ports: instance_ports
+                    .iter()
+                    .filter(|p| p.state == "blocking")
+                    .map(|p| p.if_index)
+                    .collect(),
+                protocol: instance.protocol,
+                vlans: instance.vlans,
+                bridge_id: instance.bridge_id,
+                designated_root: instance.designated_root,
+                root_device_id: instance.root_device_id,
+                root_port_if_index: instance.root_port_if_index,
+                root_cost: instance.root_cost,
+                ports: instance_ports,
+            }
+        })
+        .collect();
+    Ok(Json(views))
+}
+
+// Query parameters of /api/stp/roots
+#[derive(Debug, Default, Deserialize)]
+struct ListStpRootsParams {
+    vlan: Option<i32>, // trees carrying this VLAN; all trees when unset
+}
+
+// Handler to show which bridge is root for each spanning tree instance across devices
+async fn list_stp_roots_handler(
+    State(state): State<AppState>,
+    Query(params): Query<ListStpRootsParams>,
+) -> Result<Json<Vec<StpRoot>>, ApiError> {
+    tracing::info!(?params, "Handling request for /api/stp/roots");
+    let mut roots = state.repo.list_stp_roots().await?;
+    if let Some(vlan) = params.vlan {
+        // A device's instance 0 carries the VLANs none of its other trees do
+        let carried_elsewhere: HashSet<Uuid> = roots
+            .iter()
+            .filter(|r| r.instance != 0 && r.vlans.contains(&vlan))
+            .flat_map(|r| r.device_ids.iter().copied())
+            .collect();
+        roots.retain(|r| match r.instance {
+            0 => r.device_ids.iter().any(|id| !carried_elsewhere.contains(id)),
+            _ => r.vlans.contains(&vlan),
+        });
+    }
+    Ok(Json(roots))
+}
+
+// Handler to list the APs joined to a wireless controller
+async fn list_wireless_aps_handler(
+    State(state): State<AppState>,
+    Path(device_id): Path<Uuid>,
+) -> Result<Json<Vec<WirelessAp>>, ApiError> {
+    tracing::info!(%device_id, "Handling request for /api/devices/:id/wireless-aps");
+    let aps = state.repo.list_wireless_aps(device_id).await?;
+    Ok(Json(aps))
+}
+
+// A node with the vendor and kind of its MAC
+#[derive(Debug, Serialize)]
+struct NodeView {
+    #[serde(flatten)]
+    node: Node,
+    mac_info: MacInfo,
+}
+
+impl NodeView {
+    fn new(node: Node, vendors: &OuiDatabase) -> Self {
+        Self { mac_info: vendors.describe(node.mac_address), node }
+    }
+}
+
+// An interface with the vendor and kind of its MAC, if it has one
+#[derive(Debug, Serialize)]
+struct InterfaceView {
+    #[serde(flatten)]
+    interface: Interface,
+    mac_info: Option<MacInfo>,
+}
+
+impl InterfaceView {
+    fn new(interface: Interface, vendors: &OuiDatabase) -> Self {
+        Self { mac_info: interface.mac_address.map(|mac| vendors.describe(mac)), interface }
+    }
+}
+
+// Handler to list the active nodes (MACs, wireless clients) on a device
+async fn list_nodes_handler(
+    State(state): State<AppState>,
+    Path(device_id): Path<Uuid>,
+) -> Result<Json<Vec<NodeView>>, ApiError> {
+    tracing::info!(%device_id, "Handling request for /api/devices/:id/nodes");
+    let nodes = state.repo.list_nodes(device_id).await?;
+    let vendors = state.vendors().await?;
+    Ok(Json(nodes.into_iter().map(|node| NodeView::new(node, vendors)).collect()))
+}
+
+#[derive(Debug, Serialize)]
+struct MacLookupView {
+    mac_address: MacAddress,
+    mac_info: MacInfo,
+    interfaces: Vec<InterfaceView>, // Device ports owning the address
+    nodes: Vec<NodeView>,           // Where the address was seen, most recent first
+}
+
+// Handler to find where a MAC address lives; accepts colon, dash, Cisco dotted or bare hex
+async fn lookup_mac_handler(
+    State(state): State<AppState>,
+    Path(mac): Path<String>,
+) -> Result<Json<MacLookupView>, ApiError> {
+    tracing::info!(%mac, "Handling request for /api/macs/:mac");
+    let mac_address: MacAddress = mac.parse().map_err(|e: InvalidMacAddress| DbError::InvalidQuery(e.to_string()))?;
+    let interfaces = state.repo.find_interfaces_by_mac(mac_address).await?;
+    let nodes = state.repo.find_nodes_by_mac(mac_address).await?;
+    let vendors = state.vendors().await?;
+    Ok(Json(MacLookupView {
+        mac_address,
+        mac_info: vendors.describe(mac_address),
+        interfaces: interfaces.into_iter().map(|interface| InterfaceView::new(interface, vendors)).collect(),
+        nodes: nodes.into_iter().map(|node| NodeView::new(node, vendors)).collect(),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchParams {
+    q: String,
+    limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+struct SearchView {
+    query_type: &'static str, // ip, subnet, mac or text
+    hits: Vec<SearchHit>,
+}
+
+// Handler to search devices, interfaces and nodes by address, subnet, MAC, name, serial or description
+async fn search_handler(
+    State(state): State<AppState>,
+    Query(params): Query<SearchParams>,
+) -> Result<Json<SearchView>, ApiError> {
+    tracing::info!(?params, "Handling request for /api/search");
+    let query = SearchQuery { term: SearchTerm::parse(&params.q)?, limit: params.limit };
+    let hits = state.repo.search(&query).await?;
+    Ok(Json(SearchView { query_type: query.term.as_str(), hits }))
+}
+
+// Query parameters of /api/anomalies; all optional
+#[derive(Debug, Default, Deserialize)]
+struct ListAnomaliesParams {
+    kind: Option<String>, // duplicate_ip, mac_flapping or mac_multiple_ports
+    mac: Option<String>,
+    ip: Option<String>,
+    open: Option<bool>,    // only anomalies still being detected
+    since: Option<String>, // RFC 3339; detected at or after
+    limit: Option<u32>,
+}
+
+impl ListAnomaliesParams {
+    fn into_filter(self) -> Result<AnomalyFilter, DbError> {
+        let invalid = |name: &str, value: &str| DbError::InvalidQuery(format!("invalid {} '{}'", name, value));
+        Ok(AnomalyFilter {
+            kind: self.kind.map(|v| AnomalyKind::try_from(v.clone()).map_err(|_| invalid("kind", &v))).transpose()?,
+            mac_address: self.mac.map(|v| v.parse().map_err(|_| invalid("mac", &v))).transpose()?,
+            ip_address: self.ip.map(|v| v.parse().map_err(|_| invalid("ip", &v))).transpose()?,
+            open_only: self.open.unwrap_or(false),
+            since: self.since.map(|v| OffsetDateTime::parse(&v, &Rfc3339).map_err(|_| invalid("since", &v))).transpose()?,
+            limit: self.limit,
+        })
+    }
+}
+
+// Handler to list detected anomalies, most recently detected first
+async fn list_anomalies_handler(
+    State(state): State<AppState>,
+    Query(params): Query<ListAnomaliesParams>,
+) -> Result<Json<Vec<Anomaly>>, ApiError> {
+    tracing::info!(?params, "Handling request for /api/anomalies");
+    let anomalies = state.repo.list_anomalies(&params.into_filter()?).await?;
+    Ok(Json(anomalies))
+}
+
+// Handler to run anomaly detection now instead of waiting for the next scheduled run
+async fn detect_anomalies_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Result<Json<AnomalyReport>, ApiError> {
+    tracing::info!(user = %user.0, "Running anomaly detection");
+    let report = state.repo.detect_anomalies(&state.anomalies).await?;
+    Ok(Json(report))
+}
+
+#[derive(Debug, Deserialize)]
+struct AllowedMacRequest {
+    mac_prefix: String,       // OUI, MA-M/MA-S block or full MAC, any notation
+    location_id: Option<Uuid>, // applies to this location and below
+    vlan: Option<i32>,
+    description: Option<String>,
+}
+
+// Handler to list the MAC allowlist
+async fn list_allowed_macs_handler(State(state): State<AppState>) -> Result<Json<Vec<AllowedMac>>, ApiError> {
+    tracing::info!("Handling request for /api/mac-allowlist");
+    let entries = state.repo.list_allowed_macs().await?;
+    Ok(Json(entries))
+}
+
+async fn create_allowed_mac_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(request): Json<AllowedMacRequest>,
+) -> Result<Json<AllowedMac>, ApiError> {
+    tracing::info!(mac_prefix = %request.mac_prefix, user = %user.0, "Adding MAC allowlist entry");
+    let entry = AllowedMac {
+        id: Uuid::nil(),
+        mac_prefix: request.mac_prefix,
+        location_id: request.location_id,
+        vlan: request.vlan,
+        description: request.description,
+        created_at: OffsetDateTime::now_utc(),
+    };
+    let entry = state.repo.create_allowed_mac(&entry).await?;
+    Ok(Json(entry))
+}
+
+async fn delete_allowed_mac_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(entry_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    tracing::info!(%entry_id, user = %user.0, "Deleting MAC allowlist entry");
+    state.repo.delete_allowed_mac(entry_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Query parameters of /api/mac-violations; all optional
+#[derive(Debug, Default, Deserialize)]
+struct ListMacViolationsParams {
+    device: Option<Uuid>,
+    mac: Option<String>,
+    since: Option<String>, // RFC 3339; seen at or after
+}
+
+impl ListMacViolationsParams {
+    fn into_filter(self) -> Result<MacViolationFilter, DbError> {
+        let invalid = |name: &str, value: &str| DbError::InvalidQuery(format!("invalid {} '{}'", name, value));
+        Ok(MacViolationFilter {
+            device_id: self.device,
+            mac_address: self.mac.map(|v| v.parse().map_err(|_| invalid("mac", &v))).transpose()?,
+            since: self.since.map(|v| OffsetDateTime::parse(&v, &Rfc3339).map_err(|_| invalid("since", &v))).transpose()?,
+        })
+    }
+}
+
+// Handler to list MACs seen where the allowlist does not approve them, most recently seen first
+async fn list_mac_violations_handler(
+    State(state): State<AppState>,
+    Query(params): Query<ListMacViolationsParams>,
+) -> Result<Json<Vec<MacViolation>>, ApiError> {
+    tracing::info!(?params, "Handling request for /api/mac-violations");
+    let violations = state.repo.list_mac_violations(&params.into_filter()?).await?;
+    Ok(Json(violations))
+}
+
+// Query parameters of /api/subnets and /api/subnets/:id
+#[derive(Debug, Default, Deserialize)]
+struct SubnetUsageParams {
+    window_days: Option<u32>, // defaults to ipam.window_days
+}
+
+impl SubnetUsageParams {
+    fn window_start(&self, settings: &IpamSettings) -> OffsetDateTime {
+        usage_window_start(self.window_days.unwrap_or(settings.window_days), OffsetDateTime::now_utc())
+    }
+}
+
+// A subnet in the list, with its counts but without ranges and stale addresses
+#[derive(Debug, Serialize)]
+struct SubnetSummary {
+    #[serde(flatten)]
+    subnet: Subnet,
+    size: u128,
+    used: u128,
+    free: u128,
+    utilisation: f64,
+    stale: usize,
+}
+
+async fn load_subnet_usage(state: &AppState, subnet: Subnet, window_start: OffsetDateTime) -> Result<SubnetUsage, ApiError> {
+    let sightings = state.repo.list_ip_sightings(subnet.network).await?;
+    Ok(subnet_usage(subnet, &sightings, window_start))
+}
+
+// Handler to list subnets with their utilisation, in address order
+async fn list_subnets_handler(
+    State(state): State<AppState>,
+    Query(params): Query<SubnetUsageParams>,
+) -> Result<Json<Vec<SubnetSummary>>, ApiError> {
+    tracing::info!(?params, "Handling request for /api/subnets");
+    let window_start = params.window_start(&state.ipam);
+    let mut summaries = Vec::new();
+    for subnet in state.repo.list_subnets().await? {
+        let usage = load_subnet_usage(&state, subnet, window_start).await?;
+        summaries.push(SubnetSummary {
+            subnet: usage.subnet,
+            size: usage.size,
+            used: usage.used,
+            free: usage.free,
+            utilisation: usage.utilisation,
+            stale: usage.stale_ips.len(),
+        });
+    }
+    Ok(Json(summaries))
+}
+
+// Handler to get a subnet's utilisation with its free ranges and stale addresses
+async fn get_subnet_handler(
+    State(state): State<AppState>,
+    Path(subnet_id): Path<Uuid>,
+    Query(params): Query<SubnetUsageParams>,
+) -> Result<Json<SubnetUsage>, ApiError> {
+    tracing::info!(%subnet_id, ?params, "Handling request for /api/subnets/:id");
+    let subnet = state.repo.get_subnet(subnet_id).await?;
+    let usage = load_subnet_usage(&state, subnet, params.window_start(&state.ipam)).await?;
+    Ok(Json(usage))
+}
+
+// Handler to rebuild the subnets from interface addresses now instead of after the next discovery
+async fn refresh_subnets_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Result<Json<SubnetRefresh>, ApiError> {
+    tracing::info!(user = %user.0, "Refreshing subnets");
+    let refresh = state.repo.refresh_subnets().await?;
+    Ok(Json(refresh))
+}
+
+// Query parameters of /api/hostnames
+#[derive(Debug, Default, Deserialize)]
+struct ListIpHostnamesParams {
+    network: Option<String>, // address or CIDR; all addresses when unset
+}
+
+// Handler to list the reverse DNS names found for device and node addresses
+async fn list_ip_hostnames_handler(
+    State(state): State<AppState>,
+    Query(params): Query<ListIpHostnamesParams>,
+) -> Result<Json<Vec<IpHostname>>, ApiError> {
+    tracing::info!(?params, "Handling request for /api/hostnames");
+    let network = params
+        .network
+        .map(|v| v.parse().map_err(|_| DbError::InvalidQuery(format!("invalid network '{}'", v))))
+        .transpose()?;
+    let hostnames = state.repo.list_ip_hostnames(network).await?;
+    Ok(Json(hostnames))
+}
+
+// Query parameters of /api/topology/links
+#[derive(Debug, Default, Deserialize)]
+struct ListTopologyLinksParams {
+    device: Option<Uuid>, // links with this device at either end; all links when unset
+}
+
+// Handler to list the links between devices
+async fn list_topology_links_handler(
+    State(state): State<AppState>,
+    Query(params): Query<ListTopologyLinksParams>,
+) -> Result<Json<Vec<TopologyLink>>, ApiError> {
+    tracing::info!(?params, "Handling request for /api/topology/links");
+    let links = state.repo.list_topology_links(params.device).await?;
+    Ok(Json(links))
+}
+
+#[derive(Debug, Deserialize)]
+struct TopologyLinkRequest {
+    device_id: Uuid,
+    interface_id: Option<Uuid>,
+    remote_device_id: Uuid,
+    remote_interface_id: Option<Uuid>,
+    confidence: Option<i16>, // defaults to 100
+}
+
+async fn create_topology_link_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(request): Json<TopologyLinkRequest>,
+) -> Result<Json<TopologyLink>, ApiError> {
+    tracing::info!(?request, user = %user.0, "Adding manual topology link");
+    let now = OffsetDateTime::now_utc();
+    let link = TopologyLink {
+        id: Uuid::nil(),
+        device_id: request.device_id,
+        interface_id: request.interface_id,
+        remote_device_id: request.remote_device_id,
+        remote_interface_id: request.remote_interface_id,
+        source: LinkSource::Manual,
+        confidence: request.confidence.unwrap_or(100),
+        first_seen: now,
+        last_seen: now,
+    };
+    let link = state.repo.create_topology_link(&link).await?;
+    Ok(Json(link))
+}
+
+async fn delete_topology_link_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(link_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    tracing::info!(%link_id, user = %user.0, "Deleting manual topology link");
+    state.repo.delete_topology_link(link_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Handler to rebuild the discovered links now instead of after the next discovery
+async fn refresh_topology_links_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Result<Json<LinkRefresh>, ApiError> {
+    tracing::info!(user = %user.0, "Refreshing topology links");
+    let refresh = state.repo.refresh_topology_links().await?;
+    Ok(Json(refresh))
+}
+
+// Handler to list the PoE state of a device's PSE ports
+async fn list_interface_poe_handler(
+    State(state): State<AppState>,
+    Path(device_id): Path<Uuid>,
+) -> Result<Json<Vec<InterfacePoe>>, ApiError> {
+    tracing::info!(%device_id, "Handling request for /api/devices/:id/poe");
+    let ports = state.repo.list_interface_poe(device_id).await?;
+    Ok(Json(ports))
+}
+
+// Handler to list the port control audit log of a device
+async fn list_port_changes_handler(
+    State(state): State<AppState>,
+    Path(device_id): Path<Uuid>,
+) -> Result<Json<Vec<PortChange>>, ApiError> {
+    tracing::info!(%device_id, "Handling request for /api/devices/:id/port-changes");
+    let changes = state.repo.list_port_changes(device_id).await?;
+    Ok(Json(changes))
+}
+
+// Handler to set tags and custom field values of a device,
+// e.g. `{"tags": ["core"], "custom_fields": {"cost_center": 4711, "owner": null}}`
+async fn set_device_metadata_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(device_id): Path<Uuid>,
+    Json(update): Json<MetadataUpdate>,
+) -> Result<Json<Device>, ApiError> {
+    tracing::info!(%device_id, user = %user.0, "Setting device tags and custom fields");
+    let device = state.repo.set_device_metadata(device_id, &update).await?;
+    Ok(Json(device))
+}
+
+#[derive(Debug, Deserialize)]
+struct FindInterfacesParams {
+    tag: Option<String>,   // comma-separated, all must be set
+    field: Option<String>, // comma-separated name:value custom field values
+}
+
+// Handler to find interfaces on any device by tag or custom field, e.g. a circuit ID
+async fn find_interfaces_handler(
+    State(state): State<AppState>,
+    Query(params): Query<FindInterfacesParams>,
+) -> Result<Json<Vec<InterfaceView>>, ApiError> {
+    tracing::info!(?params, "Handling request for /api/interfaces");
+    let filter = metadata_filter(params.tag, params.field)?;
+    let interfaces = state.repo.find_interfaces(&filter).await?;
+    let vendors = state.vendors().await?;
+    Ok(Json(interfaces.into_iter().map(|interface| InterfaceView::new(interface, vendors)).collect()))
+}
+
+async fn set_interface_metadata_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(interface_id): Path<Uuid>,
+    Json(update): Json<MetadataUpdate>,
+) -> Result<Json<Interface>, ApiError> {
+    tracing::info!(%interface_id, user = %user.0, "Setting interface tags and custom fields");
+    let interface = state.repo.set_interface_metadata(interface_id, &update).await?;
+    Ok(Json(interface))
+}
+
+#[derive(Debug, Deserialize)]
+struct CustomFieldRequest {
+    object_type: CustomFieldObject,
+    name: String,
+    field_type: CustomFieldType,
+    #[serde(default)]
+    choices: Vec<String>, // for enum fields
+    description: Option<String>,
+}
+
+// Handler to list the custom field schemas
+async fn list_custom_fields_handler(State(state): State<AppState>) -> Result<Json<Vec<CustomField>>, ApiError> {
+    tracing::info!("Handling request for /api/custom-fields");
+    let fields = state.repo.list_custom_fields().await?;
+    Ok(Json(fields))
+}
+
+async fn create_custom_field_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(request): Json<CustomFieldRequest>,
+) -> Result<Json<CustomField>, ApiError> {
+    tracing::info!(name = %request.name, object_type = request.object_type.as_str(), user = %user.0, "Creating custom field");
+    let now = OffsetDateTime::now_utc();
+    let field = CustomField {
+        id: Uuid::nil(),
+        object_type: request.object_type,
+        name: request.name,
+        field_type: request.field_type,
+        choices: request.choices,
+        description: request.description,
+        created_at: now,
+        updated_at: now,
+    };
+    let field = state.repo.create_custom_field(&field).await?;
+    Ok(Json(field))
+}
+
+// Handler to delete a custom field and its values on all devices or interfaces
+async fn delete_custom_field_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(field_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    tracing::info!(%field_id, user = %user.0, "Deleting custom field");
+    state.repo.delete_custom_field(field_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+struct LocationRequest {
+    parent_id: Option<Uuid>,
+    kind: LocationKind,
+    name: String,
+    description: Option<String>,
+}
+
+impl LocationRequest {
+    fn into_location(self, id: Uuid) -> Location {
+        let now = OffsetDateTime::now_utc();
+        Location {
+            id,
+            parent_id: self.parent_id,
+            kind: self.kind,
+            name: self.name,
+            description: self.description,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+// Handler to list all locations; parent_id links them into the site > building > room > rack tree
+async fn list_locations_handler(State(state): State<AppState>) -> Result<Json<Vec<Location>>, ApiError> {
+    tracing::info!("Handling request for /api/locations");
+    let locations = state.repo.list_locations().await?;
+    Ok(Json(locations))
+}
+
+async fn get_location_handler(
+    State(state): State<AppState>,
+    Path(location_id): Path<Uuid>,
+) -> Result<Json<Location>, ApiError> {
+    tracing::info!(%location_id, "Handling request for /api/locations/:id");
+    let location = state.repo.get_location(location_id).await?;
+    Ok(Json(location))
+}
+
+async fn create_location_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(request): Json<LocationRequest>,
+) -> Result<Json<Location>, ApiError> {
+    tracing::info!(name = %request.name, kind = request.kind.as_str(), user = %user.0, "Creating location");
+    let location = state.repo.create_location(&request.into_location(Uuid::nil())).await?;
+    Ok(Json(location))
+}
+
+async fn update_location_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(location_id): Path<Uuid>,
+    Json(request): Json<LocationRequest>,
+) -> Result<Json<Location>, ApiError> {
+    tracing::info!(%location_id, user = %user.0, "Updating location");
+    let location = state.repo.update_location(&request.into_location(location_id)).await?;
+    Ok(Json(location))
+}
+
+// Handler to delete a location with everything below it; its devices lose their location
+async fn delete_location_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(location_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    tracing::info!(%location_id, user = %user.0, "Deleting location");
+    state.repo.delete_location(location_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+struct LocationRuleRequest {
+    location_id: Uuid,
+    priority: Option<i32>, // lower is tried first, default 100
+    sys_location_pattern: Option<String>,
+    subnet: Option<String>, // CIDR
+}
+
+// Handler to list the location rules in the order they are tried
+async fn list_location_rules_handler(State(state): State<AppState>) -> Result<Json<Vec<LocationRule>>, ApiError> {
+    tracing::info!("Handling request for /api/location-rules");
+    let rules = state.repo.list_location_rules().await?;
+    Ok(Json(rules))
+}
+
+async fn create_location_rule_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(request): Json<LocationRuleRequest>,
+) -> Result<Json<LocationRule>, ApiError> {
+    tracing::info!(location_id = %request.location_id, user = %user.0, "Creating location rule");
+    let subnet = request
+        .subnet
+        .map(|v| v.parse().map_err(|_| DbError::InvalidQuery(format!("invalid subnet '{}'", v))))
+        .transpose()?;
+    let rule = LocationRule {
+        id: Uuid::nil(),
+        location_id: request.location_id,
+        priority: request.priority.unwrap_or(100),
+        sys_location_pattern: request.sys_location_pattern,
+        subnet,
+        created_at: OffsetDateTime::now_utc(),
+    };
+    let rule = state.repo.create_location_rule(&rule).await?;
+    Ok(Json(rule))
+}
+
+async fn delete_location_rule_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(rule_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    tracing::info!(%rule_id, user = %user.0, "Deleting location rule");
+    state.repo.delete_location_rule(rule_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Handler to re-place all devices not placed by hand, e.g. after editing the rules
+async fn apply_location_rules_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    tracing::info!(user = %user.0, "Applying location rules");
+    let moved = state.repo.apply_location_rules().await?;
+    Ok(Json(serde_json::json!({ "moved": moved })))
+}
+
+#[derive(Debug, Deserialize)]
+struct DeviceLocationRequest {
+    location_id: Option<Uuid>, // null hands the device back to the location rules
+}
+
+// Handler to place a device by hand; the placement is kept by the location rules
+async fn set_device_location_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(device_id): Path<Uuid>,
+    Json(request): Json<DeviceLocationRequest>,
+) -> Result<Json<Device>, ApiError> {
+    tracing::info!(%device_id, location_id = ?request.location_id, user = %user.0, "Setting device location");
+    let source = format!("{}:{}", MANUAL_SOURCE, user.0);
+    let device = state.repo.set_device_location(device_id, request.location_id, &source).await?;
+    Ok(Json(device))
+}
+
+#[derive(Debug, Deserialize)]
+struct GroupRequest {
+    name: String,
+    description: Option<String>,
+    rules: Option<DeviceFilter>, // set for dynamic groups, e.g. {"vendor": "cisco", "location_id": "..."}
+}
+
+impl GroupRequest {
+    fn into_group(self, id: Uuid) -> DeviceGroup {
+        let now = OffsetDateTime::now_utc();
+        DeviceGroup {
+            id,
+            name: self.name,
+            description: self.description,
+            rules: self.rules,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+// Handler to list device groups; members are listed with /api/devices?group=<id>
+async fn list_groups_handler(State(state): State<AppState>) -> Result<Json<Vec<DeviceGroup>>, ApiError> {
+    tracing::info!("Handling request for /api/groups");
+    let groups = state.repo.list_groups().await?;
+    Ok(Json(groups))
+}
+
+async fn get_group_handler(
+    State(state): State<AppState>,
+    Path(group_id): Path<Uuid>,
+) -> Result<Json<DeviceGroup>, ApiError> {
+    tracing::info!(%group_id, "Handling request for /api/groups/:id");
+    let group = state.repo.get_group(group_id).await?;
+    Ok(Json(group))
+}
+
+async fn create_group_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(request): Json<GroupRequest>,
+) -> Result<Json<DeviceGroup>, ApiError> {
+    tracing::info!(name = %request.name, user = %user.0, "Creating device group");
+    let group = state.repo.create_group(&request.into_group(Uuid::nil())).await?;
+    Ok(Json(group))
+}
+
+async fn update_group_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(group_id): Path<Uuid>,
+    Json(request): Json<GroupRequest>,
+) -> Result<Json<DeviceGroup>, ApiError> {
+    tracing::info!(%group_id, user = %user.0, "Updating device group");
+    let group = state.repo.update_group(&request.into_group(group_id)).await?;
+    Ok(Json(group))
+}
+
+async fn delete_group_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(group_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    tracing::info!(%group_id, user = %user.0, "Deleting device group");
+    state.repo.delete_group(group_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Handler to add a device to a static group
+async fn add_group_member_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path((group_id, device_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, ApiError> {
+    tracing::info!(%group_id, %device_id, user = %user.0, "Adding device to group");
+    state.repo.add_group_member(group_id, device_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn remove_group_member_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path((group_id, device_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, ApiError> {
+    tracing::info!(%group_id, %device_id, user = %user.0, "Removing device from group");
+    state.repo.remove_group_member(group_id, device_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+struct AdminStatusRequest {
+    up: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct DescriptionRequest {
+    description: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct VlanRequest {
+    vlan: i32,
+}
+
+#[derive(Debug, Deserialize)]
+struct PoeRequest {
+    enabled: bool,
+}
+
+// Shared body of the port control handlers
+async fn apply_port_action(
+    state: &AppState,
+    user: AuthUser,
+    (device_id, if_index): (Uuid, i32),
+    action: PortAction,
+) -> Result<Json<PortChange>, ApiError> {
+    tracing::info!(%device_id, if_index, action = action.name(), user = %user.0, "Handling port control request");
+    let change = state.port_control.apply(device_id, if_index, action, &user.0).await?;
+    Ok(Json(change))
+}
+
+// Handler to bring a port up or down
+async fn set_admin_status_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(port): Path<(Uuid, i32)>,
+    Json(request): Json<AdminStatusRequest>,
+) -> Result<Json<PortChange>, ApiError> {
+    apply_port_action(&state, user, port, PortAction::AdminStatus { up: request.up }).await
+}
+
+// Handler to change a port's description (ifAlias)
+async fn set_description_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(port): Path<(Uuid, i32)>,
+    Json(request): Json<DescriptionRequest>,
+) -> Result<Json<PortChange>, ApiError> {
+    apply_port_action(&state, user, port, PortAction::Description(request.description)).await
+}
+
+// Handler to move an access port to another VLAN
+async fn set_vlan_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(port): Path<(Uuid, i32)>,
+    Json(request): Json<VlanRequest>,
+) -> Result<Json<PortChange>, ApiError> {
+    apply_port_action(&state, user, port, PortAction::AccessVlan(request.vlan)).await
+}
+
+// Handler to enable or disable PoE on a port
+async fn set_poe_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(port): Path<(Uuid, i32)>,
+    Json(request): Json<PoeRequest>,
+) -> Result<Json<PortChange>, ApiError> {
+    apply_port_action(&state, user, port, PortAction::Poe { enabled: request.enabled }).await
+}
+
+// Handler to power-cycle the device powered by a port
+async fn power_cycle_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(port): Path<(Uuid, i32)>,
+) -> Result<Json<PortChange>, ApiError> {
+    apply_port_action(&state, user, port, PortAction::PowerCycle).await
+}
+
+// Builds the application router over a repository, so handlers can be tested without a database
+fn build_router(repo: Arc<dyn Repository>, settings: &Settings, changes: broadcast::Sender<ChangeEvent>) -> Router {
+    let port_control = PortControl::new(
+        repo.clone(),
+        settings.snmp.as_ref(),
+        settings.port_control.clone().unwrap_or_default(),
+    );
+    let api_tokens = settings
+        .api
+        .iter()
+        .flat_map(|api| &api.tokens)
+        .map(|t| (t.user.clone(), t.token.clone()))
+        .collect();
+    let app_state = AppState {
+        repo,
+        port_control: Arc::new(port_control),
+        api_tokens: Arc::new(api_tokens),
+        changes,
+        anomalies: Arc::new(settings.anomalies.clone().unwrap_or_default()),
+        vendors: Arc::new(OnceCell::new()),
+        ipam: Arc::new(settings.ipam.clone().unwrap_or_default()),
+    };
 
     // Define API routes
     let api_routes = Router::new()
-        .route("/devices", get(list_devices_handler));
+        .route("/devices", get(list_devices_handler))
+        .route("/devices/:id", patch(update_device_handler))
+        .route("/devices/:id/field-sources", get(get_field_sources_handler))
+        .route("/devices/:id/history", get(list_device_history_handler))
+        .route("/devices/:id/routing-neighbors", get(list_routing_neighbors_handler))
+        .route("/devices/:id/neighbors", get(list_device_neighbors_handler))
+        .route("/devices/:id/stp", get(get_stp_handler))
+        .route("/stp/roots", get(list_stp_roots_handler))
+        .route("/devices/:id/wireless-aps", get(list_wireless_aps_handler))
+        .route("/devices/:id/nodes", get(list_nodes_handler))
+        .route("/devices/:id/poe", get(list_interface_poe_handler))
+        .route("/devices/:id/port-changes", get(list_port_changes_handler))
+        .route("/devices/:id/interfaces/:if_index/admin-status", post(set_admin_status_handler))
+        .route("/devices/:id/interfaces/:if_index/description", post(set_description_handler))
+        .route("/devices/:id/interfaces/:if_index/vlan", post(set_vlan_handler))
+        .route("/devices/:id/interfaces/:if_index/poe", post(set_poe_handler))
+        .route("/devices/:id/interfaces/:if_index/power-cycle", post(power_cycle_handler))
+        .route("/devices/:id/location", put(set_device_location_handler))
+        .route("/devices/:id/metadata", patch(set_device_metadata_handler))
+        .route("/interfaces", get(find_interfaces_handler))
+        .route("/interfaces/:id/metadata", patch(set_interface_metadata_handler))
+        .route("/custom-fields", get(list_custom_fields_handler).post(create_custom_field_handler))
+        .route("/custom-fields/:id", delete(delete_custom_field_handler))
+        .route("/macs/:mac", get(lookup_mac_handler))
+        .route("/search", get(search_handler))
+        .route("/anomalies", get(list_anomalies_handler))
+        .route("/anomalies/detect", post(detect_anomalies_handler))
+        .route("/mac-allowlist", get(list_allowed_macs_handler).post(create_allowed_mac_handler))
+        .route("/mac-allowlist/:id", delete(delete_allowed_mac_handler))
+        .route("/mac-violations", get(list_mac_violations_handler))
+        .route("/subnets", get(list_subnets_handler))
+        .route("/subnets/refresh", post(refresh_subnets_handler))
+        .route("/subnets/:id", get(get_subnet_handler))
+        .route("/hostnames", get(list_ip_hostnames_handler))
+        .route("/topology/links", get(list_topology_links_handler).post(create_topology_link_handler))
+        .route("/topology/links/:id", delete(delete_topology_link_handler))
+        .route("/topology/refresh", post(refresh_topology_links_handler))
+        .route("/events", get(events_handler))
+        .route("/locations", get(list_locations_handler).post(create_location_handler))
+        .route(
+            "/locations/:id",
+            get(get_location_handler).put(update_location_handler).delete(delete_location_handler),
+        )
+        .route("/location-rules", get(list_location_rules_handler).post(create_location_rule_handler))
+        .route("/location-rules/apply", post(apply_location_rules_handler))
+        .route("/location-rules/:id", delete(delete_location_rule_handler))
+        .route("/groups", get(list_groups_handler).post(create_group_handler))
+        .route("/groups/:id", get(get_group_handler).put(update_group_handler).delete(delete_group_handler))
+        .route(
+            "/groups/:id/members/:device_id",
+            put(add_group_member_handler).delete(remove_group_member_handler),
+        );
         // Add more API routes here later
 
     // Define the main application router
-    let app = Router::new()
+    Router::new()
         .route("/", get(root_handler)) // Health check
         .nest("/api", api_routes) // Mount API routes under /api
         .with_state(app_state)
@@ -87,7 +1237,19 @@ pub async fn run_server(pool: PgPool, settings: &Settings) -> Result<(), Box<dyn
                 .on_response(()), // Customize later if needed
         )
         // Add CORS layer - adjust origins as needed for production
-        .layer(CorsLayer::new().allow_origin(Any).allow_methods(Any)); 
+        .layer(CorsLayer::new().allow_origin(Any).allow_methods(Any))
+}
+
+// Function to create and run the Axum server
+pub async fn run_server(pool: DbPool, settings: &Settings) -> Result<(), Box<dyn std::error::Error>> {
+    let (changes, _) = broadcast::channel(CHANGE_BUFFER);
+    match pool.postgres() {
+        Some(pg_pool) => {
+            tokio::spawn(forward_changes(pg_pool.clone(), changes.clone()));
+        }
+        None => tracing::info!("No change feed on this database; /api/events stays quiet"),
+    }
+    let app = build_router(pool.repository(), settings, changes);
 
     // Define the address to bind to - consider making this configurable later
     let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
@@ -100,6 +1262,710 @@ pub async fn run_server(pool: PgPool, settings: &Settings) -> Result<(), Box<dyn
     Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use db::{
+        AllowlistRepository, ArpEntry, CustomFieldValues, DeviceRepository, HostnameRepository, InterfaceRepository, IpamRepository, LinkRepository, MacVendor, MemoryRepository,
+        NeighborProtocol, NodeRepository, StpInstance, TopologyRepository, VendorRepository,
+    };
+    use futures_util::StreamExt;
+    use nd_core::{ApiSettings, ApiToken};
+    use tower::ServiceExt;
+
+    fn settings() -> Settings {
+        Settings {
+            debug: None,
+            database: None,
+            log_level: None,
+            snmp: None,
+            api: Some(ApiSettings { tokens: vec![ApiToken { user: "alice".to_string(), token: "s3cret".to_string() }] }),
+            port_control: None,
+            discovery: None,
+            retention: None,
+            anomalies: None,
+            rogue: None,
+            ipam: None,
+            dns: None,
+        }
+    }
+
+    fn device(hostname: &str, ip: &str, vendor: &str) -> Device {
+        Device {
+            hostname: Some(hostname.to_string()),
+            vendor: Some(vendor.to_string()),
+            last_seen: Some(OffsetDateTime::now_utc()),
+            ..Device::new(ip.parse().unwrap())
+        }
+    }
+
+    fn changes() -> broadcast::Sender<ChangeEvent> {
+        broadcast::channel(CHANGE_BUFFER).0
+    }
+
+    async fn get_json(app: &Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let response = app.clone().oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn lists_devices_from_repository() {
+        let repo = Arc::new(MemoryRepository::new());
+        repo.upsert_device(&device("core-1", "192.0.2.1/32", "Cisco"), "test").await.unwrap();
+        let app = build_router(repo, &settings(), changes());
+
+        let (status, page) = get_json(&app, "/api/devices").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(page["devices"][0]["hostname"], "core-1");
+        assert!(page["next_cursor"].is_null());
+    }
+
+    #[tokio::test]
+    async fn pages_through_filtered_devices() {
+        let repo = Arc::new(MemoryRepository::new());
+        for (hostname, ip, vendor) in [
+            ("sw-3", "10.0.1.3/32", "Cisco"),
+            ("sw-1", "10.0.1.1/32", "Cisco"),
+            ("sw-2", "10.0.1.2/32", "Cisco"),
+            ("fw-1", "10.0.1.4/32", "Fortinet"),
+            ("sw-9", "10.9.0.1/32", "Cisco"),
+        ] {
+            repo.upsert_device(&device(hostname, ip, vendor), "test").await.unwrap();
+        }
+        let app = build_router(repo, &settings(), changes());
+        let base = "/api/devices?vendor=cisco&subnet=10.0.0.0/16&sort=hostname&order=desc&limit=2";
+
+        let (_, first) = get_json(&app, base).await;
+        let cursor = first["next_cursor"].as_str().unwrap();
+        let (_, second) = get_json(&app, &format!("{}&cursor={}", base, cursor)).await;
+
+        let hostnames = |page: &serde_json::Value| {
+            page["devices"].as_array().unwrap().iter().map(|d| d["hostname"].clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(hostnames(&first), ["sw-3", "sw-2"]);
+        assert_eq!(hostnames(&second), ["sw-1"]);
+        assert!(second["next_cursor"].is_null());
+
+        let (status, _) = get_json(&app, "/api/devices?sort=colour").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn shows_device_timeline() {
+        let repo = Arc::new(MemoryRepository::new());
+        let mut switch = device("sw-1", "10.0.1.1/32", "Cisco");
+        switch.os_version = Some("17.3.4".to_string());
+        let stored = repo.upsert_device(&switch, "discovery").await.unwrap();
+        switch.os_version = Some("17.6.1".to_string());
+        repo.upsert_device(&switch, "discovery").await.unwrap();
+        let app = build_router(repo, &settings(), changes());
+
+        let (status, history) = get_json(&app, &format!("/api/devices/{}/history", stored.id)).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(history.as_array().unwrap().len(), 1);
+        assert_eq!(history[0]["attribute"], "os_version");
+        assert_eq!(history[0]["old_value"], "17.3.4");
+        assert_eq!(history[0]["new_value"], "17.6.1");
+        assert_eq!(history[0]["source"], "discovery");
+    }
+
+    #[tokio::test]
+    async fn manual_overrides_survive_discovery() {
+        let repo = Arc::new(MemoryRepository::new());
+        let discovered = device("sw-1", "10.0.1.1/32", "Cisco");
+        let stored = repo.upsert_device(&discovered, "discovery").await.unwrap();
+        let app = build_router(repo.clone(), &settings(), changes());
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::patch(format!("/api/devices/{}", stored.id))
+                    .header("content-type", "application/json")
+                    .header("authorization", "Bearer s3cret")
+                    .body(Body::from(r#"{"hostname":"dist-sw-1"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Rediscovery without a vendor neither reverts the override nor clears the vendor
+        repo.upsert_device(&Device { vendor: None, ..discovered }, "discovery").await.unwrap();
+        let device = repo.get_device(stored.id).await.unwrap();
+        assert_eq!(device.hostname.as_deref(), Some("dist-sw-1"));
+        assert_eq!(device.vendor.as_deref(), Some("Cisco"));
+
+        let (_, sources) = get_json(&app, &format!("/api/devices/{}/field-sources", stored.id)).await;
+        assert_eq!(sources["hostname"], "manual:alice");
+        assert_eq!(sources["vendor"], "discovery");
+    }
+
+    #[tokio::test]
+    async fn retired_devices_stay_retired_and_hidden() {
+        let repo = Arc::new(MemoryRepository::new());
+        let discovered = device("sw-old", "10.0.1.9/32", "Cisco");
+        let stored = repo.upsert_device(&discovered, "discovery").await.unwrap();
+        repo.upsert_device(&device("sw-new", "10.0.1.10/32", "Cisco"), "discovery").await.unwrap();
+        let app = build_router(repo.clone(), &settings(), changes());
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::patch(format!("/api/devices/{}", stored.id))
+                    .header("content-type", "application/json")
+                    .header("authorization", "Bearer s3cret")
+                    .body(Body::from(r#"{"status":"retired","device_type":"switch"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Still answering polls does not bring it back
+        repo.upsert_device(&Device { status: Some(DeviceStatus::Up), ..discovered }, "discovery").await.unwrap();
+        let device = repo.get_device(stored.id).await.unwrap();
+        assert_eq!(device.status, Some(DeviceStatus::Retired));
+        assert_eq!(device.device_type, Some(DeviceType::Switch));
+
+        let (_, page) = get_json(&app, "/api/devices").await;
+        assert_eq!(page["devices"].as_array().unwrap().len(), 1);
+        assert_eq!(page["devices"][0]["hostname"], "sw-new");
+        let (_, page) = get_json(&app, "/api/devices?include_retired=true&device_type=switch").await;
+        assert_eq!(page["devices"][0]["hostname"], "sw-old");
+        assert_eq!(page["devices"][0]["status"], "retired");
+    }
+
+    #[tokio::test]
+    async fn looks_up_mac_in_any_format() {
+        let repo = Arc::new(MemoryRepository::new());
+        let switch = repo.upsert_device(&device("sw-1", "10.0.1.1/32", "Cisco"), "test").await.unwrap();
+        let now = OffsetDateTime::now_utc();
+        repo.upsert_node(&Node {
+            id: Uuid::nil(),
+            mac_address: "00:1a:2b:3c:4d:5e".parse().unwrap(),
+            device_id: switch.id,
+            interface_id: None,
+            vlan: 10,
+            active: true,
+            first_seen: now,
+            last_seen: now,
+            wireless: None,
+        })
+        .await
+        .unwrap();
+        let app = build_router(repo, &settings(), changes());
+
+        let (status, found) = get_json(&app, "/api/macs/001A.2B3C.4D5E").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(found["mac_address"], "00:1a:2b:3c:4d:5e");
+        assert_eq!(found["nodes"][0]["device_id"], switch.id.to_string());
+        assert_eq!(found["nodes"][0]["mac_address"], "00:1a:2b:3c:4d:5e");
+
+        let (status, _) = get_json(&app, "/api/macs/not-a-mac").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn names_mac_vendors() {
+        let repo = Arc::new(MemoryRepository::new());
+        let switch = repo.upsert_device(&device("sw-1", "10.0.1.1/32", "Cisco"), "test").await.unwrap();
+        let now = OffsetDateTime::now_utc();
+        for mac in ["00:1a:2b:3c:4d:5e", "00:50:56:00:00:01", "da:a1:19:00:00:01"] {
+            let node = Node {
+                id: Uuid::nil(),
+                mac_address: mac.parse().unwrap(),
+                device_id: switch.id,
+                interface_id: None,
+                vlan: 10,
+                active: true,
+                first_seen: now,
+                last_seen: now,
+                wireless: None,
+            };
+            repo.upsert_node(&node).await.unwrap();
+        }
+
+        // The bundled list until an import
+        let app = build_router(repo.clone(), &settings(), changes());
+        let (_, found) = get_json(&app, "/api/macs/00:50:56:00:00:01").await;
+        assert_eq!(found["mac_info"]["vendor"], "VMware, Inc.");
+        assert_eq!(found["nodes"][0]["mac_info"]["vendor"], "VMware, Inc.");
+
+        let imported = MacVendor { prefix: "001a2b".to_string(), organization: "Example Networks Ltd.".to_string() };
+        repo.replace_mac_vendors(&[imported]).await.unwrap();
+        let app = build_router(repo, &settings(), changes());
+        let (status, nodes) = get_json(&app, &format!("/api/devices/{}/nodes", switch.id)).await;
+        assert_eq!(status, StatusCode::OK);
+        let info = |mac: &str| nodes.as_array().unwrap().iter().find(|n| n["mac_address"] == mac).unwrap()["mac_info"].clone();
+        assert_eq!(info("00:1a:2b:3c:4d:5e")["vendor"], "Example Networks Ltd.");
+        assert_eq!(info("00:50:56:00:00:01")["vendor"], serde_json::Value::Null);
+        assert_eq!(info("da:a1:19:00:00:01")["randomized"], true);
+        assert_eq!(info("da:a1:19:00:00:01")["locally_administered"], true);
+    }
+
+    async fn send_json(app: &Router, method: &str, uri: &str, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .header("authorization", "Bearer s3cret")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn places_devices_by_rules_and_groups_them() {
+        let repo = Arc::new(MemoryRepository::new());
+        let app = build_router(repo.clone(), &settings(), changes());
+        let (_, site) = send_json(&app, "POST", "/api/locations", serde_json::json!({"kind": "site", "name": "BER1"})).await;
+        let (status, building) = send_json(
+            &app,
+            "POST",
+            "/api/locations",
+            serde_json::json!({"kind": "building", "name": "DC1", "parent_id": site["id"]}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) =
+            send_json(&app, "POST", "/api/locations", serde_json::json!({"kind": "rack", "name": "R1", "parent_id": site["id"]}))
+                .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        send_json(
+            &app,
+            "POST",
+            "/api/location-rules",
+            serde_json::json!({"location_id": building["id"], "sys_location_pattern": "berlin dc1"}),
+        )
+        .await;
+
+        let mut core = device("core-1", "10.0.1.1/32", "Cisco");
+        core.sys_location = Some("Berlin DC1, Room 2.14".to_string());
+        let core = repo.upsert_device(&core, "discovery").await.unwrap();
+        let edge = repo.upsert_device(&device("edge-1", "10.0.2.1/32", "Juniper"), "discovery").await.unwrap();
+        assert_eq!(core.location_id.map(|id| id.to_string()).as_deref(), building["id"].as_str());
+
+        // Devices in a building are listed under its site
+        let (_, page) = get_json(&app, &format!("/api/devices?location={}", site["id"].as_str().unwrap())).await;
+        assert_eq!(page["devices"].as_array().unwrap().len(), 1);
+        assert_eq!(page["devices"][0]["hostname"], "core-1");
+
+        let (_, group) = send_json(&app, "POST", "/api/groups", serde_json::json!({"name": "edge"})).await;
+        let group_id = group["id"].as_str().unwrap();
+        let (status, _) = send_json(&app, "PUT", &format!("/api/groups/{}/members/{}", group_id, edge.id), serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (_, dynamic) =
+            send_json(&app, "POST", "/api/groups", serde_json::json!({"name": "cisco", "rules": {"vendor": "cisco"}})).await;
+
+        let (_, page) = get_json(&app, &format!("/api/devices?group={}", group_id)).await;
+        assert_eq!(page["devices"][0]["hostname"], "edge-1");
+        let (_, page) = get_json(&app, &format!("/api/devices?group={}", dynamic["id"].as_str().unwrap())).await;
+        assert_eq!(page["devices"].as_array().unwrap().len(), 1);
+        assert_eq!(page["devices"][0]["hostname"], "core-1");
+    }
+
+    #[tokio::test]
+    async fn custom_fields_and_tags_survive_rediscovery() {
+        let repo = Arc::new(MemoryRepository::new());
+        let discovered = device("sw-1", "10.0.1.1/32", "Cisco");
+        let stored = repo.upsert_device(&discovered, "discovery").await.unwrap();
+        repo.upsert_device(&device("sw-2", "10.0.1.2/32", "Cisco"), "discovery").await.unwrap();
+        let app = build_router(repo.clone(), &settings(), changes());
+        let (status, _) = send_json(
+            &app,
+            "POST",
+            "/api/custom-fields",
+            serde_json::json!({"object_type": "device", "name": "cost_center", "field_type": "int"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let uri = format!("/api/devices/{}/metadata", stored.id);
+        let (status, _) = send_json(&app, "PATCH", &uri, serde_json::json!({"custom_fields": {"cost_center": "abc"}})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = send_json(
+            &app,
+            "PATCH",
+            &uri,
+            serde_json::json!({"tags": ["Core", "prod"], "custom_fields": {"cost_center": 4711}}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        repo.upsert_device(&discovered, "discovery").await.unwrap();
+        let (_, page) = get_json(&app, "/api/devices?tag=core,prod&field=cost_center:4711").await;
+        assert_eq!(page["devices"].as_array().unwrap().len(), 1);
+        assert_eq!(page["devices"][0]["hostname"], "sw-1");
+        assert_eq!(page["devices"][0]["tags"], serde_json::json!(["core", "prod"]));
+        assert_eq!(page["devices"][0]["custom_fields"]["cost_center"], 4711);
+    }
+
+    #[tokio::test]
+    async fn search_detects_the_query_type() {
+        let repo = Arc::new(MemoryRepository::new());
+        let stored = repo.upsert_device(&device("core-sw1", "10.0.2.1/32", "Cisco"), "discovery").await.unwrap();
+        repo.upsert_device(&device("access-sw7", "10.0.2.7/32", "Cisco"), "discovery").await.unwrap();
+        let app = build_router(repo, &settings(), changes());
+
+        let (status, body) = get_json(&app, "/api/search?q=10.0.2.1").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["query_type"], "ip");
+        assert_eq!(body["hits"][0]["device_id"], stored.id.to_string());
+        let (_, body) = get_json(&app, "/api/search?q=10.0.2.0/24&limit=1").await;
+        assert_eq!((body["query_type"].as_str(), body["hits"].as_array().unwrap().len()), (Some("subnet"), 1));
+        let (_, body) = get_json(&app, "/api/search?q=CORE").await;
+        assert_eq!(body["hits"][0]["kind"], "device");
+        assert_eq!(body["hits"][0]["field"], "hostname");
+        let (status, _) = get_json(&app, "/api/search?q=x").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn detects_and_lists_anomalies() {
+        let repo = Arc::new(MemoryRepository::new());
+        let now = OffsetDateTime::now_utc();
+        for ip in ["10.0.3.1/32", "10.0.3.2/32"] {
+            let switch = repo.upsert_device(&device(ip, ip, "Cisco"), "discovery").await.unwrap();
+            let node = Node {
+                id: Uuid::nil(),
+                mac_address: "00:1a:2b:3c:4d:5e".parse().unwrap(),
+                device_id: switch.id,
+                interface_id: Some(Uuid::new_v4()),
+                vlan: 10,
+                active: true,
+                first_seen: now,
+                last_seen: now,
+                wireless: None,
+            };
+            repo.upsert_node(&node).await.unwrap();
+        }
+        let app = build_router(repo, &settings(), changes());
+
+        let (status, report) = send_json(&app, "POST", "/api/anomalies/detect", serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(report["opened"], 1);
+
+        let (status, found) = get_json(&app, "/api/anomalies?kind=mac_multiple_ports&open=true&mac=001a.2b3c.4d5e").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(found[0]["mac_address"], "00:1a:2b:3c:4d:5e");
+        assert_eq!(found[0]["device_ids"].as_array().unwrap().len(), 2);
+        let (_, found) = get_json(&app, "/api/anomalies?kind=duplicate_ip").await;
+        assert!(found.as_array().unwrap().is_empty());
+        let (status, _) = get_json(&app, "/api/anomalies?kind=loop").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn manages_the_mac_allowlist_and_lists_violations() {
+        let repo = Arc::new(MemoryRepository::new());
+        let switch = repo.upsert_device(&device("10.0.4.1/32", "10.0.4.1/32", "Cisco"), "discovery").await.unwrap();
+        let app = build_router(repo.clone(), &settings(), changes());
+
+        let entry = serde_json::json!({ "mac_prefix": "00-1A-2B", "vlan": 10, "description": "Corporate laptops" });
+        let (status, created) = send_json(&app, "POST", "/api/mac-allowlist", entry).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(created["mac_prefix"], "001a2b");
+        let (status, _) = send_json(&app, "POST", "/api/mac-allowlist", serde_json::json!({ "mac_prefix": "00:1a" })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (_, entries) = get_json(&app, "/api/mac-allowlist").await;
+        assert_eq!(entries.as_array().unwrap().len(), 1);
+
+        let now = OffsetDateTime::now_utc();
+        let node = Node {
+            id: Uuid::nil(),
+            mac_address: "02:00:00:00:00:01".parse().unwrap(),
+            device_id: switch.id,
+            interface_id: Some(Uuid::new_v4()),
+            vlan: 10,
+            active: true,
+            first_seen: now,
+            last_seen: now,
+            wireless: None,
+        };
+        repo.check_mac_allowlist(&[node], 4).await.unwrap();
+        let (status, found) = get_json(&app, &format!("/api/mac-violations?device={}&mac=0200.0000.0001", switch.id)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(found[0]["mac_address"], "02:00:00:00:00:01");
+        let (status, _) = get_json(&app, "/api/mac-violations?mac=nope").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let path = format!("/api/mac-allowlist/{}", created["id"].as_str().unwrap());
+        let (status, _) = send_json(&app, "DELETE", &path, serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send_json(&app, "DELETE", &path, serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn port_control_requires_a_token() {
+        let app = build_router(Arc::new(MemoryRepository::new()), &settings(), changes());
+        let request = |token: &str| {
+            Request::post(format!("/api/devices/{}/interfaces/1/admin-status", Uuid::nil()))
+                .header("content-type", "application/json")
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::from(r#"{"up":false}"#))
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(request("wrong")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // Authenticated, but no write community is configured
+        let response = app.oneshot(request("s3cret")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn reports_subnet_utilisation() {
+        let repo = Arc::new(MemoryRepository::new());
+        let router = repo.upsert_device(&device("rtr-1", "10.0.0.1/32", "Cisco"), "test").await.unwrap();
+        let now = OffsetDateTime::now_utc();
+        let vlan = Interface {
+            id: Uuid::nil(),
+            device_id: router.id,
+            if_index: 20,
+            if_name: Some("Vlan20".to_string()),
+            if_alias: None,
+            if_descr: None,
+            if_type: None,
+            mac_address: None,
+            ip_address: Some("10.1.20.1/29".parse().unwrap()),
+            admin_status: None,
+            oper_status: None,
+            speed: None,
+            mtu: None,
+            access_vlan: None,
+            last_changed: None,
+            tags: Vec::new(),
+            custom_fields: CustomFieldValues::new(),
+            created_at: now,
+            updated_at: now,
+        };
+        repo.upsert_interfaces(&[vlan]).await.unwrap();
+        let arp = ArpEntry {
+            id: Uuid::nil(),
+            device_id: router.id,
+            interface_id: None,
+            ip_address: "10.1.20.4/32".parse().unwrap(),
+            mac_address: "00:1a:2b:3c:4d:5e".parse().unwrap(),
+            first_seen: now,
+            last_seen: now,
+        };
+        repo.upsert_arp_entries(router.id, &[arp]).await.unwrap();
+        let app = build_router(repo, &settings(), changes());
+
+        let (status, refresh) = send_json(&app, "POST", "/api/subnets/refresh", serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((refresh["added"].as_u64(), refresh["total"].as_u64()), (Some(1), Some(1)));
+
+        let (status, subnets) = get_json(&app, "/api/subnets").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(subnets[0]["network"], "10.1.20.0/29");
+        assert_eq!((subnets[0]["size"].as_u64(), subnets[0]["used"].as_u64()), (Some(6), Some(2)));
+
+        let (status, usage) = get_json(&app, &format!("/api/subnets/{}?window_days=7", subnets[0]["id"].as_str().unwrap())).await;
+        assert_eq!(status, StatusCode::OK);
+        let ranges: Vec<(&str, &str)> = usage["free_ranges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| (r["first"].as_str().unwrap(), r["last"].as_str().unwrap()))
+            .collect();
+        assert_eq!(ranges, [("10.1.20.2", "10.1.20.3"), ("10.1.20.5", "10.1.20.6")]);
+
+        let (status, _) = get_json(&app, &format!("/api/subnets/{}", Uuid::new_v4())).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn lists_reverse_dns_names_and_refuses_duplicate_hostnames() {
+        let repo = Arc::new(MemoryRepository::new());
+        repo.set_ip_hostname("10.1.20.5/32".parse().unwrap(), Some("pc-5.example.net")).await.unwrap();
+        repo.set_ip_hostname("10.1.30.5/32".parse().unwrap(), Some("pc-30.example.net")).await.unwrap();
+        let first = repo.upsert_device(&device("sw-1", "10.0.1.1/32", "Cisco"), "test").await.unwrap();
+        let second = repo.upsert_device(&device("sw-2", "10.0.1.2/32", "Cisco"), "test").await.unwrap();
+        let app = build_router(repo.clone(), &settings(), changes());
+
+        let (status, names) = get_json(&app, "/api/hostnames?network=10.1.20.0/24").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(names.as_array().unwrap().len(), 1);
+        assert_eq!(names[0]["hostname"], "pc-5.example.net");
+        let (status, _) = get_json(&app, "/api/hostnames?network=10.1.20").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let uri = format!("/api/devices/{}", second.id);
+        let request = serde_json::json!({"hostname": first.hostname, "status": "maintenance"});
+        let (status, _) = send_json(&app, "PATCH", &uri, request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        // Nothing of a refused request is applied
+        assert_eq!(repo.get_device(second.id).await.unwrap().status, second.status);
+    }
+
+    #[tokio::test]
+    async fn builds_topology_links_and_keeps_manual_ones() {
+        let repo = Arc::new(MemoryRepository::new());
+        let core = repo.upsert_device(&device("core-1", "10.0.1.1/32", "Cisco"), "test").await.unwrap();
+        let access = repo.upsert_device(&device("access-1", "10.0.1.2/32", "Cisco"), "test").await.unwrap();
+        let wlc = repo.upsert_device(&device("wlc-1", "10.0.1.3/32", "Cisco"), "test").await.unwrap();
+        let now = OffsetDateTime::now_utc();
+        let neighbor = DeviceNeighbor {
+            id: Uuid::nil(),
+            device_id: access.id,
+            interface_id: None,
+            protocol: NeighborProtocol::Cdp,
+            remote_chassis_id: Some("core-1(FOC1234X0AB)".to_string()),
+            remote_port: None,
+            remote_sys_name: Some("core-1".to_string()),
+            remote_address: Some("10.0.1.1/32".parse().unwrap()),
+            last_seen: now,
+        };
+        repo.replace_device_neighbors(access.id, NeighborProtocol::Cdp, &[neighbor]).await.unwrap();
+        let app = build_router(repo, &settings(), changes());
+
+        let (status, refresh) = send_json(&app, "POST", "/api/topology/refresh", serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(refresh["added"], 1);
+        let (status, neighbors) = get_json(&app, &format!("/api/devices/{}/neighbors", access.id)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(neighbors[0]["protocol"], "cdp");
+
+        let manual = serde_json::json!({"device_id": wlc.id, "remote_device_id": core.id});
+        let (status, manual) = send_json(&app, "POST", "/api/topology/links", manual).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((&manual["source"], &manual["confidence"]), (&serde_json::json!("manual"), &serde_json::json!(100)));
+        let (status, _) =
+            send_json(&app, "POST", "/api/topology/links", serde_json::json!({"device_id": wlc.id, "remote_device_id": wlc.id})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (_, links) = get_json(&app, &format!("/api/topology/links?device={}", core.id)).await;
+        let sources: Vec<&str> = links.as_array().unwrap().iter().map(|l| l["source"].as_str().unwrap()).collect();
+        assert_eq!(sources.len(), 2);
+        assert!(sources.contains(&"cdp") && sources.contains(&"manual"));
+        let cdp = links.as_array().unwrap().iter().find(|l| l["source"] == "cdp").unwrap();
+        let (status, _) = send_json(&app, "DELETE", &format!("/api/topology/links/{}", cdp["id"].as_str().unwrap()), serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // A refresh leaves the manual link alone; deleting it is up to users
+        send_json(&app, "POST", "/api/topology/refresh", serde_json::json!({})).await;
+        let path = format!("/api/topology/links/{}", manual["id"].as_str().unwrap());
+        let (status, _) = send_json(&app, "DELETE", &path, serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send_json(&app, "DELETE", &path, serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn shows_the_root_of_each_spanning_tree_across_devices() {
+        let repo = Arc::new(MemoryRepository::new());
+        let core = repo.upsert_device(&device("core-1", "10.0.1.1/32", "Cisco"), "test").await.unwrap();
+        let access = repo.upsert_device(&device("access-1", "10.0.1.2/32", "Cisco"), "test").await.unwrap();
+        let now = OffsetDateTime::now_utc();
+        let uplink = Interface {
+            id: Uuid::nil(),
+            device_id: access.id,
+            if_index: 2,
+            if_name: Some("Gi0/2".to_string()),
+            if_alias: None,
+            if_descr: None,
+            if_type: None,
+            mac_address: None,
+            ip_address: None,
+            admin_status: None,
+            oper_status: None,
+            speed: None,
+            mtu: None,
+            access_vlan: None,
+            last_changed: None,
+            tags: Vec::new(),
+            custom_fields: CustomFieldValues::new(),
+            created_at: now,
+            updated_at: now,
+        };
+        repo.upsert_interfaces(&[uplink]).await.unwrap();
+        // The CIST and MST instance 1 (VLAN 10) both have core-1 as root
+        let instance = |device_id, instance, protocol: &str, vlans: Vec<i32>, bridge_id: &str, root: &str| StpInstance {
+            id: Uuid::nil(),
+            device_id,
+            instance,
+            protocol: protocol.to_string(),
+            vlans,
+            bridge_id: bridge_id.to_string(),
+            designated_root: root.to_string(),
+            root_cost: None,
+            root_port_if_index: None,
+            root_device_id: None,
+            last_seen: now,
+            created_at: now,
+            updated_at: now,
+        };
+        let core_instances = [
+            instance(core.id, 0, "stp", vec![], "1000aabbccddeeff", "1000aabbccddeeff"),
+            instance(core.id, 1, "mst", vec![10], "1001aabbccddeeff", "1001aabbccddeeff"),
+        ];
+        let access_instances = [
+            instance(access.id, 0, "stp", vec![], "8000001122334455", "1000aabbccddeeff"),
+            instance(access.id, 1, "mst", vec![10], "8001001122334455", "1001aabbccddeeff"),
+        ];
+        let blocking = StpPort {
+            id: Uuid::nil(),
+            interface_id: Uuid::nil(),
+            if_index: 2,
+            instance: 1,
+            state: "blocking".to_string(),
+            role: Some("alternate".to_string()),
+            path_cost: None,
+            designated_bridge: None,
+            last_seen: now,
+            created_at: now,
+            updated_at: now,
+        };
+        repo.replace_stp(core.id, &core_instances, &[]).await.unwrap();
+        repo.replace_stp(access.id, &access_instances, &[blocking]).await.unwrap();
+        let app = build_router(repo, &settings(), changes());
+
+        let (status, roots) = get_json(&app, "/api/stp/roots").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(roots.as_array().unwrap().len(), 2);
+        assert_eq!(roots[1]["protocol"], "mst");
+        assert_eq!(roots[1]["root_device_id"], core.id.to_string());
+        assert_eq!(roots[1]["device_ids"].as_array().unwrap().len(), 2);
+        assert_eq!(roots[1]["blocking_ports"], 1);
+
+        // VLAN 10 runs in instance 1 everywhere; VLAN 20 falls to the CIST
+        let (_, roots) = get_json(&app, "/api/stp/roots?vlan=10").await;
+        let instances: Vec<_> = roots.as_array().unwrap().iter().map(|r| r["instance"].as_i64().unwrap()).collect();
+        assert_eq!(instances, [1]);
+        let (_, roots) = get_json(&app, "/api/stp/roots?vlan=20").await;
+        let instances: Vec<_> = roots.as_array().unwrap().iter().map(|r| r["instance"].as_i64().unwrap()).collect();
+        assert_eq!(instances, [0]);
+    }
+
+    #[tokio::test]
+    async fn streams_change_events() {
+        let changes = changes();
+        let app = build_router(Arc::new(MemoryRepository::new()), &settings(), changes.clone());
+        let response = app.oneshot(Request::get("/api/events").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+
+        let device_id = Uuid::new_v4();
+        changes.send(ChangeEvent::DeviceUpdated { device_id }).unwrap();
+        let mut body = response.into_body().into_data_stream();
+        let chunk = body.next().await.unwrap().unwrap();
+        let data = std::str::from_utf8(&chunk).unwrap().trim().strip_prefix("data:").unwrap().to_string();
+        assert_eq!(serde_json::from_str::<ChangeEvent>(&data).unwrap(), ChangeEvent::DeviceUpdated { device_id });
+    }
+}
+
 // Remove default lib content if present
 /*
 pub fn add(left: usize, right: usize) -> usize {
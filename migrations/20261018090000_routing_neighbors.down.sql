@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP TRIGGER IF EXISTS update_routing_neighbors_updated_at ON routing_neighbors;
+DROP TABLE IF EXISTS routing_neighbors;
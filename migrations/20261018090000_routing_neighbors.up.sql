@@ -0,0 +1,24 @@
+-- Routing protocol neighbors (BGP peers, OSPF neighbors) collected per device
+
+CREATE TABLE routing_neighbors (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    protocol VARCHAR(16) NOT NULL CHECK (protocol IN ('bgp', 'ospf')),
+    neighbor_address INET NOT NULL, -- BGP remote address or OSPF neighbor interface address
+    remote_router_id INET, -- BGP identifier or OSPF router ID
+    remote_as BIGINT, -- BGP only; 4-byte ASNs need more than INTEGER
+    state VARCHAR(50) NOT NULL, -- e.g., established, active, full, twoWay
+    uptime_seconds BIGINT, -- BGP only: time in established state
+    last_seen TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (device_id, protocol, neighbor_address)
+);
+
+CREATE INDEX idx_routing_neighbors_device_id ON routing_neighbors (device_id);
+CREATE INDEX idx_routing_neighbors_state ON routing_neighbors (protocol, state);
+
+CREATE TRIGGER update_routing_neighbors_updated_at
+BEFORE UPDATE ON routing_neighbors
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
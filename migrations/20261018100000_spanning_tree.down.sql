@@ -0,0 +1,6 @@
+-- Add down migration script here
+
+DROP TRIGGER IF EXISTS update_stp_ports_updated_at ON stp_ports;
+DROP TRIGGER IF EXISTS update_stp_instances_updated_at ON stp_instances;
+DROP TABLE IF EXISTS stp_ports;
+DROP TABLE IF EXISTS stp_instances;
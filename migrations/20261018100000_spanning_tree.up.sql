@@ -0,0 +1,49 @@
+-- Spanning tree state per device instance and per interface
+
+-- One row per spanning tree instance seen on a device.
+-- instance 0 is the tree the agent reports without a context; per-VLAN
+-- trees (e.g., Cisco PVST via community indexing) use the VLAN ID and MST
+-- instances their MSTI number.
+CREATE TABLE stp_instances (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    instance INTEGER NOT NULL,
+    protocol VARCHAR(10) NOT NULL DEFAULT 'stp', -- stp (instance 0), pvst or mst
+    vlans INTEGER[] NOT NULL DEFAULT '{}', -- VLANs the tree carries; empty for instance 0, which carries the rest
+    bridge_id VARCHAR(16) NOT NULL, -- priority + MAC as 16 hex digits
+    designated_root VARCHAR(16) NOT NULL,
+    root_cost INTEGER,
+    root_port_if_index INTEGER, -- NULL when this bridge is the root
+    last_seen TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (device_id, instance)
+);
+
+CREATE INDEX idx_stp_instances_bridge_id ON stp_instances (instance, bridge_id);
+
+CREATE TABLE stp_ports (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    interface_id UUID NOT NULL REFERENCES interfaces(id) ON DELETE CASCADE,
+    instance INTEGER NOT NULL,
+    state VARCHAR(50) NOT NULL, -- disabled, blocking, listening, learning, forwarding, broken
+    role VARCHAR(50), -- root, designated, alternate, disabled
+    path_cost INTEGER,
+    designated_bridge VARCHAR(16),
+    last_seen TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (interface_id, instance)
+);
+
+CREATE INDEX idx_stp_ports_state ON stp_ports (state);
+
+CREATE TRIGGER update_stp_instances_updated_at
+BEFORE UPDATE ON stp_instances
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
+
+CREATE TRIGGER update_stp_ports_updated_at
+BEFORE UPDATE ON stp_ports
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
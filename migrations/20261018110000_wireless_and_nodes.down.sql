@@ -0,0 +1,7 @@
+-- Add down migration script here
+
+DROP TRIGGER IF EXISTS update_wireless_aps_updated_at ON wireless_aps;
+DROP TRIGGER IF EXISTS update_nodes_updated_at ON nodes;
+DROP TABLE IF EXISTS wireless_aps;
+DROP TABLE IF EXISTS node_wireless;
+DROP TABLE IF EXISTS nodes;
@@ -0,0 +1,54 @@
+-- End stations (nodes) seen on devices, and wireless controller data
+
+-- A MAC address seen on a device. For wired nodes the device is the switch
+-- and interface_id the port; for wireless clients the device is the AP.
+CREATE TABLE nodes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    mac_address MACADDR NOT NULL,
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    interface_id UUID REFERENCES interfaces(id) ON DELETE SET NULL,
+    vlan INTEGER NOT NULL DEFAULT 0, -- 0 when the VLAN is unknown
+    active BOOLEAN NOT NULL DEFAULT TRUE, -- FALSE once a later collection no longer sees it here
+    first_seen TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_seen TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (mac_address, device_id, vlan)
+);
+
+CREATE INDEX idx_nodes_mac_address ON nodes (mac_address);
+CREATE INDEX idx_nodes_device_id ON nodes (device_id, active);
+
+-- Wireless association details of a node
+CREATE TABLE node_wireless (
+    node_id UUID PRIMARY KEY REFERENCES nodes(id) ON DELETE CASCADE,
+    ssid VARCHAR(32),
+    rssi INTEGER, -- dBm
+    ip_address INET, -- as reported by the controller
+    last_seen TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Access points managed by a wireless controller. Each AP is also a device.
+CREATE TABLE wireless_aps (
+    device_id UUID PRIMARY KEY REFERENCES devices(id) ON DELETE CASCADE,
+    controller_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    mac_address MACADDR NOT NULL UNIQUE, -- base radio/Ethernet MAC used by the controller
+    name VARCHAR(255),
+    location VARCHAR(255),
+    oper_status VARCHAR(50), -- e.g., associated, disassociating, downloading
+    last_seen TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_wireless_aps_controller_id ON wireless_aps (controller_id);
+
+CREATE TRIGGER update_nodes_updated_at
+BEFORE UPDATE ON nodes
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
+
+CREATE TRIGGER update_wireless_aps_updated_at
+BEFORE UPDATE ON wireless_aps
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP TRIGGER IF EXISTS update_interface_poe_updated_at ON interface_poe;
+DROP TABLE IF EXISTS interface_poe;
@@ -0,0 +1,19 @@
+-- Power over Ethernet state per interface (POWER-ETHERNET-MIB pethPsePortTable)
+
+CREATE TABLE interface_poe (
+    interface_id UUID PRIMARY KEY REFERENCES interfaces(id) ON DELETE CASCADE,
+    group_index INTEGER NOT NULL, -- pethPsePortGroupIndex (usually the stack member)
+    port_index INTEGER NOT NULL, -- pethPsePortIndex
+    admin_enabled BOOLEAN NOT NULL,
+    detection_status VARCHAR(50), -- disabled, searching, deliveringPower, fault, test, otherFault
+    power_class VARCHAR(10), -- class0 .. class4
+    power_mw INTEGER, -- Consumption in milliwatts, where the vendor reports it
+    last_seen TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER update_interface_poe_updated_at
+BEFORE UPDATE ON interface_poe
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
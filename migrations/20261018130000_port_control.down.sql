@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP TABLE IF EXISTS port_changes;
+ALTER TABLE interfaces DROP COLUMN IF EXISTS access_vlan;
@@ -0,0 +1,19 @@
+-- Port control: access VLAN per interface and an audit log of SNMP SET changes
+
+ALTER TABLE interfaces ADD COLUMN access_vlan INTEGER; -- Untagged VLAN of an access port, where known
+
+CREATE TABLE port_changes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    interface_id UUID REFERENCES interfaces(id) ON DELETE SET NULL,
+    if_index INTEGER NOT NULL, -- Kept so the log stays readable after the interface is removed
+    action VARCHAR(50) NOT NULL, -- admin_status, description, access_vlan, poe, power_cycle
+    old_value TEXT,
+    new_value TEXT,
+    changed_by VARCHAR(255) NOT NULL,
+    succeeded BOOLEAN NOT NULL,
+    error TEXT,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_port_changes_device_id ON port_changes (device_id, changed_at);
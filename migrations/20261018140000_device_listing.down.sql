@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_devices_ip_address_gist;
+DROP INDEX IF EXISTS idx_devices_sort_last_seen;
+DROP INDEX IF EXISTS idx_devices_sort_hostname;
@@ -0,0 +1,5 @@
+-- Indexes for keyset-paginated device listings (see db::query_devices).
+-- Expressions must match DeviceSort::sql_expr for the planner to use them.
+CREATE INDEX idx_devices_sort_hostname ON devices ((COALESCE(hostname, '')), id);
+CREATE INDEX idx_devices_sort_last_seen ON devices ((COALESCE(last_seen, '-infinity'::timestamptz)), id);
+CREATE INDEX idx_devices_ip_address_gist ON devices USING gist (ip_address inet_ops);
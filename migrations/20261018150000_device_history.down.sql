@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+DROP TABLE IF EXISTS device_history;
+
+ALTER TABLE devices DROP COLUMN IF EXISTS last_boot;
@@ -0,0 +1,16 @@
+-- Device history: one row per attribute changed by an upsert
+
+-- Boot time derived from sysUpTime; a later one is recorded as a reboot
+ALTER TABLE devices ADD COLUMN last_boot TIMESTAMPTZ;
+
+CREATE TABLE device_history (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    attribute VARCHAR(50) NOT NULL, -- devices column name, e.g. os_version, serial_number
+    old_value TEXT,
+    new_value TEXT,
+    source VARCHAR(100) NOT NULL, -- Job or component that wrote the change, e.g. discovery, wireless
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_device_history_device_id ON device_history (device_id, changed_at);
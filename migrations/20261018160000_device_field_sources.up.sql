@@ -0,0 +1,9 @@
+-- Source that last set each device attribute, for merge upserts and manual overrides
+
+CREATE TABLE device_field_sources (
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    field VARCHAR(50) NOT NULL, -- devices column name
+    source VARCHAR(100) NOT NULL, -- e.g. discovery, wireless, manual:<user>
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (device_id, field)
+);
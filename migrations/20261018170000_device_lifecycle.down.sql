@@ -0,0 +1,23 @@
+-- Add down migration script here
+
+ALTER TABLE devices DROP COLUMN IF EXISTS device_type;
+DROP TYPE IF EXISTS device_type;
+
+-- Enum values cannot be dropped; rebuild device_status with the original values
+ALTER TABLE devices ALTER COLUMN status DROP DEFAULT;
+ALTER TABLE devices ALTER COLUMN status TYPE TEXT USING status::text;
+UPDATE devices SET status = CASE status
+    WHEN 'active' THEN 'up'
+    WHEN 'maintenance' THEN 'unknown'
+    WHEN 'retired' THEN 'down'
+    WHEN 'decommissioned' THEN 'down'
+    ELSE status
+END;
+DROP TYPE device_status;
+CREATE TYPE device_status AS ENUM (
+    'up',
+    'down',
+    'unknown'
+);
+ALTER TABLE devices ALTER COLUMN status TYPE device_status USING status::device_status;
+ALTER TABLE devices ALTER COLUMN status SET DEFAULT 'unknown';
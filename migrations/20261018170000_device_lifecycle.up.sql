@@ -0,0 +1,22 @@
+-- Lifecycle states next to reachability, and a device type.
+-- maintenance, retired and decommissioned are set by operators and kept by discovery.
+
+ALTER TYPE device_status ADD VALUE IF NOT EXISTS 'active';
+ALTER TYPE device_status ADD VALUE IF NOT EXISTS 'maintenance';
+ALTER TYPE device_status ADD VALUE IF NOT EXISTS 'retired';
+ALTER TYPE device_status ADD VALUE IF NOT EXISTS 'decommissioned';
+
+CREATE TYPE device_type AS ENUM (
+    'router',
+    'switch',
+    'firewall',
+    'access_point',
+    'host',
+    'printer',
+    'storage',
+    'unknown'
+);
+
+ALTER TABLE devices ADD COLUMN device_type device_type DEFAULT 'unknown';
+
+CREATE INDEX idx_devices_device_type ON devices (device_type);
@@ -0,0 +1,9 @@
+-- Add down migration script here
+
+DROP TABLE IF EXISTS device_group_members;
+DROP TABLE IF EXISTS device_groups;
+ALTER TABLE devices DROP COLUMN IF EXISTS location_id;
+ALTER TABLE devices DROP COLUMN IF EXISTS sys_location;
+DROP TABLE IF EXISTS location_rules;
+DROP TABLE IF EXISTS locations;
+DROP TYPE IF EXISTS location_kind;
@@ -0,0 +1,54 @@
+-- Location hierarchy (site > building > room > rack), rules assigning devices
+-- to locations, and static or rule-based device groups
+
+CREATE TYPE location_kind AS ENUM (
+    'site',
+    'building',
+    'room',
+    'rack'
+);
+
+CREATE TABLE locations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    parent_id UUID REFERENCES locations(id) ON DELETE CASCADE, -- NULL for sites
+    kind location_kind NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    description TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_locations_parent_name ON locations (COALESCE(parent_id, '00000000-0000-0000-0000-000000000000'::uuid), name);
+
+-- First matching rule (lowest priority) places a device; all set conditions must match
+CREATE TABLE location_rules (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    location_id UUID NOT NULL REFERENCES locations(id) ON DELETE CASCADE,
+    priority INTEGER NOT NULL DEFAULT 100,
+    sys_location_pattern VARCHAR(255), -- case-insensitive substring of sys_location
+    subnet CIDR, -- management address within this subnet
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (sys_location_pattern IS NOT NULL OR subnet IS NOT NULL)
+);
+
+ALTER TABLE devices ADD COLUMN sys_location VARCHAR(255);
+ALTER TABLE devices ADD COLUMN location_id UUID REFERENCES locations(id) ON DELETE SET NULL;
+CREATE INDEX idx_devices_location_id ON devices (location_id);
+
+CREATE TABLE device_groups (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(255) NOT NULL UNIQUE,
+    description TEXT,
+    rules JSONB, -- db::DeviceFilter for dynamic groups, NULL for static ones
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE device_group_members (
+    group_id UUID NOT NULL REFERENCES device_groups(id) ON DELETE CASCADE,
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    added_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (group_id, device_id)
+);
+
+CREATE INDEX idx_device_group_members_device_id ON device_group_members (device_id);
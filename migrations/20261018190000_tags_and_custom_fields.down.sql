@@ -0,0 +1,9 @@
+-- Add down migration script here
+
+ALTER TABLE interfaces DROP COLUMN IF EXISTS custom_fields;
+ALTER TABLE interfaces DROP COLUMN IF EXISTS tags;
+ALTER TABLE devices DROP COLUMN IF EXISTS custom_fields;
+ALTER TABLE devices DROP COLUMN IF EXISTS tags;
+DROP TABLE IF EXISTS custom_fields;
+DROP TYPE IF EXISTS custom_field_type;
+DROP TYPE IF EXISTS custom_field_object;
@@ -0,0 +1,37 @@
+-- Free-form tags and typed, user-defined custom fields on devices and interfaces.
+-- Values live next to the discovered columns but are never written by discovery.
+
+CREATE TYPE custom_field_object AS ENUM (
+    'device',
+    'interface'
+);
+
+CREATE TYPE custom_field_type AS ENUM (
+    'string',
+    'int',
+    'enum',
+    'date',
+    'ip'
+);
+
+CREATE TABLE custom_fields (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    object_type custom_field_object NOT NULL,
+    name VARCHAR(64) NOT NULL,
+    field_type custom_field_type NOT NULL,
+    choices TEXT[] NOT NULL DEFAULT '{}', -- allowed values of enum fields
+    description TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (object_type, name)
+);
+
+ALTER TABLE devices ADD COLUMN tags TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE devices ADD COLUMN custom_fields JSONB NOT NULL DEFAULT '{}';
+CREATE INDEX idx_devices_tags ON devices USING GIN (tags);
+CREATE INDEX idx_devices_custom_fields ON devices USING GIN (custom_fields);
+
+ALTER TABLE interfaces ADD COLUMN tags TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE interfaces ADD COLUMN custom_fields JSONB NOT NULL DEFAULT '{}';
+CREATE INDEX idx_interfaces_tags ON interfaces USING GIN (tags);
+CREATE INDEX idx_interfaces_custom_fields ON interfaces USING GIN (custom_fields);
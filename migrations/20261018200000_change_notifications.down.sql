@@ -0,0 +1,4 @@
+DROP TRIGGER IF EXISTS notify_interfaces_oper_status ON interfaces;
+DROP FUNCTION IF EXISTS notify_interface_oper_status();
+DROP TRIGGER IF EXISTS notify_devices_change ON devices;
+DROP FUNCTION IF EXISTS notify_device_change();
@@ -0,0 +1,42 @@
+-- Change feed: triggers publish inventory changes on the nd_changes channel as JSON
+-- (see db::subscribe_changes). Refreshing last_seen alone is not a change.
+
+CREATE OR REPLACE FUNCTION notify_device_change()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        PERFORM pg_notify('nd_changes', json_build_object('event', 'device_created', 'device_id', NEW.id)::text);
+    ELSIF TG_OP = 'DELETE' THEN
+        PERFORM pg_notify('nd_changes', json_build_object('event', 'device_removed', 'device_id', OLD.id)::text);
+    ELSIF (to_jsonb(OLD) - 'last_seen' - 'updated_at') IS DISTINCT FROM (to_jsonb(NEW) - 'last_seen' - 'updated_at') THEN
+        PERFORM pg_notify('nd_changes', json_build_object('event', 'device_updated', 'device_id', NEW.id)::text);
+    END IF;
+    RETURN NULL;
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER notify_devices_change
+AFTER INSERT OR UPDATE OR DELETE ON devices
+FOR EACH ROW
+EXECUTE FUNCTION notify_device_change();
+
+CREATE OR REPLACE FUNCTION notify_interface_oper_status()
+RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify('nd_changes', json_build_object(
+        'event', 'interface_oper_status_changed',
+        'interface_id', NEW.id,
+        'device_id', NEW.device_id,
+        'if_index', NEW.if_index,
+        'old_status', OLD.oper_status,
+        'new_status', NEW.oper_status
+    )::text);
+    RETURN NULL;
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER notify_interfaces_oper_status
+AFTER UPDATE OF oper_status ON interfaces
+FOR EACH ROW
+WHEN (OLD.oper_status IS DISTINCT FROM NEW.oper_status)
+EXECUTE FUNCTION notify_interface_oper_status();
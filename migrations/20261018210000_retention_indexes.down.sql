@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_port_changes_changed_at;
+DROP INDEX IF EXISTS idx_device_history_changed_at;
+DROP INDEX IF EXISTS idx_nodes_last_seen;
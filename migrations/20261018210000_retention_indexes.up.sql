@@ -0,0 +1,5 @@
+-- Retention prunes by age; these let it find old rows without scanning whole tables.
+
+CREATE INDEX idx_nodes_last_seen ON nodes (last_seen);
+CREATE INDEX idx_device_history_changed_at ON device_history (changed_at);
+CREATE INDEX idx_port_changes_changed_at ON port_changes (changed_at);
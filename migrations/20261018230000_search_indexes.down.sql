@@ -0,0 +1,11 @@
+DROP INDEX IF EXISTS idx_node_wireless_ip_address_gist;
+DROP INDEX IF EXISTS idx_nodes_mac_hex_trgm;
+DROP INDEX IF EXISTS idx_interfaces_ip_address_gist;
+DROP INDEX IF EXISTS idx_interfaces_mac_hex_trgm;
+DROP INDEX IF EXISTS idx_interfaces_description_fts;
+DROP INDEX IF EXISTS idx_interfaces_if_alias_trgm;
+DROP INDEX IF EXISTS idx_interfaces_if_name_trgm;
+DROP INDEX IF EXISTS idx_devices_serial_number_trgm;
+DROP INDEX IF EXISTS idx_devices_sys_name_trgm;
+DROP INDEX IF EXISTS idx_devices_hostname_trgm;
+-- pg_trgm stays installed; other objects may depend on it
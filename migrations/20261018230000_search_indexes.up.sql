@@ -0,0 +1,19 @@
+-- Unified search: trigram indexes for name and serial fragments (and typos), a text search
+-- index over interface descriptions, MAC fragments without separators, and GiST indexes so
+-- address and subnet lookups need no table scan.
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX idx_devices_hostname_trgm ON devices USING gin (hostname gin_trgm_ops);
+CREATE INDEX idx_devices_sys_name_trgm ON devices USING gin (sys_name gin_trgm_ops);
+CREATE INDEX idx_devices_serial_number_trgm ON devices USING gin (serial_number gin_trgm_ops);
+
+CREATE INDEX idx_interfaces_if_name_trgm ON interfaces USING gin (if_name gin_trgm_ops);
+CREATE INDEX idx_interfaces_if_alias_trgm ON interfaces USING gin (if_alias gin_trgm_ops);
+CREATE INDEX idx_interfaces_description_fts ON interfaces
+    USING gin (to_tsvector('simple', COALESCE(if_alias, '') || ' ' || COALESCE(if_descr, '')));
+CREATE INDEX idx_interfaces_mac_hex_trgm ON interfaces USING gin (replace(mac_address::text, ':', '') gin_trgm_ops);
+CREATE INDEX idx_interfaces_ip_address_gist ON interfaces USING gist (ip_address inet_ops);
+
+CREATE INDEX idx_nodes_mac_hex_trgm ON nodes USING gin (replace(mac_address::text, ':', '') gin_trgm_ops);
+CREATE INDEX idx_node_wireless_ip_address_gist ON node_wireless USING gist (ip_address inet_ops);
@@ -0,0 +1,8 @@
+DROP TABLE IF EXISTS anomalies;
+DROP TYPE IF EXISTS anomaly_kind;
+DROP TRIGGER IF EXISTS track_node_ips ON node_wireless;
+DROP FUNCTION IF EXISTS track_node_ip();
+ALTER TABLE node_wireless DROP COLUMN IF EXISTS ip_since;
+DROP TRIGGER IF EXISTS record_node_moves ON nodes;
+DROP FUNCTION IF EXISTS record_node_move();
+DROP TABLE IF EXISTS node_moves;
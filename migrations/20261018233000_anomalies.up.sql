@@ -0,0 +1,78 @@
+-- Port moves of MAC sightings, client address changes, and the anomalies detected from them
+
+CREATE TABLE node_moves (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    node_id UUID NOT NULL REFERENCES nodes(id) ON DELETE CASCADE,
+    from_interface_id UUID REFERENCES interfaces(id) ON DELETE SET NULL,
+    to_interface_id UUID REFERENCES interfaces(id) ON DELETE SET NULL,
+    moved_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_node_moves_moved_at ON node_moves (moved_at);
+CREATE INDEX idx_node_moves_node_id ON node_moves (node_id);
+
+-- Only moves between known ports; a sighting gaining or losing its port is not a move
+CREATE OR REPLACE FUNCTION record_node_move()
+RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO node_moves (node_id, from_interface_id, to_interface_id, moved_at)
+    VALUES (NEW.id, OLD.interface_id, NEW.interface_id, NEW.last_seen);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER record_node_moves
+    AFTER UPDATE OF interface_id ON nodes
+    FOR EACH ROW
+    WHEN (OLD.interface_id IS NOT NULL AND NEW.interface_id IS NOT NULL
+          AND OLD.interface_id <> NEW.interface_id)
+    EXECUTE FUNCTION record_node_move();
+
+-- When a client took its current address; a duplicate needs two MACs claiming it at once,
+-- not one handing it over to the next
+ALTER TABLE node_wireless ADD COLUMN ip_since TIMESTAMPTZ;
+UPDATE node_wireless SET ip_since = last_seen WHERE ip_address IS NOT NULL;
+
+CREATE OR REPLACE FUNCTION track_node_ip()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.ip_address IS NULL THEN
+        NEW.ip_since := NULL;
+    ELSIF TG_OP = 'INSERT' OR OLD.ip_address IS DISTINCT FROM NEW.ip_address THEN
+        NEW.ip_since := NEW.last_seen;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER track_node_ips
+    BEFORE INSERT OR UPDATE OF ip_address ON node_wireless
+    FOR EACH ROW
+    EXECUTE FUNCTION track_node_ip();
+
+CREATE TYPE anomaly_kind AS ENUM (
+    'duplicate_ip',
+    'mac_flapping',
+    'mac_multiple_ports'
+);
+
+-- One row per finding; a detection run refreshes open findings and resolves the ones it no longer sees
+CREATE TABLE anomalies (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    kind anomaly_kind NOT NULL,
+    ip_address INET, -- duplicate_ip
+    mac_address MACADDR, -- mac_flapping, mac_multiple_ports
+    mac_addresses MACADDR[] NOT NULL DEFAULT '{}', -- duplicate_ip: MACs claiming the address
+    device_ids UUID[] NOT NULL DEFAULT '{}',
+    interface_ids UUID[] NOT NULL DEFAULT '{}',
+    moves INTEGER, -- mac_flapping: port moves within the window
+    first_detected TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_detected TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    resolved_at TIMESTAMPTZ,
+    CHECK (ip_address IS NOT NULL OR mac_address IS NOT NULL)
+);
+
+CREATE INDEX idx_anomalies_last_detected ON anomalies (last_detected);
+CREATE INDEX idx_anomalies_mac_address ON anomalies (mac_address);
+CREATE INDEX idx_anomalies_ip_address ON anomalies (ip_address);
+CREATE INDEX idx_anomalies_open ON anomalies (kind) WHERE resolved_at IS NULL;
@@ -0,0 +1,4 @@
+DROP TRIGGER IF EXISTS notify_mac_violations ON mac_violations;
+DROP FUNCTION IF EXISTS notify_mac_violation();
+DROP TABLE IF EXISTS mac_violations;
+DROP TABLE IF EXISTS mac_allowlist;
@@ -0,0 +1,47 @@
+-- Approved MACs and OUIs, and the unknown MACs seen on access ports
+
+CREATE TABLE mac_allowlist (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    mac_prefix VARCHAR(12) NOT NULL CHECK (mac_prefix ~ '^[0-9a-f]{6,12}$'), -- Lowercase hex: an OUI, MA-M/MA-S block or full MAC
+    location_id UUID REFERENCES locations(id) ON DELETE CASCADE, -- Devices in this location or below; everywhere when NULL
+    vlan INTEGER, -- Every VLAN when NULL
+    description TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_mac_allowlist_location_id ON mac_allowlist (location_id);
+
+-- One row per unknown MAC on a switch or AP and VLAN; port moves update interface_id
+CREATE TABLE mac_violations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    mac_address MACADDR NOT NULL,
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    interface_id UUID REFERENCES interfaces(id) ON DELETE SET NULL, -- NULL for wireless clients
+    vlan INTEGER NOT NULL DEFAULT 0,
+    first_seen TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_seen TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    port_shutdown_at TIMESTAMPTZ, -- Set when the rogue policy shut the port
+    UNIQUE (mac_address, device_id, vlan)
+);
+
+CREATE INDEX idx_mac_violations_last_seen ON mac_violations (last_seen);
+
+-- Alert on the change feed when an unknown MAC first shows up
+CREATE OR REPLACE FUNCTION notify_mac_violation()
+RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify('nd_changes', json_build_object(
+        'event', 'mac_violation',
+        'violation_id', NEW.id,
+        'mac_address', NEW.mac_address,
+        'device_id', NEW.device_id,
+        'interface_id', NEW.interface_id
+    )::text);
+    RETURN NULL;
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER notify_mac_violations
+AFTER INSERT ON mac_violations
+FOR EACH ROW
+EXECUTE FUNCTION notify_mac_violation();
@@ -0,0 +1,7 @@
+-- IEEE MAC address block assignments imported with `nd-rust oui import`
+
+CREATE TABLE mac_vendors (
+    prefix VARCHAR(9) PRIMARY KEY CHECK (prefix ~ '^([0-9a-f]{6}|[0-9a-f]{7}|[0-9a-f]{9})$'), -- MA-L, MA-M or MA-S
+    organization TEXT NOT NULL,
+    imported_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,25 @@
+-- ARP entries collected from routers, and the subnets derived from interface addresses
+
+CREATE TABLE arp_entries (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    interface_id UUID REFERENCES interfaces(id) ON DELETE SET NULL,
+    ip_address INET NOT NULL,
+    mac_address MACADDR NOT NULL,
+    first_seen TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_seen TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (device_id, ip_address)
+);
+
+CREATE INDEX idx_arp_entries_ip_address_gist ON arp_entries USING gist (ip_address inet_ops);
+-- Retention prunes ARP entries by age
+CREATE INDEX idx_arp_entries_last_seen ON arp_entries (last_seen);
+-- Search looks ARP entries up by MAC
+CREATE INDEX idx_arp_entries_mac_address ON arp_entries (mac_address);
+
+CREATE TABLE subnets (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    network CIDR NOT NULL UNIQUE,
+    first_seen TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_seen TIMESTAMPTZ NOT NULL DEFAULT NOW() -- Last refresh that found it on an interface
+);
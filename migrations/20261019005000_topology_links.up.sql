@@ -0,0 +1,41 @@
+-- LLDP/CDP neighbors collected per device, and the links between devices built from them,
+-- from MAC tables and by hand
+
+CREATE TABLE device_neighbors (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    interface_id UUID REFERENCES interfaces(id) ON DELETE SET NULL, -- Local port
+    protocol VARCHAR(16) NOT NULL CHECK (protocol IN ('lldp', 'cdp')),
+    remote_chassis_id TEXT, -- LLDP chassis ID (a MAC is stored as aa:bb:..) or CDP device ID
+    remote_port TEXT, -- LLDP port ID or CDP device port, e.g. Gi1/0/1
+    remote_sys_name TEXT,
+    remote_address INET, -- Management address
+    last_seen TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_device_neighbors_device_id ON device_neighbors (device_id);
+
+CREATE TABLE topology_links (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    interface_id UUID REFERENCES interfaces(id) ON DELETE SET NULL,
+    remote_device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    remote_interface_id UUID REFERENCES interfaces(id) ON DELETE SET NULL,
+    source VARCHAR(16) NOT NULL CHECK (source IN ('lldp', 'cdp', 'fdb', 'manual')),
+    confidence SMALLINT NOT NULL CHECK (confidence BETWEEN 0 AND 100), -- Percent
+    first_seen TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_seen TIMESTAMPTZ NOT NULL DEFAULT NOW(), -- Last refresh that found it; creation for manual links
+    CHECK (device_id <> remote_device_id)
+);
+
+CREATE INDEX idx_topology_links_device_id ON topology_links (device_id);
+CREATE INDEX idx_topology_links_remote_device_id ON topology_links (remote_device_id);
+
+-- One link per connection and source, so concurrent refreshes cannot store a link twice
+CREATE UNIQUE INDEX idx_topology_links_connection ON topology_links (
+    device_id,
+    COALESCE(interface_id, '00000000-0000-0000-0000-000000000000'::uuid),
+    remote_device_id,
+    COALESCE(remote_interface_id, '00000000-0000-0000-0000-000000000000'::uuid),
+    source
+);
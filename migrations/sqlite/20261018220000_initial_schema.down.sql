@@ -0,0 +1,17 @@
+DROP TABLE IF EXISTS custom_fields;
+DROP TABLE IF EXISTS device_group_members;
+DROP TABLE IF EXISTS device_groups;
+DROP TABLE IF EXISTS device_field_sources;
+DROP TABLE IF EXISTS device_history;
+DROP TABLE IF EXISTS port_changes;
+DROP TABLE IF EXISTS interface_poe;
+DROP TABLE IF EXISTS wireless_aps;
+DROP TABLE IF EXISTS node_wireless;
+DROP TABLE IF EXISTS nodes;
+DROP TABLE IF EXISTS stp_ports;
+DROP TABLE IF EXISTS stp_instances;
+DROP TABLE IF EXISTS routing_neighbors;
+DROP TABLE IF EXISTS interfaces;
+DROP TABLE IF EXISTS devices;
+DROP TABLE IF EXISTS location_rules;
+DROP TABLE IF EXISTS locations;
@@ -0,0 +1,236 @@
+-- SQLite schema, equivalent to the Postgres migrations up to 20261018210000.
+-- UUIDs are 16-byte blobs, timestamps RFC 3339 text (compare them with julianday()),
+-- INET/CIDR and MACADDR their canonical text ("192.0.2.1/32", "00:1a:2b:3c:4d:5e"),
+-- arrays and JSONB JSON text, and enums text checked against their values.
+-- Postgres-only features (change notifications, GiST/GIN indexes) have no equivalent.
+
+CREATE TABLE locations (
+    id BLOB PRIMARY KEY,
+    parent_id BLOB REFERENCES locations(id) ON DELETE CASCADE, -- NULL for sites
+    kind TEXT NOT NULL CHECK (kind IN ('site', 'building', 'room', 'rack')),
+    name TEXT NOT NULL,
+    description TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE UNIQUE INDEX idx_locations_parent_name ON locations (COALESCE(parent_id, x''), name);
+
+CREATE TABLE location_rules (
+    id BLOB PRIMARY KEY,
+    location_id BLOB NOT NULL REFERENCES locations(id) ON DELETE CASCADE,
+    priority INTEGER NOT NULL DEFAULT 100,
+    sys_location_pattern TEXT, -- case-insensitive substring of sys_location
+    subnet TEXT, -- management address within this subnet
+    created_at TEXT NOT NULL,
+    CHECK (sys_location_pattern IS NOT NULL OR subnet IS NOT NULL)
+);
+
+CREATE TABLE devices (
+    id BLOB PRIMARY KEY,
+    hostname TEXT UNIQUE,
+    ip_address TEXT NOT NULL UNIQUE,
+    sys_name TEXT,
+    sys_descr TEXT,
+    sys_location TEXT,
+    vendor TEXT,
+    model TEXT,
+    os_version TEXT,
+    serial_number TEXT,
+    status TEXT DEFAULT 'unknown'
+        CHECK (status IN ('up', 'down', 'unknown', 'active', 'maintenance', 'retired', 'decommissioned')),
+    device_type TEXT DEFAULT 'unknown'
+        CHECK (device_type IN ('router', 'switch', 'firewall', 'access_point', 'host', 'printer', 'storage', 'unknown')),
+    location_id BLOB REFERENCES locations(id) ON DELETE SET NULL,
+    tags TEXT NOT NULL DEFAULT '[]',
+    custom_fields TEXT NOT NULL DEFAULT '{}',
+    last_seen TEXT,
+    last_boot TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_devices_location_id ON devices (location_id);
+
+CREATE TABLE interfaces (
+    id BLOB PRIMARY KEY,
+    device_id BLOB NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    if_index INTEGER NOT NULL,
+    if_name TEXT,
+    if_alias TEXT,
+    if_descr TEXT,
+    if_type TEXT,
+    mac_address TEXT,
+    ip_address TEXT,
+    admin_status TEXT,
+    oper_status TEXT,
+    speed INTEGER,
+    mtu INTEGER,
+    access_vlan INTEGER,
+    last_changed TEXT,
+    tags TEXT NOT NULL DEFAULT '[]',
+    custom_fields TEXT NOT NULL DEFAULT '{}',
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    UNIQUE (device_id, if_index)
+);
+
+CREATE INDEX idx_interfaces_mac_address ON interfaces (mac_address);
+
+CREATE TABLE routing_neighbors (
+    id BLOB PRIMARY KEY,
+    device_id BLOB NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    protocol TEXT NOT NULL CHECK (protocol IN ('bgp', 'ospf')),
+    neighbor_address TEXT NOT NULL,
+    remote_router_id TEXT,
+    remote_as INTEGER,
+    state TEXT NOT NULL,
+    uptime_seconds INTEGER,
+    last_seen TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    UNIQUE (device_id, protocol, neighbor_address)
+);
+
+CREATE TABLE stp_instances (
+    id BLOB PRIMARY KEY,
+    device_id BLOB NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    instance INTEGER NOT NULL,
+    protocol TEXT NOT NULL DEFAULT 'stp', -- stp (instance 0), pvst or mst
+    vlans TEXT NOT NULL DEFAULT '[]', -- JSON array; empty for instance 0
+    bridge_id TEXT NOT NULL,
+    designated_root TEXT NOT NULL,
+    root_cost INTEGER,
+    root_port_if_index INTEGER,
+    last_seen TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    UNIQUE (device_id, instance)
+);
+
+CREATE INDEX idx_stp_instances_bridge_id ON stp_instances (instance, bridge_id);
+
+CREATE TABLE stp_ports (
+    id BLOB PRIMARY KEY,
+    interface_id BLOB NOT NULL REFERENCES interfaces(id) ON DELETE CASCADE,
+    instance INTEGER NOT NULL,
+    state TEXT NOT NULL,
+    role TEXT,
+    path_cost INTEGER,
+    designated_bridge TEXT,
+    last_seen TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    UNIQUE (interface_id, instance)
+);
+
+CREATE TABLE nodes (
+    id BLOB PRIMARY KEY,
+    mac_address TEXT NOT NULL,
+    device_id BLOB NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    interface_id BLOB REFERENCES interfaces(id) ON DELETE SET NULL,
+    vlan INTEGER NOT NULL DEFAULT 0,
+    active INTEGER NOT NULL DEFAULT 1,
+    first_seen TEXT NOT NULL,
+    last_seen TEXT NOT NULL,
+    UNIQUE (mac_address, device_id, vlan)
+);
+
+CREATE INDEX idx_nodes_device_id ON nodes (device_id, active);
+
+CREATE TABLE node_wireless (
+    node_id BLOB PRIMARY KEY REFERENCES nodes(id) ON DELETE CASCADE,
+    ssid TEXT,
+    rssi INTEGER,
+    ip_address TEXT,
+    last_seen TEXT NOT NULL
+);
+
+CREATE TABLE wireless_aps (
+    device_id BLOB PRIMARY KEY REFERENCES devices(id) ON DELETE CASCADE,
+    controller_id BLOB NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    mac_address TEXT NOT NULL UNIQUE,
+    name TEXT,
+    location TEXT,
+    oper_status TEXT,
+    last_seen TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_wireless_aps_controller_id ON wireless_aps (controller_id);
+
+CREATE TABLE interface_poe (
+    interface_id BLOB PRIMARY KEY REFERENCES interfaces(id) ON DELETE CASCADE,
+    group_index INTEGER NOT NULL,
+    port_index INTEGER NOT NULL,
+    admin_enabled INTEGER NOT NULL,
+    detection_status TEXT,
+    power_class TEXT,
+    power_mw INTEGER,
+    last_seen TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE port_changes (
+    id BLOB PRIMARY KEY,
+    device_id BLOB NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    interface_id BLOB REFERENCES interfaces(id) ON DELETE SET NULL,
+    if_index INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    old_value TEXT,
+    new_value TEXT,
+    changed_by TEXT NOT NULL,
+    succeeded INTEGER NOT NULL,
+    error TEXT,
+    changed_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_port_changes_device_id ON port_changes (device_id);
+
+CREATE TABLE device_history (
+    id BLOB PRIMARY KEY,
+    device_id BLOB NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    attribute TEXT NOT NULL,
+    old_value TEXT,
+    new_value TEXT,
+    source TEXT NOT NULL,
+    changed_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_device_history_device_id ON device_history (device_id);
+
+CREATE TABLE device_field_sources (
+    device_id BLOB NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    field TEXT NOT NULL,
+    source TEXT NOT NULL,
+    PRIMARY KEY (device_id, field)
+);
+
+CREATE TABLE device_groups (
+    id BLOB PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    description TEXT,
+    rules TEXT, -- db::DeviceFilter as JSON for dynamic groups, NULL for static ones
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE device_group_members (
+    group_id BLOB NOT NULL REFERENCES device_groups(id) ON DELETE CASCADE,
+    device_id BLOB NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    PRIMARY KEY (group_id, device_id)
+);
+
+CREATE TABLE custom_fields (
+    id BLOB PRIMARY KEY,
+    object_type TEXT NOT NULL CHECK (object_type IN ('device', 'interface')),
+    name TEXT NOT NULL,
+    field_type TEXT NOT NULL CHECK (field_type IN ('string', 'int', 'enum', 'date', 'ip')),
+    choices TEXT NOT NULL DEFAULT '[]',
+    description TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    UNIQUE (object_type, name)
+);
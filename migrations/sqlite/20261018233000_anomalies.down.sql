@@ -0,0 +1,6 @@
+DROP TABLE IF EXISTS anomalies;
+DROP TRIGGER IF EXISTS track_node_ips;
+DROP TRIGGER IF EXISTS track_new_node_ips;
+ALTER TABLE node_wireless DROP COLUMN ip_since;
+DROP TRIGGER IF EXISTS record_node_moves;
+DROP TABLE IF EXISTS node_moves;
@@ -0,0 +1,63 @@
+-- Port moves of MAC sightings, client address changes, and the anomalies detected from them
+
+CREATE TABLE node_moves (
+    id BLOB PRIMARY KEY DEFAULT (randomblob(16)),
+    node_id BLOB NOT NULL REFERENCES nodes(id) ON DELETE CASCADE,
+    from_interface_id BLOB REFERENCES interfaces(id) ON DELETE SET NULL,
+    to_interface_id BLOB REFERENCES interfaces(id) ON DELETE SET NULL,
+    moved_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_node_moves_moved_at ON node_moves (julianday(moved_at));
+CREATE INDEX idx_node_moves_node_id ON node_moves (node_id);
+
+-- Only moves between known ports; a sighting gaining or losing its port is not a move
+CREATE TRIGGER record_node_moves
+    AFTER UPDATE OF interface_id ON nodes
+    FOR EACH ROW
+    WHEN OLD.interface_id IS NOT NULL AND NEW.interface_id IS NOT NULL AND OLD.interface_id <> NEW.interface_id
+BEGIN
+    INSERT INTO node_moves (node_id, from_interface_id, to_interface_id, moved_at)
+    VALUES (NEW.id, OLD.interface_id, NEW.interface_id, NEW.last_seen);
+END;
+
+-- When a client took its current address; a duplicate needs two MACs claiming it at once,
+-- not one handing it over to the next
+ALTER TABLE node_wireless ADD COLUMN ip_since TEXT;
+UPDATE node_wireless SET ip_since = last_seen WHERE ip_address IS NOT NULL;
+
+CREATE TRIGGER track_new_node_ips
+    AFTER INSERT ON node_wireless
+    FOR EACH ROW
+    WHEN NEW.ip_address IS NOT NULL
+BEGIN
+    UPDATE node_wireless SET ip_since = NEW.last_seen WHERE node_id = NEW.node_id;
+END;
+
+CREATE TRIGGER track_node_ips
+    AFTER UPDATE OF ip_address ON node_wireless
+    FOR EACH ROW
+    WHEN OLD.ip_address IS NOT NEW.ip_address
+BEGIN
+    UPDATE node_wireless SET ip_since = CASE WHEN NEW.ip_address IS NULL THEN NULL ELSE NEW.last_seen END
+    WHERE node_id = NEW.node_id;
+END;
+
+CREATE TABLE anomalies (
+    id BLOB PRIMARY KEY,
+    kind TEXT NOT NULL CHECK (kind IN ('duplicate_ip', 'mac_flapping', 'mac_multiple_ports')),
+    ip_address TEXT,
+    mac_address TEXT,
+    mac_addresses TEXT NOT NULL DEFAULT '[]',
+    device_ids TEXT NOT NULL DEFAULT '[]',
+    interface_ids TEXT NOT NULL DEFAULT '[]',
+    moves INTEGER,
+    first_detected TEXT NOT NULL,
+    last_detected TEXT NOT NULL,
+    resolved_at TEXT,
+    CHECK (ip_address IS NOT NULL OR mac_address IS NOT NULL)
+);
+
+CREATE INDEX idx_anomalies_last_detected ON anomalies (julianday(last_detected));
+CREATE INDEX idx_anomalies_mac_address ON anomalies (mac_address);
+CREATE INDEX idx_anomalies_ip_address ON anomalies (ip_address);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS mac_violations;
+DROP TABLE IF EXISTS mac_allowlist;
@@ -0,0 +1,26 @@
+-- Approved MACs and OUIs, and the unknown MACs seen on access ports
+
+CREATE TABLE mac_allowlist (
+    id BLOB PRIMARY KEY,
+    mac_prefix TEXT NOT NULL CHECK (length(mac_prefix) BETWEEN 6 AND 12 AND mac_prefix NOT GLOB '*[^0-9a-f]*'),
+    location_id BLOB REFERENCES locations(id) ON DELETE CASCADE,
+    vlan INTEGER,
+    description TEXT,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_mac_allowlist_location_id ON mac_allowlist (location_id);
+
+CREATE TABLE mac_violations (
+    id BLOB PRIMARY KEY,
+    mac_address TEXT NOT NULL,
+    device_id BLOB NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    interface_id BLOB REFERENCES interfaces(id) ON DELETE SET NULL,
+    vlan INTEGER NOT NULL DEFAULT 0,
+    first_seen TEXT NOT NULL,
+    last_seen TEXT NOT NULL,
+    port_shutdown_at TEXT,
+    UNIQUE (mac_address, device_id, vlan)
+);
+
+CREATE INDEX idx_mac_violations_last_seen ON mac_violations (julianday(last_seen));
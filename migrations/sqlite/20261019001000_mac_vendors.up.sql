@@ -0,0 +1,7 @@
+-- IEEE MAC address block assignments imported with `nd-rust oui import`
+
+CREATE TABLE mac_vendors (
+    prefix TEXT PRIMARY KEY CHECK (length(prefix) IN (6, 7, 9) AND prefix NOT GLOB '*[^0-9a-f]*'), -- MA-L, MA-M or MA-S
+    organization TEXT NOT NULL,
+    imported_at TEXT NOT NULL
+);
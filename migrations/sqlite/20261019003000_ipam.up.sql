@@ -0,0 +1,19 @@
+-- ARP entries collected from routers, and the subnets derived from interface addresses
+
+CREATE TABLE arp_entries (
+    id BLOB PRIMARY KEY,
+    device_id BLOB NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    interface_id BLOB REFERENCES interfaces(id) ON DELETE SET NULL,
+    ip_address TEXT NOT NULL,
+    mac_address TEXT NOT NULL,
+    first_seen TEXT NOT NULL,
+    last_seen TEXT NOT NULL,
+    UNIQUE (device_id, ip_address)
+);
+
+CREATE TABLE subnets (
+    id BLOB PRIMARY KEY,
+    network TEXT NOT NULL UNIQUE,
+    first_seen TEXT NOT NULL,
+    last_seen TEXT NOT NULL -- Last refresh that found it on an interface
+);
@@ -0,0 +1,9 @@
+-- Reverse DNS (PTR) names of device and node addresses
+
+CREATE TABLE ip_hostnames (
+    ip_address TEXT PRIMARY KEY,
+    hostname TEXT NOT NULL,
+    resolved_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_ip_hostnames_hostname ON ip_hostnames (hostname);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS topology_links;
+DROP TABLE IF EXISTS device_neighbors;
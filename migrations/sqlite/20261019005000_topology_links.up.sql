@@ -0,0 +1,41 @@
+-- LLDP/CDP neighbors collected per device, and the links between devices built from them,
+-- from MAC tables and by hand
+
+CREATE TABLE device_neighbors (
+    id BLOB PRIMARY KEY,
+    device_id BLOB NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    interface_id BLOB REFERENCES interfaces(id) ON DELETE SET NULL,
+    protocol TEXT NOT NULL CHECK (protocol IN ('lldp', 'cdp')),
+    remote_chassis_id TEXT,
+    remote_port TEXT,
+    remote_sys_name TEXT,
+    remote_address TEXT,
+    last_seen TEXT NOT NULL
+);
+
+CREATE INDEX idx_device_neighbors_device_id ON device_neighbors (device_id);
+
+CREATE TABLE topology_links (
+    id BLOB PRIMARY KEY,
+    device_id BLOB NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    interface_id BLOB REFERENCES interfaces(id) ON DELETE SET NULL,
+    remote_device_id BLOB NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    remote_interface_id BLOB REFERENCES interfaces(id) ON DELETE SET NULL,
+    source TEXT NOT NULL CHECK (source IN ('lldp', 'cdp', 'fdb', 'manual')),
+    confidence INTEGER NOT NULL CHECK (confidence BETWEEN 0 AND 100),
+    first_seen TEXT NOT NULL,
+    last_seen TEXT NOT NULL,
+    CHECK (device_id <> remote_device_id)
+);
+
+CREATE INDEX idx_topology_links_device_id ON topology_links (device_id);
+CREATE INDEX idx_topology_links_remote_device_id ON topology_links (remote_device_id);
+
+-- One link per connection and source, so concurrent refreshes cannot store a link twice
+CREATE UNIQUE INDEX idx_topology_links_connection ON topology_links (
+    device_id,
+    COALESCE(interface_id, x''),
+    remote_device_id,
+    COALESCE(remote_interface_id, x''),
+    source
+);
@@ -2,6 +2,14 @@ use tracing_subscriber::{EnvFilter, fmt};
 use nd_core::Settings; // Import Settings
 use db::create_pool; // Import db pool creation function
 use web::run_server; // Import web server run function
+use db::Repository;
+use discovery::control::PortControl;
+use discovery::dns::PtrResolver;
+use discovery::hostnames::HostnameResolution;
+use discovery::rogue::RogueCheck;
+use discovery::{DiscoveryManager, SnmpCredentials};
+use std::sync::Arc;
+use std::time::Duration;
 
 #[tokio::main] // Make main async
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -43,6 +51,58 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
+    // Subcommands run against the database and exit
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("migrate") => return run_migrate_command(&db_pool, args.get(1).map(String::as_str)).await,
+        Some("prune") => return run_prune_command(&db_pool, &settings, args.get(1).map(String::as_str)).await,
+        Some("oui") => return run_oui_command(&db_pool, &args[1..]).await,
+        Some(other) => {
+            eprintln!(
+                "Unknown command: {}\nUsage: nd-rust [migrate up|down|status | prune [--dry-run] | oui import <file.csv>...]",
+                other
+            );
+            std::process::exit(2);
+        }
+        None => {}
+    }
+
+    // Bring the schema up to date if configured; never run against a newer schema
+    let run_migrations = settings.database.as_ref().and_then(|d| d.run_migrations).unwrap_or(false);
+    let schema_result = if run_migrations {
+        tracing::info!("Applying pending database migrations");
+        db::migrate(&db_pool).await
+    } else {
+        db::check_schema_version(&db_pool).await
+    };
+    if let Err(e) = schema_result {
+        tracing::error!(error = %e, "Database schema check failed");
+        eprintln!("Database schema error: {}", e);
+        std::process::exit(1);
+    }
+
+    // Prune historical data in the background
+    if let Some(retention) = settings.retention.clone() {
+        tokio::spawn(db::run_retention(db_pool.repository(), retention));
+    }
+
+    // Look for duplicate addresses and misbehaving MACs in the background
+    if let Some(anomalies) = settings.anomalies.clone() {
+        tokio::spawn(db::run_anomaly_detection(db_pool.repository(), anomalies));
+    }
+
+    // Collect from the known devices in the background
+    if let Some(discovery) = settings.discovery.clone() {
+        match settings.snmp.as_ref().and_then(|snmp| snmp.community.clone()) {
+            Some(community) => {
+                let manager = discovery_manager(db_pool.repository(), &settings);
+                let interval = Duration::from_secs(discovery.interval_minutes.max(1) * 60);
+                tokio::spawn(discovery::run_sweeps(manager, SnmpCredentials { community }, interval));
+            }
+            None => tracing::warn!("Discovery needs snmp.community; not collecting"),
+        }
+    }
+
     // Run the web server
     tracing::info!("Starting web server...");
     if let Err(e) = run_server(db_pool, &settings).await {
@@ -56,3 +116,108 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing::info!("nd-rust shutting down");
     Ok(())
 }
+
+// Builds the collection pipeline from the settings; MACs are always checked against the
+// allowlist, and ports are only shut down with `rogue.shutdown_ports`
+fn discovery_manager(repo: Arc<dyn Repository>, settings: &Settings) -> DiscoveryManager {
+    let port_control = PortControl::new(
+        repo.clone(),
+        settings.snmp.as_ref(),
+        settings.port_control.clone().unwrap_or_default(),
+    );
+    let access_port_max_macs = settings.anomalies.clone().unwrap_or_default().access_port_max_macs;
+    let rogue_check = RogueCheck::new(repo.clone(), settings.rogue.clone().unwrap_or_default(), access_port_max_macs)
+        .with_port_control(Arc::new(port_control));
+    let manager = DiscoveryManager::new(repo.clone()).with_rogue_check(rogue_check);
+    match settings.dns.clone().map(PtrResolver::new) {
+        Some(Ok(resolver)) => manager.with_hostname_resolution(HostnameResolution::new(repo, resolver)),
+        Some(Err(e)) => {
+            tracing::warn!(error = %e, "Reverse DNS disabled");
+            manager
+        }
+        None => manager,
+    }
+}
+
+// Handles `nd-rust migrate up|down|status`
+async fn run_migrate_command(pool: &db::DbPool, action: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    match action {
+        Some("up") => {
+            db::migrate(pool).await?;
+            println!("Database is up to date");
+        }
+        Some("down") => match db::migrate_down(pool).await? {
+            Some(version) => println!("Reverted migration {}", version),
+            None => println!("No migrations to revert"),
+        },
+        Some("status") => {
+            for migration in db::migration_status(pool).await? {
+                let state = match (migration.applied, migration.known) {
+                    (true, true) => "applied",
+                    (false, _) => "pending",
+                    (true, false) => "applied (unknown to this binary)",
+                };
+                println!("{} {:<40} {}", migration.version, migration.description, state);
+            }
+        }
+        _ => {
+            eprintln!("Usage: nd-rust migrate up|down|status");
+            std::process::exit(2);
+        }
+    }
+    Ok(())
+}
+
+// Handles `nd-rust prune [--dry-run]`: one retention run with the configured ages
+async fn run_prune_command(
+    pool: &db::DbPool,
+    settings: &Settings,
+    flag: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(mut retention) = settings.retention.clone() else {
+        eprintln!("No retention settings configured");
+        std::process::exit(2);
+    };
+    match flag {
+        Some("--dry-run") => retention.dry_run = true,
+        None => {}
+        _ => {
+            eprintln!("Usage: nd-rust prune [--dry-run]");
+            std::process::exit(2);
+        }
+    }
+    let report = pool.repository().apply_retention(&retention).await?;
+    let verb = if report.dry_run { "Would remove" } else { "Removed" };
+    println!("{} {} node sightings", verb, report.nodes);
+    println!("{} {} device history entries", verb, report.device_history);
+    println!("{} {} port changes", verb, report.port_changes);
+    println!("{} {} ARP entries", verb, report.arp_entries);
+    println!("{} {} port moves", verb, report.node_moves);
+    println!("{} {} resolved anomalies", verb, report.anomalies);
+    println!("{} {} allowlist violations", verb, report.mac_violations);
+    println!("{} {} stale devices (retired)", if report.dry_run { "Would archive" } else { "Archived" }, report.archived_devices);
+    Ok(())
+}
+
+// Handles `nd-rust oui import <file.csv>...`: loads IEEE registry CSVs (oui.csv, mam.csv,
+// oui36.csv) downloaded beforehand; the server picks them up on its next start
+async fn run_oui_command(pool: &db::DbPool, args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let [action, files @ ..] = args else {
+        eprintln!("Usage: nd-rust oui import <file.csv>...");
+        std::process::exit(2);
+    };
+    if action != "import" || files.is_empty() {
+        eprintln!("Usage: nd-rust oui import <file.csv>...");
+        std::process::exit(2);
+    }
+    let mut vendors = Vec::new();
+    for file in files {
+        let text = std::fs::read_to_string(file).map_err(|e| format!("{}: {}", file, e))?;
+        let parsed = db::parse_ieee_csv(&text).map_err(|e| format!("{}: {}", file, e))?;
+        println!("Read {} assignments from {}", parsed.len(), file);
+        vendors.extend(parsed);
+    }
+    let stored = pool.repository().replace_mac_vendors(&vendors).await?;
+    println!("Stored {} MAC vendor assignments", stored);
+    Ok(())
+}